@@ -1,18 +1,84 @@
-use reqwest::{Client, Response};
-use anyhow::{anyhow, Context, Result};
+use reqwest::{Client, Method, RequestBuilder, Response};
+use anyhow::{Context, Result};
+use std::sync::OnceLock;
+use crate::error::{ApiErrorCode, OpsError};
+use std::time::Duration;
 use crate::types::{
-    ErrorResponse, LoginResponse, CiKeyResponse, RegisterResponse, WhoamiResponse,
+    ErrorResponse, LoginResponse, CiKeyResponse, CiPublicKeyResponse, RegisterResponse, WhoamiResponse,
     ProjectResponse, ServerWhoamiResponse, NodeSetResponse, ProjectListResponse,
     SyncAppResponse, CreateDeploymentResponse, UpdateDeploymentResponse,
     OpsToml,
     // Node Group types
     NodeGroupListResponse, NodeGroupDetailResponse, CreateNodeGroupResponse,
     // Node types
-    NodeInitResponse, Node, NodeListResponse, PrimaryNodeResponse,
+    NodeInitRequest, NodeInitResponse, Node, NodeListResponse, PrimaryNodeResponse,
     BindNodeResponse, BindByNameResponse, MessageResponse, CreateTunnelResponse,
 };
 
-const BASE_URL: &str = "https://api.ops.autos";
+pub const BASE_URL: &str = "https://api.ops.autos";
+/// Per-request timeout, override with OPS_HTTP_TIMEOUT (seconds)
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const CONNECT_TIMEOUT_SECS: u64 = 10;
+/// Retries after the first attempt, with exponential backoff from RETRY_BASE_DELAY_MS
+const MAX_RETRIES: u32 = 3;
+const RETRY_BASE_DELAY_MS: u64 = 500;
+
+fn http_timeout() -> Duration {
+    std::env::var("OPS_HTTP_TIMEOUT").ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+}
+
+/// Shared client, so connections to the API are reused across calls
+pub(crate) fn http_client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        let timeout = http_timeout();
+        Client::builder()
+            .timeout(timeout)
+            .connect_timeout(timeout.min(Duration::from_secs(CONNECT_TIMEOUT_SECS)))
+            .build()
+            .expect("Failed to build HTTP client")
+    })
+}
+
+pub(crate) trait SendWithRetry {
+    async fn send_with_retry(self) -> reqwest::Result<Response>;
+}
+
+impl SendWithRetry for RequestBuilder {
+    /// Send, retrying connection errors, which never reach the server. Timeouts and 5xx
+    /// are retried only for idempotent requests (not POST/PATCH): the server may already
+    /// have acted on them, and repeating a POST or PATCH could apply it twice.
+    async fn send_with_retry(self) -> reqwest::Result<Response> {
+        let (client, request) = self.build_split();
+        let request = request?;
+        let idempotent = !matches!(*request.method(), Method::POST | Method::PATCH);
+
+        let mut attempt = 0;
+        loop {
+            // Streaming bodies can't be replayed
+            let Some(req) = request.try_clone() else {
+                return client.execute(request).await;
+            };
+            let reason = match client.execute(req).await {
+                Ok(res) if res.status().is_server_error() && idempotent && attempt < MAX_RETRIES => {
+                    format!("HTTP {}", res.status())
+                }
+                Ok(res) => return Ok(res),
+                Err(e) if attempt < MAX_RETRIES && (e.is_connect() || (idempotent && e.is_timeout())) => e.to_string(),
+                Err(e) => return Err(e),
+            };
+            attempt += 1;
+            let delay = Duration::from_millis(RETRY_BASE_DELAY_MS << (attempt - 1));
+            o_debug!("   {} {}: {} (retry {}/{} in {}ms)",
+                request.method(), request.url().path(), reason, attempt, MAX_RETRIES, delay.as_millis());
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
 
 async fn handle_response<T: serde::de::DeserializeOwned>(res: Response) -> Result<T> {
     let status = res.status();
@@ -20,64 +86,71 @@ async fn handle_response<T: serde::de::DeserializeOwned>(res: Response) -> Resul
         res.json::<T>().await.context("Failed to parse success response")
     } else {
         let error_text = res.text().await.unwrap_or_else(|_| format!("HTTP Error: {}", status));
-        let error_res: Result<ErrorResponse, _> = serde_json::from_str(&error_text);
-        match error_res {
-            Ok(parsed_err) => Err(anyhow!(parsed_err.error)),
-            Err(_) => Err(anyhow!(error_text)),
-        }
+        let (code, message) = match serde_json::from_str::<ErrorResponse>(&error_text) {
+            Ok(ErrorResponse { code, required_role: Some(role), resource: Some(resource), .. }) =>
+                (code, format!("permission denied: you need {} on {}", role, resource)),
+            Ok(parsed_err) => (parsed_err.code, parsed_err.error),
+            Err(_) => (None, error_text),
+        };
+        Err(OpsError::Api {
+            code: ApiErrorCode::from_response(code.as_deref(), status.as_u16()),
+            message,
+        }.into())
     }
 }
 
 pub async fn register(username: &str, password: &str) -> Result<RegisterResponse> {
-    let client = Client::new();
+    let client = http_client();
     let body = serde_json::json!({ "username": username, "password": password });
-    let res = client.post(format!("{}/auth/register", BASE_URL)).json(&body).send().await?;
+    let res = client.post(format!("{}/auth/register", BASE_URL)).json(&body).send_with_retry().await?;
     handle_response(res).await
 }
 
 pub async fn login(username: &str, password: &str) -> Result<LoginResponse> {
-    let client = Client::new();
+    let client = http_client();
     let body = serde_json::json!({ "username": username, "password": password });
-    let res = client.post(format!("{}/auth/login", BASE_URL)).json(&body).send().await?;
+    let res = client.post(format!("{}/auth/login", BASE_URL)).json(&body).send_with_retry().await?;
     handle_response(res).await
 }
 
 pub async fn whoami(token: &str) -> Result<WhoamiResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .get(format!("{}/me", BASE_URL))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
     handle_response(res).await
 }
 
 pub async fn create_project(token: &str, name: &str) -> Result<ProjectResponse> {
-    let client = Client::new();
+    let client = http_client();
     let body = serde_json::json!({ "name": name });
     let res = client.post(format!("{}/projects", BASE_URL))
-        .bearer_auth(token).json(&body).send().await?;
+        .bearer_auth(token).json(&body).send_with_retry().await?;
     handle_response(res).await
 }
 
 // 支持 ops project list
-pub async fn list_projects(token: &str, name_filter: Option<&str>) -> Result<ProjectListResponse> {
-    let client = Client::new();
-    let mut url = format!("{}/projects", BASE_URL);
-    if let Some(name) = name_filter {
-        url = format!("{}?name={}", url, name);
-    }
-    let res = client.get(&url).bearer_auth(token).send().await?;
+/// List projects with their apps (GET /projects?name=&owner=mine|shared&region=)
+pub async fn list_projects(token: &str, query: &crate::types::ProjectListQuery) -> Result<ProjectListResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/projects", BASE_URL))
+        .query(query)
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
     handle_response(res).await
 }
 
 pub async fn server_whoami(token: Option<&str>) -> Result<ServerWhoamiResponse> {
-    let client = Client::new();
+    let client = http_client();
     let mut request_builder = client.get(format!("{}/server/whoami", BASE_URL));
     if let Some(t) = token {
         request_builder = request_builder.bearer_auth(t);
     }
-    let res = request_builder.send().await?;
+    let res = request_builder.send_with_retry().await?;
     handle_response(res).await
 }
 
@@ -87,18 +160,16 @@ pub async fn set_node(
     project: &str,
     environment: &str,
     ssh_pub_key: &str,
-    force_reset: bool,
     region: Option<&str>,
     zone: Option<&str>,
     hostname: Option<&str>,
     weight: Option<u8>,
 ) -> Result<NodeSetResponse> {
-    let client = Client::new();
+    let client = http_client();
     let mut body = serde_json::json!({
         "project": project,
         "environment": environment,
-        "ssh_pub_key": ssh_pub_key,
-        "force_reset": force_reset
+        "ssh_pub_key": ssh_pub_key
     });
 
     // Add optional multi-region fields
@@ -118,7 +189,7 @@ pub async fn set_node(
     let res = client.post(format!("{}/nodes/set", BASE_URL))
         .bearer_auth(token)
         .json(&body)
-        .send()
+        .send_with_retry()
         .await?;
     handle_response(res).await
 }
@@ -142,7 +213,7 @@ fn extract_github_repo(git_url: &str) -> Option<String> {
 
 /// Sync app record to backend (PUT /apps/sync)
 pub async fn sync_app(token: &str, config: &OpsToml) -> Result<SyncAppResponse> {
-    let client = Client::new();
+    let client = http_client();
 
     let app_name = config.apps.first()
         .map(|a| a.name.clone())
@@ -164,10 +235,12 @@ pub async fn sync_app(token: &str, config: &OpsToml) -> Result<SyncAppResponse>
     // Convert config to JSON
     let config_json = serde_json::to_string(config).ok();
 
-    // Get port from the matching app definition
-    let port = config.apps.iter()
-        .find(|a| a.name == app_name)
-        .and_then(|a| a.port);
+    // Get port and session affinity from the matching app definition
+    let app_def = config.apps.iter().find(|a| a.name == app_name);
+    let port = app_def.and_then(|a| a.port);
+    let lb = app_def
+        .and_then(|a| a.lb.affinity().ok().flatten())
+        .map(|(mode, key)| serde_json::json!({ "sticky": mode, "sticky_key": key }));
 
     let body = serde_json::json!({
         "project": config.project,
@@ -178,13 +251,38 @@ pub async fn sync_app(token: &str, config: &OpsToml) -> Result<SyncAppResponse>
         "routes": routes,
         "config_json": config_json,
         "port": port,
+        "lb": lb,
     });
 
     let res = client
         .put(format!("{}/apps/sync", BASE_URL))
         .bearer_auth(token)
         .json(&body)
-        .send()
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Get an app record (GET /apps/:project/:app)
+pub async fn get_app(token: &str, project: &str, app: &str) -> Result<crate::types::AppDetailResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/apps/{}/{}", BASE_URL, project, app))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Delete an app record (DELETE /apps/:project/:app)
+pub async fn delete_app(token: &str, project: &str, app: &str) -> Result<MessageResponse> {
+    let client = http_client();
+    let res = client
+        .delete(format!("{}/apps/{}/{}", BASE_URL, project, app))
+        .bearer_auth(token)
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -192,7 +290,7 @@ pub async fn sync_app(token: &str, config: &OpsToml) -> Result<SyncAppResponse>
 
 /// Create deployment record (POST /apps/:id/deployments)
 pub async fn create_deployment(token: &str, app_id: i64, trigger: &str) -> Result<CreateDeploymentResponse> {
-    let client = Client::new();
+    let client = http_client();
     let body = serde_json::json!({
         "trigger": trigger
     });
@@ -201,7 +299,7 @@ pub async fn create_deployment(token: &str, app_id: i64, trigger: &str) -> Resul
         .post(format!("{}/apps/{}/deployments", BASE_URL, app_id))
         .bearer_auth(token)
         .json(&body)
-        .send()
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -209,7 +307,7 @@ pub async fn create_deployment(token: &str, app_id: i64, trigger: &str) -> Resul
 
 /// Update deployment status (PATCH /apps/deployments/:id)
 pub async fn update_deployment(token: &str, deployment_id: i64, status: &str, logs: Option<&str>) -> Result<UpdateDeploymentResponse> {
-    let client = Client::new();
+    let client = http_client();
     let body = serde_json::json!({
         "status": status,
         "logs": logs
@@ -219,7 +317,104 @@ pub async fn update_deployment(token: &str, deployment_id: i64, status: &str, lo
         .patch(format!("{}/apps/deployments/{}", BASE_URL, deployment_id))
         .bearer_auth(token)
         .json(&body)
-        .send()
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Get deployment (GET /apps/deployments/:id)
+pub async fn get_deployment(token: &str, deployment_id: i64) -> Result<crate::types::DeploymentInfo> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/apps/deployments/{}", BASE_URL, deployment_id))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Log a deploy that went ahead despite `[policy]` violations (POST /apps/deployments/:id/policy-override)
+pub async fn record_policy_override(token: &str, deployment_id: i64, reason: &str, violations: &[String]) -> Result<MessageResponse> {
+    let client = http_client();
+    let body = serde_json::json!({
+        "reason": reason,
+        "violations": violations,
+    });
+    let res = client
+        .post(format!("{}/apps/deployments/{}/policy-override", BASE_URL, deployment_id))
+        .bearer_auth(token)
+        .json(&body)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Record what a deployment left on a node (PUT /apps/deployments/:id/nodes/:node_id/state)
+pub async fn record_deployed_state(token: &str, deployment_id: i64, node_id: i64, state: &crate::types::DeployedState) -> Result<MessageResponse> {
+    let client = http_client();
+    let res = client
+        .put(format!("{}/apps/deployments/{}/nodes/{}/state", BASE_URL, deployment_id, node_id))
+        .bearer_auth(token)
+        .json(state)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Record the images, git commit and routes a deployment left on a node (PUT /apps/deployments/:id/nodes/:node_id/release)
+pub async fn record_release(token: &str, deployment_id: i64, node_id: i64, release: &crate::types::NodeRelease) -> Result<MessageResponse> {
+    let client = http_client();
+    let res = client
+        .put(format!("{}/apps/deployments/{}/nodes/{}/release", BASE_URL, deployment_id, node_id))
+        .bearer_auth(token)
+        .json(release)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Deployments of an app with their recorded releases, newest first (GET /apps/:project/:app/releases)
+pub async fn list_releases(token: &str, project: &str, app: &str) -> Result<crate::types::ReleaseListResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/apps/{}/{}/releases", BASE_URL, project, app))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Attach an image scan to the deployments of `tag` (PUT /apps/:project/:app/image-scans/:tag)
+pub async fn record_image_scan(token: &str, project: &str, app: &str, tag: &str, scanner: &str, images: &[crate::types::ImageScan]) -> Result<MessageResponse> {
+    let client = http_client();
+    let body = serde_json::json!({
+        "scanner": scanner,
+        "passed": images.iter().all(|i| i.critical == 0),
+        "images": images,
+    });
+    let res = client
+        .put(format!("{}/apps/{}/{}/image-scans/{}", BASE_URL, project, app, tag))
+        .bearer_auth(token)
+        .json(&body)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Latest recorded state per node (GET /apps/:project/:app/deployed-state)
+pub async fn get_deployed_state(token: &str, project: &str, app: &str) -> Result<crate::types::DeployedStateResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/apps/{}/{}/deployed-state", BASE_URL, project, app))
+        .bearer_auth(token)
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -235,7 +430,7 @@ pub async fn create_node_group(
     name: Option<&str>,
     lb_strategy: &str,
 ) -> Result<CreateNodeGroupResponse> {
-    let client = Client::new();
+    let client = http_client();
     let mut body = serde_json::json!({
         "project": project,
         "environment": environment,
@@ -250,7 +445,7 @@ pub async fn create_node_group(
         .post(format!("{}/node-groups", BASE_URL))
         .bearer_auth(token)
         .json(&body)
-        .send()
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -258,7 +453,7 @@ pub async fn create_node_group(
 
 /// List node groups (GET /node-groups)
 pub async fn list_node_groups(token: &str, project: Option<&str>) -> Result<NodeGroupListResponse> {
-    let client = Client::new();
+    let client = http_client();
     let mut url = format!("{}/node-groups", BASE_URL);
 
     if let Some(p) = project {
@@ -268,7 +463,7 @@ pub async fn list_node_groups(token: &str, project: Option<&str>) -> Result<Node
     let res = client
         .get(&url)
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -276,11 +471,11 @@ pub async fn list_node_groups(token: &str, project: Option<&str>) -> Result<Node
 
 /// Get node group details (GET /node-groups/:id)
 pub async fn get_node_group(token: &str, id: i64) -> Result<NodeGroupDetailResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .get(format!("{}/node-groups/{}", BASE_URL, id))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -301,11 +496,11 @@ pub struct NodeGroupSummary {
 }
 
 pub async fn get_nodes_in_env(token: &str, project: &str, environment: &str) -> Result<NodesInEnvResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .get(format!("{}/nodes/{}/{}", BASE_URL, project, environment))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -314,41 +509,13 @@ pub async fn get_nodes_in_env(token: &str, project: &str, environment: &str) ->
 // ===== Nodes API =====
 
 /// Initialize a new node (POST /nodes/init)
-pub async fn init_node(
-    token: &str,
-    ssh_pub_key: &str,
-    region: Option<&str>,
-    allowed_projects: Option<Vec<String>>,
-    allowed_apps: Option<Vec<String>>,
-    port: Option<u16>,
-    hostname: Option<&str>,
-) -> Result<NodeInitResponse> {
-    let client = Client::new();
-    let mut body = serde_json::json!({
-        "ssh_pub_key": ssh_pub_key
-    });
-
-    if let Some(r) = region {
-        body["region"] = serde_json::Value::String(r.to_string());
-    }
-    if let Some(p) = allowed_projects {
-        body["allowed_projects"] = serde_json::json!(p);
-    }
-    if let Some(a) = allowed_apps {
-        body["allowed_apps"] = serde_json::json!(a);
-    }
-    if let Some(port) = port {
-        body["port"] = serde_json::Value::Number(port.into());
-    }
-    if let Some(h) = hostname {
-        body["hostname"] = serde_json::Value::String(h.to_string());
-    }
-
+pub async fn init_node(token: &str, req: &NodeInitRequest) -> Result<NodeInitResponse> {
+    let client = http_client();
     let res = client
         .post(format!("{}/nodes/init", BASE_URL))
         .bearer_auth(token)
-        .json(&body)
-        .send()
+        .json(req)
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -356,41 +523,13 @@ pub async fn init_node(
 
 /// Re-initialize an existing node (POST /nodes/reinit)
 /// Used to get serve token for daemon setup on a server that's already registered
-pub async fn reinit_node(
-    token: &str,
-    ssh_pub_key: &str,
-    region: Option<&str>,
-    allowed_projects: Option<Vec<String>>,
-    allowed_apps: Option<Vec<String>>,
-    port: Option<u16>,
-    hostname: Option<&str>,
-) -> Result<NodeInitResponse> {
-    let client = Client::new();
-    let mut body = serde_json::json!({
-        "ssh_pub_key": ssh_pub_key
-    });
-
-    if let Some(r) = region {
-        body["region"] = serde_json::Value::String(r.to_string());
-    }
-    if let Some(p) = allowed_projects {
-        body["allowed_projects"] = serde_json::json!(p);
-    }
-    if let Some(a) = allowed_apps {
-        body["allowed_apps"] = serde_json::json!(a);
-    }
-    if let Some(port) = port {
-        body["port"] = serde_json::Value::Number(port.into());
-    }
-    if let Some(h) = hostname {
-        body["hostname"] = serde_json::Value::String(h.to_string());
-    }
-
+pub async fn reinit_node(token: &str, req: &NodeInitRequest) -> Result<NodeInitResponse> {
+    let client = http_client();
     let res = client
         .post(format!("{}/nodes/reinit", BASE_URL))
         .bearer_auth(token)
-        .json(&body)
-        .send()
+        .json(req)
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -398,11 +537,11 @@ pub async fn reinit_node(
 
 /// List user's nodes (GET /nodes)
 pub async fn list_nodes(token: &str) -> Result<NodeListResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .get(format!("{}/nodes", BASE_URL))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -410,11 +549,170 @@ pub async fn list_nodes(token: &str) -> Result<NodeListResponse> {
 
 /// Get node by ID (GET /nodes/:id)
 pub async fn get_node(token: &str, node_id: u64) -> Result<Node> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .get(format!("{}/nodes/{}", BASE_URL, node_id))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Set (or clear, with `cost = None`) a node's monthly cost (PATCH /nodes/:id)
+pub async fn set_node_cost(token: &str, node_id: u64, cost: Option<f64>, currency: Option<&str>) -> Result<MessageResponse> {
+    let client = http_client();
+    let body = serde_json::json!({ "monthly_cost": cost, "cost_currency": currency });
+    let res = client
+        .patch(format!("{}/nodes/{}", BASE_URL, node_id))
+        .bearer_auth(token)
+        .json(&body)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Record personal SSH access to a node (POST /nodes/:id/ssh-grants)
+pub async fn create_ssh_grant(token: &str, node_id: u64, label: &str, source: &str, public_keys: &[String]) -> Result<MessageResponse> {
+    let client = http_client();
+    let body = serde_json::json!({ "label": label, "source": source, "public_keys": public_keys });
+    let res = client
+        .post(format!("{}/nodes/{}/ssh-grants", BASE_URL, node_id))
+        .bearer_auth(token)
+        .json(&body)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Remove a recorded SSH grant (DELETE /nodes/:id/ssh-grants/:label)
+pub async fn delete_ssh_grant(token: &str, node_id: u64, label: &str) -> Result<MessageResponse> {
+    let client = http_client();
+    let res = client
+        .delete(format!("{}/nodes/{}/ssh-grants/{}", BASE_URL, node_id, label))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// A GitHub user's public SSH keys (https://github.com/:user.keys)
+pub async fn fetch_github_keys(user: &str) -> Result<Vec<String>> {
+    let res = http_client()
+        .get(format!("https://github.com/{}.keys", user))
+        .send_with_retry()
+        .await?;
+    if !res.status().is_success() {
+        anyhow::bail!("GitHub returned {} for user '{}'", res.status(), user);
+    }
+    Ok(res.text().await?.lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect())
+}
+
+/// Project roles live under /projects/:p/access, app roles under /apps/:p/:a/access
+fn access_url(project: &str, app: Option<&str>) -> String {
+    match app {
+        Some(app) => format!("{}/apps/{}/{}/access", BASE_URL, project, app),
+        None => format!("{}/projects/{}/access", BASE_URL, project),
+    }
+}
+
+/// Give `user` a role on a project or app (PUT .../access/:user)
+pub async fn grant_access(token: &str, project: &str, app: Option<&str>, user: &str, role: &str) -> Result<MessageResponse> {
+    let client = http_client();
+    let body = serde_json::json!({ "role": role });
+    let res = client
+        .put(format!("{}/{}", access_url(project, app), user))
+        .bearer_auth(token)
+        .json(&body)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Remove `user`'s role on a project or app (DELETE .../access/:user)
+pub async fn revoke_access(token: &str, project: &str, app: Option<&str>, user: &str) -> Result<MessageResponse> {
+    let client = http_client();
+    let res = client
+        .delete(format!("{}/{}", access_url(project, app), user))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Roles on a project or app; for an app, includes the project roles that apply to it (GET .../access)
+pub async fn list_access(token: &str, project: &str, app: Option<&str>) -> Result<crate::types::AccessListResponse> {
+    let client = http_client();
+    let res = client
+        .get(access_url(project, app))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Register a branch preview and its apps, which gives them `<slug>.<app>.<project>.ops.autos` (PUT /projects/:p/previews/:slug)
+pub async fn upsert_preview(token: &str, project: &str, slug: &str, branch: &str, node_id: i64, apps: &[String], ttl_secs: u64) -> Result<crate::types::PreviewInfo> {
+    let client = http_client();
+    let body = serde_json::json!({ "branch": branch, "node_id": node_id, "apps": apps, "ttl_seconds": ttl_secs });
+    let res = client
+        .put(format!("{}/projects/{}/previews/{}", BASE_URL, project, slug))
+        .bearer_auth(token)
+        .json(&body)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Starter templates for `ops new` (GET /templates, no login needed)
+pub async fn list_templates() -> Result<crate::types::TemplateListResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/templates", BASE_URL))
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Previews of a project with owner and expiry (GET /projects/:p/previews)
+pub async fn list_previews(token: &str, project: &str) -> Result<crate::types::PreviewListResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/projects/{}/previews", BASE_URL, project))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// GET /projects/:p/previews/:slug
+pub async fn get_preview(token: &str, project: &str, slug: &str) -> Result<crate::types::PreviewInfo> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/projects/{}/previews/{}", BASE_URL, project, slug))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Release a preview's subdomains (DELETE /projects/:p/previews/:slug)
+pub async fn delete_preview(token: &str, project: &str, slug: &str) -> Result<MessageResponse> {
+    let client = http_client();
+    let res = client
+        .delete(format!("{}/projects/{}/previews/{}", BASE_URL, project, slug))
+        .bearer_auth(token)
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -422,11 +720,11 @@ pub async fn get_node(token: &str, node_id: u64) -> Result<Node> {
 
 /// Delete node (DELETE /nodes/:id)
 pub async fn delete_node(token: &str, node_id: u64) -> Result<MessageResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .delete(format!("{}/nodes/{}", BASE_URL, node_id))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -434,11 +732,59 @@ pub async fn delete_node(token: &str, node_id: u64) -> Result<MessageResponse> {
 
 /// Get CI key for node (GET /nodes/:id/ci-key)
 pub async fn get_node_ci_key(token: &str, node_id: u64) -> Result<CiKeyResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .get(format!("{}/nodes/{}/ci-key", BASE_URL, node_id))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Get CI public key for node (GET /nodes/:id/ci-key/public)
+pub async fn get_node_ci_public_key(token: &str, node_id: u64) -> Result<CiPublicKeyResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/nodes/{}/ci-key/public", BASE_URL, node_id))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Replace a node's CI keypair (POST /nodes/:id/ci-key/rotate)
+pub async fn rotate_node_ci_key(token: &str, node_id: u64) -> Result<crate::types::CiKeyRotateResponse> {
+    let client = http_client();
+    let res = client
+        .post(format!("{}/nodes/{}/ci-key/rotate", BASE_URL, node_id))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Replace an app's CI keypair (POST /apps/:project/:app/ci-key/rotate)
+pub async fn rotate_app_ci_key(token: &str, project: &str, app: &str) -> Result<crate::types::CiKeyRotateResponse> {
+    let client = http_client();
+    let res = client
+        .post(format!("{}/apps/{}/{}/ci-key/rotate", BASE_URL, project, app))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// List CI keypairs of all nodes and node groups (GET /ci-keys)
+pub async fn list_ci_keys(token: &str) -> Result<crate::types::CiKeyListResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/ci-keys", BASE_URL))
+        .bearer_auth(token)
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -446,11 +792,11 @@ pub async fn get_node_ci_key(token: &str, node_id: u64) -> Result<CiKeyResponse>
 
 /// Get all deploy targets for app (GET /apps/:project/:app/deploy-targets)
 pub async fn get_app_deploy_targets(token: &str, project: &str, app: &str) -> Result<crate::types::DeployTargetsResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .get(format!("{}/apps/{}/{}/deploy-targets", BASE_URL, project, app))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -458,11 +804,11 @@ pub async fn get_app_deploy_targets(token: &str, project: &str, app: &str) -> Re
 
 /// Get primary node for app (GET /apps/:project/:app/primary-node)
 pub async fn get_app_primary_node(token: &str, project: &str, app: &str) -> Result<PrimaryNodeResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .get(format!("{}/apps/{}/{}/primary-node", BASE_URL, project, app))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -470,11 +816,23 @@ pub async fn get_app_primary_node(token: &str, project: &str, app: &str) -> Resu
 
 /// Get CI key for app (GET /apps/:project/:app/ci-key)
 pub async fn get_app_ci_key(token: &str, project: &str, app: &str) -> Result<CiKeyResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .get(format!("{}/apps/{}/{}/ci-key", BASE_URL, project, app))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Get CI public key for app (GET /apps/:project/:app/ci-key/public)
+pub async fn get_app_ci_public_key(token: &str, project: &str, app: &str) -> Result<CiPublicKeyResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/apps/{}/{}/ci-key/public", BASE_URL, project, app))
+        .bearer_auth(token)
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -488,7 +846,7 @@ pub async fn bind_app_node(
     is_primary: bool,
     weight: Option<u8>,
 ) -> Result<BindNodeResponse> {
-    let client = Client::new();
+    let client = http_client();
     let mut body = serde_json::json!({
         "node_id": node_id,
         "is_primary": is_primary
@@ -502,7 +860,21 @@ pub async fn bind_app_node(
         .post(format!("{}/apps/{}/bind", BASE_URL, app_id))
         .bearer_auth(token)
         .json(&body)
-        .send()
+        .send_with_retry()
+        .await?;
+
+    handle_response(res).await
+}
+
+/// Unbind node from app (POST /apps/:id/unbind)
+pub async fn unbind_app_node(token: &str, app_id: i64, node_id: u64) -> Result<crate::types::UnbindNodeResponse> {
+    let client = http_client();
+    let body = serde_json::json!({ "node_id": node_id });
+    let res = client
+        .post(format!("{}/apps/{}/unbind", BASE_URL, app_id))
+        .bearer_auth(token)
+        .json(&body)
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -518,7 +890,7 @@ pub async fn bind_node_by_name(
     is_primary: bool,
     weight: Option<u8>,
 ) -> Result<BindByNameResponse> {
-    let client = Client::new();
+    let client = http_client();
     let mut body = serde_json::json!({
         "project": project,
         "app": app,
@@ -534,7 +906,7 @@ pub async fn bind_node_by_name(
         .post(format!("{}/apps/bind-by-name", BASE_URL))
         .bearer_auth(token)
         .json(&body)
-        .send()
+        .send_with_retry()
         .await?;
 
     handle_response(res).await
@@ -543,7 +915,7 @@ pub async fn bind_node_by_name(
 // ===== Custom Domains API =====
 
 pub async fn add_custom_domain(token: &str, project: &str, app: &str, domain: &str) -> Result<crate::types::AddDomainResponse> {
-    let client = Client::new();
+    let client = http_client();
     let body = serde_json::json!({
         "project": project,
         "app": app,
@@ -553,27 +925,27 @@ pub async fn add_custom_domain(token: &str, project: &str, app: &str, domain: &s
         .post(format!("{}/domains", BASE_URL))
         .bearer_auth(token)
         .json(&body)
-        .send()
+        .send_with_retry()
         .await?;
     handle_response(res).await
 }
 
 pub async fn list_custom_domains(token: &str, project: &str, app: &str) -> Result<crate::types::ListDomainsResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .get(format!("{}/domains?project={}&app={}", BASE_URL, project, app))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
     handle_response(res).await
 }
 
 pub async fn remove_custom_domain(token: &str, domain: &str) -> Result<crate::types::MessageResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .delete(format!("{}/domains/{}", BASE_URL, domain))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
     handle_response(res).await
 }
@@ -581,36 +953,177 @@ pub async fn remove_custom_domain(token: &str, domain: &str) -> Result<crate::ty
 // ===== Pool Management API =====
 
 pub async fn update_node_group_strategy(token: &str, group_id: i64, strategy: &str) -> Result<crate::types::MessageResponse> {
-    let client = Client::new();
+    let client = http_client();
     let body = serde_json::json!({ "lb_strategy": strategy });
     let res = client
         .patch(format!("{}/node-groups/{}", BASE_URL, group_id))
         .bearer_auth(token)
         .json(&body)
-        .send()
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// Set (or clear, with `sticky = None`) session affinity on a node group's load balancer
+pub async fn update_node_group_sticky(token: &str, group_id: i64, sticky: Option<&str>, key: Option<&str>) -> Result<crate::types::MessageResponse> {
+    let client = http_client();
+    let body = serde_json::json!({ "lb_sticky": sticky, "lb_sticky_key": key });
+    let res = client
+        .patch(format!("{}/node-groups/{}", BASE_URL, group_id))
+        .bearer_auth(token)
+        .json(&body)
+        .send_with_retry()
         .await?;
     handle_response(res).await
 }
 
 pub async fn drain_node(token: &str, group_id: i64, node_id: u64) -> Result<crate::types::MessageResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .post(format!("{}/node-groups/{}/nodes/{}/drain", BASE_URL, group_id, node_id))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
     handle_response(res).await
 }
 
 pub async fn undrain_node(token: &str, group_id: i64, node_id: u64) -> Result<crate::types::MessageResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .post(format!("{}/node-groups/{}/nodes/{}/undrain", BASE_URL, group_id, node_id))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+pub async fn update_health_check(token: &str, group_id: i64, health: &crate::types::HealthCheckConfig) -> Result<crate::types::MessageResponse> {
+    let client = http_client();
+    let res = client
+        .put(format!("{}/node-groups/{}/health-check", BASE_URL, group_id))
+        .bearer_auth(token)
+        .json(health)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+pub async fn set_node_weight(token: &str, group_id: i64, node_id: u64, weight: u8) -> Result<crate::types::MessageResponse> {
+    let client = http_client();
+    let body = serde_json::json!({ "weight": weight });
+    let res = client
+        .patch(format!("{}/node-groups/{}/nodes/{}", BASE_URL, group_id, node_id))
+        .bearer_auth(token)
+        .json(&body)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+/// Set several nodes' weights in one change, so traffic never sees a half-applied split (PUT /node-groups/:id/weights)
+pub async fn set_node_weights(token: &str, group_id: i64, weights: &[crate::types::NodeWeight]) -> Result<crate::types::MessageResponse> {
+    let client = http_client();
+    let body = serde_json::json!({ "weights": weights });
+    let res = client
+        .put(format!("{}/node-groups/{}/weights", BASE_URL, group_id))
+        .bearer_auth(token)
+        .json(&body)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// Report an event from `ops serve`, authenticated with the node's serve token (POST /nodes/:id/events)
+pub async fn report_node_event(serve_token: &str, node_id: i64, event: &crate::types::NodeEvent) -> Result<MessageResponse> {
+    let client = http_client();
+    let res = client
+        .post(format!("{}/nodes/{}/events", BASE_URL, node_id))
+        .bearer_auth(serve_token)
+        .json(event)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// The previews still on a node, from `ops serve` with the node's serve token (POST /nodes/:id/previews/heartbeat)
+pub async fn preview_heartbeats(serve_token: &str, node_id: i64, previews: &[crate::types::PreviewRef]) -> Result<MessageResponse> {
+    let client = http_client();
+    let body = serde_json::json!({ "previews": previews });
+    let res = client
+        .post(format!("{}/nodes/{}/previews/heartbeat", BASE_URL, node_id))
+        .bearer_auth(serve_token)
+        .json(&body)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// Latest events of an app's nodes, newest first (GET /apps/:p/:a/events)
+pub async fn list_app_events(token: &str, project: &str, app: &str, limit: u32) -> Result<crate::types::EventListResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/apps/{}/{}/events?limit={}", BASE_URL, project, app, limit))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// Secret names of an app, without values (GET /apps/:project/:app/secrets)
+pub async fn list_secrets(token: &str, project: &str, app: &str) -> Result<crate::types::SecretListResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/apps/{}/{}/secrets", BASE_URL, project, app))
+        .bearer_auth(token)
+        .send_with_retry()
         .await?;
     handle_response(res).await
 }
+
+/// One decrypted secret (GET /apps/:project/:app/secrets/:key)
+pub async fn get_secret(token: &str, project: &str, app: &str, key: &str) -> Result<crate::types::SecretValueResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/apps/{}/{}/secrets/{}", BASE_URL, project, app, key))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// Create or replace a secret; the backend stores it encrypted (PUT /apps/:project/:app/secrets/:key)
+pub async fn set_secret(token: &str, project: &str, app: &str, key: &str, value: &str) -> Result<MessageResponse> {
+    let client = http_client();
+    let res = client
+        .put(format!("{}/apps/{}/{}/secrets/{}", BASE_URL, project, app, key))
+        .bearer_auth(token)
+        .json(&crate::types::SetSecretRequest { value: value.to_string() })
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// DELETE /apps/:project/:app/secrets/:key
+pub async fn delete_secret(token: &str, project: &str, app: &str, key: &str) -> Result<MessageResponse> {
+    let client = http_client();
+    let res = client
+        .delete(format!("{}/apps/{}/{}/secrets/{}", BASE_URL, project, app, key))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// Every secret of an app with its value, for a deploy (GET /apps/:project/:app/secret-values)
+pub async fn secret_values(token: &str, project: &str, app: &str) -> Result<crate::types::SecretValuesResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/apps/{}/{}/secret-values", BASE_URL, project, app))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
 /// Register tunnel (POST /tunnels)
 pub async fn create_tunnel(
     token: &str,
@@ -619,7 +1132,7 @@ pub async fn create_tunnel(
     node_id: u64,
     remote_port: u16,
 ) -> Result<crate::types::CreateTunnelResponse> {
-    let client = Client::new();
+    let client = http_client();
     let body = serde_json::json!({
         "subdomain": subdomain,
         "project_name": project_name,
@@ -630,18 +1143,51 @@ pub async fn create_tunnel(
         .post(format!("{}/tunnels", BASE_URL))
         .bearer_auth(token)
         .json(&body)
-        .send()
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// Tunnels of the current user (GET /tunnels)
+pub async fn list_tunnels(token: &str) -> Result<crate::types::TunnelListResponse> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/tunnels", BASE_URL))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// Mark a tunnel as still open (POST /tunnels/:id/heartbeat)
+pub async fn tunnel_heartbeat(token: &str, tunnel_id: i64) -> Result<MessageResponse> {
+    let client = http_client();
+    let res = client
+        .post(format!("{}/tunnels/{}/heartbeat", BASE_URL, tunnel_id))
+        .bearer_auth(token)
+        .send_with_retry()
+        .await?;
+    handle_response(res).await
+}
+
+/// Tunnels and previews whose heartbeats stopped (GET /gc/stale)
+pub async fn list_stale(token: &str) -> Result<crate::types::StaleResources> {
+    let client = http_client();
+    let res = client
+        .get(format!("{}/gc/stale", BASE_URL))
+        .bearer_auth(token)
+        .send_with_retry()
         .await?;
     handle_response(res).await
 }
 
 /// Delete tunnel (DELETE /tunnels/:id)
 pub async fn delete_tunnel(token: &str, tunnel_id: i64) -> Result<MessageResponse> {
-    let client = Client::new();
+    let client = http_client();
     let res = client
         .delete(format!("{}/tunnels/{}", BASE_URL, tunnel_id))
         .bearer_auth(token)
-        .send()
+        .send_with_retry()
         .await?;
     handle_response(res).await
 }
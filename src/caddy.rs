@@ -0,0 +1,135 @@
+// src/caddy.rs
+//! Caddy config written by `ops init`: the ops block in /etc/caddy/Caddyfile.
+//! Route fragments in /etc/caddy/routes.d are managed through `router`.
+
+pub const ROUTES_DIR: &str = "/etc/caddy/routes.d";
+pub const CADDYFILE: &str = "/etc/caddy/Caddyfile";
+
+const BLOCK_BEGIN: &str = "# BEGIN ops-managed (do not edit, regenerated by `ops init`)";
+const BLOCK_END: &str = "# END ops-managed";
+
+/// The ops-managed Caddyfile block: route fragments plus ops-serve fallback on :80.
+/// Each of `tls_domains` (e.g. "42.node.ops.autos") also gets an HTTPS site with automatic certificates.
+pub fn managed_block(serve_port: u16, tls_domains: &[String]) -> String {
+    let site = |address: &str| format!(
+        "{} {{\n    import {}/*.caddy\n\n    # Fallback: ops-serve daemon\n    reverse_proxy 127.0.0.1:{}\n}}\n",
+        address, ROUTES_DIR, serve_port
+    );
+    let mut block = format!("{}\n{}", BLOCK_BEGIN, site(":80"));
+    if !tls_domains.is_empty() {
+        block.push('\n');
+        block.push_str(&site(&tls_domains.join(", ")));
+    }
+    block.push_str(BLOCK_END);
+    block.push('\n');
+    block
+}
+
+/// Result of merging the ops block into an existing Caddyfile
+#[derive(Debug, PartialEq)]
+pub enum CaddyfileMerge {
+    /// Already up to date
+    Unchanged,
+    /// Existing content replaced entirely (empty, stock or written by an older ops)
+    Replaced(String),
+    /// Ops block inserted or updated, user sites kept
+    Merged(String),
+}
+
+/// Merge the ops block into `existing` without touching user-defined sites
+pub fn merge_caddyfile(existing: &str, serve_port: u16, tls_domains: &[String]) -> CaddyfileMerge {
+    let block = managed_block(serve_port, tls_domains);
+
+    // 1. Existing managed block: update in place
+    if let (Some(start), Some(end)) = (existing.find(BLOCK_BEGIN), existing.find(BLOCK_END)) {
+        if start < end {
+            let end = end + BLOCK_END.len();
+            let end = if existing[end..].starts_with('\n') { end + 1 } else { end };
+            let merged = format!("{}{}{}", &existing[..start], block, &existing[end..]);
+            if merged == existing {
+                return CaddyfileMerge::Unchanged;
+            }
+            return CaddyfileMerge::Merged(merged);
+        }
+    }
+
+    // 2. Empty, the distro's stock Caddyfile, or the unmarked file older ops versions wrote
+    let is_stock = existing.contains("/usr/share/caddy") && existing.contains("file_server");
+    let is_legacy_ops = existing.contains("# Fallback: ops-serve daemon")
+        && existing.matches('{').count() == 1;
+    if existing.trim().is_empty() || is_stock || is_legacy_ops {
+        return CaddyfileMerge::Replaced(block);
+    }
+
+    // 3. User Caddyfile: append our block after their sites
+    let sep = if existing.ends_with('\n') { "\n" } else { "\n\n" };
+    CaddyfileMerge::Merged(format!("{}{}{}", existing, sep, block))
+}
+
+/// Remove the ops block from a Caddyfile (for `ops init --uninstall`). None if there is no block.
+pub fn strip_managed_block(existing: &str) -> Option<String> {
+    let start = existing.find(BLOCK_BEGIN)?;
+    let end = existing.find(BLOCK_END).filter(|e| *e > start)? + BLOCK_END.len();
+    let end = if existing[end..].starts_with('\n') { end + 1 } else { end };
+    let before = existing[..start].trim_end_matches('\n');
+    let after = &existing[end..];
+    let mut out = format!("{}{}", before, if before.is_empty() { "" } else { "\n" });
+    out.push_str(after.trim_start_matches('\n'));
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_caddyfile_keeps_user_sites() {
+        let user = "example.com {\n    respond \"hi\"\n}\n";
+        let merged = match merge_caddyfile(user, 8377, &[]) {
+            CaddyfileMerge::Merged(c) => c,
+            other => panic!("Expected Merged, got {:?}", other),
+        };
+        assert!(merged.starts_with(user));
+        assert!(merged.contains("reverse_proxy 127.0.0.1:8377"));
+
+        // Idempotent, and a port change only rewrites the managed block
+        assert_eq!(merge_caddyfile(&merged, 8377, &[]), CaddyfileMerge::Unchanged);
+        match merge_caddyfile(&merged, 9000, &[]) {
+            CaddyfileMerge::Merged(c) => {
+                assert!(c.starts_with(user));
+                assert!(c.contains("127.0.0.1:9000") && !c.contains("127.0.0.1:8377"));
+            }
+            other => panic!("Expected Merged, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_merge_caddyfile_replaces_legacy_and_empty() {
+        let legacy = ":80 {\n    import /etc/caddy/routes.d/*.caddy\n\n    # Fallback: ops-serve daemon\n    reverse_proxy 127.0.0.1:8377\n}\n";
+        assert_eq!(merge_caddyfile(legacy, 8377, &[]), CaddyfileMerge::Replaced(managed_block(8377, &[])));
+        assert_eq!(merge_caddyfile("", 8377, &[]), CaddyfileMerge::Replaced(managed_block(8377, &[])));
+    }
+
+    #[test]
+    fn test_strip_managed_block_restores_user_file() {
+        let user = "example.com {\n    respond \"hi\"\n}\n";
+        let merged = match merge_caddyfile(user, 8377, &[]) {
+            CaddyfileMerge::Merged(c) => c,
+            other => panic!("Expected Merged, got {:?}", other),
+        };
+        assert_eq!(strip_managed_block(&merged).as_deref(), Some(user));
+        assert_eq!(strip_managed_block(user), None);
+    }
+
+    #[test]
+    fn test_managed_block_tls_site() {
+        let domains = vec!["42.node.ops.autos".to_string(), "web1.example.com".to_string()];
+        let block = managed_block(8377, &domains);
+        assert!(block.contains(":80 {"));
+        assert!(block.contains("42.node.ops.autos, web1.example.com {"));
+
+        // Enabling TLS later rewrites the existing block in place
+        let plain = managed_block(8377, &[]);
+        assert_eq!(merge_caddyfile(&plain, 8377, &domains), CaddyfileMerge::Merged(block));
+    }
+}
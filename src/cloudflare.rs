@@ -0,0 +1,195 @@
+// src/cloudflare.rs
+//! Minimal Cloudflare API v4 client for `ops domain cloudflare`: zones, DNS records and the
+//! zone settings that decide whether a node can get a certificate for a proxied domain.
+
+use crate::api::{http_client, SendWithRetry};
+use anyhow::{bail, Context, Result};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const API: &str = "https://api.cloudflare.com/client/v4";
+/// CAs Caddy gets certificates from: Let's Encrypt, then ZeroSSL (issued by Sectigo)
+const NODE_CAS: [&str; 2] = ["letsencrypt.org", "sectigo.com"];
+
+#[derive(Deserialize)]
+struct Envelope<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<ApiMessage>,
+    result: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct ApiMessage {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Zone {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DnsRecord {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub content: String,
+    #[serde(default)]
+    pub proxied: bool,
+}
+
+#[derive(Deserialize)]
+struct Setting {
+    value: Value,
+}
+
+/// What to do with the domain's existing records to point it at the app
+#[derive(Debug, PartialEq)]
+pub enum RecordPlan {
+    Create,
+    /// Same CNAME target and proxy status already
+    Unchanged,
+    /// A CNAME to update in place
+    Update(String),
+    /// A/AAAA records in the way, deleted before the CNAME is created
+    Replace(Vec<DnsRecord>),
+}
+
+pub fn plan_record(existing: &[DnsRecord], target: &str, proxied: bool) -> RecordPlan {
+    if let Some(cname) = existing.iter().find(|r| r.kind == "CNAME") {
+        return if cname.content.eq_ignore_ascii_case(target) && cname.proxied == proxied {
+            RecordPlan::Unchanged
+        } else {
+            RecordPlan::Update(cname.id.clone())
+        };
+    }
+    let blocking: Vec<DnsRecord> = existing.iter().filter(|r| r.kind == "A" || r.kind == "AAAA").cloned().collect();
+    if blocking.is_empty() { RecordPlan::Create } else { RecordPlan::Replace(blocking) }
+}
+
+/// "a.b.example.com" → ["a.b.example.com", "b.example.com", "example.com"]: zone names to try, longest first
+pub fn zone_candidates(domain: &str) -> Vec<String> {
+    let labels: Vec<&str> = domain.trim_end_matches('.').split('.').collect();
+    (0..labels.len().saturating_sub(1)).map(|i| labels[i..].join(".")).collect()
+}
+
+/// Whether CAA records (as `0 issue "letsencrypt.org"` content) let the node's CAs issue.
+/// No `issue` records means any CA may.
+pub fn caa_allows_node(records: &[String]) -> bool {
+    let issuers: Vec<String> = records.iter().filter_map(|r| {
+        let mut parts = r.splitn(3, char::is_whitespace);
+        let (_flags, tag, value) = (parts.next()?, parts.next()?, parts.next()?);
+        (tag.eq_ignore_ascii_case("issue")).then(|| value.trim().trim_matches('"').to_lowercase())
+    }).collect();
+    issuers.is_empty() || issuers.iter().any(|v| NODE_CAS.iter().any(|ca| v.split(';').next().unwrap_or("").trim() == *ca))
+}
+
+pub struct Cloudflare {
+    token: String,
+}
+
+impl Cloudflare {
+    pub fn new(token: &str) -> Self {
+        Cloudflare { token: token.to_string() }
+    }
+
+    async fn request<T: DeserializeOwned>(&self, method: Method, path: &str, body: Option<Value>) -> Result<T> {
+        let mut req = http_client().request(method.clone(), format!("{}{}", API, path)).bearer_auth(&self.token);
+        if let Some(body) = body {
+            req = req.json(&body);
+        }
+        let res = req.send_with_retry().await.with_context(|| format!("Cloudflare {} {} failed", method, path))?;
+        let status = res.status();
+        let text = res.text().await.unwrap_or_default();
+        let envelope: Envelope<T> = serde_json::from_str(&text)
+            .with_context(|| format!("Cloudflare {} {}: unexpected response (HTTP {})", method, path, status))?;
+        if !envelope.success {
+            let errors: Vec<String> = envelope.errors.iter().map(|e| format!("{} ({})", e.message, e.code)).collect();
+            bail!("Cloudflare {} {}: {}", method, path, errors.join("; "));
+        }
+        envelope.result.with_context(|| format!("Cloudflare {} {}: empty result", method, path))
+    }
+
+    /// Fails when the token is invalid or disabled
+    pub async fn verify_token(&self) -> Result<()> {
+        let status: Value = self.request(Method::GET, "/user/tokens/verify", None).await?;
+        match status.get("status").and_then(Value::as_str) {
+            Some("active") => Ok(()),
+            other => bail!("Cloudflare token is {}", other.unwrap_or("not active")),
+        }
+    }
+
+    /// The zone `domain` belongs to, among those the token can see
+    pub async fn find_zone(&self, domain: &str) -> Result<Zone> {
+        for name in zone_candidates(domain) {
+            let zones: Vec<Zone> = self.request(Method::GET, &format!("/zones?name={}", name), None).await?;
+            if let Some(zone) = zones.into_iter().next() {
+                return Ok(zone);
+            }
+        }
+        bail!("No Cloudflare zone for {} (the token needs Zone:Read on it)", domain)
+    }
+
+    pub async fn dns_records(&self, zone: &Zone, name: &str, kind: Option<&str>) -> Result<Vec<DnsRecord>> {
+        let filter = kind.map(|k| format!("&type={}", k)).unwrap_or_default();
+        self.request(Method::GET, &format!("/zones/{}/dns_records?name={}{}", zone.id, name, filter), None).await
+    }
+
+    /// Create or (with `id`) overwrite a CNAME; ttl 1 is "automatic"
+    pub async fn put_cname(&self, zone: &Zone, id: Option<&str>, name: &str, target: &str, proxied: bool) -> Result<DnsRecord> {
+        let body = json!({ "type": "CNAME", "name": name, "content": target, "proxied": proxied, "ttl": 1 });
+        match id {
+            Some(id) => self.request(Method::PUT, &format!("/zones/{}/dns_records/{}", zone.id, id), Some(body)).await,
+            None => self.request(Method::POST, &format!("/zones/{}/dns_records", zone.id), Some(body)).await,
+        }
+    }
+
+    pub async fn delete_record(&self, zone: &Zone, id: &str) -> Result<()> {
+        let _: Value = self.request(Method::DELETE, &format!("/zones/{}/dns_records/{}", zone.id, id), None).await?;
+        Ok(())
+    }
+
+    /// A zone setting's value as a string, e.g. `ssl` → "flexible"
+    pub async fn setting(&self, zone: &Zone, name: &str) -> Result<String> {
+        let setting: Setting = self.request(Method::GET, &format!("/zones/{}/settings/{}", zone.id, name), None).await?;
+        Ok(setting.value.as_str().map(str::to_string).unwrap_or_else(|| setting.value.to_string()))
+    }
+
+    pub async fn set_setting(&self, zone: &Zone, name: &str, value: &str) -> Result<()> {
+        let _: Setting = self.request(Method::PATCH, &format!("/zones/{}/settings/{}", zone.id, name), Some(json!({ "value": value }))).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(kind: &str, content: &str, proxied: bool) -> DnsRecord {
+        DnsRecord { id: format!("{}-1", kind), kind: kind.into(), name: "api.example.com".into(), content: content.into(), proxied }
+    }
+
+    #[test]
+    fn test_record_plan_zones_and_caa() {
+        let target = "api.shop.ops.autos";
+        assert_eq!(plan_record(&[], target, false), RecordPlan::Create);
+        assert_eq!(plan_record(&[record("CNAME", "API.shop.ops.autos", false)], target, false), RecordPlan::Unchanged);
+        assert_eq!(plan_record(&[record("CNAME", target, true)], target, false), RecordPlan::Update("CNAME-1".into()));
+        let a = record("A", "203.0.113.1", true);
+        assert_eq!(plan_record(&[a.clone(), record("TXT", "v=spf1", false)], target, false), RecordPlan::Replace(vec![a]));
+
+        assert_eq!(zone_candidates("a.b.example.com"), vec!["a.b.example.com", "b.example.com", "example.com"]);
+        assert_eq!(zone_candidates("example.com."), vec!["example.com"]);
+
+        assert!(caa_allows_node(&[]));
+        assert!(caa_allows_node(&["0 iodef \"mailto:sec@example.com\"".into()]));
+        assert!(caa_allows_node(&["0 issue \"digicert.com\"".into(), "0 issue \"letsencrypt.org; validationmethods=http-01\"".into()]));
+        assert!(!caa_allows_node(&["0 issue \"digicert.com\"".into()]));
+    }
+}
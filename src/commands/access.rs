@@ -0,0 +1,82 @@
+use crate::utils::{self, Target};
+use crate::{api, config};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+
+/// Least to most privileged: viewers read status and logs, deployers also deploy and manage domains,
+/// admins also manage access
+pub const ROLES: [&str; 3] = ["viewer", "deployer", "admin"];
+
+/// `app.project` for an app role, a bare project name for a project role
+fn parse_scope(target: &str) -> Result<(String, Option<String>)> {
+    if !target.contains('.') {
+        return Ok((target.to_string(), None));
+    }
+    match utils::parse_target(target)? {
+        Target::AppTarget { app, project, path: None } => Ok((project, Some(app))),
+        _ => bail!("Expected 'app.project' (e.g. api.RedQ) or a project name, got '{}'", target),
+    }
+}
+
+fn token() -> Result<String> {
+    let cfg = config::load_config().context("Config error")?;
+    cfg.token.context("Please run `ops login` first.")
+}
+
+/// ops access grant <user> <app.project|project> <role>
+pub async fn handle_grant(user: String, target: String, role: String) -> Result<()> {
+    if !ROLES.contains(&role.as_str()) {
+        bail!("Unknown role '{}'. Expected one of: {}", role, ROLES.join(", "));
+    }
+    let (project, app) = parse_scope(&target)?;
+    let token = token()?;
+    api::grant_access(&token, &project, app.as_deref(), &user, &role).await
+        .with_context(|| format!("Failed to grant {} on {}", role, target))?;
+    o_success!("{} {} is now {} on {}", "✔".green(), user.bold(), role.cyan(), target.green());
+    Ok(())
+}
+
+/// ops access revoke <user> <app.project|project>
+pub async fn handle_revoke(user: String, target: String) -> Result<()> {
+    let (project, app) = parse_scope(&target)?;
+    let token = token()?;
+    api::revoke_access(&token, &project, app.as_deref(), &user).await
+        .with_context(|| format!("Failed to revoke access on {}", target))?;
+    o_success!("{} {} no longer has a role on {}", "✔".green(), user.bold(), target.green());
+    if app.is_some() {
+        o_detail!("   Roles on project '{}' still apply. Check with `ops access list {}`.", project, project);
+    }
+    Ok(())
+}
+
+/// ops access list <app.project|project>
+pub async fn handle_list(target: String) -> Result<()> {
+    let (project, app) = parse_scope(&target)?;
+    let token = token()?;
+    let grants = api::list_access(&token, &project, app.as_deref()).await
+        .with_context(|| format!("Failed to list access on {}", target))?
+        .grants;
+
+    if grants.is_empty() {
+        o_result!("No roles granted on {}.", target);
+        return Ok(());
+    }
+    o_result!("{:<24} {:<10} {:<10} {}", "USER".bold(), "ROLE".bold(), "SCOPE".bold(), "GRANTED BY".bold());
+    for g in &grants {
+        o_result!("{:<24} {:<10} {:<10} {}", g.user, g.role, g.scope, g.granted_by.as_deref().unwrap_or("-"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scope() {
+        assert_eq!(parse_scope("RedQ").unwrap(), ("RedQ".to_string(), None));
+        assert_eq!(parse_scope("api.RedQ").unwrap(), ("RedQ".to_string(), Some("api".to_string())));
+        assert!(parse_scope("12345.node").is_err());
+        assert!(parse_scope("api.RedQ:/opt").is_err());
+    }
+}
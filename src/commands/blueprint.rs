@@ -0,0 +1,233 @@
+//! `ops export` / `ops import`: a project's backend records as a YAML blueprint, to recreate it
+//! under another account (migrations, disaster recovery drills). Secret values are never exported.
+
+use crate::commands::project::app_summaries;
+use crate::types::{HealthCheckConfig, OpsToml, ProjectListQuery};
+use crate::{api, config, prompt};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+pub const BLUEPRINT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Blueprint {
+    pub version: u32,
+    pub project: String,
+    #[serde(default)]
+    pub apps: Vec<BlueprintApp>,
+    #[serde(default)]
+    pub node_groups: Vec<BlueprintNodeGroup>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BlueprintApp {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+    /// Secret names the app expects; values have to be set again after import
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<String>,
+    /// The ops.toml of the app's last deploy ([[apps]], [[routes]], healthchecks...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<OpsToml>,
+}
+
+/// A node group without its nodes: those belong to the old account
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BlueprintNodeGroup {
+    pub environment: String,
+    pub name: String,
+    pub lb_strategy: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheckConfig>,
+}
+
+impl Blueprint {
+    pub fn parse(text: &str) -> Result<Blueprint> {
+        let bp: Blueprint = serde_yaml::from_str(text).context("Invalid blueprint")?;
+        if bp.version != BLUEPRINT_VERSION {
+            bail!("Unsupported blueprint version {} (this ops reads version {})", bp.version, BLUEPRINT_VERSION);
+        }
+        Ok(bp)
+    }
+}
+
+/// The app's config to sync under `project`: sync_app records the first [[apps]] entry, so it goes first
+pub fn app_config(app: &BlueprintApp, project: &str) -> Option<OpsToml> {
+    let mut config = app.config.clone()?;
+    config.project = project.to_string();
+    if let Some(i) = config.apps.iter().position(|a| a.name == app.name) {
+        let def = config.apps.remove(i);
+        config.apps.insert(0, def);
+    }
+    Some(config)
+}
+
+/// ops export <project>: write the project's blueprint
+pub async fn handle_export(project: String, out: Option<String>) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let query = ProjectListQuery { name: Some(project.clone()), owner: None, region: None };
+    let item = api::list_projects(&token, &query).await.context("Failed to list projects")?
+        .projects.into_iter().find(|p| p.name == project)
+        .with_context(|| format!("Project '{}' not found", project))?;
+
+    o_step!("{} {}", "📦 Exporting".cyan(), project.bold());
+    let mut apps = Vec::new();
+    for summary in app_summaries(&item) {
+        let name = summary.name;
+        let config = match api::get_app(&token, &project, &name).await {
+            Ok(detail) => match detail.config_json.as_deref().map(serde_json::from_str::<OpsToml>) {
+                Some(Ok(c)) => Some(c),
+                Some(Err(e)) => {
+                    o_warn!("   {} {}: stored ops.toml unreadable ({})", "⚠".yellow(), name, e);
+                    None
+                }
+                None => None,
+            },
+            Err(e) => {
+                o_warn!("   {} {}: {}", "⚠".yellow(), name, e);
+                None
+            }
+        };
+        let domains: Vec<String> = api::list_custom_domains(&token, &project, &name).await
+            .map(|d| d.domains.into_iter().map(|d| d.domain).collect())
+            .unwrap_or_default();
+        let env: Vec<String> = api::list_secrets(&token, &project, &name).await
+            .map(|s| s.secrets.into_iter().map(|s| s.key).collect())
+            .unwrap_or_default();
+        o_detail!("   {} {} ({} domains, {} env keys{})", "✔".green(), name,
+            domains.len(), env.len(), if config.is_some() { ", ops.toml" } else { "" });
+        apps.push(BlueprintApp { name, domains, env, config });
+    }
+
+    let mut node_groups = Vec::new();
+    for group in api::list_node_groups(&token, Some(&project)).await.context("Failed to list node groups")?.node_groups {
+        let health_check = api::get_node_group(&token, group.id).await.ok().and_then(|g| g.health_config);
+        node_groups.push(BlueprintNodeGroup {
+            environment: group.environment,
+            name: group.name,
+            lb_strategy: group.lb_strategy,
+            health_check,
+        });
+    }
+
+    let blueprint = Blueprint { version: BLUEPRINT_VERSION, project: project.clone(), apps, node_groups };
+    let yaml = serde_yaml::to_string(&blueprint)?;
+    let out = out.unwrap_or_else(|| format!("{}.ops.yaml", project));
+    fs::write(&out, yaml).with_context(|| format!("Failed to write {}", out))?;
+    o_success!("{} {} ({} apps, {} node groups)", "✔ Wrote".green(), out.cyan(),
+        blueprint.apps.len(), blueprint.node_groups.len());
+    o_detail!("   Secret values are not included. Recreate with: ops import {}", out);
+    Ok(())
+}
+
+/// ops import <file>: recreate a blueprint's project under the logged-in account
+pub async fn handle_import(file: String, project: Option<String>, interactive: bool) -> Result<()> {
+    let text = fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file))?;
+    let blueprint = Blueprint::parse(&text)?;
+    let project = project.unwrap_or_else(|| blueprint.project.clone());
+
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    o_step!("{} {} → {} ({} apps, {} node groups)", "📦 Importing".cyan(), file, project.bold(),
+        blueprint.apps.len(), blueprint.node_groups.len());
+    if !prompt::confirm_yes(&format!("Create these records under project '{}'?", project), interactive)? {
+        bail!("Aborted");
+    }
+
+    let query = ProjectListQuery { name: Some(project.clone()), owner: None, region: None };
+    let exists = api::list_projects(&token, &query).await.context("Failed to list projects")?
+        .projects.iter().any(|p| p.name == project);
+    if exists {
+        o_detail!("   Project {} exists, adding to it", project);
+    } else {
+        api::create_project(&token, &project).await.context("Failed to create project")?;
+        o_detail!("   {} project {}", "✔ Created".green(), project);
+    }
+
+    let mut missing_env = Vec::new();
+    for app in &blueprint.apps {
+        match app_config(app, &project) {
+            Some(config) => {
+                api::sync_app(&token, &config).await.with_context(|| format!("Failed to create app {}", app.name))?;
+                o_detail!("   {} app {}", "✔".green(), app.name);
+            }
+            None => o_warn!("   {} app {}: no ops.toml in the blueprint, created on its first deploy", "⚠".yellow(), app.name),
+        }
+        for domain in &app.domains {
+            match api::add_custom_domain(&token, &project, &app.name, domain).await {
+                Ok(res) => o_detail!("   {} {} → CNAME {}", "✔".green(), domain, res.cname_target),
+                // Usually still attached to the old account
+                Err(e) => o_warn!("   {} {}: {}", "⚠".yellow(), domain, e),
+            }
+        }
+        missing_env.extend(app.env.iter().map(|k| (app.name.as_str(), k.as_str())));
+    }
+
+    for group in &blueprint.node_groups {
+        let created = api::create_node_group(&token, &project, &group.environment, Some(&group.name), &group.lb_strategy).await
+            .with_context(|| format!("Failed to create node group {}", group.name))?;
+        if let Some(health) = &group.health_check {
+            api::update_health_check(&token, created.node_group.id, health).await
+                .with_context(|| format!("Failed to set the health check of {}", group.name))?;
+        }
+        o_detail!("   {} node group {} ({})", "✔".green(), group.name, group.lb_strategy);
+    }
+
+    o_success!("{} {}", "✔ Imported".green(), project);
+    if !missing_env.is_empty() {
+        o_result!("\nSet these secrets, then bind nodes (ops set) and deploy:");
+        for (app, key) in missing_env {
+            o_result!("  ops secrets set {} --target {}.{}", key, app, project);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLUEPRINT: &str = r#"
+version: 1
+project: shop
+apps:
+  - name: web
+    domains: [shop.example.com]
+    env: [STRIPE_KEY]
+    config:
+      project: shop
+      deploy_path: /opt/shop
+      deploy: { source: image }
+      apps:
+        - { name: api, services: [api] }
+        - { name: web, services: [web] }
+      routes:
+        - { domain: shop.example.com, port: 3000 }
+  - name: worker
+node_groups:
+  - { environment: web, name: web-pool, lb_strategy: round_robin }
+"#;
+
+    #[test]
+    fn test_blueprint_round_trip_and_app_config() {
+        let bp = Blueprint::parse(BLUEPRINT).unwrap();
+        assert_eq!(bp.apps.len(), 2);
+        assert!(bp.apps[1].config.is_none());
+        let yaml = serde_yaml::to_string(&bp).unwrap();
+        assert_eq!(serde_yaml::to_string(&Blueprint::parse(&yaml).unwrap()).unwrap(), yaml);
+
+        let config = app_config(&bp.apps[0], "shop-dr").unwrap();
+        assert_eq!(config.project, "shop-dr");
+        assert_eq!(config.apps[0].name, "web");
+        assert_eq!(config.routes[0].port, 3000);
+        assert!(app_config(&bp.apps[1], "shop-dr").is_none());
+
+        assert!(Blueprint::parse("version: 2\nproject: shop\n").is_err());
+    }
+}
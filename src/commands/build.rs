@@ -1,7 +1,10 @@
 use crate::commands::common::resolve_env_value;
-use crate::commands::deploy::load_ops_toml;
+use crate::commands::deploy::{load_ops_toml, resolve_app_name};
+use crate::commands::secrets;
+use crate::commands::summary::{self, BuildSummary};
 use crate::commands::ssh::SshSession;
-use crate::types::{BuildConfig, OpsToml};
+use crate::types::{BuildConfig, ImageScan, OpsToml};
+use crate::registry::Credentials;
 use crate::{api, config};
 use anyhow::{Context, Result};
 use colored::Colorize;
@@ -52,7 +55,7 @@ chmod 600 ~/.ssh/config"#,
 }
 
 /// Resolve build node: build.node → API auto-query
-async fn resolve_build_node(config: &OpsToml, build: &BuildConfig) -> Result<String> {
+pub(crate) async fn resolve_build_node(config: &OpsToml, build: &BuildConfig) -> Result<String> {
     if let Some(id) = build.node {
         return Ok(id.to_string());
     }
@@ -75,8 +78,10 @@ pub async fn handle_build(
     tag: Option<String>,
     no_push: bool,
     jobs: u8,
+    summary_file: Option<String>,
 ) -> Result<()> {
     let total_start = Instant::now();
+    let started = summary::Started::now();
     let jobs = jobs.max(1) as usize;
 
     // 1. 加载配置
@@ -90,26 +95,67 @@ pub async fn handle_build(
     o_detail!("   Path: {}", build.path.green());
     o_detail!("   Command: {}", build.command.yellow());
 
-    // 2. 建立 SSH 会话（只 fetch 一次 CI key）
-    o_step!("\n{}", "🔑 Connecting to build node...".cyan());
-    let session = SshSession::connect(&node).await?;
-    session.exec(&format!("mkdir -p {}", build.path), None)?;
-
-    // 3. 同步代码
-    sync_code(build, &session, &node, &git_ref, &config.project).await?;
-
-    // 4. 执行构建命令
-    o_step!("\n{}", "🔨 Running build...".cyan());
-    let build_start = Instant::now();
-    let build_cmd = format!("source $HOME/.cargo/env 2>/dev/null; cd {} && {}", build.path, build.command);
-    session.exec(&build_cmd, None)?;
-    let build_duration = build_start.elapsed();
-    o_success!("   {} ({})", "✔ Build complete".green(), format_duration(build_duration));
-
-    // 5. 构建并推送 Docker 镜像（如果配置了 [build.image]）
-    if let Some(image_config) = &build.image {
-        build_and_push_images(build, &session, image_config, &service_filter, &tag, no_push, jobs)?;
+    let result: Result<()> = async {
+        // 2. 建立 SSH 会话（只 fetch 一次 CI key）
+        o_step!("\n{}", "🔑 Connecting to build node...".cyan());
+        let session = SshSession::connect(&node).await?;
+        session.exec(&format!("mkdir -p {}", build.path), None)?;
+
+        // 3. 同步代码
+        if build.source == "push" {
+            secrets::guard(config.deploy.secret_scan.as_deref(), &[], config.deploy.gitignore)?;
+        }
+        sync_code(build, &session, &node, &git_ref, &config).await?;
+
+        // 4. 执行构建命令
+        o_step!("\n{}", "🔨 Running build...".cyan());
+        let build_start = Instant::now();
+        let build_cmd = format!("source $HOME/.cargo/env 2>/dev/null; cd {} && {}", build.path, build.command);
+        session.exec(&build_cmd, None)?;
+        let build_duration = build_start.elapsed();
+        o_success!("   {} ({})", "✔ Build complete".green(), format_duration(build_duration));
+
+        // 5. 构建并推送 Docker 镜像（如果配置了 [build.image]）
+        if let Some(image_config) = &build.image {
+            let scans = build_and_push_images(build, &session, image_config, &service_filter, &tag, no_push, jobs)?;
+            if let Some(scanner) = &image_config.scan {
+                record_image_scan(&config, tag.as_deref().unwrap_or("latest"), scanner, &scans).await;
+                let blocked: Vec<&str> = scans.iter().filter(|s| s.critical > 0).map(|s| s.service.as_str()).collect();
+                if !blocked.is_empty() {
+                    return Err(anyhow::anyhow!("Critical vulnerabilities in: {}. Images were not pushed.", blocked.join(", ")));
+                }
+            }
+        }
+        Ok(())
+    }.await;
+
+    if let Some(ref path) = summary_file {
+        let tag = tag.clone().unwrap_or_else(|| "latest".into());
+        let images = build.image.as_ref().map(|img| {
+            let services = match &service_filter {
+                Some(svc) => vec![svc.clone()],
+                None => img.services.clone(),
+            };
+            services.iter().map(|svc| format!("{}/{}:{}", img.prefix, svc, tag)).collect()
+        }).unwrap_or_default();
+        let record = BuildSummary {
+            kind: "build",
+            project: config.project.clone(),
+            status: if result.is_ok() { "success" } else { "failed" },
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            node: node.clone(),
+            pushed: result.is_ok() && !no_push && build.image.is_some(),
+            tag,
+            images,
+            started_at: started.at.clone(),
+            duration_secs: started.elapsed_secs(),
+        };
+        match summary::write(path, &record) {
+            Ok(_) => o_detail!("   Summary written to {}", path),
+            Err(e) => o_warn!("   {} {:#}", "⚠".yellow(), e),
+        }
     }
+    result?;
 
     // 6. 输出总结
     let total_duration = total_start.elapsed();
@@ -122,8 +168,13 @@ pub async fn handle_build(
     Ok(())
 }
 
+/// 从 stdin 读取 token，并通过 credential helper 提供给 git（不出现在 URL、.git/config 或 ps 中）
+const GIT_TOKEN_PREFIX: &str = "read -r OPS_GIT_TOKEN && export OPS_GIT_TOKEN && \
+export GIT_CONFIG_COUNT=1 GIT_CONFIG_KEY_0=credential.helper \
+GIT_CONFIG_VALUE_0='!f() { echo username=x-access-token; echo \"password=$OPS_GIT_TOKEN\"; }; f' && ";
+
 /// 同步代码到构建节点
-async fn sync_code(build: &BuildConfig, session: &SshSession, node: &str, git_ref: &Option<String>, project_name: &str) -> Result<()> {
+async fn sync_code(build: &BuildConfig, session: &SshSession, node: &str, git_ref: &Option<String>, config: &OpsToml) -> Result<()> {
     match build.source.as_str() {
         "git" => {
             o_step!("\n{}", "📤 Syncing code (git)...".cyan());
@@ -137,7 +188,7 @@ async fn sync_code(build: &BuildConfig, session: &SshSession, node: &str, git_re
             // 配置认证（如果需要）
             if let Some(key_path) = &git.ssh_key {
                 let expanded = shellexpand::tilde(key_path).to_string();
-                setup_build_ssh_key(session, &expanded, project_name)?;
+                setup_build_ssh_key(session, &expanded, &config.project)?;
             }
 
             // 检查远程是否已有 .git 目录
@@ -148,30 +199,37 @@ async fn sync_code(build: &BuildConfig, session: &SshSession, node: &str, git_re
             let output = session.exec_output(&check)?;
             let output_str = String::from_utf8_lossy(&output).trim().to_string();
 
-            // 构建 clone URL（token 方式需要注入到 URL）
-            let repo_url = if let Some(token_val) = &git.token {
-                let token = resolve_env_value(token_val)?;
-                let https_url = git.repo
+            // token 方式：HTTPS URL 不含 token，token 经 stdin 交给一次性 credential helper
+            let token = git.token.as_ref().map(|t| resolve_env_value(t)).transpose()?;
+            let repo_url = if token.is_some() {
+                git.repo
                     .replace("git@github.com:", "https://github.com/")
-                    .replace(".git", "");
-                format!("https://x-access-token:{}@{}", token, https_url.trim_start_matches("https://"))
+                    .replace(".git", "")
             } else {
                 git.repo.clone()
             };
+            let run = |cmd: String| -> Result<()> {
+                match &token {
+                    Some(t) => session.exec_with_secret(&format!("{}{}", GIT_TOKEN_PREFIX, cmd), t),
+                    None => session.exec(&cmd, None),
+                }
+            };
 
             if output_str == "exists" {
+                // Older builds embedded the token in the remote URL; reset it
+                let set_url = if token.is_some() { format!("git remote set-url origin {} && ", repo_url) } else { String::new() };
                 let cmd = if git_ref.is_some() {
                     format!(
-                        "cd {} && git fetch origin && git checkout {} && git reset --hard {}",
-                        build.path, ref_or_branch, ref_or_branch
+                        "cd {} && {}git fetch origin && git checkout {} && git reset --hard {}",
+                        build.path, set_url, ref_or_branch, ref_or_branch
                     )
                 } else {
                     format!(
-                        "cd {} && git fetch origin && git checkout {} && git pull origin {}",
-                        build.path, ref_or_branch, ref_or_branch
+                        "cd {} && {}git fetch origin && git checkout {} && git pull origin {}",
+                        build.path, set_url, ref_or_branch, ref_or_branch
                     )
                 };
-                session.exec(&cmd, None)?;
+                run(cmd)?;
             } else {
                 let ssh_opts = if git.token.is_none() && git.ssh_key.is_none() {
                     "GIT_SSH_COMMAND='ssh -o StrictHostKeyChecking=no' "
@@ -184,13 +242,13 @@ async fn sync_code(build: &BuildConfig, session: &SshSession, node: &str, git_re
                     "{}git clone {} {} && cd {} && git checkout {}",
                     ssh_opts, repo_url, build.path, build.path, ref_or_branch
                 );
-                session.exec(&cmd, None)?;
+                run(cmd)?;
             }
             o_success!("   {} (ref: {})", "✔ Code synced".green(), ref_or_branch.yellow());
         }
         "push" => {
             o_step!("\n{}", "📤 Syncing code (rsync)...".cyan());
-            session.rsync_push(&build.path, &[])?;
+            session.rsync_push(&build.path, &[], config.deploy.gitignore)?;
             o_success!("   {}", "✔ Code synced".green());
         }
         other => return Err(anyhow::anyhow!("Unknown build source: {}", other)),
@@ -198,7 +256,63 @@ async fn sync_code(build: &BuildConfig, session: &SshSession, node: &str, git_re
     Ok(())
 }
 
-/// 构建并推送 Docker 镜像
+/// trivy 扫描结果 (`--format json`) 中按严重级别计数
+fn parse_trivy(service: &str, image: &str, json: &[u8]) -> Result<ImageScan> {
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Report { #[serde(default)] results: Vec<Target> }
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Target { #[serde(default)] vulnerabilities: Option<Vec<Vuln>> }
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Vuln { #[serde(rename = "VulnerabilityID")] id: String, severity: String }
+
+    let report: Report = serde_json::from_slice(json).context("Unexpected trivy output")?;
+    let mut scan = ImageScan { service: service.into(), image: image.into(), ..Default::default() };
+    for v in report.results.into_iter().flat_map(|t| t.vulnerabilities.unwrap_or_default()) {
+        match v.severity.as_str() {
+            "CRITICAL" => {
+                scan.critical += 1;
+                if !scan.critical_ids.contains(&v.id) {
+                    scan.critical_ids.push(v.id);
+                }
+            }
+            "HIGH" => scan.high += 1,
+            "MEDIUM" => scan.medium += 1,
+            "LOW" => scan.low += 1,
+            _ => {}
+        }
+    }
+    Ok(scan)
+}
+
+/// 在构建节点上用 trivy 扫描镜像
+fn scan_image(session: &SshSession, service: &str, image: &str) -> Result<ImageScan> {
+    let output = session.exec_output(&format!("trivy image --quiet --scanners vuln --format json {}", image))
+        .with_context(|| format!("Failed to scan image for {}", service))?;
+    let scan = parse_trivy(service, image, &output)?;
+
+    let counts = format!("critical {}, high {}, medium {}, low {}", scan.critical, scan.high, scan.medium, scan.low);
+    if scan.critical > 0 {
+        o_error!("   {} {} {}", "✗".red(), service.red(), counts);
+        o_error!("      {}", scan.critical_ids.iter().take(10).cloned().collect::<Vec<_>>().join(", "));
+    } else {
+        o_success!("   {} {} {}", "✔".green(), service, counts.dimmed());
+    }
+    Ok(scan)
+}
+
+/// 扫描结果附到部署记录 (失败只警告)
+async fn record_image_scan(config: &OpsToml, tag: &str, scanner: &str, scans: &[ImageScan]) {
+    let Some(token) = config::load_config().ok().and_then(|c| c.token) else { return };
+    let app = resolve_app_name(config);
+    if let Err(e) = api::record_image_scan(&token, &config.project, &app, tag, scanner, scans).await {
+        o_warn!("   {} {}", "⚠ Failed to record image scan:".yellow(), e);
+    }
+}
+
+/// 构建并推送 Docker 镜像；配置了 scan 时在推送前扫描，有 critical 漏洞则不推送
 fn build_and_push_images(
     build: &BuildConfig,
     session: &SshSession,
@@ -207,8 +321,16 @@ fn build_and_push_images(
     tag: &Option<String>,
     no_push: bool,
     jobs: usize,
-) -> Result<()> {
+) -> Result<Vec<ImageScan>> {
     let tag = tag.as_deref().unwrap_or("latest");
+    if let Some(scanner) = &image_config.scan {
+        if scanner != "trivy" {
+            return Err(anyhow::anyhow!("Unknown build.image.scan '{}'. Supported: trivy", scanner));
+        }
+        session.exec_output("command -v trivy")
+            .context("trivy not found on the build node. Install it: https://trivy.dev/latest/getting-started/installation/")?;
+    }
+    let mut scans = Vec::new();
     let services: Vec<&str> = if let Some(filter) = service_filter {
         vec![filter.as_str()]
     } else {
@@ -224,12 +346,10 @@ fn build_and_push_images(
     );
 
     // Docker registry login
-    let token = resolve_env_value(&image_config.token)?;
-    let login_cmd = format!(
-        "echo '{}' | docker login {} -u {} --password-stdin 2>/dev/null",
-        token, image_config.registry, image_config.username,
-    );
-    session.exec(&login_cmd, None)?;
+    Credentials::resolve(
+        &image_config.registry, &image_config.username, &image_config.token,
+        image_config.helper.as_deref(), image_config.region.as_deref(),
+    )?.docker_login(session, &image_config.registry)?;
     o_success!("   {}", "✔ Registry login".green());
 
     let img_start = Instant::now();
@@ -250,7 +370,17 @@ fn build_and_push_images(
             session.exec(&build_cmd, None)
                 .with_context(|| format!("Failed to build image for {}", svc))?;
 
-            if !no_push {
+            if image_config.scan.is_some() {
+                scans.push(scan_image(session, svc, &format!("{}/{}:{}", image_config.prefix, svc, tag))?);
+            }
+        }
+
+        // 全部扫描通过后才推送，避免先推送的镜像在后面的扫描失败时已经上线
+        if scans.iter().any(|s| s.critical > 0) {
+            return Ok(scans);
+        }
+        if !no_push {
+            for svc in &services {
                 let push_cmd = format!(
                     "docker push {}/{}:{} && docker push {}/{}:latest",
                     image_config.prefix, svc, tag,
@@ -322,6 +452,17 @@ fn build_and_push_images(
             }
         }
 
+        // 推送前扫描（顺序执行，trivy 共用漏洞库缓存）
+        if image_config.scan.is_some() {
+            o_detail!("   {}", "Scanning images...".dimmed());
+            for svc in &services {
+                scans.push(scan_image(session, svc, &format!("{}/{}:{}", image_config.prefix, svc, tag))?);
+            }
+            if scans.iter().any(|s| s.critical > 0) {
+                return Ok(scans);
+            }
+        }
+
         // 并行推送
         if !no_push {
             o_detail!("   {}", "Pushing images...".dimmed());
@@ -371,7 +512,7 @@ fn build_and_push_images(
 
     session.exec("docker image prune -f 2>/dev/null", None).ok();
 
-    Ok(())
+    Ok(scans)
 }
 
 fn format_duration(d: std::time::Duration) -> String {
@@ -382,3 +523,30 @@ fn format_duration(d: std::time::Duration) -> String {
         format!("{}m{}s", secs / 60, secs % 60)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trivy() {
+        let json = br#"{"Results": [
+            {"Target": "debian 12", "Vulnerabilities": [
+                {"VulnerabilityID": "CVE-2024-3094", "Severity": "CRITICAL"},
+                {"VulnerabilityID": "CVE-2023-4911", "Severity": "HIGH"},
+                {"VulnerabilityID": "CVE-2023-0001", "Severity": "LOW"}
+            ]},
+            {"Target": "app/Cargo.lock", "Vulnerabilities": null},
+            {"Target": "usr/lib/libz", "Vulnerabilities": [
+                {"VulnerabilityID": "CVE-2024-3094", "Severity": "CRITICAL"},
+                {"VulnerabilityID": "CVE-2022-37434", "Severity": "MEDIUM"}
+            ]}
+        ]}"#;
+        let scan = parse_trivy("api", "ghcr.io/acme/api:v1", json).unwrap();
+        assert_eq!((scan.critical, scan.high, scan.medium, scan.low), (2, 1, 1, 1));
+        assert_eq!(scan.critical_ids, vec!["CVE-2024-3094"]);
+
+        let clean = parse_trivy("web", "ghcr.io/acme/web:v1", br#"{"SchemaVersion": 2}"#).unwrap();
+        assert_eq!(clean.critical, 0);
+    }
+}
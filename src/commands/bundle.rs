@@ -0,0 +1,266 @@
+use crate::commands::deploy::{compose_base_args, fallback_routes, load_ops_toml, run_health_checks, sync_env_files};
+use crate::commands::drift::DEFAULT_COMPOSE_FILES;
+use crate::commands::ssh::SshSession;
+use crate::router::Router;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const IMAGES_FILE: &str = "images.tar";
+
+/// manifest.json at the root of a bundle
+#[derive(Serialize, Deserialize, Debug)]
+struct BundleManifest {
+    ops_version: String,
+    project: String,
+    compose_project: String,
+    created_at: String,
+    images: Vec<String>,
+    /// Relative to deploy_path; stored under compose/
+    compose_files: Vec<String>,
+    env_files: Vec<BundleEnvFile>,
+    routes: Vec<BundleRoute>,
+}
+
+/// Keys-only copy of an env file, used on the node only if the real file is missing
+#[derive(Serialize, Deserialize, Debug)]
+struct BundleEnvFile {
+    remote: String,
+    template: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct BundleRoute {
+    /// Where the fragment goes on the node
+    path: String,
+    /// Stored under routes/
+    file: String,
+}
+
+/// ops bundle create: images, compose files, env templates and route fragments in one archive
+pub fn handle_create(file: String, output: Option<String>, app_filter: Option<String>) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    let router = Router::from_config(config.deploy.router.as_deref())?;
+    let compose_project = config.compose_project();
+    let compose = format!("docker compose -p {} {}", compose_project, compose_base_args(&config));
+    let output = output.unwrap_or_else(|| format!("{}-bundle.tar.gz", config.project));
+
+    let staging = tempfile::tempdir()?;
+    let dir = staging.path();
+
+    // 1. Images: build (or pull) locally, then `docker save` everything compose references
+    if config.deploy.source == "image" {
+        o_step!("{}", "📥 Pulling images...".cyan());
+        run_local(&format!("{} pull", compose))?;
+    } else {
+        o_step!("{}", "🔨 Building images...".cyan());
+        run_local(&format!("{} build", compose))?;
+    }
+    let images_out = Command::new("sh").arg("-c").arg(format!("{} config --images", compose))
+        .output().context("Failed to run docker compose")?;
+    if !images_out.status.success() {
+        bail!("docker compose config failed: {}", String::from_utf8_lossy(&images_out.stderr).trim());
+    }
+    let mut images: Vec<String> = String::from_utf8_lossy(&images_out.stdout)
+        .lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+    images.sort();
+    images.dedup();
+    if images.is_empty() {
+        bail!("docker compose lists no images");
+    }
+    o_step!("{}", format!("📦 Saving {} image(s)...", images.len()).cyan());
+    for image in &images {
+        o_detail!("   {}", image);
+    }
+    let status = Command::new("docker").arg("save").arg("-o").arg(dir.join(IMAGES_FILE)).args(&images)
+        .status().context("Failed to run docker save")?;
+    if !status.success() {
+        bail!("docker save failed");
+    }
+
+    // 2. Compose files and ops.toml
+    let compose_files: Vec<String> = match &config.deploy.compose_files {
+        Some(files) => files.clone(),
+        None => DEFAULT_COMPOSE_FILES.iter().filter(|f| Path::new(f).exists()).map(|f| f.to_string()).collect(),
+    };
+    for f in &compose_files {
+        copy_into(Path::new(f), &dir.join("compose").join(f))?;
+    }
+    fs::copy(&file, dir.join("ops.toml")).with_context(|| format!("Failed to copy {}", file))?;
+
+    // 3. Env templates: keys only, never values
+    let mut env_files = Vec::new();
+    for (i, ef) in config.env_files.iter().enumerate() {
+        let Ok(content) = fs::read_to_string(&ef.local) else { continue };
+        let template = format!("{}.template", i);
+        copy_content(&env_template(&content), &dir.join("env").join(&template))?;
+        env_files.push(BundleEnvFile { remote: ef.remote.clone(), template });
+    }
+
+    // 4. Route fragments as `ops deploy` writes them for published ports
+    let mut routes = Vec::new();
+    for route in fallback_routes(router, &config, &app_filter) {
+        let name = route.path.rsplit('/').next().unwrap_or(&route.path).to_string();
+        copy_content(&route.content, &dir.join("routes").join(&name))?;
+        routes.push(BundleRoute { path: route.path, file: name });
+    }
+
+    let manifest = BundleManifest {
+        ops_version: env!("CARGO_PKG_VERSION").to_string(),
+        project: config.project.clone(),
+        compose_project: compose_project.to_string(),
+        created_at: humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string(),
+        images,
+        compose_files,
+        env_files,
+        routes,
+    };
+    fs::write(dir.join(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?)?;
+
+    o_step!("{}", "🗜️  Writing archive...".cyan());
+    let status = Command::new("tar")
+        .arg("czf").arg(absolute(&output)?)
+        .arg("-C").arg(dir)
+        .args([MANIFEST_FILE, "ops.toml", IMAGES_FILE])
+        .args(["compose", "env", "routes"].iter().filter(|d| dir.join(d).exists()))
+        .status().context("Failed to run tar")?;
+    if !status.success() {
+        bail!("tar failed");
+    }
+
+    let size = fs::metadata(&output).map(|m| m.len()).unwrap_or(0);
+    o_result!("\n{} Bundle written to {} ({:.1} MB)", "✅".green(), output.cyan(), size as f64 / 1_048_576.0);
+    o_detail!("   Apply it with: ops bundle apply {} --node <id>", output);
+    Ok(())
+}
+
+/// ops bundle apply: deploy a bundle over SSH; the node needs no registry or internet access
+pub async fn handle_apply(bundle: String, node: u64) -> Result<()> {
+    let bundle_path = PathBuf::from(&bundle);
+    if !bundle_path.is_file() {
+        bail!("Bundle not found: {}", bundle);
+    }
+
+    // Only the small files are extracted locally; images stream straight to the node
+    let local = tempfile::tempdir()?;
+    let status = Command::new("tar")
+        .arg("xzf").arg(&bundle_path).arg("-C").arg(local.path())
+        .args([MANIFEST_FILE, "ops.toml"])
+        .status().context("Failed to run tar")?;
+    if !status.success() {
+        bail!("{} is not an ops bundle", bundle);
+    }
+    let manifest: BundleManifest = serde_json::from_str(&fs::read_to_string(local.path().join(MANIFEST_FILE))?)
+        .context("Invalid bundle manifest")?;
+    let config = load_ops_toml(&local.path().join("ops.toml").to_string_lossy())?;
+    let router = Router::from_config(config.deploy.router.as_deref())?;
+
+    o_step!("{} {} → node {}", "📦 Bundle:".cyan(), manifest.project.green(), node.to_string().cyan());
+    o_detail!("   Created {} by ops {}, {} image(s)", manifest.created_at, manifest.ops_version, manifest.images.len());
+
+    let session = SshSession::connect(&node.to_string()).await?;
+    let deploy_path = &config.deploy_path;
+    let remote_dir = format!("{}/.ops-bundle", deploy_path);
+
+    // 1. Upload and unpack
+    o_step!("\n{}", "📤 Uploading bundle...".cyan());
+    session.exec_file(&format!("rm -rf {0} && mkdir -p {0} && tar xzf - -C {0}", remote_dir), &bundle_path)?;
+
+    // 2. Images
+    o_step!("\n{}", "📥 Loading images...".cyan());
+    session.exec(&format!("docker load -i {}/{}", remote_dir, IMAGES_FILE), None)?;
+
+    // 3. Compose files, then env files: local ones win, templates only fill gaps
+    session.exec(&format!("[ -d {0}/compose ] && cp -r {0}/compose/. {1}/; true", remote_dir, deploy_path), None)?;
+    sync_env_files(&config, &session)?;
+    for ef in &manifest.env_files {
+        let remote = format!("{}/{}", deploy_path, ef.remote);
+        let filled = session.exec_output(&format!(
+            "[ -f {0} ] && exit 0; mkdir -p $(dirname {0}) && cp {1}/env/{2} {0} && echo created",
+            remote, remote_dir, ef.template
+        ))?;
+        if String::from_utf8_lossy(&filled).contains("created") {
+            o_warn!("   {} {} was missing; created from its template. Fill in the values and re-apply.", "⚠".yellow(), remote);
+        }
+    }
+
+    // 4. Start with the loaded images
+    o_step!("\n{}", "🚀 Starting services...".cyan());
+    session.exec(&format!(
+        "cd {} && docker compose -p {} {} up -d --no-build --pull never",
+        deploy_path, manifest.compose_project, compose_base_args(&config)
+    ), None)?;
+
+    // 5. Routes
+    if !manifest.routes.is_empty() {
+        o_step!("\n{}", format!("⚙️  Installing {} routes...", router.name()).cyan());
+        router.ensure_base(&session)?;
+        for route in &manifest.routes {
+            session.exec(&format!("cp {}/routes/{} {}", remote_dir, route.file, route.path), None)?;
+            o_detail!("   ✔ {}", route.path);
+        }
+        router.reload(&session)?;
+    }
+
+    session.exec(&format!("rm -rf {}", remote_dir), None)?;
+    run_health_checks(&config, &session)?;
+
+    o_result!("\n{} Applied {} to node {}", "✅".green(), bundle.green(), node.to_string().cyan());
+    Ok(())
+}
+
+/// `KEY=value` → `KEY=`; comments and blank lines are kept
+fn env_template(content: &str) -> String {
+    content.lines()
+        .map(|line| match line.split_once('=') {
+            Some((key, _)) if !line.trim_start().starts_with('#') => format!("{}=", key),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n") + "\n"
+}
+
+fn run_local(command: &str) -> Result<()> {
+    o_debug!("   {}", command);
+    let status = Command::new("sh").arg("-c").arg(command).status()
+        .with_context(|| format!("Failed to run {}", command))?;
+    if !status.success() {
+        bail!("`{}` failed", command);
+    }
+    Ok(())
+}
+
+fn copy_into(src: &Path, dst: &Path) -> Result<()> {
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(src, dst).with_context(|| format!("Failed to copy {}", src.display()))?;
+    Ok(())
+}
+
+fn copy_content(content: &str, dst: &Path) -> Result<()> {
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(dst, content).with_context(|| format!("Failed to write {}", dst.display()))
+}
+
+fn absolute(path: &str) -> Result<PathBuf> {
+    let path = Path::new(path);
+    Ok(if path.is_absolute() { path.to_path_buf() } else { std::env::current_dir()?.join(path) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_template_strips_values() {
+        let content = "# database\nDATABASE_URL=postgres://u:secret@db/app\n\nAPI_KEY=abc=def\n";
+        assert_eq!(env_template(content), "# database\nDATABASE_URL=\n\nAPI_KEY=\n");
+    }
+}
@@ -0,0 +1,265 @@
+//! Canary deploys. `ops deploy --canary <percent>` deploys to a few of an app's pool nodes and
+//! weights the load balancer so they get about that share of traffic. `ops deploy promote` then
+//! deploys the other nodes and puts the weights back; `ops deploy abort` puts the weights back and
+//! rolls the canary nodes back to the release they ran before. The rollout in progress is kept in
+//! `.ops/state.json`, so promote and abort run from the same checkout.
+
+use crate::commands::deploy::{self, load_ops_toml, load_ops_toml_env, resolve_app_name, resolve_targets};
+use crate::commands::release;
+use crate::project_state::{CanaryState, ProjectState};
+use crate::types::{DeployTarget, NodeWeight, OpsToml};
+use crate::{api, config, prompt};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+
+pub enum Mode {
+    /// Deploy to this percentage of the pool
+    Start(u8),
+    /// Deploy the nodes outside the canary
+    Promote,
+}
+
+/// A canary step under way in `ops deploy`, finished by `finish` with the deploy's result
+pub struct Run {
+    app: String,
+    state: CanaryState,
+    promote: bool,
+}
+
+/// `percent` of the pool rounded up, at least one node and never all of them. Healthy nodes go
+/// first and the primary last, so a bad release hits as little as possible.
+pub fn pick_nodes(targets: &[DeployTarget], percent: u8) -> Vec<i64> {
+    let count = (targets.len() * percent as usize).div_ceil(100).clamp(1, targets.len().saturating_sub(1).max(1));
+    let mut ordered: Vec<&DeployTarget> = targets.iter().collect();
+    ordered.sort_by_key(|t| (t.status != "healthy", t.is_primary));
+    ordered.iter().take(count).map(|t| t.node_id).collect()
+}
+
+/// Weights giving the `canary` nodes of `pool` together about `percent` of the traffic
+pub fn canary_weights(pool: &[i64], canary: &[i64], percent: u8) -> Vec<NodeWeight> {
+    let k = pool.iter().filter(|id| canary.contains(id)).count().max(1);
+    let m = pool.len().saturating_sub(k).max(1);
+    let share = |total: u8, n: usize| ((total as f64 / n as f64).round() as i64).clamp(1, 100);
+    pool.iter().map(|&node_id| NodeWeight {
+        node_id,
+        weight: if canary.contains(&node_id) { share(percent, k) } else { share(100 - percent, m) },
+    }).collect()
+}
+
+fn token() -> Result<String> {
+    config::load_config().context("Config error")?.token.context("Please run `ops login` first.")
+}
+
+fn domains(targets: &[DeployTarget], ids: &[i64]) -> String {
+    targets.iter().filter(|t| ids.contains(&t.node_id)).map(|t| t.domain.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Narrow `targets` to the nodes this step deploys. `options` carries the deploy options a
+/// promote repeats; for a promote they come from the recorded canary instead.
+pub async fn prepare(
+    file: &str,
+    config: &OpsToml,
+    app_filter: &Option<String>,
+    mode: &Mode,
+    targets: &mut Vec<DeployTarget>,
+    options: CanaryState,
+) -> Result<Run> {
+    let app = app_filter.clone().unwrap_or_else(|| resolve_app_name(config));
+    let recorded = ProjectState::load(file)?.canaries.remove(&app);
+
+    let percent = match mode {
+        Mode::Promote => {
+            let state = recorded.with_context(|| format!(
+                "No canary of {} in progress. Start one with `ops deploy --canary <percent>`", app))?;
+            targets.retain(|t| !state.nodes.contains(&t.node_id));
+            if targets.is_empty() {
+                bail!("No nodes of {} outside the canary to promote to. Run `ops deploy abort` to end it", app);
+            }
+            o_detail!("   Promoting the {}% canary of {} to {} more node(s)", state.percent, app.green(), targets.len());
+            return Ok(Run { app, state, promote: true });
+        }
+        Mode::Start(percent) => *percent,
+    };
+    if !(1..=99).contains(&percent) {
+        bail!("Invalid canary percentage {}. Must be between 1 and 99", percent);
+    }
+    if recorded.is_some() {
+        bail!("A canary of {} is already in progress. Run `ops deploy promote` or `ops deploy abort` first", app);
+    }
+
+    let token = token()?;
+    let pool = api::get_app_deploy_targets(&token, &config.project, &app).await
+        .context("Failed to get deploy targets")?;
+    let node_group_id = pool.node_group_id
+        .context("Canary deploys need a pool. Bind a second node to the app first")?;
+    if targets.len() < 2 {
+        bail!("Canary deploys need at least two nodes to choose from; {} matched", targets.len());
+    }
+    let nodes = pick_nodes(targets, percent);
+
+    // The release the canary nodes run now, for `ops deploy abort`
+    let previous_release = match api::list_releases(&token, &config.project, &app).await {
+        Ok(resp) => resp.releases.iter().find(|r| r.status == "success" && !r.nodes.is_empty()).map(|r| r.deployment_id),
+        Err(e) => {
+            o_warn!("   {} Could not read releases ({}); `ops deploy abort` won't be able to roll back", "⚠".yellow(), e);
+            None
+        }
+    };
+
+    o_detail!("   Canary: {} of {} node(s), ~{}% of traffic: {}", nodes.len(), targets.len(), percent, domains(targets, &nodes).cyan());
+    targets.retain(|t| nodes.contains(&t.node_id));
+    let state = CanaryState {
+        percent,
+        nodes,
+        node_group_id,
+        weights: pool.targets.iter().map(|t| NodeWeight { node_id: t.node_id, weight: t.weight }).collect(),
+        lb_strategy: pool.lb_strategy,
+        previous_release,
+        ..options
+    };
+    Ok(Run { app, state, promote: false })
+}
+
+/// Put back the weights and strategy the pool had before the canary
+async fn restore_weights(token: &str, state: &CanaryState) -> Result<()> {
+    api::set_node_weights(token, state.node_group_id, &state.weights).await
+        .context("Failed to restore the pool weights")?;
+    if let Some(strategy) = state.lb_strategy.as_deref().filter(|s| *s != "weighted") {
+        api::update_node_group_strategy(token, state.node_group_id, strategy).await
+            .context("Failed to restore the load-balancing strategy")?;
+    }
+    Ok(())
+}
+
+/// After the deploy of a canary step: shift traffic to a new canary, or end a promoted one
+pub async fn finish(file: &str, mut run: Run, deployment_id: Option<i64>, deployed: bool) -> Result<()> {
+    let token = token()?;
+    if run.promote {
+        if !deployed {
+            o_warn!("\n{} The canary weights stay. Re-run `ops deploy promote`, or `ops deploy abort`", "⚠".yellow());
+            return Ok(());
+        }
+        restore_weights(&token, &run.state).await?;
+        ProjectState::update(file, |s| { s.canaries.remove(&run.app); })?;
+        o_result!("{} Promoted the canary of {}; the pool's weights are restored", "✅".green(), run.app.green());
+        return Ok(());
+    }
+
+    run.state.deployment_id = deployment_id;
+    let state = run.state.clone();
+    ProjectState::update(file, |s| { s.canaries.insert(run.app.clone(), state); })?;
+    if !deployed {
+        o_warn!("\n{} The canary deploy failed; traffic is unchanged. Run `ops deploy abort` to roll its nodes back", "⚠".yellow());
+        return Ok(());
+    }
+
+    let state = &run.state;
+    if state.lb_strategy.as_deref() != Some("weighted") {
+        api::update_node_group_strategy(&token, state.node_group_id, "weighted").await
+            .context("Failed to switch the pool to weighted load balancing")?;
+    }
+    let pool: Vec<i64> = state.weights.iter().map(|w| w.node_id).collect();
+    api::set_node_weights(&token, state.node_group_id, &canary_weights(&pool, &state.nodes, state.percent)).await
+        .context("Failed to shift traffic to the canary. Run `ops deploy abort` to roll it back")?;
+    o_result!("\n{} Canary of {} is live on {} node(s) with ~{}% of traffic", "🐤".green(), run.app.green(), state.nodes.len(), state.percent);
+    o_detail!("   Watch it, then run `ops deploy promote` to roll out everywhere or `ops deploy abort` to roll back.");
+    Ok(())
+}
+
+/// Keys of `--set KEY=VALUE` flags, sorted
+pub fn env_keys(env_vars: &[String]) -> Vec<String> {
+    let mut keys: Vec<String> = env_vars.iter().map(|kv| kv.split_once('=').map_or(kv.as_str(), |(k, _)| k).to_string()).collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// ops deploy promote: the canary's deploy, repeated on the rest of the pool
+pub async fn handle_promote(file: String, app_filter: Option<String>, env_vars: Vec<String>, rolling: bool, interactive: bool) -> Result<()> {
+    let app = app_filter.clone().unwrap_or_else(|| load_ops_toml(&file).map(|c| resolve_app_name(&c)).unwrap_or_default());
+    let state = ProjectState::load(&file)?.canaries.remove(&app)
+        .with_context(|| format!("No canary of {} in progress. Start one with `ops deploy --canary <percent>`", app))?;
+    // Only the keys were kept; the values have to be the canary's again
+    if env_keys(&env_vars) != state.env_keys {
+        let expected = if state.env_keys.is_empty() { "no --set".to_string() } else {
+            state.env_keys.iter().map(|k| format!("--set {}=...", k)).collect::<Vec<_>>().join(" ")
+        };
+        bail!("The canary of {} was deployed with {}; pass the same to `ops deploy promote`", app, expected);
+    }
+    let opts = deploy::DeployOptions {
+        file,
+        service_filter: state.service,
+        app_filter,
+        restart_only: state.restart_only,
+        env_vars,
+        rolling,
+        no_pull: state.no_pull,
+        interactive,
+        env: state.env,
+        ..Default::default()
+    };
+    deploy::handle_deploy(opts, Some(Mode::Promote)).await
+}
+
+/// ops deploy abort
+pub async fn handle_abort(file: String, app_filter: Option<String>, interactive: bool) -> Result<()> {
+    let app = app_filter.clone().unwrap_or_else(|| load_ops_toml(&file).map(|c| resolve_app_name(&c)).unwrap_or_default());
+    let state = ProjectState::load(&file)?.canaries.remove(&app)
+        .with_context(|| format!("No canary of {} in progress", app))?;
+    let config = load_ops_toml_env(&file, state.env.as_deref())?;
+    let token = token()?;
+    let mut targets = resolve_targets(&config, &app_filter).await?;
+    targets.retain(|t| state.nodes.contains(&t.node_id));
+
+    o_step!("{} the {}% canary of {} on {}", "⏪ Aborting".cyan(), state.percent, app.green(), domains(&targets, &state.nodes).cyan());
+    if !prompt::confirm_yes("Restore the pool's weights and roll the canary nodes back?", interactive)? {
+        o_warn!("Operation cancelled.");
+        return Ok(());
+    }
+
+    // Traffic first: the old release keeps serving while the canary nodes are rolled back
+    restore_weights(&token, &state).await?;
+    o_success!("   {} Pool weights restored", "✔".green());
+
+    match state.previous_release {
+        Some(id) => {
+            let resp = api::list_releases(&token, &config.project, &app).await.context("Failed to get releases")?;
+            let release = resp.releases.iter().find(|r| r.deployment_id == id)
+                .with_context(|| format!("Release #{} is no longer recorded; roll back with `ops rollback --to <id>`", id))?;
+            release::roll_back_nodes(&config, &token, resp.app_id, release, &targets, &app_filter).await?;
+        }
+        None => o_warn!("   {} No earlier release was recorded; the canary nodes keep the new build. Take them out with `ops pool drain`",
+            "⚠".yellow()),
+    }
+    ProjectState::update(&file, |s| { s.canaries.remove(&app); })?;
+    o_result!("\n{} Canary of {} aborted", "✅".green(), app.green());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_nodes_and_weights() {
+        let target = |node_id: i64, status: &str, is_primary: bool| DeployTarget {
+            node_id, domain: format!("n{}.ops.autos", node_id), ip_address: String::new(), ipv6_address: None,
+            hostname: None, region: None, zone: None, weight: 100, is_primary, status: status.into(),
+        };
+        let pool = vec![target(1, "healthy", true), target(2, "unhealthy", false), target(3, "healthy", false),
+            target(4, "healthy", false), target(5, "healthy", false)];
+        assert_eq!(pick_nodes(&pool, 10), vec![3]);
+        assert_eq!(pick_nodes(&pool, 30), vec![3, 4]);
+        assert_eq!(pick_nodes(&pool, 99), vec![3, 4, 5, 1]);
+        assert_eq!(pick_nodes(&pool[..2], 50), vec![1]);
+
+        let weight = |w: &[NodeWeight], id: i64| w.iter().find(|x| x.node_id == id).unwrap().weight;
+        let w = canary_weights(&[1, 2, 3, 4, 5], &[3], 10);
+        assert_eq!((weight(&w, 3), weight(&w, 1)), (10, 23));
+        let w = canary_weights(&[1, 2], &[2], 99);
+        assert_eq!((weight(&w, 2), weight(&w, 1)), (99, 1));
+
+        let set = |kvs: &[&str]| kvs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(env_keys(&set(&["TOKEN=s3cret", "DEBUG=1", "TOKEN=other"])), vec!["DEBUG", "TOKEN"]);
+    }
+}
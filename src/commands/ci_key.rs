@@ -1,27 +1,76 @@
 use crate::{api, config, utils};
 use crate::utils::Target;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 
-/// Get CI private key for a target
+/// How `ops ci-keys` hands the key over
+enum KeyOutput {
+    /// Key as-is on stdout
+    Pem,
+    /// A `gh secret set` command with the key in a quoted heredoc
+    GithubSecret,
+    /// Single-line base64, for CI variables that cannot hold newlines
+    Base64,
+    /// Written to a file with 0600 permissions
+    File(String),
+}
+
+impl KeyOutput {
+    fn parse(s: &str) -> Result<Self> {
+        match s {
+            "pem" => Ok(KeyOutput::Pem),
+            "github-secret" => Ok(KeyOutput::GithubSecret),
+            "base64" => Ok(KeyOutput::Base64),
+            _ => match s.strip_prefix("file:") {
+                Some(path) if !path.is_empty() => Ok(KeyOutput::File(path.to_string())),
+                _ => bail!("Unknown output '{}'. Expected pem, github-secret, base64 or file:<path>", s),
+            },
+        }
+    }
+}
+
+/// Get CI private key (or `--public` key) for a target
 /// Supports both Node ID (e.g., "12345") and App target (e.g., "api.RedQ")
-pub async fn handle_get_ci_private_key(target_str: String) -> Result<()> {
+pub async fn handle_get_ci_private_key(target_str: String, output: String, public: bool) -> Result<()> {
     let target = utils::parse_target(&target_str)?;
+    let output = KeyOutput::parse(&output)?;
 
     let cfg = config::load_config().context("Config error")?;
     let token = cfg.token.context("Please run `ops login`")?;
 
-    let private_key = match &target {
-        Target::NodeId { id, .. } => {
-            let res = api::get_node_ci_key(&token, *id).await?;
-            res.private_key
-        }
-        Target::AppTarget { app, project, .. } => {
-            let res = api::get_app_ci_key(&token, project, app).await?;
-            res.private_key
-        }
+    let key = match (&target, public) {
+        (Target::NodeId { id, .. }, false) => api::get_node_ci_key(&token, *id).await?.private_key,
+        (Target::NodeId { id, .. }, true) => api::get_node_ci_public_key(&token, *id).await?.public_key,
+        (Target::AppTarget { app, project, .. }, false) => api::get_app_ci_key(&token, project, app).await?.private_key,
+        (Target::AppTarget { app, project, .. }, true) => api::get_app_ci_public_key(&token, project, app).await?.public_key,
     };
+    let key = format!("{}\n", key.trim_end());
 
-    println!("{}", private_key);
+    match output {
+        KeyOutput::Pem => print!("{}", key),
+        KeyOutput::GithubSecret => {
+            let name = if public { "OPS_SSH_PUBLIC_KEY" } else { "OPS_SSH_KEY" };
+            println!("gh secret set {} <<'OPS_KEY_EOF'\n{}OPS_KEY_EOF", name, key);
+        }
+        KeyOutput::Base64 => println!("{}", utils::base64_encode(key.as_bytes())),
+        KeyOutput::File(path) => {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&path)
+                .with_context(|| format!("Failed to open {}", path))?;
+            // mode() only applies on creation
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            file.write_all(key.as_bytes())
+                .with_context(|| format!("Failed to write {}", path))?;
+            o_success!("{} Wrote {} key to {} (0600)", "✔".green(), if public { "public" } else { "private" }, path.cyan());
+        }
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,164 @@
+use crate::types::OpsToml;
+use crate::{api, config};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// ops clone <app.project> [dir]: a deployable checkout of an app from its backend record
+pub async fn handle_clone(target: String, directory: Option<String>) -> Result<()> {
+    let (app, project) = target.split_once('.')
+        .with_context(|| format!("Invalid target '{}'. Expected app.project (e.g., api.RedQ)", target))?;
+
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    o_step!("{}", format!("📥 Fetching {}...", target).cyan());
+    let record = api::get_app(&token, project, app).await
+        .with_context(|| format!("Failed to get app {}", target))?;
+    let mut config: Option<OpsToml> = record.config_json.as_deref()
+        .map(|json| serde_json::from_str(json).context("Recorded ops.toml is invalid"))
+        .transpose()?;
+
+    let dir = directory.unwrap_or_else(|| app.to_string());
+    let dir_path = Path::new(&dir);
+    if dir_path.exists() && fs::read_dir(dir_path)?.next().is_some() {
+        bail!("Directory '{}' already exists and is not empty", dir);
+    }
+
+    // 1. Repository: ops.toml's [deploy.git] first, then the GitHub repo the backend derived from it
+    let repo_url = config.as_ref()
+        .and_then(|c| c.deploy.git.as_ref())
+        .map(|g| g.repo.clone())
+        .or_else(|| record.github_repo.as_ref().map(|r| format!("https://github.com/{}.git", r)));
+    match repo_url {
+        Some(url) => {
+            let branch = config.as_ref()
+                .and_then(|c| c.deploy.branch.clone())
+                .or(record.github_branch.clone());
+            o_step!("{} {}", "🔀 Cloning".cyan(), url);
+            let mut cmd = Command::new("git");
+            cmd.arg("clone");
+            if let Some(ref branch) = branch {
+                cmd.args(["--branch", branch]);
+            }
+            let status = cmd.args([&url, &dir]).status().context("Failed to run git")?;
+            if !status.success() {
+                bail!("git clone {} failed", url);
+            }
+        }
+        None => {
+            o_warn!("   {} No git repository recorded, creating an empty directory", "⚠".yellow());
+            fs::create_dir_all(dir_path)?;
+        }
+    }
+
+    // 2. ops.toml: a committed one wins over the recorded copy
+    let ops_toml = dir_path.join("ops.toml");
+    let mut secret_vars = Vec::new();
+    match config.as_mut() {
+        None => o_warn!(
+            "   {} No ops.toml recorded for {}. Run `ops deploy` once from an existing checkout to record it.",
+            "⚠".yellow(), target
+        ),
+        Some(_) if ops_toml.exists() => o_detail!("   ✔ Using the ops.toml from the repository"),
+        Some(config) => {
+            secret_vars = redact_secrets(config);
+            let content = toml::to_string(config).context("Failed to serialize ops.toml")?;
+            fs::write(&ops_toml, format!("# Recorded by the last `ops deploy` of {}\n\n{}", target, content))
+                .with_context(|| format!("Failed to write {}", ops_toml.display()))?;
+            o_success!("   ✔ Wrote {} from the last deploy", ops_toml.display());
+        }
+    }
+
+    o_result!("\n{} Cloned {} into {}", "✅".green(), target.green(), dir.cyan());
+
+    // 3. Secrets are never recorded: list what the new checkout still needs
+    if let Some(config) = &config {
+        let missing: Vec<_> = config.env_files.iter()
+            .filter(|ef| !dir_path.join(&ef.local).exists())
+            .collect();
+        if !missing.is_empty() || !secret_vars.is_empty() {
+            o_result!("\nBefore deploying:");
+        }
+        for ef in missing {
+            o_result!("  • create {} (uploaded to {}/{})", ef.local.yellow(), config.deploy_path, ef.remote);
+        }
+        for var in &secret_vars {
+            o_result!("  • export {}", var.yellow());
+        }
+    }
+    o_result!("\n  cd {} && ops deploy", dir);
+    Ok(())
+}
+
+/// Replace literal tokens with `$VAR` references. Returns the env vars the config now needs.
+fn redact_secrets(config: &mut OpsToml) -> Vec<String> {
+    let mut vars = Vec::new();
+    let mut redact = |token: &mut String, placeholder: &str| {
+        // ecr/gcr helpers have no token to redact
+        if token.is_empty() {
+            return;
+        }
+        let var = match token.strip_prefix('$') {
+            Some(var) => var.to_string(),
+            None => {
+                *token = format!("${}", placeholder);
+                placeholder.to_string()
+            }
+        };
+        if !vars.contains(&var) {
+            vars.push(var);
+        }
+    };
+
+    if let Some(registry) = config.deploy.registry.as_mut() {
+        redact(&mut registry.token, "REGISTRY_TOKEN");
+    }
+    if let Some(build) = config.build.as_mut() {
+        if let Some(token) = build.git.as_mut().and_then(|g| g.token.as_mut()) {
+            redact(token, "GIT_TOKEN");
+        }
+        if let Some(image) = build.image.as_mut() {
+            redact(&mut image.token, "IMAGE_REGISTRY_TOKEN");
+        }
+    }
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_secrets() {
+        let mut config: OpsToml = toml::from_str(r#"
+            project = "RedQ"
+            deploy_path = "/opt/redq"
+
+            [deploy]
+            source = "image"
+
+            [deploy.registry]
+            url = "ghcr.io"
+            token = "ghp_literal"
+
+            [build]
+            path = "/opt/build"
+            command = "make"
+
+            [build.git]
+            repo = "https://github.com/acme/redq.git"
+            token = "$GH_PAT"
+        "#).unwrap();
+
+        assert_eq!(redact_secrets(&mut config), vec!["REGISTRY_TOKEN", "GH_PAT"]);
+        assert_eq!(config.deploy.registry.as_ref().unwrap().token, "$REGISTRY_TOKEN");
+
+        // The written ops.toml must load again
+        let written = toml::to_string(&config).unwrap();
+        assert!(!written.contains("ghp_literal"));
+        toml::from_str::<OpsToml>(&written).unwrap();
+    }
+}
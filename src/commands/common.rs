@@ -9,3 +9,71 @@ pub fn resolve_env_value(val: &str) -> Result<String> {
         Ok(val.to_string())
     }
 }
+
+/// Resolve a command target. An explicit target is parsed with `utils::parse_target`;
+/// when omitted, the user picks one of their nodes interactively.
+pub async fn resolve_target(target: Option<String>, interactive: bool) -> Result<crate::utils::Target> {
+    if let Some(t) = target {
+        return crate::utils::parse_target(&t);
+    }
+
+    if !interactive {
+        anyhow::bail!("No target given. Pass a node ID or app.project (e.g., 12345 or api.RedQ)");
+    }
+
+    let cfg = crate::config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let nodes = crate::api::list_nodes(&token).await?.nodes;
+    if nodes.is_empty() {
+        anyhow::bail!("No nodes found. Run `ops init` on a server first.");
+    }
+
+    let labels: Vec<String> = nodes.iter().map(|n| {
+        let apps = n.bound_apps.as_ref()
+            .map(|apps| apps.iter()
+                .map(|a| format!("{}.{}", a.name, a.project_name))
+                .collect::<Vec<_>>()
+                .join(", "))
+            .unwrap_or_default();
+        format!(
+            "#{} {} ({}) {}",
+            n.id,
+            n.hostname.as_deref().unwrap_or("-"),
+            n.ip_address,
+            apps
+        ).trim_end().to_string()
+    }).collect();
+    let options: Vec<&str> = labels.iter().map(|s| s.as_str()).collect();
+
+    o_step!("Select a target:");
+    let idx = crate::prompt::select("Node", &options, 0, interactive)?;
+    Ok(crate::utils::Target::NodeId { id: nodes[idx].id as u64, path: None })
+}
+
+/// Resolve the node an ops.toml command runs on: explicit `--target`, otherwise the first
+/// node bound to `app` (default: the first [[apps]] entry, or the project itself) that the
+/// `--env` environment selects
+pub async fn resolve_deploy_target(
+    config: &crate::types::OpsToml,
+    app: Option<&str>,
+    target: Option<String>,
+) -> Result<crate::utils::Target> {
+    if let Some(t) = target {
+        return crate::utils::parse_target(&t);
+    }
+
+    let project = &config.project;
+    let app = app
+        .or_else(|| config.apps.first().map(|a| a.name.as_str()))
+        .unwrap_or(project.as_str());
+
+    let cfg = crate::config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let resp = crate::api::get_app_deploy_targets(&token, project, app).await
+        .context("Failed to get deploy targets")?;
+    let t = resp.targets.iter().find(|t| config.env_selects(t.node_id, t.region.as_deref()))
+        .with_context(|| format!("No nodes bound to {}.{}{}", app, project,
+            config.env.as_ref().map(|e| format!(" for environment {}", e)).unwrap_or_default()))?;
+    Ok(crate::utils::Target::NodeId { id: t.node_id as u64, path: None })
+}
@@ -0,0 +1,189 @@
+//! Shell completion through clap_complete's `CompleteEnv`. `ops completions <shell>` prints the
+//! registration script, which runs `COMPLETE=<shell> ops -- <words>` on each tab press. Commands
+//! and flags come from the clap definition; `app.project` targets, node IDs and project names are
+//! added from the API. Those are cached for a few minutes in `<config dir>/ops/completion-cache.json`,
+//! so a tab press rarely waits on it.
+
+use crate::commands::project::app_summaries;
+use crate::types::ProjectListQuery;
+use crate::{api, config};
+use anyhow::{Context, Result};
+use clap_complete::env::Shells;
+use clap_complete::{ArgValueCandidates, CompletionCandidate};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The variable that switches `ops` into completion mode
+pub const COMPLETE_VAR: &str = "COMPLETE";
+const CACHE_TTL: Duration = Duration::from_secs(300);
+/// Longest a tab press waits for the API
+const FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Values that come from the account rather than the CLI definition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueKind {
+    /// `app.project` targets and node IDs
+    Target,
+    Node,
+    Project,
+}
+
+/// The dynamic kind of `arg` in the command at `path` (subcommand names from the root), by argument name
+fn value_kind(path: &[String], arg: &clap::Arg) -> Option<ValueKind> {
+    match arg.get_id().as_str() {
+        "target" => Some(ValueKind::Target),
+        "node" => Some(ValueKind::Node),
+        "project" => Some(ValueKind::Project),
+        "id" | "from" | "to" if path.first().is_some_and(|p| p == "node") => Some(ValueKind::Node),
+        _ => None,
+    }
+}
+
+/// `root` with `dynamic`'s values as the candidates of every argument that takes a target, node
+/// or project
+pub fn with_candidates(root: clap::Command, dynamic: fn(ValueKind) -> Vec<String>) -> clap::Command {
+    add_candidates(root, &[], dynamic)
+}
+
+// mut_args and mut_subcommands keep the order, which positional indexes and help depend on;
+// mut_arg and mut_subcommand move the item to the end
+fn add_candidates(cmd: clap::Command, path: &[String], dynamic: fn(ValueKind) -> Vec<String>) -> clap::Command {
+    cmd.mut_args(|arg| {
+        let kind = value_kind(path, &arg).filter(|_| arg.get_possible_values().is_empty());
+        match kind {
+            Some(kind) => arg.add(ArgValueCandidates::new(move || {
+                dynamic(kind).into_iter().map(CompletionCandidate::new).collect::<Vec<_>>()
+            })),
+            None => arg,
+        }
+    })
+    .mut_subcommands(|sub| {
+        let sub_path = [path, &[sub.get_name().to_string()]].concat();
+        add_candidates(sub, &sub_path, dynamic)
+    })
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Cache {
+    /// Unix seconds
+    fetched_at: u64,
+    targets: Vec<String>,
+    nodes: Vec<String>,
+    projects: Vec<String>,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+async fn fetch(token: &str) -> Result<Cache> {
+    let query = ProjectListQuery::default();
+    let (projects, nodes) = tokio::try_join!(api::list_projects(token, &query), api::list_nodes(token))?;
+    let mut cache = Cache { fetched_at: now(), ..Default::default() };
+    for project in &projects.projects {
+        cache.projects.push(project.name.clone());
+        cache.targets.extend(app_summaries(project).into_iter().map(|a| format!("{}.{}", a.name, project.name)));
+    }
+    for node in &nodes.nodes {
+        cache.nodes.push(node.id.to_string());
+        cache.targets.extend(node.bound_apps.iter().flatten().map(|b| format!("{}.{}", b.name, b.project_name)));
+    }
+    cache.targets.sort();
+    cache.targets.dedup();
+    Ok(cache)
+}
+
+/// Cached names, refreshed from the API when stale; nothing when logged out or offline
+async fn load_cache() -> Cache {
+    let path = config::completion_cache_path();
+    let cached: Option<Cache> = path.as_ref()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str(&s).ok());
+    if cached.as_ref().is_some_and(|c| now().saturating_sub(c.fetched_at) < CACHE_TTL.as_secs()) {
+        return cached.unwrap_or_default();
+    }
+    let Some(token) = config::load_config().ok().and_then(|c| c.token) else { return cached.unwrap_or_default() };
+    match tokio::time::timeout(FETCH_TIMEOUT, fetch(&token)).await {
+        Ok(Ok(cache)) => {
+            if let (Some(path), Ok(json)) = (path, serde_json::to_string(&cache)) {
+                if let Some(dir) = path.parent() {
+                    let _ = std::fs::create_dir_all(dir);
+                }
+                let _ = config::write_atomic(&path, &json);
+            }
+            cache
+        }
+        // A stale list beats none
+        _ => cached.unwrap_or_default(),
+    }
+}
+
+/// The account's names of `kind`, fetched once per tab press
+pub fn account_values(kind: ValueKind) -> Vec<String> {
+    static CACHE: OnceLock<Cache> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| {
+        // Completion runs at the top of main, inside its runtime; the API calls get their own
+        std::thread::spawn(|| {
+            tokio::runtime::Builder::new_current_thread().enable_all().build()
+                .map(|rt| rt.block_on(load_cache()))
+                .unwrap_or_default()
+        }).join().unwrap_or_default()
+    });
+    match kind {
+        ValueKind::Target => cache.targets.iter().chain(&cache.nodes).cloned().collect(),
+        ValueKind::Node => cache.nodes.clone(),
+        ValueKind::Project => cache.projects.clone(),
+    }
+}
+
+/// ops completions <shell>
+pub fn handle_completions(shell: &str) -> Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells.completer(shell)
+        .with_context(|| format!("Unsupported shell '{}'. Use bash, zsh or fish", shell))?;
+    completer.write_registration(COMPLETE_VAR, "ops", "ops", "ops", &mut std::io::stdout())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction, Command};
+
+    fn cli() -> Command {
+        let cmd = Command::new("ops")
+            .arg(Arg::new("json").long("json").action(ArgAction::SetTrue).global(true))
+            .subcommand(Command::new("ssh").arg(Arg::new("target")).arg(Arg::new("user").long("user")))
+            .subcommand(Command::new("deploy")
+                .arg(Arg::new("file").short('f').long("file"))
+                .arg(Arg::new("node").long("node"))
+                .arg(Arg::new("rolling").long("rolling").action(ArgAction::SetTrue)))
+            .subcommand(Command::new("node").subcommand(Command::new("info").arg(Arg::new("id"))))
+            .subcommand(Command::new("completions").arg(Arg::new("shell").value_parser(["bash", "zsh", "fish"])));
+        with_candidates(cmd, |kind| match kind {
+            ValueKind::Target => vec!["api.shop".into(), "web.shop".into(), "4".into()],
+            ValueKind::Node => vec!["4".into(), "12".into()],
+            ValueKind::Project => vec!["shop".into()],
+        })
+    }
+
+    fn run(words: &[&str]) -> Vec<String> {
+        let args: Vec<std::ffi::OsString> = std::iter::once("ops").chain(words.iter().copied()).map(Into::into).collect();
+        let index = args.len() - 1;
+        let mut values: Vec<String> = clap_complete::engine::complete(&mut cli(), args, index, None).unwrap()
+            .into_iter().map(|c| c.get_value().to_string_lossy().into_owned()).collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn test_complete() {
+        assert_eq!(run(&["d"]), vec!["deploy"]);
+        assert_eq!(run(&["ssh", "a"]), vec!["api.shop"]);
+        assert_eq!(run(&["deploy", "--node", ""]), vec!["12", "4"]);
+        assert_eq!(run(&["deploy", "-f", "ops.toml", "--rolling", "--no"]), vec!["--node"]);
+        assert_eq!(run(&["node", "info", "1"]), vec!["12"]);
+        assert_eq!(run(&["completions", "z"]), vec!["zsh"]);
+    }
+}
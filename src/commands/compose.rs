@@ -0,0 +1,95 @@
+use crate::commands::deploy::{app_routes, compose_args, env_prefix, fallback_routes, load_ops_toml, RenderedRoute};
+use crate::router::Router;
+use anyhow::{bail, Result};
+use colored::Colorize;
+use std::path::Path;
+
+/// ops compose render: print what `ops deploy` would put on a node, without connecting to it
+pub fn handle_render(file: String, app_filter: Option<String>, env_vars: Vec<String>) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    let router = Router::from_config(config.deploy.router.as_deref())?;
+    if let Some(ref name) = app_filter {
+        if !config.apps.iter().any(|a| a.name == *name) {
+            bail!("App '{}' not found in {}", name, file);
+        }
+    }
+
+    let project = &config.project;
+    let deploy_path = &config.deploy_path;
+    let compose = compose_args(&config);
+    let compose_project = config.compose_project();
+    let env = env_prefix(&env_vars);
+
+    o_result!("{}", format!("# {} ({}) → {}", project, file, deploy_path).bold());
+
+    // 1. Compose
+    section("Compose");
+    o_result!("project:   {}", compose_project);
+    o_result!("profiles:  {}", if config.deploy.profiles.is_empty() { "(none)".to_string() } else { config.deploy.profiles.join(", ") });
+    o_result!("files:     {}", config.deploy.compose_files.as_ref().map(|f| f.join(" ")).unwrap_or_else(|| "(default docker-compose.yml)".to_string()));
+    o_result!("source:    {}", config.deploy.source);
+    o_result!("strategy:  {}", config.deploy.strategy.as_deref().unwrap_or("deploy-id"));
+    // The same arguments deploy passes: `-p` only when compose_project_name is set
+    o_result!("command:   cd {} && {}docker compose{}", deploy_path, env, if compose.is_empty() { String::new() } else { format!(" {}", compose) });
+
+    // 2. Overrides: --set values prefixed to every compose command, plus per-service run settings
+    section("Overrides");
+    if env_vars.is_empty() {
+        o_result!("{}", "(no --set values)".dimmed());
+    }
+    for kv in &env_vars {
+        o_result!("{}", kv);
+    }
+    let apps: Vec<_> = config.apps.iter()
+        .filter(|a| a.port.is_some())
+        .filter(|a| app_filter.as_ref().is_none_or(|f| *f == a.name))
+        .collect();
+    // strategy = "up" keeps the compose definition as-is; the deploy-id path runs containers itself
+    if config.deploy.strategy.as_deref() != Some("up") {
+        for app in &apps {
+            for svc in &app.services {
+                o_result!();
+                o_result!("{} {}", "service".dimmed(), svc.cyan());
+                o_result!("  name:      {}-{}-<deploy-id>", compose_project, svc);
+                o_result!("  image:     {}-{}:latest", compose_project, svc);
+                o_result!("  network:   (detected from the running compose project)");
+                o_result!("  env-file:  {}/.ops-env-{} (generated from `docker compose config`)", deploy_path, svc);
+                o_result!("  volume:    {}/public:/app/public", deploy_path);
+            }
+        }
+    }
+
+    // 3. Env files (names only, never contents)
+    section("Env files");
+    if config.env_files.is_empty() {
+        o_result!("{}", "(none)".dimmed());
+    }
+    for ef in &config.env_files {
+        let state = if Path::new(&ef.local).exists() { "".normal() } else { " (missing locally, skipped)".yellow() };
+        o_result!("{} → {}/{}{}", ef.local, deploy_path, ef.remote, state);
+    }
+
+    // 4. Route fragments, exactly as written; zero-downtime upstreams are container IPs known only at deploy time
+    section(&format!("{} routes", router.name()));
+    let routes: Vec<RenderedRoute> = if apps.is_empty() {
+        fallback_routes(router, &config, &app_filter)
+    } else {
+        apps.iter()
+            .flat_map(|app| app_routes(router, &config, app, &format!("<container-ip>:{}", app.port.unwrap_or_default())))
+            .collect()
+    };
+    if routes.is_empty() {
+        o_result!("{}", "(none)".dimmed());
+    }
+    for route in &routes {
+        o_result!("{}", route.path.cyan());
+        o_result!("{}", route.content);
+    }
+
+    Ok(())
+}
+
+fn section(title: &str) {
+    o_result!();
+    o_result!("{}", format!("## {}", title).bold());
+}
@@ -0,0 +1,157 @@
+use crate::types::Node;
+use crate::{api, config};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+/// Monthly cost of an app: its share of each node it is bound to
+#[derive(Debug, Default, PartialEq)]
+struct AppCost {
+    amount: f64,
+    nodes: BTreeSet<i64>,
+}
+
+/// Node costs split across projects/apps. A node's cost is divided evenly among its bound apps.
+#[derive(Debug, Default)]
+struct CostReport {
+    /// (project, app, currency) → cost
+    apps: BTreeMap<(String, String, String), AppCost>,
+    /// Priced nodes without bound apps: currency → (cost, node ids)
+    unallocated: BTreeMap<String, AppCost>,
+    /// Nodes without a cost
+    unpriced: Vec<i64>,
+}
+
+fn apportion(nodes: &[Node], project: Option<&str>) -> CostReport {
+    let mut report = CostReport::default();
+    for node in nodes {
+        let bound: Vec<_> = node.bound_apps.iter().flatten().collect();
+        let Some(cost) = node.monthly_cost else {
+            if project.is_none_or(|p| bound.iter().any(|b| b.project_name == p)) {
+                report.unpriced.push(node.id);
+            }
+            continue;
+        };
+        let currency = node.cost_currency.clone().unwrap_or_else(|| DEFAULT_CURRENCY.into());
+
+        if bound.is_empty() {
+            if project.is_none() {
+                let entry = report.unallocated.entry(currency).or_default();
+                entry.amount += cost;
+                entry.nodes.insert(node.id);
+            }
+            continue;
+        }
+        let share = cost / bound.len() as f64;
+        for b in bound.iter().filter(|b| project.is_none_or(|p| b.project_name == p)) {
+            let entry = report.apps.entry((b.project_name.clone(), b.name.clone(), currency.clone())).or_default();
+            entry.amount += share;
+            entry.nodes.insert(node.id);
+        }
+    }
+    report
+}
+
+fn money(amount: f64, currency: &str) -> String {
+    format!("{} {:.2}", currency, amount)
+}
+
+fn nodes_label(n: usize) -> String {
+    if n == 1 { "1 node".into() } else { format!("{} nodes", n) }
+}
+
+/// ops cost [project]: monthly node cost per project and app
+pub async fn handle_cost(project: Option<String>) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let nodes = api::list_nodes(&token).await.context("Failed to list nodes")?.nodes;
+    let report = apportion(&nodes, project.as_deref());
+
+    o_step!("{} {}\n", "💰 Monthly cost".cyan(), "(node cost split evenly across its bound apps)".dimmed());
+
+    if report.apps.is_empty() && report.unallocated.is_empty() {
+        o_detail!("  No priced nodes{}.", project.as_ref().map(|p| format!(" bound to {}", p)).unwrap_or_default());
+    }
+
+    // Per project, then per app
+    let mut totals: BTreeMap<String, f64> = BTreeMap::new();
+    let mut last_project = None;
+    let project_totals: BTreeMap<(&str, &str), f64> = report.apps.iter()
+        .fold(BTreeMap::new(), |mut acc, ((p, _, cur), c)| {
+            *acc.entry((p.as_str(), cur.as_str())).or_default() += c.amount;
+            acc
+        });
+    for ((proj, app, currency), cost) in &report.apps {
+        if last_project != Some(proj) {
+            let sums: Vec<String> = project_totals.iter()
+                .filter(|((p, _), _)| p == proj)
+                .map(|((_, cur), sum)| money(*sum, cur))
+                .collect();
+            o_result!("  {:<34} {}", proj.bold(), sums.join(" + ").bold());
+            last_project = Some(proj);
+        }
+        o_result!("    {:<18} {:<13} {}", app.cyan(), nodes_label(cost.nodes.len()), money(cost.amount, currency));
+        *totals.entry(currency.clone()).or_default() += cost.amount;
+    }
+    for (currency, cost) in &report.unallocated {
+        let ids: Vec<String> = cost.nodes.iter().map(|id| format!("#{}", id)).collect();
+        o_result!("  {:<34} {}", format!("(unallocated) {}", ids.join(" ")).dimmed(), money(cost.amount, currency));
+        *totals.entry(currency.clone()).or_default() += cost.amount;
+    }
+
+    if !totals.is_empty() {
+        let sums: Vec<String> = totals.iter().map(|(cur, sum)| money(*sum, cur)).collect();
+        o_result!("\n  {:<34} {}", "Total".bold(), sums.join(" + ").green().bold());
+    }
+
+    if !report.unpriced.is_empty() {
+        let ids: Vec<String> = report.unpriced.iter().map(|id| format!("#{}", id)).collect();
+        o_warn!(
+            "\n  {} {} without a cost: {}. Set one with `ops node set-cost <id> <amount> [currency]`.",
+            "⚠".yellow(), nodes_label(ids.len()), ids.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: i64, cost: Option<f64>, apps: &[(&str, &str)]) -> Node {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "ip_address": "203.0.113.1", "ipv6_address": null, "hostname": null,
+            "domain": format!("{}.node.ops.autos", id), "region": null, "zone": null, "serve_port": 8377,
+            "allowed_projects": null, "allowed_apps": null, "status": "healthy", "last_health_check": null,
+            "has_serve_token": 1, "created_at": "2026-01-01",
+            "bound_apps": apps.iter().enumerate().map(|(i, (app, project))| serde_json::json!({
+                "id": i, "name": app, "project_name": project, "is_primary": null
+            })).collect::<Vec<_>>(),
+            "monthly_cost": cost,
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_apportion() {
+        let nodes = vec![
+            node(1, Some(20.0), &[("api", "RedQ"), ("web", "RedQ")]),
+            node(2, Some(14.0), &[("api", "RedQ")]),
+            node(3, Some(5.0), &[]),
+            node(4, None, &[("web", "Shop")]),
+        ];
+        let report = apportion(&nodes, None);
+        let api = &report.apps[&("RedQ".into(), "api".into(), "USD".into())];
+        assert_eq!(api.amount, 24.0);
+        assert_eq!(api.nodes.len(), 2);
+        assert_eq!(report.apps[&("RedQ".into(), "web".into(), "USD".into())].amount, 10.0);
+        assert_eq!(report.unallocated["USD"].amount, 5.0);
+        assert_eq!(report.unpriced, vec![4]);
+
+        let redq = apportion(&nodes, Some("RedQ"));
+        assert_eq!(redq.apps.len(), 2);
+        assert!(redq.unallocated.is_empty() && redq.unpriced.is_empty());
+    }
+}
@@ -1,33 +1,77 @@
-use crate::types::{OpsToml, DeployTarget, AppDef};
-use crate::commands::common::resolve_env_value;
+use crate::types::{OpsToml, DeployTarget, AppDef, HealthCheck, HooksConfig, DeployedState, RouteDef};
+use crate::registry::Credentials;
+use crate::commands::{canary, deploy_plan, dir_sync, disk, drift, policy, release, secret_store, secrets, summary, verify};
+use crate::commands::summary::{DeploySummary, NodeSummary};
+use crate::error::OpsError;
 use crate::commands::ssh::SshSession;
-use crate::commands::scp;
-use crate::{api, config, prompt};
+use crate::router::{ProxyOptions, RouteMatch, Router};
+use crate::node_state;
+use crate::serve::queue::{FinishRequest, HeldPlace, HoldRequest, HOLD_LEASE_SECS};
+use crate::project_state::{CanaryState, ProjectState};
+use crate::utils::shell_quote;
+use crate::{api, config, prompt, scanner};
 use anyhow::{anyhow, bail, Context, Result};
 use colored::Colorize;
 use std::fs;
 use std::path::Path;
 
+const SERVE_QUEUE_POLL: std::time::Duration = std::time::Duration::from_secs(5);
+/// How long a deploy waits for the node's ops serve queue
+const SERVE_QUEUE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1800);
+
 /// 读取并解析 ops.toml
 pub fn load_ops_toml(path: &str) -> Result<OpsToml> {
     let content = fs::read_to_string(path)
         .with_context(|| format!("Cannot read {}", path))?;
-    let config: OpsToml = toml::from_str(&content)
+    let mut config: OpsToml = toml::from_str(&content)
         .with_context(|| format!("Invalid ops.toml format in {}", path))?;
+    config.dir = Path::new(path).parent().map(Path::to_path_buf).unwrap_or_default();
+    for app in &config.apps {
+        app.lb.affinity().with_context(|| format!("Invalid [[apps]] '{}' in {}", app.name, path))?;
+        app.edge.max_body_bytes().with_context(|| format!("Invalid [[apps]] '{}' in {}", app.name, path))?;
+        app.static_cache().with_context(|| format!("Invalid [[apps]] '{}' in {}", app.name, path))?;
+    }
+    if let Some(mode) = &config.deploy.secret_scan {
+        if !["warn", "block", "off"].contains(&mode.as_str()) {
+            bail!("Invalid deploy.secret_scan '{}' in {}. Expected warn, block or off", mode, path);
+        }
+    }
+    Ok(config)
+}
+
+/// load_ops_toml, then the `[environments.<env>]` overlay when `--env` is given
+pub fn load_ops_toml_env(path: &str, env: Option<&str>) -> Result<OpsToml> {
+    let mut config = load_ops_toml(path)?;
+    if let Some(env) = env {
+        config.apply_env(env).with_context(|| format!("Invalid --env for {}", path))?;
+    }
     Ok(config)
 }
 
 // ===== 辅助函数 =====
 
-/// 构建 -f 参数: "-f a.yml -f b.yml"，无配置时返回空串
-fn compose_file_args(config: &OpsToml) -> String {
-    config.deploy.compose_files.as_ref()
-        .map(|files| files.iter().map(|f| format!("-f {}", f)).collect::<Vec<_>>().join(" "))
-        .unwrap_or_default()
+/// 构建全局 compose 参数: "-p name --profile prod -f a.yml -f b.yml"，无配置时返回空串
+/// `-p` 仅在设置了 [deploy] compose_project_name 时添加
+pub fn compose_args(config: &OpsToml) -> String {
+    let base = compose_base_args(config);
+    match &config.deploy.compose_project_name {
+        Some(name) if base.is_empty() => format!("-p {}", name),
+        Some(name) => format!("-p {} {}", name, base),
+        None => base,
+    }
+}
+
+/// 不含 `-p` 的 compose 参数 (profiles + -f)，供显式指定 `-p` 的调用使用
+pub fn compose_base_args(config: &OpsToml) -> String {
+    let mut args: Vec<String> = config.deploy.profiles.iter().map(|p| format!("--profile {}", p)).collect();
+    if let Some(files) = &config.deploy.compose_files {
+        args.extend(files.iter().map(|f| format!("-f {}", f)));
+    }
+    args.join(" ")
 }
 
 /// 构建环境变量前缀: "K=V K2=V2 "
-fn env_prefix(env_vars: &[String]) -> String {
+pub fn env_prefix(env_vars: &[String]) -> String {
     if env_vars.is_empty() { return String::new(); }
     let mut s = env_vars.join(" ");
     s.push(' ');
@@ -35,7 +79,7 @@ fn env_prefix(env_vars: &[String]) -> String {
 }
 
 /// 解析 --app 到具体的 docker-compose service names
-fn resolve_services(config: &OpsToml, app: &Option<String>, service: &Option<String>) -> String {
+pub fn resolve_services(config: &OpsToml, app: &Option<String>, service: &Option<String>) -> String {
     if let Some(svc) = service {
         return svc.clone();
     }
@@ -48,14 +92,136 @@ fn resolve_services(config: &OpsToml, app: &Option<String>, service: &Option<Str
 }
 
 /// Resolve app name: first [[apps]] entry, otherwise project name
-fn resolve_app_name(config: &OpsToml) -> String {
+pub fn resolve_app_name(config: &OpsToml) -> String {
     config.apps.first()
         .map(|a| a.name.clone())
         .unwrap_or_else(|| config.project.clone())
 }
 
+// ===== 部署命令 (deploy 与 deploy --dry-run 共用) =====
+
+pub const IMAGE_PRUNE: &str = "docker image prune -f";
+
+/// `cd <deploy_path> && [env] docker compose [args]`, the start of every compose command a deploy runs
+pub fn compose_command(config: &OpsToml, env: &str, args: &str) -> String {
+    let args = if args.is_empty() { String::new() } else { format!(" {}", args) };
+    format!("cd {} && {}docker compose{}", config.deploy_path, env, args)
+}
+
+/// compose_command that always names the project, as the deploy-id containers rely on
+fn project_compose_command(config: &OpsToml, env: &str) -> String {
+    let args = format!("-p {} {}", config.compose_project(), compose_base_args(config));
+    compose_command(config, env, args.trim_end())
+}
+
+/// ` <services>`, or nothing for all of them
+fn services_arg(svcs: &str) -> String {
+    if svcs.is_empty() { String::new() } else { format!(" {}", svcs) }
+}
+
+fn pull_arg(no_pull: bool) -> &'static str {
+    if no_pull { "" } else { " --pull" }
+}
+
+/// Apps a deploy writes secrets for: `--app`, else every [[apps]] entry, else the project's one app
+pub fn deployed_apps(config: &OpsToml, app_filter: &Option<String>) -> Vec<String> {
+    match app_filter {
+        Some(name) => vec![name.clone()],
+        None if config.apps.is_empty() => vec![resolve_app_name(config)],
+        None => config.apps.iter().map(|a| a.name.clone()).collect(),
+    }
+}
+
+/// source = "git": update an existing checkout
+pub fn git_pull_command(config: &OpsToml, branch: &str) -> String {
+    format!("cd {} && git pull origin {}", config.deploy_path, branch)
+}
+
+/// source = "git": first deploy to the node
+pub fn git_clone_command(config: &OpsToml, repo: &str, branch: &str) -> String {
+    format!("GIT_SSH_COMMAND='ssh -o StrictHostKeyChecking=no' git clone -b {} {} {}", branch, repo, config.deploy_path)
+}
+
+/// source = "image": pull the services' images
+pub fn image_pull_command(config: &OpsToml, env: &str, svcs: &str) -> String {
+    format!("{} pull{}", compose_command(config, env, &compose_args(config)), services_arg(svcs))
+}
+
+/// Compose services outside [[apps]] (databases, caches), started before any app
+pub fn infra_up_command(config: &OpsToml, env: &str, svcs: &str) -> String {
+    format!("{} up -d --no-deps{}", project_compose_command(config, env), services_arg(svcs))
+}
+
+/// Zero-downtime: build the images of an app's services
+pub fn image_build_command(config: &OpsToml, env: &str, svcs: &str, no_pull: bool) -> String {
+    format!("{} build{}{}", project_compose_command(config, env), pull_arg(no_pull), services_arg(svcs))
+}
+
+/// Zero-downtime: the new `<project>-<svc>-<deployment>` container of a service
+pub fn container_run_command(config: &OpsToml, svc: &str, deployment: &str, network: &str) -> String {
+    let project = config.compose_project();
+    format!(
+        "docker run -d --name {p}-{s}-{d} --network {n} --env-file {dp}/.ops-env-{s} -v {dp}/public:/app/public {p}-{s}:latest",
+        p = project, s = svc, d = deployment, n = network, dp = config.deploy_path
+    )
+}
+
+pub fn active_deployment_command(config: &OpsToml, deployment: &str) -> String {
+    format!("echo {} > {}/.ops-active-deployment", deployment, config.deploy_path)
+}
+
+/// strategy = "up": pull (source = "image") or build an app's images, and which of the two it is
+pub fn scale_up_image_command(config: &OpsToml, env: &str, app: &AppDef, no_pull: bool) -> (String, &'static str) {
+    let compose = compose_command(config, env, &compose_args(config));
+    let svcs = services_arg(&app.services.join(" "));
+    if config.deploy.source == "image" {
+        (format!("{} pull{}", compose, svcs), "pull")
+    } else {
+        (format!("{} build{}{}", compose, pull_arg(no_pull), svcs), "build")
+    }
+}
+
+/// Without zero-downtime: restart, start (source = "image") or build and start the services,
+/// and which of the three it is
+pub fn start_command(config: &OpsToml, env: &str, svcs: &str, restart_only: bool, no_pull: bool) -> (String, &'static str) {
+    let compose = compose_command(config, env, &compose_args(config));
+    let svcs = services_arg(svcs);
+    if restart_only {
+        (format!("{} restart{}", compose, svcs), "restart")
+    } else if config.deploy.source == "image" {
+        (format!("{} up -d --remove-orphans{}", compose, svcs), "pull")
+    } else {
+        // 旧行为: build + up
+        let up = format!("{}docker compose{} up -d --remove-orphans{}", env, services_arg(&compose_args(config)), svcs);
+        (format!("{} build{}{} && {}", compose, pull_arg(no_pull), svcs, up), "build")
+    }
+}
+
+/// An [[init]] command in the service's compose container
+pub fn init_command(config: &OpsToml, env: &str, service: &str, command: &str) -> String {
+    format!("{} exec {} {}", compose_command(config, env, &compose_args(config)), service, command)
+}
+
+/// An [[init]] command in a zero-downtime deploy's new container
+pub fn container_init_command(config: &OpsToml, service: &str, deployment: &str, command: &str) -> String {
+    format!("docker exec {}-{}-{} {}", config.compose_project(), service, deployment, command)
+}
+
+/// `wait_for` entries of the deployed apps, each once, with its timeout in seconds
+pub fn dependency_waits<'a>(config: &'a OpsToml, app_filter: &Option<String>) -> Vec<(&'a str, u64)> {
+    let mut waits: Vec<(&str, u64)> = Vec::new();
+    for app in config.apps.iter().filter(|a| app_filter.as_ref().is_none_or(|f| *f == a.name)) {
+        for entry in &app.wait_for {
+            if !waits.iter().any(|(e, _)| e == entry) {
+                waits.push((entry, app.wait_timeout.unwrap_or(DEFAULT_WAIT_TIMEOUT)));
+            }
+        }
+    }
+    waits
+}
+
 /// Resolve deploy targets from API
-async fn resolve_targets(config: &OpsToml, app_filter: &Option<String>) -> Result<Vec<DeployTarget>> {
+pub async fn resolve_targets(config: &OpsToml, app_filter: &Option<String>) -> Result<Vec<DeployTarget>> {
     let project = &config.project;
 
     let cfg = config::load_config().context("Config error")?;
@@ -66,7 +232,7 @@ async fn resolve_targets(config: &OpsToml, app_filter: &Option<String>) -> Resul
         let resp = api::get_app_deploy_targets(&token, project, app_name).await
             .with_context(|| format!("Failed to get deploy targets for '{}' in project '{}'", app_name, project))?;
         if resp.targets.is_empty() {
-            return Err(anyhow!("No nodes bound to app '{}' in project '{}'", app_name, project));
+            return Err(OpsError::NoNodesBound { app: Some(app_name.clone()), project: project.clone() }.into());
         }
         return Ok(resp.targets);
     }
@@ -93,6 +259,7 @@ async fn resolve_targets(config: &OpsToml, app_filter: &Option<String>) -> Resul
                 node_id: n.id,
                 domain: n.domain.clone(),
                 ip_address: n.ip_address.clone(),
+                ipv6_address: n.ipv6_address.clone(),
                 hostname: n.hostname.clone(),
                 region: n.region.clone(),
                 zone: n.zone.clone(),
@@ -104,7 +271,7 @@ async fn resolve_targets(config: &OpsToml, app_filter: &Option<String>) -> Resul
         .collect();
 
     if targets.is_empty() {
-        return Err(anyhow!("No nodes bound to project '{}'. Bind a node first with `ops set <app.project> --node <id>`.", project));
+        return Err(OpsError::NoNodesBound { app: None, project: project.clone() }.into());
     }
 
     Ok(targets)
@@ -158,6 +325,7 @@ async fn auto_allocate_node(
         node_id: selected.id,
         domain: selected.domain.clone(),
         ip_address: selected.ip_address.clone(),
+        ipv6_address: selected.ipv6_address.clone(),
         hostname: selected.hostname.clone(),
         region: selected.region.clone(),
         zone: selected.zone.clone(),
@@ -167,30 +335,88 @@ async fn auto_allocate_node(
     }])
 }
 
+/// `ops deploy` flags
+#[derive(Debug, Default, Clone)]
+pub struct DeployOptions {
+    pub file: String,
+    pub service_filter: Option<String>,
+    pub app_filter: Option<String>,
+    pub restart_only: bool,
+    pub env_vars: Vec<String>,
+    pub node_filter: Option<u64>,
+    pub region_filter: Option<String>,
+    pub rolling: bool,
+    pub force: bool,
+    pub no_pull: bool,
+    pub init: bool,
+    pub override_policy: Option<String>,
+    pub interactive: bool,
+    pub summary_file: Option<String>,
+    pub all_apps: bool,
+    pub verify: bool,
+    pub dry_run: bool,
+    pub env: Option<String>,
+}
+
+impl DeployOptions {
+    /// The flags that change what runs on each node
+    fn steps(&self) -> StepOptions<'_> {
+        StepOptions {
+            service_filter: &self.service_filter,
+            app_filter: &self.app_filter,
+            restart_only: self.restart_only,
+            env_vars: &self.env_vars,
+            no_pull: self.no_pull,
+            init: self.init,
+        }
+    }
+}
+
+/// The deploy flags that change what runs on a node
+#[derive(Clone, Copy)]
+pub struct StepOptions<'a> {
+    pub service_filter: &'a Option<String>,
+    pub app_filter: &'a Option<String>,
+    pub restart_only: bool,
+    pub env_vars: &'a [String],
+    pub no_pull: bool,
+    pub init: bool,
+}
+
 /// ops deploy 主入口
-pub async fn handle_deploy(
-    file: String,
-    service_filter: Option<String>,
-    app_filter: Option<String>,
-    restart_only: bool,
-    env_vars: Vec<String>,
-    node_filter: Option<u64>,
-    region_filter: Option<String>,
-    rolling: bool,
-    force: bool,
-    no_pull: bool,
-    init: bool,
-    interactive: bool,
-) -> Result<()> {
+pub async fn handle_deploy(mut opts: DeployOptions, canary: Option<canary::Mode>) -> Result<()> {
+    let started = summary::Started::now();
     // 1. 解析配置
     o_step!("{}", "📦 Reading ops.toml...".cyan());
-    let config = load_ops_toml(&file)?;
+    let config = load_ops_toml_env(&opts.file, opts.env.as_deref())?;
+    // The environment's node selection, unless given on the command line
+    opts.node_filter = opts.node_filter.or(config.active_env().and_then(|e| e.node));
+    opts.region_filter = opts.region_filter.or_else(|| config.active_env().and_then(|e| e.region.clone()));
+    if let Some(env) = &config.env {
+        o_detail!("   Environment: {}", env.yellow());
+    }
+    warn_ignored_route_options(&config, &opts.app_filter)?;
+    let overridden = policy::enforce(&config, &opts.file, &opts.env_vars, opts.override_policy.as_deref())?;
+    if config.deploy.source == "push" && !opts.restart_only {
+        secrets::guard(config.deploy.secret_scan.as_deref(), &config.deploy.include, config.deploy.gitignore)?;
+    }
+    if opts.dry_run {
+        return dry_run_plans(&config, &opts).await;
+    }
+    if opts.all_apps {
+        return deploy_all_apps(config, opts, &overridden).await;
+    }
+    let steps = opts.steps();
+    let DeployOptions {
+        ref file, ref service_filter, ref app_filter, restart_only, ref env_vars, node_filter, ref region_filter,
+        rolling, force, no_pull, init, ref override_policy, interactive, ref summary_file, verify, ref env, ..
+    } = opts;
 
     let app_name = resolve_app_name(&config);
-    let mut targets = match resolve_targets(&config, &app_filter).await {
+    let mut targets = match resolve_targets(&config, app_filter).await {
         Ok(t) => t,
-        Err(e) if e.to_string().contains("No nodes bound") => {
-            auto_allocate_node(&config, &app_filter, interactive).await?
+        Err(e) if OpsError::is_no_nodes_bound(&e) => {
+            auto_allocate_node(&config, app_filter, interactive).await?
         }
         Err(e) => return Err(e),
     };
@@ -202,12 +428,22 @@ pub async fn handle_deploy(
             return Err(anyhow!("Node {} is not bound to this app", nid));
         }
     }
-    if let Some(ref region) = region_filter {
+    if let Some(region) = region_filter {
         targets.retain(|t| t.region.as_deref() == Some(region.as_str()));
         if targets.is_empty() {
             return Err(anyhow!("No nodes in region '{}' bound to this app", region));
         }
     }
+    let canary = match canary {
+        Some(mode) => {
+            let options = CanaryState {
+                service: service_filter.clone(), env_keys: canary::env_keys(env_vars), restart_only, no_pull, env: env.clone(),
+                ..Default::default()
+            };
+            Some(canary::prepare(file, &config, app_filter, &mode, &mut targets, options).await?)
+        }
+        None => None,
+    };
 
     // 打印部署计划
     o_detail!("   Project: {}", app_name.green());
@@ -223,13 +459,13 @@ pub async fn handle_deploy(
                 t.domain.cyan(), region_str, primary_str);
         }
     }
-    if let Some(ref app) = app_filter {
-        let svcs = resolve_services(&config, &app_filter, &service_filter);
+    if let Some(app) = app_filter {
+        let svcs = resolve_services(&config, app_filter, service_filter);
         if !svcs.is_empty() {
             o_detail!("   Group: {} → [{}]", app.yellow(), svcs);
         }
     }
-    if let Some(ref svc) = service_filter {
+    if let Some(svc) = service_filter {
         o_detail!("   Service: {}", svc.yellow());
     }
 
@@ -239,27 +475,42 @@ pub async fn handle_deploy(
     session.exec(&format!("mkdir -p {}", deploy_path), None)?;
 
     if !restart_only {
-        check_containers(&session, &config, &env_vars, force, interactive)?;
+        check_containers(&session, &config, env_vars, force, interactive)?;
     }
 
     // 3. 同步 App 记录到后端
-    let (_app_id, deployment_id) = sync_app_record(&config, &targets[0].domain).await;
+    let (app_id, deployment_id) = sync_app_record(&config, file).await;
+    if let (false, Some(reason)) = (overridden.is_empty(), override_policy) {
+        // No audit trail, no override
+        let did = deployment_id.context("Cannot log the policy override without a deployment record; not deploying")?;
+        policy::record_override(did, reason, &overridden).await
+            .context("Failed to log the policy override; not deploying")?;
+    }
 
     // 4. 部署到所有节点
-    if targets.len() == 1 {
-        let deploy_result = execute_deployment(
-            &config, &session, &service_filter, &app_filter, restart_only, &env_vars, no_pull, init, deployment_id,
-        ).await;
+    let first_domain = targets[0].domain.clone();
+    let total = targets.len();
+    let want_images = summary_file.is_some();
+    let verify_targets = if verify { targets.clone() } else { Vec::new() };
+    let mut nodes: Vec<NodeSummary> = Vec::new();
+    let outcome: Result<()> = if targets.len() == 1 {
+        let deploy_result = execute_deployment(&config, &session, &steps, deployment_id).await;
 
         if let Some(deployment_id) = deployment_id {
+            if deploy_result.is_ok() {
+                record_deployed_state(&config, &session, deployment_id, targets[0].node_id).await;
+            }
             update_deployment_status(deployment_id, &deploy_result).await;
         }
 
-        deploy_result?;
-        o_result!("\n{} Deployed {} to {}", "✅".green(), app_name.green(), targets[0].domain.cyan());
+        let images = if want_images && deploy_result.is_ok() { summary::running_images(&config, &session) } else { Vec::new() };
+        nodes.push(NodeSummary::new(&targets[0], &started, &deploy_result, images));
+        if deploy_result.is_ok() {
+            o_result!("\n{} Deployed {} to {}", "✅".green(), app_name.green(), targets[0].domain.cyan());
+        }
+        deploy_result
     } else if rolling {
         // 滚动部署：顺序执行
-        let total = targets.len();
         let mut success_count = 0;
         let mut failed: Vec<String> = Vec::new();
 
@@ -268,23 +519,29 @@ pub async fn handle_deploy(
             o_step!("\n{} [{}/{}] Deploying to {} ({})...",
                 "🚀".cyan(), i + 1, total, t.domain.cyan(), region_str);
 
+            let node_started = summary::Started::now();
+            let mut images = Vec::new();
             let deploy_path = &config.deploy_path;
-            let session = match SshSession::connect(&t.node_id.to_string()).await {
-                Ok(s) => s,
-                Err(e) => {
-                    o_error!("   {} {} ({}): {}", "✘".red(), t.domain, region_str, e);
-                    failed.push(t.domain.clone());
-                    continue;
-                }
+            let result: Result<()> = match SshSession::connect(&t.node_id.to_string()).await {
+                Err(e) => Err(e),
+                Ok(session) => match session.exec(&format!("mkdir -p {}", deploy_path), None) {
+                    Err(e) => Err(e),
+                    Ok(_) => {
+                        let result = execute_deployment(&config, &session, &steps, deployment_id).await;
+                        if result.is_ok() {
+                            if let Some(did) = deployment_id {
+                                record_deployed_state(&config, &session, did, t.node_id).await;
+                            }
+                            if want_images {
+                                images = summary::running_images(&config, &session);
+                            }
+                        }
+                        result
+                    }
+                },
             };
 
-            if let Err(e) = session.exec(&format!("mkdir -p {}", deploy_path), None) {
-                o_error!("   {} {} ({}): {}", "✘".red(), t.domain, region_str, e);
-                failed.push(t.domain.clone());
-                continue;
-            }
-
-            match execute_deployment(&config, &session, &service_filter, &app_filter, restart_only, &env_vars, no_pull, init, deployment_id).await {
+            match &result {
                 Ok(_) => {
                     o_success!("   {} {} ({})", "✔".green(), t.domain.green(), region_str);
                     success_count += 1;
@@ -294,15 +551,13 @@ pub async fn handle_deploy(
                     failed.push(t.domain.clone());
                 }
             }
+            nodes.push(NodeSummary::new(t, &node_started, &result, images));
         }
 
         print_deploy_summary(&app_name, success_count, &failed, deployment_id).await;
-        if !failed.is_empty() {
-            return Err(anyhow!("{} node(s) failed deployment", failed.len()));
-        }
+        if failed.is_empty() { Ok(()) } else { Err(anyhow!("{} node(s) failed deployment", failed.len())) }
     } else {
         // 并行部署
-        let total = targets.len();
         o_step!("\n{} Deploying to {} nodes in parallel...", "🚀".cyan(), total);
 
         let mut join_set = tokio::task::JoinSet::new();
@@ -312,21 +567,24 @@ pub async fn handle_deploy(
             let sf = service_filter.clone();
             let af = app_filter.clone();
             let ev = env_vars.clone();
-            let domain = t.domain.clone();
-            let region = t.region.clone();
-            let node_id = t.node_id;
 
             join_set.spawn(async move {
+                let node_started = summary::Started::now();
                 let deploy_path = &config.deploy_path;
-                let session = match SshSession::connect(&node_id.to_string()).await {
+                let session = match SshSession::connect(&t.node_id.to_string()).await {
                     Ok(s) => s,
-                    Err(e) => return (domain, region, Err(e)),
+                    Err(e) => return (t, node_started, Err(e), Vec::new()),
                 };
                 if let Err(e) = session.exec(&format!("mkdir -p {}", deploy_path), None) {
-                    return (domain.clone(), region, Err(e.into()));
+                    return (t, node_started, Err(e.into()), Vec::new());
                 }
-                let result = execute_deployment(&config, &session, &sf, &af, restart_only, &ev, no_pull, init, deployment_id).await;
-                (domain, region, result)
+                let steps = StepOptions { service_filter: &sf, app_filter: &af, restart_only, env_vars: &ev, no_pull, init };
+                let result = execute_deployment(&config, &session, &steps, deployment_id).await;
+                if let (Ok(_), Some(did)) = (&result, deployment_id) {
+                    record_deployed_state(&config, &session, did, t.node_id).await;
+                }
+                let images = if want_images && result.is_ok() { summary::running_images(&config, &session) } else { Vec::new() };
+                (t, node_started, result, images)
             });
         }
 
@@ -335,18 +593,19 @@ pub async fn handle_deploy(
 
         while let Some(result) = join_set.join_next().await {
             match result {
-                Ok((domain, region, deploy_result)) => {
-                    let region_str = region.as_deref().unwrap_or("?");
-                    match deploy_result {
+                Ok((t, node_started, deploy_result, images)) => {
+                    let region_str = t.region.as_deref().unwrap_or("?");
+                    match &deploy_result {
                         Ok(_) => {
-                            o_success!("   {} {} ({})", "✔".green(), domain.green(), region_str);
+                            o_success!("   {} {} ({})", "✔".green(), t.domain.green(), region_str);
                             success_count += 1;
                         }
                         Err(e) => {
-                            o_error!("   {} {} ({}): {}", "✘".red(), domain, region_str, e);
-                            failed.push(domain);
+                            o_error!("   {} {} ({}): {}", "✘".red(), t.domain, region_str, e);
+                            failed.push(t.domain.clone());
                         }
                     }
+                    nodes.push(NodeSummary::new(&t, &node_started, &deploy_result, images));
                 }
                 Err(e) => {
                     o_error!("   {} join error: {}", "✘".red(), e);
@@ -356,11 +615,307 @@ pub async fn handle_deploy(
         }
 
         print_deploy_summary(&app_name, success_count, &failed, deployment_id).await;
-        if !failed.is_empty() {
-            return Err(anyhow!("{} node(s) failed deployment", failed.len()));
+        if failed.is_empty() { Ok(()) } else { Err(anyhow!("{} node(s) failed deployment", failed.len())) }
+    };
+
+    if let Some(path) = summary_file {
+        let succeeded = nodes.iter().filter(|n| n.status == "success").count();
+        let record = DeploySummary {
+            kind: "deploy",
+            project: config.project.clone(),
+            app: app_name.clone(),
+            status: summary::overall_status(succeeded, total),
+            release_id: deployment_id,
+            started_at: started.at.clone(),
+            duration_secs: started.elapsed_secs(),
+            nodes,
+            urls: summary::app_urls(&config, app_filter),
+        };
+        match summary::write(path, &record) {
+            Ok(_) => o_detail!("   Summary written to {}", path),
+            Err(e) => o_warn!("   {} {:#}", "⚠".yellow(), e),
+        }
+    }
+    let finished = match canary {
+        Some(run) => canary::finish(file, run, deployment_id, outcome.is_ok()).await,
+        None => Ok(()),
+    };
+    // The deploy's own error first; a canary bookkeeping error after a failed deploy is secondary
+    if let Err(e) = outcome {
+        if let Err(finish_err) = finished {
+            o_warn!("   {} {:#}", "⚠".yellow(), finish_err);
+        }
+        return Err(e);
+    }
+    finished?;
+
+    // Local bookkeeping only; never fail a finished deploy over it
+    if let Err(e) = ProjectState::update(file, |s| {
+        s.last_target = Some(first_domain);
+        if let Some(id) = app_id {
+            s.app_ids.insert(app_name.clone(), id);
         }
+    }) {
+        o_warn!("   {} {:#}", "⚠ Could not update .ops/state.json:".yellow(), e);
     }
 
+    if verify {
+        verify::report(&verify::run(&config, &verify_targets).await?, false)?;
+    }
+    Ok(())
+}
+
+/// ops deploy --dry-run: print each app's plan (every [[apps]] entry in deploy order with --all-apps)
+async fn dry_run_plans(config: &OpsToml, opts: &DeployOptions) -> Result<()> {
+    let filters: Vec<Option<String>> = if opts.all_apps {
+        app_waves(&config.apps)?.into_iter().flatten().map(|a| Some(a.name.clone())).collect()
+    } else {
+        vec![opts.app_filter.clone()]
+    };
+    for filter in &filters {
+        let steps = StepOptions { app_filter: filter, ..opts.steps() };
+        let mut plan = deploy_plan::build(config, &steps)?;
+        // Nothing is allocated: a deploy would pick a node when none is bound
+        plan.targets = match resolve_targets(config, filter).await {
+            Ok(t) => t,
+            Err(e) if OpsError::is_no_nodes_bound(&e) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        plan.targets.retain(|t| opts.node_filter.is_none_or(|nid| t.node_id == nid as i64)
+            && opts.region_filter.as_ref().is_none_or(|r| t.region.as_deref() == Some(r.as_str())));
+        deploy_plan::print(&plan, opts.rolling)?;
+    }
+    Ok(())
+}
+
+/// [[apps]] in deploy order: each wave only depends on apps of earlier waves
+fn app_waves(apps: &[AppDef]) -> Result<Vec<Vec<&AppDef>>> {
+    for app in apps {
+        if let Some(dep) = app.depends_on.iter().find(|d| !apps.iter().any(|a| a.name == **d)) {
+            bail!("App '{}' depends on unknown app '{}'", app.name, dep);
+        }
+    }
+    let mut done: Vec<&str> = Vec::new();
+    let mut waves = Vec::new();
+    while done.len() < apps.len() {
+        let wave: Vec<&AppDef> = apps.iter()
+            .filter(|a| !done.contains(&a.name.as_str()) && a.depends_on.iter().all(|d| done.contains(&d.as_str())))
+            .collect();
+        if wave.is_empty() {
+            let rest: Vec<&str> = apps.iter().map(|a| a.name.as_str()).filter(|n| !done.contains(n)).collect();
+            bail!("Circular depends_on between apps: {}", rest.join(", "));
+        }
+        done.extend(wave.iter().map(|a| a.name.as_str()));
+        waves.push(wave);
+    }
+    Ok(waves)
+}
+
+/// ops deploy --all-apps: every [[apps]] entry on its own nodes. Waves follow depends_on; within a wave
+/// nodes deploy in parallel, and apps sharing a node go one after another since they share its deploy path.
+async fn deploy_all_apps(config: OpsToml, run: DeployOptions, overridden: &[policy::Violation]) -> Result<()> {
+    let started = summary::Started::now();
+    if config.apps.is_empty() {
+        bail!("--all-apps needs [[apps]] entries in ops.toml");
+    }
+    let waves = app_waves(&config.apps)?;
+
+    // 1. Nodes of each app
+    o_detail!("   Project: {}", config.project.green());
+    let mut app_targets: Vec<(String, Vec<DeployTarget>)> = Vec::new();
+    for app in &config.apps {
+        let filter = Some(app.name.clone());
+        let mut targets = match resolve_targets(&config, &filter).await {
+            Ok(t) => t,
+            Err(e) if OpsError::is_no_nodes_bound(&e) => auto_allocate_node(&config, &filter, run.interactive).await?,
+            Err(e) => return Err(e),
+        };
+        if let Some(nid) = run.node_filter {
+            targets.retain(|t| t.node_id == nid as i64);
+        }
+        if let Some(ref region) = run.region_filter {
+            targets.retain(|t| t.region.as_deref() == Some(region.as_str()));
+        }
+        let domains: Vec<&str> = targets.iter().map(|t| t.domain.as_str()).collect();
+        o_detail!("   {} → {}", app.name.yellow(), if domains.is_empty() { "(no matching nodes)".to_string() } else { domains.join(", ") });
+        app_targets.push((app.name.clone(), targets));
+    }
+    let mut nodes: Vec<DeployTarget> = Vec::new();
+    for t in app_targets.iter().flat_map(|(_, ts)| ts) {
+        if !nodes.iter().any(|n| n.node_id == t.node_id) {
+            nodes.push(t.clone());
+        }
+    }
+    if nodes.is_empty() {
+        bail!("No nodes match --node/--region for any app");
+    }
+    let targets_of = |name: &str| app_targets.iter().find(|(n, _)| n == name).map(|(_, ts)| ts.as_slice()).unwrap_or_default();
+
+    // 2. Pre-deploy checks on every node
+    for t in &nodes {
+        let session = SshSession::connect(&t.node_id.to_string()).await?;
+        session.exec(&format!("mkdir -p {}", config.deploy_path), None)?;
+        if !run.restart_only {
+            check_containers(&session, &config, &run.env_vars, run.force, run.interactive)?;
+        }
+    }
+
+    // 3. App record
+    let (app_id, deployment_id) = sync_app_record(&config, &run.file).await;
+    if let (false, Some(reason)) = (overridden.is_empty(), &run.override_policy) {
+        let did = deployment_id.context("Cannot log the policy override without a deployment record; not deploying")?;
+        policy::record_override(did, reason, overridden).await
+            .context("Failed to log the policy override; not deploying")?;
+    }
+
+    // 4. Deploy wave by wave
+    let mut rows: Vec<NodeSummary> = Vec::new();
+    let mut failed_apps: Vec<String> = Vec::new();
+    let mut join_failed = false;
+    for (i, wave) in waves.iter().enumerate() {
+        let mut by_node: Vec<(DeployTarget, Vec<String>)> = Vec::new();
+        for app in wave {
+            if let Some(dep) = app.depends_on.iter().find(|d| failed_apps.contains(d)) {
+                o_warn!("   {} {} skipped: {} failed", "⚠".yellow(), app.name, dep);
+                for t in targets_of(&app.name) {
+                    rows.push(NodeSummary {
+                        app: Some(app.name.clone()),
+                        status: "skipped",
+                        error: Some(format!("dependency '{}' failed", dep)),
+                        duration_secs: 0.0,
+                        ..NodeSummary::new(t, &started, &Ok(()), Vec::new())
+                    });
+                }
+                failed_apps.push(app.name.clone());
+                continue;
+            }
+            for t in targets_of(&app.name) {
+                match by_node.iter_mut().find(|(n, _)| n.node_id == t.node_id) {
+                    Some((_, apps)) => apps.push(app.name.clone()),
+                    None => by_node.push((t.clone(), vec![app.name.clone()])),
+                }
+            }
+        }
+        if by_node.is_empty() {
+            continue;
+        }
+        let names: Vec<&str> = wave.iter().map(|a| a.name.as_str()).collect();
+        o_step!("\n{} [{}/{}] Deploying {} on {} node(s)...", "🚀".cyan(), i + 1, waves.len(), names.join(", ").cyan(), by_node.len());
+
+        let mut join_set = tokio::task::JoinSet::new();
+        for (t, apps) in by_node {
+            let config = config.clone();
+            let run = run.clone();
+            join_set.spawn(async move {
+                let mut results = Vec::new();
+                let node_started = summary::Started::now();
+                let session = match SshSession::connect(&t.node_id.to_string()).await {
+                    Ok(s) => s,
+                    Err(e) => {
+                        let msg = format!("{:#}", e);
+                        for app in apps {
+                            results.push((app, NodeSummary::new(&t, &node_started, &Err(anyhow!(msg.clone())), Vec::new())));
+                        }
+                        return results;
+                    }
+                };
+                for app in apps {
+                    let app_started = summary::Started::now();
+                    let filter = Some(app.clone());
+                    let steps = StepOptions { service_filter: &None, app_filter: &filter, ..run.steps() };
+                    let result = execute_deployment(&config, &session, &steps, deployment_id).await;
+                    if let (Ok(_), Some(did)) = (&result, deployment_id) {
+                        record_deployed_state(&config, &session, did, t.node_id).await;
+                    }
+                    let images = if run.summary_file.is_some() && result.is_ok() { summary::running_images(&config, &session) } else { Vec::new() };
+                    results.push((app, NodeSummary::new(&t, &app_started, &result, images)));
+                }
+                results
+            });
+        }
+
+        while let Some(result) = join_set.join_next().await {
+            match result {
+                Ok(results) => {
+                    for (app, mut row) in results {
+                        let region_str = row.region.as_deref().unwrap_or("?");
+                        match &row.error {
+                            None => o_success!("   {} {} @ {} ({})", "✔".green(), app.green(), row.domain, region_str),
+                            Some(e) => {
+                                o_error!("   {} {} @ {} ({}): {}", "✘".red(), app, row.domain, region_str, e);
+                                if !failed_apps.contains(&app) {
+                                    failed_apps.push(app.clone());
+                                }
+                            }
+                        }
+                        row.app = Some(app);
+                        rows.push(row);
+                    }
+                }
+                Err(e) => {
+                    o_error!("   {} join error: {}", "✘".red(), e);
+                    join_failed = true;
+                }
+            }
+        }
+    }
+
+    // 5. Combined app × node table
+    let order = |row: &NodeSummary| config.apps.iter().position(|a| Some(&a.name) == row.app.as_ref());
+    rows.sort_by(|a, b| order(a).cmp(&order(b)).then_with(|| a.domain.cmp(&b.domain)));
+    o_result!("\n{:<16} {:<32} {:<12} {:<8} {}", "APP".bold(), "NODE".bold(), "REGION".bold(), "STATUS".bold(), "TIME".bold());
+    for row in &rows {
+        let status = match row.status {
+            "success" => row.status.green(),
+            "failed" => row.status.red(),
+            _ => row.status.yellow(),
+        };
+        o_result!("{:<16} {:<32} {:<12} {:<8} {:.1}s",
+            row.app.as_deref().unwrap_or("?"), row.domain, row.region.as_deref().unwrap_or("?"), status, row.duration_secs);
+    }
+    let succeeded = rows.iter().filter(|r| r.status == "success").count();
+    let outcome: Result<()> = if failed_apps.is_empty() && !join_failed {
+        o_result!("\n{} Deployed {} app(s) to {} node(s)", "✅".green(), config.apps.len(), nodes.len());
+        Ok(())
+    } else {
+        o_result!("\n{} {}/{} app deployments succeeded (failed: {})",
+            "⚠️".yellow(), succeeded, rows.len(), failed_apps.join(", ").yellow());
+        Err(anyhow!("{} app(s) failed deployment", failed_apps.len().max(1)))
+    };
+    if let Some(did) = deployment_id {
+        update_deployment_status(did, &outcome).await;
+    }
+
+    if let Some(ref path) = run.summary_file {
+        let record = DeploySummary {
+            kind: "deploy",
+            project: config.project.clone(),
+            app: resolve_app_name(&config),
+            status: summary::overall_status(succeeded, rows.len()),
+            release_id: deployment_id,
+            started_at: started.at.clone(),
+            duration_secs: started.elapsed_secs(),
+            nodes: rows,
+            urls: summary::app_urls(&config, &None),
+        };
+        match summary::write(path, &record) {
+            Ok(_) => o_detail!("   Summary written to {}", path),
+            Err(e) => o_warn!("   {} {:#}", "⚠".yellow(), e),
+        }
+    }
+    outcome?;
+
+    if let Err(e) = ProjectState::update(&run.file, |s| {
+        s.last_target = Some(nodes[0].domain.clone());
+        if let Some(id) = app_id {
+            s.app_ids.insert(resolve_app_name(&config), id);
+        }
+    }) {
+        o_warn!("   {} {:#}", "⚠ Could not update .ops/state.json:".yellow(), e);
+    }
+    if run.verify {
+        verify::report(&verify::run(&config, &nodes).await?, false)?;
+    }
     Ok(())
 }
 
@@ -386,7 +941,7 @@ async fn print_deploy_summary(app_name: &str, success_count: usize, failed: &[St
 }
 
 /// 同步 App 记录到后端，返回 (app_id, deployment_id)
-async fn sync_app_record(config: &OpsToml, _target: &str) -> (Option<i64>, Option<i64>) {
+async fn sync_app_record(config: &OpsToml, file: &str) -> (Option<i64>, Option<i64>) {
     // 尝试加载 token
     let cfg = match config::load_config() {
         Ok(c) => c,
@@ -404,9 +959,24 @@ async fn sync_app_record(config: &OpsToml, _target: &str) -> (Option<i64>, Optio
         }
     };
 
+    // Every environment shares the app record, which keeps describing the base ops.toml
+    let base;
+    let record = if config.env.is_some() {
+        base = match load_ops_toml(file) {
+            Ok(c) => c,
+            Err(e) => {
+                o_warn!("   {} {:#} (continuing anyway)", "⚠ Sync failed:".yellow(), e);
+                return (None, None);
+            }
+        };
+        &base
+    } else {
+        config
+    };
+
     // 同步 App
     o_step!("{}", "📝 Syncing app record...".cyan());
-    let sync_result = match api::sync_app(&token, config).await {
+    let sync_result = match api::sync_app(&token, record).await {
         Ok(r) => r,
         Err(e) => {
             o_warn!("   {} {} (continuing anyway)", "⚠ Sync failed:".yellow(), e);
@@ -432,7 +1002,7 @@ async fn sync_app_record(config: &OpsToml, _target: &str) -> (Option<i64>, Optio
 }
 
 /// 更新部署状态
-async fn update_deployment_status(deployment_id: i64, result: &Result<()>) {
+pub async fn update_deployment_status(deployment_id: i64, result: &Result<()>) {
     let cfg = config::load_config().ok();
     let token = cfg.and_then(|c| c.token);
 
@@ -448,30 +1018,164 @@ async fn update_deployment_status(deployment_id: i64, result: &Result<()>) {
     }
 }
 
+/// 记录节点部署后的状态 (compose/env 校验和、镜像、域名)，供 `ops drift` 对比；
+/// 以及 release (镜像 tag、git commit、路由)，供 `ops rollback` 使用
+pub async fn record_deployed_state(config: &OpsToml, session: &SshSession, deployment_id: i64, node_id: i64) {
+    let Some(token) = config::load_config().ok().and_then(|c| c.token) else { return };
+
+    let scope = drift::state_scope(config);
+    let result = match drift::capture_snapshot(session, &scope) {
+        Ok(snapshot) => api::record_deployed_state(&token, deployment_id, node_id, &DeployedState { scope, snapshot }).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        o_warn!("   {} {}", "⚠ Failed to record deployed state:".yellow(), e);
+    }
+
+    let result = match release::capture(config, session, deployment_id) {
+        Ok(r) => api::record_release(&token, deployment_id, node_id, &r).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        o_warn!("   {} {}", "⚠ Failed to record release:".yellow(), e);
+    }
+}
+
 /// 执行实际部署流程
-async fn execute_deployment(
+async fn execute_deployment(config: &OpsToml, session: &SshSession, opts: &StepOptions<'_>, deployment_id: Option<i64>) -> Result<()> {
+    let app = opts.app_filter.clone().unwrap_or_else(|| resolve_app_name(config));
+    let hooks = config.hooks.clone().unwrap_or_default();
+    let held = hold_serve_queue(config, session).await?;
+
+    let result = match deploy_steps(config, session, opts, deployment_id, &app, &hooks).await {
+        Ok(()) => run_hooks(config, session, "post_deploy", &hooks.post_deploy, &app, deployment_id, None),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = &result {
+        // The deploy already failed; a failing on_failure hook is only reported
+        if let Err(hook_err) = run_hooks(config, session, "on_failure", &hooks.on_failure, &app, deployment_id, Some(e)) {
+            o_warn!("   {} on_failure hook failed: {:#}", "⚠".yellow(), hook_err);
+        }
+    }
+    if let Some(id) = held {
+        release_serve_queue(session, id, &result);
+    }
+    result
+}
+
+/// `curl` arguments that POST `body` as JSON
+fn json_post(body: &impl serde::Serialize) -> Result<String> {
+    Ok(format!("-m 5 -X POST -H 'Content-Type: application/json' -d {}", shell_quote(&serde_json::to_string(body)?)))
+}
+
+/// Hold a place in `ops serve`'s queue for this deploy path on the node, and wait until the `/deploy`
+/// runs ahead of it are done; webhooks and CI runs arriving meanwhile wait for this deploy in turn.
+/// None when the node has no serve with a queue.
+async fn hold_serve_queue(config: &OpsToml, session: &SshSession) -> Result<Option<u64>> {
+    let hold = node_state::serve_request(&json_post(&HoldRequest { deploy_path: config.deploy_path.clone() })?, "/deploys");
+    // No serve on the node, or one without a queue: nothing to wait for
+    let Ok(Ok(place)) = session.exec_output(&hold).map(|out| serde_json::from_slice::<HeldPlace>(&out)) else { return Ok(None) };
+    let poll = node_state::serve_request("-m 5 -X POST", &format!("/deploys/{}/start", place.id));
+    let started = std::time::Instant::now();
+    let mut reported = None;
+    loop {
+        let out = session.exec_output(&poll).context("Lost this deploy's place in the ops serve queue")?;
+        let waiting = serde_json::from_slice::<HeldPlace>(&out).context("Unexpected answer from the ops serve queue")?.ahead;
+        if waiting == 0 {
+            if reported.is_some() {
+                o_detail!("   {} ops serve queue is clear", "✔".green());
+            }
+            return Ok(Some(place.id));
+        }
+        if reported != Some(waiting) {
+            o_warn!("   {} {} deploy(s) of {} queued by ops serve on this node; waiting (queue position {})",
+                "⏳".yellow(), waiting, config.deploy_path, waiting + 1);
+            reported = Some(waiting);
+        }
+        if started.elapsed() > SERVE_QUEUE_TIMEOUT {
+            let err = anyhow!("Still {} deploy(s) queued by ops serve after {}s; see GET /deploys on the node",
+                waiting, SERVE_QUEUE_TIMEOUT.as_secs());
+            release_serve_queue(session, place.id, &Err(anyhow!("gave up waiting")));
+            return Err(err);
+        }
+        tokio::time::sleep(SERVE_QUEUE_POLL).await;
+    }
+}
+
+/// Give the place from hold_serve_queue back, with the deploy's outcome for `/deploys`
+fn release_serve_queue(session: &SshSession, id: u64, result: &Result<()>) {
+    let finish = FinishRequest {
+        success: result.is_ok(),
+        message: match result {
+            Ok(()) => "ops deploy finished".into(),
+            Err(e) => format!("ops deploy failed: {:#}", e),
+        },
+    };
+    let released = json_post(&finish)
+        .and_then(|args| session.exec_output(&node_state::serve_request(&args, &format!("/deploys/{}/finish", id))));
+    if let Err(e) = released {
+        o_warn!("   {} Could not release the ops serve queue (it frees itself within {}s): {:#}",
+            "⚠".yellow(), HOLD_LEASE_SECS, e);
+    }
+}
+
+/// `cd deploy_path` and run `command` with the deploy's context in OPS_* variables
+pub fn hook_command(config: &OpsToml, hook: &str, command: &str, app: &str, deployment_id: Option<i64>, error: Option<&anyhow::Error>) -> String {
+    let mut vars = vec![
+        format!("OPS_HOOK={}", hook),
+        format!("OPS_PROJECT={}", shell_quote(&config.project)),
+        format!("OPS_APP={}", shell_quote(app)),
+    ];
+    if let Some(id) = deployment_id {
+        vars.push(format!("OPS_DEPLOYMENT_ID={}", id));
+    }
+    if let Some(e) = error {
+        vars.push(format!("OPS_ERROR={}", shell_quote(&format!("{:#}", e))));
+    }
+    format!("cd {} && export {} && {}", config.deploy_path, vars.join(" "), command)
+}
+
+/// Run one `[hooks]` list in order, stopping at the first command that fails
+fn run_hooks(config: &OpsToml, session: &SshSession, hook: &str, commands: &[String], app: &str, deployment_id: Option<i64>, error: Option<&anyhow::Error>) -> Result<()> {
+    if commands.is_empty() {
+        return Ok(());
+    }
+    o_step!("\n{} {}", "🪝 Running hooks:".cyan(), hook);
+    for command in commands {
+        o_detail!("   → {}", command);
+        session.exec(&hook_command(config, hook, command, app, deployment_id, error), None)
+            .with_context(|| format!("{} hook failed: {}", hook, command))?;
+    }
+    o_success!("   ✔ {}", hook.green());
+    Ok(())
+}
+
+async fn deploy_steps(
     config: &OpsToml,
     session: &SshSession,
-    service_filter: &Option<String>,
-    app_filter: &Option<String>,
-    restart_only: bool,
-    env_vars: &[String],
-    no_pull: bool,
-    init: bool,
+    opts: &StepOptions<'_>,
     deployment_id: Option<i64>,
+    app: &str,
+    hooks: &HooksConfig,
 ) -> Result<()> {
+    let StepOptions { service_filter, app_filter, restart_only, env_vars, no_pull, init } = *opts;
     sync_env_files(config, session)?;
+    // Every app of this deploy, not just the one named for hooks
+    secret_store::inject(config, session, &deployed_apps(config, app_filter)).await?;
     sync_directories(config, session).await?;
 
     if !restart_only {
         sync_code(config, session, app_filter, service_filter, env_vars)?;
+        if config.deploy.source != "image" {
+            disk::preflight_build(config, session)?;
+        }
     }
 
-    let deploy_path = &config.deploy_path;
-    let project = &config.project;
+    run_hooks(config, session, "pre_deploy", &hooks.pre_deploy, app, deployment_id, None)?;
+
     let env = env_prefix(env_vars);
     let compose_arg = {
-        let compose = compose_file_args(config);
+        let compose = compose_base_args(config);
         if compose.is_empty() { String::new() } else { format!(" {}", compose) }
     };
 
@@ -481,13 +1185,12 @@ async fn execute_deployment(
 
     // Start infra (shared, not versioned)
     if !infra_svcs.is_empty() && !restart_only {
-        let infra_list = infra_svcs.join(" ");
         o_step!("\n{}", "🔧 Ensuring infrastructure...".cyan());
-        let cmd = format!(
-            "cd {} && {}docker compose -p {} {} up -d --no-deps {}",
-            deploy_path, env, project, compose_arg.trim(), infra_list
-        );
-        session.exec(&cmd, None)?;
+        session.exec(&infra_up_command(config, &env, &infra_svcs.join(" ")), None)?;
+    }
+    // Before any app container starts, so apps and their health checks don't race databases and caches
+    if !restart_only {
+        wait_for_dependencies(config, session, app_filter)?;
     }
 
     // Deploy each app with deploy-id
@@ -496,6 +1199,18 @@ async fn execute_deployment(
         .filter(|a| app_filter.is_none() || app_filter.as_ref() == Some(&a.name))
         .collect();
 
+    // strategy = "up": replace containers through compose (scale 2 → flip → remove old)
+    if config.deploy.strategy.as_deref() == Some("up") && !restart_only && !apps_with_port.is_empty() {
+        for app in &apps_with_port {
+            deploy_app_scale_up(config, session, app, &env, no_pull)?;
+        }
+        if init {
+            run_init_commands(config, session, env_vars)?;
+        }
+        run_health_checks(config, session)?;
+        return Ok(());
+    }
+
     if let Some(did) = deployment_id {
         if !restart_only && !apps_with_port.is_empty() {
             for app in &apps_with_port {
@@ -507,10 +1222,8 @@ async fn execute_deployment(
                 for step in &config.init {
                     if app_svcs.contains(&step.service) {
                         for command in step.all_commands() {
-                            // Find the new container name
-                            let container = format!("{}-{}-{}", project, step.service, did);
                             o_detail!("   {} → {}", step.service.yellow(), command);
-                            session.exec(&format!("docker exec {} {}", container, command), None)?;
+                            session.exec(&container_init_command(config, &step.service, &did.to_string(), command), None)?;
                         }
                     }
                 }
@@ -546,118 +1259,211 @@ fn deploy_app_zero_downtime(
     compose_arg: &str,
     no_pull: bool,
 ) -> Result<()> {
-    let project = &config.project;
-    let deploy_path = &config.deploy_path;
-    let port = app.port.unwrap();
-    let active_file = format!("{}/.ops-active-deployment", deploy_path);
-    let svc_list: String = app.services.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" ");
-
     // 1. Build image
     o_step!("\n{}", "🔨 Building images...".cyan());
-    let pull_arg = if no_pull { "" } else { " --pull" };
-    let build_cmd = format!(
-        "cd {} && {}docker compose -p {} {} build{} {}",
-        deploy_path, env, project, compose_arg.trim(), pull_arg, svc_list
-    );
-    session.exec(&build_cmd, None)?;
+    session.exec(&image_build_command(config, env, &app.services.join(" "), no_pull), None)?;
 
     for svc in &app.services {
-        let image = format!("{}-{}:latest", project, svc);
-        let new_name = format!("{}-{}-{}", project, svc, deployment_id);
+        start_service_container(config, session, deployment_id, app, svc, compose_arg)?;
+    }
 
-        // 2. Detect network
-        let network = detect_network(session, deploy_path, project)?;
+    // 9. Write active deployment
+    session.exec(&active_deployment_command(config, &deployment_id.to_string()), None)?;
+    o_detail!("   Active deployment: {}", deployment_id.to_string().green());
 
-        // 3. Generate env file from compose config
-        let env_file = format!("{}/.ops-env-{}", deploy_path, svc);
-        let gen_env_cmd = format!(
-            "cd {} && docker compose config --format json 2>/dev/null | python3 -c 'import sys,json; svc=json.load(sys.stdin)[\"services\"].get(\"{}\",{{}}); [print(str(k)+\"=\"+str(v)) for k,v in svc.get(\"environment\",{{}}).items()]' > {} 2>/dev/null; cat {}",
-            deploy_path, svc, env_file, env_file
-        );
-        let env_out = session.exec_output(&gen_env_cmd).unwrap_or_default();
-        let env_content = String::from_utf8_lossy(&env_out).trim().to_string();
-        o_debug!("   env: {} lines", env_content.lines().count());
-
-        // 4. Start new container
-        o_step!("\n{}", format!("🚀 Starting {}", new_name).cyan());
-        let volumes = format!("{}/public:/app/public", deploy_path);
-        let run_cmd = format!(
-            "docker run -d --name {} --network {} --env-file {} -v {} {}",
-            new_name, network, env_file, volumes, image
-        );
-        session.exec(&run_cmd, None)?;
+    // 10. Prune
+    session.exec(IMAGE_PRUNE, None)?;
 
-        // 5. Resolve IP
-        let ip = resolve_container_ip(session, &new_name)?;
-        o_detail!("   {} → {}:{}", new_name.cyan(), ip, port);
+    Ok(())
+}
 
-        // 6. Health check
-        o_step!("\n{}", "💚 Health check...".cyan());
-        let hc = config.healthchecks.iter().find(|h| h.name == app.name);
-        let health_path = hc
-            .map(|h| {
-                // Extract path from URL: "https://example.com/api/v1/health" -> "/api/v1/health"
-                h.url.splitn(4, '/').nth(3).map(|p| format!("/{}", p)).unwrap_or_else(|| "/status".into())
-            })
-            .unwrap_or_else(|| "/status".into());
-        let retries = hc.map(|h| h.retries).unwrap_or(10);
-        let interval = hc.map(|h| h.interval).unwrap_or(2);
-        let initial_delay = hc.map(|h| h.initial_delay).unwrap_or(0);
-        let health_url = format!("http://{}:{}{}", ip, port, health_path);
-        o_detail!("   url: {}  retries: {}  interval: {}s  delay: {}s", health_url, retries, interval, initial_delay);
-        let delay_cmd = if initial_delay > 0 { format!("sleep {}; ", initial_delay) } else { String::new() };
-        let seq = (1..=retries).map(|i| i.to_string()).collect::<Vec<_>>().join(" ");
-        let health_cmd = format!(
-            "{}for i in {}; do curl -sf {} > /dev/null && echo 'OK' && exit 0; sleep {}; done; echo 'FAIL'; exit 1",
-            delay_cmd, seq, health_url, interval
-        );
-        if let Err(_) = session.exec(&health_cmd, None) {
-            o_warn!("   {} Health check failed, rolling back", "✘".red());
-            session.exec(&format!("docker rm -f {}", new_name), None)?;
-            return Err(anyhow::anyhow!("Health check failed for {}", new_name));
-        }
-        o_success!("   {} Healthy", "✔".green());
+/// Run `<project>-<svc>:latest` as `<project>-<svc>-<deployment_id>`, health-check it, point the app's
+/// routes at it and remove the service's older containers
+pub fn start_service_container(
+    config: &OpsToml,
+    session: &SshSession,
+    deployment_id: i64,
+    app: &AppDef,
+    svc: &str,
+    compose_arg: &str,
+) -> Result<()> {
+    let project = config.compose_project();
+    let deploy_path = &config.deploy_path;
+    let port = app.port.context("App has no port")?;
 
-        // 7. Switch Caddy routes
-        o_step!("\n{}", "⚙️  Switching routes...".cyan());
-        upload_caddy_routes_for_app(session, config, app, &ip, port)?;
+    let new_name = format!("{}-{}-{}", project, svc, deployment_id);
 
-        // 8. Stop ALL old containers for this service (not just the previous one)
-        let current_name = format!("{}-{}-{}", project, svc, deployment_id);
-        let cleanup_cmd = format!(
-            "docker ps -a --filter 'name={}-{}-' --format '{{{{.Names}}}}' | grep -v '{}' | xargs -r docker rm -f 2>/dev/null; true",
-            project, svc, current_name
-        );
-        let removed = session.exec_output(&cleanup_cmd)
-            .map(|o| String::from_utf8_lossy(&o).trim().to_string())
-            .unwrap_or_default();
-        if !removed.is_empty() {
-            for name in removed.lines() {
-                if !name.is_empty() {
-                    o_step!("{}", format!("🛑 Stopping old {}", name).cyan());
-                }
+    // 2. Detect network
+    let network = detect_network(session, deploy_path, project, compose_arg)?;
+
+    // 3. Generate env file from compose config
+    let env_file = format!("{}/.ops-env-{}", deploy_path, svc);
+    let gen_env_cmd = format!(
+        "cd {} && docker compose -p {} {} config --format json 2>/dev/null | python3 -c 'import sys,json; svc=json.load(sys.stdin)[\"services\"].get(\"{}\",{{}}); [print(str(k)+\"=\"+str(v)) for k,v in svc.get(\"environment\",{{}}).items()]' > {} 2>/dev/null; cat {}",
+        deploy_path, project, compose_arg.trim(), svc, env_file, env_file
+    );
+    let env_out = session.exec_output(&gen_env_cmd).unwrap_or_default();
+    let env_content = String::from_utf8_lossy(&env_out).trim().to_string();
+    o_debug!("   env: {} lines", env_content.lines().count());
+
+    // 4. Start new container
+    o_step!("\n{}", format!("🚀 Starting {}", new_name).cyan());
+    session.exec(&container_run_command(config, svc, &deployment_id.to_string(), &network), None)?;
+
+    // 5. Resolve IP
+    let ip = resolve_container_ip(session, &new_name)?;
+    o_detail!("   {} → {}:{}", new_name.cyan(), ip, port);
+
+    // 6. Health check
+    o_step!("\n{}", "💚 Health check...".cyan());
+    let (check, health_url) = container_health_check(config, app, &ip, port);
+    o_detail!("   url: {}  retries: {}  interval: {}s  delay: {}s", health_url, check.retries, check.interval, check.initial_delay);
+    if !matches!(run_health_check(&check, &health_url, session), Ok((true, _))) {
+        o_warn!("   {} Health check failed, rolling back", "✘".red());
+        session.exec(&format!("docker rm -f {}", new_name), None)?;
+        return Err(anyhow::anyhow!("Health check failed for {}", new_name));
+    }
+    o_success!("   {} Healthy", "✔".green());
+
+    // 7. Switch Caddy routes
+    o_step!("\n{}", "⚙️  Switching routes...".cyan());
+    upload_caddy_routes_for_app(session, config, app, &format!("{}:{}", ip, port))?;
+
+    // 8. Stop ALL old containers for this service (not just the previous one)
+    let current_name = format!("{}-{}-{}", project, svc, deployment_id);
+    let cleanup_cmd = format!(
+        "docker ps -a --filter 'name={}-{}-' --format '{{{{.Names}}}}' | grep -v '{}' | xargs -r docker rm -f 2>/dev/null; true",
+        project, svc, current_name
+    );
+    let removed = session.exec_output(&cleanup_cmd)
+        .map(|o| String::from_utf8_lossy(&o).trim().to_string())
+        .unwrap_or_default();
+    if !removed.is_empty() {
+        for name in removed.lines() {
+            if !name.is_empty() {
+                o_step!("{}", format!("🛑 Stopping old {}", name).cyan());
             }
         }
+    }
+
+    // Also clean up any legacy blue-green containers
+    let _ = session.exec(&format!("rm -f {}/.ops-slot", deploy_path), None);
+    Ok(())
+}
+
+/// Health check for a new app container, reached directly by IP: the app's [[healthchecks]] path
+/// (default /status) on `ip:port`. New containers are only reachable inside the node, so it always runs there.
+pub fn container_health_check(config: &OpsToml, app: &AppDef, ip: &str, port: u16) -> (HealthCheck, String) {
+    let hc = config.healthchecks.iter().find(|h| h.name == app.name);
+    let health_path = hc
+        .map(|h| {
+            // Extract path from URL: "https://example.com/api/v1/health" -> "/api/v1/health"
+            h.url.splitn(4, '/').nth(3).map(|p| format!("/{}", p)).unwrap_or_else(|| "/status".into())
+        })
+        .unwrap_or_else(|| "/status".into());
+    let health_url = format!("http://{}:{}{}", ip, port, health_path);
+    let mut check = hc.cloned().unwrap_or_else(|| HealthCheck {
+        name: app.name.clone(),
+        url: health_url.clone(),
+        retries: 10,
+        interval: 2,
+        initial_delay: 0,
+        timeout: 5,
+        expected_status: None,
+        from: "node".into(),
+        required: true,
+    });
+    check.from = "node".into();
+    (check, health_url)
+}
+
+/// `strategy = "up"`: replace each service of an app through compose itself. New containers are
+/// started next to the old ones (as many as were running), health-checked, routes are flipped to them,
+/// then the old ones are removed. Several replicas share one route, balanced (and made sticky) by Caddy.
+/// Unlike the deploy-id path, the container keeps its full compose definition (volumes, env, networks).
+fn deploy_app_scale_up(
+    config: &OpsToml,
+    session: &SshSession,
+    app: &AppDef,
+    env: &str,
+    no_pull: bool,
+) -> Result<()> {
+    app.port.context("strategy = \"up\" requires a port on the app")?;
 
-        // Also clean up any legacy blue-green containers
-        let _ = session.exec(&format!("rm -f {}/.ops-slot", deploy_path), None);
+    // 1. Build or pull the new image
+    let (cmd, action) = scale_up_image_command(config, env, app, no_pull);
+    if action == "pull" {
+        o_step!("\n{}", "🐳 Pulling images...".cyan());
+    } else {
+        o_step!("\n{}", "🔨 Building images...".cyan());
     }
+    session.exec(&cmd, None)?;
 
-    // 9. Write active deployment
-    session.exec(&format!("echo {} > {}", deployment_id, active_file), None)?;
-    o_detail!("   Active deployment: {}", deployment_id.to_string().green());
+    replace_app_containers(config, session, app, &compose_command(config, env, &compose_args(config)))?;
 
-    // 10. Prune
-    session.exec("docker image prune -f", None)?;
+    if action == "pull" {
+        session.exec(IMAGE_PRUNE, None).ok();
+    }
+    Ok(())
+}
+
+/// Replace each service's containers with new ones from the current image and compose config:
+/// start them next to the old ones, health check them, switch routes, then remove the old ones.
+/// `compose` is `cd <deploy_path> && [env] docker compose <args>`.
+pub fn replace_app_containers(config: &OpsToml, session: &SshSession, app: &AppDef, compose: &str) -> Result<()> {
+    let port = app.port.context("Replacing containers requires a port on the app")?;
+    for svc in &app.services {
+        let ids = |session: &SshSession| -> Vec<String> {
+            session.exec_output(&format!("{} ps -q {}", compose, svc))
+                .map(|o| String::from_utf8_lossy(&o).split_whitespace().map(String::from).collect())
+                .unwrap_or_default()
+        };
+        let old = ids(session);
+
+        // 2. Start as many new containers as are running, alongside the old ones
+        let replicas = old.len().max(1);
+        o_step!("\n{}", format!("🚀 Starting {} new {} next to {} running", replicas, svc, old.len()).cyan());
+        session.exec(&format!("{} up -d --no-deps --no-recreate --scale {}={} {}", compose, svc, old.len() + replicas, svc), None)?;
+        let new: Vec<String> = ids(session).into_iter().filter(|id| !old.contains(id)).collect();
+        if new.is_empty() {
+            bail!("No new container started for {} (does it publish a fixed host port?)", svc);
+        }
 
+        // 3. Health check each new container directly
+        o_step!("\n{}", "💚 Health check...".cyan());
+        let mut upstreams = Vec::new();
+        for new_id in &new {
+            let ip = resolve_container_ip(session, new_id)?;
+            o_detail!("   {} → {}:{}", new_id.chars().take(12).collect::<String>().cyan(), ip, port);
+            let (check, health_url) = container_health_check(config, app, &ip, port);
+            o_detail!("   url: {}  retries: {}  interval: {}s  delay: {}s", health_url, check.retries, check.interval, check.initial_delay);
+            if !matches!(run_health_check(&check, &health_url, session), Ok((true, _))) {
+                o_warn!("   {} Health check failed, removing new containers (old ones keep serving)", "✘".red());
+                session.exec(&format!("docker rm -f {}", new.join(" ")), None)?;
+                bail!("Health check failed for {}", svc);
+            }
+            upstreams.push(format!("{}:{}", ip, port));
+        }
+        o_success!("   {} Healthy", "✔".green());
+
+        // 4. Flip routes to the new containers
+        o_step!("\n{}", "⚙️  Switching routes...".cyan());
+        upload_caddy_routes_for_app(session, config, app, &upstreams.join(" "))?;
+
+        // 5. Remove the old container(s)
+        if !old.is_empty() {
+            o_step!("{}", format!("🛑 Removing old {}", svc).cyan());
+            session.exec(&format!("docker stop {0} >/dev/null && docker rm {0} >/dev/null", old.join(" ")), None)?;
+        }
+    }
     Ok(())
 }
 
-fn detect_network(session: &SshSession, deploy_path: &str, project: &str) -> Result<String> {
+fn detect_network(session: &SshSession, deploy_path: &str, project: &str, compose_arg: &str) -> Result<String> {
     // 1. Ask docker compose for the actual network name
     let compose_net = session.exec_output(&format!(
-        "cd {} && docker compose config --format json 2>/dev/null | python3 -c \"import sys,json; nets=json.load(sys.stdin).get('networks',{{}}); print(next(iter(nets.values()),{{}}).get('name',''))\" 2>/dev/null",
-        deploy_path
+        "cd {} && docker compose -p {} {} config --format json 2>/dev/null | python3 -c \"import sys,json; nets=json.load(sys.stdin).get('networks',{{}}); print(next(iter(nets.values()),{{}}).get('name',''))\" 2>/dev/null",
+        deploy_path, project, compose_arg.trim()
     ));
     if let Ok(out) = &compose_net {
         let net = String::from_utf8_lossy(out).trim().to_string();
@@ -736,44 +1542,68 @@ fn build_container_env_file(session: &SshSession, deploy_path: &str, env: &str,
     Ok(env_file)
 }
 
-fn upload_caddy_routes_for_app(session: &SshSession, config: &OpsToml, app: &AppDef, ip: &str, port: u16) -> Result<()> {
-    let project = &config.project;
-    let target = format!("{}.{}", app.name, project);
-    let conf_name = format!("ops-{}-{}", app.name, project);
+/// A route fragment deploy writes to the node
+pub struct RenderedRoute {
+    pub path: String,
+    pub content: String,
+    /// Matched target or domain, for output
+    pub label: String,
+    pub upstream: String,
+}
 
-    let mut caddy_content = String::new();
+/// Fragments for one app in a zero-downtime deploy: the app target plus one per [[routes]] domain
+pub fn app_routes(router: Router, config: &OpsToml, app: &AppDef, upstream: &str) -> Vec<RenderedRoute> {
+    let project = &config.project;
+    let stem = format!("ops-{}-{}", app.name, project);
 
     // App target route
-    let matcher = conf_name.replace('-', "_");
-    caddy_content.push_str(&format!(
-        "# {}\n@{} header X-OPS-Target {}\nhandle @{} {{\n    reverse_proxy {}:{}\n}}\n",
-        target, matcher, target, matcher, ip, port
-    ));
+    let target = format!("{}.{}", app.name, project);
+    let opts = ProxyOptions::for_app(app);
+    let (path, content) = router.render(&stem, &app.name, project, &RouteMatch::Target(target.clone()), upstream, &opts);
+    let mut routes = vec![RenderedRoute { path, content, label: target, upstream: upstream.to_string() }];
 
     // Domain routes
     for route in &config.routes {
-        let domain = &route.domain;
-        let route_matcher = domain.replace('.', "_").replace('-', "_");
-        caddy_content.push_str(&format!(
-            "\n# {}\n@{} host {}\nhandle @{} {{\n    reverse_proxy {}:{}\n}}\n",
-            domain, route_matcher, domain, route_matcher, ip, port
-        ));
-        o_detail!("   ✔ {} → {}:{}", domain.cyan(), ip, port);
+        let safe_domain = route.domain.replace(['.', '-'], "_");
+        let (path, content) = router.render(
+            &format!("{}-host-{}", stem, safe_domain),
+            &app.name, project,
+            &RouteMatch::Host(route.domain.clone()),
+            upstream,
+            &opts.clone().with_protocol(route.protocol.or(app.protocol)),
+        );
+        routes.push(RenderedRoute { path, content, label: route.domain.clone(), upstream: upstream.to_string() });
     }
+    routes
+}
 
-    // Write and reload
-    let caddy_path = format!("/etc/caddy/routes.d/{}.caddy", conf_name);
-    session.exec(
-        &format!("mkdir -p /etc/caddy/routes.d && cat > {}", caddy_path),
-        Some(&caddy_content),
-    )?;
+/// Warn about [[apps]] route options the configured router doesn't render, rather than drop them silently
+fn warn_ignored_route_options(config: &OpsToml, app_filter: &Option<String>) -> Result<()> {
+    let router = Router::from_config(config.deploy.router.as_deref())?;
+    for app in config.apps.iter().filter(|a| app_filter.as_ref().is_none_or(|f| *f == a.name)) {
+        let ignored = router.ignored_options(&ProxyOptions::for_app(app));
+        if !ignored.is_empty() {
+            o_warn!("   {} {} ignores {} of app '{}'", "⚠".yellow(), router.name(), ignored.join(", "), app.name);
+        }
+    }
+    Ok(())
+}
 
-    let validate = session.exec("caddy validate --config /etc/caddy/Caddyfile", None);
-    if validate.is_ok() {
-        session.exec("systemctl reload caddy", None)?;
-        o_success!("   ✔ Caddy reloaded");
+/// `upstream` is one or more space-separated `ip:port` addresses
+fn upload_caddy_routes_for_app(session: &SshSession, config: &OpsToml, app: &AppDef, upstream: &str) -> Result<()> {
+    let router = Router::from_config(config.deploy.router.as_deref())?;
+    router.ensure_base(session)?;
+
+    for route in app_routes(router, config, app, upstream) {
+        router.write(session, &route.path, &route.content)?;
+        o_detail!("   ✔ {} → {}", route.label.cyan(), route.upstream);
+    }
+
+    // Validate and reload
+    if router.reload(session).is_ok() {
+        o_success!("   ✔ {} reloaded", router.name());
     } else {
-        o_warn!("   {} Caddy validation failed", "⚠".yellow());
+        o_warn!("   {} {} validation failed", "⚠".yellow(), router.name());
     }
 
     Ok(())
@@ -821,7 +1651,7 @@ chmod 600 ~/.ssh/config"#,
     Ok(())
 }
 
-fn sync_code(
+pub fn sync_code(
     config: &OpsToml,
     session: &SshSession,
     app_filter: &Option<String>,
@@ -849,8 +1679,7 @@ fn sync_code(
             let output_str = String::from_utf8_lossy(&output).trim().to_string();
 
             if output_str == "exists" {
-                let cmd = format!("cd {} && git pull origin {}", deploy_path, branch);
-                session.exec(&cmd, None)?;
+                session.exec(&git_pull_command(config, branch), None)?;
             } else {
                 // 初次 clone — 先配置 deploy key
                 if let Some(key_path) = &git.ssh_key {
@@ -858,17 +1687,13 @@ fn sync_code(
                     let project_name = resolve_app_name(config);
                     setup_deploy_key(session, &expanded, &project_name)?;
                 }
-                let cmd = format!(
-                    "GIT_SSH_COMMAND='ssh -o StrictHostKeyChecking=no' git clone -b {} {} {}",
-                    branch, git.repo, deploy_path
-                );
-                session.exec(&cmd, None)?;
+                session.exec(&git_clone_command(config, &git.repo, branch), None)?;
             }
             o_success!("   {}", "✔ Code synced.".green());
         }
         "push" => {
             o_step!("\n{}", "📤 Syncing code (rsync)...".cyan());
-            session.rsync_push(&deploy_path, &config.deploy.include)?;
+            session.rsync_push(&deploy_path, &config.deploy.include, config.deploy.gitignore)?;
             o_success!("   {}", "✔ Code synced.".green());
         }
         "image" => {
@@ -876,29 +1701,80 @@ fn sync_code(
 
             // 1. Docker login
             if let Some(reg) = &config.deploy.registry {
-                let user = resolve_env_value(&reg.username)?;
-                let token = resolve_env_value(&reg.token)?;
-                session.exec(
-                    &format!("echo '{}' | docker login {} -u {} --password-stdin", token, reg.url, user),
-                    None,
-                )?;
+                Credentials::resolve(&reg.url, &reg.username, &reg.token, reg.helper.as_deref(), reg.region.as_deref())?
+                    .docker_login(session, &reg.url)?;
                 o_success!("   {}", "✔ Registry login".green());
             }
 
             // 2. Pull
-            let compose = compose_file_args(config);
+            let compose = compose_args(config);
             let env = env_prefix(env_vars);
             let svcs = resolve_services(config, app_filter, service_filter);
-            let cmd = format!("cd {} && {}docker compose {} pull {}", deploy_path, env, compose, svcs);
-            session.exec(&cmd, None)?;
+            disk::preflight_pull(config, session, &env, &compose, &svcs)?;
+            session.exec(&image_pull_command(config, &env, &svcs), None)?;
             o_success!("   {}", "✔ Images pulled".green());
         }
         other => return Err(anyhow::anyhow!("Unknown deploy source: {}", other)),
     }
+    if config.deploy.source != "image" {
+        ensure_compose_file(config, session)?;
+    }
     Ok(())
 }
 
-fn sync_env_files(config: &OpsToml, session: &SshSession) -> Result<()> {
+/// Files docker compose would read: the single configured file, or its default names.
+/// Several configured files are left to the user.
+fn compose_file_candidates(config: &OpsToml) -> Vec<&str> {
+    match config.deploy.compose_files.as_deref() {
+        None | Some([]) => drift::DEFAULT_COMPOSE_FILES.to_vec(),
+        Some([file]) => vec![file.as_str()],
+        Some(_) => Vec::new(),
+    }
+}
+
+/// What a zero-config deploy writes next to the synced code, as (file, content): the compose file,
+/// plus a Dockerfile and .dockerignore unless `existing` already has them
+fn generated_files(config: &OpsToml, info: &scanner::SourceInfo, target: &str, existing: &[&str]) -> Vec<(String, String)> {
+    let mut files = vec![(target.to_string(), scanner::dockerfile::render_compose(&config.project, info))];
+    if !existing.contains(&"Dockerfile") {
+        files.push(("Dockerfile".to_string(), scanner::dockerfile::render_dockerfile(info)));
+    }
+    if !existing.contains(&".dockerignore") {
+        files.push((".dockerignore".to_string(), scanner::dockerfile::render_dockerignore(info)));
+    }
+    files
+}
+
+/// Zero-config deploys: when the synced code has no compose file, write the ones `ops launch`
+/// would generate from a scan of the project next to ops.toml
+fn ensure_compose_file(config: &OpsToml, session: &SshSession) -> Result<()> {
+    let candidates = compose_file_candidates(config);
+    let Some(target) = candidates.iter().find(|f| f.starts_with("docker-compose")).or(candidates.first()) else {
+        return Ok(());
+    };
+    let deploy_path = &config.deploy_path;
+    let check = format!(
+        "cd {} && for f in {} Dockerfile .dockerignore; do [ -f \"$f\" ] && echo \"$f\"; done; true",
+        deploy_path, candidates.join(" ")
+    );
+    let existing = String::from_utf8_lossy(&session.exec_output(&check)?).to_string();
+    let existing: Vec<&str> = existing.lines().map(str::trim).collect();
+    if candidates.iter().any(|f| existing.contains(f)) {
+        return Ok(());
+    }
+
+    let info = scanner::scan(config.project_dir())?.with_context(|| format!(
+        "No compose file in {} and the project type could not be detected. Add {} or run `ops launch`", deploy_path, target
+    ))?;
+    o_step!("\n{} ({})", "🧩 No compose file, generating one...".cyan(), info.framework.display_name());
+    for (file, content) in generated_files(config, &info, target, &existing) {
+        session.exec(&format!("cat > {}/{}", deploy_path, file), Some(&content))?;
+        o_success!("   {} {}", "✔".green(), file);
+    }
+    Ok(())
+}
+
+pub fn sync_env_files(config: &OpsToml, session: &SshSession) -> Result<()> {
     if config.env_files.is_empty() {
         return Ok(());
     }
@@ -926,40 +1802,20 @@ fn sync_env_files(config: &OpsToml, session: &SshSession) -> Result<()> {
     Ok(())
 }
 
-async fn sync_directories(config: &OpsToml, session: &SshSession) -> Result<()> {
-    if config.sync.is_empty() {
-        return Ok(());
-    }
-
-    let deploy_path = &config.deploy_path;
-    let target = session.target();
-    let mut printed_header = false;
-
-    for s in &config.sync {
-        if Path::new(&s.local).exists() {
-            if !printed_header {
-                o_step!("\n{}", "📤 Syncing directories...".cyan());
-                printed_header = true;
-            }
-            let remote = format!("{}:{}/{}", target, deploy_path, s.remote);
-            o_detail!("   {} → {}", s.local.cyan(), remote);
-            // Ensure parent directory exists on remote
-            session.exec(&format!("mkdir -p {}/{}", deploy_path, s.remote), None)?;
-            scp::handle_push(s.local.clone(), remote).await?;
-        }
-    }
-    Ok(())
+/// `[[sync]]` entries, see `dir_sync`
+pub async fn sync_directories(config: &OpsToml, session: &SshSession) -> Result<()> {
+    dir_sync::sync_all(config, session)
 }
 
-/// Upload Caddy route fragments for each app
-fn upload_caddy_routes(config: &OpsToml, session: &SshSession, app_filter: &Option<String>) -> Result<()> {
+/// Fragments for a non-zero-downtime deploy: [[routes]] (legacy) and [[apps]] with a port, upstream on 127.0.0.1
+pub fn fallback_routes(router: Router, config: &OpsToml, app_filter: &Option<String>) -> Vec<RenderedRoute> {
     let project_name = &config.project;
-
-    // Ensure routes directory exists
-    session.exec("mkdir -p /etc/caddy/routes.d", None)?;
-
-    // Collect app → port mappings from [[routes]] (legacy) and [[apps]]
-    let mut routes_written = false;
+    let mut routes = Vec::new();
+    let mut push = |stem: String, owner: &str, m: RouteMatch, label: String, port: u16, opts: ProxyOptions| {
+        let upstream = format!("127.0.0.1:{}", port);
+        let (path, content) = router.render(&stem, owner, project_name, &m, &upstream, &opts);
+        routes.push(RenderedRoute { path, content, label, upstream });
+    };
 
     // Handle legacy [[routes]]
     if !config.routes.is_empty() {
@@ -967,8 +1823,6 @@ fn upload_caddy_routes(config: &OpsToml, session: &SshSession, app_filter: &Opti
             .cloned()
             .unwrap_or_else(|| resolve_app_name(config));
 
-        o_step!("\n{}", "⚙️  Generating Caddy routes...".cyan());
-
         // Group routes by port to determine if we need domain-based matching
         let first_port = config.routes[0].port;
         let all_same_port = config.routes.iter().all(|r| r.port == first_port);
@@ -976,82 +1830,63 @@ fn upload_caddy_routes(config: &OpsToml, session: &SshSession, app_filter: &Opti
         if all_same_port {
             // All routes share the same port — use X-OPS-Target matcher
             let target = format!("{}.{}", deployed_app, project_name);
-            let matcher_name = format!("ops_{}_{}", deployed_app, project_name).replace('-', "_");
-            let caddy_snippet = format!(
-                "# {target}\n@{matcher} header X-OPS-Target {target}\nhandle @{matcher} {{\n    reverse_proxy 127.0.0.1:{port}\n}}\n",
-                target = target,
-                matcher = matcher_name,
-                port = first_port,
-            );
-            let conf_name = format!("ops-{}-{}.caddy", deployed_app, project_name);
-            session.exec(
-                &format!("cat > /etc/caddy/routes.d/{}", conf_name),
-                Some(&caddy_snippet),
-            )?;
-            o_detail!("   ✔ {} → :{}", target.green(), first_port);
+            let opts = route_options(config, &config.routes[0]);
+            let protocol = config.routes.iter().fold(opts.protocol, |p, r| p.or(r.protocol));
+            push(format!("ops-{}-{}", deployed_app, project_name), &deployed_app, RouteMatch::Target(target.clone()), target, first_port, opts.with_protocol(protocol));
         } else {
             // Different ports per route — use X-Forwarded-Host for domain-based matching
             for route in &config.routes {
-                let safe_domain = route.domain.replace('.', "_").replace('-', "_");
-                let matcher_name = format!("ops_route_{}", safe_domain);
-                let caddy_snippet = format!(
-                    "# {domain}\n@{matcher} header X-Forwarded-Host {domain}\nhandle @{matcher} {{\n    reverse_proxy 127.0.0.1:{port}\n}}\n",
-                    domain = route.domain,
-                    matcher = matcher_name,
-                    port = route.port,
-                );
-                let conf_name = format!("ops-route-{}.caddy", safe_domain);
-                session.exec(
-                    &format!("cat > /etc/caddy/routes.d/{}", conf_name),
-                    Some(&caddy_snippet),
-                )?;
-                o_detail!("   ✔ {} → :{}", route.domain.green(), route.port);
+                let safe_domain = route.domain.replace(['.', '-'], "_");
+                push(format!("ops-route-{}", safe_domain), &deployed_app, RouteMatch::ForwardedHost(route.domain.clone()), route.domain.clone(), route.port, route_options(config, route));
             }
         }
-        routes_written = true;
     }
 
     // Handle [[apps]] with port (skip if [[routes]] already covered them)
     let route_ports: std::collections::HashSet<u16> = config.routes.iter().map(|r| r.port).collect();
-    let apps_to_process: Vec<_> = if let Some(ref filter) = app_filter {
-        config.apps.iter().filter(|a| a.name == *filter).collect()
-    } else {
-        config.apps.iter().collect()
-    };
-    let apps_with_port: Vec<_> = apps_to_process.iter()
-        .filter(|a| a.port.is_some() && !route_ports.contains(&a.port.unwrap()))
-        .collect();
-
-    if !apps_with_port.is_empty() {
-        if !routes_written {
-            o_step!("\n{}", "⚙️  Generating Caddy routes...".cyan());
+    for app in &config.apps {
+        if app_filter.as_ref().is_some_and(|f| *f != app.name) {
+            continue;
         }
+        let Some(port) = app.port.filter(|p| !route_ports.contains(p)) else { continue };
+        let target = format!("{}.{}", app.name, project_name);
+        push(format!("ops-{}-{}", app.name, project_name), &app.name, RouteMatch::Target(target.clone()), target, port, ProxyOptions::for_app(app));
+    }
 
-        for app in &apps_with_port {
-            let port = app.port.unwrap();
-            let target = format!("{}.{}", app.name, project_name);
-            let matcher_name = format!("ops_{}_{}", app.name, project_name).replace('-', "_");
-            let caddy_snippet = format!(
-                "# {target}\n@{matcher} header X-OPS-Target {target}\nhandle @{matcher} {{\n    reverse_proxy 127.0.0.1:{port}\n}}\n",
-                target = target,
-                matcher = matcher_name,
-                port = port,
-            );
-            let conf_name = format!("ops-{}-{}.caddy", app.name, project_name);
-            session.exec(
-                &format!("cat > /etc/caddy/routes.d/{}", conf_name),
-                Some(&caddy_snippet),
-            )?;
-            o_detail!("   ✔ {} → :{}", target.green(), port);
-        }
-        routes_written = true;
+    routes
+}
+
+/// Proxy options of a [[routes]] entry: those of the app on its port, with the route's own protocol
+fn route_options(config: &OpsToml, route: &RouteDef) -> ProxyOptions {
+    let opts = config.apps.iter()
+        .find(|a| a.port == Some(route.port))
+        .map(ProxyOptions::for_app)
+        .unwrap_or_default();
+    let protocol = route.protocol.or(opts.protocol);
+    opts.with_protocol(protocol)
+}
+
+/// Upload route fragments for each app (Caddy, or nginx with `[deploy] router = "nginx"`)
+fn upload_caddy_routes(config: &OpsToml, session: &SshSession, app_filter: &Option<String>) -> Result<()> {
+    let router = Router::from_config(config.deploy.router.as_deref())?;
+
+    // Ensure routes directory / base config exists
+    router.ensure_base(session)?;
+
+    let routes = fallback_routes(router, config, app_filter);
+    if routes.is_empty() {
+        return Ok(());
     }
 
-    if routes_written {
-        // Validate & reload Caddy
-        session.exec("caddy validate --config /etc/caddy/Caddyfile && systemctl reload caddy", None)?;
+    o_step!("\n{}", format!("⚙️  Generating {} routes...", router.name()).cyan());
+    for route in &routes {
+        router.write(session, &route.path, &route.content)?;
+        o_detail!("   ✔ {} → {}", route.label.green(), route.upstream);
     }
 
+    // Validate & reload
+    router.reload(session)?;
+
     Ok(())
 }
 
@@ -1064,7 +1899,7 @@ fn check_containers(
     interactive: bool,
 ) -> Result<()> {
     let deploy_path = &config.deploy_path;
-    let compose = compose_file_args(config);
+    let compose = compose_args(config);
     let env = env_prefix(env_vars);
     let compose_arg = if compose.is_empty() { String::new() } else { format!(" {}", compose) };
 
@@ -1116,6 +1951,10 @@ fn check_containers(
     for line in ps_str.lines() {
         o_detail!("   {}", line);
     }
+    let unhealthy = unhealthy_containers(session, config.compose_project());
+    if !unhealthy.is_empty() {
+        o_warn!("   {} Docker reports unhealthy: {}", "⚠".yellow(), unhealthy.join(", "));
+    }
 
     // 3. --force 自动 clean
     if force {
@@ -1153,6 +1992,21 @@ fn check_containers(
     }
 }
 
+/// This compose project's containers whose `healthcheck:` is failing. Containers without one have
+/// no Health field and never show up here, whatever their State.
+fn unhealthy_containers(session: &SshSession, project: &str) -> Vec<String> {
+    let cmd = format!(
+        "docker ps -q --filter label=com.docker.compose.project={} | xargs -r docker inspect --format '{{{{.Name}}}} {{{{if .State.Health}}}}{{{{.State.Health.Status}}}}{{{{end}}}}' 2>/dev/null",
+        shell_quote(project)
+    );
+    let output = session.exec_output(&cmd).unwrap_or_default();
+    String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|l| l.trim().strip_suffix(" unhealthy"))
+        .map(|name| name.trim_start_matches('/').to_string())
+        .collect()
+}
+
 fn build_and_start(
     config: &OpsToml,
     session: &SshSession,
@@ -1162,40 +2016,76 @@ fn build_and_start(
     env_vars: &[String],
     no_pull: bool,
 ) -> Result<()> {
-    let deploy_path = &config.deploy_path;
-
-    let compose = compose_file_args(config);
     let env = env_prefix(env_vars);
     let svcs = resolve_services(config, app_filter, service_filter);
 
-    // Add space before compose args and services if non-empty
-    let compose_arg = if compose.is_empty() { String::new() } else { format!(" {}", compose) };
-    let svc_arg = if svcs.is_empty() { String::new() } else { format!(" {}", svcs) };
-
     o_step!("\n{}", "🚀 Building & starting services...".cyan());
+    let (cmd, action) = start_command(config, &env, &svcs, restart_only, no_pull);
+    session.exec(&cmd, None)?;
+    if action == "pull" {
+        // image 模式: 清理旧镜像
+        session.exec(IMAGE_PRUNE, None).ok();
+    }
 
+    // A deploy waited before starting anything; a restart took the dependencies down with the rest
     if restart_only {
-        let cmd = format!("cd {} && {}docker compose{} restart{}", deploy_path, env, compose_arg, svc_arg);
-        session.exec(&cmd, None)?;
-    } else if config.deploy.source == "image" {
-        // image 模式: 只 up，不 build
-        let cmd = format!(
-            "cd {} && {}docker compose{} up -d --remove-orphans{}",
-            deploy_path, env, compose_arg, svc_arg
-        );
-        session.exec(&cmd, None)?;
-        // 清理旧镜像
-        session.exec("docker image prune -f", None).ok();
-    } else {
-        // 旧行为: build + up
-        let pull_arg = if no_pull { "" } else { " --pull" };
-        let cmd = format!(
-            "cd {} && {}docker compose{} build{}{} && {}docker compose{} up -d --remove-orphans{}",
-            deploy_path, env, compose_arg, pull_arg, svc_arg, env, compose_arg, svc_arg
-        );
-        session.exec(&cmd, None)?;
+        wait_for_dependencies(config, session, app_filter)?;
+    }
+    Ok(())
+}
+
+const DEFAULT_WAIT_TIMEOUT: u64 = 60;
+
+/// `wait_for` entry: compose service or host, and the TCP port to open (None: container ready only)
+fn parse_wait_target(entry: &str) -> Result<(&str, Option<u16>)> {
+    match entry.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port.parse().with_context(|| format!("Invalid port in wait_for entry '{}'", entry))?;
+            Ok((host, Some(port)))
+        }
+        None => Ok((entry, None)),
+    }
+}
+
+/// Polls until the target is ready and prints "ready <secs>" or "timeout <health>". A compose service is
+/// ready once its container runs, is healthy if it has a healthcheck, and accepts connections on the port;
+/// anything else is probed as a host from the node.
+fn wait_script(project: &str, host: &str, port: Option<u16>, timeout: u64) -> String {
+    let probe = match port {
+        Some(port) => format!("timeout 2 bash -c \"</dev/tcp/$ip/{}\" 2>/dev/null", port),
+        None => "[ -n \"$c\" ]".to_string(),
+    };
+    format!(
+        "start=$(date +%s); while :; do \
+c=$(docker ps -q --filter label=com.docker.compose.project={p} --filter label=com.docker.compose.service={h} | head -1); \
+if [ -n \"$c\" ]; then h=$(docker inspect -f '{{{{if .State.Health}}}}{{{{.State.Health.Status}}}}{{{{else}}}}none{{{{end}}}}' $c); \
+ip=$(docker inspect -f '{{{{range .NetworkSettings.Networks}}}}{{{{.IPAddress}}}} {{{{end}}}}' $c | awk '{{print $1}}'); \
+else h=none; ip={h}; fi; \
+if {{ [ \"$h\" = healthy ] || [ \"$h\" = none ]; }} && {probe}; then echo \"ready $(( $(date +%s) - start ))\"; exit 0; fi; \
+if [ $(( $(date +%s) - start )) -ge {t} ]; then echo \"timeout $h\"; exit 0; fi; sleep 1; done",
+        p = project, h = host, probe = probe, t = timeout
+    )
+}
+
+/// `wait_for` of the deployed apps: block until databases, caches etc. accept connections, so health
+/// checks and the "deployed" result don't race their startup
+fn wait_for_dependencies(config: &OpsToml, session: &SshSession, app_filter: &Option<String>) -> Result<()> {
+    let waits = dependency_waits(config, app_filter);
+    if waits.is_empty() {
+        return Ok(());
     }
 
+    o_step!("\n{}", "⏳ Waiting for dependencies...".cyan());
+    for (entry, timeout) in waits {
+        let (host, port) = parse_wait_target(entry)?;
+        let out = session.exec_output(&wait_script(config.compose_project(), host, port, timeout))?;
+        let out = String::from_utf8_lossy(&out).trim().to_string();
+        match out.split_once(' ') {
+            Some(("ready", secs)) => o_success!("   ✔ {} ready ({}s)", entry.green(), secs),
+            Some(("timeout", health)) if health != "none" => bail!("{} not ready after {}s (container {})", entry, timeout, health),
+            _ => bail!("{} not ready after {}s", entry, timeout),
+        }
+    }
     Ok(())
 }
 
@@ -1217,7 +2107,7 @@ fn collect_app_services(config: &OpsToml) -> Vec<String> {
 /// 获取基础设施 services（compose 中定义但不在 [[apps]] 中的 services）
 fn collect_infra_services(config: &OpsToml, session: &SshSession, env_vars: &[String]) -> Result<Vec<String>> {
     let deploy_path = &config.deploy_path;
-    let compose = compose_file_args(config);
+    let compose = compose_args(config);
     let env = env_prefix(env_vars);
     let compose_arg = if compose.is_empty() { String::new() } else { format!(" {}", compose) };
 
@@ -1247,10 +2137,10 @@ fn blue_green_deploy(
     init: bool,
 ) -> Result<()> {
     let deploy_path = &config.deploy_path;
-    let compose = compose_file_args(config);
+    let compose = compose_base_args(config);
     let env = env_prefix(env_vars);
     let compose_arg = if compose.is_empty() { String::new() } else { format!(" {}", compose) };
-    let project = &config.project;
+    let project = config.compose_project();
 
     // 1. 读取当前 slot
     let slot_file = format!("{}/.ops-slot", deploy_path);
@@ -1394,8 +2284,8 @@ fn blue_green_deploy(
         // 健康检查失败 — 停掉 target slot，不切流量
         o_warn!("\n{}", "⚠ Health checks failed — rolling back (stopping new slot)".yellow());
         let down_cmd = format!(
-            "cd {} && docker compose -p {} down 2>/dev/null; true",
-            deploy_path, target_project
+            "cd {} && docker compose -p {} {} down 2>/dev/null; true",
+            deploy_path, target_project, compose_arg.trim()
         );
         session.exec(&down_cmd, None)?;
         return Err(anyhow!("Blue-green deploy aborted: health checks failed on new slot"));
@@ -1440,7 +2330,8 @@ fn upload_caddy_routes_bg(
     session: &SshSession,
     ip_map: &std::collections::HashMap<String, (String, u16)>,
 ) -> Result<()> {
-    session.exec("mkdir -p /etc/caddy/routes.d", None)?;
+    let router = Router::from_config(config.deploy.router.as_deref())?;
+    router.ensure_base(session)?;
 
     let project_name = &config.project;
 
@@ -1448,25 +2339,22 @@ fn upload_caddy_routes_bg(
     if !config.routes.is_empty() {
         for route in &config.routes {
             // 找到此路由对应的 app (通过 port 匹配)
-            let upstream = config.apps.iter()
-                .find(|a| a.port == Some(route.port))
+            let route_app = config.apps.iter().find(|a| a.port == Some(route.port));
+            let upstream = route_app
                 .and_then(|a| ip_map.get(&a.name))
                 .map(|(ip, port)| format!("{}:{}", ip, port))
                 .unwrap_or_else(|| format!("127.0.0.1:{}", route.port));
-
-            let safe_domain = route.domain.replace('.', "_").replace('-', "_");
-            let matcher_name = format!("ops_route_{}", safe_domain);
-            let caddy_snippet = format!(
-                "# {domain}\n@{matcher} header X-Forwarded-Host {domain}\nhandle @{matcher} {{\n    reverse_proxy {upstream}\n}}\n",
-                domain = route.domain,
-                matcher = matcher_name,
-                upstream = upstream,
+            let owner_app = route_app.map(|a| a.name.clone()).unwrap_or_else(|| resolve_app_name(config));
+
+            let safe_domain = route.domain.replace(['.', '-'], "_");
+            let (path, content) = router.render(
+                &format!("ops-route-{}", safe_domain),
+                &owner_app, project_name,
+                &RouteMatch::ForwardedHost(route.domain.clone()),
+                &upstream,
+                &route_options(config, route),
             );
-            let conf_name = format!("ops-route-{}.caddy", safe_domain);
-            session.exec(
-                &format!("cat > /etc/caddy/routes.d/{}", conf_name),
-                Some(&caddy_snippet),
-            )?;
+            router.write(session, &path, &content)?;
             o_detail!("   ✔ {} → {}", route.domain.green(), upstream);
         }
     }
@@ -1476,25 +2364,20 @@ fn upload_caddy_routes_bg(
         if app.port.is_none() { continue; }
         if let Some((ip, port)) = ip_map.get(&app.name) {
             let target = format!("{}.{}", app.name, project_name);
-            let matcher_name = format!("ops_{}_{}", app.name, project_name).replace('-', "_");
-            let caddy_snippet = format!(
-                "# {target}\n@{matcher} header X-OPS-Target {target}\nhandle @{matcher} {{\n    reverse_proxy {ip}:{port}\n}}\n",
-                target = target,
-                matcher = matcher_name,
-                ip = ip,
-                port = port,
+            let (path, content) = router.render(
+                &format!("ops-{}-{}", app.name, project_name),
+                &app.name, project_name,
+                &RouteMatch::Target(target),
+                &format!("{}:{}", ip, port),
+                &ProxyOptions::for_app(app),
             );
-            let conf_name = format!("ops-{}-{}.caddy", app.name, project_name);
-            session.exec(
-                &format!("cat > /etc/caddy/routes.d/{}", conf_name),
-                Some(&caddy_snippet),
-            )?;
+            router.write(session, &path, &content)?;
         }
     }
 
-    // Validate & reload Caddy
-    session.exec("caddy validate --config /etc/caddy/Caddyfile && systemctl reload caddy", None)?;
-    o_success!("   ✔ Caddy reloaded");
+    // Validate & reload
+    router.reload(session)?;
+    o_success!("   ✔ {} reloaded", router.name());
 
     Ok(())
 }
@@ -1504,21 +2387,14 @@ fn run_init_commands(config: &OpsToml, session: &SshSession, env_vars: &[String]
         return Ok(());
     }
 
-    let deploy_path = &config.deploy_path;
-    let compose = compose_file_args(config);
     let env = env_prefix(env_vars);
-    let compose_arg = if compose.is_empty() { String::new() } else { format!(" {}", compose) };
 
     o_step!("\n{}", "🔧 Running init commands...".cyan());
 
     for step in &config.init {
         for command in step.all_commands() {
             o_detail!("   {} → {}", step.service.yellow(), command);
-            let cmd = format!(
-                "cd {} && {}docker compose{} exec {} {}",
-                deploy_path, env, compose_arg, step.service, command
-            );
-            session.exec(&cmd, None)?;
+            session.exec(&init_command(config, &env, &step.service, command), None)?;
         }
         o_success!("   ✔ {}", step.service.green());
     }
@@ -1526,29 +2402,213 @@ fn run_init_commands(config: &OpsToml, session: &SshSession, env_vars: &[String]
     Ok(())
 }
 
-fn run_health_checks(config: &OpsToml, session: &SshSession) -> Result<()> {
+/// Shell script that polls `url` according to `hc` and prints "OK <code>" or "FAIL <code>".
+/// Always exits 0 so the caller can read the last status code.
+fn health_check_script(hc: &HealthCheck, url: &str) -> String {
+    let seq = (1..=hc.retries.max(1)).map(|i| i.to_string()).collect::<Vec<_>>().join(" ");
+    let delay_cmd = if hc.initial_delay > 0 { format!("sleep {}; ", hc.initial_delay) } else { String::new() };
+    let matcher = match hc.expected_status {
+        Some(code) => format!("[ \"$code\" = \"{}\" ]", code),
+        None => "case $code in 2??|3??) true;; *) false;; esac".to_string(),
+    };
+    format!(
+        "{}code=000; for i in {}; do code=$(curl -s -o /dev/null -w '%{{http_code}}' --max-time {} '{}'); if {}; then echo \"OK $code\"; exit 0; fi; sleep {}; done; echo \"FAIL $code\"",
+        delay_cmd, seq, hc.timeout.max(1), url, matcher, hc.interval
+    )
+}
+
+/// Run one health check, on the node or from this machine. Returns (ok, last status code).
+fn run_health_check(hc: &HealthCheck, url: &str, session: &SshSession) -> Result<(bool, String)> {
+    let script = health_check_script(hc, url);
+    let stdout = match hc.from.as_str() {
+        "local" => {
+            let output = std::process::Command::new("sh").arg("-c").arg(&script).output()
+                .context("Failed to run local health check (is curl installed?)")?;
+            output.stdout
+        }
+        "node" => session.exec_output(&script)?,
+        other => return Err(anyhow!("Unknown healthcheck `from` value '{}' (expected node or local)", other)),
+    };
+    let out = String::from_utf8_lossy(&stdout);
+    let mut parts = out.trim().splitn(2, ' ');
+    let ok = parts.next() == Some("OK");
+    Ok((ok, parts.next().unwrap_or("000").to_string()))
+}
+
+pub fn run_health_checks(config: &OpsToml, session: &SshSession) -> Result<()> {
     if config.healthchecks.is_empty() {
         return Ok(());
     }
 
     o_step!("\n{}", "💚 Health checks:".cyan());
 
-    for hc in &config.healthchecks {
-        let seq = (1..=hc.retries).map(|i| i.to_string()).collect::<Vec<_>>().join(" ");
-        let delay_cmd = if hc.initial_delay > 0 { format!("sleep {}; ", hc.initial_delay) } else { String::new() };
-        let cmd = format!(
-            "{}for i in {}; do curl -sf {} > /dev/null && echo 'OK' && exit 0; sleep {}; done; echo 'FAIL'; exit 1",
-            delay_cmd, seq, hc.url, hc.interval
-        );
-        let output = session.exec_output(&cmd);
-        match output {
-            Ok(o) if String::from_utf8_lossy(&o).trim() == "OK" => {
-                o_success!("   ✔ {}  {}  {}", hc.name.green(), hc.url, "OK".green());
+    // 并发执行所有检查，按定义顺序输出结果
+    let results: Vec<Result<(bool, String)>> = std::thread::scope(|s| {
+        let handles: Vec<_> = config.healthchecks.iter()
+            .map(|hc| s.spawn(move || run_health_check(hc, &hc.url, session)))
+            .collect();
+        handles.into_iter()
+            .map(|h| h.join().unwrap_or_else(|_| Err(anyhow!("health check thread panicked"))))
+            .collect()
+    });
+
+    let mut required_failed = Vec::new();
+    for (hc, result) in config.healthchecks.iter().zip(results) {
+        match result {
+            Ok((true, code)) => {
+                o_success!("   ✔ {}  {}  {}", hc.name.green(), hc.url, format!("OK ({})", code).green());
+            }
+            Ok((false, code)) => {
+                o_warn!("   ✘ {}  {}  {}", hc.name.red(), hc.url, format!("FAILED ({})", code).red());
+                if hc.required { required_failed.push(hc.name.clone()); }
             }
-            _ => {
-                o_warn!("   ✘ {}  {}  {}", hc.name.red(), hc.url, "FAILED".red());
+            Err(e) => {
+                o_warn!("   ✘ {}  {}  {}", hc.name.red(), hc.url, format!("ERROR: {}", e).red());
+                if hc.required { required_failed.push(hc.name.clone()); }
             }
         }
     }
+
+    if !required_failed.is_empty() {
+        return Err(anyhow!("Required health check(s) failed: {}", required_failed.join(", ")));
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_app_waves() {
+        let config: OpsToml = toml::from_str(r#"
+            project = "shop"
+            deploy_path = "/opt/shop"
+            [deploy]
+            [[apps]]
+            name = "web"
+            services = ["web"]
+            depends_on = ["api"]
+            [[apps]]
+            name = "api"
+            services = ["api"]
+            depends_on = ["db"]
+            [[apps]]
+            name = "db"
+            services = ["postgres"]
+            [[apps]]
+            name = "docs"
+            services = ["docs"]
+        "#).unwrap();
+        let names = |waves: Vec<Vec<&AppDef>>| -> Vec<Vec<String>> {
+            waves.into_iter().map(|w| w.into_iter().map(|a| a.name.clone()).collect()).collect()
+        };
+        assert_eq!(names(app_waves(&config.apps).unwrap()), vec![vec!["db", "docs"], vec!["api"], vec!["web"]]);
+
+        let mut cyclic = config.apps.clone();
+        cyclic[2].depends_on = vec!["web".into()];
+        assert!(app_waves(&cyclic).unwrap_err().to_string().starts_with("Circular depends_on"));
+
+        cyclic[2].depends_on = vec!["cache".into()];
+        assert!(app_waves(&cyclic).unwrap_err().to_string().contains("unknown app 'cache'"));
+    }
+
+    #[test]
+    fn test_wait_for() {
+        assert_eq!(parse_wait_target("db:5432").unwrap(), ("db", Some(5432)));
+        assert_eq!(parse_wait_target("redis").unwrap(), ("redis", None));
+        assert!(parse_wait_target("db:pg").is_err());
+
+        let script = wait_script("shop", "db", Some(5432), 90);
+        assert!(script.contains("label=com.docker.compose.project=shop --filter label=com.docker.compose.service=db"));
+        assert!(script.contains("</dev/tcp/$ip/5432"));
+        assert!(script.contains("-ge 90 ]"));
+        assert!(script.contains("{{if .State.Health}}"));
+    }
+    #[test]
+    fn test_hook_command() {
+        let config: OpsToml = toml::from_str(r#"
+            project = "shop"
+            deploy_path = "/opt/shop"
+            [deploy]
+            [hooks]
+            pre_deploy = ["docker compose run --rm api ./migrate"]
+        "#).unwrap();
+        assert_eq!(config.hooks.as_ref().unwrap().pre_deploy.len(), 1);
+        assert!(config.hooks.as_ref().unwrap().on_failure.is_empty());
+
+        assert_eq!(
+            hook_command(&config, "pre_deploy", "./migrate", "api", Some(42), None),
+            "cd /opt/shop && export OPS_HOOK=pre_deploy OPS_PROJECT=shop OPS_APP=api OPS_DEPLOYMENT_ID=42 && ./migrate"
+        );
+        let err = anyhow!("it's down");
+        assert_eq!(
+            hook_command(&config, "on_failure", "notify", "api", None, Some(&err)),
+            "cd /opt/shop && export OPS_HOOK=on_failure OPS_PROJECT=shop OPS_APP=api OPS_ERROR='it'\\''s down' && notify"
+        );
+    }
+
+    #[test]
+    fn test_env_overlay() {
+        let base: OpsToml = toml::from_str(r#"
+            project = "shop"
+            deploy_path = "/opt/shop"
+            [deploy]
+            compose_files = ["docker-compose.yml"]
+            [[apps]]
+            name = "web"
+            services = ["web"]
+            domains = ["shop.example.com"]
+            [[env_files]]
+            local = ".env.production"
+            remote = ".env"
+            [environments.staging]
+            deploy_path = "/opt/shop-staging"
+            compose_files = ["docker-compose.yml", "docker-compose.staging.yml"]
+            env_files = [{ local = ".env.staging", remote = ".env" }]
+            domains = { web = ["staging.shop.example.com"] }
+            routes = [{ domain = "staging-api.example.com", port = 3000 }]
+            region = "eu-west"
+            [[routes]]
+            domain = "api.example.com"
+            port = 3000
+        "#).unwrap();
+
+        let mut staging = base.clone();
+        staging.apply_env("staging").unwrap();
+        assert_eq!(staging.deploy_path, "/opt/shop-staging");
+        assert_eq!(compose_args(&staging), "-f docker-compose.yml -f docker-compose.staging.yml");
+        assert_eq!(staging.env_files[0].local, ".env.staging");
+        assert_eq!(staging.apps[0].domains, vec!["staging.shop.example.com"]);
+        assert_eq!(staging.routes.iter().map(|r| r.domain.as_str()).collect::<Vec<_>>(), vec!["staging-api.example.com"]);
+
+        assert!(staging.env_selects(1, Some("eu-west")));
+        assert!(!staging.env_selects(1, Some("us-east")));
+        assert!(base.env_selects(1, None));
+
+        let err = base.clone().apply_env("prod").unwrap_err().to_string();
+        assert_eq!(err, "Unknown environment 'prod'. Defined: staging");
+    }
+
+    #[test]
+    fn test_generated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "<h1>hi</h1>").unwrap();
+        let file = dir.path().join("ops.toml");
+        fs::write(&file, "project = \"site\"\ndeploy_path = \"/opt/site\"\n[deploy]\nsource = \"push\"\n").unwrap();
+        let config = load_ops_toml(file.to_str().unwrap()).unwrap();
+        assert_eq!(config.project_dir(), dir.path());
+        assert_eq!(compose_file_candidates(&config), drift::DEFAULT_COMPOSE_FILES.to_vec());
+
+        let info = scanner::scan(config.project_dir()).unwrap().unwrap();
+        let files = generated_files(&config, &info, "docker-compose.yml", &[]);
+        let names: Vec<&str> = files.iter().map(|(f, _)| f.as_str()).collect();
+        assert_eq!(names, vec!["docker-compose.yml", "Dockerfile", ".dockerignore"]);
+        assert!(files[0].1.contains("site"));
+        assert!(files[1].1.contains("nginx:alpine"));
+        assert!(files[2].1.lines().any(|l| l == ".dockerignore"));
+
+        let files = generated_files(&config, &info, "docker-compose.yml", &["Dockerfile", ".dockerignore"]);
+        assert_eq!(files.len(), 1);
+    }
+}
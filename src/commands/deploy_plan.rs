@@ -0,0 +1,330 @@
+//! `ops deploy --dry-run`: what a deploy would do, worked out from ops.toml and the backend's list of
+//! target nodes without connecting to any of them. Values only known on the node (the deployment ID,
+//! container IPs, the compose network) appear as `<placeholders>`.
+
+use crate::commands::deploy::{
+    active_deployment_command, app_routes, container_health_check, container_init_command, container_run_command,
+    dependency_waits, deployed_apps, env_prefix, fallback_routes, git_clone_command, git_pull_command, hook_command,
+    image_build_command, image_pull_command, infra_up_command, init_command, resolve_app_name, resolve_services,
+    scale_up_image_command, start_command, StepOptions, IMAGE_PRUNE,
+};
+use crate::commands::secret_store::SECRETS_ENV_FILE;
+use crate::output;
+use crate::router::Router;
+use crate::types::{DeployTarget, OpsToml};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::path::Path;
+
+const DEPLOYMENT: &str = "<deployment>";
+const CONTAINER_IP: &str = "<container-ip>";
+const NETWORK: &str = "<compose network>";
+const SERVICES_OUTSIDE_APPS: &str = "<compose services not in [[apps]]>";
+
+#[derive(Serialize, Debug)]
+pub struct PlannedService {
+    pub name: String,
+    /// "build" | "pull" | "restart"
+    pub action: &'static str,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PlannedUpload {
+    /// "env" | "dir" | "code"
+    pub kind: &'static str,
+    pub local: String,
+    pub remote: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlannedRoute {
+    pub path: String,
+    pub label: String,
+    pub upstream: String,
+    pub content: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct DeployPlan {
+    pub project: String,
+    pub app: String,
+    pub targets: Vec<DeployTarget>,
+    /// "zero-downtime" | "up" | "compose" | "restart"
+    pub strategy: &'static str,
+    pub services: Vec<PlannedService>,
+    pub uploads: Vec<PlannedUpload>,
+    pub routes: Vec<PlannedRoute>,
+    /// Run on each node, in order; lines starting with `#` describe steps that aren't a single command
+    pub commands: Vec<String>,
+}
+
+/// The steps `deploy_steps` takes for this config, without the targets. Commands come from the same
+/// builders the deploy runs.
+pub fn build(config: &OpsToml, opts: &StepOptions) -> Result<DeployPlan> {
+    let app = opts.app_filter.clone().unwrap_or_else(|| resolve_app_name(config));
+    let hooks = config.hooks.clone().unwrap_or_default();
+    let deploy_path = &config.deploy_path;
+    let env = env_prefix(opts.env_vars);
+    let router = Router::from_config(config.deploy.router.as_deref())?;
+    let mut commands = vec![format!("mkdir -p {}", deploy_path)];
+    let mut uploads = Vec::new();
+    let mut services = Vec::new();
+
+    for ef in config.env_files.iter().filter(|ef| Path::new(&ef.local).exists()) {
+        uploads.push(PlannedUpload { kind: "env", local: ef.local.clone(), remote: format!("{}/{}", deploy_path, ef.remote) });
+    }
+    commands.push(format!("# write the secrets of {} into {}/{} (ops secrets)",
+        deployed_apps(config, opts.app_filter).join(", "), deploy_path, SECRETS_ENV_FILE));
+    for s in config.sync.iter().filter(|s| Path::new(&s.local).exists()) {
+        uploads.push(PlannedUpload { kind: "dir", local: s.local.clone(), remote: format!("{}/{}", deploy_path, s.remote) });
+    }
+
+    if !opts.restart_only {
+        match config.deploy.source.as_str() {
+            "git" => {
+                let git = config.deploy.git.as_ref().context("deploy.source='git' requires [deploy.git] section")?;
+                let branch = config.deploy.branch.as_deref().unwrap_or("main");
+                commands.push(git_pull_command(config, branch));
+                commands.push(format!("# first deploy: {}", git_clone_command(config, &git.repo, branch)));
+            }
+            "push" => {
+                let local = if config.deploy.include.is_empty() { ".".to_string() } else { config.deploy.include.join(" ") };
+                uploads.push(PlannedUpload { kind: "code", local, remote: deploy_path.clone() });
+            }
+            "image" => {
+                if let Some(reg) = &config.deploy.registry {
+                    commands.push(format!("docker login {}", reg.url));
+                }
+                commands.push(image_pull_command(config, &env, &resolve_services(config, opts.app_filter, opts.service_filter)));
+            }
+            other => bail!("Unknown deploy source: {}", other),
+        }
+    }
+
+    commands.extend(hooks.pre_deploy.iter().map(|c| hook_command(config, "pre_deploy", c, &app, None, None)));
+
+    let apps_with_port: Vec<_> = config.apps.iter()
+        .filter(|a| a.port.is_some())
+        .filter(|a| opts.app_filter.is_none() || opts.app_filter.as_ref() == Some(&a.name))
+        .collect();
+    let strategy = if opts.restart_only {
+        "restart"
+    } else if apps_with_port.is_empty() {
+        "compose"
+    } else if config.deploy.strategy.as_deref() == Some("up") {
+        "up"
+    } else {
+        "zero-downtime"
+    };
+    let wait_for: Vec<String> = dependency_waits(config, opts.app_filter).into_iter()
+        .map(|(entry, timeout)| format!("# wait up to {}s for {}", timeout, entry))
+        .collect();
+    if !opts.restart_only {
+        commands.push(infra_up_command(config, &env, SERVICES_OUTSIDE_APPS));
+        commands.extend(wait_for.iter().cloned());
+    }
+
+    let mut routes = Vec::new();
+    let compose_init = |commands: &mut Vec<String>| {
+        for step in config.init.iter().filter(|_| opts.init) {
+            for command in step.all_commands() {
+                commands.push(init_command(config, &env, &step.service, command));
+            }
+        }
+    };
+
+    match strategy {
+        "zero-downtime" => {
+            for app in &apps_with_port {
+                let port = app.port.unwrap_or_default();
+                commands.push(image_build_command(config, &env, &app.services.join(" "), opts.no_pull));
+                services.extend(app.services.iter().map(|s| PlannedService { name: s.clone(), action: "build" }));
+                for svc in &app.services {
+                    commands.push(container_run_command(config, svc, DEPLOYMENT, NETWORK));
+                    commands.push(format!("# health check {}", container_health_check(config, app, CONTAINER_IP, port).1));
+                    commands.push(format!("# switch routes of {} to {}:{}", app.name, CONTAINER_IP, port));
+                    commands.push(format!("# remove older {}-{}-* containers", config.compose_project(), svc));
+                }
+                commands.push(active_deployment_command(config, DEPLOYMENT));
+                commands.push(IMAGE_PRUNE.into());
+                routes.extend(app_routes(router, config, app, &format!("{}:{}", CONTAINER_IP, port)));
+            }
+            for step in config.init.iter().filter(|_| opts.init) {
+                if apps_with_port.iter().any(|a| a.services.contains(&step.service)) {
+                    for command in step.all_commands() {
+                        commands.push(container_init_command(config, &step.service, DEPLOYMENT, command));
+                    }
+                }
+            }
+        }
+        "up" => {
+            for app in &apps_with_port {
+                let port = app.port.unwrap_or_default();
+                let (cmd, action) = scale_up_image_command(config, &env, app, opts.no_pull);
+                commands.push(cmd);
+                services.extend(app.services.iter().map(|s| PlannedService { name: s.clone(), action }));
+                for svc in &app.services {
+                    commands.push(format!("# replace {} containers: start new ones, health check, switch routes, remove old", svc));
+                }
+                if action == "pull" {
+                    commands.push(IMAGE_PRUNE.into());
+                }
+                routes.extend(app_routes(router, config, app, &format!("{}:{}", CONTAINER_IP, port)));
+            }
+            compose_init(&mut commands);
+        }
+        _ => {
+            let svcs = resolve_services(config, opts.app_filter, opts.service_filter);
+            let (cmd, action) = start_command(config, &env, &svcs, opts.restart_only, opts.no_pull);
+            commands.push(cmd);
+            if action == "pull" {
+                commands.push(IMAGE_PRUNE.into());
+            }
+            let names: Vec<String> = if svcs.is_empty() { vec!["<all compose services>".into()] } else { svcs.split_whitespace().map(String::from).collect() };
+            services.extend(names.into_iter().map(|name| PlannedService { name, action }));
+            if opts.restart_only {
+                commands.extend(wait_for);
+            }
+            compose_init(&mut commands);
+            if !opts.restart_only {
+                routes.extend(fallback_routes(router, config, opts.app_filter));
+            }
+        }
+    }
+    if strategy != "zero-downtime" {
+        commands.extend(config.healthchecks.iter().map(|hc| format!("# health check {}: {}", hc.name, hc.url)));
+    }
+    commands.extend(hooks.post_deploy.iter().map(|c| hook_command(config, "post_deploy", c, &app, None, None)));
+
+    Ok(DeployPlan {
+        project: config.project.clone(),
+        app,
+        targets: Vec::new(),
+        strategy,
+        services,
+        uploads,
+        routes: routes.into_iter().map(|r| PlannedRoute { path: r.path, label: r.label, upstream: r.upstream, content: r.content }).collect(),
+        commands,
+    })
+}
+
+/// Print the plan, or emit it as JSON with --json
+pub fn print(plan: &DeployPlan, rolling: bool) -> Result<()> {
+    if output::json() {
+        return output::print_json(plan);
+    }
+    o_step!("\n{} {}", "🧪 Dry run:".cyan(), "nothing will be changed".dimmed());
+    o_detail!("   App: {}.{}", plan.app.green(), plan.project.green());
+    let order = match plan.targets.len() {
+        0 | 1 => "",
+        _ if rolling => ", one node at a time",
+        _ => ", nodes in parallel",
+    };
+    o_detail!("   Strategy: {}{}", plan.strategy.yellow(), order);
+    if plan.targets.is_empty() {
+        o_detail!("   Targets: {}", "none bound yet (a deploy would allocate a node)".yellow());
+    }
+    for t in &plan.targets {
+        o_detail!("   Target: {} ({}){}", t.domain.cyan(), t.region.as_deref().unwrap_or("?"), if t.is_primary { " *" } else { "" });
+    }
+
+    o_step!("\n{}", "📦 Services:".cyan());
+    for s in &plan.services {
+        o_detail!("   {} {}", s.name, format!("({})", s.action).dimmed());
+    }
+
+    if !plan.uploads.is_empty() {
+        o_step!("\n{}", "📤 Uploads:".cyan());
+        for u in &plan.uploads {
+            o_detail!("   {} → {} {}", u.local.cyan(), u.remote, format!("({})", u.kind).dimmed());
+        }
+    }
+
+    if !plan.routes.is_empty() {
+        o_step!("\n{}", "⚙️  Route fragments:".cyan());
+        for r in &plan.routes {
+            o_detail!("   {}  {} → {}", r.path, r.label.green(), r.upstream);
+            for line in r.content.lines() {
+                o_detail!("     {}", line.dimmed());
+            }
+        }
+    }
+
+    o_step!("\n{}", "💻 Remote commands (on each node):".cyan());
+    for c in &plan.commands {
+        if c.starts_with('#') {
+            o_detail!("   {}", c.dimmed());
+        } else {
+            o_detail!("   $ {}", c);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(toml: &str, restart_only: bool) -> DeployPlan {
+        let config: OpsToml = toml::from_str(toml).unwrap();
+        let opts = StepOptions { service_filter: &None, app_filter: &None, restart_only, env_vars: &[], no_pull: false, init: true };
+        build(&config, &opts).unwrap()
+    }
+
+    #[test]
+    fn test_build_plan() {
+        let zero_downtime = plan(r#"
+            project = "shop"
+            deploy_path = "/opt/shop"
+            [deploy]
+            source = "push"
+            include = ["src/", "Dockerfile"]
+            [hooks]
+            pre_deploy = ["./migrate.sh"]
+            [[apps]]
+            name = "web"
+            services = ["web"]
+            port = 3000
+            wait_for = ["db:5432"]
+            [[init]]
+            service = "web"
+            command = "npm run seed"
+        "#, false);
+        assert_eq!(zero_downtime.strategy, "zero-downtime");
+        assert_eq!(zero_downtime.uploads, vec![PlannedUpload { kind: "code", local: "src/ Dockerfile".into(), remote: "/opt/shop".into() }]);
+        assert_eq!(zero_downtime.services.iter().map(|s| (s.name.as_str(), s.action)).collect::<Vec<_>>(), vec![("web", "build")]);
+        assert_eq!(zero_downtime.routes[0].path, "/etc/caddy/routes.d/ops-web-shop.caddy");
+        assert_eq!(zero_downtime.routes[0].upstream, "<container-ip>:3000");
+        let cmds = &zero_downtime.commands;
+        assert_eq!(cmds[0], "mkdir -p /opt/shop");
+        assert_eq!(cmds[1], "# write the secrets of web into /opt/shop/.env (ops secrets)");
+        assert!(cmds[2].starts_with("cd /opt/shop && export OPS_HOOK=pre_deploy") && cmds[2].ends_with("&& ./migrate.sh"));
+        assert!(cmds.contains(&"cd /opt/shop && docker compose -p shop build --pull web".to_string()));
+        assert!(cmds.contains(&"# health check http://<container-ip>:3000/status".to_string()));
+        assert!(cmds.contains(&"docker exec shop-web-<deployment> npm run seed".to_string()));
+        // Dependencies are ready before the app is built and started
+        let at = |c: &str| cmds.iter().position(|x| x == c).unwrap();
+        assert!(at("# wait up to 60s for db:5432") < at("cd /opt/shop && docker compose -p shop build --pull web"));
+
+        let restart = plan(r#"
+            project = "shop"
+            deploy_path = "/opt/shop"
+            [deploy]
+            source = "image"
+            [[apps]]
+            name = "web"
+            services = ["web", "worker"]
+            port = 3000
+        "#, true);
+        assert_eq!(restart.strategy, "restart");
+        assert!(restart.routes.is_empty());
+        // Without --app or --service, compose restarts everything
+        assert_eq!(restart.commands, vec![
+            "mkdir -p /opt/shop",
+            "# write the secrets of web into /opt/shop/.env (ops secrets)",
+            "cd /opt/shop && docker compose restart",
+        ]);
+        assert_eq!(restart.services[0].name, "<all compose services>");
+    }
+}
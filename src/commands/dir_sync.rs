@@ -0,0 +1,158 @@
+//! `[[sync]]` entries: each local directory (or file) is copied to `<deploy_path>/<remote>` with
+//! `rsync -az`, several entries at a time. A sha256 manifest of what was sent is kept on the node
+//! under `/etc/ops/sync`; an entry is skipped when the local manifest is unchanged and the files
+//! on the node still match it.
+
+use crate::commands::ssh::SshSession;
+use crate::node_state::SYNC_MANIFEST_DIR;
+use crate::types::{OpsToml, SyncMapping};
+use crate::utils::shell_quote;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Entries synced at once over rsync. SFTP shares the session's one connection, so it goes one by one
+const PARALLEL: usize = 4;
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hash `path` in chunks, so large assets are never held in memory whole
+fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn walk(dir: &Path, rel: &str, out: &mut BTreeMap<String, String>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let rel_path = if rel.is_empty() { name } else { format!("{}/{}", rel, name) };
+        let path = entry.path();
+        if fs::symlink_metadata(&path)?.is_dir() {
+            walk(&path, &rel_path, out)?;
+        } else if let Ok(sum) = file_sha256(&path) {
+            // Symlinks count by the content they point at, as `sha256sum -c` sees them; dangling ones are left out
+            out.insert(rel_path, sum);
+        }
+    }
+    Ok(())
+}
+
+/// `sha256sum` lines for `local` synced to `remote`: every file under a directory, relative to it,
+/// or a file by its name on the node
+pub fn manifest(local: &Path, remote: &str) -> Result<String> {
+    let mut sums = BTreeMap::new();
+    if local.is_dir() {
+        walk(local, "", &mut sums)?;
+    } else {
+        let name = Path::new(remote).file_name().map(|n| n.to_string_lossy().to_string()).context("Sync entry has no file name")?;
+        sums.insert(name, file_sha256(local).with_context(|| format!("Failed to read {}", local.display()))?);
+    }
+    Ok(sums.iter().map(|(path, sum)| format!("{}  {}\n", sum, path)).collect())
+}
+
+/// Where the manifest of the entry synced to `remote` lives on the node
+fn manifest_path(remote: &str) -> String {
+    format!("{}/{}", SYNC_MANIFEST_DIR, &sha256_hex(remote.as_bytes())[..16])
+}
+
+/// Directory `sha256sum -c` runs in: the entry itself, or a file's parent
+fn check_dir(remote: &str, is_dir: bool) -> String {
+    if is_dir {
+        return remote.to_string();
+    }
+    match Path::new(remote).parent().map(|p| p.to_string_lossy().to_string()) {
+        Some(p) if !p.is_empty() => p,
+        _ => "/".to_string(),
+    }
+}
+
+/// Sync one entry unless it is unchanged; whether anything was sent
+fn sync_entry(session: &SshSession, local: &Path, remote: &str) -> Result<bool> {
+    let manifest = manifest(local, remote)?;
+    let stored = manifest_path(remote);
+    let check = format!("cmp -s - {} && cd {} && sha256sum -c --status {}",
+        stored, shell_quote(&check_dir(remote, local.is_dir())), stored);
+    if session.exec(&check, Some(&manifest)).is_ok() {
+        return Ok(false);
+    }
+
+    session.rsync_copy(local, remote)?;
+    session.exec(&format!("mkdir -p {} && cat > {}", SYNC_MANIFEST_DIR, stored), Some(&manifest))
+        .context("Failed to record the sync manifest")?;
+    Ok(true)
+}
+
+/// Sync the `[[sync]]` entries that exist locally to the node
+pub fn sync_all(config: &OpsToml, session: &SshSession) -> Result<()> {
+    let entries: Vec<&SyncMapping> = config.sync.iter().filter(|s| Path::new(&s.local).exists()).collect();
+    if entries.is_empty() {
+        return Ok(());
+    }
+    o_step!("\n{}", "📤 Syncing directories...".cyan());
+
+    let parallel = if session.has_rsync() { PARALLEL } else { 1 };
+    for chunk in entries.chunks(parallel) {
+        let results: Vec<Result<bool>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = chunk.iter().map(|s| {
+                let remote = format!("{}/{}", config.deploy_path.trim_end_matches('/'), s.remote);
+                scope.spawn(move || sync_entry(session, Path::new(&s.local), &remote))
+            }).collect();
+            handles.into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| Err(anyhow::anyhow!("sync thread panicked"))))
+                .collect()
+        });
+        for (s, result) in chunk.iter().zip(results) {
+            let target = format!("{}:{}/{}", session.target(), config.deploy_path, s.remote);
+            match result.with_context(|| format!("Failed to sync {}", s.local))? {
+                true => o_detail!("   {} {} → {}", "✔".green(), s.local.cyan(), target),
+                false => o_detail!("   {} {} → {} {}", "✔".green(), s.local.cyan(), target, "(unchanged)".dimmed()),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("assets");
+        fs::create_dir_all(root.join("img")).unwrap();
+        fs::write(root.join("app.css"), "body {}").unwrap();
+        fs::write(root.join("img/logo.svg"), "<svg/>").unwrap();
+        std::os::unix::fs::symlink("app.css", root.join("site.css")).unwrap();
+        std::os::unix::fs::symlink("missing", root.join("broken")).unwrap();
+
+        let m = manifest(&root, "/opt/shop/assets").unwrap();
+        let lines: Vec<&str> = m.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], format!("{}  app.css", sha256_hex(b"body {}")));
+        assert!(lines[1].ends_with("  img/logo.svg"));
+        assert_eq!(lines[2], format!("{}  site.css", sha256_hex(b"body {}")));
+
+        // Same tree, same manifest; any content change shows
+        assert_eq!(manifest(&root, "/opt/shop/assets").unwrap(), m);
+        fs::write(root.join("img/logo.svg"), "<svg></svg>").unwrap();
+        assert_ne!(manifest(&root, "/opt/shop/assets").unwrap(), m);
+
+        let file = manifest(&root.join("app.css"), "/opt/shop/public/main.css").unwrap();
+        assert_eq!(file, format!("{}  main.css\n", sha256_hex(b"body {}")));
+        assert_eq!(check_dir("/opt/shop/docker-compose.yml", false), "/opt/shop");
+        assert_eq!(check_dir("/opt/shop/configs", true), "/opt/shop/configs");
+        assert_ne!(manifest_path("/opt/shop/configs"), manifest_path("/opt/blog/configs"));
+    }
+}
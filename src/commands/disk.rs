@@ -0,0 +1,164 @@
+//! Free-disk preflight before `docker compose pull` / `build` on a node, so a full disk stops the
+//! deploy up front instead of failing halfway through a pull with ENOSPC.
+
+use crate::commands::ssh::SshSession;
+use crate::types::OpsToml;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde_json::Value;
+
+/// Pulled layers are downloaded compressed, then unpacked to roughly twice that size
+const UNPACK_FACTOR: u64 = 3;
+/// Space a rebuild needs at least, whatever the size of the previous images
+const MIN_BUILD_BYTES: u64 = 1 << 30;
+/// Kept free on top of the estimate (logs, containers' writable layers)
+const HEADROOM_BYTES: u64 = 512 << 20;
+
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1} GiB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.0} MiB", b as f64 / (1u64 << 20) as f64),
+        b => format!("{} KiB", b >> 10),
+    }
+}
+
+/// Free bytes on the filesystem holding Docker's data root
+pub fn free_bytes(session: &SshSession) -> Result<u64> {
+    let cmd = "d=$(docker info -f '{{.DockerRootDir}}' 2>/dev/null); d=${d:-/var/lib/docker}; [ -d \"$d\" ] || d=/; df -Pk \"$d\" | tail -1";
+    let out = String::from_utf8_lossy(&session.exec_output(cmd)?).to_string();
+    let kib: u64 = out.split_whitespace().nth(3)
+        .and_then(|v| v.parse().ok())
+        .with_context(|| format!("Unexpected df output: {}", out.trim()))?;
+    Ok(kib * 1024)
+}
+
+/// Docker architecture name of `uname -m`
+fn docker_arch(uname: &str) -> &str {
+    match uname.trim() {
+        "x86_64" => "amd64",
+        "aarch64" | "arm64" => "arm64",
+        "armv7l" => "arm",
+        other => other,
+    }
+}
+
+/// (manifest digest, compressed layer bytes) from `docker manifest inspect -v`, which prints one
+/// object for a single-platform image and an array for a multi-platform one
+fn manifest_size(json: &[u8], arch: &str) -> Option<(String, u64)> {
+    let value: Value = serde_json::from_slice(json).ok()?;
+    let entry = match value {
+        Value::Array(entries) => entries.into_iter().find(|e| {
+            let platform = &e["Descriptor"]["platform"];
+            platform["os"] == "linux" && platform["architecture"] == arch
+        })?,
+        entry => entry,
+    };
+    let manifest = entry.get("SchemaV2Manifest").or_else(|| entry.get("OCIManifest"))?;
+    let size = manifest["layers"].as_array()?.iter().filter_map(|l| l["size"].as_u64()).sum();
+    Some((entry["Descriptor"]["digest"].as_str().unwrap_or_default().to_string(), size))
+}
+
+/// Space needed to pull `images`; images the node already has at the registry's digest count for nothing
+fn pull_estimate(session: &SshSession, images: &[String]) -> u64 {
+    let arch = session.exec_output("uname -m").map(|o| String::from_utf8_lossy(&o).to_string()).unwrap_or_default();
+    let arch = docker_arch(&arch);
+    let mut total = 0;
+    for image in images {
+        let Ok(out) = session.exec_output(&format!("docker manifest inspect -v {} 2>/dev/null", image)) else { continue };
+        let Some((digest, size)) = manifest_size(&out, arch) else { continue };
+        let local = session.exec_output(&format!("docker image inspect -f '{{{{join .RepoDigests \" \"}}}}' {} 2>/dev/null", image))
+            .map(|o| String::from_utf8_lossy(&o).to_string())
+            .unwrap_or_default();
+        if !digest.is_empty() && local.contains(&digest) {
+            continue;
+        }
+        o_debug!("   {} ~{}", image, format_bytes(size));
+        total += size * UNPACK_FACTOR;
+    }
+    total
+}
+
+/// Compare the estimate with free space; `[deploy] disk_check` = "block" (default) | "warn" | "off"
+fn check(config: &OpsToml, session: &SshSession, needed: u64, action: &str) -> Result<()> {
+    let mode = config.deploy.disk_check.as_deref().unwrap_or("block");
+    if mode == "off" || needed == 0 {
+        return Ok(());
+    }
+    let free = match free_bytes(session) {
+        Ok(free) => free,
+        Err(e) => {
+            o_warn!("   {} {:#}", "⚠ Disk check skipped:".yellow(), e);
+            return Ok(());
+        }
+    };
+    if free >= needed + HEADROOM_BYTES {
+        o_debug!("   disk: {} free, ~{} needed", format_bytes(free), format_bytes(needed));
+        return Ok(());
+    }
+    let message = format!(
+        "Not enough disk on node {}: {} free, ~{} needed to {}",
+        session.target(), format_bytes(free), format_bytes(needed + HEADROOM_BYTES), action
+    );
+    if mode == "warn" {
+        o_warn!("   {} {}", "⚠".yellow(), message);
+        return Ok(());
+    }
+    bail!(
+        "{}.\n  Free space with `ops node prune {}` (add --all to drop every unused image), \
+         or set [deploy] disk_check = \"warn\" to deploy anyway.",
+        message, session.target()
+    )
+}
+
+/// Before `docker compose pull`: estimate from the registry manifests of the images
+pub fn preflight_pull(config: &OpsToml, session: &SshSession, env: &str, compose: &str, svcs: &str) -> Result<()> {
+    if config.deploy.disk_check.as_deref() == Some("off") {
+        return Ok(());
+    }
+    let cmd = format!("cd {} && {}docker compose {} config --images {}", config.deploy_path, env, compose, svcs);
+    let Ok(out) = session.exec_output(&cmd) else { return Ok(()) };
+    let mut images: Vec<String> = String::from_utf8_lossy(&out).lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+    images.sort();
+    images.dedup();
+    let needed = pull_estimate(session, &images);
+    check(config, session, needed, &format!("pull {} image(s)", images.len()))
+}
+
+/// Before `docker compose build`: a rebuild takes about as much as the project's current images
+pub fn preflight_build(config: &OpsToml, session: &SshSession) -> Result<()> {
+    if config.deploy.disk_check.as_deref() == Some("off") {
+        return Ok(());
+    }
+    let cmd = format!(
+        "docker images -q --filter 'reference={}-*' | sort -u | xargs -r docker image inspect -f '{{{{.Size}}}}'",
+        config.compose_project()
+    );
+    let current: u64 = session.exec_output(&cmd)
+        .map(|o| String::from_utf8_lossy(&o).lines().filter_map(|l| l.trim().parse::<u64>().ok()).sum())
+        .unwrap_or(0);
+    check(config, session, current.max(MIN_BUILD_BYTES), "build images")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_size() {
+        let single = br#"{"Ref": "ghcr.io/acme/api:v1",
+            "Descriptor": {"digest": "sha256:aaa", "platform": {"architecture": "amd64", "os": "linux"}},
+            "SchemaV2Manifest": {"layers": [{"size": 1000}, {"size": 2500}]}}"#;
+        assert_eq!(manifest_size(single, "amd64"), Some(("sha256:aaa".into(), 3500)));
+
+        let multi = br#"[
+            {"Descriptor": {"digest": "sha256:x86", "platform": {"architecture": "amd64", "os": "linux"}},
+             "OCIManifest": {"layers": [{"size": 10}]}},
+            {"Descriptor": {"digest": "sha256:arm", "platform": {"architecture": "arm64", "os": "linux"}},
+             "OCIManifest": {"layers": [{"size": 20}, {"size": 5}]}}
+        ]"#;
+        assert_eq!(manifest_size(multi, docker_arch("aarch64\n")), Some(("sha256:arm".into(), 25)));
+        assert_eq!(manifest_size(multi, "s390x"), None);
+        assert_eq!(format_bytes(3 << 30), "3.0 GiB");
+        assert_eq!(format_bytes(300 << 20), "300 MiB");
+    }
+}
@@ -0,0 +1,148 @@
+//! `ops doctor --node`: checks a node's Docker daemon, ops serve and, when ops.toml has
+//! `[deploy.registry.mirrors]`, that the node pulls through those mirrors.
+
+use crate::commands::deploy::load_ops_toml;
+use crate::commands::mirrors::{hosts_toml_path, is_docker_hub};
+use crate::commands::ssh::SshSession;
+use crate::utils::shell_quote;
+use anyhow::{bail, Result};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, PartialEq)]
+struct Check {
+    name: String,
+    status: Status,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Check { name: name.into(), status, detail: detail.into() }
+    }
+}
+
+/// One SSH round trip: a "key|value" line per fact the checks need
+fn probe_script(mirrors: &BTreeMap<String, String>) -> String {
+    let mut script = String::from(concat!(
+        "printf 'docker|%s\\n' \"$(docker info --format '{{.ServerVersion}}' 2>/dev/null)\"\n",
+        "printf 'serve|%s\\n' \"$(systemctl is-active ops-serve 2>/dev/null)\"\n",
+    ));
+    if !mirrors.is_empty() {
+        script.push_str(concat!(
+            "printf 'mirrors|%s\\n' \"$(docker info --format '{{json .RegistryConfig.Mirrors}}' 2>/dev/null)\"\n",
+            "printf 'driver|%s\\n' \"$(docker info --format '{{json .DriverStatus}}' 2>/dev/null)\"\n",
+        ));
+    }
+    for (registry, url) in mirrors.iter().filter(|(r, _)| !is_docker_hub(r)) {
+        script.push_str(&format!(
+            "grep -qF {} {} 2>/dev/null && echo 'hosts|{}|yes' || echo 'hosts|{}|no'\n",
+            shell_quote(&format!("[host.\"{}\"]", url.trim_end_matches('/'))),
+            hosts_toml_path(registry), registry, registry
+        ));
+    }
+    script.push_str("true");
+    script
+}
+
+fn checks(output: &str, mirrors: &BTreeMap<String, String>) -> Vec<Check> {
+    let facts: BTreeMap<&str, &str> = output.lines().filter_map(|l| l.split_once('|')).collect();
+    let mut checks = Vec::new();
+
+    match facts.get("docker").copied().unwrap_or("") {
+        "" => checks.push(Check::new("docker", Status::Fail, "daemon not reachable")),
+        version => checks.push(Check::new("docker", Status::Ok, format!("Docker {}", version))),
+    }
+    match facts.get("serve").copied().unwrap_or("") {
+        "active" => checks.push(Check::new("ops serve", Status::Ok, "active")),
+        "" => checks.push(Check::new("ops serve", Status::Fail, "not installed (run `ops init` on the node)")),
+        state => checks.push(Check::new("ops serve", Status::Fail, state)),
+    }
+
+    let active: Vec<String> = serde_json::from_str::<Vec<String>>(facts.get("mirrors").copied().unwrap_or("[]"))
+        .unwrap_or_default()
+        .iter().map(|m| m.trim_end_matches('/').to_string()).collect();
+    let containerd_store = facts.get("driver").is_some_and(|d| d.contains("io.containerd.snapshotter"));
+    for (registry, url) in mirrors {
+        let name = format!("mirror {}", registry);
+        let configured = if is_docker_hub(registry) {
+            active.iter().any(|m| m == url.trim_end_matches('/'))
+        } else {
+            output.lines().any(|l| l == format!("hosts|{}|yes", registry))
+        };
+        if !configured {
+            checks.push(Check::new(name, Status::Fail, format!("{} not configured (run `ops init -f ops.toml` on the node)", url)));
+        } else if !is_docker_hub(registry) && !containerd_store {
+            checks.push(Check::new(name, Status::Warn,
+                format!("{} in {}, but Docker doesn't use the containerd image store, so pulls skip it", url, hosts_toml_path(registry))));
+        } else {
+            checks.push(Check::new(name, Status::Ok, url.clone()));
+        }
+    }
+    checks
+}
+
+/// ops doctor --node
+pub async fn handle_doctor(node_id: u64, file: String) -> Result<()> {
+    let mirrors = if Path::new(&file).exists() {
+        load_ops_toml(&file)?.deploy.registry.map(|r| r.mirrors).unwrap_or_default()
+    } else {
+        BTreeMap::new()
+    };
+
+    let session = SshSession::connect(&node_id.to_string()).await?;
+    let output = session.exec_output(&probe_script(&mirrors))?;
+    let checks = checks(&String::from_utf8_lossy(&output), &mirrors);
+
+    o_step!("{}", format!("Checking node #{}", node_id).bold());
+    for c in &checks {
+        let mark = match c.status {
+            Status::Ok => "✔".green(),
+            Status::Warn => "⚠".yellow(),
+            Status::Fail => "✘".red(),
+        };
+        o_detail!("   {} {:<20} {}", mark, c.name, c.detail);
+    }
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    if failed > 0 {
+        bail!("{} check(s) failed on node #{}", failed, node_id);
+    }
+    o_success!("{}", format!("✔ Node #{} looks healthy", node_id).green());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks() {
+        let mirrors = BTreeMap::from([
+            ("docker.io".to_string(), "https://mirror.gcr.io".to_string()),
+            ("ghcr.io".to_string(), "https://ghcr-cache.example.com".to_string()),
+        ]);
+        let script = probe_script(&mirrors);
+        assert!(script.contains("grep -qF '[host.\"https://ghcr-cache.example.com\"]' /etc/containerd/certs.d/ghcr.io/hosts.toml"));
+        assert!(!probe_script(&BTreeMap::new()).contains("mirrors|"));
+
+        let output = "docker|27.3.1\nserve|active\nmirrors|[\"https://mirror.gcr.io/\"]\ndriver|[[\"Backing Filesystem\",\"extfs\"]]\nhosts|ghcr.io|yes\n";
+        assert_eq!(checks(output, &mirrors), vec![
+            Check::new("docker", Status::Ok, "Docker 27.3.1"),
+            Check::new("ops serve", Status::Ok, "active"),
+            Check::new("mirror docker.io", Status::Ok, "https://mirror.gcr.io"),
+            Check::new("mirror ghcr.io", Status::Warn,
+                "https://ghcr-cache.example.com in /etc/containerd/certs.d/ghcr.io/hosts.toml, but Docker doesn't use the containerd image store, so pulls skip it"),
+        ]);
+
+        let broken = checks("docker|\nserve|inactive\nmirrors|null\nhosts|ghcr.io|no\n", &mirrors);
+        assert!(broken.iter().all(|c| c.status == Status::Fail));
+    }
+}
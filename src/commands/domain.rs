@@ -1,9 +1,14 @@
 use std::collections::HashSet;
 use anyhow::{bail, Context, Result};
 use colored::Colorize;
-use crate::{api, config, prompt};
-use crate::commands::deploy::load_ops_toml;
+use crate::{api, config, output, prompt};
+use crate::cloudflare::{caa_allows_node, plan_record, zone_candidates, Cloudflare, DnsRecord, RecordPlan};
+use crate::commands::login::read_stdin_secret;
+use std::io::IsTerminal;
+use crate::table::{Column, Table, TableOptions};
+use crate::commands::deploy::{load_ops_toml, load_ops_toml_env};
 use crate::types::OpsToml;
+use crate::error::{ApiErrorCode, OpsError};
 
 /// Resolve (project, app) from ops.toml + optional --app flag.
 fn resolve_project_app(ops_config: &OpsToml, app_flag: Option<&str>) -> Result<(String, String)> {
@@ -81,7 +86,7 @@ pub async fn handle_add(file: String, domain: String, app_flag: Option<String>)
     Ok(())
 }
 
-pub async fn handle_list(file: String, app_flag: Option<String>) -> Result<()> {
+pub async fn handle_list(file: String, app_flag: Option<String>, opts: &TableOptions) -> Result<()> {
     let cfg = config::load_config().context("Config error")?;
     let token = cfg.token.context("Please run `ops login` first.")?;
 
@@ -89,29 +94,36 @@ pub async fn handle_list(file: String, app_flag: Option<String>) -> Result<()> {
     let (project, app) = resolve_project_app(&ops_config, app_flag.as_deref())?;
 
     let resp = api::list_custom_domains(&token, &project, &app).await?;
+    if output::json() {
+        return output::print_json(&resp);
+    }
 
     o_step!("{} Domains for {}.{}:\n", "🌐".cyan(), app.green(), project.green());
-    o_detail!("  {} (default)", resp.default_domain.cyan());
+    // The default domain always comes first, then the custom ones
+    let mut table = Table::new(vec![
+        Column::new("domain"),
+        Column::new("status"),
+        Column::new("cname"),
+        Column::wide("created"),
+    ]);
+    table.row(vec![resp.default_domain.cyan().to_string(), "default".dimmed().to_string(), "-".into(), "-".into()]);
+    for d in &resp.domains {
+        let status_color = match d.status.as_str() {
+            "active" => d.status.green(),
+            "pending" => d.status.yellow(),
+            _ => d.status.red(),
+        };
+        // The CNAME target only matters until the domain is verified
+        let cname = match &d.cname_target {
+            Some(target) if d.status == "pending" => target.green().to_string(),
+            _ => "-".into(),
+        };
+        table.row(vec![d.domain.cyan().to_string(), status_color.to_string(), cname, d.created_at.clone()]);
+    }
+    table.print(opts)?;
 
     if resp.domains.is_empty() {
         o_detail!("\n  No custom domains configured.");
-    } else {
-        for d in &resp.domains {
-            let status_color = match d.status.as_str() {
-                "active" => d.status.green(),
-                "pending" => d.status.yellow(),
-                _ => d.status.red(),
-            };
-            if d.status == "pending" {
-                if let Some(ref target) = d.cname_target {
-                    o_detail!("  {} [{}] → CNAME {}", d.domain.cyan(), status_color, target.green());
-                } else {
-                    o_detail!("  {} [{}]", d.domain.cyan(), status_color);
-                }
-            } else {
-                o_detail!("  {} [{}]", d.domain.cyan(), status_color);
-            }
-        }
     }
 
     Ok(())
@@ -131,10 +143,15 @@ pub async fn handle_remove(file: String, domain: String) -> Result<()> {
     Ok(())
 }
 
-pub async fn handle_sync(file: String, app_flag: Option<String>, prune: bool, interactive: bool) -> Result<()> {
+pub async fn handle_sync(file: String, app_flag: Option<String>, prune: bool, remove: Option<Vec<String>>, env: Option<String>, interactive: bool) -> Result<()> {
     let cfg = config::load_config().context("Config error")?;
     let token = cfg.token.context("Please run `ops login` first.")?;
-    let ops_config = load_ops_toml(&file)?;
+    if prune && env.is_some() {
+        // The environments share each app's domains in the backend; the others would look extra
+        bail!("--prune can't be combined with --env: it would remove the domains of the other environments. \
+            Use --remove <domain,...> for the ones to drop.");
+    }
+    let ops_config = load_ops_toml_env(&file, env.as_deref())?;
 
     let sync_targets = build_sync_targets(&ops_config, app_flag.as_deref())?;
 
@@ -146,6 +163,8 @@ pub async fn handle_sync(file: String, app_flag: Option<String>, prune: bool, in
     let mut total_added: u32 = 0;
     let mut total_removed: u32 = 0;
     let mut total_errors: u32 = 0;
+    // --remove entries that matched an extra domain
+    let mut matched: HashSet<String> = HashSet::new();
 
     for (project, app_name, desired) in &sync_targets {
         o_step!("\n{} Syncing domains for {}.{}...", "🌐".cyan(), app_name.green(), project.green());
@@ -160,9 +179,10 @@ pub async fn handle_sync(file: String, app_flag: Option<String>, prune: bool, in
         let to_add: Vec<&str> = desired_set.iter()
             .filter(|d| !existing_set.contains(**d))
             .copied().collect();
-        let to_remove: Vec<&String> = existing_set.iter()
+        let mut to_remove: Vec<&String> = existing_set.iter()
             .filter(|d| !desired_set.contains(d.as_str()))
             .collect();
+        to_remove.sort();
 
         if to_add.is_empty() && to_remove.is_empty() {
             o_success!("   {} Already in sync ({} domain(s))", "✔".green(), desired.len());
@@ -180,52 +200,63 @@ pub async fn handle_sync(file: String, app_flag: Option<String>, prune: bool, in
                     total_added += 1;
                 }
                 Err(e) => {
-                    let msg = e.to_string();
-                    if msg.contains("already exists") || msg.contains("UNIQUE") {
+                    if matches!(OpsError::api_code(&e), Some(ApiErrorCode::DomainExists | ApiErrorCode::Conflict)) {
                         o_detail!("   {} {} (already exists)", "=".dimmed(), domain);
                     } else {
-                        o_error!("   {} Failed to add {}: {}", "✘".red(), domain, msg);
+                        o_error!("   {} Failed to add {}: {}", "✘".red(), domain, e);
                         total_errors += 1;
                     }
                 }
             }
         }
 
-        // Handle extra domains in backend
+        // Handle extra domains in backend: the ones named by --remove, or picked when pruning
         if !to_remove.is_empty() {
-            if prune {
-                if interactive {
-                    o_warn!("\n   Domains to remove from backend:");
-                    for d in &to_remove {
-                        o_warn!("     - {}", d);
-                    }
-                    if !prompt::confirm_no("Continue?", interactive)? {
-                        o_warn!("   Skipped pruning for {}.{}", app_name, project);
-                        continue;
-                    }
-                }
-                for domain in &to_remove {
-                    match api::remove_custom_domain(&token, domain).await {
-                        Ok(_) => {
-                            o_success!("   {} Removed {}", "-".red(), domain.yellow());
-                            total_removed += 1;
-                        }
-                        Err(e) => {
-                            o_error!("   {} Failed to remove {}: {}", "✘".red(), domain, e);
-                            total_errors += 1;
-                        }
-                    }
-                }
+            let selected: Vec<&String> = if let Some(list) = &remove {
+                to_remove.iter()
+                    .filter(|d| list.iter().any(|r| r.eq_ignore_ascii_case(d)))
+                    .copied().collect()
+            } else if prune {
+                o_warn!("\n   Domains in backend not in ops.toml:");
+                let options: Vec<&str> = to_remove.iter().map(|d| d.as_str()).collect();
+                prompt::multi_select("Remove which?", &options, true, interactive)?
+                    .into_iter().map(|i| to_remove[i]).collect()
             } else {
                 o_warn!("   {} {} domain(s) in backend not in ops.toml:", "⚠".yellow(), to_remove.len());
                 for d in &to_remove {
                     o_warn!("     - {}", d);
                 }
-                o_warn!("   Use --prune to remove them.");
+                o_warn!("   Use --prune to remove them, or --remove <domain,...> for some of them.");
+                continue;
+            };
+
+            let kept = to_remove.len() - selected.len();
+            if kept > 0 {
+                o_detail!("   {} Keeping {} extra domain(s)", "=".dimmed(), kept);
+            }
+            for domain in selected {
+                matched.insert(domain.to_lowercase());
+                match api::remove_custom_domain(&token, domain).await {
+                    Ok(_) => {
+                        o_success!("   {} Removed {}", "-".red(), domain.yellow());
+                        total_removed += 1;
+                    }
+                    Err(e) => {
+                        o_error!("   {} Failed to remove {}: {}", "✘".red(), domain, e);
+                        total_errors += 1;
+                    }
+                }
             }
         }
     }
 
+    // A --remove entry that is declared in ops.toml or not in the backend is left alone
+    for domain in remove.iter().flatten() {
+        if !matched.contains(&domain.to_lowercase()) {
+            o_warn!("{} {} is not an extra backend domain of the synced apps, not removed", "⚠".yellow(), domain);
+        }
+    }
+
     // Summary
     o_result!("\n{} Domain sync complete: {} added, {} removed, {} errors",
         "✔".green(), total_added, total_removed, total_errors);
@@ -236,3 +267,116 @@ pub async fn handle_sync(file: String, app_flag: Option<String>, prune: bool, in
 
     Ok(())
 }
+
+/// "A 203.0.113.1 (proxied), AAAA ..." for the replace prompt
+fn describe_records(records: &[DnsRecord]) -> String {
+    records.iter().map(|r| format!("{} {}{}", r.kind, r.content, if r.proxied { " (proxied)" } else { "" }))
+        .collect::<Vec<_>>().join(", ")
+}
+
+/// ops domain cloudflare token: read a token from a prompt or stdin, check it with Cloudflare and save it
+pub async fn handle_cloudflare_token() -> Result<()> {
+    let token = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password("Cloudflare API token: ")?
+    } else {
+        read_stdin_secret("token")?
+    };
+    let token = token.trim().to_string();
+    o_step!("Verifying token...");
+    Cloudflare::new(&token).verify_token().await.context("Cloudflare rejected the token")?;
+    let saved_to = config::save_cloudflare_token(token).context("Failed to save credentials")?;
+    o_success!("{} {}", "✔ Cloudflare token saved to the".green(), saved_to);
+    Ok(())
+}
+
+/// ops domain cloudflare link: add the domain, point a CNAME at the app in its Cloudflare zone
+/// and fix the zone settings that keep the node from getting a certificate
+pub async fn handle_cloudflare_link(file: String, domain: String, app_flag: Option<String>, proxied: bool, yes: bool, interactive: bool) -> Result<()> {
+    let cf_token = config::cloudflare_token()?
+        .context("No Cloudflare API token. Run `ops domain cloudflare token` or set CLOUDFLARE_API_TOKEN.")?;
+    let token = config::load_config().context("Config error")?.token.context("Please run `ops login` first.")?;
+    let ops_config = load_ops_toml(&file)?;
+    let (project, app) = resolve_project_app(&ops_config, app_flag.as_deref())?;
+    let cf = Cloudflare::new(&cf_token);
+
+    o_step!("{} Linking {} to {}.{} via Cloudflare...", "🌐".cyan(), domain.green(), app, project);
+
+    // 1. The domain on the app, or its CNAME target if it is there already
+    let target = match api::add_custom_domain(&token, &project, &app, &domain).await {
+        Ok(resp) => {
+            o_detail!("   {} Added {} to {}.{}", "✔".green(), domain.cyan(), app, project);
+            resp.cname_target
+        }
+        Err(e) if matches!(OpsError::api_code(&e), Some(ApiErrorCode::DomainExists | ApiErrorCode::Conflict)) => {
+            let list = api::list_custom_domains(&token, &project, &app).await?;
+            let item = list.domains.into_iter().find(|d| d.domain.eq_ignore_ascii_case(&domain))
+                .with_context(|| format!("{} is already used by another app", domain))?;
+            o_detail!("   {} {} is already on {}.{}", "=".dimmed(), domain.cyan(), app, project);
+            item.cname_target.unwrap_or(list.default_domain)
+        }
+        Err(e) => return Err(e.context("Failed to add domain")),
+    };
+
+    // 2. The CNAME in the zone
+    let zone = cf.find_zone(&domain).await?;
+    o_detail!("   {} Zone {}", "✔".green(), zone.name.cyan());
+    let existing = cf.dns_records(&zone, &domain, None).await?;
+    match plan_record(&existing, &target, proxied) {
+        RecordPlan::Unchanged => o_detail!("   {} CNAME {} → {} already set", "=".dimmed(), domain, target),
+        RecordPlan::Update(id) => {
+            cf.put_cname(&zone, Some(&id), &domain, &target, proxied).await?;
+            o_detail!("   {} Updated CNAME {} → {}", "✔".green(), domain, target.green());
+        }
+        RecordPlan::Create => {
+            cf.put_cname(&zone, None, &domain, &target, proxied).await?;
+            o_detail!("   {} Created CNAME {} → {}", "✔".green(), domain, target.green());
+        }
+        RecordPlan::Replace(records) => {
+            if !yes && !prompt::confirm_no(&format!("Replace {} with a CNAME to {}?", describe_records(&records), target), interactive)? {
+                bail!("{} has {}. Remove them in Cloudflare, or pass --yes to replace them.", domain, describe_records(&records));
+            }
+            for r in &records {
+                cf.delete_record(&zone, &r.id).await?;
+            }
+            cf.put_cname(&zone, None, &domain, &target, proxied).await?;
+            o_detail!("   {} Replaced {} record(s) with CNAME {} → {}", "✔".green(), records.len(), domain, target.green());
+        }
+    }
+    o_detail!("   {} Proxy: {}", "✔".green(), if proxied { "proxied (orange cloud)" } else { "DNS only" });
+
+    // 3. Settings that break issuance or loop when proxied
+    let ssl = cf.setting(&zone, "ssl").await?;
+    if proxied && (ssl == "off" || ssl == "flexible") {
+        // Flexible talks plain HTTP to the node, which redirects to HTTPS: an endless loop.
+        // The mode is zone-wide, so the other sites of the zone change with it.
+        let question = format!("Change the SSL mode of the whole {} zone from {} to full?", zone.name, ssl);
+        if yes || prompt::confirm_no(&question, interactive)? {
+            cf.set_setting(&zone, "ssl", "full").await.context("Failed to set SSL mode")?;
+            o_detail!("   {} SSL mode {} → {}", "✔".green(), ssl.yellow(), "full".green());
+        } else {
+            o_warn!("   {} SSL mode of {} left at {}: proxied requests to {} will redirect forever. \
+                Set it to Full in Cloudflare, or re-run with --yes.", "⚠".yellow(), zone.name, ssl, domain);
+        }
+    } else {
+        o_detail!("   {} SSL mode {}", "✔".green(), ssl);
+    }
+    if proxied && cf.setting(&zone, "always_use_https").await? == "on" {
+        o_warn!("   {} Always Use HTTPS is on for {}: the node's first HTTP certificate challenge gets redirected \
+            to HTTPS before it has a certificate. Turn it off until `ops domain list` shows the domain active.", "⚠".yellow(), zone.name);
+    }
+    // CAA is inherited from the closest name that has records, up to the zone apex
+    for name in zone_candidates(&domain).iter().take_while(|n| n.len() >= zone.name.len()) {
+        let caa: Vec<String> = cf.dns_records(&zone, name, Some("CAA")).await?.into_iter().map(|r| r.content).collect();
+        if caa.is_empty() {
+            continue;
+        }
+        if !caa_allows_node(&caa) {
+            o_warn!("   {} CAA records on {} don't allow letsencrypt.org or sectigo.com, so the node can't get a certificate.", "⚠".yellow(), name);
+        }
+        break;
+    }
+
+    o_success!("\n{} {} → {}", "✔".green(), domain.cyan(), target.green());
+    o_detail!("   {}", "The certificate is issued once DNS resolves; check with `ops domain list`.".dimmed());
+    Ok(())
+}
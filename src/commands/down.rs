@@ -0,0 +1,95 @@
+use crate::commands::deploy::{compose_args, load_ops_toml};
+use crate::commands::ssh::SshSession;
+use crate::router::Router;
+use crate::{api, config, prompt};
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+/// ops down: stop an app's containers on all bound nodes and remove its Caddy routes.
+/// With `delete`, the app record goes too, once nothing of it is left on the nodes.
+pub async fn handle_down(file: String, app_filter: Option<String>, keep_routes: bool, delete: bool, force: bool, interactive: bool) -> Result<()> {
+    let ops_config = load_ops_toml(&file)?;
+    let project = &ops_config.project;
+
+    // [[apps]] to take down; without [[apps]] the project itself is the app
+    let apps: Vec<(String, Vec<String>)> = if ops_config.apps.is_empty() {
+        vec![(project.clone(), Vec::new())]
+    } else {
+        ops_config.apps.iter()
+            .filter(|a| app_filter.as_ref().is_none_or(|f| *f == a.name))
+            .map(|a| (a.name.clone(), a.services.clone()))
+            .collect()
+    };
+    if apps.is_empty() {
+        anyhow::bail!("App '{}' not found in {}", app_filter.unwrap_or_default(), file);
+    }
+    // Other apps share the compose project, so an app is only ever stopped by its services
+    if let Some((app, _)) = apps.iter().find(|(_, services)| services.is_empty() && !ops_config.apps.is_empty()) {
+        anyhow::bail!("App '{}' lists no services in {}; add `services = [...]` so `ops down` knows what to stop", app, file);
+    }
+
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let names: Vec<String> = apps.iter().map(|(a, _)| format!("{}.{}", a, project)).collect();
+    if !force {
+        o_warn!("{} This will stop {} on all bound nodes.", "⚠".yellow(), names.join(", ").bold());
+        if delete {
+            o_warn!("   The app records are deleted from OPS as well.");
+        }
+        if !interactive {
+            anyhow::bail!("Destructive operation requires --force in non-interactive mode");
+        }
+        if !prompt::confirm_no("Continue?", interactive)? {
+            o_warn!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    let router = Router::from_config(ops_config.deploy.router.as_deref())?;
+    let compose = compose_args(&ops_config);
+    let compose_arg = if compose.is_empty() { String::new() } else { format!(" {}", compose) };
+
+    for (app, services) in &apps {
+        let resp = api::get_app_deploy_targets(&token, project, app).await
+            .with_context(|| format!("Failed to get deploy targets for {}.{}", app, project))?;
+        if resp.targets.is_empty() {
+            o_warn!("No nodes bound to {}.{}, nothing to stop", app, project);
+        }
+
+        for t in &resp.targets {
+            o_step!("\n{} {}.{} on node #{}", "🛑".cyan(), app.green(), project, t.node_id);
+            let session = SshSession::connect(&t.node_id.to_string()).await?;
+
+            // The app's compose services; without [[apps]], every service of the compose files.
+            // Not `down`, which would also take the networks other apps on the project use.
+            let down_cmd = format!("cd {} && docker compose{} rm -sf {}", ops_config.deploy_path, compose_arg, services.join(" "));
+            session.exec(down_cmd.trim_end(), None)?;
+
+            // Zero-downtime containers: {project}-{svc}-{deployment_id}
+            for svc in services {
+                session.exec(&format!(
+                    "docker ps -aq --filter 'name=^{}-{}-[0-9]+$' | xargs -r docker rm -f >/dev/null",
+                    ops_config.compose_project(), svc
+                ), None)?;
+            }
+            o_success!("   ✔ Containers stopped");
+
+            if !keep_routes {
+                let removed = router.remove_app_fragments(&session, app, project)?;
+                for f in &removed {
+                    o_detail!("   ✔ Removed route {}", f.dimmed());
+                }
+            }
+        }
+
+        if delete {
+            api::delete_app(&token, project, app).await
+                .with_context(|| format!("Failed to delete {}.{}", app, project))?;
+            o_success!("   ✔ Deleted {}.{} from OPS", app, project);
+        }
+    }
+
+    o_result!("\n{} Down: {}", "✅".green(), names.join(", "));
+    Ok(())
+}
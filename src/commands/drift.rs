@@ -0,0 +1,282 @@
+use crate::commands::deploy::{load_ops_toml, resolve_app_name};
+use crate::commands::ssh::SshSession;
+use crate::router::{parse_owner, Router};
+use crate::types::{DeployedState, NodeSnapshot, OpsToml, StateScope};
+use crate::{api, config};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Files docker compose picks up when no `-f` is given
+pub const DEFAULT_COMPOSE_FILES: [&str; 4] = ["compose.yaml", "compose.yml", "docker-compose.yml", "docker-compose.yaml"];
+
+/// What `ops deploy` records for a node: everything needed to re-read its state later
+pub fn state_scope(config: &OpsToml) -> StateScope {
+    let mut apps: Vec<String> = config.apps.iter().map(|a| a.name.clone()).collect();
+    let app_name = resolve_app_name(config);
+    if !apps.contains(&app_name) {
+        apps.push(app_name);
+    }
+    StateScope {
+        project: config.project.clone(),
+        deploy_path: config.deploy_path.clone(),
+        compose_project: config.compose_project().to_string(),
+        compose_files: config.deploy.compose_files.clone()
+            .unwrap_or_else(|| DEFAULT_COMPOSE_FILES.iter().map(|f| f.to_string()).collect()),
+        env_files: config.env_files.iter().map(|ef| ef.remote.clone()).collect(),
+        apps,
+        services: config.apps.iter().flat_map(|a| a.services.clone()).collect(),
+        router: config.deploy.router.clone().unwrap_or_else(|| "caddy".into()),
+        app_services: config.apps.iter().map(|a| (a.name.clone(), a.services.clone())).collect(),
+    }
+}
+
+/// Read a node's current state in one SSH round trip
+pub fn capture_snapshot(session: &SshSession, scope: &StateScope) -> Result<NodeSnapshot> {
+    let router = Router::from_config(Some(&scope.router))?;
+    let mut script = format!(
+        "cd {} 2>/dev/null || exit 0\nprintf 'compose|%s\\n' \"$(cat {} 2>/dev/null | sha256sum | cut -d' ' -f1)\"\n",
+        scope.deploy_path,
+        scope.compose_files.join(" ")
+    );
+    if !scope.env_files.is_empty() {
+        script.push_str(&format!(
+            "for f in {}; do [ -f \"$f\" ] && printf 'env|%s|%s\\n' \"$f\" \"$(sha256sum < \"$f\" | cut -d' ' -f1)\"; done\n",
+            scope.env_files.join(" ")
+        ));
+    }
+    script.push_str(concat!(
+        "docker ps -q | xargs -r docker inspect --format ",
+        "'container|{{.Name}}|{{index .Config.Labels \"com.docker.compose.project\"}}|{{index .Config.Labels \"com.docker.compose.service\"}}|{{.Image}}'\n",
+    ));
+    script.push_str(&format!(
+        "for f in {}; do [ -e \"$f\" ] && printf 'route|%s|%s\\n' \"$(sed -n 1p \"$f\")\" \"$(sed -n 2p \"$f\")\"; done\ntrue",
+        router.fragment_globs().join(" ")
+    ));
+
+    let output = session.exec_output(&script)?;
+    Ok(parse_snapshot(&String::from_utf8_lossy(&output), scope))
+}
+
+fn parse_snapshot(output: &str, scope: &StateScope) -> NodeSnapshot {
+    let mut snapshot = NodeSnapshot::default();
+    let mut images: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut domains = BTreeSet::new();
+
+    for line in output.lines() {
+        match line.split('|').collect::<Vec<_>>().as_slice() {
+            ["compose", sum] => snapshot.compose_checksum = sum.to_string(),
+            ["env", path, sum] => {
+                snapshot.env_files.insert(path.to_string(), sum.to_string());
+            }
+            ["container", name, project, service, image] => {
+                let service = if *project == scope.compose_project && !service.is_empty() {
+                    Some(service.to_string())
+                } else {
+                    deploy_id_service(name.trim_start_matches('/'), scope)
+                };
+                if let Some(service) = service {
+                    images.entry(service).or_default().insert(image.to_string());
+                }
+            }
+            ["route", header, label]
+                if parse_owner(header).is_some_and(|(app, project)| project == scope.project && scope.apps.contains(&app)) =>
+            {
+                domains.insert(label.trim_start_matches('#').trim().to_string());
+            }
+            _ => {}
+        }
+    }
+
+    snapshot.images = images.into_iter()
+        .map(|(svc, ids)| (svc, ids.into_iter().collect::<Vec<_>>().join(",")))
+        .collect();
+    snapshot.domains = domains.into_iter().collect();
+    snapshot
+}
+
+/// `{compose_project}-{svc}-{deployment_id}` → svc
+fn deploy_id_service(name: &str, scope: &StateScope) -> Option<String> {
+    let rest = name.strip_prefix(&format!("{}-", scope.compose_project))?;
+    let (svc, id) = rest.rsplit_once('-')?;
+    (id.chars().all(|c| c.is_ascii_digit()) && scope.services.iter().any(|s| s == svc)).then(|| svc.to_string())
+}
+
+/// One difference between the recorded and the actual state
+#[derive(Debug, PartialEq)]
+struct Drift {
+    kind: &'static str,
+    subject: String,
+    expected: String,
+    actual: String,
+}
+
+const MISSING: &str = "(missing)";
+
+fn diff(expected: &NodeSnapshot, actual: &NodeSnapshot) -> Vec<Drift> {
+    let mut drifts = Vec::new();
+    let mut push = |kind, subject: &str, expected: Option<&String>, actual: Option<&String>| {
+        if expected != actual {
+            drifts.push(Drift {
+                kind,
+                subject: subject.to_string(),
+                expected: expected.map(|v| short(v)).unwrap_or_else(|| MISSING.into()),
+                actual: actual.map(|v| short(v)).unwrap_or_else(|| MISSING.into()),
+            });
+        }
+    };
+
+    push("compose", "compose files",
+        Some(&expected.compose_checksum).filter(|s| !s.is_empty()),
+        Some(&actual.compose_checksum).filter(|s| !s.is_empty()));
+
+    let env_files: BTreeSet<&String> = expected.env_files.keys().chain(actual.env_files.keys()).collect();
+    for path in env_files {
+        push("env", path, expected.env_files.get(path), actual.env_files.get(path));
+    }
+
+    let services: BTreeSet<&String> = expected.images.keys().chain(actual.images.keys()).collect();
+    for svc in services {
+        push("image", svc, expected.images.get(svc), actual.images.get(svc));
+    }
+
+    let present = "present".to_string();
+    for domain in expected.domains.iter().filter(|d| !actual.domains.contains(d)) {
+        push("domain", domain, Some(&present), None);
+    }
+    for domain in actual.domains.iter().filter(|d| !expected.domains.contains(d)) {
+        push("domain", domain, None, Some(&present));
+    }
+
+    drifts
+}
+
+/// Shorten checksums and image IDs for display
+fn short(value: &str) -> String {
+    value.split(',')
+        .map(|v| {
+            let v = v.strip_prefix("sha256:").unwrap_or(v);
+            if v.len() > 12 && v.chars().all(|c| c.is_ascii_hexdigit()) { &v[..12] } else { v }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// ops drift [app.project]: compare each node against the state recorded by its last deploy
+pub async fn handle_drift(file: String, target: Option<String>) -> Result<()> {
+    let (app, project) = match target {
+        Some(t) => {
+            let (app, project) = t.split_once('.')
+                .with_context(|| format!("Invalid target '{}'. Expected app.project (e.g., api.RedQ)", t))?;
+            (app.to_string(), project.to_string())
+        }
+        None => {
+            let config = load_ops_toml(&file)?;
+            (resolve_app_name(&config), config.project)
+        }
+    };
+
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let targets = api::get_app_deploy_targets(&token, &project, &app).await
+        .context("Failed to get deploy targets")?
+        .targets;
+    if targets.is_empty() {
+        bail!("No nodes bound to {}.{}", app, project);
+    }
+    let recorded = api::get_deployed_state(&token, &project, &app).await
+        .context("Failed to get recorded deploy state")?
+        .nodes;
+
+    o_step!("{} {}.{} ({} node(s))\n", "🔍 Drift:".cyan(), app.green(), project.green(), targets.len());
+
+    let mut drifted = 0;
+    for t in &targets {
+        let node = format!("node {} ({})", t.node_id, t.ip_address);
+        let Some(record) = recorded.iter().find(|r| r.node_id == t.node_id) else {
+            o_warn!("  {} {} no recorded state, deploy once to record it", "?".yellow(), node);
+            continue;
+        };
+        let DeployedState { scope, snapshot: expected } = &record.state;
+
+        let actual = match SshSession::connect(&t.node_id.to_string()).await
+            .and_then(|session| capture_snapshot(&session, scope))
+        {
+            Ok(s) => s,
+            Err(e) => {
+                o_error!("  {} {} {}", "✘".red(), node, e);
+                drifted += 1;
+                continue;
+            }
+        };
+
+        let drifts = diff(expected, &actual);
+        if drifts.is_empty() {
+            o_result!("  {} {} in sync with deployment #{}", "✔".green(), node, record.deployment_id);
+            continue;
+        }
+        drifted += 1;
+        o_result!(
+            "  {} {} drifted from deployment #{}{}",
+            "✘".red(), node.yellow(), record.deployment_id,
+            record.recorded_at.as_deref().map(|at| format!(" ({})", at)).unwrap_or_default()
+        );
+        for d in &drifts {
+            o_result!("      {:<8} {:<24} {} → {}", d.kind, d.subject, d.expected.dimmed(), d.actual.red());
+        }
+    }
+
+    if drifted > 0 {
+        bail!("{} node(s) drifted from their last deploy", drifted);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope() -> StateScope {
+        StateScope {
+            project: "RedQ".into(),
+            deploy_path: "/opt/redq".into(),
+            compose_project: "redq".into(),
+            compose_files: vec!["docker-compose.yml".into()],
+            env_files: vec![".env".into()],
+            apps: vec!["api".into()],
+            services: vec!["api".into()],
+            router: "caddy".into(),
+            app_services: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_snapshot_and_diff() {
+        let recorded = parse_snapshot(
+            "compose|aaaa\nenv|.env|bbbb\n\
+             container|/redq-api-42|||sha256:1111\ncontainer|/redq-db-1|redq|db|sha256:2222\n\
+             container|/other-web-1|other|web|sha256:3333\n\
+             route|# ops-owner: api.RedQ|# api.RedQ\nroute|# ops-owner: web.Other|# web.Other\n",
+            &scope(),
+        );
+        assert_eq!(recorded.images.get("api").map(String::as_str), Some("sha256:1111"));
+        assert_eq!(recorded.images.get("db").map(String::as_str), Some("sha256:2222"));
+        assert_eq!(recorded.images.len(), 2);
+        assert_eq!(recorded.domains, vec!["api.RedQ".to_string()]);
+        assert!(diff(&recorded, &recorded).is_empty());
+
+        // Hand-edited env file, stale api container, removed route
+        let actual = parse_snapshot(
+            "compose|aaaa\nenv|.env|cccc\n\
+             container|/redq-api-41|||sha256:9999\ncontainer|/redq-db-1|redq|db|sha256:2222\n",
+            &scope(),
+        );
+        let kinds: Vec<_> = diff(&recorded, &actual).iter().map(|d| (d.kind, d.subject.clone())).collect();
+        assert_eq!(kinds, vec![
+            ("env", ".env".to_string()),
+            ("image", "api".to_string()),
+            ("domain", "api.RedQ".to_string()),
+        ]);
+    }
+}
@@ -1,5 +1,5 @@
 // src/commands/env.rs
-use crate::{config, utils};
+use crate::commands::common::resolve_target;
 use crate::commands::ssh::{execute_remote_command, execute_remote_command_with_output}; // 核心修复：导入函数
 use anyhow::{Context, Result};
 use colored::Colorize;
@@ -7,7 +7,8 @@ use std::fs;
 use std::process::Command;
 
 // ops env upload <target>
-pub async fn handle_upload(target_str: String) -> Result<()> {
+pub async fn handle_upload(target_str: Option<String>, interactive: bool) -> Result<()> {
+    let target_str = resolve_target(target_str, interactive).await?.to_string();
     let local_env_path = "./.env";
     if !fs::metadata(local_env_path).is_ok() {
         return Err(anyhow::anyhow!("Local file './.env' not found."));
@@ -30,7 +31,8 @@ pub async fn handle_upload(target_str: String) -> Result<()> {
 }
 
 // ops env download <target>
-pub async fn handle_download(target_str: String) -> Result<()> {
+pub async fn handle_download(target_str: Option<String>, interactive: bool) -> Result<()> {
+    let target_str = resolve_target(target_str, interactive).await?.to_string();
     o_step!("Downloading .env from {}...", target_str.cyan());
     
     let remote_path = format!("/opt/judge/.env");
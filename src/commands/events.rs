@@ -0,0 +1,54 @@
+//! `ops events`: what `ops serve` reported from an app's nodes, e.g. containers that crashed
+//! or restarted in a loop right after a deploy, with their last log lines.
+
+use crate::commands::deploy::{load_ops_toml, resolve_app_name};
+use crate::types::EventInfo;
+use crate::{api, config};
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+/// One line per event, with its log lines indented below when `show_logs`
+pub fn print_events(events: &[EventInfo], show_logs: bool) {
+    for e in events {
+        let service = e.event.app.as_deref().map_or_else(|| e.event.service.clone(), |app| format!("{}/{}", app, e.event.service));
+        o_detail!("  {}  {}  node {}  {}  {}",
+            e.created_at.dimmed(), e.event.kind.red(), e.node_id, service.cyan(), e.event.reason);
+        if show_logs && !e.event.logs.trim().is_empty() {
+            for line in e.event.logs.lines() {
+                o_detail!("      {}", line.dimmed());
+            }
+        }
+    }
+}
+
+/// ops events
+pub async fn handle_events(file: String, target: Option<String>, limit: u32, show_logs: bool) -> Result<()> {
+    let (app, project) = match target {
+        Some(t) => {
+            let (app, project) = t.split_once('.')
+                .with_context(|| format!("Invalid target '{}'. Expected app.project (e.g., api.RedQ)", t))?;
+            (app.to_string(), project.to_string())
+        }
+        None => {
+            let config = load_ops_toml(&file)?;
+            (resolve_app_name(&config), config.project)
+        }
+    };
+
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let events = api::list_app_events(&token, &project, &app, limit).await
+        .context("Failed to get events")?
+        .events;
+
+    o_step!("{} {}.{}\n", "🔔 Events:".cyan(), app.green(), project.green());
+    if events.is_empty() {
+        o_detail!("  {}", "No events".dimmed());
+        return Ok(());
+    }
+    print_events(&events, show_logs);
+    if !show_logs && events.iter().any(|e| !e.event.logs.trim().is_empty()) {
+        o_detail!("\n  {}", "Run with --logs to see the last log lines of each container.".dimmed());
+    }
+    Ok(())
+}
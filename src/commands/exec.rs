@@ -0,0 +1,74 @@
+use crate::commands::common::resolve_deploy_target;
+use crate::commands::deploy::{compose_args, env_prefix, load_ops_toml};
+use crate::commands::ssh::{self, SshOptions};
+use crate::utils::shell_quote;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::io::IsTerminal;
+
+/// ops exec <service> -- <cmd>: `docker compose exec` in the service's running container on the
+/// deploy target. Runs `sh` without a command. Exits with the command's exit code.
+pub async fn handle_exec(
+    file: String,
+    service: String,
+    command: Vec<String>,
+    target: Option<String>,
+    env_vars: Vec<String>,
+    user: Option<String>,
+    set_vars: Vec<String>,
+) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+
+    for kv in env_vars.iter().chain(&set_vars) {
+        if !kv.contains('=') {
+            bail!("Invalid KEY=VALUE: {}", kv);
+        }
+    }
+
+    // The node of the app that owns this service
+    let app = config.apps.iter()
+        .find(|a| a.services.contains(&service))
+        .map(|a| a.name.as_str());
+    let target = resolve_deploy_target(&config, app, target).await?;
+
+    let command = if command.is_empty() { vec!["sh".to_string()] } else { command };
+
+    // `-it` when attached to a terminal; compose exec allocates a TTY unless told not to
+    let tty = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    let mut exec_args = vec!["exec".to_string()];
+    if !tty {
+        exec_args.push("-T".into());
+    }
+    if let Some(user) = &user {
+        exec_args.push("--user".into());
+        exec_args.push(shell_quote(user));
+    }
+    for kv in &env_vars {
+        exec_args.push("-e".into());
+        exec_args.push(shell_quote(kv));
+    }
+    exec_args.push(shell_quote(&service));
+    exec_args.extend(command.iter().map(|w| shell_quote(w)));
+
+    let remote_cmd = format!(
+        "cd {} && {}docker compose {} {}",
+        config.deploy_path,
+        env_prefix(&set_vars),
+        compose_args(&config),
+        exec_args.join(" ")
+    );
+
+    o_step!("{} {} in {} on {}", "▶".cyan(), command.join(" ").bold(), service.cyan(), target.to_string().green());
+    o_debug!("   {}", remote_cmd);
+
+    let opts = SshOptions { tty, ..Default::default() };
+    let (mut cmd, _temp_key_file) = ssh::build_ssh_command_with(&target, &opts).await?;
+    cmd.arg(&remote_cmd);
+
+    let status = cmd.status().context("Failed to run ssh")?;
+    if !status.success() {
+        // Propagate the command's exit code (ssh passes it through)
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
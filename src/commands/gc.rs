@@ -0,0 +1,163 @@
+//! `ops gc`: clean up tunnels and branch previews whose heartbeats stopped, e.g. a tunnel whose
+//! laptop went to sleep for good or a preview on a node that was rebuilt. The node side (routes,
+//! and for previews the containers and checkout) goes through `POST /gc` of the node's ops serve;
+//! the DNS names and backend records go once the node confirms.
+
+use crate::commands::ssh::SshSession;
+use crate::commands::tunnel;
+use crate::error::{ApiErrorCode, OpsError};
+use crate::node_state;
+use crate::serve::gc::{GcRequest, GcResult};
+use crate::types::StaleResources;
+use crate::utils::shell_quote;
+use crate::{api, config, prompt};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::collections::BTreeMap;
+
+/// The `/gc` request for each node the stale resources live on
+fn requests_by_node(stale: &StaleResources) -> BTreeMap<i64, GcRequest> {
+    let mut by_node: BTreeMap<i64, GcRequest> = BTreeMap::new();
+    for t in &stale.tunnels {
+        by_node.entry(t.node_id as i64).or_default().tunnels.push(format!("{}.{}", t.subdomain, t.project_name));
+    }
+    for p in &stale.previews {
+        by_node.entry(p.node_id).or_default().previews.push(format!("{}.{}", p.slug, p.project));
+    }
+    by_node
+}
+
+/// Ask ops serve on `node_id` to remove the node side
+async fn collect_on_node(node_id: i64, req: &GcRequest) -> Result<GcResult> {
+    let session = SshSession::connect(&node_id.to_string()).await?;
+    let args = format!("-m 300 -X POST -H 'Content-Type: application/json' -d {}", shell_quote(&serde_json::to_string(req)?));
+    let cmd = node_state::serve_request(&args, "/gc");
+    let out = session.exec_output(&cmd)
+        .context("ops serve did not answer POST /gc; run `ops update` on the node")?;
+    serde_json::from_slice(&out).context("Unexpected /gc response from ops serve")
+}
+
+fn not_found(e: &anyhow::Error) -> bool {
+    matches!(OpsError::api_code(e), Some(ApiErrorCode::NotFound))
+}
+
+/// Drop the resources that never sent a heartbeat: they may still be starting up. Returns how many.
+fn skip_never_seen(stale: &mut StaleResources) -> usize {
+    let before = stale.tunnels.len() + stale.previews.len();
+    stale.tunnels.retain(|t| t.last_heartbeat_at.is_some());
+    stale.previews.retain(|p| p.last_heartbeat_at.is_some());
+    before - stale.tunnels.len() - stale.previews.len()
+}
+
+/// ops gc [--dry-run] [--force]
+pub async fn handle_gc(dry_run: bool, force: bool, interactive: bool) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let mut stale = api::list_stale(&token).await.context("Failed to list stale resources")?;
+    if !force {
+        let skipped = skip_never_seen(&mut stale);
+        if skipped > 0 {
+            o_detail!("   Skipping {} resource(s) that never sent a heartbeat; --force includes them", skipped);
+        }
+    }
+    if stale.tunnels.is_empty() && stale.previews.is_empty() {
+        o_result!("{} Nothing to clean up", "✔".green());
+        return Ok(());
+    }
+
+    let last_seen = |at: &Option<String>| at.clone().unwrap_or_else(|| "never".into());
+    o_step!("{}", "Stale resources (no recent heartbeat):".cyan());
+    for t in &stale.tunnels {
+        o_detail!("   tunnel  #{:<6} https://{} on node {}, last heartbeat {}", t.id, t.domain, t.node_id, last_seen(&t.last_heartbeat_at));
+    }
+    for p in &stale.previews {
+        o_detail!("   preview {} ({}.{}) on node {}, last heartbeat {}", p.branch, p.slug, p.project, p.node_id, last_seen(&p.last_heartbeat_at));
+    }
+    if dry_run {
+        o_detail!("\n   Dry run; nothing removed.");
+        return Ok(());
+    }
+    if !prompt::confirm_yes("Remove them?", interactive)? {
+        o_warn!("Cancelled.");
+        return Ok(());
+    }
+
+    let mut removed = 0;
+    let mut failed = 0;
+    for (node_id, req) in requests_by_node(&stale) {
+        let result = match collect_on_node(node_id, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                o_warn!("   {} node {}: {:#}", "✘".red(), node_id, e);
+                failed += req.tunnels.len() + req.previews.len();
+                continue;
+            }
+        };
+        for f in &result.failed {
+            o_warn!("   {} node {}: {}", "✘".red(), node_id, f);
+        }
+        failed += result.failed.len();
+
+        for t in stale.tunnels.iter().filter(|t| result.removed.contains(&format!("{}.{}", t.subdomain, t.project_name))) {
+            match api::delete_tunnel(&token, t.id).await {
+                Ok(_) => {}
+                Err(e) if not_found(&e) => {}
+                Err(e) => {
+                    o_warn!("   {} tunnel #{}: {:#}", "✘".red(), t.id, e);
+                    failed += 1;
+                    continue;
+                }
+            }
+            let _ = tunnel::forget(t.id);
+            o_success!("   {} tunnel #{} ({})", "✔".green(), t.id, t.domain);
+            removed += 1;
+        }
+        for p in stale.previews.iter().filter(|p| result.removed.contains(&format!("{}.{}", p.slug, p.project))) {
+            match api::delete_preview(&token, &p.project, &p.slug).await {
+                Ok(_) => {}
+                Err(e) if not_found(&e) => {}
+                Err(e) => {
+                    o_warn!("   {} preview {}: {:#}", "✘".red(), p.branch, e);
+                    failed += 1;
+                    continue;
+                }
+            }
+            o_success!("   {} preview {} ({}.{})", "✔".green(), p.branch, p.slug, p.project);
+            removed += 1;
+        }
+    }
+
+    if failed > 0 {
+        bail!("Removed {}, {} failed; run `ops gc` again to retry", removed, failed);
+    }
+    o_result!("\n{} Removed {} stale resource(s)", "✅".green(), removed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{StalePreview, TunnelInfo};
+
+    #[test]
+    fn test_requests_by_node() {
+        let mut stale = StaleResources {
+            tunnels: vec![TunnelInfo {
+                id: 3, subdomain: "hook".into(), project_name: "redq".into(), node_id: 7,
+                domain: "hook.redq.ops.autos".into(), last_heartbeat_at: None,
+            }],
+            previews: vec![
+                StalePreview { project: "redq".into(), slug: "feature-x".into(), branch: "feature/x".into(), node_id: 7, last_heartbeat_at: None },
+                StalePreview { project: "shop".into(), slug: "fix".into(), branch: "fix".into(), node_id: 2, last_heartbeat_at: None },
+            ],
+        };
+        let by_node = requests_by_node(&stale);
+        assert_eq!(by_node.keys().copied().collect::<Vec<_>>(), vec![2, 7]);
+        assert_eq!(by_node[&7], GcRequest { tunnels: vec!["hook.redq".into()], previews: vec!["feature-x.redq".into()] });
+        assert_eq!(by_node[&2].previews, vec!["fix.shop"]);
+
+        stale.previews[1].last_heartbeat_at = Some("2026-10-01T12:00:00Z".into());
+        assert_eq!(skip_never_seen(&mut stale), 2);
+        assert_eq!(requests_by_node(&stale).keys().copied().collect::<Vec<_>>(), vec![2]);
+    }
+}
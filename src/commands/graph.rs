@@ -0,0 +1,211 @@
+use crate::commands::deploy::load_ops_toml;
+use crate::types::OpsToml;
+use crate::{api, config};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Domain,
+    App,
+    Service,
+    Node,
+}
+
+/// Apps, services, nodes and domains of one project, and how they connect
+#[derive(Debug, Default)]
+struct Graph {
+    project: String,
+    /// id → (kind, label); BTreeMap keeps the output stable between runs
+    vertices: BTreeMap<String, (Kind, String)>,
+    edges: Vec<(String, String, Option<String>)>,
+}
+
+impl Graph {
+    fn vertex(&mut self, kind: Kind, name: &str) -> String {
+        let prefix = match kind {
+            Kind::Domain => "domain",
+            Kind::App => "app",
+            Kind::Service => "svc",
+            Kind::Node => "node",
+        };
+        let id = format!("{}_{}", prefix, name.replace(|c: char| !c.is_ascii_alphanumeric(), "_"));
+        self.vertices.entry(id.clone()).or_insert((kind, name.to_string()));
+        id
+    }
+
+    fn edge(&mut self, from: String, to: String, label: Option<String>) {
+        if !self.edges.iter().any(|(f, t, _)| *f == from && *t == to) {
+            self.edges.push((from, to, label));
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let mut out = format!("digraph \"{}\" {{\n  rankdir=LR;\n  node [fontname=\"Helvetica\"];\n", self.project);
+        for (id, (kind, label)) in &self.vertices {
+            let shape = match kind {
+                Kind::Domain => "ellipse",
+                Kind::App => "box, style=\"rounded,bold\"",
+                Kind::Service => "box",
+                Kind::Node => "box3d",
+            };
+            out.push_str(&format!("  {} [label=\"{}\", shape={}];\n", id, label, shape));
+        }
+        for (from, to, label) in &self.edges {
+            match label {
+                Some(l) => out.push_str(&format!("  {} -> {} [label=\"{}\"];\n", from, to, l)),
+                None => out.push_str(&format!("  {} -> {};\n", from, to)),
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn to_mermaid(&self) -> String {
+        let mut out = format!("---\ntitle: {}\n---\nflowchart LR\n", self.project);
+        for (id, (kind, label)) in &self.vertices {
+            let shape = match kind {
+                Kind::Domain => format!("([\"{}\"])", label),
+                Kind::App => format!("[\"{}\"]", label),
+                Kind::Service => format!("(\"{}\")", label),
+                Kind::Node => format!("[(\"{}\")]", label),
+            };
+            out.push_str(&format!("  {}{}\n", id, shape));
+        }
+        for (from, to, label) in &self.edges {
+            match label {
+                Some(l) => out.push_str(&format!("  {} -->|{}| {}\n", from, l, to)),
+                None => out.push_str(&format!("  {} --> {}\n", from, to)),
+            }
+        }
+        out
+    }
+}
+
+/// domain → app → service from ops.toml. `[[routes]]` attach to the app on the same port.
+fn add_config(graph: &mut Graph, config: &OpsToml) {
+    for app in &config.apps {
+        let app_id = graph.vertex(Kind::App, &app.name);
+        for svc in &app.services {
+            let svc_id = graph.vertex(Kind::Service, svc);
+            graph.edge(app_id.clone(), svc_id, None);
+        }
+        for domain in &app.domains {
+            let domain_id = graph.vertex(Kind::Domain, domain);
+            graph.edge(domain_id, app_id.clone(), app.port.map(|p| format!(":{}", p)));
+        }
+    }
+    for route in &config.routes {
+        let domain_id = graph.vertex(Kind::Domain, &route.domain);
+        let target = match config.apps.iter().find(|a| a.port == Some(route.port)) {
+            Some(app) => graph.vertex(Kind::App, &app.name),
+            None => graph.vertex(Kind::Service, &format!("port {}", route.port)),
+        };
+        graph.edge(domain_id, target, Some(format!(":{}", route.port)));
+    }
+}
+
+/// ops graph [project]: the project's topology as Graphviz dot or Mermaid
+pub async fn handle_graph(file: String, project: Option<String>, format: String) -> Result<()> {
+    if format != "dot" && format != "mermaid" {
+        bail!("Unknown format '{}'. Expected dot or mermaid", format);
+    }
+
+    // ops.toml only describes the project it belongs to
+    let local = if Path::new(&file).exists() { Some(load_ops_toml(&file)?) } else { None };
+    let local = local.filter(|c| project.as_ref().is_none_or(|p| *p == c.project));
+    let project = match (project, &local) {
+        (Some(p), _) => p,
+        (None, Some(c)) => c.project.clone(),
+        (None, None) => bail!("No {} found. Pass a project name: ops graph <project>", file),
+    };
+
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let mut graph = Graph { project: project.clone(), ..Default::default() };
+    if let Some(ref c) = local {
+        add_config(&mut graph, c);
+    }
+
+    // Backend: app → node bindings, then each app's domains
+    let nodes = api::list_nodes(&token).await.context("Failed to list nodes")?.nodes;
+    let mut apps: Vec<String> = local.iter().flat_map(|c| c.apps.iter().map(|a| a.name.clone())).collect();
+    for node in &nodes {
+        for bound in node.bound_apps.iter().flatten().filter(|b| b.project_name == project) {
+            let app_id = graph.vertex(Kind::App, &bound.name);
+            let label = node.hostname.as_deref()
+                .map(|h| format!("#{} {}", node.id, h))
+                .unwrap_or_else(|| format!("#{} {}", node.id, node.ip_address));
+            let node_id = graph.vertex(Kind::Node, &label);
+            graph.edge(app_id, node_id, (bound.is_primary == Some(1)).then(|| "primary".to_string()));
+            if !apps.contains(&bound.name) {
+                apps.push(bound.name.clone());
+            }
+        }
+    }
+    if apps.is_empty() {
+        bail!("Project '{}' has no apps in ops.toml and none bound to nodes", project);
+    }
+    for app in &apps {
+        // Best effort: a missing domain list must not hide the rest of the graph
+        let Ok(domains) = api::list_custom_domains(&token, &project, app).await else {
+            o_debug!("   could not list domains of {}.{}", app, project);
+            continue;
+        };
+        let app_id = graph.vertex(Kind::App, app);
+        for domain in std::iter::once(domains.default_domain).chain(domains.domains.into_iter().map(|d| d.domain)) {
+            let domain_id = graph.vertex(Kind::Domain, &domain);
+            graph.edge(domain_id, app_id.clone(), None);
+        }
+    }
+
+    // Plain stdout, so the output can be piped into `dot -Tsvg` or pasted into Markdown
+    match format.as_str() {
+        "dot" => print!("{}", graph.to_dot()),
+        _ => print!("{}", graph.to_mermaid()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph_from_config() {
+        let config: OpsToml = toml::from_str(r#"
+            project = "RedQ"
+            deploy_path = "/opt/redq"
+
+            [deploy]
+
+            [[apps]]
+            name = "api"
+            services = ["api", "worker"]
+            domains = ["api.redq.io"]
+            port = 8080
+
+            [[routes]]
+            domain = "admin.redq.io"
+            port = 8080
+
+            [[routes]]
+            domain = "grafana.redq.io"
+            port = 3000
+        "#).unwrap();
+
+        let mut graph = Graph { project: "RedQ".into(), ..Default::default() };
+        add_config(&mut graph, &config);
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("app_api -> svc_worker;"));
+        assert!(dot.contains("domain_admin_redq_io -> app_api [label=\":8080\"];"));
+        assert!(dot.contains("domain_grafana_redq_io -> svc_port_3000"));
+
+        let mermaid = graph.to_mermaid();
+        assert!(mermaid.contains("  domain_api_redq_io([\"api.redq.io\"])\n"));
+        assert!(mermaid.contains("  domain_api_redq_io -->|:8080| app_api\n"));
+    }
+}
@@ -0,0 +1,170 @@
+//! `ops ide setup`: VS Code tasks and launch entries for deploy, logs and status, and a
+//! devcontainer.json built from the project's Dockerfile (generated by the scanner if missing).
+//! Existing tasks and launch configurations are kept; only the `ops: ...` entries are replaced.
+
+use crate::commands::deploy::load_ops_toml;
+use crate::scanner::{self, dockerfile};
+use crate::types::OpsToml;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::Path;
+
+const TASK_PREFIX: &str = "ops: ";
+
+/// (label, command) for tasks.json and launch.json
+fn commands(file: &str) -> Vec<(&'static str, String)> {
+    let f = if file == "ops.toml" { String::new() } else { format!(" --file {}", file) };
+    vec![
+        ("deploy", format!("ops deploy{}", f)),
+        ("logs", format!("ops logs ${{input:opsService}} --follow{}", f)),
+        ("status", format!("ops status{}", f)),
+    ]
+}
+
+/// Read a JSON object file; a missing file is an empty object. Files with comments (JSONC)
+/// don't parse and are reported, not overwritten.
+fn read_object(path: &Path) -> Result<Map<String, Value>> {
+    match fs::read_to_string(path) {
+        Ok(content) if !content.trim().is_empty() => serde_json::from_str(&content).with_context(|| format!(
+            "{} is not plain JSON (comments?); remove it or run with --force to replace it", path.display()
+        )),
+        _ => Ok(Map::new()),
+    }
+}
+
+/// Replace the entries of `doc[key]` whose `field` starts with TASK_PREFIX by `entries`
+fn merge_entries(doc: &mut Map<String, Value>, key: &str, field: &str, entries: Vec<Value>) {
+    let mut list = match doc.remove(key) {
+        Some(Value::Array(list)) => list,
+        _ => Vec::new(),
+    };
+    list.retain(|e| !e[field].as_str().is_some_and(|l| l.starts_with(TASK_PREFIX)));
+    list.extend(entries);
+    doc.insert(key.into(), Value::Array(list));
+}
+
+fn tasks_json(mut doc: Map<String, Value>, file: &str, services: &[String]) -> Map<String, Value> {
+    doc.entry("version").or_insert(json!("2.0.0"));
+    let tasks = commands(file).into_iter().map(|(label, command)| json!({
+        "label": format!("{}{}", TASK_PREFIX, label),
+        "type": "shell",
+        "command": command,
+        "problemMatcher": [],
+        "isBackground": label == "logs",
+    })).collect();
+    merge_entries(&mut doc, "tasks", "label", tasks);
+
+    let input = if services.is_empty() {
+        json!({ "id": "opsService", "type": "promptString", "description": "Compose service" })
+    } else {
+        json!({ "id": "opsService", "type": "pickString", "description": "Compose service", "options": services })
+    };
+    let mut inputs = match doc.remove("inputs") {
+        Some(Value::Array(list)) => list,
+        _ => Vec::new(),
+    };
+    inputs.retain(|i| i["id"] != "opsService");
+    inputs.push(input);
+    doc.insert("inputs".into(), Value::Array(inputs));
+    doc
+}
+
+fn launch_json(mut doc: Map<String, Value>, file: &str) -> Map<String, Value> {
+    doc.entry("version").or_insert(json!("0.2.0"));
+    // node-terminal runs a command in the integrated terminal; it ships with VS Code
+    let configs = commands(file).into_iter().filter(|(label, _)| *label != "logs").map(|(label, command)| json!({
+        "name": format!("{}{}", TASK_PREFIX, label),
+        "type": "node-terminal",
+        "request": "launch",
+        "command": command,
+    })).collect();
+    merge_entries(&mut doc, "configurations", "name", configs);
+    doc
+}
+
+fn devcontainer_json(config: &OpsToml, port: Option<u16>) -> Value {
+    let mut doc = json!({
+        "name": config.project,
+        "build": { "dockerfile": "../Dockerfile", "context": ".." },
+        "customizations": { "vscode": { "extensions": ["ms-azuretools.vscode-docker", "tamasfe.even-better-toml"] } },
+    });
+    if let Some(port) = port {
+        doc["forwardPorts"] = json!([port]);
+    }
+    doc
+}
+
+fn write_json(path: &Path, value: &impl serde::Serialize) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    fs::write(path, serde_json::to_string_pretty(value)? + "\n").with_context(|| format!("Failed to write {}", path.display()))?;
+    o_success!("  {} {}", "✔".green(), path.display());
+    Ok(())
+}
+
+/// ops ide setup
+pub fn handle_setup(file: String, force: bool) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    let services: Vec<String> = config.apps.iter().flat_map(|a| a.services.clone()).collect();
+    o_step!("{}", "Setting up VS Code for ops".cyan());
+
+    let load = |path: &Path| if force { Ok(Map::new()) } else { read_object(path) };
+    let tasks = Path::new(".vscode/tasks.json");
+    write_json(tasks, &tasks_json(load(tasks)?, &file, &services))?;
+    let launch = Path::new(".vscode/launch.json");
+    write_json(launch, &launch_json(load(launch)?, &file))?;
+
+    // The devcontainer builds the same image the app deploys
+    let scanned = scanner::scan(Path::new("."))?;
+    let dockerfile_path = Path::new("Dockerfile");
+    if !dockerfile_path.exists() {
+        match &scanned {
+            Some(info) => {
+                fs::write(dockerfile_path, dockerfile::render_dockerfile(info)).context("Failed to write Dockerfile")?;
+                o_success!("  {} Dockerfile ({})", "✔".green(), info.framework.display_name());
+            }
+            None => {
+                o_warn!("  {} No Dockerfile and no framework detected, skipping .devcontainer", "!".yellow());
+                return Ok(());
+            }
+        }
+    }
+    let devcontainer = Path::new(".devcontainer/devcontainer.json");
+    if devcontainer.exists() && !force {
+        o_detail!("  {} {} already exists, skipping (--force to overwrite)", "→".dimmed(), devcontainer.display());
+    } else {
+        let port = config.apps.iter().find_map(|a| a.port).or(scanned.as_ref().map(|i| i.port));
+        write_json(devcontainer, &devcontainer_json(&config, port))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tasks_and_launch_merge() {
+        let existing: Map<String, Value> = serde_json::from_value(json!({
+            "version": "2.0.0",
+            "tasks": [{ "label": "build", "command": "make" }, { "label": "ops: deploy", "command": "old" }],
+            "inputs": [{ "id": "other" }],
+        })).unwrap();
+        let tasks = Value::Object(tasks_json(existing, "ops.toml", &["api".into(), "worker".into()]));
+        let labels: Vec<&str> = tasks["tasks"].as_array().unwrap().iter().map(|t| t["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, vec!["build", "ops: deploy", "ops: logs", "ops: status"]);
+        assert_eq!(tasks["tasks"][1]["command"], "ops deploy");
+        assert_eq!(tasks["tasks"][2]["command"], "ops logs ${input:opsService} --follow");
+        assert_eq!(tasks["inputs"][1]["options"], json!(["api", "worker"]));
+        assert_eq!(tasks["inputs"].as_array().unwrap().len(), 2);
+
+        let launch = Value::Object(launch_json(Map::new(), "deploy/prod.toml"));
+        assert_eq!(launch["configurations"][0], json!({
+            "name": "ops: deploy", "type": "node-terminal", "request": "launch", "command": "ops deploy --file deploy/prod.toml",
+        }));
+        assert_eq!(launch["configurations"].as_array().unwrap().len(), 2);
+    }
+}
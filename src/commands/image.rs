@@ -0,0 +1,262 @@
+use crate::commands::build::resolve_build_node;
+use crate::commands::deploy::{load_ops_toml, resolve_targets};
+use crate::commands::ssh::SshSession;
+use crate::prompt;
+use crate::registry::{split_image, Credentials, Registry, TagInfo};
+use crate::types::{BuildImageConfig, OpsToml};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+
+/// `ops build` also pushes this tag; it is never deleted
+const LATEST: &str = "latest";
+
+fn image_config(config: &OpsToml) -> Result<&BuildImageConfig> {
+    config.build.as_ref()
+        .and_then(|b| b.image.as_ref())
+        .context("ops.toml has no [build.image] section")
+}
+
+/// Nodes that may hold images: the app's deploy targets and the build node
+/// Deploy nodes plus the build node, and what couldn't be resolved
+async fn image_nodes(config: &OpsToml, missed: &mut Vec<String>) -> Vec<String> {
+    let mut nodes: Vec<String> = match resolve_targets(config, &None).await {
+        Ok(targets) => targets.iter().map(|t| t.node_id.to_string()).collect(),
+        Err(e) => {
+            o_warn!("   {} {:#}", "⚠ Could not resolve deploy nodes:".yellow(), e);
+            missed.push("deploy nodes".to_string());
+            Vec::new()
+        }
+    };
+    if let Some(build) = &config.build {
+        if let Ok(node) = resolve_build_node(config, build).await {
+            if !nodes.contains(&node) {
+                nodes.push(node);
+            }
+        }
+    }
+    nodes
+}
+
+/// `<prefix>/<svc>:<tag>` of running containers, with the nodes running them.
+/// Nodes where `docker ps` fails go to `missed`
+fn deployed_images(nodes: &[(String, SshSession)], prefix: &str, missed: &mut Vec<String>) -> HashMap<String, Vec<String>> {
+    let mut deployed: HashMap<String, Vec<String>> = HashMap::new();
+    for (node, session) in nodes {
+        let output = match session.exec_output("docker ps --format '{{.Image}}'") {
+            Ok(output) => output,
+            Err(e) => {
+                o_warn!("   {} node {}: {:#}", "⚠".yellow(), node, e);
+                missed.push(format!("node {}", node));
+                continue;
+            }
+        };
+        for image in String::from_utf8_lossy(&output).lines().map(str::trim) {
+            if image.starts_with(&format!("{}/", prefix)) {
+                let image = if image.contains(':') { image.to_string() } else { format!("{}:{}", image, LATEST) };
+                deployed.entry(image).or_default().push(node.clone());
+            }
+        }
+    }
+    deployed
+}
+
+async fn connect_all(nodes: Vec<String>, missed: &mut Vec<String>) -> Vec<(String, SshSession)> {
+    let mut sessions = Vec::new();
+    for node in nodes {
+        match SshSession::connect(&node).await {
+            Ok(session) => sessions.push((node, session)),
+            Err(e) => {
+                o_warn!("   {} node {}: {:#}", "⚠".yellow(), node, e);
+                missed.push(format!("node {}", node));
+            }
+        }
+    }
+    sessions
+}
+
+/// Newest first; tags without a creation time last
+fn sort_newest_first(tags: &mut [TagInfo]) {
+    tags.sort_by(|a, b| match (&a.created, &b.created) {
+        (Some(x), Some(y)) => y.cmp(x),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.tag.cmp(&b.tag),
+    });
+}
+
+/// Tags to delete: all but the `keep` newest, never `latest` or a deployed tag,
+/// nor anything sharing their digest (deleting a digest deletes all its tags)
+fn plan_deletes(tags: &[TagInfo], keep: usize, deployed: &HashSet<String>) -> Vec<TagInfo> {
+    let mut sorted = tags.to_vec();
+    sort_newest_first(&mut sorted);
+    let protected: HashSet<&str> = sorted.iter()
+        .filter(|t| t.tag == LATEST || deployed.contains(&t.tag))
+        .map(|t| t.digest.as_str())
+        .collect();
+    sorted.iter()
+        .filter(|t| !protected.contains(t.digest.as_str()))
+        .skip(keep)
+        .cloned()
+        .collect()
+}
+
+/// Registry client for [build.image], and the repository path services live under
+fn registry_for(image: &BuildImageConfig, actions: &'static str) -> Result<(Registry, String)> {
+    let (host, repo_prefix) = split_image(&image.prefix);
+    let credentials = Credentials::resolve(&image.registry, &image.username, &image.token, image.helper.as_deref(), image.region.as_deref())?;
+    Ok((Registry::new(&host, credentials.username(), &credentials.password()?, actions), repo_prefix))
+}
+
+async fn service_tags(registry: &mut Registry, repo: &str) -> Result<Vec<TagInfo>> {
+    let mut infos = Vec::new();
+    for tag in registry.tags(repo).await? {
+        infos.push(registry.tag_info(repo, &tag).await?);
+    }
+    sort_newest_first(&mut infos);
+    Ok(infos)
+}
+
+/// Deployed tags of one service
+fn deployed_tags(deployed: &HashMap<String, Vec<String>>, prefix: &str, svc: &str) -> HashMap<String, Vec<String>> {
+    let image = format!("{}/{}:", prefix, svc);
+    deployed.iter()
+        .filter_map(|(i, nodes)| i.strip_prefix(&image).map(|tag| (tag.to_string(), nodes.clone())))
+        .collect()
+}
+
+/// ops image list: tags pushed under [build.image] prefix, newest first, with the nodes running them
+pub async fn handle_list(file: String) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    let image = image_config(&config)?;
+    let (mut registry, repo_prefix) = registry_for(image, "pull")?;
+    // Listing only: nodes that can't be inspected were already warned about
+    let mut missed = Vec::new();
+    let sessions = connect_all(image_nodes(&config, &mut missed).await, &mut missed).await;
+    let deployed = deployed_images(&sessions, &image.prefix, &mut missed);
+
+    for svc in &image.services {
+        let repo = format!("{}/{}", repo_prefix, svc);
+        let tags = service_tags(&mut registry, &repo).await
+            .with_context(|| format!("Failed to list {}/{}", image.prefix, svc))?;
+        let running = deployed_tags(&deployed, &image.prefix, svc);
+        o_result!("\n{} ({} tags)", format!("{}/{}", image.prefix, svc).bold(), tags.len());
+        for t in &tags {
+            let created = t.created.as_deref().map(|c| c.chars().take(16).collect::<String>().replace('T', " ")).unwrap_or_else(|| "?".into());
+            let digest = t.digest.strip_prefix("sha256:").unwrap_or(&t.digest);
+            let deployed = match running.get(&t.tag) {
+                Some(nodes) => format!("deployed on {}", nodes.join(", ")).green(),
+                None => "".normal(),
+            };
+            o_result!("  {:<24} {:<17} {:<14} {}", t.tag, created, &digest[..digest.len().min(12)], deployed);
+        }
+    }
+    Ok(())
+}
+
+/// ops image clean: delete old tags from the registry and unused images from nodes
+pub async fn handle_clean(file: String, keep: usize, dry_run: bool, force: bool, interactive: bool) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    let image = image_config(&config)?;
+    let (mut registry, repo_prefix) = registry_for(image, "pull,delete")?;
+    let mut missed = Vec::new();
+    let sessions = connect_all(image_nodes(&config, &mut missed).await, &mut missed).await;
+    let deployed = deployed_images(&sessions, &image.prefix, &mut missed);
+    // A node we can't see could be running any tag, and deleting it from the registry breaks its next pull
+    if !missed.is_empty() {
+        if !force && !dry_run {
+            bail!("Could not inspect {}, so their running tags are unknown. Fix access, or pass --force to delete anyway", missed.join(", "));
+        }
+        o_warn!("   {} Tags running on {} are not protected", "⚠".yellow(), missed.join(", "));
+    }
+
+    // Registry: per service, what goes and what stays
+    let mut deletes: Vec<(String, Vec<TagInfo>)> = Vec::new();
+    let mut kept: HashSet<String> = HashSet::new();
+    for svc in &image.services {
+        let repo = format!("{}/{}", repo_prefix, svc);
+        let tags = service_tags(&mut registry, &repo).await
+            .with_context(|| format!("Failed to list {}/{}", image.prefix, svc))?;
+        let running: HashSet<String> = deployed_tags(&deployed, &image.prefix, svc).into_keys().collect();
+        let doomed = plan_deletes(&tags, keep, &running);
+        for t in tags.iter().filter(|t| !doomed.contains(t)) {
+            kept.insert(format!("{}/{}:{}", image.prefix, svc, t.tag));
+        }
+        o_step!("{} {} → delete {} of {} tag(s)", "🧹".cyan(), format!("{}/{}", image.prefix, svc).bold(), doomed.len(), tags.len());
+        for t in &doomed {
+            o_detail!("   - {} ({})", t.tag, t.created.as_deref().unwrap_or("?"));
+        }
+        deletes.push((repo, doomed));
+    }
+    kept.extend(deployed.keys().cloned());
+
+    if dry_run {
+        o_result!("\nDry run: nothing deleted.");
+        return Ok(());
+    }
+    let total: usize = deletes.iter().map(|(_, d)| d.len()).sum();
+    if total > 0 && !prompt::confirm_yes(&format!("Delete {} tag(s) from {}?", total, image.registry), interactive)? {
+        o_warn!("Cancelled.");
+        return Ok(());
+    }
+
+    let mut deleted = 0;
+    for (repo, doomed) in &deletes {
+        let mut digests = HashSet::new();
+        for t in doomed {
+            if !digests.insert(t.digest.clone()) {
+                continue;
+            }
+            match registry.delete(repo, &t.digest).await {
+                Ok(()) => deleted += 1,
+                Err(e) => {
+                    o_warn!("   {} {:#}", "⚠".yellow(), e);
+                    break;
+                }
+            }
+        }
+    }
+    o_success!("   {} {} manifest(s) deleted from the registry", "✔".green(), deleted);
+
+    // Nodes: old tags of these images, then dangling layers
+    for (node, session) in &sessions {
+        let output = session.exec_output("docker images --format '{{.Repository}}:{{.Tag}}'").unwrap_or_default();
+        let stale: Vec<String> = String::from_utf8_lossy(&output)
+            .lines()
+            .map(str::trim)
+            .filter(|i| i.starts_with(&format!("{}/", image.prefix)) && !i.ends_with(&format!(":{}", LATEST)) && !kept.contains(*i))
+            .map(|i| format!("'{}'", i))
+            .collect();
+        let rmi = if stale.is_empty() { String::new() } else { format!("docker rmi {} 2>/dev/null; ", stale.join(" ")) };
+        match session.exec(&format!("{}docker image prune -f --filter dangling=true >/dev/null", rmi), None) {
+            Ok(()) => o_success!("   {} node {}: {} old image(s) removed, dangling images pruned", "✔".green(), node, stale.len()),
+            Err(e) => o_warn!("   {} node {}: {:#}", "⚠".yellow(), node, e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(tag: &str, digest: &str, created: &str) -> TagInfo {
+        TagInfo { tag: tag.into(), digest: digest.into(), created: Some(created.into()) }
+    }
+
+    #[test]
+    fn test_plan_deletes() {
+        let tags = vec![
+            tag("a1", "d1", "2026-01-01T00:00:00Z"),
+            tag("a2", "d2", "2026-01-02T00:00:00Z"),
+            tag("a3", "d3", "2026-01-03T00:00:00Z"),
+            tag("a4", "d4", "2026-01-04T00:00:00Z"),
+            tag(LATEST, "d4", "2026-01-04T00:00:00Z"),
+        ];
+        let deployed = HashSet::from(["a1".to_string()]);
+        let doomed: Vec<String> = plan_deletes(&tags, 1, &deployed).into_iter().map(|t| t.tag).collect();
+        // a4 and latest share a digest; a1 is deployed; a3 is the one kept
+        assert_eq!(doomed, vec!["a2"]);
+        assert_eq!(plan_deletes(&tags, 5, &deployed), vec![]);
+    }
+}
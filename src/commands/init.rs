@@ -1,5 +1,12 @@
-use crate::{api, config, prompt, ssh};
-use anyhow::{Context, Result};
+use crate::commands::deploy::load_ops_toml;
+use crate::commands::mirrors;
+use crate::node_state::{self, FileMode, NodeState, SERVE_CONFIG_FILE, SERVE_ENV_FILE, STATE_FILE, USER_SERVE_ENV_FILE};
+use crate::privilege::Privilege;
+use crate::serve::config as serve_config;
+use crate::error::{ApiErrorCode, OpsError};
+use crate::types::NodeInitRequest;
+use crate::{api, caddy, config, prompt, ssh};
+use anyhow::{anyhow, Context, Result};
 use colored::Colorize;
 use serde::Deserialize;
 use std::io::{self, Write};
@@ -126,28 +133,39 @@ fn cleanup_old_residue() -> Result<bool> {
 
 
 /// Check and install Docker + Caddy if not present
-fn ensure_system_deps() -> Result<()> {
-    if std::env::var("USER").unwrap_or_default() != "root" {
-        o_warn!("{}", "Not root — skipping dependency checks.".yellow());
-        return Ok(());
-    }
-
+fn ensure_system_deps(privilege: &Privilege, state: &mut NodeState) -> Result<()> {
     // Docker
     let has_docker = Command::new("which").arg("docker")
         .output().map(|o| o.status.success()).unwrap_or(false);
 
+    if *privilege == Privilege::User {
+        // Nothing can be installed; the user service only needs a docker daemon it may talk to
+        let usable = has_docker && Command::new("docker").arg("info")
+            .output().map(|o| o.status.success()).unwrap_or(false);
+        if usable {
+            o_success!("{}", "✔ Docker installed".green());
+        } else if has_docker {
+            o_warn!("{}", "Warning: Docker is installed but this user cannot reach the daemon. Add it to the docker group.".yellow());
+        } else {
+            o_warn!("{}", "Warning: Docker is not installed and cannot be installed without root or sudo.".yellow());
+        }
+        o_warn!("{}", "Skipping Caddy (needs root or sudo).".yellow());
+        return Ok(());
+    }
+
     if has_docker {
         o_success!("{}", "✔ Docker installed".green());
     } else {
         o_step!("Installing Docker...");
-        let status = Command::new("sh")
+        let status = privilege.command("sh")
             .args(["-c", "curl -fsSL https://get.docker.com | sh"])
             .status()
             .context("Failed to install Docker")?;
         if !status.success() {
             return Err(anyhow::anyhow!("Docker installation failed"));
         }
-        Command::new("systemctl").args(["enable", "--now", "docker"]).status().ok();
+        privilege.systemctl().args(["enable", "--now", "docker"]).status().ok();
+        state.record_package("docker");
         o_success!("{}", "✔ Docker installed".green());
     }
 
@@ -166,14 +184,15 @@ fn ensure_system_deps() -> Result<()> {
             "apt-get update && ",
             "apt-get install -y caddy"
         );
-        let status = Command::new("sh")
+        let status = privilege.command("sh")
             .args(["-c", install_script])
             .status()
             .context("Failed to install Caddy")?;
         if !status.success() {
             o_warn!("{}", "Caddy installation failed — continuing without it.".yellow());
         } else {
-            Command::new("systemctl").args(["enable", "--now", "caddy"]).status().ok();
+            privilege.systemctl().args(["enable", "--now", "caddy"]).status().ok();
+            state.record_package("caddy");
             o_success!("{}", "✔ Caddy installed".green());
         }
     }
@@ -183,11 +202,18 @@ fn ensure_system_deps() -> Result<()> {
 
 /// Configure and start ops serve as a systemd service
 fn configure_serve_daemon(
+    privilege: &Privilege,
     token: &str,
     port: u16,
     node_id: u64,
     compose_dir: &str,
+    tls_domains: &[String],
+    state: &mut NodeState,
 ) -> Result<()> {
+    if *privilege == Privilege::User {
+        return configure_user_serve_daemon(token, port, node_id, compose_dir, state);
+    }
+
     o_step!("Configuring systemd service...");
 
     let service_content = format!(r#"[Unit]
@@ -197,82 +223,163 @@ Requires=docker.service
 
 [Service]
 Type=simple
-ExecStart=/usr/local/bin/ops serve --token {} --port {} --compose-dir {}
+ExecStart=/usr/local/bin/ops serve --config {}
+ExecReload=/bin/kill -HUP $MAINPID
 Restart=always
 RestartSec=5
 Environment=RUST_LOG=info
 
 [Install]
 WantedBy=multi-user.target
-"#, node_id, token, port, compose_dir);
+"#, node_id, SERVE_CONFIG_FILE);
 
     let service_path = "/etc/systemd/system/ops-serve.service";
 
-    // Check if running as root
-    if std::env::var("USER").unwrap_or_default() != "root" {
-        o_warn!("{}", "Warning: Not running as root. Cannot install systemd service.".yellow());
-        o_warn!("Run with sudo or as root to enable auto-start.");
-        return Ok(());
+    node_state::write_serve_env_as(privilege, SERVE_ENV_FILE, token)?;
+    state.record_file(SERVE_ENV_FILE, FileMode::Created, None);
+    // Kept on re-init so hand edits (webhooks, watch, alerts) survive
+    if serve_config::write_default(privilege, port, &[compose_dir.to_string()], tls_domains, false)? {
+        state.record_file(SERVE_CONFIG_FILE, FileMode::Created, None);
     }
 
-    fs::write(service_path, &service_content)
+    privilege.write(service_path, &service_content, 0o644)
         .context("Failed to write systemd service file")?;
+    state.record_file(service_path, FileMode::Created, None);
+    state.record_service("ops-serve");
+    start_service(privilege)?;
 
-    // Reload systemd and start service
-    let commands = [
-        ("systemctl", vec!["daemon-reload"]),
-        ("systemctl", vec!["enable", "ops-serve"]),
-        ("systemctl", vec!["restart", "ops-serve"]),
-    ];
+    o_success!("{}", "✔ ops-serve daemon installed and started".green());
+    Ok(())
+}
 
-    for (cmd, args) in &commands {
-        let status = Command::new(cmd)
-            .args(args)
+/// daemon-reload, then enable and restart ops-serve
+fn start_service(privilege: &Privilege) -> Result<()> {
+    for args in [vec!["daemon-reload"], vec!["enable", "ops-serve"], vec!["restart", "ops-serve"]] {
+        let status = privilege.systemctl()
+            .args(&args)
             .status()
-            .context(format!("Failed to run {} {:?}", cmd, args))?;
+            .context(format!("Failed to run systemctl {:?}", args))?;
 
         if !status.success() {
-            o_warn!("{}", format!("Warning: {} {:?} failed", cmd, args).yellow());
+            o_warn!("{}", format!("Warning: systemctl {:?} failed", args).yellow());
         }
     }
+    Ok(())
+}
 
-    o_success!("{}", "✔ ops-serve daemon installed and started".green());
+/// Without root or sudo: ops serve as a `systemctl --user` service on this user's manager.
+/// Caddy is left alone, so apps are reached on their own ports.
+fn configure_user_serve_daemon(token: &str, port: u16, node_id: u64, compose_dir: &str, state: &mut NodeState) -> Result<()> {
+    o_step!("Configuring systemd user service...");
+    o_warn!("{}", "Not root and passwordless sudo is unavailable — installing ops serve for this user only.".yellow());
+
+    let home = std::env::var("HOME").context("Could not find HOME directory")?;
+    let exe = std::env::current_exe().context("Failed to locate the ops binary")?;
+    let env_file = Path::new(&home).join(USER_SERVE_ENV_FILE).to_string_lossy().to_string();
+    node_state::write_serve_env_as(&Privilege::User, &env_file, token)?;
+    state.record_file(&env_file, FileMode::Created, None);
+
+    let service_content = format!(r#"[Unit]
+Description=OPS Serve - Node {}
+After=network.target
+
+[Service]
+Type=simple
+EnvironmentFile={}
+ExecStart={} serve --port {} --compose-dir {}
+Restart=always
+RestartSec=5
+Environment=RUST_LOG=info
 
-    // Configure Caddy if available
-    if Path::new("/etc/caddy").exists() {
-        configure_caddy(port)?;
+[Install]
+WantedBy=default.target
+"#, node_id, env_file, exe.display(), port, compose_dir);
+
+    let service_path = Path::new(&home).join(".config/systemd/user/ops-serve.service");
+    let service_path = service_path.to_string_lossy();
+    Privilege::User.write(&service_path, &service_content, 0o644)
+        .context("Failed to write systemd user service file")?;
+    state.record_file(&service_path, FileMode::Created, None);
+    state.record_service("ops-serve");
+    start_service(&Privilege::User)?;
+
+    // User services stop at logout unless lingering is enabled; polkit usually allows it for yourself
+    let linger = Command::new("loginctl").arg("enable-linger").status();
+    if !linger.is_ok_and(|s| s.success()) {
+        let user = std::env::var("USER").unwrap_or_default();
+        o_warn!("{}", "Warning: could not enable lingering, ops serve stops when you log out.".yellow());
+        o_warn!("Ask an admin to run `loginctl enable-linger {}`", user);
     }
 
+    o_success!("{}", "✔ ops-serve user service installed and started".green());
     Ok(())
 }
 
 /// Configure Caddy reverse proxy for ops serve
-pub fn configure_caddy(port: u16) -> Result<()> {
-    let caddyfile = format!(r#":80 {{
-    import /etc/caddy/routes.d/*.caddy
+pub fn configure_caddy(port: u16, tls_domains: &[String], force_overwrite: bool, state: &mut NodeState) -> Result<()> {
+    configure_caddy_as(&Privilege::detect(), port, tls_domains, force_overwrite, state)
+}
 
-    # Fallback: ops-serve daemon
-    reverse_proxy 127.0.0.1:{}
-}}
-"#, port);
+fn configure_caddy_as(privilege: &Privilege, port: u16, tls_domains: &[String], force_overwrite: bool, state: &mut NodeState) -> Result<()> {
+    privilege.create_dir_all(caddy::ROUTES_DIR)?;
+
+    let existing = fs::read_to_string(caddy::CADDYFILE).unwrap_or_default();
+    let (new_content, mode) = if force_overwrite {
+        (caddy::managed_block(port, tls_domains), FileMode::Replaced)
+    } else {
+        match caddy::merge_caddyfile(&existing, port, tls_domains) {
+            caddy::CaddyfileMerge::Unchanged => {
+                state.record_file(caddy::CADDYFILE, FileMode::Merged, None);
+                o_success!("{}", "✔ Caddy already configured".green());
+                return Ok(());
+            }
+            caddy::CaddyfileMerge::Replaced(c) => (c, FileMode::Replaced),
+            caddy::CaddyfileMerge::Merged(c) => {
+                o_detail!("Existing Caddyfile found, keeping your sites and updating the ops block");
+                (c, FileMode::Merged)
+            }
+        }
+    };
+    // Nothing to restore if there was no Caddyfile before
+    let mode = if mode == FileMode::Replaced && existing.trim().is_empty() { FileMode::Created } else { mode };
 
-    fs::create_dir_all("/etc/caddy/routes.d")
-        .context("Failed to create /etc/caddy/routes.d")?;
+    // Back up anything we are about to change
+    let backup = if existing.trim().is_empty() {
+        None
+    } else {
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = format!("{}.ops-backup-{}", caddy::CADDYFILE, ts);
+        privilege.write(&path, &existing, 0o644).context("Failed to back up Caddyfile")?;
+        o_detail!("Backed up existing Caddyfile to {}", path);
+        Some(path)
+    };
 
-    fs::write("/etc/caddy/Caddyfile", &caddyfile)
+    privilege.write(caddy::CADDYFILE, &new_content, 0o644)
         .context("Failed to write Caddyfile")?;
 
     let validate = Command::new("caddy")
-        .args(["validate", "--config", "/etc/caddy/Caddyfile"])
+        .args(["validate", "--config", caddy::CADDYFILE])
         .status()
         .context("Failed to validate Caddy config")?;
 
     if validate.success() {
-        Command::new("systemctl")
+        privilege.systemctl()
             .args(["reload", "caddy"])
             .status()
             .context("Failed to reload Caddy")?;
+        state.record_file(caddy::CADDYFILE, mode, backup);
         o_success!("{}", "✔ Caddy configured".green());
+        for domain in tls_domains {
+            o_detail!("  HTTPS: {}", format!("https://{}", domain).cyan());
+        }
+    } else if let Some(path) = &backup {
+        // Most likely the user already serves :80 — put their config back untouched
+        privilege.copy(path, caddy::CADDYFILE).context("Failed to restore Caddyfile backup")?;
+        o_warn!("{}", "Warning: merged Caddy config failed validation, original Caddyfile restored".yellow());
+        o_warn!("Add `import {}/*.caddy` to your :80 site, or re-run with --force-overwrite", caddy::ROUTES_DIR);
     } else {
         o_warn!("{}", "Warning: Caddy config validation failed".yellow());
     }
@@ -288,6 +395,28 @@ struct GeoResponse {
     timezone: String,
 }
 
+/// First global IPv6 address of this server, from `ip -6 addr`. Skips ULA (fc00::/7) and
+/// deprecated or temporary (privacy) addresses, which change and are not reachable from outside.
+fn detect_ipv6() -> Option<String> {
+    let out = Command::new("ip")
+        .args(["-6", "-o", "addr", "show", "scope", "global"])
+        .output()
+        .ok()?;
+    parse_ipv6_addr(&String::from_utf8_lossy(&out.stdout))
+}
+
+fn parse_ipv6_addr(output: &str) -> Option<String> {
+    output.lines()
+        .filter(|l| !l.contains("deprecated") && !l.contains("temporary"))
+        .filter_map(|l| {
+            let mut words = l.split_whitespace();
+            words.find(|w| *w == "inet6")?;
+            words.next()?.split('/').next()?.parse::<std::net::Ipv6Addr>().ok()
+        })
+        .find(|a| a.segments()[0] & 0xfe00 != 0xfc00)
+        .map(|a| a.to_string())
+}
+
 /// Detect region from IP geolocation via ip-api.com
 async fn detect_region() -> Option<(String, String)> {
     let client = reqwest::Client::builder()
@@ -403,18 +532,33 @@ fn confirm_region(detected: Option<(String, String)>, interactive: bool) -> Opti
     }
 }
 
+/// Flags of `ops init`
+#[derive(Debug, Default, Clone)]
+pub struct InitOptions {
+    pub region: Option<String>,
+    pub port: u16,
+    pub hostname: Option<String>,
+    pub compose_dir: Option<String>,
+    pub tls: bool,
+    pub force_overwrite_caddy: bool,
+    /// ops.toml whose registry mirrors the node should use
+    pub file: Option<String>,
+}
+
 /// Handle `ops init` command
 /// Initializes this server as a node in the OPS platform
-pub async fn handle_init(
-    _daemon: bool,
-    _projects: Option<String>,
-    _apps: Option<String>,
-    region: Option<String>,
-    port: u16,
-    hostname: Option<String>,
-    compose_dir: Option<String>,
-    interactive: bool,
-) -> Result<()> {
+pub async fn handle_init(opts: InitOptions, interactive: bool) -> Result<()> {
+    let InitOptions { region, port, hostname, compose_dir, tls, force_overwrite_caddy, file } = opts;
+    // Read ops.toml before changing anything, so a typo doesn't leave a half-initialized node
+    let mirrors = match &file {
+        Some(f) => {
+            let mirrors = load_ops_toml(f)?.deploy.registry.map(|r| r.mirrors).unwrap_or_default();
+            mirrors::validate(&mirrors)?;
+            mirrors
+        }
+        None => Default::default(),
+    };
+
     o_step!();
     o_step!("{}", "OPS Node Initialization".cyan().bold());
     o_step!("{}", "═══════════════════════".cyan());
@@ -427,8 +571,24 @@ pub async fn handle_init(
         .context("Not logged in. Run `ops login` first.")?;
     o_success!("{}", "✔ Logged in".green());
 
-    // 2. Check and clean up old residue
-    cleanup_old_residue()?;
+    // Root-only steps go through `sudo -n` when not root; without it, a user-level setup
+    let privilege = Privilege::detect();
+    if privilege == Privilege::Sudo {
+        o_detail!("Not root, using sudo for system changes");
+    }
+
+    // 2. Previous run of this ops version? Converge on it; otherwise clean up old residue
+    let mut state = NodeState::load()?.unwrap_or_default();
+    if state.node_id.is_some() && !state.user_level && privilege == Privilege::User {
+        return Err(anyhow!("This node was initialized as root ({}). Re-run with sudo.", STATE_FILE));
+    }
+    state.user_level = privilege == Privilege::User;
+    let previous_node = state.node_id;
+    if let Some(id) = previous_node {
+        o_detail!("Node #{} already initialized here ({}), updating in place", id, state.path()?);
+    } else if privilege != Privilege::User {
+        cleanup_old_residue()?;
+    }
 
     // 3. Get SSH public key
     let ssh_pub_key = get_ssh_public_key()?;
@@ -450,44 +610,41 @@ pub async fn handle_init(
 
     // 5. Try to initialize node
     o_step!("Registering node...");
+    let request = NodeInitRequest {
+        ssh_pub_key,
+        region,
+        port: Some(port),
+        hostname: hostname.clone(),
+        ipv6_address: detect_ipv6(),
+        ..Default::default()
+    };
 
-    let res = match api::init_node(
-        &token,
-        &ssh_pub_key,
-        region.as_deref(),
-        None,
-        None,
-        Some(port),
-        hostname.as_deref(),
-    ).await {
-        Ok(r) => r,
-        Err(e) => {
-            let err_msg = e.to_string();
-            // If IP already registered, ask user if they want to overwrite
-            if err_msg.contains("already registered") {
-                // Extract existing node ID from error message if available
-                o_warn!();
-                o_warn!("{}", "This server is already registered as a node.".yellow());
-
-                if prompt::confirm_no("Overwrite existing configuration?", interactive)? {
-                    api::reinit_node(
-                        &token,
-                        &ssh_pub_key,
-                        region.as_deref(),
-                        None,
-                        None,
-                        Some(port),
-                        hostname.as_deref(),
-                    ).await?
+    // A node initialized here before is re-registered without asking
+    let res = match previous_node {
+        Some(_) => api::reinit_node(&token, &request).await?,
+        None => match api::init_node(&token, &request).await {
+            Ok(r) => r,
+            Err(e) => {
+                // If IP already registered, ask user if they want to overwrite
+                if matches!(OpsError::api_code(&e), Some(ApiErrorCode::NodeAlreadyRegistered | ApiErrorCode::Conflict)) {
+                    o_warn!();
+                    o_warn!("{}", "This server is already registered as a node.".yellow());
+
+                    if prompt::confirm_no("Overwrite existing configuration?", interactive)? {
+                        api::reinit_node(&token, &request).await?
+                    } else {
+                        o_warn!("Aborted.");
+                        return Ok(());
+                    }
                 } else {
-                    o_warn!("Aborted.");
-                    return Ok(());
+                    return Err(e);
                 }
-            } else {
-                return Err(e);
             }
-        }
+        },
     };
+    state.node_id = Some(res.node_id);
+    state.domain = Some(res.domain.clone());
+    save_state(&privilege, &mut state);
 
     o_success!();
     o_success!("{}", "✔ Node registered".green().bold());
@@ -495,6 +652,9 @@ pub async fn handle_init(
     o_detail!("  Node ID:  {}", res.node_id.to_string().cyan().bold());
     o_detail!("  Domain:   {}", res.domain.cyan());
     o_detail!("  IP:       {}", res.ip_address);
+    if let Some(v6) = res.ipv6_address.as_ref().or(request.ipv6_address.as_ref()) {
+        o_detail!("  IPv6:     {}", v6);
+    }
     match &res.region {
         Some(r) => o_detail!("  Region:   {}", r.cyan()),
         None => o_detail!("  Region:   {}", "(not set, use --region to configure)".dimmed()),
@@ -503,23 +663,53 @@ pub async fn handle_init(
     // 5.5 Check and install system dependencies
     o_step!();
     o_step!("{}", "Checking system dependencies...".cyan());
-    ensure_system_deps()?;
+    ensure_system_deps(&privilege, &mut state)?;
+    if !mirrors.is_empty() {
+        if privilege == Privilege::User {
+            o_warn!("{}", "Warning: Registry mirrors need root or sudo, not configured.".yellow());
+        } else {
+            mirrors::apply_local(&privilege, &mirrors, &mut state)?;
+            save_state(&privilege, &mut state);
+        }
+    }
 
     // 6. Add CI public key to authorized_keys
     o_step!();
     o_step!("Configuring SSH access...");
-    ssh::add_to_authorized_keys(&res.ci_ssh_public_key)?;
-    o_success!("{}", "✔ CI key added to authorized_keys".green());
+    let keys_path = ssh::authorized_keys_path()?.to_string_lossy().to_string();
+    if ssh::add_to_authorized_keys(&res.ci_ssh_public_key)? {
+        state.record_key(&keys_path, &res.ci_ssh_public_key);
+        o_success!("{}", "✔ CI key added to authorized_keys".green());
+    } else {
+        o_success!("{}", "✔ CI key already in authorized_keys".green());
+    }
 
     // 7. Configure systemd daemon (always)
     o_step!();
-    let compose_directory = compose_dir.as_deref().unwrap_or("/root");
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/root".to_string());
+    let default_compose_dir = if privilege == Privilege::User { home.as_str() } else { "/root" };
+    let compose_directory = compose_dir.as_deref().unwrap_or(default_compose_dir);
+    // Automatic HTTPS for the node domain, plus the custom hostname when it is a full domain
+    let mut tls_domains = Vec::new();
+    if tls {
+        tls_domains.push(res.domain.clone());
+        if let Some(h) = hostname.as_ref().filter(|h| h.contains('.')) {
+            tls_domains.push(h.clone());
+        }
+    }
     configure_serve_daemon(
+        &privilege,
         &res.serve_token,
         res.serve_port,
         res.node_id as u64,
         compose_directory,
+        &tls_domains,
+        &mut state,
     )?;
+    if privilege != Privilege::User && Path::new("/etc/caddy").exists() {
+        configure_caddy_as(&privilege, res.serve_port, &tls_domains, force_overwrite_caddy, &mut state)?;
+    }
+    save_state(&privilege, &mut state);
 
     // Done
     o_result!();
@@ -535,3 +725,134 @@ pub async fn handle_init(
 
     Ok(())
 }
+
+/// Persist the init manifest (under $HOME for a user-level init)
+fn save_state(privilege: &Privilege, state: &mut NodeState) {
+    if let Err(e) = state.save_as(privilege) {
+        o_warn!("{}", format!("Warning: failed to write the node state file: {:#}", e).yellow());
+    }
+}
+
+/// Handle `ops init --uninstall`
+/// Reverts everything recorded in the node state manifest
+pub async fn handle_uninstall(force: bool, interactive: bool) -> Result<()> {
+    let state = NodeState::load()?.with_context(|| format!(
+        "No init state found at {} — nothing to uninstall. Nodes set up by an older ops can be removed with `ops node remove <id>`.",
+        STATE_FILE
+    ))?;
+
+    o_warn!("{}", "This will revert `ops init` on this server:".yellow());
+    if let Some(id) = state.node_id {
+        o_detail!("  Node:     #{} ({}), removed from OPS", id, state.domain.as_deref().unwrap_or("-"));
+    }
+    for svc in &state.services {
+        o_detail!("  Service:  {} (stopped and disabled)", svc);
+    }
+    for f in &state.files {
+        let action = match f.mode {
+            FileMode::Created => "deleted",
+            FileMode::Replaced => "restored from backup",
+            FileMode::Merged => "ops block removed",
+        };
+        o_detail!("  File:     {} ({})", f.path, action);
+    }
+    for k in &state.authorized_keys {
+        o_detail!("  SSH key:  CI key removed from {}", k.path);
+    }
+    o_detail!();
+
+    let privilege = if state.user_level { Privilege::User } else { Privilege::detect() };
+    if privilege == Privilege::User && !state.user_level {
+        return Err(anyhow!("Reverting {} needs root. Re-run with sudo.", STATE_FILE));
+    }
+
+    if !force {
+        if !interactive {
+            return Err(anyhow!("Destructive operation requires --force in non-interactive mode"));
+        }
+        if !prompt::confirm_no("Are you sure?", interactive)? {
+            o_warn!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    // 1. Services
+    for svc in &state.services {
+        o_step!("Stopping {}...", svc);
+        let _ = privilege.systemctl().args(["stop", svc]).status();
+        let _ = privilege.systemctl().args(["disable", svc]).status();
+    }
+
+    // 2. Files, newest first
+    let mut caddy_changed = false;
+    for f in state.files.iter().rev() {
+        let result = match f.mode {
+            FileMode::Created => privilege.remove_file(&f.path),
+            FileMode::Replaced => match &f.backup {
+                Some(b) => privilege.copy(b, &f.path),
+                None => Err(anyhow!("no backup recorded")),
+            },
+            FileMode::Merged => {
+                let content = fs::read_to_string(&f.path).unwrap_or_default();
+                match caddy::strip_managed_block(&content) {
+                    Some(stripped) => privilege.write(&f.path, &stripped, 0o644),
+                    None => Ok(()),
+                }
+            }
+        };
+        match result {
+            Ok(()) => o_success!("{}", format!("✔ Reverted {}", f.path).green()),
+            Err(e) => o_warn!("{}", format!("Warning: could not revert {}: {}", f.path, e).yellow()),
+        }
+        caddy_changed |= f.path == caddy::CADDYFILE;
+    }
+    let _ = privilege.systemctl().arg("daemon-reload").status();
+    if caddy_changed {
+        let _ = privilege.systemctl().args(["reload", "caddy"]).status();
+    }
+
+    // 3. SSH keys
+    for k in &state.authorized_keys {
+        match ssh::remove_from_authorized_keys(&k.path, &k.key) {
+            Ok(_) => o_success!("{}", format!("✔ CI key removed from {}", k.path).green()),
+            Err(e) => o_warn!("{}", format!("Warning: {}", e).yellow()),
+        }
+    }
+
+    // 4. Backend
+    if let Some(id) = state.node_id {
+        match config::load_config().ok().and_then(|c| c.token) {
+            Some(token) => match api::delete_node(&token, id as u64).await {
+                Ok(res) => o_success!("{}", format!("✔ {}", res.message).green()),
+                Err(e) => o_warn!("{}", format!("Warning: failed to remove node #{}: {}", id, e).yellow()),
+            },
+            None => o_warn!("Not logged in — remove the node with `ops node remove {}`", id),
+        }
+    }
+
+    state.remove(&privilege)?;
+
+    if !state.packages.is_empty() {
+        o_detail!();
+        o_detail!("Installed by init and left in place: {}", state.packages.join(", "));
+    }
+    o_result!("{}", "✔ ops init reverted".green().bold());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ipv6_addr() {
+        let out = "\
+2: eth0    inet6 2001:db8::1234:5678:9abc:def0/64 scope global temporary dynamic \\       valid_lft 86000sec
+2: eth0    inet6 fd00::5/64 scope global \\       valid_lft forever
+2: eth0    inet6 2001:db8::5/64 scope global \\       valid_lft forever preferred_lft forever
+";
+        assert_eq!(parse_ipv6_addr(out).as_deref(), Some("2001:db8::5"));
+        assert_eq!(parse_ipv6_addr(""), None);
+    }
+}
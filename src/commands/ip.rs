@@ -1,36 +1,101 @@
 // src/commands/ip.rs
 
-use crate::utils;
+use crate::commands::common::resolve_target;
+use crate::utils::Target;
+use crate::{api, config, output};
 use anyhow::{Context, Result};
 use colored::Colorize;
-use std::net::ToSocketAddrs;
+use std::net::{IpAddr, ToSocketAddrs};
 
-/// Resolve IP address for a target
+/// Resolve IP addresses for a target
 /// Supports both Node ID (e.g., "12345") and App target (e.g., "api.RedQ")
-pub async fn handle_ip(target_str: String) -> Result<()> {
-    let target = utils::parse_target(&target_str)?;
+/// Uses the address registered with the backend; `--dns` forces a local DNS lookup
+pub async fn handle_ip(target_str: Option<String>, dns: bool, interactive: bool) -> Result<()> {
+    let target = resolve_target(target_str, interactive).await?;
     let full_domain = target.domain();
 
-    o_step!("Resolving IP for {}...", full_domain.cyan());
-
-    // 使用标准库进行 DNS 查询
-    // (domain, 0) 是一个技巧，表示任何端口
-    let addrs_iter = (full_domain.as_str(), 0).to_socket_addrs()
-        .with_context(|| format!("Failed to resolve DNS for '{}'", full_domain))?;
-
-    let mut found_ip = false;
-    for addr in addrs_iter {
-        // 我们只关心 IPv4 地址，因为这是后端设置的
-        if let std::net::SocketAddr::V4(socket_addr_v4) = addr {
-            println!("{}", socket_addr_v4.ip().to_string().green());
-            found_ip = true;
-            break; // 找到第一个就退出
+    let (source, addrs) = if dns {
+        ("dns", resolve_dns(&full_domain)?)
+    } else {
+        match fetch_registered(&target).await {
+            Ok(addrs) if !addrs.is_empty() => ("api", addrs),
+            Ok(_) => {
+                o_warn!("No address registered for {}, falling back to DNS.", full_domain);
+                ("dns", resolve_dns(&full_domain)?)
+            }
+            Err(e) => {
+                o_warn!("Could not query backend ({}), falling back to DNS.", e);
+                ("dns", resolve_dns(&full_domain)?)
+            }
         }
+    };
+
+    let ipv4: Vec<String> = addrs.iter().filter(|a| a.is_ipv4()).map(|a| a.to_string()).collect();
+    let ipv6: Vec<String> = addrs.iter().filter(|a| a.is_ipv6()).map(|a| a.to_string()).collect();
+
+    if output::json() {
+        let out = serde_json::json!({
+            "target": target.to_string(),
+            "domain": full_domain,
+            "source": source,
+            "ipv4": ipv4,
+            "ipv6": ipv6,
+        });
+        println!("{}", serde_json::to_string_pretty(&out)?);
+        return Ok(());
     }
 
-    if !found_ip {
-        return Err(anyhow::anyhow!("Could not find an IPv4 address for the specified target."));
+    if ipv4.is_empty() && ipv6.is_empty() {
+        return Err(anyhow::anyhow!("Could not find an IP address for the specified target."));
+    }
+
+    o_debug!("Resolved {} via {}", full_domain, source);
+    for ip in &ipv4 {
+        println!("{}", ip.green());
+    }
+    for ip in &ipv6 {
+        println!("{}", ip.cyan());
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Addresses registered in the backend (all bound nodes for an app target)
+pub(crate) async fn fetch_registered(target: &Target) -> Result<Vec<IpAddr>> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let mut raw: Vec<String> = Vec::new();
+    match target {
+        Target::NodeId { id, .. } => {
+            let node = api::get_node(&token, *id).await?;
+            raw.push(node.ip_address);
+            raw.extend(node.ipv6_address);
+        }
+        Target::AppTarget { app, project, .. } => {
+            let resp = api::get_app_deploy_targets(&token, project, app).await?;
+            for t in resp.targets {
+                raw.push(t.ip_address);
+                raw.extend(t.ipv6_address);
+            }
+        }
+    }
+
+    let mut addrs: Vec<IpAddr> = raw.iter().filter_map(|s| s.trim().parse().ok()).collect();
+    addrs.sort();
+    addrs.dedup();
+    Ok(addrs)
+}
+
+/// Resolve the domain with the local resolver
+pub(crate) fn resolve_dns(domain: &str) -> Result<Vec<IpAddr>> {
+    o_debug!("Resolving {} via local DNS...", domain);
+    // (domain, 0) 是一个技巧，表示任何端口
+    let mut addrs: Vec<IpAddr> = (domain, 0).to_socket_addrs()
+        .with_context(|| format!("Failed to resolve DNS for '{}'", domain))?
+        .map(|a| a.ip())
+        .collect();
+    addrs.sort();
+    addrs.dedup();
+    Ok(addrs)
+}
@@ -0,0 +1,206 @@
+use crate::commands::ssh::SshSession;
+use crate::node_state::STATE_FILE;
+use crate::ssh::AUTHORIZED_KEY_COMMENT;
+use crate::utils::{self, Target};
+use crate::{api, config, prompt};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::time::SystemTime;
+
+/// Keys older than this are flagged in `ops keys list`
+const ROTATE_AFTER_DAYS: u64 = 90;
+pub(crate) const AUTHORIZED_KEYS: &str = "~/.ssh/authorized_keys";
+
+/// Days since an API timestamp ("2026-01-01 12:00:00" or RFC 3339)
+pub(crate) fn age_days(created_at: &str, now: SystemTime) -> Option<u64> {
+    let created = humantime::parse_rfc3339_weak(created_at.trim_end_matches('Z')).ok()?;
+    Some(now.duration_since(created).ok()?.as_secs() / 86_400)
+}
+
+/// ops keys list: CI keypairs, who uses them and how old they are
+pub async fn handle_list() -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let keys = api::list_ci_keys(&token).await.context("Failed to list CI keys")?.keys;
+
+    if keys.is_empty() {
+        o_result!("No CI keys yet. They are created by `ops init` and `ops set`.");
+        return Ok(());
+    }
+
+    o_result!("{:<14} {:<12} {:<24} {:<10} {}", "KEY".bold(), "TYPE".bold(), "FINGERPRINT".bold(), "AGE".bold(), "USED BY".bold());
+    let now = SystemTime::now();
+    let mut stale = 0;
+    for k in &keys {
+        let owner = match (k.scope.as_str(), k.node_id, k.node_group_id) {
+            ("node", Some(id), _) => format!("node #{}", id),
+            (_, _, Some(id)) => format!("group #{}", id),
+            (scope, _, _) => scope.to_string(),
+        };
+        let age = age_days(&k.created_at, now);
+        let age_str = age.map(|d| format!("{} days", d)).unwrap_or_else(|| "?".into());
+        let age_str = if age.is_some_and(|d| d >= ROTATE_AFTER_DAYS) {
+            stale += 1;
+            age_str.yellow()
+        } else {
+            age_str.normal()
+        };
+        let fingerprint = k.fingerprint.as_deref().unwrap_or("-");
+        let fingerprint = fingerprint.strip_prefix("SHA256:").unwrap_or(fingerprint);
+        let short: String = fingerprint.chars().take(20).collect();
+        let used_by = if k.apps.is_empty() { "-".to_string() } else { k.apps.join(", ") };
+        o_result!("{:<14} {:<12} {:<24} {:<10} {}", owner, k.key_type.as_deref().unwrap_or("-"), short, age_str, used_by);
+    }
+
+    if stale > 0 {
+        o_warn!(
+            "\n{} {} key(s) older than {} days. Rotate with `ops keys rotate <app.project|node>`.",
+            "⚠".yellow(), stale, ROTATE_AFTER_DAYS
+        );
+    }
+    Ok(())
+}
+
+/// `path` as a shell word; a leading `~/` stays unquoted so the remote shell still expands it
+pub(crate) fn path_arg(path: &str) -> String {
+    match path.strip_prefix("~/") {
+        Some(rest) => format!("~/{}", utils::shell_quote(rest)),
+        None => utils::shell_quote(path),
+    }
+}
+
+/// Shell that adds `key` to `path` below the `comment` marker line, unless it's already there
+pub(crate) fn install_script(path: &str, key: &str, comment: &str) -> String {
+    format!(
+        "mkdir -p ~/.ssh && touch {path} && chmod 600 {path} && \
+         (grep -qxF -e {key} {path} || printf '\\n%s\\n%s\\n' {comment} {key} >> {path})",
+        path = path_arg(path), key = utils::shell_quote(key.trim()), comment = utils::shell_quote(comment),
+    )
+}
+
+/// Shell that drops `key` and its ops marker line from `path`, and swaps it for `new_key` in the node state
+/// (so `ops init --uninstall` removes the new key instead)
+fn remove_script(path: &str, key: &str, new_key: &str) -> String {
+    let sed = |s: &str| s.replace('\\', "\\\\").replace('|', "\\|").replace('&', "\\&")
+        .replace('.', "\\.").replace('[', "\\[").replace('*', "\\*").replace('^', "\\^").replace('$', "\\$");
+    // awk -v would interpret backslash escapes, so the values go in through the environment
+    format!(
+        "k={key} c={comment} awk '\
+         {{ if ($0 == ENVIRON[\"k\"]) {{ held = 0; next }} if (held) {{ print hold; held = 0 }} \
+         if ($0 == ENVIRON[\"c\"]) {{ hold = $0; held = 1; next }} print }} END {{ if (held) print hold }}' \
+         {path} > {path}.ops-tmp && cat {path}.ops-tmp > {path} && rm -f {path}.ops-tmp; \
+         [ -f {state} ] && sed -i {expr} {state}; true",
+        key = utils::shell_quote(key.trim()), comment = utils::shell_quote(AUTHORIZED_KEY_COMMENT), path = path_arg(path),
+        state = utils::shell_quote(STATE_FILE), expr = utils::shell_quote(&format!("s|{}|{}|g", sed(key.trim()), sed(new_key.trim()))),
+    )
+}
+
+/// ops keys rotate <app.project|node>: new CI keypair, installed on every node that trusted the old one
+pub async fn handle_rotate(target_str: String, interactive: bool) -> Result<()> {
+    let target = utils::parse_target(&target_str)?;
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    // The old key is needed to log in and install the new one
+    let (old_private, old_public, nodes) = match &target {
+        Target::NodeId { id, .. } => (
+            api::get_node_ci_key(&token, *id).await?.private_key,
+            api::get_node_ci_public_key(&token, *id).await?.public_key,
+            vec![*id as i64],
+        ),
+        Target::AppTarget { app, project, .. } => (
+            api::get_app_ci_key(&token, project, app).await?.private_key,
+            api::get_app_ci_public_key(&token, project, app).await?.public_key,
+            api::get_app_deploy_targets(&token, project, app).await
+                .context("Failed to get deploy targets")?
+                .targets.iter().map(|t| t.node_id).collect(),
+        ),
+    };
+
+    o_step!("{} {} ({} node(s))", "🔑 Rotating CI key for".cyan(), target_str.green(), nodes.len());
+    o_detail!("   The old key stops working on each node once the new one is confirmed there.");
+    if !prompt::confirm_yes("Continue?", interactive)? {
+        o_warn!("Cancelled.");
+        return Ok(());
+    }
+
+    let new = match &target {
+        Target::NodeId { id, .. } => api::rotate_node_ci_key(&token, *id).await,
+        Target::AppTarget { app, project, .. } => api::rotate_app_ci_key(&token, project, app).await,
+    }.context("Failed to rotate CI key")?;
+    o_success!("   {}", "✔ New keypair generated".green());
+
+    let mut failed = Vec::new();
+    for node_id in &nodes {
+        let node = node_id.to_string();
+        let result = (|| -> Result<()> {
+            SshSession::with_key(&node, &old_private)?
+                .exec(&install_script(AUTHORIZED_KEYS, &new.public_key, AUTHORIZED_KEY_COMMENT), None)
+                .context("installing the new key")?;
+            // Only drop the old key after logging in with the new one
+            SshSession::with_key(&node, &new.private_key)?
+                .exec(&remove_script(AUTHORIZED_KEYS, &old_public, &new.public_key), None)
+                .context("logging in with the new key")?;
+            Ok(())
+        })();
+        match result {
+            Ok(()) => o_success!("   {} node {} now trusts only the new key", "✔".green(), node_id),
+            Err(e) => {
+                o_error!("   {} node {}: {:#}", "✘".red(), node_id, e);
+                failed.push(node);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!(
+            "Key rotated, but node(s) {} were not updated. Run `sudo ops init` on them to install the current key.",
+            failed.join(", ")
+        );
+    }
+    o_result!("\n{} CI key rotated. Update CI secrets that hold the old key: `ops ci-keys {} --output github-secret`", "✅".green(), target_str);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate_scripts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("authorized_keys");
+        let path = path.to_str().unwrap();
+        let old = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOld+key/x ci@ops";
+        let new = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAINew+key/y ci@ops";
+        std::fs::write(path, format!("ssh-rsa AAAAuser me@laptop\n\n{}\n{}\n", AUTHORIZED_KEY_COMMENT, old)).unwrap();
+
+        let run = |script: String| {
+            let status = std::process::Command::new("sh").arg("-c").arg(script).status().unwrap();
+            assert!(status.success());
+        };
+        run(install_script(path, new, AUTHORIZED_KEY_COMMENT));
+        run(install_script(path, new, AUTHORIZED_KEY_COMMENT));
+        run(remove_script(path, old, new));
+
+        let content = std::fs::read_to_string(path).unwrap();
+        assert_eq!(content.matches(new).count(), 1);
+        assert!(!content.contains(old));
+        assert_eq!(content.matches(AUTHORIZED_KEY_COMMENT).count(), 1);
+        assert!(content.starts_with("ssh-rsa AAAAuser me@laptop\n"));
+
+        // A key comment with a quote must stay data, not shell
+        let marker = dir.path().join("pwned");
+        let evil = format!("ssh-ed25519 AAAAevil x'; touch {}; echo '", marker.display());
+        run(install_script(path, &evil, "# it's ops"));
+        run(install_script(path, &evil, "# it's ops"));
+        assert!(!marker.exists());
+        let content = std::fs::read_to_string(path).unwrap();
+        assert_eq!(content.matches(evil.as_str()).count(), 1);
+        assert!(content.contains("\n# it's ops\n"));
+
+        assert_eq!(path_arg(AUTHORIZED_KEYS), "~/.ssh/authorized_keys");
+        assert_eq!(path_arg("/tmp/a b"), "'/tmp/a b'");
+        assert_eq!(age_days("2026-01-01 00:00:00", humantime::parse_rfc3339("2026-01-31T12:00:00Z").unwrap()), Some(30));
+    }
+}
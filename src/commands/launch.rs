@@ -1,9 +1,11 @@
 use crate::scanner;
 use crate::prompt;
-use anyhow::{Context, Result};
+use crate::config;
+use anyhow::{bail, Context, Result};
 use colored::Colorize;
+use serde::Deserialize;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::SystemTime;
 
@@ -73,14 +75,90 @@ fn has_compose_files(dir: &Path) -> bool {
         || dir.join("docker-compose.prod.yaml").exists()
 }
 
-/// Generate ops.toml content (project mode)
-fn generate_ops_toml(
-    project_name: &str,
+/// Flags of `ops launch`. Anything unset comes from the preset, then the scan, then a prompt.
+#[derive(Debug, Default, Clone)]
+pub struct LaunchOptions {
+    pub name: Option<String>,
+    pub port: Option<u16>,
+    pub domain: Option<String>,
+    pub source: Option<String>,
+    pub deploy_path: Option<String>,
+    pub compose_files: Vec<String>,
+    pub registry: Option<String>,
+    pub health_url: Option<String>,
+    pub preset: Option<String>,
+}
+
+/// `ops launch --preset`: shared defaults, as TOML. `{name}` and `{port}` are substituted.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Preset {
+    source: Option<String>,
+    registry: Option<String>,
+    port: Option<u16>,
+    deploy_path: Option<String>,
+    #[serde(default)]
+    compose_files: Vec<String>,
+    health_url: Option<String>,
+}
+
+const BUILTIN_PRESETS: &[(&str, &str)] = &[
+    ("push", "source = \"push\""),
+    ("git", "source = \"git\""),
+    ("nextjs-ghcr", "source = \"image\"\nregistry = \"ghcr.io\"\nport = 3000\nhealth_url = \"http://localhost:{port}/\""),
+    ("fastapi-push", "source = \"push\"\nport = 8000\nhealth_url = \"http://localhost:{port}/docs\""),
+];
+
+const SOURCES: &[&str] = &["push", "git", "image"];
+
+/// A built-in preset, a TOML file path, or `<config dir>/ops/presets/<name>.toml`
+fn load_preset(name: &str) -> Result<Preset> {
+    let content = if let Some((_, toml)) = BUILTIN_PRESETS.iter().find(|(n, _)| *n == name) {
+        toml.to_string()
+    } else {
+        let path = if name.ends_with(".toml") { Some(PathBuf::from(name)) } else { config::preset_path(name) };
+        match path.filter(|p| p.exists()) {
+            Some(path) => fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?,
+            None => {
+                let builtin: Vec<&str> = BUILTIN_PRESETS.iter().map(|(n, _)| *n).collect();
+                bail!("Unknown preset '{}'. Built-in presets: {}; or pass a .toml file", name, builtin.join(", "));
+            }
+        }
+    };
+    toml::from_str(&content).with_context(|| format!("Invalid preset '{}'", name))
+}
+
+/// Image for `source = "image"`: `<registry>/<owner>/<name>` when the owner can be read from the git remote
+fn image_name(registry: &str, git_remote: Option<&str>, name: &str) -> String {
+    let owner = git_remote
+        .and_then(|r| r.trim_end_matches(".git").rsplit(['/', ':']).nth(1))
+        .map(|o| o.to_lowercase());
+    match owner {
+        Some(owner) => format!("{}/{}/{}", registry, owner, name.to_lowercase()),
+        None => format!("{}/{}", registry, name.to_lowercase()),
+    }
+}
+
+/// Everything `ops launch` settled on, ready to render
+struct LaunchPlan {
+    name: String,
+    /// Compose service the app runs as
+    service: String,
+    /// What the scanner found, e.g. "Next.js 14.2.3 (npm start)"
+    detected: Option<String>,
     port: u16,
-    domain: &Option<String>,
-    env_files: &[(String, String)],
-    compose_files: &[String],
-) -> String {
+    domain: Option<String>,
+    source: String,
+    deploy_path: String,
+    compose_files: Vec<String>,
+    registry: Option<String>,
+    git_repo: Option<String>,
+    health_url: String,
+    env_files: Vec<(String, String)>,
+}
+
+/// Generate ops.toml content (project mode)
+fn generate_ops_toml(plan: &LaunchPlan) -> String {
     let mut out = String::new();
 
     let now = humantime::format_rfc3339_seconds(SystemTime::now());
@@ -89,28 +167,43 @@ fn generate_ops_toml(
         env!("CARGO_PKG_VERSION"),
         now
     ));
+    if let Some(ref detected) = plan.detected {
+        out.push_str(&format!("# Detected: {}\n\n", detected));
+    }
 
+    let project_name = &plan.name;
     out.push_str(&format!("project = \"{}\"\n", project_name));
-    out.push_str(&format!("deploy_path = \"/opt/{}\"\n", project_name));
+    out.push_str(&format!("deploy_path = \"{}\"\n", plan.deploy_path));
 
     out.push_str("\n[deploy]\n");
-    out.push_str("source = \"push\"\n");
+    out.push_str(&format!("source = \"{}\"\n", plan.source));
 
-    if compose_files.is_empty() {
-        out.push_str("compose_files = [\"docker-compose.yml\"]\n");
-    } else {
-        let files: Vec<String> = compose_files.iter().map(|f| format!("\"{}\"", f)).collect();
-        out.push_str(&format!("compose_files = [{}]\n", files.join(", ")));
+    let files: Vec<String> = plan.compose_files.iter().map(|f| format!("\"{}\"", f)).collect();
+    out.push_str(&format!("compose_files = [{}]\n", files.join(", ")));
+
+    if let Some(ref repo) = plan.git_repo {
+        out.push_str(&format!("\n[deploy.git]\nrepo = \"{}\"\n", repo));
+    }
+    if let Some(ref registry) = plan.registry {
+        out.push_str(&format!(
+            "\n[deploy.registry]\nurl = \"{}\"\ntoken = \"$REGISTRY_TOKEN\"\n",
+            registry
+        ));
     }
 
-    // [[apps]]
+    // [[apps]]: with a port, deploys start a new container, health check it and switch routes to it.
+    // Pulled images aren't named after the compose project, so source = "image" keeps plain compose.
+    let port = plan.port;
     out.push_str(&format!(
         "\n[[apps]]\nname = \"{}\"\nservices = [\"{}\"]\n",
-        project_name, project_name
+        project_name, plan.service
     ));
+    if plan.source != "image" {
+        out.push_str(&format!("port = {}\n", port));
+    }
 
     // [[routes]]
-    if let Some(ref d) = domain {
+    if let Some(ref d) = plan.domain {
         out.push_str(&format!(
             "\n[[routes]]\ndomain = \"{}\"\nport = {}\nssl = true\n",
             d, port
@@ -123,7 +216,7 @@ fn generate_ops_toml(
     }
 
     // env_files
-    for (local, remote) in env_files {
+    for (local, remote) in &plan.env_files {
         out.push_str(&format!(
             "\n[[env_files]]\nlocal = \"{}\"\nremote = \"{}\"\n",
             local, remote
@@ -132,15 +225,25 @@ fn generate_ops_toml(
 
     // healthchecks
     out.push_str(&format!(
-        "\n[[healthchecks]]\nname = \"{}\"\nurl = \"http://localhost:{}\"\n",
-        project_name, port
+        "\n[[healthchecks]]\nname = \"{}\"\nurl = \"{}\"\n",
+        project_name, plan.health_url
     ));
 
     out
 }
 
 /// ops launch — scan project, generate Dockerfile + docker-compose.yml + ops.toml
-pub async fn handle_launch(output: String, interactive: bool) -> Result<()> {
+pub async fn handle_launch(output: String, opts: LaunchOptions, interactive: bool) -> Result<()> {
+    let preset = match &opts.preset {
+        Some(name) => load_preset(name)?,
+        None => Preset::default(),
+    };
+    if let Some(source) = opts.source.as_ref().or(preset.source.as_ref()) {
+        if !SOURCES.contains(&source.as_str()) {
+            bail!("Invalid source '{}'. Expected {}", source, SOURCES.join(", "));
+        }
+    }
+
     o_step!();
     o_step!("{}", "OPS Launch".cyan().bold());
     o_step!("{}", "══════════".cyan());
@@ -164,6 +267,7 @@ pub async fn handle_launch(output: String, interactive: bool) -> Result<()> {
             }
         }
         o_success!("  {} Port: {}", "✔".green(), info.port.to_string().yellow());
+        o_success!("  {} Start: {}", "✔".green(), info.start_cmd);
         for note in &info.notes {
             o_warn!("  {} {}", "ℹ".yellow(), note);
         }
@@ -190,19 +294,53 @@ pub async fn handle_launch(output: String, interactive: bool) -> Result<()> {
         o_detail!();
     }
 
-    // 4. Minimal interactive prompts
-    let project_name = prompt::input_with_default("Project name", &ctx.dir_name, interactive)?;
+    // 4. Flags, then preset, then prompts
+    let project_name = match opts.name {
+        Some(name) => name,
+        None => prompt::input_with_default("Project name", &ctx.dir_name, interactive)?,
+    };
+
+    let port = match opts.port.or(preset.port) {
+        Some(port) => port,
+        None => {
+            let default = scan_result.as_ref().map(|i| i.port)
+                .or_else(|| {
+                    let dockerfile = fs::read_to_string(source_dir.join("Dockerfile")).ok()?;
+                    scanner::dockerfile::exposed_port(&dockerfile)
+                })
+                .unwrap_or(8080);
+            let p = prompt::input_with_default("Port", &default.to_string(), interactive)?;
+            p.parse().unwrap_or(default)
+        }
+    };
+
+    let domain = match opts.domain {
+        Some(domain) => Some(domain),
+        None => {
+            let domain = prompt::input_optional("Domain (e.g. app.example.com, enter to skip):", interactive)?;
+            if domain.is_empty() { None } else { Some(domain) }
+        }
+    };
 
-    let port = if let Some(ref info) = scan_result {
-        let p = prompt::input_with_default("Port", &info.port.to_string(), interactive)?;
-        p.parse().unwrap_or(info.port)
+    let registry = opts.registry.or(preset.registry);
+    // A registry means pre-built images, unless the source says otherwise
+    let source = opts.source.or(preset.source)
+        .unwrap_or_else(|| if registry.is_some() { "image".into() } else { "push".into() });
+    let git_repo = if source == "git" {
+        Some(ctx.git_remote.clone().context("source = \"git\" needs a git remote named origin")?)
     } else {
-        let p = prompt::input_with_default("Port", "8080", interactive)?;
-        p.parse().unwrap_or(8080)
+        None
     };
+    let registry = registry.filter(|_| source == "image");
 
-    let domain = prompt::input_optional("Domain (e.g. app.example.com, enter to skip):", interactive)?;
-    let domain = if domain.is_empty() { None } else { Some(domain) };
+    let substitute = |s: &str| s.replace("{name}", &project_name).replace("{port}", &port.to_string());
+    let deploy_path = match opts.deploy_path.or(preset.deploy_path.as_deref().map(substitute)) {
+        Some(path) => path,
+        None => prompt::input_with_default("Deploy path", &format!("/opt/{}", project_name), interactive)?,
+    };
+    let health_url = opts.health_url
+        .or(preset.health_url.as_deref().map(substitute))
+        .unwrap_or_else(|| format!("http://localhost:{}", port));
 
     let env_files = if ctx.has_env_file {
         if prompt::confirm_yes("Sync .env to remote?", interactive)? {
@@ -231,10 +369,16 @@ pub async fn handle_launch(output: String, interactive: bool) -> Result<()> {
         }
     }
 
-    // docker-compose.yml
-    if let Some(ref info) = scan_result {
+    // docker-compose.yml, unless compose files were named
+    let compose_files = if !opts.compose_files.is_empty() { opts.compose_files } else { preset.compose_files };
+    if let Some(info) = scan_result.as_ref().filter(|_| compose_files.is_empty()) {
         if !has_compose_files(&source_dir) {
-            let compose = scanner::dockerfile::render_compose(&project_name, info);
+            let mut compose = scanner::dockerfile::render_compose(&project_name, info);
+            if let Some(ref registry) = registry {
+                // Pulled, not built, on the node
+                let image = image_name(registry, ctx.git_remote.as_deref(), &project_name);
+                compose = compose.replacen("    build: .\n", &format!("    image: {}:latest\n", image), 1);
+            }
             fs::write(source_dir.join("docker-compose.yml"), &compose)
                 .context("Failed to write docker-compose.yml")?;
             generated.push("docker-compose.yml");
@@ -253,15 +397,34 @@ pub async fn handle_launch(output: String, interactive: bool) -> Result<()> {
         }
     }
 
-    // Use existing compose files if present, else the one we generated
-    let compose_files = if !ctx.compose_files.is_empty() {
+    // Named compose files, else existing ones, else the one we generated
+    let compose_files = if !compose_files.is_empty() {
+        compose_files
+    } else if !ctx.compose_files.is_empty() {
         ctx.compose_files.clone()
     } else {
         vec!["docker-compose.yml".to_string()]
     };
 
     // ops.toml
-    let toml_content = generate_ops_toml(&project_name, port, &domain, &env_files, &compose_files);
+    let detected = scan_result.as_ref().map(|info| match &info.version {
+        Some(ver) => format!("{} {} ({})", info.framework.display_name(), ver, info.start_cmd),
+        None => format!("{} ({})", info.framework.display_name(), info.start_cmd),
+    });
+    let toml_content = generate_ops_toml(&LaunchPlan {
+        service: scanner::dockerfile::service_name(&project_name),
+        name: project_name,
+        detected,
+        port,
+        domain,
+        source,
+        deploy_path,
+        compose_files,
+        registry,
+        git_repo,
+        health_url,
+        env_files,
+    });
     fs::write(&output, &toml_content)
         .with_context(|| format!("Failed to write {}", output))?;
     generated.push("ops.toml");
@@ -278,3 +441,25 @@ pub async fn handle_launch(output: String, interactive: bool) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_and_image_name() {
+        for (name, _) in BUILTIN_PRESETS {
+            load_preset(name).unwrap();
+        }
+        let preset = load_preset("nextjs-ghcr").unwrap();
+        assert_eq!(preset.source.as_deref(), Some("image"));
+        assert_eq!(preset.port, Some(3000));
+        assert!(load_preset("no-such-preset").is_err());
+
+        assert_eq!(image_name("ghcr.io", Some("git@github.com:Acme/shop.git"), "Web"), "ghcr.io/acme/web");
+        assert_eq!(image_name("ghcr.io", Some("https://github.com/acme/shop"), "web"), "ghcr.io/acme/web");
+        assert_eq!(image_name("registry.example.com", None, "web"), "registry.example.com/web");
+        assert_eq!(scanner::dockerfile::exposed_port("FROM x\nEXPOSE 8081/tcp 9000\n"), Some(8081));
+        assert_eq!(scanner::dockerfile::service_name("My Shop"), "my-shop");
+    }
+}
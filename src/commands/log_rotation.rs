@@ -0,0 +1,180 @@
+//! `ops node logging configure`: json-file rotation in the node's /etc/docker/daemon.json, so
+//! container logs can't grow until they fill the disk. The daemon defaults only apply to new
+//! containers, so running compose services are recreated afterwards.
+
+use crate::commands::ssh::SshSession;
+use crate::prompt;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde_json::{json, Map, Value};
+
+pub const DAEMON_JSON: &str = "/etc/docker/daemon.json";
+
+/// Docker size: a number with an optional k, m or g suffix ("50m")
+fn validate_size(size: &str) -> Result<()> {
+    let digits = size.trim_end_matches(['k', 'm', 'g', 'K', 'M', 'G']);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) || size.len() - digits.len() > 1 {
+        bail!("Invalid --max-size '{}': use a number with an optional k, m or g suffix (e.g. 50m)", size);
+    }
+    Ok(())
+}
+
+/// daemon.json with the json-file driver and rotation options; other settings are kept
+fn merge_daemon_json(current: &str, max_size: &str, max_file: u32) -> Result<String> {
+    let mut config: Map<String, Value> = if current.trim().is_empty() {
+        Map::new()
+    } else {
+        serde_json::from_str(current).with_context(|| format!("{} is not a JSON object", DAEMON_JSON))?
+    };
+    // Options of another driver don't apply to json-file
+    let same_driver = config.get("log-driver").is_none_or(|d| d == "json-file");
+    let mut opts = match config.remove("log-opts") {
+        Some(Value::Object(opts)) if same_driver => opts,
+        _ => Map::new(),
+    };
+    opts.insert("max-size".into(), json!(max_size));
+    opts.insert("max-file".into(), json!(max_file.to_string()));
+    config.insert("log-driver".into(), json!("json-file"));
+    config.insert("log-opts".into(), Value::Object(opts));
+    Ok(serde_json::to_string_pretty(&config)? + "\n")
+}
+
+/// A compose project whose running containers don't rotate logs as configured
+#[derive(Debug, PartialEq)]
+struct StaleProject {
+    name: String,
+    working_dir: String,
+    config_files: String,
+}
+
+/// From `docker inspect` lines "project|working_dir|config_files|driver|max-size|max-file"
+fn stale_projects(inspect: &str, max_size: &str, max_file: u32) -> (Vec<StaleProject>, usize) {
+    let mut projects: Vec<StaleProject> = Vec::new();
+    let mut standalone = 0;
+    for line in inspect.lines() {
+        let parts: Vec<&str> = line.split('|').collect();
+        let [project, working_dir, config_files, driver, size, files] = parts[..] else { continue };
+        if driver == "json-file" && size == max_size && files == max_file.to_string() {
+            continue;
+        }
+        if project.is_empty() || working_dir.is_empty() {
+            standalone += 1;
+        } else if !projects.iter().any(|p| p.name == project) {
+            projects.push(StaleProject {
+                name: project.to_string(),
+                working_dir: working_dir.to_string(),
+                config_files: config_files.to_string(),
+            });
+        }
+    }
+    (projects, standalone)
+}
+
+fn recreate_command(project: &StaleProject) -> String {
+    let files: String = project.config_files.split(',')
+        .filter(|f| !f.is_empty())
+        .map(|f| format!(" -f {}", f))
+        .collect();
+    format!("cd {} && docker compose -p {}{} up -d --force-recreate", project.working_dir, project.name, files)
+}
+
+/// ops node logging configure
+pub async fn handle_configure(node_id: u64, max_size: String, max_file: u32, no_restart: bool, interactive: bool) -> Result<()> {
+    validate_size(&max_size)?;
+    if max_file == 0 {
+        bail!("--max-file must be at least 1");
+    }
+    let session = SshSession::connect(&node_id.to_string()).await?;
+
+    let current = session.exec_output(&format!("cat {} 2>/dev/null || true", DAEMON_JSON))?;
+    let current = String::from_utf8_lossy(&current);
+    let updated = merge_daemon_json(&current, &max_size, max_file)?;
+    if current.trim() == updated.trim() {
+        o_detail!("   {}", format!("{} already rotates logs at {} × {}", DAEMON_JSON, max_size, max_file).dimmed());
+    } else {
+        o_step!("{}", format!("Setting json-file logging on node #{}: max-size {}, max-file {}", node_id, max_size, max_file).bold());
+        session.exec(&format!("mkdir -p /etc/docker && cat > {0}.ops && mv {0}.ops {0}", DAEMON_JSON), Some(&updated))?;
+        o_success!("   {} {}", "✔".green(), DAEMON_JSON);
+    }
+
+    // Read before restarting Docker: containers without a restart policy don't come back
+    let inspect = format!(
+        "docker ps -q | xargs -r docker inspect -f '{}'",
+        "{{index .Config.Labels \"com.docker.compose.project\"}}|{{index .Config.Labels \"com.docker.compose.project.working_dir\"}}|\
+         {{index .Config.Labels \"com.docker.compose.project.config_files\"}}|{{.HostConfig.LogConfig.Type}}|\
+         {{index .HostConfig.LogConfig.Config \"max-size\"}}|{{index .HostConfig.LogConfig.Config \"max-file\"}}"
+    );
+    let inspect = String::from_utf8_lossy(&session.exec_output(&inspect)?).to_string();
+    let (projects, standalone) = stale_projects(&inspect, &max_size, max_file);
+    if projects.is_empty() && standalone == 0 {
+        o_success!("{}", "✔ Every running container already uses these settings".green());
+        return Ok(());
+    }
+
+    if no_restart {
+        o_detail!("   {} compose project(s) and {} other container(s) keep their old settings until Docker restarts and they are recreated.",
+            projects.len(), standalone);
+        return Ok(());
+    }
+    let names: Vec<&str> = projects.iter().map(|p| p.name.as_str()).collect();
+    o_detail!("   Docker will restart, then these compose projects are recreated: {}",
+        if names.is_empty() { "-".to_string() } else { names.join(", ") });
+    if !prompt::confirm_yes("Restart Docker and recreate the containers now?", interactive)? {
+        o_warn!("Not restarted. The settings apply once Docker restarts and the containers are recreated.");
+        return Ok(());
+    }
+
+    o_step!("Restarting Docker...");
+    session.exec("systemctl restart docker", None)?;
+    let mut failed = 0;
+    for project in &projects {
+        o_step!("Recreating {}...", project.name.cyan());
+        if let Err(e) = session.exec(&recreate_command(project), None) {
+            o_warn!("   {} {}: {:#}", "⚠".yellow(), project.name, e);
+            failed += 1;
+        }
+    }
+    if standalone > 0 {
+        o_detail!("   {}", format!("{} container(s) outside compose keep their old settings until the next deploy recreates them.", standalone).dimmed());
+    }
+    if failed > 0 {
+        bail!("{} compose project(s) could not be recreated; run `ops deploy` for them", failed);
+    }
+    o_success!("{}", format!("✔ Log rotation active on node #{}", node_id).green());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon_json_and_stale_projects() {
+        assert!(validate_size("50m").is_ok());
+        assert!(validate_size("100k").is_ok());
+        assert!(validate_size("m").is_err());
+        assert!(validate_size("50mb").is_err());
+
+        let merged: Value = serde_json::from_str(&merge_daemon_json(
+            r#"{"live-restore": true, "log-driver": "json-file", "log-opts": {"compress": "true"}}"#, "50m", 3,
+        ).unwrap()).unwrap();
+        assert_eq!(merged, json!({
+            "live-restore": true, "log-driver": "json-file",
+            "log-opts": {"compress": "true", "max-size": "50m", "max-file": "3"}
+        }));
+        let from_journald: Value = serde_json::from_str(&merge_daemon_json(
+            r#"{"log-driver": "journald", "log-opts": {"tag": "x"}}"#, "10m", 2,
+        ).unwrap()).unwrap();
+        assert_eq!(from_journald["log-opts"], json!({"max-size": "10m", "max-file": "2"}));
+        assert!(merge_daemon_json("", "10m", 2).is_ok());
+
+        let inspect = "shop|/opt/shop|/opt/shop/docker-compose.yml|json-file||\n\
+                       shop|/opt/shop|/opt/shop/docker-compose.yml|json-file|||\n\
+                       blog|/opt/blog|/opt/blog/a.yml,/opt/blog/b.yml|json-file|50m|3\n\
+                       |||json-file||\n";
+        let (projects, standalone) = stale_projects(inspect, "50m", 3);
+        assert_eq!(projects.len(), 1);
+        assert_eq!(standalone, 1);
+        assert_eq!(recreate_command(&projects[0]), "cd /opt/shop && docker compose -p shop -f /opt/shop/docker-compose.yml up -d --force-recreate");
+    }
+}
@@ -1,23 +1,70 @@
 use crate::{api, config};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use colored::Colorize;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
+
+/// How `ops login` gets its credentials
+pub enum LoginInput {
+    /// Prompt for username and password
+    Interactive,
+    /// An existing session or API token (`--token`)
+    Token(String),
+    /// `--token-stdin`
+    TokenStdin,
+    /// `--username`, with the password from stdin or a prompt
+    Password { username: String, password_stdin: bool },
+}
+
+/// All of stdin without the trailing newline, so `echo $TOKEN | ops login --token-stdin` works
+pub fn read_stdin_secret(what: &str) -> Result<String> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input).with_context(|| format!("Failed to read {} from stdin", what))?;
+    let secret = input.trim_end_matches(['\r', '\n']).to_string();
+    if secret.is_empty() {
+        bail!("No {} on stdin", what);
+    }
+    Ok(secret)
+}
+
+/// Where the token went: the OS keychain's name, or credentials.json
+fn save_token(token: String) -> Result<&'static str> {
+    config::save_token(token).context("Failed to save credentials")
+}
+
+/// Check the token against the API before saving it, so a typo doesn't log you out
+async fn login_with_token(token: String) -> Result<()> {
+    let token = token.trim().to_string();
+    o_step!("Verifying token...");
+    let me = api::whoami(&token).await.context("Token was rejected")?;
+    let saved_in = save_token(token)?;
+    o_success!("{} {}", "✔ Logged in as".green(), me.username.cyan());
+    o_detail!("   Token saved in {}", saved_in);
+    Ok(())
+}
+
+pub async fn handle_login(input: LoginInput) -> Result<()> {
+    let (username, password) = match input {
+        LoginInput::Token(token) => return login_with_token(token).await,
+        LoginInput::TokenStdin => return login_with_token(read_stdin_secret("token")?).await,
+        LoginInput::Password { username, password_stdin: true } => (username, read_stdin_secret("password")?),
+        LoginInput::Password { username, password_stdin: false } => {
+            let password = rpassword::prompt_password("Enter password: ")?;
+            (username, password)
+        }
+        LoginInput::Interactive => {
+            o_print!("Enter username: ");
+            io::stdout().flush()?;
+            let mut username = String::new();
+            io::stdin().read_line(&mut username)?;
+            let password = rpassword::prompt_password("Enter password: ")?;
+            (username, password)
+        }
+    };
 
-pub async fn handle_login() -> Result<()> {
-    o_print!("Enter username: ");
-    io::stdout().flush()?;
-    let mut username = String::new();
-    io::stdin().read_line(&mut username)?;
-    
-    let password = rpassword::prompt_password("Enter password: ")?;
-    
     o_step!("Logging in...");
     let res = api::login(username.trim(), &password).await?;
-    
-    let mut cfg = config::load_config().unwrap_or_default();
-    cfg.token = Some(res.token);
-    config::save_config(&cfg).context("Failed to save credentials")?;
+    let saved_in = save_token(res.token)?;
 
-    o_success!("{}", "✔ Login successful! Token saved.".green());
+    o_success!("{} {}.", "✔ Login successful! Token saved in".green(), saved_in);
     Ok(())
-}
\ No newline at end of file
+}
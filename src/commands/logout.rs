@@ -4,16 +4,15 @@ use anyhow::{Context, Result};
 use colored::Colorize;
 
 pub async fn handle_logout() -> Result<()> {
-    let mut cfg = config::load_config().context("Could not load config file.")?;
-
-    if cfg.token.is_none() {
+    // Both places: credentials.json and the OS keychain
+    if !config::clear_token().context("Failed to clear credentials.")? {
         o_warn!("{}", "You are not logged in.".yellow());
         return Ok(());
     }
 
-    cfg.token = None;
-    config::save_config(&cfg).context("Failed to clear credentials.")?;
-
     o_success!("{}", "✔ You have been logged out.".green());
+    if std::env::var("OPS_TOKEN").is_ok_and(|t| !t.is_empty()) {
+        o_warn!("{}", "OPS_TOKEN is still set in this shell and keeps authenticating ops.".yellow());
+    }
     Ok(())
-}
\ No newline at end of file
+}
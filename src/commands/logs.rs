@@ -1,30 +1,296 @@
-use crate::commands::deploy::load_ops_toml;
-use crate::commands::ssh;
-use crate::{api, config};
-use anyhow::{Context, Result};
+//! `ops logs`: a service's compose logs from its node, or with `--all-nodes` from every node of
+//! the app at once, each line prefixed with `[node-id/service]`. `--since` and `--until` go to
+//! `docker compose logs`, and are applied again here with `--grep` to the timestamped lines, the
+//! same way for every node.
 
-pub async fn handle_logs(file: String, service: String, tail: u32, follow: bool) -> Result<()> {
-    let config = load_ops_toml(&file)?;
+use crate::commands::deploy::{compose_args, load_ops_toml_env, resolve_app_name, resolve_targets};
+use crate::commands::common::resolve_deploy_target;
+use crate::commands::ssh::{self, SshSession};
+use crate::router::{self, Router};
+use crate::types::OpsToml;
+use crate::utils::Target;
+use anyhow::{anyhow, bail, Result};
+use colored::{Color, Colorize};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-    let project = &config.project;
-    let app = config.apps.first()
-        .map(|a| a.name.as_str())
-        .unwrap_or(project.as_str());
+/// One per node, in turn
+const NODE_COLORS: [Color; 6] = [Color::Cyan, Color::Green, Color::Yellow, Color::Magenta, Color::Blue, Color::Red];
 
-    let cfg = config::load_config().context("Config error")?;
-    let token = cfg.token.context("Please run `ops login` first.")?;
+pub struct LogOptions {
+    /// Lines per node, before the filters; not applied with --since or --until
+    pub tail: u32,
+    pub follow: bool,
+    pub all_nodes: bool,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub grep: Option<String>,
+}
+
+/// A duration ago ("10m", "2h") or a timestamp ("2025-06-01T10:00:00", UTC unless it has an offset)
+pub fn parse_time(value: &str, now: SystemTime) -> Result<SystemTime> {
+    if let Ok(ago) = humantime::parse_duration(value) {
+        return Ok(now - ago);
+    }
+    humantime::parse_rfc3339_weak(value)
+        .map_err(|_| anyhow!("Invalid time '{}'. Use a duration like 10m or 2h, or a timestamp like 2025-06-01T10:00:00", value))
+}
+
+/// What --since, --until and --grep let through
+struct LineFilter {
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+    grep: Option<String>,
+}
+
+impl LineFilter {
+    fn new(opts: &LogOptions) -> Result<Self> {
+        let now = SystemTime::now();
+        Ok(LineFilter {
+            since: opts.since.as_deref().map(|s| parse_time(s, now)).transpose()?,
+            until: opts.until.as_deref().map(|s| parse_time(s, now)).transpose()?,
+            grep: opts.grep.clone(),
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.since.is_none() && self.until.is_none() && self.grep.is_none()
+    }
+
+    /// `docker compose logs` flags: the time window, or `--tail` without one, so that the tail never
+    /// cuts a window short
+    fn compose_flags(&self, tail: u32) -> String {
+        let mut flags = String::new();
+        if let Some(since) = self.since {
+            flags.push_str(&format!(" --since={}", humantime::format_rfc3339_seconds(since)));
+        }
+        if let Some(until) = self.until {
+            flags.push_str(&format!(" --until={}", humantime::format_rfc3339_seconds(until)));
+        }
+        if self.since.is_none() && self.until.is_none() {
+            flags.push_str(&format!(" --tail={}", tail));
+        }
+        flags
+    }
+
+    /// Lines without a timestamp only pass the time filters when there are none
+    fn matches(&self, line: &LogLine) -> bool {
+        let in_range = match line.at {
+            Some(at) => self.since.is_none_or(|s| at >= s) && self.until.is_none_or(|u| at < u),
+            None => self.since.is_none() && self.until.is_none(),
+        };
+        in_range && self.grep.as_deref().is_none_or(|g| line.message.contains(g))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct LogLine {
+    at: Option<SystemTime>,
+    service: String,
+    message: String,
+}
+
+/// A line of `docker compose logs --no-color --timestamps`: "api-1  | 2025-06-01T10:00:00.123456789Z message"
+fn parse_line(line: &str) -> LogLine {
+    let (prefix, rest) = line.split_once(" | ").map(|(p, r)| (p.trim(), r)).unwrap_or(("", line));
+    // Replicas are "<service>-<n>"
+    let service = prefix.rsplit_once('-')
+        .filter(|(_, n)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        .map_or(prefix, |(s, _)| s);
+    let (ts, message) = rest.split_once(' ').unwrap_or((rest, ""));
+    match humantime::parse_rfc3339(ts) {
+        Ok(at) => LogLine { at: Some(at), service: service.to_string(), message: message.to_string() },
+        Err(_) => LogLine { at: None, service: service.to_string(), message: rest.to_string() },
+    }
+}
+
+/// Turns one node's log output into filtered, prefixed lines: printed as they come with
+/// --follow, otherwise kept so every node's lines can be merged by time
+struct NodeLogs {
+    node_id: i64,
+    color: Color,
+    filter: Arc<LineFilter>,
+    kept: Option<Vec<(Option<SystemTime>, String)>>,
+    buf: Vec<u8>,
+}
+
+impl NodeLogs {
+    fn line(&mut self, raw: &[u8]) {
+        let raw = String::from_utf8_lossy(raw);
+        let line = parse_line(raw.trim_end_matches('\r'));
+        if !self.filter.matches(&line) {
+            return;
+        }
+        let text = format!("{} {}", format!("[{}/{}]", self.node_id, line.service).color(self.color), line.message);
+        match &mut self.kept {
+            Some(kept) => kept.push((line.at, text)),
+            None => o_result!("{}", text),
+        }
+    }
+
+    fn finish(mut self) -> Vec<(Option<SystemTime>, String)> {
+        if !self.buf.is_empty() {
+            let rest = std::mem::take(&mut self.buf);
+            self.line(&rest);
+        }
+        self.kept.unwrap_or_default()
+    }
+}
+
+impl Write for NodeLogs {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        while let Some(i) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=i).collect();
+            self.line(&line[..line.len() - 1]);
+        }
+        Ok(data.len())
+    }
 
-    let resp = api::get_app_deploy_targets(&token, project, app).await
-        .context("Failed to get deploy targets")?;
-    let t = resp.targets.first()
-        .context("No nodes bound")?;
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn node_logs(node_id: i64, color: Color, command: Arc<String>, filter: Arc<LineFilter>, follow: bool) -> Result<Vec<(Option<SystemTime>, String)>> {
+    let session = SshSession::connect(&node_id.to_string()).await?;
+    tokio::task::spawn_blocking(move || {
+        let mut out = NodeLogs { node_id, color, filter, kept: (!follow).then(Vec::new), buf: Vec::new() };
+        let mut err = Vec::new();
+        let code = session.exec_to(&command, &mut out, &mut err)?;
+        if code != 0 {
+            bail!("exit {}: {}", code, String::from_utf8_lossy(&err).trim());
+        }
+        Ok(out.finish())
+    }).await?
+}
+
+/// Read the logs of `node_ids` at once through the filter
+async fn stream_logs(config: &OpsToml, service: &str, node_ids: Vec<i64>, opts: &LogOptions, filter: LineFilter) -> Result<()> {
+    let follow_flag = if opts.follow { " -f" } else { "" };
+    let command = Arc::new(format!(
+        "cd {} && docker compose {} logs --no-color --timestamps{}{} {}",
+        config.deploy_path, compose_args(config), filter.compose_flags(opts.tail), follow_flag, service
+    ));
+    let filter = Arc::new(filter);
+    let mut join_set = tokio::task::JoinSet::new();
+    for (i, node_id) in node_ids.iter().copied().enumerate() {
+        let (command, filter) = (command.clone(), filter.clone());
+        let color = NODE_COLORS[i % NODE_COLORS.len()];
+        let follow = opts.follow;
+        join_set.spawn(async move { (node_id, node_logs(node_id, color, command, filter, follow).await) });
+    }
+
+    let mut lines = Vec::new();
+    let mut failed = 0;
+    while let Some(joined) = join_set.join_next().await {
+        match joined? {
+            (_, Ok(kept)) => lines.extend(kept),
+            (node_id, Err(e)) => {
+                o_warn!("{} node {}: {:#}", "⚠".yellow(), node_id, e);
+                failed += 1;
+            }
+        }
+    }
+    // Stable, so each node's lines without a timestamp keep their order
+    lines.sort_by_key(|(at, _)| at.unwrap_or(UNIX_EPOCH));
+    for (_, text) in lines {
+        o_result!("{}", text);
+    }
+    if failed == node_ids.len() {
+        bail!("Could not read logs from any node");
+    }
+    Ok(())
+}
+
+pub async fn handle_logs(file: String, service: Option<String>, target: Option<String>, access: bool, env: Option<String>, opts: LogOptions) -> Result<()> {
+    let config = load_ops_toml_env(&file, env.as_deref())?;
+    if access {
+        return handle_access_logs(&config, service, target, opts.tail, opts.follow).await;
+    }
+    let service = service.unwrap_or_default();
+    let filter = LineFilter::new(&opts)?;
+    // The app that owns this service, for its nodes
+    let app = config.apps.iter().find(|a| a.services.contains(&service)).map(|a| a.name.clone());
+
+    if opts.all_nodes {
+        let node_ids: Vec<i64> = resolve_targets(&config, &app).await?.iter()
+            .filter(|t| config.env_selects(t.node_id, t.region.as_deref()))
+            .map(|t| t.node_id)
+            .collect();
+        if node_ids.is_empty() {
+            bail!("No nodes to read logs from{}", config.env.as_ref().map(|e| format!(" for environment {}", e)).unwrap_or_default());
+        }
+        o_step!("{} {} from {} node(s)", "📜 Logs of".cyan(), if service.is_empty() { "all services" } else { &service }, node_ids.len());
+        return stream_logs(&config, &service, node_ids, &opts, filter).await;
+    }
+
+    // Explicit --target wins; otherwise use the first node bound to the app in ops.toml
+    let target = resolve_deploy_target(&config, app.as_deref(), target).await?;
+    if !filter.is_empty() {
+        let Target::NodeId { id, .. } = target else {
+            bail!("--since, --until and --grep need a node ID as --target");
+        };
+        return stream_logs(&config, &service, vec![id as i64], &opts, filter).await;
+    }
 
-    let follow_flag = if follow { " -f" } else { "" };
+    let follow_flag = if opts.follow { " -f" } else { "" };
     let cmd = format!(
-        "cd {} && docker compose logs --tail={}{} {}",
-        config.deploy_path, tail, follow_flag, service
+        "cd {} && docker compose {} logs --tail={}{} {}",
+        config.deploy_path, compose_args(&config), opts.tail, follow_flag, service
     );
 
-    ssh::handle_ssh(t.domain.clone(), Some(cmd)).await?;
+    ssh::handle_ssh(Some(target.to_string()), ssh::SshOptions::default(), vec![cmd], false).await?;
     Ok(())
 }
+
+/// ops logs --access [app]: tail the JSON access log Caddy writes for an app with `access_logs = true`
+async fn handle_access_logs(config: &OpsToml, app: Option<String>, target: Option<String>, tail: u32, follow: bool) -> Result<()> {
+    if Router::from_config(config.deploy.router.as_deref())? != Router::Caddy {
+        bail!("Access logs are written by Caddy; this project uses router = \"nginx\"");
+    }
+    let app = app.unwrap_or_else(|| resolve_app_name(config));
+    match config.apps.iter().find(|a| a.name == app) {
+        Some(def) if !def.access_logs => bail!("Access logs are off for {}. Set `access_logs = true` in its [[apps]] entry and deploy.", app),
+        None if !config.apps.is_empty() => bail!("App '{}' not found in [[apps]]", app),
+        _ => {}
+    }
+
+    let target = resolve_deploy_target(config, Some(&app), target).await?;
+    let path = router::access_log_path(&app, &config.project);
+    let follow_flag = if follow { " -F" } else { "" };
+    let cmd = format!("tail -n {}{} {}", tail, follow_flag, path);
+
+    ssh::handle_ssh(Some(target.to_string()), ssh::SshOptions::default(), vec![cmd], false).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_and_filter() {
+        let line = parse_line("api-2  | 2025-06-01T10:00:00.123456789Z GET /health 200");
+        assert_eq!(line.service, "api");
+        assert_eq!(line.message, "GET /health 200");
+        assert_eq!(line.at, Some(humantime::parse_rfc3339("2025-06-01T10:00:00.123456789Z").unwrap()));
+        let plain = parse_line("worker  | starting");
+        assert_eq!((plain.at, plain.service.as_str(), plain.message.as_str()), (None, "worker", "starting"));
+
+        let now = humantime::parse_rfc3339("2025-06-01T12:00:00Z").unwrap();
+        assert_eq!(parse_time("2h", now).unwrap(), now - Duration::from_secs(7200));
+        assert_eq!(parse_time("2025-06-01T10:00:00", now).unwrap(), humantime::parse_rfc3339("2025-06-01T10:00:00Z").unwrap());
+        assert!(parse_time("yesterday", now).is_err());
+
+        let filter = LineFilter { since: Some(now - Duration::from_secs(3 * 3600)), until: None, grep: Some("/health".into()) };
+        assert!(filter.matches(&line));
+        assert!(!filter.matches(&parse_line("api-1  | 2025-06-01T10:00:00Z GET /orders 200")));
+        assert!(!filter.matches(&parse_line("api-1  | 2025-06-01T08:00:00Z GET /health 200")));
+        assert!(!filter.matches(&plain));
+        assert_eq!(filter.compose_flags(100), " --since=2025-06-01T09:00:00Z");
+        assert_eq!(LineFilter { since: None, until: None, grep: Some("x".into()) }.compose_flags(100), " --tail=100");
+    }
+}
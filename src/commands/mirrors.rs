@@ -0,0 +1,157 @@
+//! Registry mirrors from `[deploy.registry.mirrors]`. Docker only mirrors Docker Hub itself,
+//! through `registry-mirrors` in daemon.json; other registries (ghcr.io, ...) get a containerd
+//! hosts.toml, which pulls through the containerd image store use.
+
+use super::log_rotation::DAEMON_JSON;
+use crate::node_state::{FileMode, NodeState};
+use crate::privilege::Privilege;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const CONTAINERD_CERTS_DIR: &str = "/etc/containerd/certs.d";
+const DOCKER_HUB_NAMES: [&str; 3] = ["docker.io", "index.docker.io", "registry-1.docker.io"];
+
+pub fn is_docker_hub(registry: &str) -> bool {
+    DOCKER_HUB_NAMES.contains(&registry)
+}
+
+/// Mirror URLs need a scheme; plain http ones are also listed as insecure registries
+pub fn validate(mirrors: &BTreeMap<String, String>) -> Result<()> {
+    for (registry, url) in mirrors {
+        if registry.contains('/') || registry.is_empty() {
+            bail!("Invalid registry '{}' in [deploy.registry.mirrors]: use a host such as ghcr.io", registry);
+        }
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            bail!("Mirror for {} must be an http:// or https:// URL, got '{}'", registry, url);
+        }
+    }
+    Ok(())
+}
+
+/// Host (and port) of a mirror URL
+fn mirror_host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, r)| r);
+    rest.split('/').next().unwrap_or(rest)
+}
+
+/// daemon.json with the Docker Hub mirror and any plain-http mirror hosts; other settings are kept
+pub fn merge_daemon_json(current: &str, mirrors: &BTreeMap<String, String>) -> Result<String> {
+    let mut config: Map<String, Value> = if current.trim().is_empty() {
+        Map::new()
+    } else {
+        serde_json::from_str(current).with_context(|| format!("{} is not a JSON object", DAEMON_JSON))?
+    };
+    let hub: Vec<&String> = mirrors.iter().filter(|(r, _)| is_docker_hub(r)).map(|(_, url)| url).collect();
+    if !hub.is_empty() {
+        config.insert("registry-mirrors".into(), json!(hub));
+    }
+    let mut insecure: Vec<Value> = match config.remove("insecure-registries") {
+        Some(Value::Array(list)) => list,
+        _ => Vec::new(),
+    };
+    for url in mirrors.values().filter(|url| url.starts_with("http://")) {
+        let host = json!(mirror_host(url));
+        if !insecure.contains(&host) {
+            insecure.push(host);
+        }
+    }
+    if !insecure.is_empty() {
+        config.insert("insecure-registries".into(), Value::Array(insecure));
+    }
+    Ok(serde_json::to_string_pretty(&config)? + "\n")
+}
+
+/// containerd hosts.toml sending pulls of `registry` to `url` first, the registry itself after
+pub fn hosts_toml(registry: &str, url: &str) -> String {
+    let mut toml = format!(
+        "# Managed by ops ([deploy.registry.mirrors])\nserver = \"https://{}\"\n\n[host.\"{}\"]\n  capabilities = [\"pull\", \"resolve\"]\n",
+        registry, url.trim_end_matches('/')
+    );
+    if url.starts_with("http://") {
+        toml.push_str("  skip_verify = true\n");
+    }
+    toml
+}
+
+pub fn hosts_toml_path(registry: &str) -> String {
+    format!("{}/{}/hosts.toml", CONTAINERD_CERTS_DIR, registry)
+}
+
+/// Write the mirrors on this node (`ops init`) and reload Docker, which picks up
+/// registry-mirrors and insecure-registries without restarting containers
+pub fn apply_local(privilege: &Privilege, mirrors: &BTreeMap<String, String>, state: &mut NodeState) -> Result<()> {
+    validate(mirrors)?;
+    o_step!("Configuring registry mirrors...");
+
+    let existing = fs::read_to_string(DAEMON_JSON).unwrap_or_default();
+    let updated = merge_daemon_json(&existing, mirrors)?;
+    if existing.trim() != updated.trim() && updated.trim() != "{}" {
+        let backup = if existing.trim().is_empty() {
+            None
+        } else {
+            let ts = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let path = format!("{}.ops-backup-{}", DAEMON_JSON, ts);
+            privilege.write(&path, &existing, 0o644).context("Failed to back up daemon.json")?;
+            Some(path)
+        };
+        privilege.write(DAEMON_JSON, &updated, 0o644).with_context(|| format!("Failed to write {}", DAEMON_JSON))?;
+        let mode = if backup.is_some() { FileMode::Replaced } else { FileMode::Created };
+        state.record_file(DAEMON_JSON, mode, backup);
+        let reloaded = privilege.systemctl().args(["reload", "docker"]).status().is_ok_and(|s| s.success());
+        if !reloaded {
+            o_warn!("{}", "Warning: could not reload Docker; the mirrors apply after it restarts".yellow());
+        }
+    }
+
+    for (registry, url) in mirrors.iter().filter(|(r, _)| !is_docker_hub(r)) {
+        let path = hosts_toml_path(registry);
+        let existed = Path::new(&path).exists();
+        privilege.write(&path, &hosts_toml(registry, url), 0o644).with_context(|| format!("Failed to write {}", path))?;
+        if !existed {
+            state.record_file(&path, FileMode::Created, None);
+        }
+    }
+
+    for (registry, url) in mirrors {
+        o_success!("{}", format!("✔ {} → {}", registry, url).green());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon_json_and_hosts_toml() {
+        let mirrors = BTreeMap::from([
+            ("docker.io".to_string(), "https://mirror.gcr.io".to_string()),
+            ("ghcr.io".to_string(), "http://cache.internal:5000".to_string()),
+        ]);
+        assert!(validate(&mirrors).is_ok());
+        assert!(validate(&BTreeMap::from([("ghcr.io".to_string(), "cache:5000".to_string())])).is_err());
+
+        let merged: Value = serde_json::from_str(&merge_daemon_json(
+            r#"{"log-driver": "json-file", "insecure-registries": ["10.0.0.5:5000"]}"#, &mirrors,
+        ).unwrap()).unwrap();
+        assert_eq!(merged, json!({
+            "log-driver": "json-file",
+            "registry-mirrors": ["https://mirror.gcr.io"],
+            "insecure-registries": ["10.0.0.5:5000", "cache.internal:5000"],
+        }));
+        // Nothing for Docker Hub: registry-mirrors is left alone
+        let ghcr_only = BTreeMap::from([("ghcr.io".to_string(), "https://ghcr-cache.example.com".to_string())]);
+        assert_eq!(merge_daemon_json("", &ghcr_only).unwrap(), "{}\n");
+
+        assert_eq!(hosts_toml_path("ghcr.io"), "/etc/containerd/certs.d/ghcr.io/hosts.toml");
+        assert_eq!(hosts_toml("ghcr.io", "http://cache.internal:5000/"),
+            "# Managed by ops ([deploy.registry.mirrors])\nserver = \"https://ghcr.io\"\n\n[host.\"http://cache.internal:5000\"]\n  capabilities = [\"pull\", \"resolve\"]\n  skip_verify = true\n");
+    }
+}
@@ -1,3 +1,4 @@
+pub mod access;
 pub mod ci_key;
 pub mod login;
 pub mod project;
@@ -5,8 +6,11 @@ pub mod register;
 pub mod server;
 pub mod set;
 pub mod ssh;
+pub mod ssh_config;
 pub mod whoami;
 pub mod update;
+pub mod completions;
+pub mod verify;
 pub mod ip;
 pub mod ping;
 pub mod scp;
@@ -14,10 +18,47 @@ pub mod token;
 pub mod logout;
 pub mod env;
 pub mod build;
+pub mod blueprint;
+pub mod bundle;
+pub mod clone;
 pub mod common;
+pub mod compose;
+pub mod cost;
 pub mod deploy;
+pub mod deploy_plan;
+pub mod dir_sync;
+pub mod disk;
+pub mod doctor;
+pub mod down;
+pub mod drift;
+pub mod graph;
+pub mod ide;
+pub mod image;
+pub mod keys;
+pub mod plugin;
+pub mod policy;
+pub mod preview;
+pub mod ps;
+pub mod secret_store;
+pub mod secrets;
+pub mod sftp;
+pub mod report;
+pub mod restart;
 pub mod status;
+pub mod summary;
+pub mod template;
 pub mod logs;
+pub mod release;
+pub mod canary;
+pub mod events;
+pub mod log_rotation;
+pub mod mirrors;
+pub mod node_config;
+pub mod node_exec;
+pub mod node_migrate;
+pub mod run;
+pub mod exec;
+pub mod scan;
 pub mod serve;
 pub mod node_group;
 pub mod init;
@@ -26,3 +67,5 @@ pub mod launch;
 pub mod domain;
 pub mod pool;
 pub mod tunnel;
+pub mod gc;
+pub mod wait;
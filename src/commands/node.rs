@@ -1,15 +1,22 @@
-use crate::{api, config, prompt};
+use crate::commands::{disk, keys};
+use crate::commands::ssh::SshSession;
+use crate::router::Router;
+use crate::table::{Column, Table, TableOptions};
+use crate::{api, config, output, prompt};
 use anyhow::{anyhow, Context, Result};
 use colored::Colorize;
 
 /// List all nodes owned by the current user
-pub async fn handle_list() -> Result<()> {
+pub async fn handle_list(opts: &TableOptions) -> Result<()> {
     let cfg = config::load_config()
         .context("Could not load config. Please log in with `ops login`.")?;
     let token = cfg.token
         .context("You are not logged in. Please run `ops login` first.")?;
 
     let res = api::list_nodes(&token).await?;
+    if output::json() {
+        return output::print_json(&res);
+    }
 
     if res.nodes.is_empty() {
         o_warn!("{}", "No nodes found.".yellow());
@@ -21,39 +28,42 @@ pub async fn handle_list() -> Result<()> {
     o_step!("{}", "My Nodes:".bold());
     o_detail!();
 
-    for node in res.nodes {
-        let status_icon = match node.status.as_str() {
-            "healthy" => "●".green(),
-            "unhealthy" => "●".red(),
-            "draining" => "◐".yellow(),
-            "offline" => "○".red(),
-            _ => "○".dimmed(),
+    let mut table = Table::new(vec![
+        Column::new("id").right(),
+        Column::new("hostname"),
+        Column::new("ip"),
+        Column::new("region"),
+        Column::new("status"),
+        Column::new("serve"),
+        Column::new("domain"),
+        Column::wide("ipv6"),
+        Column::wide("zone"),
+        Column::wide("last_check"),
+    ]);
+    for node in &res.nodes {
+        let status = match node.status.as_str() {
+            "healthy" => format!("● {}", node.status).green(),
+            "unhealthy" => format!("● {}", node.status).red(),
+            "draining" => format!("◐ {}", node.status).yellow(),
+            "offline" => format!("○ {}", node.status).red(),
+            _ => format!("○ {}", node.status).dimmed(),
         };
+        let serve = if node.has_serve_token > 0 { "✓".green() } else { "✗".red() };
 
-        let serve_status = if node.has_serve_token > 0 {
-            "serve ✓".green()
-        } else {
-            "serve ✗".red()
-        };
-
-        let region_str = node.region.as_deref().unwrap_or("-");
-        let hostname_str = node.hostname.as_deref().unwrap_or(&node.ip_address);
-
-        o_detail!(
-            "  {} {} {} ({}) region:{} [{}]",
-            status_icon,
-            format!("#{}", node.id).dimmed(),
-            hostname_str.cyan().bold(),
-            node.ip_address,
-            region_str,
-            serve_status
-        );
-        o_detail!("      Domain: {}", node.domain.dimmed());
-
-        if let Some(last_check) = node.last_health_check {
-            o_detail!("      Last check: {}", last_check.dimmed());
-        }
+        table.row(vec![
+            format!("#{}", node.id).dimmed().to_string(),
+            node.hostname.as_deref().unwrap_or(&node.ip_address).cyan().bold().to_string(),
+            node.ip_address.clone(),
+            node.region.clone().unwrap_or_else(|| "-".into()),
+            status.to_string(),
+            serve.to_string(),
+            node.domain.dimmed().to_string(),
+            node.ipv6_address.clone().unwrap_or_else(|| "-".into()),
+            node.zone.clone().unwrap_or_else(|| "-".into()),
+            node.last_health_check.clone().unwrap_or_else(|| "-".into()),
+        ]);
     }
+    table.print(opts)?;
 
     o_detail!();
     o_detail!("{}", "Use 'ops node info <id>' for details".dimmed());
@@ -61,6 +71,122 @@ pub async fn handle_list() -> Result<()> {
     Ok(())
 }
 
+/// Record (or clear, with `amount = None`) a node's monthly cost
+pub async fn handle_set_cost(node_id: u64, amount: Option<f64>, currency: String) -> Result<()> {
+    let cfg = config::load_config()
+        .context("Could not load config. Please log in with `ops login`.")?;
+    let token = cfg.token
+        .context("You are not logged in. Please run `ops login` first.")?;
+
+    let currency = currency.to_uppercase();
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(anyhow!("Invalid currency '{}'. Use a 3-letter code such as USD or EUR", currency));
+    }
+    if amount.is_some_and(|a| !a.is_finite() || a < 0.0) {
+        return Err(anyhow!("Cost must be zero or more"));
+    }
+
+    api::set_node_cost(&token, node_id, amount, amount.map(|_| currency.as_str())).await?;
+
+    match amount {
+        Some(a) => o_success!("{} Node #{} costs {} {:.2}/month", "✔".green(), node_id, currency, a),
+        None => o_success!("{} Cleared the cost of node #{}", "✔".green(), node_id),
+    }
+    Ok(())
+}
+
+/// Marker line above personal keys added by `ops node grant`; revoke removes the key below it
+const GRANT_COMMENT_PREFIX: &str = "# ops grant: ";
+
+fn validate_grant_label(label: &str) -> Result<()> {
+    let valid = !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || "._@-".contains(c));
+    if !valid {
+        return Err(anyhow!("Invalid label '{}'. Use letters, digits, '.', '_', '@' or '-'", label));
+    }
+    Ok(())
+}
+
+/// Shell that drops every `# ops grant: <label>` line and the key line below it from `path`
+fn revoke_script(path: &str, label: &str) -> String {
+    format!(
+        "[ -f {path} ] || exit 0; c={comment} awk '$0 == ENVIRON[\"c\"] {{ skip = 1; next }} skip {{ skip = 0; next }} {{ print }}' \
+         {path} > {path}.ops-tmp && cat {path}.ops-tmp > {path} && rm -f {path}.ops-tmp",
+        path = keys::path_arg(path), comment = crate::utils::shell_quote(&format!("{}{}", GRANT_COMMENT_PREFIX, label)),
+    )
+}
+
+/// ops node grant: add a person's SSH keys to a node, labeled so they can be revoked
+pub async fn handle_grant(node_id: u64, github_user: Option<String>, pubkey: Option<String>, label: Option<String>) -> Result<()> {
+    let cfg = config::load_config()
+        .context("Could not load config. Please log in with `ops login`.")?;
+    let token = cfg.token
+        .context("You are not logged in. Please run `ops login` first.")?;
+
+    let (keys, source, default_label) = match (github_user, pubkey) {
+        (Some(user), _) => {
+            if user.is_empty() || !user.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(anyhow!("Invalid GitHub user name '{}'", user));
+            }
+            let keys = api::fetch_github_keys(&user).await
+                .with_context(|| format!("Failed to fetch SSH keys of GitHub user '{}'", user))?;
+            (keys, format!("github:{}", user), user)
+        }
+        (None, Some(path)) => {
+            let expanded = shellexpand::tilde(&path).to_string();
+            let content = std::fs::read_to_string(&expanded)
+                .with_context(|| format!("Cannot read {}", expanded))?;
+            let keys: Vec<String> = content.lines().map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .collect();
+            let stem = std::path::Path::new(&expanded).file_stem()
+                .map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            (keys, format!("file:{}", path), stem)
+        }
+        (None, None) => return Err(anyhow!("Pass --github-user <name> or --pubkey <file>")),
+    };
+    if keys.is_empty() {
+        return Err(anyhow!("No public keys found for {}", source));
+    }
+    if let Some(bad) = keys.iter().find(|k| !k.starts_with("ssh-") && !k.starts_with("ecdsa-") && !k.starts_with("sk-")) {
+        return Err(anyhow!("Not an SSH public key: {}", bad.chars().take(40).collect::<String>()));
+    }
+    let label = label.unwrap_or(default_label);
+    validate_grant_label(&label)?;
+
+    o_step!("{} {} key(s) for {} on node #{}...", "🔑 Granting".cyan(), keys.len(), label.green(), node_id);
+    let session = SshSession::connect(&node_id.to_string()).await?;
+    let comment = format!("{}{}", GRANT_COMMENT_PREFIX, label);
+    for key in &keys {
+        session.exec(&keys::install_script(keys::AUTHORIZED_KEYS, key, &comment), None)?;
+    }
+    o_success!("   {} added to root's authorized_keys", "✔".green());
+
+    api::create_ssh_grant(&token, node_id, &label, &source, &keys).await
+        .context("Keys were added to the node, but recording the grant failed")?;
+    o_success!("{} Granted {} SSH access to node #{} as root. Revoke with `ops node revoke {} {}`",
+        "✔".green(), label, node_id, node_id, label);
+    Ok(())
+}
+
+/// ops node revoke: remove the keys granted under `label`
+pub async fn handle_revoke(node_id: u64, label: String) -> Result<()> {
+    let cfg = config::load_config()
+        .context("Could not load config. Please log in with `ops login`.")?;
+    let token = cfg.token
+        .context("You are not logged in. Please run `ops login` first.")?;
+    validate_grant_label(&label)?;
+
+    o_step!("{} {} on node #{}...", "🔒 Revoking".cyan(), label.green(), node_id);
+    let session = SshSession::connect(&node_id.to_string()).await?;
+    session.exec(&revoke_script(keys::AUTHORIZED_KEYS, &label), None)?;
+    o_success!("   {} keys removed from authorized_keys", "✔".green());
+
+    api::delete_ssh_grant(&token, node_id, &label).await
+        .context("Keys were removed from the node, but deleting the grant record failed")?;
+    o_success!("{} Revoked {}'s access to node #{}", "✔".green(), label, node_id);
+    Ok(())
+}
+
 /// Show detailed information about a specific node
 pub async fn handle_info(node_id: u64) -> Result<()> {
     let cfg = config::load_config()
@@ -83,6 +209,9 @@ pub async fn handle_info(node_id: u64) -> Result<()> {
     o_detail!("  Status:      {} {}", status_icon, node.status);
     o_detail!("  Domain:      {}", node.domain.cyan());
     o_detail!("  IP Address:  {}", node.ip_address);
+    if let Some(ref v6) = node.ipv6_address {
+        o_detail!("  IPv6:        {}", v6);
+    }
 
     if let Some(hostname) = node.hostname {
         o_detail!("  Hostname:    {}", hostname);
@@ -95,6 +224,9 @@ pub async fn handle_info(node_id: u64) -> Result<()> {
     }
 
     o_detail!("  Serve Port:  {}", node.serve_port);
+    if let Some(cost) = node.monthly_cost {
+        o_detail!("  Cost:        {} {:.2}/month", node.cost_currency.as_deref().unwrap_or(crate::commands::cost::DEFAULT_CURRENCY), cost);
+    }
     o_detail!("  Created:     {}", node.created_at);
 
     if let Some(last_check) = node.last_health_check {
@@ -170,3 +302,315 @@ pub async fn handle_remove(node_id: u64, force: bool, interactive: bool) -> Resu
 
     Ok(())
 }
+
+/// Free disk on a node; volumes are never touched
+pub async fn handle_prune(node_id: u64, all: bool, interactive: bool) -> Result<()> {
+    let session = SshSession::connect(&node_id.to_string()).await?;
+    let before = disk::free_bytes(&session)?;
+    o_step!("{}", format!("Disk on node #{}: {} free", node_id, disk::format_bytes(before)).bold());
+    session.exec("docker system df", None)?;
+
+    let what = if all { "stopped containers, unused images and build cache" } else { "stopped containers, dangling images and build cache" };
+    if !prompt::confirm_yes(&format!("Remove {}?", what), interactive)? {
+        o_warn!("Operation cancelled.");
+        return Ok(());
+    }
+    let images = if all { "docker image prune -af" } else { "docker image prune -f" };
+    session.exec(&format!("docker container prune -f >/dev/null && {} >/dev/null && docker builder prune -f >/dev/null", images), None)?;
+
+    let after = disk::free_bytes(&session)?;
+    o_success!("{}", format!("✔ Freed {} ({} free)", disk::format_bytes(after.saturating_sub(before)), disk::format_bytes(after)).green());
+    if !all {
+        o_detail!("{}", format!("Unused tagged images were kept. Run 'ops node prune {} --all' to remove them too.", node_id).dimmed());
+    }
+    Ok(())
+}
+
+/// List Caddy route fragments on a node, optionally pruning orphans
+pub async fn handle_routes(node_id: u64, prune_orphans: bool, interactive: bool) -> Result<()> {
+    let cfg = config::load_config()
+        .context("Could not load config. Please log in with `ops login`.")?;
+    let token = cfg.token
+        .context("You are not logged in. Please run `ops login` first.")?;
+
+    let node = api::get_node(&token, node_id).await?;
+    let bound: Vec<(String, String)> = node.bound_apps.unwrap_or_default()
+        .into_iter()
+        .map(|a| (a.name, a.project_name))
+        .collect();
+
+    let session = SshSession::connect(&node_id.to_string()).await?;
+    let mut fragments = Router::Caddy.list_fragments(&session)?;
+    fragments.extend(Router::Nginx.list_fragments(&session)?);
+
+    o_step!("{}", format!("Routes on node #{}:", node_id).bold());
+    o_detail!();
+
+    if fragments.is_empty() {
+        o_warn!("{}", "No route fragments found.".yellow());
+        return Ok(());
+    }
+
+    let mut orphans = Vec::new();
+    for f in &fragments {
+        let owned_by_bound = bound.iter().any(|(a, p)| f.belongs_to(a, p));
+        let (icon, owner) = match (&f.owner, owned_by_bound) {
+            _ if f.is_tunnel() => ("●".cyan(), "tunnel".to_string()),
+            (_, true) => {
+                let (a, p) = bound.iter().find(|(a, p)| f.belongs_to(a, p)).unwrap();
+                ("●".green(), format!("{}.{}", a, p))
+            }
+            (Some((a, p)), false) => {
+                orphans.push(f.clone());
+                ("○".red(), format!("{}.{} (orphan)", a, p))
+            }
+            (None, false) => ("?".yellow(), "unknown".to_string()),
+        };
+        o_detail!("  {} {:<55} {}", icon, f.path, owner);
+    }
+
+    o_detail!();
+    if orphans.is_empty() {
+        o_success!("{}", "✔ No orphaned routes.".green());
+        return Ok(());
+    }
+
+    if !prune_orphans {
+        o_detail!("{}", format!("{} orphaned route(s). Run 'ops node routes {} --prune-orphans' to remove them.", orphans.len(), node_id).dimmed());
+        return Ok(());
+    }
+
+    if !prompt::confirm_yes(&format!("Remove {} orphaned route(s)?", orphans.len()), interactive)? {
+        o_warn!("Operation cancelled.");
+        return Ok(());
+    }
+    for router in [Router::Caddy, Router::Nginx] {
+        let paths: Vec<String> = orphans.iter()
+            .filter(|f| f.router == router)
+            .map(|f| f.path.clone())
+            .collect();
+        if !paths.is_empty() {
+            router.remove_fragments(&session, &paths)?;
+            o_success!("{}", format!("✔ Removed {} orphaned {} route(s), {} reloaded.", paths.len(), router.name(), router.name()).green());
+        }
+    }
+
+    Ok(())
+}
+
+/// A machine found in Terraform outputs
+#[derive(Debug, PartialEq)]
+struct TfNode {
+    ip: String,
+    hostname: Option<String>,
+    region: Option<String>,
+}
+
+const TF_IP_KEYS: [&str; 5] = ["public_ip", "ipv4_address", "ip_address", "ip", "address"];
+const TF_NAME_KEYS: [&str; 2] = ["hostname", "name"];
+
+/// Read nodes from `terraform output -json` or a state file (its `outputs`). Output values may be an IP,
+/// a list of IPs, a hostname → IP map, or objects with an IP field (public_ip, ipv4_address, ...) and
+/// optional hostname/name and region.
+fn parse_tf_outputs(json: &serde_json::Value, only: Option<&str>) -> Result<Vec<TfNode>> {
+    let outputs = json.get("outputs").unwrap_or(json)
+        .as_object()
+        .context("Expected Terraform outputs (`terraform output -json` or a state file)")?;
+
+    let mut nodes = Vec::new();
+    for (name, output) in outputs {
+        if only.is_some_and(|o| o != name) {
+            continue;
+        }
+        let value = output.get("value").unwrap_or(output);
+        collect_tf_nodes(value, None, &mut nodes);
+    }
+    if let Some(name) = only {
+        if !outputs.contains_key(name) {
+            return Err(anyhow!("Output '{}' not found in Terraform outputs", name));
+        }
+    }
+    nodes.dedup_by(|a, b| a.ip == b.ip);
+    Ok(nodes)
+}
+
+fn collect_tf_nodes(value: &serde_json::Value, key: Option<&str>, nodes: &mut Vec<TfNode>) {
+    use serde_json::Value;
+    let is_ip = |s: &str| s.parse::<std::net::IpAddr>().is_ok();
+    match value {
+        Value::String(s) if is_ip(s) => nodes.push(TfNode { ip: s.clone(), hostname: key.map(String::from), region: None }),
+        Value::Array(items) => items.iter().for_each(|v| collect_tf_nodes(v, None, nodes)),
+        Value::Object(obj) => {
+            let field = |keys: &[&str]| keys.iter().find_map(|k| obj.get(*k).and_then(|v| v.as_str()).map(String::from));
+            match field(&TF_IP_KEYS).filter(|ip| is_ip(ip)) {
+                Some(ip) => nodes.push(TfNode {
+                    ip,
+                    hostname: field(&TF_NAME_KEYS).or(key.map(String::from)),
+                    region: field(&["region"]),
+                }),
+                // hostname → IP (or → object) map
+                None => obj.iter().for_each(|(k, v)| collect_tf_nodes(v, Some(k), nodes)),
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Install ops and run `ops init` on a fresh box. The token arrives on stdin, never in argv.
+fn tf_bootstrap_script(token: &str, user: &str, node: &TfNode, region: Option<&str>) -> String {
+    let sudo = if user == "root" { "" } else { "sudo " };
+    // The token stays in the environment: on a command line any user on the host could read it
+    let preserve = if user == "root" { "" } else { "sudo --preserve-env=OPS_TOKEN " };
+    let mut init = format!("{}ops init --yes", preserve);
+    if let Some(r) = node.region.as_deref().or(region) {
+        init.push_str(&format!(" --region {}", crate::utils::shell_quote(r)));
+    }
+    if let Some(h) = &node.hostname {
+        init.push_str(&format!(" --hostname {}", crate::utils::shell_quote(h)));
+    }
+    format!(
+        "set -e\nexport OPS_TOKEN={}\ncommand -v ops >/dev/null 2>&1 || curl -fsSL https://get.ops.autos | {}sh\n{}\n",
+        crate::utils::shell_quote(token), sudo, init
+    )
+}
+
+/// Register every machine in Terraform outputs as a node by running `ops init` on it over SSH
+pub async fn handle_import_tf(
+    file: String,
+    output: Option<String>,
+    user: String,
+    identity: Option<String>,
+    region: Option<String>,
+    dry_run: bool,
+    interactive: bool,
+) -> Result<()> {
+    let content = std::fs::read_to_string(&file).with_context(|| format!("Cannot read {}", file))?;
+    let json: serde_json::Value = serde_json::from_str(&content)
+        .with_context(|| format!("{} is not JSON", file))?;
+    let nodes = parse_tf_outputs(&json, output.as_deref())?;
+    if nodes.is_empty() {
+        return Err(anyhow!("No IP addresses found in {}. Pick an output with --output", file));
+    }
+
+    o_step!("{} {} machine(s) in {}", "🏗  Terraform:".cyan(), nodes.len(), file);
+    for n in &nodes {
+        o_detail!("   {:<16} {:<20} {}", n.ip, n.hostname.as_deref().unwrap_or("-"),
+            n.region.as_deref().or(region.as_deref()).unwrap_or("(auto region)"));
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !prompt::confirm_yes(&format!("Install ops and run `ops init` on {} machine(s) as {}?", nodes.len(), user), interactive)? {
+        o_warn!("Aborted.");
+        return Ok(());
+    }
+
+    let cfg = config::load_config()
+        .context("Could not load config. Please log in with `ops login`.")?;
+    let token = cfg.token
+        .context("You are not logged in. Please run `ops login` first.")?;
+
+    let mut failed = Vec::new();
+    for (i, n) in nodes.iter().enumerate() {
+        o_step!("\n{} [{}/{}] {}", "🚀".cyan(), i + 1, nodes.len(), n.hostname.as_deref().unwrap_or(&n.ip).cyan());
+        let mut cmd = std::process::Command::new("ssh");
+        if let Some(ref key) = identity {
+            cmd.arg("-i").arg(key);
+        }
+        cmd.arg("-o").arg("StrictHostKeyChecking=accept-new")
+            .arg("-o").arg("BatchMode=yes")
+            .arg(format!("{}@{}", user, n.ip))
+            .arg("sh -s")
+            .stdin(std::process::Stdio::piped());
+        let result = cmd.spawn()
+            .context("Failed to run ssh")
+            .and_then(|mut child| {
+                use std::io::Write;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(tf_bootstrap_script(&token, &user, n, region.as_deref()).as_bytes())?;
+                }
+                Ok(child.wait()?)
+            });
+        match result {
+            Ok(status) if status.success() => o_success!("   {} {}", "✔".green(), n.ip),
+            Ok(status) => {
+                o_error!("   {} {}: bootstrap exited with {}", "✘".red(), n.ip, status);
+                failed.push(n.ip.clone());
+            }
+            Err(e) => {
+                o_error!("   {} {}: {}", "✘".red(), n.ip, e);
+                failed.push(n.ip.clone());
+            }
+        }
+    }
+
+    o_result!("\n{} Registered {}/{} machine(s). Run `ops node list` to see their node IDs.",
+        if failed.is_empty() { "✅".green() } else { "⚠️".yellow() },
+        nodes.len() - failed.len(), nodes.len());
+    if !failed.is_empty() {
+        return Err(anyhow!("Bootstrap failed on {}", failed.join(", ")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grant_revoke_scripts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("authorized_keys");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "ssh-rsa AAAAowner me@laptop\n").unwrap();
+
+        let run = |script: String| {
+            assert!(std::process::Command::new("sh").arg("-c").arg(script).status().unwrap().success());
+        };
+        let alice = format!("{}alice", GRANT_COMMENT_PREFIX);
+        run(keys::install_script(path, "ssh-ed25519 AAAAalice1", &alice));
+        run(keys::install_script(path, "ssh-ed25519 AAAAalice2", &alice));
+        run(keys::install_script(path, "ssh-ed25519 AAAAbob", &format!("{}bob", GRANT_COMMENT_PREFIX)));
+        run(revoke_script(path, "alice"));
+
+        let content = std::fs::read_to_string(path).unwrap();
+        assert!(!content.contains("alice"));
+        assert!(content.contains("ssh-rsa AAAAowner me@laptop") && content.contains("ssh-ed25519 AAAAbob"));
+
+        assert!(validate_grant_label("alice@acme.io").is_ok());
+        assert!(validate_grant_label("alice'; rm -rf /").is_err());
+    }
+
+    #[test]
+    fn test_parse_tf_outputs() {
+        let state = serde_json::json!({
+            "version": 4,
+            "outputs": {
+                "web_ips": { "value": ["203.0.113.1", "203.0.113.2"], "type": ["list", "string"] },
+                "db": { "value": { "db-1": "203.0.113.9" } },
+                "workers": { "value": [{ "name": "w1", "public_ip": "198.51.100.4", "region": "eu-west" }] },
+                "vpc_id": { "value": "vpc-123" }
+            }
+        });
+        let nodes = parse_tf_outputs(&state, None).unwrap();
+        let ips: Vec<&str> = nodes.iter().map(|n| n.ip.as_str()).collect();
+        assert_eq!(ips, vec!["203.0.113.9", "203.0.113.1", "203.0.113.2", "198.51.100.4"]);
+        assert_eq!(nodes[0].hostname.as_deref(), Some("db-1"));
+        assert_eq!(nodes[3], TfNode { ip: "198.51.100.4".into(), hostname: Some("w1".into()), region: Some("eu-west".into()) });
+
+        // `terraform output -json` has no "outputs" wrapper
+        let outputs = serde_json::json!({ "web_ips": { "value": ["203.0.113.1"] } });
+        assert_eq!(parse_tf_outputs(&outputs, Some("web_ips")).unwrap().len(), 1);
+        assert!(parse_tf_outputs(&outputs, Some("missing")).is_err());
+    }
+
+    #[test]
+    fn test_tf_bootstrap_script_keeps_token_off_argv() {
+        let node = TfNode { ip: "203.0.113.1".into(), hostname: Some("web-1".into()), region: None };
+        let script = tf_bootstrap_script("tok'en", "ubuntu", &node, Some("eu"));
+        assert!(script.contains("export OPS_TOKEN='tok'\\''en'\n"));
+        assert!(script.contains("sudo --preserve-env=OPS_TOKEN ops init --yes --region eu --hostname web-1"));
+        assert!(!script.contains("env OPS_TOKEN="));
+        assert!(tf_bootstrap_script("t", "root", &node, None).contains("\nops init --yes --hostname"));
+    }
+}
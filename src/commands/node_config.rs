@@ -0,0 +1,130 @@
+//! `ops node config backup`: a tarball of what a node needs to be rebuilt. Route fragments,
+//! the Caddyfile, the ops-serve unit, ops' node state and every compose project's files are
+//! kept under their absolute paths; env files only contribute their variable names.
+
+use crate::caddy;
+use crate::commands::ssh::SshSession;
+use crate::node_state::STATE_FILE;
+use crate::serve::previews::PREVIEWS_DIR;
+use crate::utils::shell_quote;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Node files kept as they are (missing ones are skipped)
+const NODE_FILES: [&str; 6] = [
+    caddy::CADDYFILE,
+    caddy::ROUTES_DIR,
+    "/etc/nginx/conf.d/ops.conf",
+    "/etc/nginx/conf.d/ops-routes",
+    "/etc/systemd/system/ops-serve.service",
+    PREVIEWS_DIR,
+];
+
+/// A compose project found on the node
+#[derive(Debug, PartialEq)]
+struct ComposeProject {
+    working_dir: String,
+    /// Absolute paths
+    config_files: Vec<String>,
+}
+
+/// From `docker inspect` lines "working_dir|config_files", one project per working dir
+fn parse_projects(inspect: &str) -> Vec<ComposeProject> {
+    let mut projects: Vec<ComposeProject> = Vec::new();
+    for line in inspect.lines() {
+        let Some((dir, files)) = line.trim().split_once('|') else { continue };
+        if dir.is_empty() || projects.iter().any(|p| p.working_dir == dir) {
+            continue;
+        }
+        projects.push(ComposeProject {
+            working_dir: dir.to_string(),
+            config_files: files.split(',').filter(|f| !f.is_empty()).map(String::from).collect(),
+        });
+    }
+    projects
+}
+
+/// Shell that writes the archive (tar.gz) to stdout: files under `files/`, env variable names
+/// under `env/<path>.keys`
+fn backup_script(projects: &[ComposeProject]) -> String {
+    let mut files: Vec<String> = NODE_FILES.iter().map(|f| f.to_string()).collect();
+    files.push(STATE_FILE.to_string());
+    let mut dirs = Vec::new();
+    for p in projects {
+        files.push(format!("{}/ops.toml", p.working_dir));
+        files.extend(p.config_files.iter().cloned());
+        dirs.push(shell_quote(&p.working_dir));
+    }
+    let files: Vec<String> = files.iter().map(|f| shell_quote(f)).collect();
+    format!(
+        concat!(
+            "set -e\nT=$(mktemp -d)\ntrap 'rm -rf \"$T\"' EXIT\n",
+            "for f in {} /etc/nginx/conf.d/ops-*.conf; do [ -e \"$f\" ] || continue; ",
+            "mkdir -p \"$T/files$(dirname \"$f\")\" && cp -a \"$f\" \"$T/files$f\"; done\n",
+            "for d in {}; do [ -d \"$d\" ] || continue; ",
+            "find \"$d\" -maxdepth 2 -type f \\( -name '.env' -o -name '.env.*' -o -name '*.env' \\) | while read -r f; do ",
+            "mkdir -p \"$T/env$(dirname \"$f\")\" && ",
+            "sed -nE 's/^[[:space:]]*(export[[:space:]]+)?([A-Za-z_][A-Za-z0-9_]*)=.*/\\2/p' \"$f\" > \"$T/env$f.keys\"; done; done\n",
+            "tar czf - -C \"$T\" .",
+        ),
+        files.join(" "),
+        if dirs.is_empty() { "''".to_string() } else { dirs.join(" ") },
+    )
+}
+
+/// `node-<id>-config-<UTC time>.tar.gz`
+fn archive_name(node_id: u64, now: SystemTime) -> String {
+    let ts = humantime::format_rfc3339_seconds(now).to_string();
+    format!("node-{}-config-{}.tar.gz", node_id, ts.trim_end_matches('Z').replace([':', '-'], "").replace('T', "-"))
+}
+
+/// ops node config backup
+pub async fn handle_backup(node_id: u64, out: String) -> Result<()> {
+    let session = SshSession::connect(&node_id.to_string()).await?;
+
+    let inspect = concat!(
+        "docker ps -aq | xargs -r docker inspect -f ",
+        "'{{index .Config.Labels \"com.docker.compose.project.working_dir\"}}|{{index .Config.Labels \"com.docker.compose.project.config_files\"}}'",
+    );
+    let projects = parse_projects(&String::from_utf8_lossy(&session.exec_output(inspect)?));
+    o_step!("{}", format!("Backing up node #{} configuration", node_id).bold());
+    for p in &projects {
+        o_detail!("   {} {}", "•".dimmed(), p.working_dir);
+    }
+
+    let archive = session.exec_output(&backup_script(&projects))?;
+    let dir = PathBuf::from(out);
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(archive_name(node_id, SystemTime::now()));
+    std::fs::write(&path, &archive).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    o_success!("{}", format!("✔ Wrote {} ({} KiB, {} compose project(s))", path.display(), archive.len() / 1024, projects.len()).green());
+    o_detail!("   {}", "Env files are recorded by variable name only; keep their values in your secrets store.".dimmed());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_projects_and_script() {
+        let projects = parse_projects("/opt/shop|/opt/shop/docker-compose.yml,/opt/shop/.ops-preview.yml\n/opt/shop|/opt/shop/docker-compose.yml\n|\n/opt/blog|\n");
+        assert_eq!(projects, vec![
+            ComposeProject { working_dir: "/opt/shop".into(), config_files: vec!["/opt/shop/docker-compose.yml".into(), "/opt/shop/.ops-preview.yml".into()] },
+            ComposeProject { working_dir: "/opt/blog".into(), config_files: vec![] },
+        ]);
+
+        let script = backup_script(&projects);
+        assert!(script.contains("for f in /etc/caddy/Caddyfile /etc/caddy/routes.d "));
+        assert!(script.contains(" /opt/shop/ops.toml /opt/shop/docker-compose.yml /opt/shop/.ops-preview.yml /opt/blog/ops.toml /etc/nginx/conf.d/ops-*.conf;"));
+        assert!(script.contains("for d in /opt/shop /opt/blog;"));
+        assert!(script.ends_with("tar czf - -C \"$T\" ."));
+        assert!(backup_script(&[]).contains("for d in '';"));
+
+        assert_eq!(archive_name(42, UNIX_EPOCH + Duration::from_secs(1_760_000_000)), "node-42-config-20251009-085320.tar.gz");
+    }
+}
@@ -0,0 +1,211 @@
+//! `ops node exec <app.project> -- <command>`: run a shell command on every node an app is
+//! deployed to, all at once or one at a time, with each output line prefixed by its node.
+
+use crate::commands::ssh::SshSession;
+use crate::types::DeployTarget;
+use crate::utils::{self, Target};
+use crate::{api, config, output};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::Arc;
+
+#[derive(Serialize)]
+struct NodeRun {
+    node_id: i64,
+    domain: String,
+    exit_code: Option<i32>,
+    /// Captured only with --json; otherwise streamed as it arrives
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stderr: Option<String>,
+    error: Option<String>,
+}
+
+impl NodeRun {
+    fn ok(&self) -> bool {
+        self.error.is_none() && self.exit_code == Some(0)
+    }
+}
+
+/// Writes whole lines as `<prefix> line` to stdout or stderr; with no prefix, keeps them (--json)
+struct Prefixed {
+    prefix: Option<String>,
+    stderr: bool,
+    buf: Vec<u8>,
+}
+
+impl Prefixed {
+    fn new(prefix: Option<String>, stderr: bool) -> Self {
+        Prefixed { prefix, stderr, buf: Vec::new() }
+    }
+
+    fn emit(&self, line: &[u8]) {
+        let Some(prefix) = &self.prefix else { return };
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches('\r');
+        if self.stderr {
+            o_error!("{} {}", prefix, line);
+        } else {
+            o_result!("{} {}", prefix, line);
+        }
+    }
+
+    /// Print a last line without a newline; the captured text in --json mode
+    fn finish(mut self) -> Option<String> {
+        if self.prefix.is_none() {
+            return Some(String::from_utf8_lossy(&self.buf).to_string());
+        }
+        if !self.buf.is_empty() {
+            let rest = std::mem::take(&mut self.buf);
+            self.emit(&rest);
+        }
+        None
+    }
+}
+
+impl Write for Prefixed {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.prefix.is_some() {
+            while let Some(i) = self.buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=i).collect();
+                self.emit(&line[..line.len() - 1]);
+            }
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `[domain]` padded to the longest, so the output columns line up
+fn prefixes(targets: &[DeployTarget]) -> Vec<String> {
+    let width = targets.iter().map(|t| t.domain.len()).max().unwrap_or(0);
+    targets.iter().map(|t| format!("[{:<width$}]", t.domain, width = width)).collect()
+}
+
+async fn run_on(target: DeployTarget, prefix: Option<String>, command: Arc<String>) -> NodeRun {
+    let (node_id, domain) = (target.node_id, target.domain.clone());
+    let result: Result<(i32, Option<String>, Option<String>)> = async {
+        let session = SshSession::connect(&node_id.to_string()).await?;
+        tokio::task::spawn_blocking(move || {
+            let mut out = Prefixed::new(prefix.clone(), false);
+            let mut err = Prefixed::new(prefix, true);
+            let code = session.exec_to(&command, &mut out, &mut err)?;
+            Ok((code, out.finish(), err.finish()))
+        }).await?
+    }.await;
+    match result {
+        Ok((code, stdout, stderr)) => NodeRun { node_id, domain, exit_code: Some(code), stdout, stderr, error: None },
+        Err(e) => NodeRun { node_id, domain, exit_code: None, stdout: None, stderr: None, error: Some(format!("{:#}", e)) },
+    }
+}
+
+/// ops node exec <app.project> -- <command>
+pub async fn handle_exec(target: String, command: Vec<String>, parallel: Option<usize>, rolling: bool) -> Result<()> {
+    let (app, project) = match utils::parse_target(&target)? {
+        Target::AppTarget { app, project, path: None } => (app, project),
+        _ => bail!("ops node exec takes an app target (app.project), e.g. api.shop"),
+    };
+    if command.is_empty() {
+        bail!("No command given. Usage: ops node exec <app.project> -- <command>");
+    }
+    // Each word stays one argument on the node; pipes and redirects need `sh -c '...'`
+    let command = Arc::new(command.iter().map(|w| utils::shell_quote(w)).collect::<Vec<_>>().join(" "));
+
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let targets = api::get_app_deploy_targets(&token, &project, &app).await
+        .context("Failed to get deploy targets")?.targets;
+    if targets.is_empty() {
+        bail!("No nodes bound to {}.{}", app, project);
+    }
+
+    let json = output::json();
+    let labels: Vec<Option<String>> = prefixes(&targets).into_iter()
+        .map(|p| (!json).then(|| p.cyan().to_string()))
+        .collect();
+    let limit = if rolling { 1 } else { parallel.unwrap_or(targets.len()).max(1) };
+    o_step!("{} {} on {} node(s){}: {}", "▶ Running".cyan(), format!("{}.{}", app, project).bold(), targets.len(),
+        if rolling { ", one at a time" } else { "" }, command.yellow());
+
+    let mut runs = Vec::new();
+    if rolling {
+        // Stop at the first failure, like a rolling deploy
+        for (target, label) in targets.iter().zip(labels) {
+            let run = run_on(target.clone(), label, command.clone()).await;
+            let failed = !run.ok();
+            runs.push(run);
+            if failed {
+                break;
+            }
+        }
+    } else {
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(limit));
+        let mut join_set = tokio::task::JoinSet::new();
+        for (i, (target, label)) in targets.iter().zip(labels).enumerate() {
+            let (target, command, semaphore) = (target.clone(), command.clone(), semaphore.clone());
+            join_set.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                (i, run_on(target, label, command).await)
+            });
+        }
+        let mut indexed = Vec::new();
+        while let Some(joined) = join_set.join_next().await {
+            indexed.push(joined?);
+        }
+        indexed.sort_by_key(|(i, _)| *i);
+        runs = indexed.into_iter().map(|(_, run)| run).collect();
+    }
+
+    if json {
+        output::print_json(&runs)?;
+    } else {
+        for run in &runs {
+            match (&run.error, run.exit_code) {
+                (Some(e), _) => o_warn!("   {} {}: {}", "✘".red(), run.domain, e),
+                (None, Some(code)) if code != 0 => o_warn!("   {} {}: exit {}", "✘".red(), run.domain, code),
+                _ => {}
+            }
+        }
+    }
+
+    let failed = runs.iter().filter(|r| !r.ok()).count();
+    let skipped = targets.len() - runs.len();
+    if failed > 0 {
+        bail!("Failed on {} of {} node(s){}", failed, targets.len(),
+            if skipped > 0 { format!(", {} not run", skipped) } else { String::new() });
+    }
+    o_success!("{} on all {} node(s)", "✔ Succeeded".green(), runs.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefixed_lines() {
+        let mut captured = Prefixed::new(None, false);
+        captured.write_all(b"up 3 days\nload").unwrap();
+        captured.write_all(b" 0.1\n").unwrap();
+        assert_eq!(captured.finish().as_deref(), Some("up 3 days\nload 0.1\n"));
+
+        let mut streamed = Prefixed::new(Some("[a]".into()), false);
+        streamed.write_all(b"one\ntw").unwrap();
+        assert_eq!(streamed.buf, b"tw");
+        assert!(streamed.finish().is_none());
+
+        let target = |domain: &str| DeployTarget {
+            node_id: 1, domain: domain.into(), ip_address: String::new(), ipv6_address: None, hostname: None,
+            region: None, zone: None, weight: 1, is_primary: false, status: "active".into(),
+        };
+        assert_eq!(prefixes(&[target("n1.ops.autos"), target("node12.ops.autos")]),
+            vec!["[n1.ops.autos    ]", "[node12.ops.autos]"]);
+    }
+}
@@ -163,14 +163,7 @@ pub async fn handle_show(id: i64) -> Result<()> {
 
 /// List nodes in a specific environment
 pub async fn handle_nodes(target_str: String) -> Result<()> {
-    let target = utils::parse_target(&target_str)?;
-
-    let (app, project) = match &target {
-        utils::Target::AppTarget { app, project, .. } => (app.clone(), project.clone()),
-        utils::Target::NodeId { .. } => {
-            anyhow::bail!("Expected app.project format (e.g., api.RedQ), not a node ID");
-        }
-    };
+    let (app, project) = utils::parse_target(&target_str)?.app_project()?;
 
     let cfg = config::load_config().context("Could not load config. Please log in with `ops login`.")?;
     let token = cfg.token.context("You are not logged in. Please run `ops login` first.")?;
@@ -0,0 +1,370 @@
+//! `ops node migrate <from> <to>`: move every app bound to one node onto another. Per app, the
+//! deploy path and compose volumes are streamed node to node, the app is started on the target
+//! with its route fragments, then the target is bound and the source drained and unbound, which
+//! moves DNS and pool membership. Progress is saved after each step, so a rerun resumes.
+
+use crate::commands::ssh::SshSession;
+use crate::router::Router;
+use crate::types::StateScope;
+use crate::utils::shell_quote;
+use crate::{api, config, prompt};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Image used to read and write volume contents
+const VOLUME_HELPER_IMAGE: &str = "alpine:3";
+
+/// Steps of one app's move, in the order they run
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum Step {
+    /// Copy the deploy path (compose files, env files, build contexts)
+    Sync,
+    /// Stop the app on the source and copy its named volumes
+    Volumes,
+    /// `docker compose up -d` of the app's services on the target
+    Start,
+    /// Copy the app's route fragments and reload the target's router
+    Routes,
+    Bind,
+    /// Take the source out of the app's node group rotation
+    Drain,
+    Unbind,
+    /// Remove the app's containers on the source (volumes are kept) and its routes
+    StopSource,
+}
+
+const STEPS: [Step; 8] = [Step::Sync, Step::Volumes, Step::Start, Step::Routes, Step::Bind, Step::Drain, Step::Unbind, Step::StopSource];
+
+impl Step {
+    fn label(&self) -> &'static str {
+        match self {
+            Step::Sync => "sync deploy path",
+            Step::Volumes => "copy volumes",
+            Step::Start => "start on target",
+            Step::Routes => "copy routes",
+            Step::Bind => "bind target",
+            Step::Drain => "drain source",
+            Step::Unbind => "unbind source",
+            Step::StopSource => "stop on source",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct AppMigration {
+    app_id: i64,
+    app: String,
+    project: String,
+    primary: bool,
+    /// From the app's last deploy to the source; None if it was never recorded
+    scope: Option<StateScope>,
+    #[serde(default)]
+    done: Vec<Step>,
+}
+
+impl AppMigration {
+    fn target(&self) -> String {
+        format!("{}.{}", self.app, self.project)
+    }
+
+    fn pending(&self) -> Vec<Step> {
+        STEPS.iter().copied().filter(|s| !self.done.contains(s)).collect()
+    }
+}
+
+/// Saved after every step
+#[derive(Serialize, Deserialize, Debug)]
+struct Migration {
+    from: u64,
+    to: u64,
+    apps: Vec<AppMigration>,
+}
+
+impl Migration {
+    fn path(&self) -> Result<PathBuf> {
+        config::migration_path(self.from, self.to).context("Could not find config directory")
+    }
+
+    fn load(from: u64, to: u64) -> Result<Option<Self>> {
+        let path = config::migration_path(from, to).context("Could not find config directory")?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Some(serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?))
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = self.path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        config::write_atomic(&path, &serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// `cd <deploy path> && docker compose -p <project> -f ...`
+fn compose(scope: &StateScope) -> String {
+    let files: String = scope.compose_files.iter().map(|f| format!(" -f {}", shell_quote(f))).collect();
+    format!("cd {} && docker compose -p {}{}", shell_quote(&scope.deploy_path), shell_quote(&scope.compose_project), files)
+}
+
+/// The app's compose services, as shell words; empty when the app is the whole compose project.
+/// Other apps can share the project, so nothing acts on all of it unless this app is its only one.
+fn app_services(scope: &StateScope, app: &str) -> Result<String> {
+    match scope.app_services.get(app) {
+        Some(services) if !services.is_empty() => Ok(services.iter().map(|s| format!(" {}", shell_quote(s))).collect()),
+        Some(_) => bail!("App '{}' lists no services in ops.toml, so its containers can't be told apart from the other apps'", app),
+        None if scope.apps.len() <= 1 => Ok(String::new()),
+        None => bail!(
+            "The last deploy of '{}' did not record which services belong to it. Deploy it once more, then rerun the migration",
+            app
+        ),
+    }
+}
+
+/// Named volumes mounted by the app's containers, or by the whole compose project
+fn volumes_command(scope: &StateScope, services: &str) -> String {
+    if services.is_empty() {
+        return format!("docker volume ls -q --filter label=com.docker.compose.project={}", shell_quote(&scope.compose_project));
+    }
+    format!(
+        "{} ps -aq{} | xargs -r docker inspect -f '{{{{range .Mounts}}}}{{{{if eq .Type \"volume\"}}}}{{{{.Name}}}} {{{{end}}}}{{{{end}}}}'",
+        compose(scope), services
+    )
+}
+
+/// `docker volume create` with the source volume's labels, so compose adopts it on the target
+fn volume_create_command(volume: &str, labels: &str) -> String {
+    let labels: String = labels.split_whitespace()
+        .filter(|l| l.contains('='))
+        .map(|l| format!(" --label {}", shell_quote(l)))
+        .collect();
+    format!("docker volume create{} {} >/dev/null", labels, shell_quote(volume))
+}
+
+/// Copy one compose volume's contents from the source to the target
+fn copy_volume(source: &SshSession, target: &SshSession, volume: &str) -> Result<()> {
+    let labels = source.exec_output(&format!(
+        "docker volume inspect -f '{{{{range $k, $v := .Labels}}}}{{{{$k}}}}={{{{$v}}}} {{{{end}}}}' {}", shell_quote(volume)
+    ))?;
+    target.exec(&volume_create_command(volume, &String::from_utf8_lossy(&labels)), None)?;
+    source.pipe_to(
+        &format!("docker run --rm -v {}:/v:ro {} tar czf - -C /v .", shell_quote(volume), VOLUME_HELPER_IMAGE),
+        target,
+        &format!("docker run --rm -i -v {}:/v {} tar xzf - -C /v", shell_quote(volume), VOLUME_HELPER_IMAGE),
+    )
+}
+
+/// Both nodes of a migration
+struct Nodes {
+    from: u64,
+    to: u64,
+    source: SshSession,
+    target: SshSession,
+}
+
+async fn run_step(step: Step, m: &AppMigration, scope: &StateScope, nodes: &Nodes, token: &str) -> Result<()> {
+    let Nodes { from, to, source, target } = nodes;
+    let (from, to) = (*from, *to);
+    let path = shell_quote(&scope.deploy_path);
+    let services = app_services(scope, &m.app)?;
+    match step {
+        Step::Sync => {
+            target.exec(&format!("mkdir -p {}", path), None)?;
+            source.pipe_to(&format!("tar czf - -C {} .", path), target, &format!("tar xzf - -C {}", path))?;
+        }
+        Step::Volumes => {
+            let list = source.exec_output(&volumes_command(scope, &services))?;
+            let mut volumes: Vec<String> = String::from_utf8_lossy(&list).split_whitespace().map(String::from).collect();
+            volumes.sort();
+            volumes.dedup();
+            if volumes.is_empty() {
+                o_detail!("     {}", "no named volumes".dimmed());
+                return Ok(());
+            }
+            // Writes after the copy would be lost
+            o_detail!("     Stopping {} on node #{} to copy {} volume(s)", m.target(), from, volumes.len());
+            source.exec(&format!("{} stop{}", compose(scope), services), None)?;
+            for volume in &volumes {
+                o_detail!("     {} {}", "•".dimmed(), volume);
+                copy_volume(source, target, volume)?;
+            }
+        }
+        Step::Start if services.is_empty() => target.exec(&format!("{} up -d --remove-orphans", compose(scope)), None)?,
+        Step::Start => target.exec(&format!("{} up -d{}", compose(scope), services), None)?,
+        Step::Routes => {
+            let router = Router::from_config(Some(&scope.router))?;
+            let fragments: Vec<_> = router.list_fragments(source)?.into_iter()
+                .filter(|f| f.belongs_to(&m.app, &m.project))
+                .collect();
+            if fragments.is_empty() {
+                return Ok(());
+            }
+            router.ensure_base(target)?;
+            for f in &fragments {
+                let content = source.exec_output(&format!("cat {}", shell_quote(&f.path)))?;
+                router.write(target, &f.path, &String::from_utf8_lossy(&content))?;
+            }
+            router.reload(target)?;
+        }
+        Step::Bind => {
+            api::bind_app_node(token, m.app_id, to, m.primary, None).await?;
+        }
+        Step::Drain => {
+            let targets = api::get_app_deploy_targets(token, &m.project, &m.app).await?;
+            if let Some(group_id) = targets.node_group_id {
+                if targets.targets.iter().any(|t| t.node_id == from as i64) {
+                    api::drain_node(token, group_id, from).await?;
+                }
+            }
+        }
+        Step::Unbind => {
+            api::unbind_app_node(token, m.app_id, from).await?;
+        }
+        Step::StopSource => {
+            if services.is_empty() {
+                source.exec(&format!("{} down --remove-orphans", compose(scope)), None)?;
+            } else {
+                source.exec(&format!("{} rm -sf{}", compose(scope), services), None)?;
+            }
+            Router::from_config(Some(&scope.router))?.remove_app_fragments(source, &m.app, &m.project)?;
+        }
+    }
+    Ok(())
+}
+
+/// ops node migrate
+pub async fn handle_migrate(from: u64, to: u64, decommission: bool, interactive: bool) -> Result<()> {
+    if from == to {
+        bail!("Source and target are the same node");
+    }
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let mut migration = match Migration::load(from, to)? {
+        Some(m) => {
+            o_step!("{}", format!("Resuming migration of node #{} to node #{}", from, to).bold());
+            m
+        }
+        None => {
+            let node = api::get_node(&token, from).await?;
+            let mut apps = Vec::new();
+            for a in node.bound_apps.unwrap_or_default() {
+                let recorded = api::get_deployed_state(&token, &a.project_name, &a.name).await
+                    .with_context(|| format!("Failed to get deploy state of {}.{}", a.name, a.project_name))?;
+                let scope = recorded.nodes.into_iter().find(|n| n.node_id == from as i64).map(|n| n.state.scope);
+                apps.push(AppMigration {
+                    app_id: a.id, app: a.name, project: a.project_name,
+                    primary: a.is_primary.unwrap_or(0) > 0, scope, done: Vec::new(),
+                });
+            }
+            if apps.is_empty() {
+                bail!("No apps are bound to node #{}", from);
+            }
+            api::get_node(&token, to).await.with_context(|| format!("Cannot find target node #{}", to))?;
+            o_step!("{}", format!("Migrating node #{} to node #{}", from, to).bold());
+            Migration { from, to, apps }
+        }
+    };
+
+    for m in &migration.apps {
+        match &m.scope {
+            Some(scope) => o_detail!("   {} {}  {}", "•".dimmed(), m.target().cyan(), scope.deploy_path.dimmed()),
+            None => o_detail!("   {} {}  {}", "•".dimmed(), m.target().yellow(), "no recorded deploy, stays on the source".dimmed()),
+        }
+    }
+    if !prompt::confirm_yes("Start the migration? Apps with volumes are stopped on the source while they are copied.", interactive)? {
+        o_warn!("Operation cancelled.");
+        return Ok(());
+    }
+    migration.save()?;
+
+    let nodes = Nodes {
+        from,
+        to,
+        source: SshSession::connect(&from.to_string()).await?,
+        target: SshSession::connect(&to.to_string()).await?,
+    };
+    for i in 0..migration.apps.len() {
+        let m = migration.apps[i].clone();
+        let Some(scope) = &m.scope else { continue };
+        o_step!("\n{}", m.target().cyan().bold());
+        for step in m.pending() {
+            o_detail!("   {} {}", "→".dimmed(), step.label());
+            run_step(step, &m, scope, &nodes, &token).await.with_context(|| format!(
+                "{} of {} failed. Fix the cause and rerun `ops node migrate {} {}` to resume", step.label(), m.target(), from, to
+            ))?;
+            migration.apps[i].done.push(step);
+            migration.save()?;
+        }
+        o_success!("   {} {} now runs on node #{}", "✔".green(), m.target(), to);
+    }
+
+    let skipped: Vec<String> = migration.apps.iter().filter(|m| m.scope.is_none()).map(|m| m.target()).collect();
+    let path = migration.path()?;
+    let _ = std::fs::remove_file(&path);
+    o_success!("\n{}", format!("✔ Migrated {} app(s) from node #{} to node #{}", migration.apps.len() - skipped.len(), from, to).green());
+    if !skipped.is_empty() {
+        o_warn!("{} still bound to node #{}: deploy them to node #{} with `ops set <app.project> --node {}` and `ops deploy`.",
+            skipped.join(", "), from, to, to);
+        if decommission {
+            o_warn!("Not decommissioning node #{} while apps remain on it.", from);
+        }
+        return Ok(());
+    }
+
+    if decommission {
+        o_warn!("{}", format!("Node #{} will be deleted, with its DNS record. Its volumes stay on the machine until it is destroyed.", from).yellow());
+        if !prompt::confirm_no("Delete the source node?", interactive)? {
+            o_detail!("Kept node #{}. Delete it later with `ops node remove {}`.", from, from);
+            return Ok(());
+        }
+        let res = api::delete_node(&token, from).await?;
+        o_success!("{}", format!("✔ {}", res.message).green());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_and_resume() {
+        let scope = StateScope {
+            project: "RedQ".into(),
+            deploy_path: "/opt/redq".into(),
+            compose_project: "redq".into(),
+            compose_files: vec!["docker-compose.yml".into(), "docker-compose.prod.yml".into()],
+            env_files: vec![".env".into()],
+            apps: vec!["api".into()],
+            services: vec![],
+            router: "caddy".into(),
+            app_services: Default::default(),
+        };
+        assert_eq!(compose(&scope), "cd /opt/redq && docker compose -p redq -f docker-compose.yml -f docker-compose.prod.yml");
+        // A single app is the whole project; several apps each need their services recorded
+        assert_eq!(app_services(&scope, "api").unwrap(), "");
+        assert!(volumes_command(&scope, "").starts_with("docker volume ls -q --filter label=com.docker.compose.project=redq"));
+        let mut shared = scope.clone();
+        shared.apps.push("web".into());
+        assert!(app_services(&shared, "api").is_err());
+        shared.app_services.insert("api".into(), vec!["api".into(), "worker".into()]);
+        assert_eq!(app_services(&shared, "api").unwrap(), " api worker");
+        assert!(volumes_command(&shared, " api worker").starts_with(&format!("{} ps -aq api worker | xargs -r docker inspect", compose(&shared))));
+        assert_eq!(
+            volume_create_command("redq_db", "com.docker.compose.project=redq com.docker.compose.volume=db \n"),
+            "docker volume create --label com.docker.compose.project=redq --label com.docker.compose.volume=db redq_db >/dev/null"
+        );
+
+        let m: AppMigration = serde_json::from_str(
+            r#"{"app_id": 7, "app": "api", "project": "RedQ", "primary": true, "scope": null, "done": ["sync", "volumes", "stop-source"]}"#
+        ).unwrap();
+        assert_eq!(m.pending(), vec![Step::Start, Step::Routes, Step::Bind, Step::Drain, Step::Unbind]);
+        assert_eq!(m.target(), "api.RedQ");
+    }
+}
@@ -1,25 +1,44 @@
 // src/commands/ping.rs
 
-use crate::utils;
+use crate::commands::common::resolve_target;
+use crate::commands::ip::{fetch_registered, resolve_dns};
+use crate::commands::ssh::{fetch_ci_key, SshSession};
+use crate::node_state::serve_request;
+use crate::utils::Target;
 use anyhow::{Context, Result};
 use colored::Colorize;
+use std::net::{IpAddr, SocketAddr, TcpStream};
 use std::process::Command;
+use std::time::Duration;
+
+const TCP_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Ping a target
 /// Supports both Node ID (e.g., "12345") and App target (e.g., "api.RedQ")
-pub async fn handle_ping(target_str: String) -> Result<()> {
-    let target = utils::parse_target(&target_str)?;
+pub async fn handle_ping(target_str: Option<String>, ipv4: bool, ipv6: bool, deep: bool, interactive: bool) -> Result<()> {
+    let target = resolve_target(target_str, interactive).await?;
+    if deep {
+        return deep_check(&target).await;
+    }
     let full_domain = target.domain();
 
     o_step!("Pinging {}...", full_domain.cyan());
 
     // 在不同操作系统上，ping 命令的参数可能略有不同
-    // 但通常直接 ping 域名是通用的
+    // Linux (iputils) 用 -4/-6；macOS 的 ping 只支持 IPv4，IPv6 需要 ping6
+    let (program, family) = match (ipv4, ipv6) {
+        (_, true) if cfg!(target_os = "macos") => ("ping6", None),
+        (_, true) => ("ping", Some("-6")),
+        (true, _) if !cfg!(target_os = "macos") => ("ping", Some("-4")),
+        _ => ("ping", None),
+    };
+
     // 我们使用 spawn 而不是 status，这样用户可以看到实时的 ping 输出
-    let mut child = Command::new("ping")
+    let mut child = Command::new(program)
+        .args(family)
         .arg(&full_domain)
         .spawn()
-        .context("Failed to execute 'ping' command. Is it installed and in your PATH?")?;
+        .with_context(|| format!("Failed to execute '{}' command. Is it installed and in your PATH?", program))?;
 
     let status = child.wait()?;
 
@@ -28,4 +47,87 @@ pub async fn handle_ping(target_str: String) -> Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// The address DNS hands out, if it is one the backend has registered for the target
+fn dns_verdict(resolved: &[IpAddr], expected: &[IpAddr]) -> Result<IpAddr, String> {
+    let list = |addrs: &[IpAddr]| addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ");
+    if resolved.is_empty() {
+        return Err("no A/AAAA records".into());
+    }
+    if expected.is_empty() {
+        return Err(format!("resolves to {}, but the backend has no address registered", list(resolved)));
+    }
+    resolved.iter().find(|a| expected.contains(a)).copied()
+        .ok_or_else(|| format!("resolves to {}, expected {}", list(resolved), list(expected)))
+}
+
+fn hop_ok(n: usize, name: &str, detail: &str) {
+    o_success!("   {} {}. {:<14} {}", "✔".green(), n, name, detail.dimmed());
+}
+
+fn hop_failed(n: usize, name: &str, err: impl std::fmt::Display) -> anyhow::Error {
+    o_error!("   {} {}. {:<14} {}", "✘".red(), n, name, err);
+    anyhow::anyhow!("Failed at hop {} ({})", n, name)
+}
+
+/// ops ping --deep: walk the path a deploy takes and stop at the first hop that fails
+async fn deep_check(target: &Target) -> Result<()> {
+    let domain = target.domain();
+    o_step!("Checking the path to {}...", domain.cyan());
+
+    // 1. DNS points at the node the backend knows about
+    let resolved = resolve_dns(&domain).map_err(|e| hop_failed(1, "DNS", format!("{:#}", e)))?;
+    let expected = fetch_registered(target).await.map_err(|e| hop_failed(1, "DNS", format!("backend lookup failed: {:#}", e)))?;
+    let ip = dns_verdict(&resolved, &expected).map_err(|e| hop_failed(1, "DNS", e))?;
+    hop_ok(1, "DNS", &format!("{} → {}", domain, ip));
+
+    // 2. sshd answers on port 22
+    let addr = SocketAddr::new(ip, 22);
+    tokio::task::spawn_blocking(move || TcpStream::connect_timeout(&addr, TCP_TIMEOUT)).await?
+        .map_err(|e| hop_failed(2, "TCP 22", format!("{}: {}", addr, e)))?;
+    hop_ok(2, "TCP 22", &addr.to_string());
+
+    // 3. The backend hands out the target's CI key
+    let key = fetch_ci_key(target).await.map_err(|e| hop_failed(3, "CI key", format!("{:#}", e)))?;
+    hop_ok(3, "CI key", "fetched from the backend");
+
+    // 4. The CI key is accepted
+    let session = SshSession::with_key(&target.to_string(), &key).map_err(|e| hop_failed(4, "SSH auth", format!("{:#}", e)))?;
+    session.exec_output("true").map_err(|e| hop_failed(4, "SSH auth", format!("{:#}", e)))?;
+    hop_ok(4, "SSH auth", "CI key accepted");
+
+    // 5. ops serve is up, on the port from the node's serve.toml
+    let health = session.exec_output(&serve_request("-m 5", "/health"))
+        .map_err(|e| hop_failed(5, "ops serve", format!("no answer on /health: {:#}", e)))?;
+    let health: serde_json::Value = serde_json::from_slice(&health)
+        .map_err(|e| hop_failed(5, "ops serve", format!("unexpected /health response: {}", e)))?;
+    let status = health.get("status").and_then(|s| s.as_str()).unwrap_or("unknown");
+    let detail = match health.get("version").and_then(|v| v.as_str()) {
+        Some(v) => format!("{} (v{})", status, v),
+        None => status.to_string(),
+    };
+    hop_ok(5, "ops serve", &detail);
+
+    // 6. The docker daemon responds
+    let out = session.exec_output("timeout 15 docker info --format '{{.ServerVersion}}'")
+        .map_err(|e| hop_failed(6, "docker", format!("{:#}", e)))?;
+    hop_ok(6, "docker", &format!("Docker {}", String::from_utf8_lossy(&out).trim()));
+
+    o_result!("\n{} {} is reachable end to end", "✅".green(), target.to_string().cyan());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dns_verdict() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        assert_eq!(dns_verdict(&[ip("203.0.113.1")], &[ip("203.0.113.1"), ip("2001:db8::1")]), Ok(ip("203.0.113.1")));
+        assert_eq!(dns_verdict(&[ip("198.51.100.7")], &[ip("203.0.113.1")]), Err("resolves to 198.51.100.7, expected 203.0.113.1".into()));
+        assert!(dns_verdict(&[], &[ip("203.0.113.1")]).is_err());
+        assert!(dns_verdict(&[ip("203.0.113.1")], &[]).unwrap_err().contains("no address registered"));
+    }
+}
@@ -0,0 +1,125 @@
+//! External subcommands: `ops foo` runs `ops-foo` from PATH when `foo` isn't built in, so teams
+//! can add commands (`ops-billing`, `ops-compliance`) without forking. The plugin gets the
+//! context it needs through the environment.
+
+use crate::{api, config};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::env;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const PLUGIN_PREFIX: &str = "ops-";
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+/// Every `ops-<name>` executable on `path_var`, first match per name as the shell would pick
+fn plugins_in(path_var: &str) -> BTreeMap<String, PathBuf> {
+    let mut plugins = BTreeMap::new();
+    for dir in env::split_paths(path_var) {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(name) = file_name.strip_prefix(PLUGIN_PREFIX) else { continue };
+            if !name.is_empty() && !plugins.contains_key(name) && is_executable(&entry.path()) {
+                plugins.insert(name.to_string(), entry.path());
+            }
+        }
+    }
+    plugins
+}
+
+fn find_plugin(name: &str, path_var: &str) -> Option<PathBuf> {
+    env::split_paths(path_var)
+        .map(|dir| dir.join(format!("{}{}", PLUGIN_PREFIX, name)))
+        .find(|p| is_executable(p))
+}
+
+/// Environment handed to a plugin
+fn plugin_env(json: bool, yes: bool) -> Vec<(&'static str, String)> {
+    let mut vars = vec![("OPS_API_URL", api::BASE_URL.to_string())];
+    if let Ok(path) = config::get_config_path() {
+        vars.push(("OPS_TOKEN_FILE", path.display().to_string()));
+    }
+    if let Ok(exe) = env::current_exe() {
+        vars.push(("OPS_CLI", exe.display().to_string()));
+    }
+    if let Ok(toml) = std::fs::canonicalize("ops.toml") {
+        vars.push(("OPS_TOML", toml.display().to_string()));
+    }
+    if json {
+        vars.push(("OPS_JSON", "1".into()));
+    }
+    if yes {
+        vars.push(("OPS_YES", "1".into()));
+    }
+    vars
+}
+
+/// `ops <name> [args]` for a name clap doesn't know: run `ops-<name>` and exit with its code
+pub fn handle_external(args: &[String], json: bool, yes: bool) -> Result<()> {
+    let (name, rest) = args.split_first().context("No subcommand given")?;
+    let path_var = env::var("PATH").unwrap_or_default();
+    let plugin = (!name.contains('/')).then(|| find_plugin(name, &path_var)).flatten();
+    let Some(plugin) = plugin else {
+        bail!("Unknown command '{}'. Run `ops --help` for built-in commands; plugins are `{}{}` executables on PATH", name, PLUGIN_PREFIX, name);
+    };
+
+    o_debug!("Running plugin {}", plugin.display());
+    let status = Command::new(&plugin)
+        .args(rest)
+        .envs(plugin_env(json, yes))
+        .status()
+        .with_context(|| format!("Failed to run {}", plugin.display()))?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+/// ops plugins list
+pub fn handle_list() -> Result<()> {
+    let plugins = plugins_in(&env::var("PATH").unwrap_or_default());
+    if plugins.is_empty() {
+        o_result!("No plugins found. Put an `{}<name>` executable on PATH to add `ops <name>`.", PLUGIN_PREFIX);
+        return Ok(());
+    }
+    o_step!("{}", "Plugins:".bold());
+    for (name, path) in &plugins {
+        o_result!("  {:<20} {}", name.cyan(), path.display().to_string().dimmed());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_plugin_lookup() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let make = |dir: &Path, name: &str, mode: u32| {
+            let path = dir.join(name);
+            fs::write(&path, "#!/bin/sh\n").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        };
+        make(first.path(), "ops-billing", 0o755);
+        make(first.path(), "ops-notes", 0o644);
+        make(second.path(), "ops-billing", 0o755);
+        make(second.path(), "ops-compliance", 0o755);
+        make(second.path(), "ops-", 0o755);
+
+        let path_var = env::join_paths([first.path(), second.path()]).unwrap().to_string_lossy().to_string();
+        let plugins = plugins_in(&path_var);
+        assert_eq!(plugins.keys().collect::<Vec<_>>(), vec!["billing", "compliance"]);
+        assert_eq!(plugins["billing"], first.path().join("ops-billing"));
+        assert_eq!(find_plugin("compliance", &path_var), Some(second.path().join("ops-compliance")));
+        assert_eq!(find_plugin("notes", &path_var), None);
+    }
+}
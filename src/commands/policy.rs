@@ -0,0 +1,233 @@
+use crate::commands::deploy::load_ops_toml;
+use crate::commands::drift::DEFAULT_COMPOSE_FILES;
+use crate::types::{OpsToml, PolicyConfig};
+use crate::{api, config};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+/// A broken `[policy]` rule
+#[derive(Debug, PartialEq)]
+pub struct Violation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.rule, self.message)
+    }
+}
+
+/// Branch a deploy comes from: deploy.branch for source = "git" (pulled on the node), else the local checkout
+fn deploy_branch(config: &OpsToml, dir: &Path) -> Option<String> {
+    if config.deploy.source == "git" {
+        return Some(config.deploy.branch.clone().unwrap_or_else(|| "main".into()));
+    }
+    local_branch(dir)
+}
+
+/// Branch checked out in `dir`; None outside a repo or on a detached HEAD
+pub fn local_branch(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // "HEAD" means a detached checkout
+    (output.status.success() && branch != "HEAD").then_some(branch)
+}
+
+/// `${VAR}`, `${VAR:-default}` and `${VAR-default}` from --set values, then the environment
+fn interpolate(value: &str, vars: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else { break };
+        let expr = &rest[start + 2..start + end];
+        let (name, default) = match expr.split_once(":-").or_else(|| expr.split_once('-')) {
+            Some((n, d)) => (n, d),
+            None => (expr, ""),
+        };
+        let resolved = vars.get(name).cloned()
+            .or_else(|| std::env::var(name).ok())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| default.to_string());
+        out.push_str(&resolved);
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `image:` values of the local compose files, as (file, image)
+fn compose_images(config: &OpsToml, dir: &Path, env_vars: &[String]) -> Vec<(String, String)> {
+    let vars: HashMap<String, String> = env_vars.iter()
+        .filter_map(|kv| kv.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    // Without compose_files, docker compose uses the first default file that exists
+    let files: Vec<String> = match &config.deploy.compose_files {
+        Some(files) => files.clone(),
+        None => DEFAULT_COMPOSE_FILES.iter()
+            .find(|f| dir.join(f).exists())
+            .map(|f| vec![f.to_string()])
+            .unwrap_or_default(),
+    };
+
+    let mut images = Vec::new();
+    for file in files {
+        let Ok(content) = std::fs::read_to_string(dir.join(&file)) else { continue };
+        for line in content.lines() {
+            let Some(value) = line.trim().strip_prefix("image:") else { continue };
+            let value = value.split(" #").next().unwrap_or_default().trim().trim_matches(|c| c == '"' || c == '\'');
+            if !value.is_empty() {
+                images.push((file.clone(), interpolate(value, &vars)));
+            }
+        }
+    }
+    images
+}
+
+/// Tag of an image reference; None when untagged. Digest-pinned images count as tagged.
+fn image_tag(image: &str) -> Option<&str> {
+    if let Some((_, digest)) = image.split_once('@') {
+        return Some(digest);
+    }
+    let last = image.rsplit('/').next().unwrap_or(image);
+    last.split_once(':').map(|(_, tag)| tag)
+}
+
+fn evaluate(policy: &PolicyConfig, branch: Option<&str>, images: &[(String, String)]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if !policy.branches.is_empty() {
+        match branch {
+            Some(b) if policy.branches.iter().any(|allowed| allowed == b) => {}
+            Some(b) => violations.push(Violation {
+                rule: "branches",
+                message: format!("deploying from branch '{}'; allowed: {}", b, policy.branches.join(", ")),
+            }),
+            None => violations.push(Violation {
+                rule: "branches",
+                message: "cannot tell which branch this deploy comes from (not a git checkout, or detached HEAD)".into(),
+            }),
+        }
+    }
+
+    for (file, image) in images {
+        if !policy.image_prefixes.is_empty() && !policy.image_prefixes.iter().any(|p| image.starts_with(p.as_str())) {
+            violations.push(Violation {
+                rule: "image_prefixes",
+                message: format!("{} ({}) is not from {}", image, file, policy.image_prefixes.join(", ")),
+            });
+        }
+        if policy.no_latest && image_tag(image).is_none_or(|t| t.is_empty() || t == "latest") {
+            violations.push(Violation {
+                rule: "no_latest",
+                message: format!("{} ({}) is not pinned to a tag", image, file),
+            });
+        }
+    }
+
+    violations
+}
+
+/// Evaluate `[policy]` for the ops.toml at `file`; empty when there is no policy
+pub fn check(config: &OpsToml, file: &str, env_vars: &[String]) -> Vec<Violation> {
+    let Some(policy) = &config.policy else { return Vec::new() };
+    let dir = Path::new(file).parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    evaluate(policy, deploy_branch(config, dir).as_deref(), &compose_images(config, dir, env_vars))
+}
+
+/// Pre-deploy gate: blocks on violations unless `override_reason` is given.
+/// Returns the overridden violations, which the caller must record on the deployment.
+pub fn enforce(config: &OpsToml, file: &str, env_vars: &[String], override_reason: Option<&str>) -> Result<Vec<Violation>> {
+    if config.policy.is_none() {
+        return Ok(Vec::new());
+    }
+    let violations = check(config, file, env_vars);
+    if violations.is_empty() {
+        o_success!("   {}", "✔ Policy passed".green());
+        return Ok(violations);
+    }
+
+    for v in &violations {
+        o_error!("   {} {}", "✘".red(), v);
+    }
+    match override_reason {
+        Some(reason) => {
+            o_warn!("   {} {} violation(s) overridden: {}", "⚠".yellow(), violations.len(), reason);
+            Ok(violations)
+        }
+        None => bail!(
+            "Deploy blocked by [policy] ({} violation(s)). Fix them, or pass --override-policy \"<reason>\" to deploy anyway (the override is logged on the deployment)",
+            violations.len()
+        ),
+    }
+}
+
+/// Audit trail for `--override-policy`
+pub async fn record_override(deployment_id: i64, reason: &str, violations: &[Violation]) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+    api::record_policy_override(&token, deployment_id, reason, &violations).await?;
+    o_detail!("   Policy override logged on deployment #{}", deployment_id);
+    Ok(())
+}
+
+/// ops policy: evaluate `[policy]` without deploying (exits non-zero on violations, for CI)
+pub fn handle_policy(file: String, env_vars: Vec<String>) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    if config.policy.is_none() {
+        o_result!("No [policy] in {}", file);
+        return Ok(());
+    }
+    let violations = check(&config, &file, &env_vars);
+    if violations.is_empty() {
+        o_result!("{} {} passes its [policy]", "✔".green(), file);
+        return Ok(());
+    }
+    for v in &violations {
+        o_result!("  {} {}", "✘".red(), v);
+    }
+    bail!("{} policy violation(s)", violations.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let policy = PolicyConfig {
+            branches: vec!["main".into()],
+            image_prefixes: vec!["ghcr.io/ourorg/".into()],
+            no_latest: true,
+        };
+        let images = vec![
+            ("compose.yml".to_string(), "ghcr.io/ourorg/api:v1.2.0".to_string()),
+            ("compose.yml".to_string(), "ghcr.io/ourorg/web@sha256:abc".to_string()),
+            ("compose.yml".to_string(), "redis".to_string()),
+            ("compose.yml".to_string(), "localhost:5000/ourorg/worker:latest".to_string()),
+        ];
+        assert!(evaluate(&policy, Some("main"), &images[..2]).is_empty());
+
+        let rules: Vec<&str> = evaluate(&policy, Some("feature/x"), &images).iter().map(|v| v.rule).collect();
+        assert_eq!(rules, vec!["branches", "image_prefixes", "no_latest", "image_prefixes", "no_latest"]);
+        assert_eq!(evaluate(&policy, None, &[])[0].rule, "branches");
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let vars = HashMap::from([("TAG".to_string(), "v2".to_string())]);
+        assert_eq!(interpolate("ghcr.io/o/api:${TAG}", &vars), "ghcr.io/o/api:v2");
+        assert_eq!(interpolate("ghcr.io/o/api:${OPS_TEST_UNSET:-v1}", &vars), "ghcr.io/o/api:v1");
+        assert_eq!(interpolate("ghcr.io/o/api:${OPS_TEST_UNSET}", &vars), "ghcr.io/o/api:");
+    }
+}
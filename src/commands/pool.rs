@@ -1,30 +1,29 @@
 use anyhow::{anyhow, Context, Result};
 use colored::Colorize;
-use crate::{api, config};
+use crate::{api, config, output, prompt, utils};
+use crate::table::{Column, Table, TableOptions};
+use crate::types::{HealthCheckConfig, DEFAULT_STICKY_COOKIE};
 
-/// Parse target in "app.project" format
-fn parse_target(target: &str) -> Result<(String, String)> {
-    let parts: Vec<&str> = target.splitn(2, '.').collect();
-    if parts.len() != 2 {
-        return Err(anyhow!("Target must be in 'app.project' format (e.g., api.RedQ)"));
-    }
-    Ok((parts[1].to_string(), parts[0].to_string()))
-}
-
-pub async fn handle_status(target: String) -> Result<()> {
+pub async fn handle_status(target: String, opts: &TableOptions) -> Result<()> {
     let cfg = config::load_config().context("Config error")?;
     let token = cfg.token.context("Please run `ops login` first.")?;
 
-    let (project, app) = parse_target(&target)?;
-
-    o_step!("{} Pool status for {}\n", "🏊".cyan(), target.green());
+    let (app, project) = utils::parse_target(&target)?.app_project()?;
 
     let resp = api::get_app_deploy_targets(&token, &project, &app).await?;
+    if output::json() {
+        return output::print_json(&resp);
+    }
+
+    o_step!("{} Pool status for {}\n", "🏊".cyan(), target.green());
 
     o_detail!("  Mode:     {}", resp.mode.cyan());
     if let Some(ref strategy) = resp.lb_strategy {
         o_detail!("  Strategy: {}", strategy.cyan());
     }
+    if let Some(ref sticky) = resp.lb_sticky {
+        o_detail!("  Sticky:   {} ({})", sticky.cyan(), resp.lb_sticky_key.as_deref().unwrap_or("-"));
+    }
     if let Some(gid) = resp.node_group_id {
         o_detail!("  Group ID: {}", gid.to_string().cyan());
     }
@@ -35,11 +34,26 @@ pub async fn handle_status(target: String) -> Result<()> {
         return Ok(());
     }
 
-    // Table header
-    o_detail!("  {:<8} {:<28} {:<16} {:<14} {:<10} {:<8}",
-        "ID", "Domain", "IP", "Region", "Status", "Primary");
-    o_detail!("  {}", "-".repeat(84));
+    // Effective share only applies to the weighted strategy; drained/unhealthy nodes get no traffic
+    let weighted = resp.lb_strategy.as_deref() == Some("weighted");
+    let active_weight: i64 = resp.targets.iter()
+        .filter(|t| t.status == "healthy")
+        .map(|t| t.weight)
+        .sum();
 
+    let mut table = Table::new(vec![
+        Column::new("id").right(),
+        Column::new("domain"),
+        Column::new("ip"),
+        Column::new("region"),
+        Column::new("status"),
+        Column::new("weight").right(),
+        Column::new("share").right(),
+        Column::new("primary"),
+        Column::wide("hostname"),
+        Column::wide("zone"),
+        Column::wide("ipv6"),
+    ]);
     for t in &resp.targets {
         let status_colored = match t.status.as_str() {
             "healthy" => t.status.green(),
@@ -47,11 +61,29 @@ pub async fn handle_status(target: String) -> Result<()> {
             _ => t.status.red(),
         };
         let primary = if t.is_primary { "yes".green() } else { "-".normal() };
-        let region = t.region.as_deref().unwrap_or("-");
+        let share = if !weighted {
+            "-".to_string()
+        } else if t.status == "healthy" && active_weight > 0 {
+            format!("{:.0}%", t.weight as f64 * 100.0 / active_weight as f64)
+        } else {
+            "0%".to_string()
+        };
 
-        o_detail!("  {:<8} {:<28} {:<16} {:<14} {:<10} {:<8}",
-            t.node_id, t.domain, t.ip_address, region, status_colored, primary);
+        table.row(vec![
+            t.node_id.to_string(),
+            t.domain.clone(),
+            t.ip_address.clone(),
+            t.region.clone().unwrap_or_else(|| "-".into()),
+            status_colored.to_string(),
+            t.weight.to_string(),
+            share,
+            primary.to_string(),
+            t.hostname.clone().unwrap_or_else(|| "-".into()),
+            t.zone.clone().unwrap_or_else(|| "-".into()),
+            t.ipv6_address.clone().unwrap_or_else(|| "-".into()),
+        ]);
     }
+    table.print(opts)?;
 
     let healthy = resp.targets.iter().filter(|t| t.status == "healthy").count();
     let total = resp.targets.len();
@@ -69,7 +101,7 @@ pub async fn handle_strategy(target: String, strategy: String) -> Result<()> {
         return Err(anyhow!("Invalid strategy '{}'. Must be one of: {}", strategy, valid.join(", ")));
     }
 
-    let (project, app) = parse_target(&target)?;
+    let (app, project) = utils::parse_target(&target)?.app_project()?;
 
     // Get deploy targets to find the node group ID
     let resp = api::get_app_deploy_targets(&token, &project, &app).await?;
@@ -84,11 +116,41 @@ pub async fn handle_strategy(target: String, strategy: String) -> Result<()> {
     Ok(())
 }
 
+pub async fn handle_sticky(target: String, mode: String, name: Option<String>) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let (sticky, key) = match mode.as_str() {
+        "cookie" => (Some("cookie"), Some(name.unwrap_or_else(|| DEFAULT_STICKY_COOKIE.to_string()))),
+        "header" => (Some("header"), Some(name.context("Header affinity needs --name (e.g. --name X-Session-Id)")?)),
+        "off" => (None, None),
+        other => return Err(anyhow!("Invalid sticky mode '{}'. Must be one of: cookie, header, off", other)),
+    };
+
+    let (app, project) = utils::parse_target(&target)?.app_project()?;
+
+    // Get deploy targets to find the node group ID
+    let resp = api::get_app_deploy_targets(&token, &project, &app).await?;
+    let group_id = resp.node_group_id
+        .context("App is in single-node mode. Bind a second node to enable pool mode.")?;
+
+    o_step!("{} Updating session affinity for {}...", "📌".cyan(), target.green());
+
+    api::update_node_group_sticky(&token, group_id, sticky, key.as_deref()).await?;
+
+    match (sticky, key) {
+        (Some(mode), Some(key)) => o_success!("{} Sticky sessions on: {} {}", "✔".green(), mode.green(), key.green()),
+        _ => o_success!("{} Sticky sessions off", "✔".green()),
+    }
+    o_detail!("  Set lb.sticky on the app in ops.toml to also pin replicas on each node (applied on the next deploy).");
+    Ok(())
+}
+
 pub async fn handle_drain(target: String, node_id: u64) -> Result<()> {
     let cfg = config::load_config().context("Config error")?;
     let token = cfg.token.context("Please run `ops login` first.")?;
 
-    let (project, app) = parse_target(&target)?;
+    let (app, project) = utils::parse_target(&target)?.app_project()?;
 
     // Get deploy targets to find the node group ID
     let resp = api::get_app_deploy_targets(&token, &project, &app).await?;
@@ -107,7 +169,7 @@ pub async fn handle_undrain(target: String, node_id: u64) -> Result<()> {
     let cfg = config::load_config().context("Config error")?;
     let token = cfg.token.context("Please run `ops login` first.")?;
 
-    let (project, app) = parse_target(&target)?;
+    let (app, project) = utils::parse_target(&target)?.app_project()?;
 
     // Get deploy targets to find the node group ID
     let resp = api::get_app_deploy_targets(&token, &project, &app).await?;
@@ -121,3 +183,248 @@ pub async fn handle_undrain(target: String, node_id: u64) -> Result<()> {
     o_success!("{} Node {} is back in rotation", "✔".green(), node_id);
     Ok(())
 }
+
+pub async fn handle_weight(target: String, node_id: u64, weight: u8) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    if !(1..=100).contains(&weight) {
+        return Err(anyhow!("Invalid weight {}. Must be between 1 and 100", weight));
+    }
+
+    let (app, project) = utils::parse_target(&target)?.app_project()?;
+
+    // Get deploy targets to find the node group ID
+    let resp = api::get_app_deploy_targets(&token, &project, &app).await?;
+    let group_id = resp.node_group_id
+        .context("App is in single-node mode. Bind a second node to enable pool mode.")?;
+
+    if !resp.targets.iter().any(|t| t.node_id == node_id as i64) {
+        return Err(anyhow!("Node {} is not bound to {}", node_id, target));
+    }
+
+    o_step!("{} Setting weight of node {} in {} to {}...",
+        "⚖️".cyan(), node_id.to_string().yellow(), target.green(), weight.to_string().yellow());
+
+    api::set_node_weight(&token, group_id, node_id, weight).await?;
+
+    o_success!("{} Node {} weight set to {}", "✔".green(), node_id, weight.to_string().green());
+    if resp.lb_strategy.as_deref() != Some("weighted") {
+        o_warn!("  {} Weights only take effect with the weighted strategy. Run `ops pool strategy {} weighted`.",
+            "⚠".yellow(), target);
+    }
+    Ok(())
+}
+
+/// Probe a health check endpoint against one live node before saving it.
+/// HTTP checks go through the node's Caddy using the X-OPS-Target header, TCP checks connect directly.
+async fn probe_endpoint(target: &str, ip: &str, health: &HealthCheckConfig) -> Result<()> {
+    let timeout = std::time::Duration::from_secs(health.timeout_seconds as u64);
+    let endpoint = &health.endpoint;
+    match health.check_type.as_str() {
+        "http" => {
+            let client = reqwest::Client::builder().timeout(timeout).build()?;
+            let url = format!("http://{}{}", ip, endpoint);
+            let res = client.get(&url)
+                .header("X-OPS-Target", target)
+                .send()
+                .await
+                .with_context(|| format!("Request to {} failed", url))?;
+            if !res.status().is_success() {
+                return Err(anyhow!("{} returned HTTP {}", url, res.status()));
+            }
+            Ok(())
+        }
+        "tcp" => {
+            let addr = format!("{}:{}", ip, endpoint);
+            tokio::time::timeout(timeout, tokio::net::TcpStream::connect(&addr))
+                .await
+                .map_err(|_| anyhow!("Connection to {} timed out", addr))?
+                .with_context(|| format!("Connection to {} failed", addr))?;
+            Ok(())
+        }
+        other => Err(anyhow!("Unsupported check type '{}'", other)),
+    }
+}
+
+pub async fn handle_healthcheck(target: String, health: HealthCheckConfig, no_verify: bool) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    match health.check_type.as_str() {
+        "http" => {
+            if !health.endpoint.starts_with('/') {
+                return Err(anyhow!("HTTP endpoint must be a path starting with '/' (e.g., /healthz)"));
+            }
+        }
+        "tcp" => {
+            health.endpoint.parse::<u16>()
+                .map_err(|_| anyhow!("TCP endpoint must be a port number (e.g., 5432)"))?;
+        }
+        other => return Err(anyhow!("Invalid check type '{}'. Must be one of: http, tcp", other)),
+    }
+    if health.interval_seconds <= 0 || health.timeout_seconds <= 0
+        || health.unhealthy_threshold <= 0 || health.healthy_threshold <= 0
+    {
+        return Err(anyhow!("Interval, timeout and thresholds must be greater than 0"));
+    }
+    if health.timeout_seconds >= health.interval_seconds {
+        return Err(anyhow!("Timeout ({}s) must be shorter than the interval ({}s)",
+            health.timeout_seconds, health.interval_seconds));
+    }
+
+    let (app, project) = utils::parse_target(&target)?.app_project()?;
+
+    let resp = api::get_app_deploy_targets(&token, &project, &app).await?;
+    let group_id = resp.node_group_id
+        .context("App is in single-node mode. Bind a second node to enable pool mode.")?;
+
+    if !no_verify {
+        let probe = resp.targets.iter()
+            .find(|t| t.status == "healthy")
+            .or_else(|| resp.targets.first())
+            .context("No nodes bound to this app to verify the endpoint against.")?;
+
+        o_step!("{} Verifying {} {} on node {}...",
+            "🔍".cyan(), health.check_type, health.endpoint.yellow(), probe.node_id.to_string().yellow());
+        probe_endpoint(&target, &probe.ip_address, &health).await
+            .context("Endpoint verification failed (use --no-verify to save anyway)")?;
+        o_success!("   {} Endpoint responded", "✔".green());
+    }
+
+    o_step!("{} Updating health check for {}...", "💚".cyan(), target.green());
+
+    api::update_health_check(&token, group_id, &health).await?;
+
+    o_success!("{} Health check configured", "✔".green());
+    o_detail!("  Type:       {}", health.check_type);
+    o_detail!("  Endpoint:   {}", health.endpoint);
+    o_detail!("  Interval:   {}s", health.interval_seconds);
+    o_detail!("  Timeout:    {}s", health.timeout_seconds);
+    o_detail!("  Thresholds: {} unhealthy / {} healthy", health.unhealthy_threshold, health.healthy_threshold);
+    Ok(())
+}
+
+/// Probe the app's public domain for `duration` seconds, returning (ok, total).
+/// Any non-5xx response counts as reachable.
+async fn probe_public_domain(url: &str, duration: u64, interval: u64) -> Result<(u32, u32)> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()?;
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(duration);
+    let mut ok = 0;
+    let mut total = 0;
+
+    while std::time::Instant::now() < deadline {
+        total += 1;
+        match client.get(url).send().await {
+            Ok(res) if !res.status().is_server_error() => {
+                ok += 1;
+                o_debug!("   probe {}: HTTP {}", total, res.status());
+            }
+            Ok(res) => o_warn!("   {} probe {}: HTTP {}", "✘".red(), total, res.status()),
+            Err(e) => o_warn!("   {} probe {}: {}", "✘".red(), total, e),
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+    }
+
+    Ok((ok, total))
+}
+
+pub async fn handle_failover_test(
+    target: String,
+    node_id: u64,
+    duration: u64,
+    path: String,
+    interactive: bool,
+) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let (app, project) = utils::parse_target(&target)?.app_project()?;
+
+    let resp = api::get_app_deploy_targets(&token, &project, &app).await?;
+    let group_id = resp.node_group_id
+        .context("App is in single-node mode. Failover needs at least two nodes.")?;
+
+    let node = resp.targets.iter()
+        .find(|t| t.node_id == node_id as i64)
+        .with_context(|| format!("Node {} is not bound to {}", node_id, target))?;
+    if node.status == "draining" {
+        return Err(anyhow!("Node {} is already draining", node_id));
+    }
+    let survivors = resp.targets.iter()
+        .filter(|t| t.node_id != node_id as i64 && t.status == "healthy")
+        .count();
+    if survivors == 0 {
+        return Err(anyhow!("No other healthy node in the pool — taking node {} out would cause an outage", node_id));
+    }
+
+    let url = format!("https://{}.{}.ops.autos{}", app, project, path);
+
+    o_step!("{} Failover test for {}\n", "🧪".cyan(), target.green());
+    o_detail!("  Node:      {} ({})", node_id.to_string().yellow(), node.domain);
+    o_detail!("  Survivors: {} healthy node(s)", survivors);
+    o_detail!("  Probe:     {} for {}s", url.cyan(), duration);
+    o_detail!("");
+
+    if !prompt::confirm_yes(&format!("Drain node {} for {}s?", node_id, duration), interactive)? {
+        o_warn!("Aborted.");
+        return Ok(());
+    }
+
+    // 1. Take the node out of rotation
+    o_step!("{} Draining node {}...", "🚰".cyan(), node_id);
+    api::drain_node(&token, group_id, node_id).await?;
+    o_success!("   {} Node drained", "✔".green());
+
+    // 2. Verify traffic shifted and the app stays reachable; Ctrl+C still restores the node
+    let probe = async {
+        let after = api::get_app_deploy_targets(&token, &project, &app).await?;
+        let shifted = after.targets.iter()
+            .find(|t| t.node_id == node_id as i64)
+            .map(|t| t.status != "healthy")
+            .unwrap_or(false);
+        if shifted {
+            o_success!("   {} Load balancer reports node {} out of rotation", "✔".green(), node_id);
+        } else {
+            o_warn!("   {} Load balancer still reports node {} as healthy", "⚠".yellow(), node_id);
+        }
+
+        o_step!("\n{} Probing {}...", "🔍".cyan(), url.cyan());
+        let (ok, total) = probe_public_domain(&url, duration, 2).await?;
+        Ok::<_, anyhow::Error>((shifted, ok, total))
+    };
+
+    let outcome = tokio::select! {
+        r = probe => Some(r),
+        _ = tokio::signal::ctrl_c() => {
+            o_warn!("\n{}", "Interrupted — restoring node...".yellow());
+            None
+        }
+    };
+
+    // 3. Always restore
+    o_step!("\n{} Restoring node {}...", "🔄".cyan(), node_id);
+    api::undrain_node(&token, group_id, node_id).await
+        .with_context(|| format!("Failed to restore node {}. Run `ops pool undrain {} --node {}`", node_id, target, node_id))?;
+    o_success!("   {} Node {} is back in rotation", "✔".green(), node_id);
+
+    let (shifted, ok, total) = match outcome {
+        Some(r) => r?,
+        None => return Err(anyhow!("Failover test interrupted")),
+    };
+
+    if ok == total && shifted {
+        o_result!("\n{} Failover passed: {}/{} probes succeeded with node {} out of rotation",
+            "✅".green(), ok, total, node_id);
+        Ok(())
+    } else if ok == total {
+        o_result!("\n{} App stayed reachable ({}/{} probes), but the node was not reported out of rotation",
+            "⚠️".yellow(), ok, total);
+        Ok(())
+    } else {
+        o_result!("\n{} Failover failed: {}/{} probes succeeded", "✘".red(), ok, total);
+        Err(anyhow!("{} of {} probes failed while node {} was drained", total - ok, total, node_id))
+    }
+}
@@ -0,0 +1,304 @@
+use crate::commands::deploy::{self, compose_args, env_prefix, load_ops_toml};
+use crate::commands::drift::DEFAULT_COMPOSE_FILES;
+use crate::commands::{keys, policy};
+use crate::commands::ssh::SshSession;
+use crate::router::{ProxyOptions, RouteMatch, Router};
+use crate::serve::previews::{manifest_path, PreviewManifest, PREVIEWS_DIR};
+use crate::types::{AppDef, OpsToml};
+use crate::{api, config, prompt};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Leaves room for `<slug>.<app>.<project>` in one DNS name
+const MAX_SLUG_LEN: usize = 30;
+/// Compose override next to the preview checkout, which moves published ports to random ones
+const OVERRIDE_SUFFIX: &str = ".ops-preview.yml";
+const DEFAULT_TTL: &str = "7d";
+
+/// Branch name as a DNS label: "feature/Login_v2" → "feature-login-v2"
+pub fn slug(branch: &str) -> Result<String> {
+    let mut slug = String::new();
+    for c in branch.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.trim_matches('-').chars().take(MAX_SLUG_LEN).collect();
+    let slug = slug.trim_end_matches('-').to_string();
+    if slug.is_empty() {
+        bail!("Cannot derive a preview name from branch '{}'", branch);
+    }
+    Ok(slug)
+}
+
+/// Owner of a preview's route fragments, kept apart from the app's own routes
+fn preview_owner(app: &str, slug: &str) -> String {
+    format!("{}--{}", app, slug)
+}
+
+/// The ops.toml of the main deployment, rewritten so the preview shares nothing with it
+fn preview_config(config: &OpsToml, slug: &str, branch: &str) -> OpsToml {
+    let mut preview = config.clone();
+    preview.deploy_path = format!("{}-preview-{}", config.deploy_path.trim_end_matches('/'), slug);
+    // Compose project names must be lowercase
+    preview.deploy.compose_project_name = Some(format!("{}-{}", config.compose_project(), slug).to_lowercase());
+    if preview.deploy.source == "git" {
+        preview.deploy.branch = Some(branch.to_string());
+    }
+    preview.deploy.strategy = None;
+    // Custom domains and health checks point at the main deployment
+    preview.routes.clear();
+    preview.healthchecks.clear();
+    for app in &mut preview.apps {
+        app.domains.clear();
+    }
+    preview
+}
+
+fn override_path(preview: &OpsToml) -> String {
+    format!("{}{}", preview.deploy_path, OVERRIDE_SUFFIX)
+}
+
+/// App ports on random loopback ports; every other service publishes nothing, so nothing clashes with the main deployment
+fn override_yaml(services: &[String], apps: &[AppDef]) -> String {
+    let mut yaml = String::from("services:\n");
+    for svc in services {
+        let port = apps.iter().find(|a| a.services.first() == Some(svc)).and_then(|a| a.port);
+        match port {
+            Some(port) => yaml.push_str(&format!("  {}:\n    ports: !override [\"127.0.0.1::{}\"]\n", svc, port)),
+            None => yaml.push_str(&format!("  {}:\n    ports: !reset []\n", svc)),
+        }
+    }
+    yaml
+}
+
+/// Host port from `docker compose port` output ("127.0.0.1:49153")
+fn parse_host_port(output: &str) -> Option<u16> {
+    output.lines().next()?.trim().rsplit(':').next()?.parse().ok()
+}
+
+/// --branch, or the branch checked out next to ops.toml
+fn resolve_branch(file: &str, branch: Option<String>) -> Result<String> {
+    if let Some(branch) = branch {
+        return Ok(branch);
+    }
+    let dir = Path::new(file).parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    policy::local_branch(dir).context("Not on a branch; pass --branch <name>")
+}
+
+/// --ttl, else [preview] ttl, else 7 days
+fn resolve_ttl(config: &OpsToml, ttl: Option<String>) -> Result<Duration> {
+    let ttl = ttl
+        .or_else(|| config.preview.as_ref().and_then(|p| p.ttl.clone()))
+        .unwrap_or_else(|| DEFAULT_TTL.to_string());
+    humantime::parse_duration(&ttl).with_context(|| format!("Invalid preview TTL '{}' (e.g. 12h, 3d)", ttl))
+}
+
+/// ops preview up: deploy a branch next to the main deployment, under its own compose project and subdomains
+pub async fn handle_up(file: String, branch: Option<String>, ttl: Option<String>, env_vars: Vec<String>) -> Result<()> {
+    let base = load_ops_toml(&file)?;
+    let ttl = resolve_ttl(&base, ttl)?;
+    let branch = resolve_branch(&file, branch)?;
+    let slug = slug(&branch)?;
+    let mut preview = preview_config(&base, &slug, &branch);
+    let apps: Vec<AppDef> = preview.apps.iter().filter(|a| a.port.is_some()).cloned().collect();
+    if apps.is_empty() {
+        bail!("Previews are routed to [[apps]] with a port; {} has none", file);
+    }
+
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let targets = deploy::resolve_targets(&base, &None).await?;
+    let target = targets.iter().find(|t| t.is_primary).unwrap_or(&targets[0]);
+
+    o_step!("{} {} ({}) on {}", "🔍 Preview".cyan(), branch.green(), slug, target.domain.cyan());
+    let session = SshSession::connect(&target.node_id.to_string()).await?;
+    let deploy_path = preview.deploy_path.clone();
+    session.exec(&format!("mkdir -p {}", deploy_path), None)?;
+
+    deploy::sync_env_files(&preview, &session)?;
+    deploy::sync_directories(&preview, &session).await?;
+    deploy::sync_code(&preview, &session, &None, &None, &env_vars)?;
+
+    // The override only works on top of the full file list
+    let env = env_prefix(&env_vars);
+    let services = session.exec_output(&format!(
+        "cd {} && {}docker compose {} config --services", deploy_path, env, compose_args(&preview)
+    ))?;
+    let services: Vec<String> = String::from_utf8_lossy(&services).lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+    let mut files = match preview.deploy.compose_files.take() {
+        Some(files) => files,
+        None => {
+            // First existing file in list order (ls would sort them)
+            let probe = format!("cd {} && for f in {}; do [ -f \"$f\" ] && echo \"$f\" && break; done; true",
+                deploy_path, DEFAULT_COMPOSE_FILES.join(" "));
+            let found = String::from_utf8_lossy(&session.exec_output(&probe)?).trim().to_string();
+            if found.is_empty() {
+                bail!("No compose file in {}", deploy_path);
+            }
+            vec![found]
+        }
+    };
+    session.exec(&format!("cat > {}", override_path(&preview)), Some(&override_yaml(&services, &apps)))?;
+    files.push(override_path(&preview));
+    preview.deploy.compose_files = Some(files);
+    let compose = compose_args(&preview);
+
+    o_step!("\n{}", "🚀 Starting preview containers...".cyan());
+    let build = if preview.deploy.source == "image" { "" } else { " --build" };
+    session.exec(&format!("cd {} && {}docker compose {} up -d{} --remove-orphans", deploy_path, env, compose, build), None)?;
+
+    let router = Router::from_config(preview.deploy.router.as_deref())?;
+    router.ensure_base(&session)?;
+    o_step!("\n{}", format!("⚙️  Generating {} routes...", router.name()).cyan());
+    let mut fragments = Vec::new();
+    for app in &apps {
+        let svc = app.services.first().with_context(|| format!("[[apps]] '{}' has no services", app.name))?;
+        let port = app.port.unwrap_or_default();
+        let output = session.exec_output(&format!("cd {} && {}docker compose {} port {} {}", deploy_path, env, compose, svc, port))?;
+        let host_port = parse_host_port(&String::from_utf8_lossy(&output))
+            .with_context(|| format!("Service '{}' does not publish port {}", svc, port))?;
+        let upstream = format!("127.0.0.1:{}", host_port);
+        // The ops edge sends <slug>.<app>.<project>.ops.autos with this target header
+        let target_header = format!("{}.{}.{}", slug, app.name, preview.project);
+        let (path, content) = router.render(
+            &format!("ops-preview-{}-{}-{}", slug, app.name, preview.project),
+            &preview_owner(&app.name, &slug), &preview.project,
+            &RouteMatch::Target(target_header.clone()), &upstream, &ProxyOptions::for_app(app),
+        );
+        router.write(&session, &path, &content)?;
+        o_detail!("   ✔ {} → {}", target_header.green(), upstream);
+        fragments.push(path);
+    }
+    router.reload(&session)?;
+
+    // ops serve removes the preview once this runs out, or when the branch is deleted
+    let manifest = PreviewManifest {
+        project: preview.project.clone(),
+        slug: slug.clone(),
+        branch: branch.clone(),
+        compose_project: preview.compose_project().to_string(),
+        deploy_path: deploy_path.clone(),
+        override_file: override_path(&preview),
+        router: router.name().to_lowercase(),
+        fragments,
+        expires_at: (SystemTime::now() + ttl).duration_since(UNIX_EPOCH)?.as_secs(),
+        git: preview.deploy.source == "git",
+    };
+    session.exec(&format!("mkdir -p {} && cat > {}", PREVIEWS_DIR, manifest.path()), Some(&serde_json::to_string_pretty(&manifest)?))?;
+
+    let app_names: Vec<String> = apps.iter().map(|a| a.name.clone()).collect();
+    let info = api::upsert_preview(&token, &preview.project, &slug, &branch, target.node_id, &app_names, ttl.as_secs()).await
+        .context("Preview is running but could not be registered; remove it with `ops preview down`")?;
+
+    o_result!("\n{} Preview of {} is up for {}", "✅".green(), branch.green(), humantime::format_duration(ttl));
+    for url in &info.urls {
+        o_result!("   {}", url.cyan());
+    }
+    Ok(())
+}
+
+/// ops preview down: remove a branch preview's containers, volumes, checkout and routes
+pub async fn handle_down(file: String, branch: Option<String>, interactive: bool) -> Result<()> {
+    let base = load_ops_toml(&file)?;
+    let branch = resolve_branch(&file, branch)?;
+    let slug = slug(&branch)?;
+    let preview = preview_config(&base, &slug, &branch);
+
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let info = api::get_preview(&token, &preview.project, &slug).await
+        .with_context(|| format!("No preview of branch '{}' in project '{}'", branch, preview.project))?;
+
+    o_step!("{} {} ({}) on node {}", "🧹 Removing preview".cyan(), branch.green(), slug, info.node_id);
+    o_detail!("   Containers, volumes and {} are deleted.", preview.deploy_path);
+    if !prompt::confirm_yes("Continue?", interactive)? {
+        o_warn!("Cancelled.");
+        return Ok(());
+    }
+
+    let session = SshSession::connect(&info.node_id.to_string()).await?;
+    session.exec(&format!("docker compose -p {} down -v --remove-orphans", preview.compose_project()), None)?;
+    session.exec(&format!(
+        "rm -rf {} {} {}", preview.deploy_path, override_path(&preview), manifest_path(preview.compose_project())
+    ), None)?;
+    o_success!("   {}", "✔ Containers and checkout removed".green());
+
+    let router = Router::from_config(preview.deploy.router.as_deref())?;
+    let owners: Vec<String> = preview.apps.iter().map(|a| preview_owner(&a.name, &slug)).collect();
+    let fragments: Vec<String> = router.list_fragments(&session)?
+        .into_iter()
+        .filter(|f| owners.iter().any(|o| f.belongs_to(o, &preview.project)))
+        .map(|f| f.path)
+        .collect();
+    router.remove_fragments(&session, &fragments)?;
+    o_success!("   {} ({} route(s))", "✔ Routes removed".green(), fragments.len());
+
+    api::delete_preview(&token, &preview.project, &slug).await.context("Failed to release the preview's subdomains")?;
+    o_result!("\n{} Preview of {} removed", "✅".green(), branch.green());
+    Ok(())
+}
+
+/// ops preview list: a project's previews with owner, age and expiry
+pub async fn handle_list(file: String) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let previews = api::list_previews(&token, &config.project).await.context("Failed to list previews")?.previews;
+
+    if previews.is_empty() {
+        o_result!("No previews in project '{}'. Create one with `ops preview up`.", config.project);
+        return Ok(());
+    }
+    o_result!("{:<30} {:<8} {:<16} {:<10} {:<12} {}", "BRANCH".bold(), "NODE".bold(), "OWNER".bold(), "AGE".bold(), "EXPIRES".bold(), "URL".bold());
+    let now = SystemTime::now();
+    for p in &previews {
+        let age = keys::age_days(&p.created_at, now).map(|d| format!("{} days", d)).unwrap_or_else(|| "?".into());
+        let expires = p.expires_at.as_deref().map(|e| e.chars().take(10).collect::<String>()).unwrap_or_else(|| "-".into());
+        let url = p.urls.first().map(String::as_str).unwrap_or("-");
+        o_result!("{:<30} {:<8} {:<16} {:<10} {:<12} {}", p.branch, p.node_id, p.owner.as_deref().unwrap_or("-"), age, expires, url);
+        for more in p.urls.iter().skip(1) {
+            o_result!("{:<80} {}", "", more);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("feature/Login_v2").unwrap(), "feature-login-v2");
+        assert_eq!(slug("--fix--").unwrap(), "fix");
+        assert_eq!(slug("a".repeat(40).as_str()).unwrap().len(), MAX_SLUG_LEN);
+        assert!(slug("///").is_err());
+    }
+
+    #[test]
+    fn test_override_yaml() {
+        let app: AppDef = toml::from_str("name = \"api\"\nservices = [\"web\", \"worker\"]\nport = 3000").unwrap();
+        let yaml = override_yaml(&["web".into(), "worker".into(), "db".into()], &[app]);
+        assert_eq!(
+            yaml,
+            "services:\n  web:\n    ports: !override [\"127.0.0.1::3000\"]\n  worker:\n    ports: !reset []\n  db:\n    ports: !reset []\n"
+        );
+        assert_eq!(parse_host_port("127.0.0.1:49153\n"), Some(49153));
+        assert_eq!(parse_host_port(""), None);
+    }
+
+    #[test]
+    fn test_resolve_ttl() {
+        let mut config: OpsToml = toml::from_str("project = \"p\"\ndeploy_path = \"/opt/p\"\n[deploy]\n").unwrap();
+        assert_eq!(resolve_ttl(&config, None).unwrap(), Duration::from_secs(7 * 86_400));
+        config.preview = Some(crate::types::PreviewConfig { ttl: Some("12h".into()) });
+        assert_eq!(resolve_ttl(&config, None).unwrap(), Duration::from_secs(12 * 3600));
+        assert_eq!(resolve_ttl(&config, Some("3d".into())).unwrap(), Duration::from_secs(3 * 86_400));
+        assert!(resolve_ttl(&config, Some("soon".into())).is_err());
+    }
+}
@@ -1,6 +1,8 @@
-use crate::{api, config};
+use crate::{api, config, output};
+use crate::table::{Column, Table, TableOptions};
 use anyhow::{Context, Result};
-use colored::Colorize;
+use crate::types::{ProjectAppSummary, ProjectItem, ProjectListQuery};
+use colored::{ColoredString, Colorize};
 
 pub async fn handle_create_project(name: String) -> Result<()> {
     o_step!("Creating project '{}'...", name.cyan());
@@ -12,42 +14,131 @@ pub async fn handle_create_project(name: String) -> Result<()> {
     Ok(())
 }
 
-pub async fn handle_list_projects(name_filter: Option<String>) -> Result<()> {
+/// The project's apps; for backends that only return nodes, one per environment without health
+pub fn app_summaries(project: &ProjectItem) -> Vec<ProjectAppSummary> {
+    if !project.apps.is_empty() || project.nodes.is_empty() {
+        return project.apps.clone();
+    }
+    let mut apps: Vec<ProjectAppSummary> = Vec::new();
+    for node in &project.nodes {
+        match apps.iter_mut().find(|a| a.name == node.environment) {
+            Some(app) => app.node_count += 1,
+            None => apps.push(ProjectAppSummary { name: node.environment.clone(), node_count: 1, healthy_nodes: None, domains: vec![] }),
+        }
+    }
+    apps
+}
+
+/// healthy / degraded / down from the healthy node count (None: unknown)
+fn health_label(healthy: Option<u32>, total: i64) -> ColoredString {
+    match (healthy, total) {
+        (_, 0) => "no nodes".dimmed(),
+        (None, _) => "-".normal(),
+        (Some(h), t) if i64::from(h) >= t => "healthy".green(),
+        (Some(0), _) => "down".red(),
+        _ => "degraded".yellow(),
+    }
+}
+
+pub async fn handle_list_projects(
+    name_filter: Option<String>,
+    mine: bool,
+    shared: bool,
+    region: Option<String>,
+    opts: &TableOptions,
+) -> Result<()> {
     let cfg = config::load_config().context("Config not found. Please log in with `ops login`.")?;
     let token = cfg.token.context("You are not logged in. Please run `ops login` first.")?;
 
-    let res = api::list_projects(&token, name_filter.as_deref()).await?;
+    let query = ProjectListQuery {
+        name: name_filter,
+        owner: if mine { Some("mine") } else if shared { Some("shared") } else { None },
+        region,
+    };
+    let res = api::list_projects(&token, &query).await?;
+    if output::json() {
+        return output::print_json(&res);
+    }
 
     if res.projects.is_empty() {
         o_result!("No projects found.");
         return Ok(());
     }
 
-    // 绘制树形结构
-    o_step!("{}", "Projects Tree".bold().underline());
-    
-    for (i, project) in res.projects.iter().enumerate() {
-        let is_last_project = i == res.projects.len() - 1;
-        let p_prefix = if is_last_project { "└──" } else { "├──" };
-        
-        o_detail!("{} {}", p_prefix, project.name.cyan().bold());
-
-        if project.nodes.is_empty() {
-            let n_prefix = if is_last_project { "    └──" } else { "│   └──" };
-            o_detail!("{} {}", n_prefix, "(no servers)".dimmed());
-        } else {
-            for (j, node) in project.nodes.iter().enumerate() {
-                let is_last_node = j == project.nodes.len() - 1;
-                
-                // 确定缩进
-                let n_prefix_start = if is_last_project { "    " } else { "│   " };
-                let n_tree_char = if is_last_node { "└──" } else { "├──" };
-                
-                let info = format!("{} ({})", node.environment.yellow(), node.ip_address);
-                o_detail!("{}{} {}", n_prefix_start, n_tree_char, info);
-            }
+    o_step!("{}", "Projects:".bold());
+    o_detail!();
+
+    // One row per app; projects without apps still get a row
+    let mut table = Table::new(vec![
+        Column::new("project"),
+        Column::new("app"),
+        Column::new("nodes").right(),
+        Column::new("health"),
+        Column::new("domains"),
+        Column::wide("owner"),
+        Column::wide("role"),
+    ]);
+    for project in &res.projects {
+        let owner = project.owner.clone().unwrap_or_else(|| "-".into());
+        let role = project.role.clone().unwrap_or_else(|| "owner".into());
+        let apps = app_summaries(project);
+        if apps.is_empty() {
+            table.row(vec![project.name.cyan().bold().to_string(), "(no apps)".dimmed().to_string(),
+                "0".into(), "-".into(), "-".into(), owner.clone(), role.clone()]);
+        }
+        for app in apps {
+            let nodes = match app.healthy_nodes {
+                Some(healthy) => format!("{}/{}", healthy, app.node_count),
+                None => app.node_count.to_string(),
+            };
+            let domains = match app.domains.len() {
+                0 => "-".to_string(),
+                1 => app.domains[0].clone(),
+                n => format!("{} +{}", app.domains[0], n - 1),
+            };
+            table.row(vec![
+                project.name.cyan().bold().to_string(),
+                app.name.yellow().to_string(),
+                nodes,
+                health_label(app.healthy_nodes, app.node_count).to_string(),
+                domains,
+                owner.clone(),
+                role.clone(),
+            ]);
         }
     }
+    table.print(opts)?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NodeItem;
+
+    #[test]
+    fn test_app_summaries_and_health() {
+        let node = |env: &str| NodeItem { environment: env.into(), ip_address: "203.0.113.1".into(), domain: format!("{}.shop.ops.autos", env) };
+        // Older backends only send nodes
+        let project = ProjectItem { name: "shop".into(), nodes: vec![node("api"), node("web"), node("api")], owner: None, role: None, apps: vec![] };
+        let apps = app_summaries(&project);
+        assert_eq!(apps.iter().map(|a| (a.name.as_str(), a.node_count, a.healthy_nodes)).collect::<Vec<_>>(),
+            vec![("api", 2, None), ("web", 1, None)]);
+
+        let project = ProjectItem {
+            apps: vec![ProjectAppSummary { name: "api".into(), node_count: 3, healthy_nodes: Some(2), domains: vec!["shop.example.com".into()] }],
+            ..project
+        };
+        assert_eq!(app_summaries(&project)[0].healthy_nodes, Some(2));
+        // An older backend without the field: unknown, not down
+        let app: ProjectAppSummary = serde_json::from_str(r#"{"name": "api", "node_count": 2}"#).unwrap();
+        assert_eq!(app.healthy_nodes, None);
+
+        assert!(health_label(Some(3), 3).to_string().contains("healthy"));
+        assert!(health_label(Some(2), 3).to_string().contains("degraded"));
+        assert!(health_label(Some(0), 3).to_string().contains("down"));
+        assert!(health_label(None, 3).to_string().contains('-'));
+        assert!(health_label(Some(0), 0).to_string().contains("no nodes"));
+    }
+}
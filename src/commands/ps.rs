@@ -0,0 +1,201 @@
+//! `ops ps`: the project's containers on every node it is bound to, in one table. Each node is
+//! asked through its `ops serve` `/containers` endpoint, or `docker compose ps` when serve
+//! isn't running there or doesn't manage the project's compose dir.
+
+use crate::commands::deploy::{compose_args, load_ops_toml_env};
+use crate::commands::ssh::SshSession;
+use crate::commands::status::parse_compose_ps;
+use crate::node_state::SERVE_ENV_FILE;
+use crate::serve::containers::Container;
+use crate::table::{Column, Table, TableOptions};
+use crate::types::{Node, OpsToml};
+use crate::utils::shell_quote;
+use crate::{api, config, output};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+struct ContainerList {
+    containers: Vec<Container>,
+}
+
+#[derive(Serialize)]
+struct NodeContainers {
+    node_id: i64,
+    domain: String,
+    region: Option<String>,
+    /// "serve" or "ssh"
+    source: Option<&'static str>,
+    containers: Vec<Container>,
+    error: Option<String>,
+}
+
+/// The project's containers among all of a node's; serve versions that don't report the
+/// compose project are matched on the full `<project>-<service>-<n>` container name, so that
+/// `shop-admin-web-1` is not taken for a container of `shop`
+fn project_containers(all: Vec<Container>, compose_project: &str, services: &[&str]) -> Vec<Container> {
+    all.into_iter()
+        .filter(|c| if c.project.is_empty() { is_compose_name(&c.name, compose_project, services) } else { c.project == compose_project })
+        .collect()
+}
+
+/// `name` is `<project>-<service>-<n>` (or the `_` of Compose v1) for one of `services`
+fn is_compose_name(name: &str, compose_project: &str, services: &[&str]) -> bool {
+    ['-', '_'].iter().any(|&sep| {
+        let Some(rest) = name.strip_prefix(compose_project).and_then(|r| r.strip_prefix(sep)) else { return false };
+        let Some((service, n)) = rest.rsplit_once(sep) else { return false };
+        services.contains(&service) && !n.is_empty() && n.chars().all(|ch| ch.is_ascii_digit())
+    })
+}
+
+/// `docker compose ps --format json` entries in serve's `/containers` shape
+fn from_compose_ps(entries: &[Value]) -> Vec<Container> {
+    let field = |v: &Value, key: &str| v[key].as_str().unwrap_or("").to_string();
+    entries.iter().map(|v| Container {
+        project: field(v, "Project"),
+        name: field(v, "Name"),
+        service: field(v, "Service"),
+        state: field(v, "State"),
+        status: field(v, "Status"),
+        ports: field(v, "Ports"),
+    }).collect()
+}
+
+fn node_containers(config: &OpsToml, session: &SshSession, serve_port: u16) -> Result<(&'static str, Vec<Container>)> {
+    let url = format!("http://127.0.0.1:{}/containers", serve_port);
+    let serve = format!(". {} 2>/dev/null; curl -fs -m 5 -H \"Authorization: Bearer $OPS_SERVE_TOKEN\" {}",
+        SERVE_ENV_FILE, shell_quote(&url));
+    if let Ok(Ok(list)) = session.exec_output(&serve).map(|out| serde_json::from_slice::<ContainerList>(&out)) {
+        let services: Vec<&str> = config.apps.iter().flat_map(|a| a.services.iter().map(String::as_str)).collect();
+        let containers = project_containers(list.containers, config.compose_project(), &services);
+        if !containers.is_empty() {
+            return Ok(("serve", containers));
+        }
+    }
+    let ps = format!("cd {} && docker compose {} ps -a --format json", config.deploy_path, compose_args(config));
+    let out = session.exec_output(&ps).context("docker compose ps failed")?;
+    Ok(("ssh", from_compose_ps(&parse_compose_ps(&String::from_utf8_lossy(&out)))))
+}
+
+fn state_label(state: &str) -> String {
+    match state {
+        "running" => state.green().to_string(),
+        "restarting" | "paused" | "created" => state.yellow().to_string(),
+        "exited" | "dead" => state.red().to_string(),
+        _ => state.to_string(),
+    }
+}
+
+/// ops ps: containers, states and nodes of the project across every bound node
+pub async fn handle_ps(file: String, env: Option<String>, app: Option<String>, opts: &TableOptions) -> Result<()> {
+    let config = Arc::new(load_ops_toml_env(&file, env.as_deref())?);
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let nodes: Vec<Node> = api::list_nodes(&token).await.context("Failed to list nodes")?.nodes.into_iter()
+        .filter(|n| n.bound_apps.iter().flatten().any(|b| {
+            b.project_name == config.project && app.as_ref().is_none_or(|a| b.name == *a)
+        }))
+        .filter(|n| config.env_selects(n.id, n.region.as_deref()))
+        .collect();
+    if nodes.is_empty() {
+        bail!("No nodes bound to {}{}", app.as_ref().map(|a| format!("{}.", a)).unwrap_or_default(), config.project);
+    }
+
+    let mut join_set = tokio::task::JoinSet::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let (config, id, serve_port) = (config.clone(), node.id, node.serve_port);
+        join_set.spawn(async move {
+            let result = match SshSession::connect(&id.to_string()).await {
+                Ok(session) => node_containers(&config, &session, serve_port),
+                Err(e) => Err(e),
+            };
+            (i, result)
+        });
+    }
+    let mut results = Vec::new();
+    while let Some(joined) = join_set.join_next().await {
+        results.push(joined?);
+    }
+    results.sort_by_key(|(i, _)| *i);
+
+    let per_node: Vec<NodeContainers> = nodes.iter().zip(results).map(|(n, (_, result))| {
+        let (source, containers, error) = match result {
+            Ok((source, containers)) => (Some(source), containers, None),
+            Err(e) => (None, Vec::new(), Some(format!("{:#}", e))),
+        };
+        NodeContainers { node_id: n.id, domain: n.domain.clone(), region: n.region.clone(), source, containers, error }
+    }).collect();
+
+    if output::json() {
+        return output::print_json(&per_node);
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("node"),
+        Column::new("service"),
+        Column::new("container"),
+        Column::new("state"),
+        Column::new("status"),
+        Column::wide("ports"),
+        Column::wide("region"),
+        Column::wide("source"),
+    ]);
+    for node in &per_node {
+        let region = node.region.clone().unwrap_or_else(|| "-".into());
+        if let Some(e) = &node.error {
+            table.row(vec![node.domain.clone(), "-".into(), "-".into(), "unreachable".red().to_string(),
+                e.lines().next().unwrap_or("").to_string(), "-".into(), region.clone(), "-".into()]);
+            continue;
+        }
+        if node.containers.is_empty() {
+            table.row(vec![node.domain.clone(), "-".into(), "(no containers)".dimmed().to_string(), "-".into(),
+                "-".into(), "-".into(), region.clone(), node.source.unwrap_or("-").into()]);
+        }
+        for c in &node.containers {
+            table.row(vec![
+                node.domain.clone(),
+                c.service.yellow().to_string(),
+                c.name.clone(),
+                state_label(&c.state),
+                c.status.clone(),
+                if c.ports.is_empty() { "-".into() } else { c.ports.clone() },
+                region.clone(),
+                node.source.unwrap_or("-").into(),
+            ]);
+        }
+    }
+    table.print(opts)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_containers_and_compose_ps() {
+        let entries = parse_compose_ps(concat!(
+            r#"{"Project":"shop","Name":"shop-api-1","Service":"api","State":"running","Status":"Up 2 hours","Ports":"0.0.0.0:3000->3000/tcp"}"#, "\n",
+            r#"{"Project":"blog","Name":"blog-web-1","Service":"web","State":"exited","Status":"Exited (1)","Ports":""}"#,
+        ));
+        let all = from_compose_ps(&entries);
+        assert_eq!(all[0].service, "api");
+        assert_eq!(all[1].state, "exited");
+
+        let shop = project_containers(all.clone(), "shop", &[]);
+        assert_eq!(shop.len(), 1);
+        assert_eq!(shop[0].name, "shop-api-1");
+
+        // Older serve: no project, matched by name
+        let legacy: Vec<Container> = all.into_iter().map(|c| Container { project: String::new(), ..c }).collect();
+        assert_eq!(project_containers(legacy.clone(), "blog", &["web"])[0].name, "blog-web-1");
+        assert!(project_containers(legacy, "blog", &["api"]).is_empty());
+        assert!(is_compose_name("shop_api_2", "shop", &["api"]));
+        assert!(!is_compose_name("shop-admin-api-1", "shop", &["api"]));
+        assert!(!is_compose_name("shop-api-worker", "shop", &["api"]));
+    }
+}
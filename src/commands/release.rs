@@ -0,0 +1,374 @@
+//! Releases and rollback. After each deploy, every service's image is tagged on the node as
+//! `<repo>:ops-release-<deployment id>` (so rebuilds and `docker image prune` keep it) and recorded
+//! in the backend with the git commit and the app's route fragments. `ops rollback` puts those
+//! images back under the names the containers use and recreates them.
+
+use crate::commands::deploy::{self, compose_args, compose_base_args, load_ops_toml, resolve_app_name, resolve_targets};
+use crate::commands::ssh::SshSession;
+use crate::router::Router;
+use crate::types::{DeployTarget, NodeRelease, OpsToml, ReleaseImage, ReleaseInfo, RouteFile};
+use crate::utils::shell_quote;
+use crate::{api, config, prompt};
+use anyhow::{anyhow, bail, Context, Result};
+use colored::Colorize;
+
+/// Release tags kept per image repository on a node
+const KEEP_RELEASES: usize = 5;
+const TAG_PREFIX: &str = "ops-release-";
+
+/// "ghcr.io/acme/api:1.2" → "ghcr.io/acme/api" (digests are dropped too, registry ports kept)
+fn image_repo(image: &str) -> &str {
+    let image = image.split('@').next().unwrap_or(image);
+    match image.rfind(':') {
+        Some(i) if !image[i..].contains('/') => &image[..i],
+        _ => image,
+    }
+}
+
+/// A running container of the project: (service, image reference, image ID)
+#[derive(Debug, PartialEq)]
+struct RunningImage {
+    service: String,
+    image: String,
+    image_id: String,
+}
+
+/// From `docker inspect` lines "/name|image|image id|compose service label". Containers started by
+/// zero-downtime deploys have no compose labels; their service comes from `<project>-<svc>-<id>`.
+fn parse_running(output: &str, compose_project: &str) -> Vec<RunningImage> {
+    let mut running: Vec<RunningImage> = Vec::new();
+    for line in output.lines() {
+        let parts: Vec<&str> = line.trim().split('|').collect();
+        let [name, image, image_id, label] = parts[..] else { continue };
+        let service = if label.is_empty() {
+            let Some(rest) = name.trim_start_matches('/').strip_prefix(&format!("{}-", compose_project)) else { continue };
+            match rest.rsplit_once('-') {
+                Some((svc, n)) if n.chars().all(|c| c.is_ascii_digit()) => svc.to_string(),
+                _ => rest.to_string(),
+            }
+        } else {
+            label.to_string()
+        };
+        if !running.iter().any(|r| r.service == service) {
+            running.push(RunningImage { service, image: image.to_string(), image_id: image_id.to_string() });
+        }
+    }
+    running
+}
+
+/// Tag each image for the release and drop the oldest release tags beyond KEEP_RELEASES
+fn tag_script(images: &[(ReleaseImage, String)]) -> String {
+    let mut script = String::new();
+    let mut repos: Vec<&str> = Vec::new();
+    for (img, image_id) in images {
+        script.push_str(&format!("docker tag {} {}\n", shell_quote(image_id), shell_quote(&img.tag)));
+        let repo = image_repo(&img.tag);
+        if !repos.contains(&repo) {
+            repos.push(repo);
+        }
+    }
+    for repo in repos {
+        script.push_str(&format!(
+            "docker images {0} --format '{{{{.Tag}}}}' | sed -n 's/^{1}//p' | sort -rn | tail -n +{2} | sed 's|^|{0}:{1}|' | xargs -r docker rmi >/dev/null 2>&1\n",
+            repo, TAG_PREFIX, KEEP_RELEASES + 1
+        ));
+    }
+    script.push_str("true");
+    script
+}
+
+/// Apps whose route fragments belong to a release
+fn app_names(config: &OpsToml) -> Vec<String> {
+    let mut names: Vec<String> = config.apps.iter().map(|a| a.name.clone()).collect();
+    let app = resolve_app_name(config);
+    if !names.contains(&app) {
+        names.push(app);
+    }
+    names
+}
+
+/// Tag what the node runs now and read its route fragments
+pub fn capture(config: &OpsToml, session: &SshSession, deployment_id: i64) -> Result<NodeRelease> {
+    let project = config.compose_project();
+    let inspect = format!(
+        "docker ps -q --filter 'name=^{}-' | xargs -r docker inspect -f '{}'",
+        project, "{{.Name}}|{{.Config.Image}}|{{.Image}}|{{index .Config.Labels \"com.docker.compose.service\"}}"
+    );
+    let running = parse_running(&String::from_utf8_lossy(&session.exec_output(&inspect)?), project);
+    let images: Vec<(ReleaseImage, String)> = running.into_iter().map(|r| {
+        let tag = format!("{}:{}{}", image_repo(&r.image), TAG_PREFIX, deployment_id);
+        (ReleaseImage { service: r.service, image: r.image, tag }, r.image_id)
+    }).collect();
+    session.exec_output(&tag_script(&images))?;
+
+    let sha = session.exec_output(&format!("git -C {} rev-parse HEAD 2>/dev/null || true", shell_quote(&config.deploy_path)))?;
+    let mut git_sha = Some(String::from_utf8_lossy(&sha).trim().to_string()).filter(|s| !s.is_empty());
+    if git_sha.is_none() && config.deploy.source == "push" {
+        git_sha = std::process::Command::new("git").args(["rev-parse", "HEAD"]).output().ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+    }
+
+    let router = Router::from_config(config.deploy.router.as_deref())?;
+    let apps = app_names(config);
+    let mut routes = Vec::new();
+    for f in router.list_fragments(session)? {
+        if apps.iter().any(|a| f.belongs_to(a, &config.project)) {
+            let content = session.exec_output(&format!("cat {}", shell_quote(&f.path)))?;
+            routes.push(RouteFile { path: f.path, content: String::from_utf8_lossy(&content).to_string() });
+        }
+    }
+
+    Ok(NodeRelease { git_sha, images: images.into_iter().map(|(i, _)| i).collect(), routes })
+}
+
+/// The release to roll back to: `to`, or the successful one before the newest successful one
+fn pick_release(releases: &[ReleaseInfo], to: Option<i64>) -> Result<&ReleaseInfo> {
+    let usable = |r: &&ReleaseInfo| r.status == "success" && !r.nodes.is_empty();
+    if let Some(id) = to {
+        let r = releases.iter().find(|r| r.deployment_id == id)
+            .ok_or_else(|| anyhow!("Deployment #{} not found. Run `ops releases list` to see the releases", id))?;
+        if !usable(&r) {
+            bail!("Deployment #{} has no recorded release to roll back to (status: {})", id, r.status);
+        }
+        return Ok(r);
+    }
+    releases.iter().filter(usable).nth(1)
+        .context("No earlier release to roll back to. Releases are recorded from this version of ops on")
+}
+
+fn short_sha(sha: &Option<String>) -> String {
+    sha.as_deref().map(|s| s.chars().take(7).collect()).unwrap_or_else(|| "-".into())
+}
+
+/// ops releases list
+pub async fn handle_list(file: String, app_filter: Option<String>) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    let app = app_filter.unwrap_or_else(|| resolve_app_name(&config));
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let releases = api::list_releases(&token, &config.project, &app).await
+        .context("Failed to get releases")?
+        .releases;
+    o_step!("{} {}.{}\n", "📦 Releases:".cyan(), app.green(), config.project.green());
+    if releases.is_empty() {
+        o_detail!("  {}", "No deployments yet".dimmed());
+        return Ok(());
+    }
+
+    let current = releases.iter().find(|r| r.status == "success" && !r.nodes.is_empty()).map(|r| r.deployment_id);
+    o_detail!("  {:<8} {:<9} {:<22} {:<9} {:<8} IMAGES", "ID", "STATUS", "CREATED", "TRIGGER", "GIT");
+    for r in &releases {
+        let status = match r.status.as_str() {
+            "success" => r.status.green(),
+            "failed" => r.status.red(),
+            _ => r.status.yellow(),
+        };
+        let first = r.nodes.first().map(|n| &n.release);
+        let images = first.map(|n| n.images.iter().map(|i| format!("{}={}", i.service, i.image)).collect::<Vec<_>>().join(" ")).unwrap_or_default();
+        let marker = if current == Some(r.deployment_id) { " ← current".cyan() } else { "".normal() };
+        o_detail!("  {:<8} {:<9} {:<22} {:<9} {:<8} {}{}",
+            format!("#{}", r.deployment_id), status, r.created_at, r.trigger.as_deref().unwrap_or("-"),
+            short_sha(&first.and_then(|n| n.git_sha.clone())), images.dimmed(), marker);
+    }
+    o_detail!("\n  {}", "Roll back with `ops rollback` (previous release) or `ops rollback --to <id>`.".dimmed());
+    Ok(())
+}
+
+/// Check every release image is still on the node and put it back under the name its containers use
+fn retag_script(release: &NodeRelease) -> String {
+    let mut script = String::from("set -e\n");
+    for img in &release.images {
+        script.push_str(&format!(
+            "docker image inspect {0} >/dev/null 2>&1 || {{ echo 'Image {0} is no longer on this node' >&2; exit 1; }}\ndocker tag {0} {1}\n",
+            shell_quote(&img.tag), shell_quote(&img.image)
+        ));
+    }
+    script.push_str("true");
+    script
+}
+
+/// Write the release's route fragments back and remove the app's fragments it didn't have
+fn restore_routes(config: &OpsToml, session: &SshSession, release: &NodeRelease) -> Result<()> {
+    let router = Router::from_config(config.deploy.router.as_deref())?;
+    router.ensure_base(session)?;
+    for route in &release.routes {
+        router.write(session, &route.path, &route.content)?;
+        o_detail!("   ✔ {}", route.path);
+    }
+    let apps = app_names(config);
+    let stale: Vec<String> = router.list_fragments(session)?.into_iter()
+        .filter(|f| apps.iter().any(|a| f.belongs_to(a, &config.project)))
+        .filter(|f| !release.routes.iter().any(|r| r.path == f.path))
+        .map(|f| f.path)
+        .collect();
+    if stale.is_empty() {
+        router.reload(session)
+    } else {
+        router.remove_fragments(session, &stale)
+    }
+}
+
+/// Run a release's images on one node. Zero-downtime apps get new deploy-id containers; compose
+/// services are recreated and the release's routes restored.
+fn restore(config: &OpsToml, session: &SshSession, release: &NodeRelease, deployment_id: Option<i64>, app_filter: &Option<String>) -> Result<()> {
+    o_step!("\n{}", "🏷  Restoring release images...".cyan());
+    session.exec(&retag_script(release), None)?;
+
+    let released = |svc: &String| release.images.iter().any(|i| &i.service == svc);
+    let apps_with_port: Vec<_> = config.apps.iter()
+        .filter(|a| a.port.is_some())
+        .filter(|a| app_filter.is_none() || app_filter.as_ref() == Some(&a.name))
+        .collect();
+
+    if let (Some(did), false, false) = (deployment_id, apps_with_port.is_empty(), config.deploy.strategy.as_deref() == Some("up")) {
+        let compose_arg = {
+            let compose = compose_base_args(config);
+            if compose.is_empty() { String::new() } else { format!(" {}", compose) }
+        };
+        for app in &apps_with_port {
+            for svc in app.services.iter().filter(|s| released(s)) {
+                deploy::start_service_container(config, session, did, app, svc, &compose_arg)?;
+            }
+        }
+        session.exec(&format!("echo {} > {}/.ops-active-deployment", did, config.deploy_path), None)?;
+        return Ok(());
+    }
+
+    let svcs: Vec<&str> = release.images.iter().map(|i| i.service.as_str()).collect();
+    session.exec(&format!(
+        "cd {} && docker compose {} up -d --no-build --no-deps --force-recreate --pull never {}",
+        config.deploy_path, compose_args(config), svcs.join(" ")
+    ), None)?;
+    o_step!("\n{}", "⚙️  Restoring routes...".cyan());
+    restore_routes(config, session, release)?;
+    deploy::run_health_checks(config, session)
+}
+
+/// Put `release` back on those of `targets` that were part of it, under a new "rollback" deployment
+pub async fn roll_back_nodes(
+    config: &OpsToml,
+    token: &str,
+    app_id: i64,
+    release: &ReleaseInfo,
+    targets: &[DeployTarget],
+    app_filter: &Option<String>,
+) -> Result<()> {
+    let deployment_id = match api::create_deployment(token, app_id, "rollback").await {
+        Ok(d) => Some(d.id),
+        Err(e) => {
+            o_warn!("   {} {} (continuing anyway)", "⚠ Deployment record failed:".yellow(), e);
+            None
+        }
+    };
+
+    let mut failed = Vec::new();
+    for t in targets {
+        let Some(node) = release.nodes.iter().find(|n| n.node_id == t.node_id) else { continue };
+        o_step!("\n{} {}", "🚀".cyan(), t.domain.cyan());
+        let result = match SshSession::connect(&t.node_id.to_string()).await {
+            Ok(session) => {
+                let result = restore(config, &session, &node.release, deployment_id, app_filter);
+                if let (Ok(_), Some(did)) = (&result, deployment_id) {
+                    deploy::record_deployed_state(config, &session, did, t.node_id).await;
+                }
+                result
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => o_success!("   {} {}", "✔".green(), t.domain.green()),
+            Err(e) => {
+                o_error!("   {} {}: {:#}", "✘".red(), t.domain, e);
+                failed.push(t.domain.clone());
+            }
+        }
+    }
+
+    let outcome = if failed.is_empty() { Ok(()) } else { Err(anyhow!("Rollback failed on {} node(s): {}", failed.len(), failed.join(", "))) };
+    if let Some(did) = deployment_id {
+        deploy::update_deployment_status(did, &outcome).await;
+    }
+    outcome
+}
+
+/// ops rollback
+pub async fn handle_rollback(file: String, app_filter: Option<String>, to: Option<i64>, interactive: bool) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    let app = app_filter.clone().unwrap_or_else(|| resolve_app_name(&config));
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let resp = api::list_releases(&token, &config.project, &app).await.context("Failed to get releases")?;
+    let release = pick_release(&resp.releases, to)?.clone();
+    let targets = resolve_targets(&config, &app_filter).await?;
+
+    o_step!("{} {} to deployment #{} ({}, git {})", "⏪ Rolling back".cyan(), app.green(),
+        release.deployment_id, release.created_at, short_sha(&release.nodes[0].release.git_sha));
+    for img in &release.nodes[0].release.images {
+        o_detail!("   {} {}", img.service.yellow(), img.image);
+    }
+    let missing: Vec<String> = targets.iter()
+        .filter(|t| !release.nodes.iter().any(|n| n.node_id == t.node_id))
+        .map(|t| t.domain.clone())
+        .collect();
+    if !missing.is_empty() {
+        o_warn!("   {} Not part of that release, skipped: {}", "⚠".yellow(), missing.join(", "));
+    }
+    if !prompt::confirm_yes("Roll back now?", interactive)? {
+        o_warn!("Operation cancelled.");
+        return Ok(());
+    }
+
+    roll_back_nodes(&config, &token, resp.app_id, &release, &targets, &app_filter).await?;
+    o_result!("\n{} Rolled back {} to deployment #{}", "✅".green(), app.green(), release.deployment_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ReleaseNode;
+
+    #[test]
+    fn test_capture_parsing_and_scripts() {
+        assert_eq!(image_repo("redq-api:latest"), "redq-api");
+        assert_eq!(image_repo("registry:5000/acme/api"), "registry:5000/acme/api");
+        assert_eq!(image_repo("ghcr.io/acme/api:1.2@sha256:abc"), "ghcr.io/acme/api");
+
+        let running = parse_running(
+            "/redq-api-120|redq-api:latest|sha256:aaa|\n/redq-worker-1|redq-worker:latest|sha256:bbb|worker\n/redq-api-119|redq-api:latest|sha256:ccc|\n",
+            "redq",
+        );
+        assert_eq!(running, vec![
+            RunningImage { service: "api".into(), image: "redq-api:latest".into(), image_id: "sha256:aaa".into() },
+            RunningImage { service: "worker".into(), image: "redq-worker:latest".into(), image_id: "sha256:bbb".into() },
+        ]);
+
+        let img = ReleaseImage { service: "api".into(), image: "redq-api:latest".into(), tag: "redq-api:ops-release-120".into() };
+        let script = tag_script(&[(img.clone(), "sha256:aaa".into())]);
+        assert!(script.starts_with("docker tag sha256:aaa redq-api:ops-release-120\n"));
+        assert!(script.contains("docker images redq-api --format '{{.Tag}}' | sed -n 's/^ops-release-//p' | sort -rn | tail -n +6 | sed 's|^|redq-api:ops-release-|'"));
+
+        let release = NodeRelease { git_sha: None, images: vec![img], routes: vec![] };
+        assert!(retag_script(&release).contains("docker tag redq-api:ops-release-120 redq-api:latest\n"));
+    }
+
+    #[test]
+    fn test_pick_release() {
+        let r = |id: i64, status: &str, nodes: usize| ReleaseInfo {
+            deployment_id: id,
+            status: status.into(),
+            trigger: None,
+            created_at: String::new(),
+            nodes: (0..nodes).map(|_| ReleaseNode { node_id: 4, release: NodeRelease::default() }).collect(),
+        };
+        let releases = vec![r(5, "failed", 0), r(4, "success", 1), r(3, "success", 0), r(2, "success", 1)];
+        assert_eq!(pick_release(&releases, None).unwrap().deployment_id, 2);
+        assert_eq!(pick_release(&releases, Some(4)).unwrap().deployment_id, 4);
+        assert!(pick_release(&releases, Some(3)).is_err());
+        assert!(pick_release(&releases, Some(9)).is_err());
+        assert!(pick_release(&releases[..2], None).is_err());
+    }
+}
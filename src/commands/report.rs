@@ -0,0 +1,216 @@
+use crate::commands::deploy::{load_ops_toml, resolve_app_name};
+use crate::commands::ssh::SshSession;
+use crate::serve::stats::{self, Sample};
+use crate::{api, config};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Which samples belong to the app: compose labels, or `{compose_project}-{svc}-{deployment_id}` names
+struct Scope {
+    compose_project: String,
+    services: Vec<String>,
+}
+
+impl Scope {
+    fn service_of(&self, s: &Sample) -> Option<String> {
+        if !s.service.is_empty() {
+            return (s.project == self.compose_project && self.wants(&s.service)).then(|| s.service.clone());
+        }
+        let rest = s.container.strip_prefix(&format!("{}-", self.compose_project))?;
+        let (svc, id) = rest.rsplit_once('-')?;
+        (id.chars().all(|c| c.is_ascii_digit()) && self.wants(svc)).then(|| svc.to_string())
+    }
+
+    /// An app without a services list covers the whole compose project
+    fn wants(&self, svc: &str) -> bool {
+        self.services.is_empty() || self.services.iter().any(|s| s == svc)
+    }
+}
+
+/// Average/max usage of one service on one node (`node` is None for all nodes together)
+#[derive(Serialize, Debug, PartialEq)]
+struct UsageRow {
+    service: String,
+    node: Option<i64>,
+    samples: usize,
+    cpu_avg: f64,
+    cpu_max: f64,
+    mem_avg_mb: f64,
+    mem_max_mb: f64,
+}
+
+/// CPU percent, memory MB
+type Point = (f64, f64);
+
+/// Replicas sampled at the same minute are summed first, so the figures are per service, not per container
+fn aggregate(samples: &[(i64, Sample)], scope: &Scope) -> Vec<UsageRow> {
+    // (service, node, ts) → (cpu, mem)
+    let mut points: BTreeMap<(String, i64, u64), Point> = BTreeMap::new();
+    for (node, s) in samples {
+        if let Some(svc) = scope.service_of(s) {
+            let p = points.entry((svc, *node, s.ts)).or_default();
+            p.0 += s.cpu_percent;
+            p.1 += s.mem_mb;
+        }
+    }
+
+    // Per node, then the service across all nodes (summed per minute)
+    let mut per_node: BTreeMap<(String, Option<i64>), Vec<Point>> = BTreeMap::new();
+    let mut across: BTreeMap<(String, u64), Point> = BTreeMap::new();
+    for ((svc, node, ts), (cpu, mem)) in points {
+        per_node.entry((svc.clone(), Some(node))).or_default().push((cpu, mem));
+        let a = across.entry((svc, ts)).or_default();
+        a.0 += cpu;
+        a.1 += mem;
+    }
+    let multi_node = per_node.keys().map(|(_, n)| n).collect::<std::collections::BTreeSet<_>>().len() > 1;
+    if multi_node {
+        for ((svc, _), point) in across {
+            per_node.entry((svc, None)).or_default().push(point);
+        }
+    }
+
+    per_node.into_iter()
+        .map(|((service, node), points)| {
+            let n = points.len() as f64;
+            UsageRow {
+                service,
+                node,
+                samples: points.len(),
+                cpu_avg: points.iter().map(|p| p.0).sum::<f64>() / n,
+                cpu_max: points.iter().map(|p| p.0).fold(0.0, f64::max),
+                mem_avg_mb: points.iter().map(|p| p.1).sum::<f64>() / n,
+                mem_max_mb: points.iter().map(|p| p.1).fold(0.0, f64::max),
+            }
+        })
+        .collect()
+}
+
+fn node_label(node: Option<i64>) -> String {
+    node.map(|id| id.to_string()).unwrap_or_else(|| "all".into())
+}
+
+fn to_csv(rows: &[UsageRow]) -> String {
+    let mut out = String::from("service,node,samples,cpu_avg,cpu_max,mem_avg_mb,mem_max_mb\n");
+    for r in rows {
+        out.push_str(&format!(
+            "{},{},{},{:.2},{:.2},{:.1},{:.1}\n",
+            r.service, node_label(r.node), r.samples, r.cpu_avg, r.cpu_max, r.mem_avg_mb, r.mem_max_mb
+        ));
+    }
+    out
+}
+
+/// ops report usage: average/max CPU and memory per service and node from the serve stats history
+pub async fn handle_usage(file: String, app: Option<String>, range: String, format: String) -> Result<()> {
+    if !["table", "csv", "json"].contains(&format.as_str()) {
+        bail!("Unknown format '{}'. Expected table, csv or json", format);
+    }
+    let range = humantime::parse_duration(&range)
+        .with_context(|| format!("Invalid range '{}'. Expected e.g. 24h, 7d, 30d", range))?;
+
+    let config = load_ops_toml(&file)?;
+    let app = app.unwrap_or_else(|| resolve_app_name(&config));
+    let scope = Scope {
+        compose_project: config.compose_project().to_string(),
+        services: config.apps.iter().find(|a| a.name == app).map(|a| a.services.clone()).unwrap_or_default(),
+    };
+
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let targets = api::get_app_deploy_targets(&token, &config.project, &app).await
+        .context("Failed to get deploy targets")?
+        .targets;
+    if targets.is_empty() {
+        bail!("No nodes bound to {}.{}", app, config.project);
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let since = now.saturating_sub(range.as_secs());
+    let days: Vec<String> = (0..=range.as_secs() / 86_400 + 1)
+        .map(|d| stats::day_file(now.saturating_sub(d * 86_400)))
+        .filter(|f| *f >= stats::day_file(since))
+        .collect();
+    let read = format!("cd {} 2>/dev/null && cat {} 2>/dev/null; true", stats::STATS_DIR, days.join(" "));
+
+    // csv/json go to stdout untouched so they can be redirected to a file
+    if format == "table" {
+        o_step!("{} {}.{} over the last {} ({} node(s))", "📊 Usage:".cyan(), app.green(), config.project.green(),
+            humantime::format_duration(range), targets.len());
+    }
+
+    let mut samples = Vec::new();
+    for t in &targets {
+        let output = match SshSession::connect(&t.node_id.to_string()).await
+            .and_then(|session| session.exec_output(&read))
+        {
+            Ok(o) => o,
+            Err(e) => {
+                o_warn!("  {} node {} ({}) skipped: {}", "⚠".yellow(), t.node_id, t.ip_address, e);
+                continue;
+            }
+        };
+        samples.extend(String::from_utf8_lossy(&output).lines()
+            .filter_map(|l| serde_json::from_str::<Sample>(l).ok())
+            .filter(|s| s.ts >= since)
+            .map(|s| (t.node_id, s)));
+    }
+
+    let rows = aggregate(&samples, &scope);
+    if rows.is_empty() {
+        bail!("No usage samples for {}.{} in this range. `ops serve` records them once a minute; update it on the node if it's older.", app, config.project);
+    }
+
+    match format.as_str() {
+        "csv" => print!("{}", to_csv(&rows)),
+        "json" => println!("{}", serde_json::to_string_pretty(&rows)?),
+        _ => {
+            o_result!("\n  {:<20} {:<6} {:>9} {:>9} {:>11} {:>11}",
+                "SERVICE".bold(), "NODE".bold(), "CPU AVG".bold(), "CPU MAX".bold(), "MEM AVG".bold(), "MEM MAX".bold());
+            for r in &rows {
+                let line = format!("  {:<20} {:<6} {:>8.1}% {:>8.1}% {:>8.0} MB {:>8.0} MB",
+                    r.service, node_label(r.node), r.cpu_avg, r.cpu_max, r.mem_avg_mb, r.mem_max_mb);
+                if r.node.is_none() { o_result!("{}", line.bold()); } else { o_result!("{}", line); }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(ts: u64, container: &str, service: &str, cpu: f64, mem: f64) -> Sample {
+        let project = if service.is_empty() { "" } else { "redq" };
+        Sample { ts, container: container.into(), project: project.into(), service: service.into(), cpu_percent: cpu, mem_mb: mem }
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let scope = Scope { compose_project: "redq".into(), services: vec!["api".into()] };
+        let samples = vec![
+            // two deploy-id replicas on node 1 are summed per minute
+            (1, sample(60, "redq-api-41", "", 10.0, 100.0)),
+            (1, sample(60, "redq-api-42", "", 20.0, 100.0)),
+            (1, sample(120, "redq-api-42", "", 10.0, 300.0)),
+            (2, sample(60, "redq-api-1", "api", 40.0, 500.0)),
+            // other services and projects are ignored
+            (2, sample(60, "redq-db-1", "db", 90.0, 900.0)),
+            (2, sample(60, "shop-api-1", "", 90.0, 900.0)),
+        ];
+        let rows = aggregate(&samples, &scope);
+        assert_eq!(rows.len(), 3);
+
+        let all = &rows[0];
+        assert_eq!((all.node, all.samples, all.cpu_max, all.mem_max_mb), (None, 2, 70.0, 700.0));
+        let node1 = &rows[1];
+        assert_eq!((node1.node, node1.samples, node1.cpu_avg, node1.mem_avg_mb), (Some(1), 2, 20.0, 250.0));
+        assert_eq!(rows[2].node, Some(2));
+
+        assert!(to_csv(&rows).contains("\napi,1,2,20.00,30.00,250.0,300.0\n"));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::commands::deploy::{
+    compose_args, compose_base_args, load_ops_toml, replace_app_containers, resolve_targets, run_health_checks,
+    start_service_container, sync_env_files,
+};
+use crate::commands::ssh::SshSession;
+use crate::types::{AppDef, OpsToml};
+use anyhow::{bail, Result};
+use colored::Colorize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How one service's containers get restarted
+#[derive(Debug, PartialEq)]
+enum Restart {
+    /// Zero-downtime deploys run `<project>-<svc>-<deployment id>` containers outside compose.
+    /// (app, service): replaced by a new container, since `docker restart` keeps the old env
+    Containers(String, String),
+    /// Compose-managed: recreate so env_files and compose changes are picked up
+    Recreate(String),
+    /// `--scale`: new replicas of an app next to the old, health checked, then routes switched
+    Replicas(String),
+}
+
+/// What to restart for `service`, or for every [[apps]] service (all of compose without [[apps]])
+fn plan(config: &OpsToml, service: &Option<String>, app_filter: &Option<String>, scale: bool) -> Result<Vec<Restart>> {
+    if let Some(ref name) = app_filter {
+        if !config.apps.iter().any(|a| a.name == *name) {
+            bail!("App '{}' not found in ops.toml", name);
+        }
+    }
+    let apps: Vec<&AppDef> = config.apps.iter()
+        .filter(|a| app_filter.as_ref().is_none_or(|f| *f == a.name))
+        .filter(|a| service.as_ref().is_none_or(|s| a.services.contains(s)))
+        .collect();
+    if config.apps.is_empty() || (apps.is_empty() && service.is_some()) {
+        // Not part of an app: plain compose
+        return Ok(vec![Restart::Recreate(service.clone().unwrap_or_default())]);
+    }
+
+    let up = config.deploy.strategy.as_deref() == Some("up");
+    let mut plan = Vec::new();
+    for app in apps {
+        if scale {
+            if !up || app.port.is_none() {
+                bail!("--scale needs strategy = \"up\" and a port on app '{}'", app.name);
+            }
+            plan.push(Restart::Replicas(app.name.clone()));
+            continue;
+        }
+        for svc in app.services.iter().filter(|s| service.as_ref().is_none_or(|f| f == *s)) {
+            let item = if app.port.is_some() && !up {
+                Restart::Containers(app.name.clone(), svc.clone())
+            } else {
+                Restart::Recreate(svc.clone())
+            };
+            if !plan.contains(&item) {
+                plan.push(item);
+            }
+        }
+    }
+    Ok(plan)
+}
+
+fn restart_node(config: &OpsToml, session: &SshSession, plan: &[Restart]) -> Result<()> {
+    sync_env_files(config, session)?;
+    let compose = format!("cd {} && docker compose {}", config.deploy_path, compose_args(config));
+    for item in plan {
+        match item {
+            Restart::Containers(name, svc) => {
+                let Some(app) = config.apps.iter().find(|a| a.name == *name) else { continue };
+                o_step!("{} {}", "🔄 Replacing".cyan(), svc);
+                // A fresh suffix: the env file is regenerated and the old container removed once the new one is healthy
+                let restart_id = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+                let base = compose_base_args(config);
+                let compose_arg = if base.is_empty() { String::new() } else { format!(" {}", base) };
+                start_service_container(config, session, restart_id, app, svc, &compose_arg)?;
+            }
+            Restart::Recreate(svc) => {
+                o_step!("{} {}", "🔄 Recreating".cyan(), if svc.is_empty() { "all services" } else { svc });
+                session.exec(&format!("{} up -d --no-deps --force-recreate {}", compose, svc), None)?;
+            }
+            Restart::Replicas(name) => {
+                let Some(app) = config.apps.iter().find(|a| a.name == *name) else { continue };
+                o_step!("{} {}", "🔄 Replacing replicas of".cyan(), name);
+                replace_app_containers(config, session, app, &compose)?;
+            }
+        }
+    }
+    run_health_checks(config, session)
+}
+
+/// ops rolling-restart: restart services one node at a time, stopping at the first node that fails its health checks
+pub async fn handle_rolling_restart(file: String, service: Option<String>, app_filter: Option<String>, scale: bool) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    let plan = plan(&config, &service, &app_filter, scale)?;
+    let targets = resolve_targets(&config, &app_filter).await?;
+
+    let total = targets.len();
+    for (i, t) in targets.iter().enumerate() {
+        o_step!("\n{} [{}/{}] {} ({})", "🚀".cyan(), i + 1, total, t.domain.cyan(), t.region.as_deref().unwrap_or("?"));
+        let session = SshSession::connect(&t.node_id.to_string()).await?;
+        if let Err(e) = restart_node(&config, &session, &plan) {
+            let untouched: Vec<&str> = targets[i + 1..].iter().map(|t| t.domain.as_str()).collect();
+            if !untouched.is_empty() {
+                o_warn!("   {} Not restarted: {}", "⚠".yellow(), untouched.join(", "));
+            }
+            return Err(e.context(format!("Rolling restart stopped at {}", t.domain)));
+        }
+    }
+    o_result!("\n{} Restarted on {} node(s)", "✅".green(), total);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(strategy: Option<&str>) -> OpsToml {
+        let mut config: OpsToml = toml::from_str(r#"
+            project = "shop"
+            deploy_path = "/opt/shop"
+            [deploy]
+            [[apps]]
+            name = "api"
+            services = ["api", "worker"]
+            port = 8080
+            [[apps]]
+            name = "web"
+            services = ["web"]
+        "#).unwrap();
+        config.deploy.strategy = strategy.map(String::from);
+        config
+    }
+
+    #[test]
+    fn test_plan() {
+        let c = config(None);
+        assert_eq!(plan(&c, &None, &None, false).unwrap(), vec![
+            Restart::Containers("api".into(), "api".into()),
+            Restart::Containers("api".into(), "worker".into()),
+            Restart::Recreate("web".into()),
+        ]);
+        assert_eq!(plan(&c, &Some("redis".into()), &None, false).unwrap(), vec![Restart::Recreate("redis".into())]);
+        assert!(plan(&c, &None, &Some("api".into()), true).is_err());
+
+        let up = config(Some("up"));
+        assert_eq!(plan(&up, &Some("api".into()), &None, false).unwrap(), vec![Restart::Recreate("api".into())]);
+        assert_eq!(plan(&up, &None, &Some("api".into()), true).unwrap(), vec![Restart::Replicas("api".into())]);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::commands::common::resolve_deploy_target;
+use crate::commands::deploy::{compose_args, env_prefix, load_ops_toml};
+use crate::commands::ssh::{self, SshOptions};
+use crate::utils::shell_quote;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::io::IsTerminal;
+
+/// ops run <service> -- <cmd>: one-off `docker compose run --rm` on the deploy target.
+/// Exits with the task's exit code.
+pub async fn handle_run(
+    file: String,
+    service: String,
+    command: Vec<String>,
+    target: Option<String>,
+    env_vars: Vec<String>,
+    set_vars: Vec<String>,
+) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+
+    for kv in env_vars.iter().chain(&set_vars) {
+        if !kv.contains('=') {
+            bail!("Invalid KEY=VALUE: {}", kv);
+        }
+    }
+
+    // The node of the app that owns this service
+    let app = config.apps.iter()
+        .find(|a| a.services.contains(&service))
+        .map(|a| a.name.as_str());
+    let target = resolve_deploy_target(&config, app, target).await?;
+
+    // Interactive tasks (shells, REPLs) get a TTY; otherwise compose must not ask for one
+    let tty = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    let mut run_args = vec!["run".to_string(), "--rm".to_string()];
+    if !tty {
+        run_args.push("-T".into());
+    }
+    for kv in &env_vars {
+        run_args.push("-e".into());
+        run_args.push(shell_quote(kv));
+    }
+    run_args.push(shell_quote(&service));
+    run_args.extend(command.iter().map(|w| shell_quote(w)));
+
+    let remote_cmd = format!(
+        "cd {} && {}docker compose {} {}",
+        config.deploy_path,
+        env_prefix(&set_vars),
+        compose_args(&config),
+        run_args.join(" ")
+    );
+
+    o_step!("{} {} on {}", "▶".cyan(), command.join(" ").bold(), target.to_string().green());
+    o_debug!("   {}", remote_cmd);
+
+    let opts = SshOptions { tty, ..Default::default() };
+    let (mut cmd, _temp_key_file) = ssh::build_ssh_command_with(&target, &opts).await?;
+    cmd.arg(&remote_cmd);
+
+    let status = cmd.status().context("Failed to run ssh")?;
+    if !status.success() {
+        // Propagate the task's exit code (ssh passes it through)
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! `ops scan`: what the scanner detects in a project, and with `--write` the Dockerfile,
+//! .dockerignore and docker-compose.yml it would generate.
+
+use crate::scanner::{self, dockerfile, SourceInfo};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The generated files, in the order they are written
+fn render(project_name: &str, info: &SourceInfo) -> [(&'static str, String); 3] {
+    [
+        ("Dockerfile", dockerfile::render_dockerfile(info)),
+        (".dockerignore", dockerfile::render_dockerignore(info)),
+        ("docker-compose.yml", dockerfile::render_compose(project_name, info)),
+    ]
+}
+
+/// Write the generated files into `dir`; existing ones are kept unless `force`.
+/// Returns each file name with whether it was written.
+fn write_files(dir: &Path, project_name: &str, info: &SourceInfo, force: bool) -> Result<Vec<(&'static str, bool)>> {
+    let mut written = Vec::new();
+    for (name, content) in render(project_name, info) {
+        let path = dir.join(name);
+        if path.exists() && !force {
+            written.push((name, false));
+            continue;
+        }
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        written.push((name, true));
+    }
+    Ok(written)
+}
+
+fn print_info(info: &SourceInfo) {
+    let framework = match &info.version {
+        Some(v) => format!("{} {}", info.framework.display_name(), v),
+        None => info.framework.display_name().to_string(),
+    };
+    o_success!("  {} {} ({})", "✔".green(), framework.cyan().bold(), info.family);
+    o_detail!("    Port:     {}", info.port.to_string().yellow());
+    if let Some(pm) = &info.package_manager {
+        o_detail!("    Packages: {}{}", pm, if info.has_lockfile { "" } else { " (no lockfile)" });
+    }
+    o_detail!("    Install:  {}", info.install_cmd);
+    if let Some(build) = &info.build_cmd {
+        o_detail!("    Build:    {}", build);
+    }
+    o_detail!("    Start:    {}", info.start_cmd);
+    if let Some(health) = &info.health_path {
+        o_detail!("    Health:   {}", health);
+    }
+    for note in &info.notes {
+        o_warn!("  {} {}", "ℹ".yellow(), note);
+    }
+}
+
+/// ops scan
+pub fn handle_scan(path: String, write: bool, force: bool) -> Result<()> {
+    let dir = PathBuf::from(&path);
+    if !dir.is_dir() {
+        bail!("{} is not a directory", path);
+    }
+    o_step!("{} {}", "Scanning".cyan(), dir.display());
+
+    let Some(info) = scanner::scan(&dir)? else {
+        o_warn!("  {} No framework detected", "!".yellow());
+        return Ok(());
+    };
+    print_info(&info);
+
+    let project_name = fs::canonicalize(&dir).ok()
+        .and_then(|d| d.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "app".into());
+    o_detail!();
+    if !write {
+        o_detail!("{}", format!("Run `ops scan {} --write` to generate Dockerfile, .dockerignore and docker-compose.yml.", path).dimmed());
+        return Ok(());
+    }
+    for (name, written) in write_files(&dir, &project_name, &info, force)? {
+        if written {
+            o_success!("  {} {}", "✔".green(), name);
+        } else {
+            o_detail!("  {} {} already exists, skipping (--force to overwrite)", "→".dimmed(), name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_files_keeps_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"shop\"\nversion = \"0.1.0\"\n").unwrap();
+        fs::write(dir.path().join("Dockerfile"), "FROM scratch\n").unwrap();
+        let info = scanner::scan(dir.path()).unwrap().unwrap();
+
+        let written = write_files(dir.path(), "shop", &info, false).unwrap();
+        assert_eq!(written, vec![("Dockerfile", false), (".dockerignore", true), ("docker-compose.yml", true)]);
+        assert_eq!(fs::read_to_string(dir.path().join("Dockerfile")).unwrap(), "FROM scratch\n");
+
+        write_files(dir.path(), "shop", &info, true).unwrap();
+        assert!(fs::read_to_string(dir.path().join("Dockerfile")).unwrap().contains("cargo build --release"));
+    }
+}
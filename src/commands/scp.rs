@@ -1,19 +1,18 @@
 // src/commands/scp.rs
 
-use crate::{api, config, utils};
-use crate::utils::Target;
+use crate::commands::common::resolve_target;
+use crate::commands::sftp;
+use crate::commands::ssh::SshSession;
 use anyhow::{Context, Result};
 use std::process::Command;
 use colored::Colorize;
-use std::io::Write;
-use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
 /// Push files to a target
 /// Supports both Node ID (e.g., "12345:/root/") and App target (e.g., "api.RedQ:/root/")
-pub async fn handle_push(source: String, target_str: String) -> Result<()> {
+pub async fn handle_push(source: String, target_str: Option<String>, interactive: bool) -> Result<()> {
     // 1. 解析目标
-    let target = utils::parse_target(&target_str)?;
+    let target = resolve_target(target_str, interactive).await?;
     let full_domain = target.domain();
 
     // 默认为 /root/，如果用户未指定路径
@@ -22,39 +21,19 @@ pub async fn handle_push(source: String, target_str: String) -> Result<()> {
 
     o_step!("Pushing {} to {}...", source.cyan(), scp_destination.cyan());
 
-    // 2. 获取凭证
-    let cfg = config::load_config().context("Config error")?;
-    let token = cfg.token.context("Please run `ops login` first.")?;
-
-    o_debug!("Fetching access credentials...");
-
-    // Get CI key based on target type
-    let private_key = match &target {
-        Target::NodeId { id, .. } => {
-            let key_resp = api::get_node_ci_key(&token, *id).await?;
-            key_resp.private_key
-        }
-        Target::AppTarget { app, project, .. } => {
-            let key_resp = api::get_app_ci_key(&token, project, app).await?;
-            key_resp.private_key
-        }
-    };
-
-    // 3. 准备私钥文件
-    let mut temp_key_file = tempfile::NamedTempFile::new()?;
-    writeln!(temp_key_file, "{}", private_key)?;
-
-    let meta = temp_key_file.as_file().metadata()?;
-    let mut perms = meta.permissions();
-    perms.set_mode(0o600);
-    temp_key_file.as_file().set_permissions(perms)?;
-
-    let key_path = temp_key_file.path().to_str().unwrap();
+    // 2. 建立会话（内置客户端走 SFTP，OPS_SSH=openssh 时用 scp）
+    let session = SshSession::connect(&target.to_string()).await?;
+    if session.is_native() {
+        sftp::upload(&session, Path::new(&source), &remote_path)
+            .with_context(|| format!("Failed to upload {}", source))?;
+        o_success!("{}", "✔ File transfer successful.".green());
+        return Ok(());
+    }
 
-    // 4. 执行 scp
+    // 3. 执行 scp
     // scp -i key -o StrictHostKeyChecking=no -r source root@domain:path
     let mut cmd = Command::new("scp");
-    cmd.arg("-i").arg(key_path)
+    cmd.arg("-i").arg(session.key_path())
        .arg("-o").arg("StrictHostKeyChecking=no")
        .arg("-o").arg("UserKnownHostsFile=/dev/null")
        .arg("-o").arg("LogLevel=ERROR");
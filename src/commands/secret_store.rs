@@ -0,0 +1,206 @@
+//! `ops secrets set/get/list/rm`: per-app key/value secrets stored encrypted by the backend
+//! instead of env files on a laptop. `ops deploy` writes them into `<deploy_path>/.env` on
+//! each node, over whatever the synced env files put there.
+
+use crate::commands::deploy::{load_ops_toml, resolve_app_name};
+use crate::commands::login::read_stdin_secret;
+use crate::commands::ssh::SshSession;
+use crate::error::{ApiErrorCode, OpsError};
+use crate::types::OpsToml;
+use crate::{api, config, output, prompt};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+
+/// Where deploys put secrets, relative to deploy_path; compose reads it for `${VAR}`
+pub const SECRETS_ENV_FILE: &str = ".env";
+
+fn validate_key(key: &str) -> Result<()> {
+    let mut chars = key.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        bail!("Invalid secret name '{}': use letters, digits and _, not starting with a digit", key);
+    }
+    Ok(())
+}
+
+/// (app, project) from `app.project`, or from ops.toml
+fn resolve_target(file: &str, target: Option<String>) -> Result<(String, String)> {
+    match target {
+        Some(t) => {
+            let (app, project) = t.split_once('.')
+                .with_context(|| format!("Invalid target '{}'. Expected app.project (e.g., api.RedQ)", t))?;
+            Ok((app.to_string(), project.to_string()))
+        }
+        None => {
+            let config = load_ops_toml(file)?;
+            Ok((resolve_app_name(&config), config.project))
+        }
+    }
+}
+
+fn login_token() -> Result<String> {
+    config::load_config().context("Config error")?.token.context("Please run `ops login` first.")
+}
+
+/// A .env value compose reads back unchanged: bare when safe, else single or double quoted
+fn quote_env_value(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./:@+,=".contains(c);
+    if value.chars().all(safe) {
+        return value.to_string();
+    }
+    if !value.contains('\'') && !value.contains('\n') {
+        return format!("'{}'", value);
+    }
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('$', "$$");
+    format!("\"{}\"", escaped)
+}
+
+/// `current` with each secret set: lines defining it are replaced in place, new ones appended
+fn merge_env(current: &str, secrets: &BTreeMap<String, String>) -> String {
+    let mut written = Vec::new();
+    let mut lines: Vec<String> = Vec::new();
+    for line in current.lines() {
+        let name = line.trim_start().trim_start_matches("export ").split('=').next().unwrap_or("").trim();
+        match secrets.get(name) {
+            Some(value) if line.contains('=') => {
+                if !written.contains(&name) {
+                    lines.push(format!("{}={}", name, quote_env_value(value)));
+                    written.push(name);
+                }
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    for (name, value) in secrets {
+        if !written.contains(&name.as_str()) {
+            lines.push(format!("{}={}", name, quote_env_value(value)));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// During a deploy: write the secrets of every deployed app into `<deploy_path>/.env` on the node (mode 0600).
+/// The apps share that file, so a name set differently by two of them keeps the later app's value.
+pub async fn inject(config: &OpsToml, session: &SshSession, apps: &[String]) -> Result<()> {
+    let token = login_token()?;
+    let mut secrets: BTreeMap<String, String> = BTreeMap::new();
+    for app in apps {
+        let values = match api::secret_values(&token, &config.project, app).await {
+            Ok(resp) => resp.values,
+            // Nothing stored for this app
+            Err(e) if matches!(OpsError::api_code(&e), Some(ApiErrorCode::NotFound)) => continue,
+            Err(e) => return Err(e.context(format!("Failed to fetch secrets of {}", app))),
+        };
+        for (key, value) in values {
+            if secrets.get(&key).is_some_and(|v| *v != value) {
+                o_warn!("   {} Secret {} differs between apps; using {}'s", "⚠".yellow(), key, app);
+            }
+            secrets.insert(key, value);
+        }
+    }
+    if secrets.is_empty() {
+        return Ok(());
+    }
+
+    let path = format!("{}/{}", config.deploy_path, SECRETS_ENV_FILE);
+    o_step!("\n{}", "🔐 Writing secrets...".cyan());
+    let current = session.exec_output(&format!("cat {} 2>/dev/null || true", path))?;
+    let merged = merge_env(&String::from_utf8_lossy(&current), &secrets);
+    session.exec(&format!("mkdir -p {0} && umask 077 && cat > {1}.ops && chmod 600 {1}.ops && mv {1}.ops {1}", config.deploy_path, path), Some(&merged))?;
+    o_detail!("   ✔ {} secret(s) → {}", secrets.len(), path);
+    Ok(())
+}
+
+/// ops secrets set KEY=VALUE (or KEY, with the value from a prompt or stdin)
+pub async fn handle_set(file: String, target: Option<String>, assignment: String) -> Result<()> {
+    let (key, value) = match assignment.split_once('=') {
+        Some((k, v)) => (k.to_string(), v.to_string()),
+        None if std::io::stdin().is_terminal() => {
+            let value = rpassword::prompt_password(format!("Value for {}: ", assignment))?;
+            (assignment, value)
+        }
+        None => {
+            let value = read_stdin_secret("value")?;
+            (assignment, value)
+        }
+    };
+    validate_key(&key)?;
+    let (app, project) = resolve_target(&file, target)?;
+
+    api::set_secret(&login_token()?, &project, &app, &key, &value).await.context("Failed to set secret")?;
+    o_success!("{} {} on {}.{}", "✔ Set".green(), key.cyan(), app, project);
+    o_detail!("   {}", "Applied on the next `ops deploy`.".dimmed());
+    Ok(())
+}
+
+/// ops secrets get: the value alone on stdout, for scripts
+pub async fn handle_get(file: String, target: Option<String>, key: String) -> Result<()> {
+    validate_key(&key)?;
+    let (app, project) = resolve_target(&file, target)?;
+    let secret = api::get_secret(&login_token()?, &project, &app, &key).await.context("Failed to get secret")?;
+    o_result!("{}", secret.value);
+    Ok(())
+}
+
+/// ops secrets list: names and when they changed, never values
+pub async fn handle_list(file: String, target: Option<String>) -> Result<()> {
+    let (app, project) = resolve_target(&file, target)?;
+    let resp = api::list_secrets(&login_token()?, &project, &app).await.context("Failed to list secrets")?;
+    if output::json() {
+        return output::print_json(&resp);
+    }
+
+    o_step!("{} {}.{}\n", "🔐 Secrets:".cyan(), app.green(), project.green());
+    if resp.secrets.is_empty() {
+        o_detail!("  {}", "No secrets".dimmed());
+        return Ok(());
+    }
+    let width = resp.secrets.iter().map(|s| s.key.len()).max().unwrap_or(0);
+    for s in &resp.secrets {
+        o_detail!("  {:width$}  {}  {}", s.key.cyan(), s.updated_at.dimmed(),
+            s.updated_by.as_deref().unwrap_or("").dimmed(), width = width);
+    }
+    Ok(())
+}
+
+/// ops secrets rm
+pub async fn handle_rm(file: String, target: Option<String>, key: String, interactive: bool) -> Result<()> {
+    validate_key(&key)?;
+    let (app, project) = resolve_target(&file, target)?;
+    if !prompt::confirm_yes(&format!("Delete secret {} from {}.{}?", key, app, project), interactive)? {
+        o_warn!("Aborted.");
+        return Ok(());
+    }
+    api::delete_secret(&login_token()?, &project, &app, &key).await.context("Failed to delete secret")?;
+    o_success!("{} {}", "✔ Deleted".green(), key.cyan());
+    o_detail!("   {}", format!("Nodes keep it in {} until it is removed there.", SECRETS_ENV_FILE).dimmed());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_merge_and_quoting() {
+        assert!(validate_key("DATABASE_URL").is_ok());
+        assert!(validate_key("_x1").is_ok());
+        assert!(validate_key("1KEY").is_err());
+        assert!(validate_key("MY-KEY").is_err());
+
+        assert_eq!(quote_env_value("postgres://u:p@db:5432/app"), "postgres://u:p@db:5432/app");
+        assert_eq!(quote_env_value("a b#c$d"), "'a b#c$d'");
+        assert_eq!(quote_env_value("it's\n$x"), "\"it's\\n$$x\"");
+
+        let secrets = BTreeMap::from([
+            ("API_KEY".to_string(), "new".to_string()),
+            ("DB_PASSWORD".to_string(), "s3cret".to_string()),
+        ]);
+        let merged = merge_env("# app\nPORT=3000\nexport API_KEY=old\nAPI_KEY=older\n", &secrets);
+        assert_eq!(merged, "# app\nPORT=3000\nAPI_KEY=new\nDB_PASSWORD=s3cret\n");
+        assert_eq!(merge_env("", &secrets), "API_KEY=new\nDB_PASSWORD=s3cret\n");
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use axum::{
-    extract::{Query, State},
+    body::Bytes,
+    extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
     response::{
         sse::{Event, Sse},
@@ -11,19 +12,32 @@ use axum::{
 };
 use colored::Colorize;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::convert::Infallible;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt;
 use tower_http::cors::CorsLayer;
 
-use crate::serve::{actions, containers, logs, metrics};
+use crate::serve::{actions, alerts, containers, crashes, gc, logs, metrics, previews, stats, watch};
+use crate::serve::config::{self, Overrides, ServeConfig, Settings};
+use crate::serve::queue::{DeployList, DeployQueue, FinishRequest, HeldPlace, HoldRequest};
+use crate::node_state::{self, SERVE_CONFIG_FILE};
+use crate::privilege::Privilege;
 use crate::update;
 
-#[derive(Clone)]
 struct AppState {
-    token: String,
-    compose_dirs: Vec<String>,
+    /// serve.toml with the flags on top; replaced on SIGHUP
+    settings: RwLock<Arc<Settings>>,
+    /// `/deploy` requests, one at a time per deploy path
+    queue: Arc<DeployQueue>,
+}
+
+impl AppState {
+    fn settings(&self) -> Arc<Settings> {
+        self.settings.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
 }
 
 fn check_auth(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
@@ -32,41 +46,59 @@ fn check_auth(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
         .and_then(|v| v.to_str().ok())
         .unwrap_or("");
 
-    if auth == format!("Bearer {}", state.token) {
+    if auth == format!("Bearer {}", state.settings().token) {
         Ok(())
     } else {
         Err(StatusCode::UNAUTHORIZED)
     }
 }
 
-pub async fn handle_serve(token: String, port: u16, compose_dir: String) -> Result<()> {
-    let compose_dirs: Vec<String> = compose_dir.split(',').map(|s| s.trim().to_string()).collect();
-    for dir in &compose_dirs {
+/// serve.toml (or `path`) with the flags on top, checked
+fn load_settings(path: Option<&str>, overrides: &Overrides) -> Result<Settings> {
+    let settings = ServeConfig::load(path)?.resolve(overrides)?;
+    for dir in &settings.compose_dirs {
         if !std::path::Path::new(dir).exists() {
             anyhow::bail!("Compose directory does not exist: {}", dir);
         }
     }
+    Ok(settings)
+}
 
+pub async fn handle_serve(config_path: Option<String>, overrides: Overrides) -> Result<()> {
+    let settings = load_settings(config_path.as_deref(), &overrides)?;
+    let port = settings.port;
     let state = Arc::new(AppState {
-        token,
-        compose_dirs,
+        settings: RwLock::new(Arc::new(settings)),
+        queue: Arc::new(DeployQueue::default()),
     });
 
     let app = Router::new()
         .route("/health", get(health))
         .route("/containers", get(get_containers))
+        .route("/containers/stats", get(get_container_stats))
         .route("/logs", get(get_logs))
         .route("/logs/stream", get(stream_logs))
         .route("/metrics", get(get_metrics))
+        .route("/metrics/prometheus", get(get_prometheus_metrics))
         .route("/restart", post(restart))
         .route("/stop", post(stop))
         .route("/start", post(start))
         .route("/deploy", post(deploy))
+        .route("/deploys", get(list_deploys).post(hold_deploy))
+        .route("/deploys/:id/start", post(start_held_deploy))
+        .route("/deploys/:id/finish", post(finish_held_deploy))
         .route("/checkupdate", get(check_update))
+        .route("/webhooks/:name", post(webhook))
+        .route("/gc", post(collect_garbage))
         .layer(CorsLayer::permissive())
-        .with_state(state);
+        .with_state(state.clone());
 
-    let addr = format!("0.0.0.0:{}", port);
+    // [::] is dual-stack on Linux (IPv4 arrives v4-mapped); hosts with IPv6 disabled fall back to 0.0.0.0
+    let listener = match tokio::net::TcpListener::bind(format!("[::]:{}", port)).await {
+        Ok(l) => l,
+        Err(_) => tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?,
+    };
+    let addr = listener.local_addr()?.to_string();
     o_success!(
         "{} ops serve listening on {}",
         "✓".green(),
@@ -92,13 +124,120 @@ pub async fn handle_serve(token: String, port: u16, compose_dir: String) -> Resu
         }
     });
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    // Record per-container CPU/memory for `ops report usage`
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(stats::SAMPLE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Ok(Err(e)) = tokio::task::spawn_blocking(stats::sample_and_record).await {
+                eprintln!("stats error: {}", e);
+            }
+        }
+    });
+
+    // Remove branch previews past their TTL or whose branch is gone
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(previews::CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Ok(Err(e)) = tokio::task::spawn_blocking(previews::enforce).await {
+                eprintln!("preview cleanup error: {}", e);
+            }
+        }
+    });
+
+    // Tell the backend which previews are still here, so ones lost with a node get expired
+    let beating = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(previews::HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = previews::heartbeat(&beating.settings().token).await {
+                eprintln!("preview heartbeat error: {:#}", e);
+            }
+        }
+    });
+
+    // `systemctl reload ops-serve`: re-read serve.toml, keeping the old settings if it's broken
+    let reloading = state.clone();
+    tokio::spawn(async move {
+        let Ok(mut hangup) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) else { return };
+        while hangup.recv().await.is_some() {
+            match load_settings(config_path.as_deref(), &overrides) {
+                Ok(settings) => {
+                    if settings.port != port {
+                        eprintln!("port {} takes effect after a restart", settings.port);
+                    }
+                    *reloading.settings.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(settings);
+                    eprintln!("settings reloaded");
+                }
+                Err(e) => eprintln!("reload failed, keeping the current settings: {:#}", e),
+            }
+        }
+    });
+
+    // [[watch]]: deploy when the remote of a path's branch moves
+    let watching = state.clone();
+    tokio::spawn(async move {
+        // Last check and failures in a row, per path
+        let mut checked: HashMap<String, (Instant, u32)> = HashMap::new();
+        let mut interval = tokio::time::interval(watch::TICK);
+        loop {
+            interval.tick().await;
+            for w in &watching.settings().watch {
+                let failures = checked.get(&w.deploy_path).map_or(0, |c| c.1);
+                if checked.get(&w.deploy_path).is_some_and(|(t, _)| t.elapsed() < watch::backoff(w.interval(), failures)) {
+                    continue;
+                }
+                let path = w.deploy_path.clone();
+                let failed = match tokio::task::spawn_blocking(move || watch::remote_moved(&path)).await {
+                    Ok(Ok(true)) => {
+                        eprintln!("{} moved upstream, deploying", w.deploy_path);
+                        match deploy_and_watch(&watching, &w.deploy_path, None, None).await {
+                            (Ok(r), _) if r.success => false,
+                            (Ok(r), _) => { eprintln!("deploy of {} failed: {}", w.deploy_path, r.message); true }
+                            (Err(e), _) => { eprintln!("deploy error for {}: {}", w.deploy_path, e); true }
+                        }
+                    }
+                    Ok(Ok(false)) => false,
+                    Ok(Err(e)) => { eprintln!("watch error for {}: {:#}", w.deploy_path, e); true }
+                    Err(_) => true,
+                };
+                let failures = if failed { failures + 1 } else { 0 };
+                if failed {
+                    eprintln!("next check of {} in {}s", w.deploy_path, watch::backoff(w.interval(), failures).as_secs());
+                }
+                checked.insert(w.deploy_path.clone(), (Instant::now(), failures));
+            }
+        }
+    });
+
+    // [[alerts]]: report node metrics that stay above their threshold
+    let alerting = state.clone();
+    tokio::spawn(async move {
+        let mut tracker = alerts::Tracker::default();
+        let mut interval = tokio::time::interval(alerts::CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let settings = alerting.settings();
+            if settings.alerts.is_empty() {
+                continue;
+            }
+            let Ok(Ok(m)) = tokio::task::spawn_blocking(metrics::collect_metrics).await else { continue };
+            for reason in tracker.observe(&settings.alerts, &m, Instant::now()) {
+                eprintln!("alert: {}", reason);
+                alerts::report(&settings.token, reason).await;
+            }
+        }
+    });
+
     axum::serve(listener, app).await?;
 
     Ok(())
 }
 
-pub async fn handle_install(token: String, port: u16, compose_dir: String, _domain: Option<String>) -> Result<()> {
+pub async fn handle_install(config_path: Option<String>, overrides: Overrides, domain: Option<String>) -> Result<()> {
+    let settings = ServeConfig::load(config_path.as_deref())?.resolve(&overrides)?;
     let exe_path = std::env::current_exe()?;
     let service = format!(
         r#"[Unit]
@@ -108,7 +247,8 @@ Wants=docker.service
 
 [Service]
 Type=simple
-ExecStart={} serve --token {} --port {} --compose-dir {}
+ExecStart={} serve --config {}
+ExecReload=/bin/kill -HUP $MAINPID
 Restart=always
 RestartSec=5
 
@@ -116,11 +256,18 @@ RestartSec=5
 WantedBy=multi-user.target
 "#,
         exe_path.display(),
-        token,
-        port,
-        compose_dir
+        SERVE_CONFIG_FILE,
     );
 
+    node_state::write_serve_env(&settings.token)?;
+    o_success!("{} Wrote {} (0600)", "✓".green(), node_state::SERVE_ENV_FILE);
+    let tls_domains: Vec<String> = domain.into_iter().collect();
+    if config::write_default(&Privilege::Root, settings.port, &settings.compose_dirs, &tls_domains, settings.public_metrics)? {
+        o_success!("{} Wrote {}", "✓".green(), SERVE_CONFIG_FILE);
+    } else {
+        o_detail!("  Kept the existing {}; edit it to change the settings", SERVE_CONFIG_FILE);
+    }
+
     let service_path = "/etc/systemd/system/ops-serve.service";
     std::fs::write(service_path, service)?;
 
@@ -145,7 +292,9 @@ WantedBy=multi-user.target
 
     // Configure Caddy reverse proxy if /etc/caddy exists
     if std::path::Path::new("/etc/caddy").exists() {
-        crate::commands::init::configure_caddy(port)?;
+        let mut state = crate::node_state::NodeState::load()?.unwrap_or_default();
+        crate::commands::init::configure_caddy(settings.port, &tls_domains, false, &mut state)?;
+        state.save()?;
     }
 
     Ok(())
@@ -161,7 +310,7 @@ async fn health(
     if check_auth(&state, &headers).is_ok() {
         let mut all_running = true;
         let mut container_count = 0;
-        for dir in &state.compose_dirs {
+        for dir in &state.settings().compose_dirs {
             if let Ok(containers) = containers::list_containers(dir) {
                 for c in &containers {
                     container_count += 1;
@@ -196,7 +345,7 @@ async fn get_containers(
 ) -> Result<impl IntoResponse, StatusCode> {
     check_auth(&state, &headers)?;
     let mut all = Vec::new();
-    for dir in &state.compose_dirs {
+    for dir in &state.settings().compose_dirs {
         match containers::list_containers(dir) {
             Ok(list) => all.extend(list),
             Err(e) => eprintln!("containers error for {}: {}", dir, e),
@@ -205,6 +354,21 @@ async fn get_containers(
     Ok(Json(serde_json::json!({ "containers": all })))
 }
 
+async fn get_container_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_auth(&state, &headers)?;
+    match tokio::task::spawn_blocking(stats::current).await {
+        Ok(Ok(list)) => Ok(Json(serde_json::json!({ "containers": list }))),
+        Ok(Err(e)) => {
+            eprintln!("container stats error: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
 #[derive(Deserialize)]
 struct LogsQuery {
     service: String,
@@ -225,7 +389,7 @@ async fn get_logs(
     // Try each compose dir; for "all", merge from all dirs
     if q.service == "all" {
         let mut combined = String::new();
-        for dir in &state.compose_dirs {
+        for dir in &state.settings().compose_dirs {
             if let Ok(output) = logs::get_logs(dir, "all", q.lines) {
                 combined.push_str(&output);
             }
@@ -233,7 +397,7 @@ async fn get_logs(
         return Ok(Json(serde_json::json!({ "logs": combined })));
     }
     // For specific service, find which dir contains it
-    for dir in &state.compose_dirs {
+    for dir in &state.settings().compose_dirs {
         if let Ok(services) = containers::list_services(dir) {
             if services.iter().any(|s| s == &q.service) {
                 match logs::get_logs(dir, &q.service, q.lines) {
@@ -266,10 +430,10 @@ async fn stream_logs(
 
     // Find which dir contains this service, or use first dir for "all"
     let target_dir = if service == "all" {
-        state.compose_dirs[0].clone()
+        state.settings().compose_dirs[0].clone()
     } else {
         let mut found = None;
-        for dir in &state.compose_dirs {
+        for dir in &state.settings().compose_dirs {
             if let Ok(services) = containers::list_services(dir) {
                 if services.iter().any(|s| s == &service) {
                     found = Some(dir.clone());
@@ -277,7 +441,7 @@ async fn stream_logs(
                 }
             }
         }
-        found.unwrap_or_else(|| state.compose_dirs[0].clone())
+        found.unwrap_or_else(|| state.settings().compose_dirs[0].clone())
     };
 
     tokio::spawn(async move {
@@ -305,13 +469,40 @@ async fn get_metrics(
     }
 }
 
+/// Node and container metrics for Prometheus; token-protected unless --public-metrics
+async fn get_prometheus_metrics(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !state.settings().public_metrics {
+        check_auth(&state, &headers)?;
+    }
+    let body = tokio::task::spawn_blocking(|| {
+        let system = metrics::collect_metrics()?;
+        // Node metrics are still worth serving when docker is down
+        let containers = stats::current().unwrap_or_else(|e| {
+            eprintln!("container stats error: {}", e);
+            Vec::new()
+        });
+        anyhow::Ok(metrics::render_prometheus(&system, &containers))
+    }).await;
+    match body {
+        Ok(Ok(text)) => Ok(([("content-type", "text/plain; version=0.0.4")], text)),
+        Ok(Err(e)) => {
+            eprintln!("metrics error: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
 #[derive(Deserialize)]
 struct ServiceQuery {
     service: String,
 }
 
 fn find_compose_dir(state: &AppState, service: &str) -> Option<String> {
-    for dir in &state.compose_dirs {
+    for dir in &state.settings().compose_dirs {
         if let Ok(services) = containers::list_services(dir) {
             if services.iter().any(|s| s == service) {
                 return Some(dir.clone());
@@ -367,6 +558,54 @@ struct DeployRequest {
     branch: Option<String>,
 }
 
+/// Run `run` once the deploys of `dir` queued before it are finished. It runs on the blocking
+/// pool, so /health and the queue endpoints keep answering during a long deploy.
+async fn queued_deploy(
+    state: &Arc<AppState>,
+    dir: &str,
+    run: impl FnOnce() -> anyhow::Result<actions::ActionResult> + Send + 'static,
+) -> (anyhow::Result<actions::ActionResult>, usize) {
+    // Leaves the queue if this request is dropped while it waits
+    let (ticket, waiting) = state.queue.ticket(dir);
+    if waiting > 0 {
+        eprintln!("deploy of {} queued behind {}", dir, waiting);
+    }
+    ticket.wait_turn().await;
+    // From here the deploy finishes its entry even if the request goes away
+    let id = ticket.detach();
+    let queue_state = state.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let result = run();
+        match &result {
+            Ok(r) => queue_state.queue.finish(id, r.success, &r.message),
+            Err(e) => queue_state.queue.finish(id, false, &e.to_string()),
+        }
+        result
+    }).await;
+    let result = result.unwrap_or_else(|e| {
+        state.queue.finish(id, false, &e.to_string());
+        Err(anyhow::anyhow!("deploy of {} panicked: {}", dir, e))
+    });
+    (result, waiting)
+}
+
+/// Deploy `deploy_path` through the queue, then watch it for crashing containers
+async fn deploy_and_watch(
+    state: &Arc<AppState>,
+    deploy_path: &str,
+    git_repo: Option<&str>,
+    branch: Option<&str>,
+) -> (anyhow::Result<actions::ActionResult>, usize) {
+    let (path, git_repo, branch) = (deploy_path.to_string(), git_repo.map(String::from), branch.map(String::from));
+    let outcome = queued_deploy(state, deploy_path, move || actions::deploy_with_repo(&path, git_repo.as_deref(), branch.as_deref())).await;
+    if let (Ok(r), _) = &outcome {
+        if r.success {
+            tokio::spawn(crashes::watch_after_deploy(deploy_path.to_string(), state.settings().token.clone()));
+        }
+    }
+    outcome
+}
+
 async fn deploy(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -378,16 +617,15 @@ async fn deploy(
 
     // If deploy_path is provided, deploy that specific app
     if let Some(deploy_path) = req.deploy_path {
-        match actions::deploy_with_repo(
-            &deploy_path,
-            req.git_repo.as_deref(),
-            req.branch.as_deref(),
-        ) {
-            Ok(r) => return Ok(Json(serde_json::json!({
-                "success": r.success,
-                "message": r.message
-            }))),
-            Err(e) => {
+        match deploy_and_watch(&state, &deploy_path, req.git_repo.as_deref(), req.branch.as_deref()).await {
+            (Ok(r), waited_behind) => {
+                return Ok(Json(serde_json::json!({
+                    "success": r.success,
+                    "message": r.message,
+                    "waited_behind": waited_behind
+                })));
+            }
+            (Err(e), _) => {
                 eprintln!("deploy error for {}: {}", deploy_path, e);
                 return Err(StatusCode::INTERNAL_SERVER_ERROR);
             }
@@ -396,9 +634,15 @@ async fn deploy(
 
     // Otherwise deploy all configured compose_dirs (legacy behavior)
     let mut results = Vec::new();
-    for dir in &state.compose_dirs {
-        match actions::deploy(dir) {
-            Ok(r) => results.push(r),
+    for dir in &state.settings().compose_dirs {
+        let owned = dir.clone();
+        match queued_deploy(&state, dir, move || actions::deploy(&owned)).await.0 {
+            Ok(r) => {
+                if r.success {
+                    tokio::spawn(crashes::watch_after_deploy(dir.clone(), state.settings().token.clone()));
+                }
+                results.push(r);
+            }
             Err(e) => { eprintln!("deploy error for {}: {}", dir, e); }
         }
     }
@@ -410,6 +654,105 @@ async fn deploy(
     })))
 }
 
+/// `[[webhooks]]` in serve.toml: a signed push deploys the hook's path. Answers right away, since
+/// GitHub and GitLab give up after about ten seconds; `/deploys` shows how it went.
+async fn webhook(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, StatusCode> {
+    let settings = state.settings();
+    let hook = settings.webhooks.iter().find(|w| w.name == name).ok_or(StatusCode::NOT_FOUND)?;
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let secret = hook.secret.as_deref().unwrap_or_default();
+    if !config::verify_webhook(secret, header("x-hub-signature-256"), header("x-gitlab-token"), &body) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    if let Some(branch) = &hook.branch {
+        let pushed = serde_json::from_slice::<serde_json::Value>(&body).ok()
+            .and_then(|v| v["ref"].as_str().map(String::from));
+        if pushed.as_deref() != Some(format!("refs/heads/{}", branch).as_str()) {
+            return Ok((StatusCode::OK, Json(serde_json::json!({
+                "deploying": false,
+                "message": format!("Not a push to {}", branch)
+            }))));
+        }
+    }
+
+    let deploy_path = hook.deploy_path.clone();
+    let (path, branch) = (deploy_path.clone(), hook.branch.clone());
+    tokio::spawn(async move {
+        if let (Err(e), _) = deploy_and_watch(&state, &path, None, branch.as_deref()).await {
+            eprintln!("deploy error for {}: {}", path, e);
+        }
+    });
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({
+        "deploying": true,
+        "deploy_path": deploy_path
+    }))))
+}
+
+#[derive(Deserialize)]
+struct DeploysQuery {
+    deploy_path: Option<String>,
+}
+
+/// The deploy queue: queued, running and recently finished `/deploy` runs
+async fn list_deploys(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(q): Query<DeploysQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_auth(&state, &headers)?;
+    Ok(Json(DeployList { deploys: state.queue.snapshot(q.deploy_path.as_deref()) }))
+}
+
+/// `ops deploy`: hold a place in the queue of a deploy path for a deploy the CLI runs itself
+async fn hold_deploy(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<HoldRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_auth(&state, &headers)?;
+    Ok(Json(state.queue.hold(&req.deploy_path)))
+}
+
+/// Poll of a held place: renews it, and marks it running once nothing is ahead
+async fn start_held_deploy(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_auth(&state, &headers)?;
+    let ahead = state.queue.try_start(id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(HeldPlace { id, ahead }))
+}
+
+async fn finish_held_deploy(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+    Json(req): Json<FinishRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_auth(&state, &headers)?;
+    state.queue.finish(id, req.success, &req.message);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `ops gc`: remove the routes of stale tunnels and the stale previews on this node
+async fn collect_garbage(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<gc::GcRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    check_auth(&state, &headers)?;
+    let result = tokio::task::spawn_blocking(move || gc::collect(&req))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(result))
+}
+
 async fn check_update(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -1,4 +1,5 @@
 use crate::{api, config};
+use crate::error::{ApiErrorCode, OpsError};
 use anyhow::Result;
 use colored::Colorize;
 
@@ -21,7 +22,7 @@ pub async fn handle_server_whoami() -> Result<()> {
         },
         Err(e) => {
             // 对404情况的更优雅处理
-            if e.to_string().contains("unbound") {
+            if matches!(OpsError::api_code(&e), Some(ApiErrorCode::ServerUnbound | ApiErrorCode::NotFound)) {
                  o_result!("{} {}", "Server IP:".bold(), "Unknown (request failed)");
                  o_detail!("{} {}", "Status:".bold(), "unbound".yellow());
                  o_detail!("This server is not currently bound to any project in ops.autos.");
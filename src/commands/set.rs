@@ -16,14 +16,9 @@ pub async fn handle_set(
     weight: Option<u8>,
     interactive: bool,
 ) -> Result<()> {
-    let target = utils::parse_target(&target_str)?;
-
-    let (app_name, project_name) = match &target {
-        utils::Target::AppTarget { app, project, .. } => (app.clone(), project.clone()),
-        utils::Target::NodeId { .. } => {
-            anyhow::bail!("ops set requires app.project format (e.g., api.RedQ), not a node ID");
-        }
-    };
+    let (app_name, project_name) = utils::parse_target(&target_str)?
+        .app_project()
+        .context("ops set requires app.project format")?;
 
     let cfg = config::load_config().context("Could not load config. Please log in with `ops login`.")?;
     let token = cfg.token.context("You are not logged in. Please run `ops login` first.")?;
@@ -81,13 +76,6 @@ pub async fn handle_set(
         return Ok(());
     }
 
-    let mut force_reset = false;
-    o_detail!();
-    o_detail!("{}", "Tip: If you are having trouble with 'ops ssh' (invalid key format), please choose Yes below.".dimmed());
-    if prompt::confirm_no("Do you want to regenerate CI/CD SSH keys for this environment?", interactive)? {
-        force_reset = true;
-    }
-
     o_step!("\nChecking local SSH key...");
     let pubkey = ssh::get_default_pubkey()?;
     o_success!("{}", "✔ SSH key ready.".green());
@@ -99,7 +87,6 @@ pub async fn handle_set(
         &project_name,
         &app_name,
         &pubkey,
-        force_reset,
         region.as_deref(),
         zone.as_deref(),
         hostname.as_deref(),
@@ -117,10 +104,7 @@ pub async fn handle_set(
     o_step!("Adding CI public key to ~/.ssh/authorized_keys...");
     ssh::add_to_authorized_keys(&res.ci_ssh_public_key)?;
 
-    if force_reset {
-        o_success!("{}", "✔ CI keys have been regenerated. Please try `ops ssh` again.".green());
-    }
-
     o_success!("{}", "✔ Setup complete!".green());
+    o_detail!("{}", format!("Tip: if `ops ssh` reports an invalid key, regenerate it with `ops keys rotate {}.{}`.", app_name, project_name).dimmed());
     Ok(())
 }
@@ -0,0 +1,394 @@
+//! File transfer over the built-in SSH client's SFTP channel, for machines without rsync or
+//! scp (minimal CI images). `push` mirrors `SshSession::rsync_push` (`rsync -az --delete`
+//! with the same excludes); `upload` mirrors `scp -r` for `ops push`; `copy` mirrors `rsync -a`
+//! (no excludes, no deletes) for `[[sync]]`.
+
+use crate::commands::ssh::SshSession;
+use crate::opsignore::IgnoreRules;
+use crate::utils::shell_quote;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Directories and files a push always leaves out, and never deletes on the node
+const EXCLUDED_DIRS: [&str; 4] = ["target", "node_modules", ".git", ".ops"];
+const EXCLUDED_FILE: &str = ".env";
+/// Paths per `rm` / `mkdir` / `ln` command
+const BATCH: usize = 200;
+
+/// A file or symlink, keyed by its '/'-separated path relative to the sync root
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    size: u64,
+    mtime: u64,
+    mode: u32,
+    /// Symlink target
+    link: Option<String>,
+}
+
+/// A tree on one side: files and symlinks, plus directories
+#[derive(Debug, Default)]
+struct Tree {
+    files: BTreeMap<String, Entry>,
+    dirs: BTreeSet<String>,
+}
+
+/// What a push does on the node, in order
+#[derive(Debug, Default, PartialEq)]
+struct Plan {
+    delete: Vec<String>,
+    rmdirs: Vec<String>,
+    mkdirs: Vec<String>,
+    upload: Vec<String>,
+    links: Vec<(String, String)>,
+}
+
+/// Left out of a push: the built-in excludes, then `.opsignore` (and `.gitignore`) rules
+fn excluded(ignore: &IgnoreRules, rel_path: &str, name: &str, is_dir: bool) -> bool {
+    (if is_dir { EXCLUDED_DIRS.contains(&name) } else { name == EXCLUDED_FILE }) || ignore.is_ignored(rel_path, is_dir)
+}
+
+fn join(rel: &str, name: &str) -> String {
+    if rel.is_empty() { name.to_string() } else { format!("{}/{}", rel, name) }
+}
+
+fn local_entry(path: &Path, meta: &fs::Metadata) -> Result<Entry> {
+    let link = if meta.file_type().is_symlink() {
+        Some(fs::read_link(path)?.to_string_lossy().to_string())
+    } else {
+        None
+    };
+    Ok(Entry {
+        size: meta.len(),
+        mtime: meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_secs()).unwrap_or(0),
+        mode: meta.permissions().mode() & 0o7777,
+        link,
+    })
+}
+
+/// `ignore`: what a push leaves out; None walks everything
+fn walk_local(dir: &Path, rel: &str, ignore: Option<&IgnoreRules>, tree: &mut Tree) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let meta = fs::symlink_metadata(entry.path())?;
+        let is_dir = meta.is_dir();
+        let rel_path = join(rel, &name);
+        if ignore.is_some_and(|i| excluded(i, &rel_path, &name, is_dir)) {
+            continue;
+        }
+        if is_dir {
+            tree.dirs.insert(rel_path.clone());
+            walk_local(&entry.path(), &rel_path, ignore, tree)?;
+        } else {
+            tree.files.insert(rel_path, local_entry(&entry.path(), &meta)?);
+        }
+    }
+    Ok(())
+}
+
+/// Local tree under `root`; with `include`, only those files and directories (rsync's whitelist)
+fn local_tree(root: &Path, include: &[&String], ignore: &IgnoreRules) -> Result<Tree> {
+    let mut tree = Tree::default();
+    if include.is_empty() {
+        walk_local(root, "", Some(ignore), &mut tree)?;
+        return Ok(tree);
+    }
+    for entry in include {
+        let rel = entry.trim_end_matches('/').trim_start_matches("./");
+        let path = root.join(rel);
+        let Ok(meta) = fs::symlink_metadata(&path) else { continue };
+        for (i, _) in rel.match_indices('/') {
+            tree.dirs.insert(rel[..i].to_string());
+        }
+        if meta.is_dir() {
+            tree.dirs.insert(rel.to_string());
+            walk_local(&path, rel, Some(ignore), &mut tree)?;
+        } else {
+            tree.files.insert(rel.to_string(), local_entry(&path, &meta)?);
+        }
+    }
+    Ok(tree)
+}
+
+fn walk_remote(sftp: &ssh2::Sftp, dir: &str, rel: &str, ignore: Option<&IgnoreRules>, tree: &mut Tree) -> Result<()> {
+    // A directory that doesn't exist yet has nothing to compare against
+    let Ok(entries) = sftp.readdir(Path::new(dir)) else { return Ok(()) };
+    for (path, stat) in entries {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if name.is_empty() || name == "." || name == ".." {
+            continue;
+        }
+        let is_dir = stat.is_dir();
+        let rel_path = join(rel, &name);
+        // Excluded paths are never deleted, as with rsync
+        if ignore.is_some_and(|i| excluded(i, &rel_path, &name, is_dir)) {
+            continue;
+        }
+        if is_dir {
+            tree.dirs.insert(rel_path.clone());
+            walk_remote(sftp, &format!("{}/{}", dir, name), &rel_path, ignore, tree)?;
+        } else {
+            let link = if stat.file_type().is_symlink() {
+                sftp.readlink(&path).ok().map(|t| t.to_string_lossy().to_string())
+            } else {
+                None
+            };
+            tree.files.insert(rel_path, Entry {
+                size: stat.size.unwrap_or(0),
+                mtime: stat.mtime.unwrap_or(0),
+                mode: stat.perm.unwrap_or(0) & 0o7777,
+                link,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Remote tree for the same scope as `local_tree`
+fn remote_tree(sftp: &ssh2::Sftp, remote_root: &str, include: &[&String], ignore: &IgnoreRules) -> Result<Tree> {
+    let mut tree = Tree::default();
+    if include.is_empty() {
+        walk_remote(sftp, remote_root, "", Some(ignore), &mut tree)?;
+        return Ok(tree);
+    }
+    for entry in include {
+        let rel = entry.trim_end_matches('/').trim_start_matches("./");
+        let path = format!("{}/{}", remote_root, rel);
+        let Ok(stat) = sftp.lstat(Path::new(&path)) else { continue };
+        if stat.is_dir() {
+            tree.dirs.insert(rel.to_string());
+            walk_remote(sftp, &path, rel, Some(ignore), &mut tree)?;
+        } else {
+            let link = stat.file_type().is_symlink()
+                .then(|| sftp.readlink(Path::new(&path)).ok().map(|t| t.to_string_lossy().to_string()))
+                .flatten();
+            tree.files.insert(rel.to_string(), Entry { size: stat.size.unwrap_or(0), mtime: stat.mtime.unwrap_or(0), mode: 0, link });
+        }
+    }
+    Ok(tree)
+}
+
+/// rsync's quick check: a file is sent when size or mtime differ, a symlink when its target does
+fn plan(local: &Tree, remote: &Tree) -> Plan {
+    let mut plan = Plan::default();
+    for (path, r) in &remote.files {
+        match local.files.get(path) {
+            None => plan.delete.push(path.clone()),
+            // A symlink becoming a file (or the reverse) is replaced, never written through
+            Some(l) if l.link.is_some() != r.link.is_some() => plan.delete.push(path.clone()),
+            _ => {}
+        }
+    }
+    // Deepest first, so parents are empty by the time they're removed
+    plan.rmdirs = remote.dirs.iter().rev().filter(|d| !local.dirs.contains(*d)).cloned().collect();
+    plan.mkdirs = local.dirs.iter().filter(|d| !remote.dirs.contains(*d)).cloned().collect();
+    for (path, l) in &local.files {
+        let r = remote.files.get(path).filter(|r| r.link.is_some() == l.link.is_some());
+        match &l.link {
+            Some(target) => if r.is_none_or(|r| r.link.as_ref() != Some(target)) {
+                plan.links.push((path.clone(), target.clone()));
+            },
+            None => if r.is_none_or(|r| r.size != l.size || r.mtime != l.mtime) {
+                plan.upload.push(path.clone());
+            },
+        }
+    }
+    plan
+}
+
+/// Run `cmd` with `paths` (quoted, under `remote_root`) appended, in batches
+fn exec_batched(session: &SshSession, cmd: &str, remote_root: &str, paths: &[String]) -> Result<()> {
+    for chunk in paths.chunks(BATCH) {
+        let args: Vec<String> = chunk.iter().map(|p| shell_quote(&format!("{}/{}", remote_root, p))).collect();
+        session.exec(&format!("{} {}", cmd, args.join(" ")), None)?;
+    }
+    Ok(())
+}
+
+fn upload_file(sftp: &ssh2::Sftp, local: &Path, remote: &str, entry: &Entry) -> Result<()> {
+    let mut source = fs::File::open(local).with_context(|| format!("Failed to open {}", local.display()))?;
+    let mut dest = sftp.create(Path::new(remote)).with_context(|| format!("Failed to create {}", remote))?;
+    io::copy(&mut source, &mut dest).with_context(|| format!("Failed to upload {}", local.display()))?;
+    drop(dest);
+    sftp.setstat(Path::new(remote), ssh2::FileStat {
+        size: None, uid: None, gid: None,
+        perm: Some(entry.mode),
+        atime: Some(entry.mtime),
+        mtime: Some(entry.mtime),
+    }).with_context(|| format!("Failed to set mode and mtime on {}", remote))?;
+    Ok(())
+}
+
+/// Mirror `root` (or its `include` entries) to `remote_root`, deleting what is gone locally
+fn sync_dir(session: &SshSession, sftp: &ssh2::Sftp, root: &Path, remote_root: &str, include: &[&String], gitignore: bool) -> Result<()> {
+    let ignore = IgnoreRules::load(root, gitignore)?;
+    let local = local_tree(root, include, &ignore)?;
+    let remote = remote_tree(sftp, remote_root, include, &ignore)?;
+    let plan = plan(&local, &remote);
+    apply(session, sftp, root, remote_root, &local, &plan)?;
+    o_detail!("   {} {} file(s) sent, {} deleted {}", "✔".green(), plan.upload.len() + plan.links.len(), plan.delete.len(), "(sftp)".dimmed());
+    Ok(())
+}
+
+fn apply(session: &SshSession, sftp: &ssh2::Sftp, root: &Path, remote_root: &str, local: &Tree, plan: &Plan) -> Result<()> {
+    session.exec(&format!("mkdir -p {}", shell_quote(remote_root)), None)?;
+    exec_batched(session, "rm -f", remote_root, &plan.delete)?;
+    // Directories still holding excluded files (.env, node_modules) stay, as with rsync
+    for chunk in plan.rmdirs.chunks(BATCH) {
+        let args: Vec<String> = chunk.iter().map(|p| shell_quote(&format!("{}/{}", remote_root, p))).collect();
+        session.exec(&format!("rmdir {} 2>/dev/null || true", args.join(" ")), None)?;
+    }
+    exec_batched(session, "mkdir -p", remote_root, &plan.mkdirs)?;
+    for path in &plan.upload {
+        o_debug!("sftp ↑ {}", path);
+        upload_file(sftp, &root.join(path), &format!("{}/{}", remote_root, path), &local.files[path])?;
+    }
+    for (path, target) in &plan.links {
+        session.exec(&format!("ln -sfn {} {}", shell_quote(target), shell_quote(&format!("{}/{}", remote_root, path))), None)?;
+    }
+    Ok(())
+}
+
+/// `plan` without deletions: only what a file ↔ symlink swap has to remove first
+fn additive(mut plan: Plan, local: &Tree) -> Plan {
+    plan.delete.retain(|p| local.files.contains_key(p));
+    plan.rmdirs.clear();
+    plan
+}
+
+/// `rsync_push` without rsync: the current directory to `remote_path`, `../` entries to
+/// `remote_path/<dir name>`
+pub fn push(session: &SshSession, remote_path: &str, include: &[String], gitignore: bool) -> Result<()> {
+    let sftp = session.sftp().context("SFTP needs the built-in SSH client (unset OPS_SSH)")??;
+    let (external, local): (Vec<_>, Vec<_>) = include.iter().partition(|e| e.starts_with("../"));
+
+    sync_dir(session, &sftp, Path::new("."), remote_path, &local, gitignore)?;
+    for entry in &external {
+        let dir_name = Path::new(entry.as_str())
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| entry.trim_end_matches('/').to_string());
+        sync_dir(session, &sftp, Path::new(entry.trim_end_matches('/')), &format!("{}/{}", remote_path, dir_name), &[], gitignore)
+            .with_context(|| format!("Failed to sync external path: {}", entry))?;
+    }
+    Ok(())
+}
+
+/// `rsync -a source/ remote/` without rsync, for `[[sync]]`: the contents of directory `source`
+/// into `remote` (or file `source` to `remote`), sending only files whose size or mtime differ.
+/// Nothing is excluded and nothing is deleted on the node. Returns the number of files sent.
+pub fn copy(session: &SshSession, source: &Path, remote: &str) -> Result<usize> {
+    let sftp = session.sftp().context("SFTP needs the built-in SSH client (unset OPS_SSH)")??;
+    let meta = fs::symlink_metadata(source).with_context(|| format!("Cannot read {}", source.display()))?;
+    if !meta.is_dir() {
+        let parent = Path::new(remote).parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+        if !parent.is_empty() {
+            session.exec(&format!("mkdir -p {}", shell_quote(&parent)), None)?;
+        }
+        upload_file(&sftp, source, remote, &local_entry(source, &meta)?)?;
+        return Ok(1);
+    }
+    let mut local = Tree::default();
+    walk_local(source, "", None, &mut local)?;
+    let mut remote_files = Tree::default();
+    walk_remote(&sftp, remote, "", None, &mut remote_files)?;
+    let plan = additive(plan(&local, &remote_files), &local);
+    apply(session, &sftp, source, remote, &local, &plan)?;
+    Ok(plan.upload.len() + plan.links.len())
+}
+
+/// `scp -r source remote`: into `remote` when it is a directory (or ends with /), else to it
+pub fn upload(session: &SshSession, source: &Path, remote: &str) -> Result<()> {
+    let sftp = session.sftp().context("SFTP needs the built-in SSH client (unset OPS_SSH)")??;
+    let name = source.file_name().map(|n| n.to_string_lossy().to_string()).context("Source has no file name")?;
+    let into_dir = remote.ends_with('/') || sftp.stat(Path::new(remote)).is_ok_and(|s| s.is_dir());
+    let dest = if into_dir { format!("{}/{}", remote.trim_end_matches('/'), name) } else { remote.to_string() };
+
+    let meta = fs::symlink_metadata(source).with_context(|| format!("Cannot read {}", source.display()))?;
+    if !meta.is_dir() {
+        return upload_file(&sftp, source, &dest, &local_entry(source, &meta)?);
+    }
+    let mut tree = Tree::default();
+    walk_local(source, "", None, &mut tree)?;
+    session.exec(&format!("mkdir -p {}", shell_quote(&dest)), None)?;
+    exec_batched(session, "mkdir -p", &dest, &tree.dirs.iter().cloned().collect::<Vec<_>>())?;
+    for (path, entry) in &tree.files {
+        let remote_file = format!("{}/{}", dest, path);
+        match &entry.link {
+            Some(target) => session.exec(&format!("ln -sfn {} {}", shell_quote(target), shell_quote(&remote_file)), None)?,
+            None => upload_file(&sftp, &PathBuf::from(source).join(path), &remote_file, entry)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(size: u64, mtime: u64) -> Entry {
+        Entry { size, mtime, mode: 0o644, link: None }
+    }
+
+    #[test]
+    fn test_local_tree_and_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("node_modules/x")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("src/nested/lib.rs"), "").unwrap();
+        fs::write(root.join(".env"), "SECRET=1").unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+        std::os::unix::fs::symlink("Cargo.toml", root.join("link")).unwrap();
+
+        let none = IgnoreRules::default();
+        let tree = local_tree(root, &[], &none).unwrap();
+        assert_eq!(tree.files.keys().collect::<Vec<_>>(), vec!["Cargo.toml", "link", "src/main.rs", "src/nested/lib.rs"]);
+        assert_eq!(tree.dirs.iter().collect::<Vec<_>>(), vec!["src", "src/nested"]);
+        assert_eq!(tree.files["link"].link.as_deref(), Some("Cargo.toml"));
+
+        let include = ["src/nested/".to_string(), "Cargo.toml".to_string()];
+        let tree = local_tree(root, &include.iter().collect::<Vec<_>>(), &none).unwrap();
+        let tree_ignoring = local_tree(root, &[], &IgnoreRules::parse("nested/\n")).unwrap();
+        assert_eq!(tree_ignoring.files.keys().collect::<Vec<_>>(), vec!["Cargo.toml", "link", "src/main.rs"]);
+        assert_eq!(tree.files.keys().collect::<Vec<_>>(), vec!["Cargo.toml", "src/nested/lib.rs"]);
+        assert_eq!(tree.dirs.iter().collect::<Vec<_>>(), vec!["src", "src/nested"]);
+
+        let local = Tree {
+            files: BTreeMap::from([
+                ("a.txt".into(), file(3, 100)),
+                ("src/b.rs".into(), file(5, 200)),
+                ("current".into(), Entry { link: Some("releases/2".into()), ..file(0, 0) }),
+            ]),
+            dirs: BTreeSet::from(["src".into()]),
+        };
+        let remote = Tree {
+            files: BTreeMap::from([
+                ("a.txt".into(), file(3, 100)),
+                ("src/b.rs".into(), file(5, 150)),
+                ("old/c.rs".into(), file(1, 1)),
+                ("current".into(), Entry { link: Some("releases/1".into()), ..file(0, 0) }),
+            ]),
+            dirs: BTreeSet::from(["old".into()]),
+        };
+        assert_eq!(plan(&local, &remote), Plan {
+            delete: vec!["old/c.rs".into()],
+            rmdirs: vec!["old".into()],
+            mkdirs: vec!["src".into()],
+            upload: vec!["src/b.rs".into()],
+            links: vec![("current".into(), "releases/2".into())],
+        });
+
+        let additive = additive(plan(&local, &remote), &local);
+        assert!(additive.delete.is_empty() && additive.rmdirs.is_empty());
+        assert_eq!(additive.upload, vec!["src/b.rs".to_string()]);
+    }
+}
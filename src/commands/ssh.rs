@@ -1,17 +1,37 @@
+use crate::commands::sftp;
+use crate::opsignore::IgnoreRules;
 use crate::{api, config, utils};
 use crate::utils::Target;
 use anyhow::{Context, Result};
 use std::process::{Command, Stdio};
 use colored::Colorize;
-use std::io::Write;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Options for `ops ssh` sessions beyond the default root shell
+#[derive(Debug, Default, Clone)]
+pub struct SshOptions {
+    /// Remote user (default: root)
+    pub user: Option<String>,
+    /// Local port forwards, passed as `-L <spec>`
+    pub local_forwards: Vec<String>,
+    /// Remote port forwards, passed as `-R <spec>`
+    pub remote_forwards: Vec<String>,
+    /// Force pseudo-terminal allocation (`-t`)
+    pub tty: bool,
+}
 
 /// 这是一个通用的 SSH 命令构建器，其他模块可以复用
-/// Supports both Node ID (e.g., "12345") and App target (e.g., "api.RedQ")
-pub async fn build_ssh_command(target_str: &str) -> Result<(Command, tempfile::NamedTempFile)> {
-    let target = utils::parse_target(target_str)?;
+/// Supports both Node ID (e.g., "12345") and App target (e.g., "api.RedQ"), with a custom
+/// user, port forwards and TTY allocation
+pub async fn build_ssh_command_with(target: &Target, opts: &SshOptions) -> Result<(Command, tempfile::NamedTempFile)> {
     let full_domain = target.domain();
-    let ssh_target = format!("root@{}", full_domain);
+    let user = opts.user.as_deref().unwrap_or("root");
+    let ssh_target = format!("{}@{}", user, full_domain);
 
     let cfg = config::load_config().context("Config error")?;
     let token = cfg.token.context("Please run `ops login` first.")?;
@@ -19,7 +39,7 @@ pub async fn build_ssh_command(target_str: &str) -> Result<(Command, tempfile::N
     o_debug!("Fetching access credentials...");
 
     // Get CI key based on target type
-    let private_key = match &target {
+    let private_key = match target {
         Target::NodeId { id, .. } => {
             let key_resp = api::get_node_ci_key(&token, *id).await?;
             key_resp.private_key
@@ -44,43 +64,208 @@ pub async fn build_ssh_command(target_str: &str) -> Result<(Command, tempfile::N
     cmd.arg("-i").arg(key_path)
        .arg("-o").arg("StrictHostKeyChecking=no")
        .arg("-o").arg("UserKnownHostsFile=/dev/null")
-       .arg("-o").arg("LogLevel=ERROR")
-       .arg(&ssh_target);
+       .arg("-o").arg("LogLevel=ERROR");
+
+    if opts.tty {
+        cmd.arg("-t");
+    }
+    for spec in &opts.local_forwards {
+        cmd.arg("-L").arg(spec);
+    }
+    for spec in &opts.remote_forwards {
+        cmd.arg("-R").arg(spec);
+    }
+
+    cmd.arg(&ssh_target);
 
     Ok((cmd, temp_key_file))
 }
 
+/// How an SshSession reaches its node
+enum Transport {
+    /// libssh2 in this process, so deploys don't need the ssh, scp or rsync binaries
+    Native {
+        session: ssh2::Session,
+        /// Held around each pass of `run_native`, which switches the session to non-blocking for it.
+        /// Commands on other threads interleave between passes, each over its own channel.
+        lock: Mutex<()>,
+    },
+    /// The OpenSSH client, with `OPS_SSH=openssh`
+    OpenSsh,
+}
+
+/// `OPS_SSH=openssh` makes sessions run the ssh binary instead of the built-in client
+pub const SSH_BACKEND_ENV: &str = "OPS_SSH";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+fn use_openssh() -> bool {
+    std::env::var(SSH_BACKEND_ENV).is_ok_and(|v| v == "openssh")
+}
+
+/// Whether `name` is an executable on PATH
+pub fn has_binary(name: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(name).is_file()))
+}
+
+/// Connect to `domain:22` and log in as root with `private_key`
+fn connect_native(domain: &str, private_key: &str) -> Result<ssh2::Session> {
+    let addrs: Vec<SocketAddr> = (domain, 22).to_socket_addrs()
+        .with_context(|| format!("Cannot resolve {}", domain))?
+        .collect();
+    let tcp = addrs.iter()
+        .find_map(|addr| TcpStream::connect_timeout(addr, CONNECT_TIMEOUT).ok())
+        .with_context(|| format!("Cannot connect to {}:22", domain))?;
+    let mut session = ssh2::Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.handshake().with_context(|| format!("SSH handshake with {} failed", domain))?;
+    session.userauth_pubkey_memory("root", None, private_key, None)
+        .with_context(|| format!("{} rejected the CI key", domain))?;
+    session.set_keepalive(true, 15);
+    Ok(session)
+}
+
+fn would_block(e: &std::io::Error) -> bool {
+    e.kind() == std::io::ErrorKind::WouldBlock
+}
+
+/// Copy what `stream` has ready into `sink`; whether anything was copied
+fn drain(stream: &mut ssh2::Stream, sink: &mut dyn Write, buf: &mut [u8]) -> Result<bool> {
+    match stream.read(buf) {
+        Ok(0) => Ok(false),
+        Ok(n) => { sink.write_all(&buf[..n])?; Ok(true) }
+        Err(e) if would_block(&e) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Run `command` over a new channel: `input` goes to its stdin, its stdout and stderr to `out` and `err`.
+/// Everything is pumped in one non-blocking loop, so neither side can stall on a full window.
+/// `lock` is only held for each pass over the channel, so other threads can run commands alongside.
+/// Returns the exit code.
+fn run_native(session: &ssh2::Session, lock: &Mutex<()>, command: &str, mut input: Option<&mut dyn Read>, out: &mut dyn Write, err: &mut dyn Write) -> Result<i32> {
+    let locked = || lock.lock().unwrap_or_else(|e| e.into_inner());
+    let (mut channel, mut stdout, mut stderr) = {
+        let _guard = locked();
+        let mut channel = session.channel_session()?;
+        channel.exec(command)?;
+        let (stdout, stderr) = (channel.stream(0), channel.stderr());
+        (channel, stdout, stderr)
+    };
+
+    let mut buf = vec![0u8; 32 * 1024];
+    let mut pending: Vec<u8> = Vec::new();
+    let mut input_done = input.is_none();
+    let mut eof_sent = false;
+    loop {
+        if !input_done && pending.is_empty() {
+            match input.as_mut().map(|i| i.read(&mut buf)) {
+                Some(Ok(0)) | None => input_done = true,
+                Some(Ok(n)) => pending.extend_from_slice(&buf[..n]),
+                Some(Err(e)) => return Err(e.into()),
+            }
+        }
+
+        let guard = locked();
+        session.set_blocking(false);
+        let pass = (|| -> Result<(bool, bool)> {
+            let mut progressed = false;
+            if !pending.is_empty() {
+                match channel.write(&pending) {
+                    Ok(n) => { pending.drain(..n); progressed = true; }
+                    Err(e) if would_block(&e) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            if input_done && pending.is_empty() && !eof_sent {
+                match channel.send_eof().map_err(std::io::Error::from) {
+                    Ok(()) => eof_sent = true,
+                    Err(e) if would_block(&e) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            progressed |= drain(&mut stdout, &mut *out, &mut buf)?;
+            progressed |= drain(&mut stderr, &mut *err, &mut buf)?;
+            Ok((progressed, channel.eof() && !progressed))
+        })();
+        session.set_blocking(true);
+        drop(guard);
+
+        let (progressed, done) = pass?;
+        if done {
+            break;
+        }
+        if !progressed {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+    out.flush()?;
+    let _guard = locked();
+    channel.wait_close()?;
+    Ok(channel.exit_status()?)
+}
+
+/// Exit status as the ssh binary's status would print it
+fn check_exit(code: i32) -> Result<()> {
+    if code != 0 {
+        return Err(anyhow::anyhow!("Remote command failed with status: exit status: {}", code));
+    }
+    Ok(())
+}
+
+/// Exclude `root`'s `.opsignore` (and `.gitignore`) matches from an rsync, as root-anchored paths
+/// in an `--exclude-from` file. The file must outlive the command.
+fn add_ignore_rules(cmd: &mut Command, root: &Path, gitignore: bool) -> Result<Option<tempfile::NamedTempFile>> {
+    let paths = IgnoreRules::load(root, gitignore)?.ignored_paths(root)?;
+    if paths.is_empty() {
+        return Ok(None);
+    }
+    o_debug!("{} path(s) ignored in {}", paths.len(), root.display());
+    let mut file = tempfile::NamedTempFile::new()?;
+    for path in &paths {
+        writeln!(file, "/{}", path)?;
+    }
+    file.flush()?;
+    cmd.arg("--exclude-from").arg(file.path());
+    Ok(Some(file))
+}
+
 /// 可复用的 SSH 会话，一次 fetch CI key，多次执行命令
 pub struct SshSession {
     ssh_target: String,
     _temp_key_file: tempfile::NamedTempFile,
     key_path: String,
     target_str: String,
+    transport: Transport,
+}
+
+/// The target's CI private key, from the backend
+pub async fn fetch_ci_key(target: &Target) -> Result<String> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    o_debug!("Fetching access credentials...");
+
+    let key_resp = match target {
+        Target::NodeId { id, .. } => api::get_node_ci_key(&token, *id).await?,
+        Target::AppTarget { app, project, .. } => api::get_app_ci_key(&token, project, app).await?,
+    };
+    Ok(key_resp.private_key)
 }
 
 impl SshSession {
     /// 建立会话：fetch CI key，创建 temp key file（只做一次）
     pub async fn connect(target_str: &str) -> Result<Self> {
         let target = utils::parse_target(target_str)?;
-        let full_domain = target.domain();
-        let ssh_target = format!("root@{}", full_domain);
-
-        let cfg = config::load_config().context("Config error")?;
-        let token = cfg.token.context("Please run `ops login` first.")?;
-
-        o_debug!("Fetching access credentials...");
+        let private_key = fetch_ci_key(&target).await?;
+        Self::with_key(target_str, &private_key)
+    }
 
-        let private_key = match &target {
-            Target::NodeId { id, .. } => {
-                let key_resp = api::get_node_ci_key(&token, *id).await?;
-                key_resp.private_key
-            }
-            Target::AppTarget { app, project, .. } => {
-                let key_resp = api::get_app_ci_key(&token, project, app).await?;
-                key_resp.private_key
-            }
-        };
+    /// 使用给定的私钥建立会话（不经 API），供密钥轮换时用旧 key 登录
+    pub fn with_key(target_str: &str, private_key: &str) -> Result<Self> {
+        let target = utils::parse_target(target_str)?;
+        let ssh_target = format!("root@{}", target.domain());
 
+        // Kept for rsync and OPS_SSH=openssh, which run the ssh binary
         let mut temp_key_file = tempfile::NamedTempFile::new()?;
         writeln!(temp_key_file, "{}", private_key)?;
         let meta = temp_key_file.as_file().metadata()?;
@@ -90,9 +275,15 @@ impl SshSession {
 
         let key_path = temp_key_file.path().to_str().unwrap().to_string();
 
+        let transport = if use_openssh() {
+            Transport::OpenSsh
+        } else {
+            Transport::Native { session: connect_native(&target.domain(), private_key)?, lock: Mutex::new(()) }
+        };
+
         o_debug!("{}", "✔ Access granted via CI Key.".green());
 
-        Ok(Self { ssh_target, _temp_key_file: temp_key_file, key_path, target_str: target_str.to_string() })
+        Ok(Self { ssh_target, _temp_key_file: temp_key_file, key_path, target_str: target_str.to_string(), transport })
     }
 
     /// 返回原始 target 标识符（如 "4" 或 "api.RedQ"），供 scp/rsync 使用
@@ -100,6 +291,35 @@ impl SshSession {
         &self.target_str
     }
 
+    /// Private key file, for the scp and rsync binaries
+    pub fn key_path(&self) -> &str {
+        &self.key_path
+    }
+
+    /// Whether commands run over the built-in client rather than the ssh binary
+    pub fn is_native(&self) -> bool {
+        matches!(self.transport, Transport::Native { .. })
+    }
+
+    /// SFTP channel of the built-in client; None with OPS_SSH=openssh
+    pub fn sftp(&self) -> Option<Result<ssh2::Sftp>> {
+        match &self.transport {
+            Transport::Native { session, .. } => Some(session.sftp().context("Failed to open SFTP channel")),
+            Transport::OpenSsh => None,
+        }
+    }
+
+    /// Run over the built-in client; None with OPS_SSH=openssh, where the caller runs the ssh binary
+    fn run(&self, command: &str, input: Option<&mut dyn Read>, out: &mut dyn Write, err: &mut dyn Write) -> Option<Result<i32>> {
+        match &self.transport {
+            Transport::Native { session, lock } => {
+                o_debug!("ssh {}: {}", self.ssh_target, command);
+                Some(run_native(session, lock, command, input, out, err))
+            }
+            Transport::OpenSsh => None,
+        }
+    }
+
     /// 构建 ssh Command，复用已有的 key
     fn command(&self) -> Command {
         let mut cmd = Command::new("ssh");
@@ -113,6 +333,12 @@ impl SshSession {
 
     /// 执行远程命令（stdout/stderr 直接输出）
     pub fn exec(&self, command: &str, stdin_data: Option<&str>) -> Result<()> {
+        let mut input = stdin_data.map(str::as_bytes);
+        let input = input.as_mut().map(|i| i as &mut dyn Read);
+        if let Some(code) = self.run(command, input, &mut std::io::stdout(), &mut std::io::stderr()) {
+            return check_exit(code?);
+        }
+
         let mut cmd = self.command();
         cmd.arg(command);
 
@@ -135,10 +361,129 @@ impl SshSession {
         Ok(())
     }
 
+    /// 执行远程命令，stdout/stderr 写入给定 writer，返回退出码（非零不算错误）
+    pub fn exec_to(&self, command: &str, out: &mut dyn Write, err: &mut dyn Write) -> Result<i32> {
+        if let Some(code) = self.run(command, None, out, err) {
+            return code;
+        }
+        let output = self.command().arg(command).stdin(Stdio::null()).output()
+            .context("Failed to execute remote command")?;
+        out.write_all(&output.stdout)?;
+        err.write_all(&output.stderr)?;
+        Ok(output.status.code().unwrap_or(-1))
+    }
+
+    /// 执行远程命令，本地文件作为 stdin（二进制安全，用于上传大文件）
+    pub fn exec_file(&self, command: &str, path: &std::path::Path) -> Result<()> {
+        let mut file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        if let Some(code) = self.run(command, Some(&mut file), &mut std::io::stdout(), &mut std::io::stderr()) {
+            return check_exit(code?);
+        }
+        let status = self.command().arg(command).stdin(Stdio::from(file)).status()?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("Remote command failed with status: {}", status));
+        }
+        Ok(())
+    }
+
+    /// 本会话上命令的 stdout 经本机管道流入另一会话上的命令（节点间拷贝，二进制安全）
+    pub fn pipe_to(&self, command: &str, dest: &SshSession, dest_command: &str) -> Result<()> {
+        if let (Transport::Native { .. }, Transport::Native { .. }) = (&self.transport, &dest.transport) {
+            if std::ptr::eq(self, dest) {
+                anyhow::bail!("Cannot pipe a session into itself");
+            }
+            // The source writes chunks from a second thread; the destination reads them as its stdin
+            let (tx, rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(16);
+            let (source_code, dest_code) = std::thread::scope(|s| {
+                let source = s.spawn(move || {
+                    let mut out = ChunkWriter(tx);
+                    self.run(command, None, &mut out, &mut std::io::stderr()).unwrap_or_else(|| Ok(-1))
+                });
+                let mut input = ChunkReader { rx, buf: Vec::new(), pos: 0 };
+                let dest_code = dest.run(dest_command, Some(&mut input), &mut std::io::stdout(), &mut std::io::stderr())
+                    .unwrap_or_else(|| Ok(-1));
+                // Let the source finish if the destination stopped reading early
+                drop(input);
+                let source_code = source.join().unwrap_or_else(|_| Err(anyhow::anyhow!("source thread panicked")));
+                (source_code, dest_code)
+            });
+            if source_code? != 0 {
+                return Err(anyhow::anyhow!("Remote command on {} failed", self.target_str));
+            }
+            if dest_code? != 0 {
+                return Err(anyhow::anyhow!("Remote command on {} failed", dest.target_str));
+            }
+            return Ok(());
+        }
+
+        let mut source = self.command().arg(command).stdout(Stdio::piped()).spawn()?;
+        let stdout = source.stdout.take().context("No stdout from source command")?;
+        let dest_status = dest.command().arg(dest_command).stdin(Stdio::from(stdout)).status()?;
+        let source_status = source.wait()?;
+        if !source_status.success() {
+            return Err(anyhow::anyhow!("Remote command on {} failed with status: {}", self.target_str, source_status));
+        }
+        if !dest_status.success() {
+            return Err(anyhow::anyhow!("Remote command on {} failed with status: {}", dest.target_str, dest_status));
+        }
+        Ok(())
+    }
+
+    /// 执行需要密钥的远程命令：secret 经 stdin 传入（不进入命令行 / ps / shell 历史），
+    /// 输出被捕获并打码后再打印
+    pub fn exec_with_secret(&self, command: &str, secret: &str) -> Result<()> {
+        let (stdout, stderr, success, status) = {
+            let line = format!("{}\n", secret);
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            match self.run(command, Some(&mut line.as_bytes()), &mut out, &mut err) {
+                Some(code) => {
+                    let code = code?;
+                    (out, err, code == 0, format!("exit status: {}", code))
+                }
+                None => {
+                    let mut child = self.command()
+                        .arg(command)
+                        .stdin(Stdio::piped())
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::piped())
+                        .spawn()?;
+                    if let Some(mut stdin) = child.stdin.take() {
+                        writeln!(stdin, "{}", secret)?;
+                    }
+                    let output = child.wait_with_output()?;
+                    (output.stdout, output.stderr, output.status.success(), output.status.to_string())
+                }
+            }
+        };
+
+        let stdout = utils::redact(&String::from_utf8_lossy(&stdout), secret);
+        let stderr = utils::redact(&String::from_utf8_lossy(&stderr), secret);
+        print!("{}", stdout);
+        eprint!("{}", stderr);
+        if !success {
+            return Err(anyhow::anyhow!("Remote command failed with status: {}. {}", status, stderr.trim()));
+        }
+        Ok(())
+    }
+
+    /// Whether pushes go through the rsync binary: always with OPS_SSH=openssh, otherwise when
+    /// rsync and ssh are installed (SFTP is used instead)
+    pub fn has_rsync(&self) -> bool {
+        !self.is_native() || (has_binary("rsync") && has_binary("ssh"))
+    }
+
     /// rsync 本地目录到远程，复用已有的 key
     /// `include` 为白名单：非空时只同步列出的路径，其余排除
     /// 支持 `..` 开头的路径（项目目录外的依赖），会单独 rsync 到远程对应子目录
-    pub fn rsync_push(&self, remote_path: &str, include: &[String]) -> Result<()> {
+    /// 本机没有 rsync / ssh 时改用内置客户端的 SFTP 同步（见 `sftp::sync_dir`）
+    /// 每个同步根目录的 .opsignore（`gitignore` 时加上 .gitignore）里的路径不会上传
+    pub fn rsync_push(&self, remote_path: &str, include: &[String], gitignore: bool) -> Result<()> {
+        if !self.has_rsync() {
+            o_debug!("rsync or ssh not found, syncing over SFTP");
+            return sftp::push(self, remote_path, include, gitignore);
+        }
+
         let ssh_cmd = format!(
             "ssh -i {} -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null -o LogLevel=ERROR",
             self.key_path
@@ -159,7 +504,10 @@ impl SshSession {
                 .arg("--exclude").arg("target/")
                 .arg("--exclude").arg("node_modules/")
                 .arg("--exclude").arg(".git/")
+                .arg("--exclude").arg(".ops/")
                 .arg("--exclude").arg(".env");
+            // Before the include whitelist: rsync stops at the first matching rule
+            let _ignore_file = add_ignore_rules(&mut cmd, Path::new("."), gitignore)?;
 
             if !local.is_empty() {
                 for entry in &local {
@@ -202,9 +550,10 @@ impl SshSession {
                 .arg("--exclude").arg("target/")
                 .arg("--exclude").arg("node_modules/")
                 .arg("--exclude").arg(".git/")
-                .arg("--exclude").arg(".env")
-                .arg(&src)
-                .arg(&dst);
+                .arg("--exclude").arg(".ops/")
+                .arg("--exclude").arg(".env");
+            let _ignore_file = add_ignore_rules(&mut cmd, Path::new(entry.trim_end_matches('/')), gitignore)?;
+            cmd.arg(&src).arg(&dst);
 
             let status = cmd.status()
                 .context(format!("Failed to rsync external path: {}", entry))?;
@@ -216,8 +565,57 @@ impl SshSession {
         Ok(())
     }
 
+    /// `[[sync]]` 条目：rsync -az 把目录内容（或单个文件）复制到远程 `remote`，不排除、不删除
+    /// 只传有变化的部分；本机没有 rsync / ssh 时改用 SFTP（见 `sftp::copy`）
+    pub fn rsync_copy(&self, local: &std::path::Path, remote: &str) -> Result<()> {
+        if !self.has_rsync() {
+            sftp::copy(self, local, remote)?;
+            return Ok(());
+        }
+
+        let is_dir = local.is_dir();
+        let remote_dir = if is_dir {
+            remote.to_string()
+        } else {
+            std::path::Path::new(remote).parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default()
+        };
+        if !remote_dir.is_empty() {
+            self.exec(&format!("mkdir -p {}", utils::shell_quote(&remote_dir)), None)?;
+        }
+
+        let ssh_cmd = format!(
+            "ssh -i {} -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null -o LogLevel=ERROR",
+            self.key_path
+        );
+        let (src, dst) = if is_dir {
+            (format!("{}/", local.display().to_string().trim_end_matches('/')), format!("{}:{}/", self.ssh_target, remote.trim_end_matches('/')))
+        } else {
+            (local.display().to_string(), format!("{}:{}", self.ssh_target, remote))
+        };
+        let status = Command::new("rsync")
+            .arg("-az")
+            .arg("-e").arg(&ssh_cmd)
+            .arg(&src)
+            .arg(&dst)
+            .status()
+            .context("Failed to execute rsync (is rsync installed?)")?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("rsync failed for '{}' with status: {}", local.display(), status));
+        }
+        Ok(())
+    }
+
     /// 执行远程命令并捕获 stdout
     pub fn exec_output(&self, command: &str) -> Result<Vec<u8>> {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        if let Some(code) = self.run(command, None, &mut out, &mut err) {
+            let code = code.context("Failed to execute remote command")?;
+            if code != 0 {
+                return Err(anyhow::anyhow!("Remote command failed: exit status: {}. {}", code, String::from_utf8_lossy(&err)));
+            }
+            return Ok(out);
+        }
+
         let mut cmd = self.command();
         cmd.arg(command);
 
@@ -230,12 +628,63 @@ impl SshSession {
     }
 }
 
-// ops ssh <target> [command]
-pub async fn handle_ssh(target_str: String, command: Option<String>) -> Result<()> {
-    let (mut cmd, _temp_key_file) = build_ssh_command(&target_str).await?;
+/// `Write` end of the chunk channel `pipe_to` streams through
+struct ChunkWriter(std::sync::mpsc::SyncSender<Vec<u8>>);
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.send(buf.to_vec()).map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "reader closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `Read` end of the chunk channel; EOF once the writer is dropped
+struct ChunkReader {
+    rx: std::sync::mpsc::Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.buf.len() {
+            match self.rx.recv() {
+                Ok(chunk) => { self.buf = chunk; self.pos = 0; }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+// ops ssh <target> [--user <u>] [-L spec] [-R spec] [-- command...]
+pub async fn handle_ssh(target_str: Option<String>, mut opts: SshOptions, command: Vec<String>, interactive: bool) -> Result<()> {
+    use std::io::IsTerminal;
+
+    let target = crate::commands::common::resolve_target(target_str, interactive).await?;
+
+    // Allocate a TTY for remote commands when attached to a terminal, so interactive
+    // programs (top, psql, docker exec -it) behave the same as in a shell
+    if !command.is_empty() && std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+        opts.tty = true;
+    }
+
+    let (mut cmd, _temp_key_file) = build_ssh_command_with(&target, &opts).await?;
 
-    if let Some(remote_cmd) = command {
-        o_step!("Executing on {}...", target_str.cyan());
+    for spec in opts.local_forwards.iter().chain(&opts.remote_forwards) {
+        o_detail!("Forwarding {}", spec.cyan());
+    }
+
+    if !command.is_empty() {
+        let remote_cmd = command.join(" ");
+        o_step!("Executing on {}...", target.to_string().cyan());
         cmd.arg(&remote_cmd);
 
         let mut child = cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit()).spawn()?;
@@ -255,37 +704,10 @@ pub async fn handle_ssh(target_str: String, command: Option<String>) -> Result<(
 
 // 用于 env upload
 pub async fn execute_remote_command(target_str: &str, command: &str, stdin_data: Option<&str>) -> Result<()> {
-    let (mut cmd, _temp_key_file) = build_ssh_command(target_str).await?;
-    cmd.arg(command);
-
-    if let Some(data) = stdin_data {
-        cmd.stdin(Stdio::piped());
-        let mut child = cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit()).spawn()?;
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(data.as_bytes())?;
-        }
-        let status = child.wait()?;
-        if !status.success() {
-            return Err(anyhow::anyhow!("Remote command failed with status: {}", status));
-        }
-    } else {
-        let status = cmd.status()?;
-        if !status.success() {
-            return Err(anyhow::anyhow!("Remote command failed with status: {}", status));
-        }
-    }
-    Ok(())
+    SshSession::connect(target_str).await?.exec(command, stdin_data)
 }
 
 // 用于 env download
 pub async fn execute_remote_command_with_output(target_str: &str, command: &str) -> Result<Vec<u8>> {
-    let (mut cmd, _temp_key_file) = build_ssh_command(target_str).await?;
-    cmd.arg(command);
-
-    let output = cmd.output().context("Failed to execute remote command and capture output")?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("Remote command failed with status: {}. Stderr: {}", output.status, stderr));
-    }
-    Ok(output.stdout)
-}
\ No newline at end of file
+    SshSession::connect(target_str).await?.exec_output(command)
+}
@@ -0,0 +1,136 @@
+//! `ops ssh-config export`: an OpenSSH config block with a Host per node and per bound app,
+//! using the CI keys written under `<config dir>/ops/ssh/`, so plain `ssh api.myproject`,
+//! scp and VS Code Remote SSH reach ops nodes without going through `ops ssh`.
+
+use crate::types::Node;
+use crate::{api, config};
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const BEGIN_MARKER: &str = "# BEGIN ops-managed ssh-config";
+pub const END_MARKER: &str = "# END ops-managed ssh-config";
+
+/// Host aliases pointing at one node, with the key that logs in there
+#[derive(Debug, PartialEq)]
+struct HostEntry {
+    aliases: Vec<String>,
+    hostname: String,
+    key_path: PathBuf,
+}
+
+/// One entry per node, then one per app on its primary node (or its first node)
+fn host_entries(nodes: &[Node], key_dir: &Path) -> Vec<HostEntry> {
+    let mut entries = Vec::new();
+    let mut apps: BTreeMap<String, (bool, &Node)> = BTreeMap::new();
+    for node in nodes {
+        let mut aliases = vec![format!("ops-{}", node.id), node.domain.clone()];
+        if let Some(h) = node.hostname.as_ref().filter(|h| !h.is_empty() && !aliases.contains(h)) {
+            aliases.push(h.clone());
+        }
+        entries.push(HostEntry { aliases, hostname: node.domain.clone(), key_path: key_path(key_dir, node.id) });
+
+        for app in node.bound_apps.iter().flatten() {
+            let primary = app.is_primary.unwrap_or(0) != 0;
+            let alias = format!("{}.{}", app.name, app.project_name);
+            match apps.get(&alias) {
+                Some((true, _)) => {}
+                Some((false, _)) if !primary => {}
+                _ => { apps.insert(alias, (primary, node)); }
+            }
+        }
+    }
+    for (alias, (_, node)) in apps {
+        entries.push(HostEntry {
+            aliases: vec![alias.clone(), format!("{}.ops.autos", alias)],
+            hostname: node.domain.clone(),
+            key_path: key_path(key_dir, node.id),
+        });
+    }
+    entries
+}
+
+fn key_path(key_dir: &Path, node_id: i64) -> PathBuf {
+    key_dir.join(format!("node-{}", node_id))
+}
+
+/// The config block, between markers so it can be found and replaced later
+fn render(entries: &[HostEntry]) -> String {
+    let mut out = format!("{}\n", BEGIN_MARKER);
+    for e in entries {
+        out.push_str(&format!(
+            "Host {}\n    HostName {}\n    User root\n    IdentityFile {}\n    IdentitiesOnly yes\n    StrictHostKeyChecking no\n    UserKnownHostsFile /dev/null\n    LogLevel ERROR\n\n",
+            e.aliases.join(" "), e.hostname, e.key_path.display()
+        ));
+    }
+    out.push_str(END_MARKER);
+    out.push('\n');
+    out
+}
+
+/// ops ssh-config export
+pub async fn handle_export() -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let key_dir = config::ssh_key_dir().context("Could not find config directory")?;
+    fs::create_dir_all(&key_dir).with_context(|| format!("Failed to create {}", key_dir.display()))?;
+
+    let nodes = api::list_nodes(&token).await.context("Failed to list nodes")?.nodes;
+    let mut reachable = Vec::new();
+    for node in nodes {
+        match api::get_node_ci_key(&token, node.id as u64).await {
+            Ok(key) => {
+                let path = key_path(&key_dir, node.id);
+                config::write_atomic(&path, &format!("{}\n", key.private_key.trim_end()))?;
+                reachable.push(node);
+            }
+            Err(e) => o_warn!("Skipping node #{}: {:#}", node.id, e),
+        }
+    }
+
+    // Status goes to stderr so the block can be appended to ~/.ssh/config as is
+    o_result!("{}", render(&host_entries(&reachable, &key_dir)).trim_end());
+    o_warn!("{} node(s), keys in {}", reachable.len(), key_dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: i64, hostname: Option<&str>, apps: Vec<(&str, &str, i64)>) -> Node {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "ip_address": "203.0.113.1", "ipv6_address": null, "hostname": hostname,
+            "domain": format!("{}.node.ops.autos", id), "region": null, "zone": null, "serve_port": 8377,
+            "allowed_projects": null, "allowed_apps": null, "status": "online", "last_health_check": null,
+            "has_serve_token": 1, "created_at": "2026-01-01",
+            "bound_apps": apps.iter().map(|(name, project, primary)| serde_json::json!({
+                "id": 1, "name": name, "project_name": project, "is_primary": primary,
+            })).collect::<Vec<_>>(),
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_entries_and_render() {
+        let nodes = vec![
+            node(4, Some("web-1"), vec![("api", "shop", 0), ("web", "shop", 1)]),
+            node(7, None, vec![("api", "shop", 1)]),
+        ];
+        let entries = host_entries(&nodes, Path::new("/home/me/.config/ops/ssh"));
+        let hosts: Vec<(String, &str)> = entries.iter().map(|e| (e.aliases.join(" "), e.hostname.as_str())).collect();
+        assert_eq!(hosts, vec![
+            ("ops-4 4.node.ops.autos web-1".to_string(), "4.node.ops.autos"),
+            ("ops-7 7.node.ops.autos".to_string(), "7.node.ops.autos"),
+            // The primary node wins
+            ("api.shop api.shop.ops.autos".to_string(), "7.node.ops.autos"),
+            ("web.shop web.shop.ops.autos".to_string(), "4.node.ops.autos"),
+        ]);
+
+        let block = render(&entries[..1]);
+        assert_eq!(block, format!(
+            "{}\nHost ops-4 4.node.ops.autos web-1\n    HostName 4.node.ops.autos\n    User root\n    IdentityFile /home/me/.config/ops/ssh/node-4\n    IdentitiesOnly yes\n    StrictHostKeyChecking no\n    UserKnownHostsFile /dev/null\n    LogLevel ERROR\n\n{}\n",
+            BEGIN_MARKER, END_MARKER
+        ));
+    }
+}
@@ -1,11 +1,21 @@
-use crate::commands::deploy::load_ops_toml;
-use crate::commands::ssh;
-use crate::{api, config};
+use crate::commands::deploy::{compose_args, load_ops_toml_env};
+use crate::commands::events;
+use crate::commands::ssh::{self, SshSession};
+use crate::serve::crashes;
+use crate::serve::stats::{self, ContainerStats};
+use crate::utils::shell_quote;
+use crate::{api, config, output};
 use anyhow::{Context, Result};
+use crate::types::{DeployTarget, DeployTargetsResponse, EventInfo, OpsToml};
 use colored::Colorize;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
 
-pub async fn handle_status(file: String) -> Result<()> {
-    let ops_config = load_ops_toml(&file)?;
+/// Events fetched to look for recent crashes
+const RECENT_EVENTS: u32 = 5;
+
+pub async fn handle_status(file: String, no_probe: bool, show_stats: bool, env: Option<String>) -> Result<()> {
+    let ops_config = load_ops_toml_env(&file, env.as_deref())?;
 
     let project = &ops_config.project;
     let app = ops_config.apps.first()
@@ -15,15 +25,28 @@ pub async fn handle_status(file: String) -> Result<()> {
     let cfg = config::load_config().context("Config error")?;
     let token = cfg.token.context("Please run `ops login` first.")?;
 
-    let resp = api::get_app_deploy_targets(&token, project, app).await
+    let mut resp = api::get_app_deploy_targets(&token, project, app).await
         .context("Failed to get deploy targets")?;
+    resp.targets.retain(|t| ops_config.env_selects(t.node_id, t.region.as_deref()));
 
     if resp.targets.is_empty() {
-        anyhow::bail!("No nodes bound to app '{}' in project '{}'", app, project);
+        anyhow::bail!("No nodes bound to app '{}' in project '{}'{}", app, project,
+            ops_config.env.as_ref().map(|e| format!(" for environment {}", e)).unwrap_or_default());
+    }
+    if output::json() {
+        return status_json(&token, &ops_config, app, resp, no_probe, show_stats).await;
     }
 
     if resp.targets.len() > 1 {
-        return show_multi_node_status(&ops_config, &resp).await;
+        show_multi_node_status(&ops_config, &resp).await?;
+        if !no_probe {
+            probe_endpoints(&ops_config, &resp.targets).await;
+        }
+        if show_stats {
+            container_stats(&ops_config, &resp.targets).await;
+        }
+        recent_crashes(&token, project, app).await;
+        return Ok(());
     }
 
     // Single-node status via SSH
@@ -31,14 +54,159 @@ pub async fn handle_status(file: String) -> Result<()> {
     o_step!("{} {}\n", "📊 Status:".cyan(), t.domain.green());
 
     let cmd = format!(
-        "cd {} && docker compose ps",
-        ops_config.deploy_path
+        "cd {} && docker compose {} ps",
+        ops_config.deploy_path, compose_args(&ops_config)
     );
 
     ssh::execute_remote_command(&t.domain, &cmd, None).await?;
+    if !no_probe {
+        probe_endpoints(&ops_config, &resp.targets).await;
+    }
+    if show_stats {
+        container_stats(&ops_config, &resp.targets).await;
+    }
+    recent_crashes(&token, project, app).await;
     Ok(())
 }
 
+/// `ops status --json`: targets, each node's compose containers, probes, stats and crashes in one document
+async fn status_json(
+    token: &str,
+    config: &OpsToml,
+    app: &str,
+    resp: DeployTargetsResponse,
+    no_probe: bool,
+    show_stats: bool,
+) -> Result<()> {
+    let ps = format!("cd {} && docker compose {} ps -a --format json", config.deploy_path, compose_args(config));
+    let mut nodes = Vec::new();
+    for t in &resp.targets {
+        let session = SshSession::connect(&t.node_id.to_string()).await;
+        let containers = session.as_ref().map_err(|e| format!("{:#}", e))
+            .and_then(|s| s.exec_output(&ps).map_err(|e| format!("{:#}", e)))
+            .map(|out| parse_compose_ps(&String::from_utf8_lossy(&out)));
+        let stats = match (&session, show_stats) {
+            (Ok(s), true) => Some(node_stats(config, s).map_err(|e| format!("{:#}", e))),
+            _ => None,
+        };
+        let mut node = json!({ "node_id": t.node_id, "domain": t.domain });
+        match containers {
+            Ok(list) => node["containers"] = json!(list),
+            Err(e) => node["error"] = json!(e),
+        }
+        match stats {
+            Some(Ok(list)) => node["stats"] = json!(list),
+            Some(Err(e)) => node["stats_error"] = json!(e),
+            None => {}
+        }
+        nodes.push(node);
+    }
+
+    let endpoints = if no_probe {
+        None
+    } else {
+        let results = probe(config, &resp.targets).await?;
+        Some(results.iter().map(|(ep, res, latency)| json!({
+            "app": ep.app,
+            "url": ep.url,
+            "target": ep.target,
+            "status": res.as_ref().ok(),
+            "error": res.as_ref().err(),
+            "latency_ms": latency.as_millis() as u64,
+        })).collect::<Vec<_>>())
+    };
+
+    output::print_json(&json!({
+        "project": config.project,
+        "app": app,
+        "mode": resp.mode,
+        "strategy": resp.lb_strategy,
+        "targets": resp.targets,
+        "nodes": nodes,
+        "endpoints": endpoints,
+        "crashes": crash_events(token, &config.project, app).await,
+    }))
+}
+
+/// `docker compose ps --format json` prints an array (older Compose) or one object per line
+pub fn parse_compose_ps(output: &str) -> Vec<Value> {
+    let trimmed = output.trim();
+    if trimmed.starts_with('[') {
+        return serde_json::from_str(trimmed).unwrap_or_default();
+    }
+    trimmed.lines().filter_map(|l| serde_json::from_str(l).ok()).collect()
+}
+
+/// Post-deploy crashes among the app's latest events
+async fn crash_events(token: &str, project: &str, app: &str) -> Vec<EventInfo> {
+    match api::list_app_events(token, project, app, RECENT_EVENTS).await {
+        Ok(resp) => resp.events.into_iter().filter(|e| e.event.kind == crashes::CRASH_EVENT).collect(),
+        Err(e) => {
+            o_debug!("   events: {:#}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Post-deploy crashes `ops serve` reported for the app, if any
+async fn recent_crashes(token: &str, project: &str, app: &str) {
+    let crashes = crash_events(token, project, app).await;
+    if crashes.is_empty() {
+        return;
+    }
+    o_step!("\n{}", "🔔 Recent post-deploy crashes".yellow());
+    events::print_events(&crashes, false);
+    o_detail!("  {}", format!("Logs: ops events {}.{} --logs", app, project).dimmed());
+}
+
+/// The project's containers on one node, from `docker stats --no-stream` like serve's `/containers/stats`
+fn node_stats(config: &OpsToml, session: &SshSession) -> Result<Vec<ContainerStats>> {
+    let cmd = format!(
+        "docker stats --no-stream --format {} && echo '---' && docker ps --format {}",
+        shell_quote(stats::STATS_FORMAT), shell_quote(stats::LABELS_FORMAT)
+    );
+    let out = String::from_utf8_lossy(&session.exec_output(&cmd)?).to_string();
+    let (stats_out, labels_out) = out.split_once("---\n").unwrap_or((&out, ""));
+    let prefix = format!("{}-", config.compose_project());
+    Ok(stats::parse_current(stats_out, &stats::parse_labels(labels_out))
+        .into_iter()
+        .filter(|s| s.container.starts_with(&prefix))
+        .collect())
+}
+
+/// Per-container CPU, memory, network and block IO on each node
+async fn container_stats(config: &OpsToml, targets: &[DeployTarget]) {
+    o_step!("\n{}", "📈 Container stats:".cyan());
+    for t in targets {
+        o_detail!("  {} {}", "Node".dimmed(), t.domain.green());
+        let result = match SshSession::connect(&t.node_id.to_string()).await {
+            Ok(session) => node_stats(config, &session),
+            Err(e) => Err(e),
+        };
+        let list = match result {
+            Ok(list) if list.is_empty() => {
+                o_detail!("    {}", "no running containers".dimmed());
+                continue;
+            }
+            Ok(list) => list,
+            Err(e) => {
+                o_warn!("    {} {:#}", "⚠".yellow(), e);
+                continue;
+            }
+        };
+        o_detail!("    {:<14} {:<28} {:>7} {:>19} {:>21} {:>21}",
+            "SERVICE", "CONTAINER", "CPU", "MEM", "NET RX/TX", "BLOCK R/W");
+        for s in &list {
+            let service = if s.service.is_empty() { "-" } else { s.service.as_str() };
+            o_detail!("    {:<14} {:<28} {:>6.1}% {:>19} {:>21} {:>21}",
+                service, s.container, s.cpu_percent,
+                format!("{:.0} / {:.0} MiB", s.mem_mb, s.mem_limit_mb),
+                format!("{:.1} / {:.1} MiB", s.net_rx_mb, s.net_tx_mb),
+                format!("{:.1} / {:.1} MiB", s.block_read_mb, s.block_write_mb));
+        }
+    }
+}
+
 async fn show_multi_node_status(
     config: &crate::types::OpsToml,
     resp: &crate::types::DeployTargetsResponse,
@@ -68,8 +236,8 @@ async fn show_multi_node_status(
         o_detail!("  Node {} ({}, {}){}", t.node_id, region, hostname, primary_tag);
 
         // Try to get container status via SSH
-        let cmd = format!("cd {} && docker compose ps --format '  {{{{.Name}}}}\\t{{{{.Status}}}}'",
-            config.deploy_path);
+        let cmd = format!("cd {} && docker compose {} ps --format '  {{{{.Name}}}}\\t{{{{.State}}}}\\t{{{{.Health}}}}'",
+            config.deploy_path, compose_args(config));
         o_print!("    Status: ");
         match ssh::execute_remote_command(&t.domain, &cmd, None).await {
             Ok(_) => {}
@@ -86,3 +254,124 @@ async fn show_multi_node_status(
 
     Ok(())
 }
+
+/// One endpoint to probe from the CLI machine
+struct Endpoint {
+    app: String,
+    label: String,
+    url: String,
+    /// X-OPS-Target header, to reach one node's router directly
+    target: Option<String>,
+}
+
+/// What one probe got: HTTP status or error, and latency
+type ProbeResult = (Endpoint, std::result::Result<u16, String>, Duration);
+
+/// Hit every app's public URLs (default and custom domains) and each node via X-OPS-Target,
+/// showing HTTP status and latency: end-to-end reachability, not just container state
+async fn probe_endpoints(config: &OpsToml, targets: &[DeployTarget]) {
+    let results = match probe(config, targets).await {
+        Ok(r) => r,
+        Err(e) => {
+            o_warn!("   {} Cannot probe endpoints: {}", "⚠".yellow(), e);
+            return;
+        }
+    };
+
+    let project = &config.project;
+    o_step!("\n{}", "🌐 Endpoints".cyan());
+    let width = results.iter().map(|(e, _, _)| e.label.len()).max().unwrap_or(0);
+    let mut current_app = "";
+    for (ep, res, latency) in &results {
+        if ep.app != current_app {
+            current_app = &ep.app;
+            o_detail!("  {}", format!("{}.{}", ep.app, project).green());
+        }
+        let status = match res {
+            Ok(code) if *code < 400 => code.to_string().green(),
+            Ok(code) if *code < 500 => code.to_string().yellow(),
+            Ok(code) => code.to_string().red(),
+            Err(e) => e.red(),
+        };
+        o_detail!("    {:width$}  {:>5}  {}", ep.label, status, format!("{}ms", latency.as_millis()).dimmed(), width = width);
+    }
+}
+
+async fn probe(config: &OpsToml, targets: &[DeployTarget]) -> Result<Vec<ProbeResult>> {
+    let project = &config.project;
+    let apps: Vec<(&str, Option<u16>)> = if config.apps.iter().any(|a| a.port.is_some()) {
+        config.apps.iter().filter(|a| a.port.is_some()).map(|a| (a.name.as_str(), a.port)).collect()
+    } else {
+        vec![(config.apps.first().map(|a| a.name.as_str()).unwrap_or(project.as_str()), None)]
+    };
+
+    let mut endpoints = Vec::new();
+    for (i, (app, port)) in apps.iter().enumerate() {
+        // The app's healthcheck path, if any
+        let path = config.healthchecks.iter()
+            .find(|h| h.name == *app)
+            .and_then(|h| h.url.splitn(4, '/').nth(3).map(|p| format!("/{}", p)))
+            .unwrap_or_else(|| "/".into());
+
+        let default_url = format!("https://{}.{}.ops.autos{}", app, project, path);
+        endpoints.push(Endpoint { app: app.to_string(), label: default_url.clone(), url: default_url, target: None });
+
+        // Custom domains route to the app with the same port (or the first app)
+        for route in &config.routes {
+            if *port == Some(route.port) || (i == 0 && !apps.iter().any(|(_, p)| *p == Some(route.port))) {
+                let url = format!("https://{}{}", route.domain, path);
+                endpoints.push(Endpoint { app: app.to_string(), label: url.clone(), url, target: None });
+            }
+        }
+
+        for t in targets {
+            endpoints.push(Endpoint {
+                app: app.to_string(),
+                label: format!("node {} ({})", t.node_id, t.ip_address),
+                url: format!("http://{}{}", t.ip_address, path),
+                target: Some(format!("{}.{}", app, project)),
+            });
+        }
+    }
+
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(5)).build()?;
+
+    let mut set = tokio::task::JoinSet::new();
+    for (i, ep) in endpoints.iter().enumerate() {
+        let mut req = client.get(&ep.url);
+        if let Some(target) = &ep.target {
+            req = req.header("X-OPS-Target", target);
+        }
+        set.spawn(async move {
+            let start = Instant::now();
+            let res = req.send().await.map(|r| r.status().as_u16()).map_err(|e| {
+                if e.is_timeout() { "timeout".to_string() } else if e.is_connect() { "connect failed".to_string() } else { e.to_string() }
+            });
+            (i, res, start.elapsed())
+        });
+    }
+    let mut results = Vec::new();
+    while let Some(Ok(r)) = set.join_next().await {
+        results.push(r);
+    }
+    results.sort_by_key(|(i, _, _)| *i);
+
+    let mut endpoints: Vec<Option<Endpoint>> = endpoints.into_iter().map(Some).collect();
+    Ok(results.into_iter()
+        .filter_map(|(i, res, latency)| Some((endpoints[i].take()?, res, latency)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compose_ps() {
+        let lines = "{\"Name\":\"shop-api-1\",\"State\":\"running\"}\n{\"Name\":\"shop-web-1\",\"State\":\"exited\"}\n";
+        let array = "[{\"Name\":\"shop-api-1\",\"State\":\"running\"},{\"Name\":\"shop-web-1\",\"State\":\"exited\"}]\n";
+        assert_eq!(parse_compose_ps(lines), parse_compose_ps(array));
+        assert_eq!(parse_compose_ps(lines)[1]["State"], "exited");
+        assert!(parse_compose_ps("").is_empty());
+    }
+}
@@ -0,0 +1,161 @@
+//! `--summary-file`: a JSON record of a deploy or build for CI. GitHub Actions job summaries and later
+//! pipeline steps read it instead of scraping colored console output.
+
+use crate::commands::ssh::SshSession;
+use crate::types::{DeployTarget, OpsToml};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::time::{Instant, SystemTime};
+
+#[derive(Serialize, Debug)]
+pub struct NodeSummary {
+    /// Set by `ops deploy --all-apps`, which reports app × node
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    pub node_id: i64,
+    pub domain: String,
+    pub region: Option<String>,
+    /// "success" | "failed" | "skipped"
+    pub status: &'static str,
+    pub error: Option<String>,
+    pub duration_secs: f64,
+    /// Images of the project's running containers after the deploy
+    pub images: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DeploySummary {
+    pub kind: &'static str,
+    pub project: String,
+    pub app: String,
+    /// "success" | "partial" | "failed"
+    pub status: &'static str,
+    /// Backend deployment ID
+    pub release_id: Option<i64>,
+    pub started_at: String,
+    pub duration_secs: f64,
+    pub nodes: Vec<NodeSummary>,
+    pub urls: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BuildSummary {
+    pub kind: &'static str,
+    pub project: String,
+    /// "success" | "failed"
+    pub status: &'static str,
+    pub error: Option<String>,
+    pub node: String,
+    pub tag: String,
+    /// `<prefix>/<service>:<tag>` of each image built
+    pub images: Vec<String>,
+    pub pushed: bool,
+    pub started_at: String,
+    pub duration_secs: f64,
+}
+
+/// Wall clock and monotonic start of a command
+pub struct Started {
+    pub at: String,
+    instant: Instant,
+}
+
+impl Started {
+    pub fn now() -> Self {
+        Started { at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(), instant: Instant::now() }
+    }
+
+    pub fn elapsed_secs(&self) -> f64 {
+        (self.instant.elapsed().as_secs_f64() * 10.0).round() / 10.0
+    }
+}
+
+pub fn overall_status(succeeded: usize, total: usize) -> &'static str {
+    match succeeded {
+        n if n == total && total > 0 => "success",
+        0 => "failed",
+        _ => "partial",
+    }
+}
+
+impl NodeSummary {
+    pub fn new(target: &DeployTarget, started: &Started, result: &Result<()>, images: Vec<String>) -> Self {
+        NodeSummary {
+            app: None,
+            node_id: target.node_id,
+            domain: target.domain.clone(),
+            region: target.region.clone(),
+            status: if result.is_ok() { "success" } else { "failed" },
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            duration_secs: started.elapsed_secs(),
+            images,
+        }
+    }
+}
+
+/// Images of the project's running containers: compose (`<project>-<svc>-<n>`) and zero-downtime
+/// (`<project>-<svc>-<deployment id>`) containers share the name prefix
+pub fn running_images(config: &OpsToml, session: &SshSession) -> Vec<String> {
+    let cmd = format!("docker ps --filter 'name=^{}-' --format '{{{{.Image}}}}' | sort -u", config.compose_project());
+    session.exec_output(&cmd)
+        .map(|o| String::from_utf8_lossy(&o).lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Public URLs of the deployed apps: the default ops.autos domain, app domains and [[routes]]
+pub fn app_urls(config: &OpsToml, app_filter: &Option<String>) -> Vec<String> {
+    let project = &config.project;
+    let mut urls = Vec::new();
+    if config.apps.is_empty() {
+        urls.push(format!("https://{}.{}.ops.autos", project, project));
+    }
+    for app in config.apps.iter().filter(|a| app_filter.as_ref().is_none_or(|f| *f == a.name)) {
+        urls.push(format!("https://{}.{}.ops.autos", app.name, project));
+        urls.extend(app.domains.iter().map(|d| format!("https://{}", d)));
+    }
+    for route in &config.routes {
+        let url = format!("https://{}", route.domain);
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+pub fn write(path: &str, summary: &impl Serialize) -> Result<()> {
+    let json = serde_json::to_string_pretty(summary)?;
+    std::fs::write(path, json + "\n").with_context(|| format!("Failed to write summary to {}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_and_urls() {
+        assert_eq!(overall_status(2, 2), "success");
+        assert_eq!(overall_status(1, 2), "partial");
+        assert_eq!(overall_status(0, 2), "failed");
+        assert_eq!(overall_status(0, 0), "failed");
+
+        let config: OpsToml = toml::from_str(r#"
+            project = "shop"
+            deploy_path = "/opt/shop"
+            [deploy]
+            [[apps]]
+            name = "api"
+            services = ["api"]
+            domains = ["api.example.com"]
+            [[apps]]
+            name = "web"
+            services = ["web"]
+            [[routes]]
+            domain = "api.example.com"
+            port = 8080
+        "#).unwrap();
+        assert_eq!(app_urls(&config, &None), vec![
+            "https://api.shop.ops.autos", "https://api.example.com", "https://web.shop.ops.autos",
+        ]);
+        assert_eq!(app_urls(&config, &Some("web".into())), vec!["https://web.shop.ops.autos", "https://api.example.com"]);
+    }
+}
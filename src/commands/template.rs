@@ -0,0 +1,189 @@
+//! `ops new` and `ops templates list`: starter apps from the ops template catalog or any git repository.
+//! A template is a directory with source, Dockerfile, compose file and ops.toml; `{{name}}` in its
+//! files becomes the new app's name.
+
+use crate::commands::deploy::load_ops_toml;
+use crate::api;
+use crate::types::{TemplateInfo, TemplateListResponse};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+const PLACEHOLDER: &str = "{{name}}";
+/// Index at the root of a template repository
+const INDEX_FILE: &str = "templates.toml";
+
+fn shallow_clone(repo: &str, git_ref: Option<&str>) -> Result<TempDir> {
+    let dir = tempfile::tempdir()?;
+    let mut cmd = Command::new("git");
+    cmd.args(["clone", "--quiet", "--depth", "1"]);
+    if let Some(git_ref) = git_ref {
+        cmd.args(["--branch", git_ref]);
+    }
+    let status = cmd.arg(repo).arg(dir.path()).status().context("Failed to run git")?;
+    if !status.success() {
+        bail!("git clone {} failed", repo);
+    }
+    Ok(dir)
+}
+
+/// Templates of a checked-out repository: its templates.toml, else every top-level directory with an ops.toml
+fn repo_templates(dir: &Path) -> Result<Vec<TemplateInfo>> {
+    let index = dir.join(INDEX_FILE);
+    if index.exists() {
+        let content = fs::read_to_string(&index)?;
+        let list: TemplateListResponse = toml::from_str(&content).with_context(|| format!("Invalid {}", INDEX_FILE))?;
+        return Ok(list.templates);
+    }
+    let mut templates: Vec<TemplateInfo> = fs::read_dir(dir)?
+        .flatten()
+        .filter(|e| e.path().join("ops.toml").exists())
+        .map(|e| TemplateInfo {
+            name: e.file_name().to_string_lossy().to_string(),
+            description: String::new(),
+            repo: None,
+            path: None,
+            git_ref: None,
+        })
+        .collect();
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+/// Copy `src` to `dst`, replacing the placeholder in text files. Returns the number of files written.
+fn copy_template(src: &Path, dst: &Path, name: &str) -> Result<usize> {
+    fs::create_dir_all(dst)?;
+    let mut count = 0;
+    for entry in fs::read_dir(src)?.flatten() {
+        let file_name = entry.file_name();
+        if file_name == ".git" || file_name == INDEX_FILE {
+            continue;
+        }
+        let (from, to) = (entry.path(), dst.join(&file_name));
+        if entry.file_type()?.is_dir() {
+            count += copy_template(&from, &to, name)?;
+            continue;
+        }
+        match fs::read_to_string(&from) {
+            Ok(text) if text.contains(PLACEHOLDER) => {
+                fs::write(&to, text.replace(PLACEHOLDER, name))?;
+                fs::set_permissions(&to, entry.metadata()?.permissions())?;
+            }
+            _ => {
+                fs::copy(&from, &to)?;
+            }
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// ops templates list
+pub async fn handle_list(from: Option<String>) -> Result<()> {
+    let templates = match &from {
+        Some(repo) => repo_templates(shallow_clone(repo, None)?.path())?,
+        None => api::list_templates().await.context("Failed to fetch templates")?.templates,
+    };
+    if templates.is_empty() {
+        o_warn!("No templates found.");
+        return Ok(());
+    }
+    o_result!("{:<24} {}", "TEMPLATE".bold(), "DESCRIPTION".bold());
+    for t in &templates {
+        o_result!("{:<24} {}", t.name.cyan(), t.description);
+    }
+    o_result!("\nCreate an app: ops new <template> [directory]");
+    Ok(())
+}
+
+/// ops new <template> [dir]: scaffold an app that `ops deploy` can ship as-is
+pub async fn handle_new(template: String, directory: Option<String>, name: Option<String>, from: Option<String>) -> Result<()> {
+    let dir = directory.unwrap_or_else(|| template.clone());
+    let dir_path = Path::new(&dir);
+    if dir_path.exists() && fs::read_dir(dir_path)?.next().is_some() {
+        bail!("Directory '{}' already exists and is not empty", dir);
+    }
+    let name = match name {
+        Some(name) => name,
+        None => dir_path.canonicalize().ok().as_deref().unwrap_or(dir_path)
+            .file_name().map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| template.clone()),
+    };
+
+    // 1. Find the template and check out its repository
+    o_step!("{}", format!("📦 Fetching template {}...", template).cyan());
+    let (info, checkout) = match &from {
+        Some(repo) => {
+            let checkout = shallow_clone(repo, None)?;
+            let info = repo_templates(checkout.path())?.into_iter().find(|t| t.name == template);
+            (info, Some(checkout))
+        }
+        None => {
+            let templates = api::list_templates().await.context("Failed to fetch templates")?.templates;
+            (templates.into_iter().find(|t| t.name == template), None)
+        }
+    };
+    let info = info.with_context(|| format!("Template '{}' not found. See `ops templates list`", template))?;
+    let checkout = match (checkout, &info.repo) {
+        (Some(checkout), _) => checkout,
+        (None, Some(repo)) => shallow_clone(repo, info.git_ref.as_deref())?,
+        (None, None) => bail!("Template '{}' has no repository", template),
+    };
+    let src = checkout.path().join(info.path.as_deref().unwrap_or(&info.name));
+    if !src.is_dir() {
+        bail!("Template '{}' points at a missing directory", template);
+    }
+
+    // 2. Copy it in
+    let count = copy_template(&src, dir_path, &name)?;
+    o_success!("   {} {} file(s) written to {}", "✔".green(), count, dir);
+
+    // 3. Check it deploys as-is
+    let ops_toml = dir_path.join("ops.toml");
+    if !ops_toml.exists() {
+        o_warn!("   {} The template has no ops.toml; run `ops launch` in {}", "⚠".yellow(), dir);
+        return Ok(());
+    }
+    let config = load_ops_toml(&ops_toml.to_string_lossy())?;
+    o_result!("\n{} Created {} from {}", "✅".green(), name.green(), template.cyan());
+    let missing: Vec<_> = config.env_files.iter().filter(|ef| !dir_path.join(&ef.local).exists()).collect();
+    if !missing.is_empty() {
+        o_result!("\nBefore deploying:");
+    }
+    for ef in missing {
+        o_result!("  • create {} (uploaded to {}/{})", ef.local.yellow(), config.deploy_path, ef.remote);
+    }
+    o_result!("\n  cd {} && ops deploy", dir);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_templates_and_copy() {
+        let repo = tempfile::tempdir().unwrap();
+        let tpl = repo.path().join("nextjs-postgres");
+        fs::create_dir_all(tpl.join("app")).unwrap();
+        fs::write(tpl.join("ops.toml"), "project = \"{{name}}\"\ndeploy_path = \"/opt/{{name}}\"\n").unwrap();
+        fs::write(tpl.join("app/page.tsx"), "export default () => null\n").unwrap();
+        fs::create_dir_all(repo.path().join("docs")).unwrap();
+
+        let templates = repo_templates(repo.path()).unwrap();
+        assert_eq!(templates.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), vec!["nextjs-postgres"]);
+
+        let out = tempfile::tempdir().unwrap();
+        let dst = out.path().join("shop");
+        assert_eq!(copy_template(&tpl, &dst, "shop").unwrap(), 2);
+        assert_eq!(fs::read_to_string(dst.join("ops.toml")).unwrap(), "project = \"shop\"\ndeploy_path = \"/opt/shop\"\n");
+        assert!(dst.join("app/page.tsx").exists());
+
+        fs::write(repo.path().join(INDEX_FILE), "[[templates]]\nname = \"pg\"\ndescription = \"Postgres\"\npath = \"nextjs-postgres\"\n").unwrap();
+        let templates = repo_templates(repo.path()).unwrap();
+        assert_eq!(templates[0].path.as_deref(), Some("nextjs-postgres"));
+    }
+}
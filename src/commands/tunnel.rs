@@ -1,13 +1,184 @@
-use crate::{api, config};
-use anyhow::{anyhow, Context, Result};
+//! `ops tunnel`: expose a local port at `https://<subdomain>.<project>...` through a reverse SSH
+//! tunnel to a node. Tunnels started here are kept in `<config dir>/ops/tunnels.json` so
+//! `ops tunnel list` can show them and `ops tunnel kill` can clean up after a detached or crashed one.
+//! While a tunnel runs it sends the backend a heartbeat every minute; `ops gc` removes the ones
+//! whose heartbeats stopped.
+
+use crate::error::{ApiErrorCode, OpsError};
+use crate::serve::gc::tunnel_conf_name;
+use crate::types::TunnelInfo;
+use crate::{api, config, output};
+use anyhow::{anyhow, bail, Context, Result};
 use colored::Colorize;
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fs;
 use std::io::Write;
-use std::os::unix::fs::PermissionsExt;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A tunnel started from this machine, recorded until it is closed or killed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct LocalTunnel {
+    id: i64,
+    /// subdomain.project
+    target: String,
+    domain: String,
+    node_id: u64,
+    remote_port: u16,
+    local_port: u16,
+    /// The `ssh -R` process
+    pid: u32,
+    /// Unix seconds
+    started_at: u64,
+}
+
+/// A row of `ops tunnel list`
+#[derive(Serialize, Debug, PartialEq)]
+struct TunnelRow {
+    id: i64,
+    domain: String,
+    node_id: u64,
+    local_port: Option<u16>,
+    /// running | orphaned (process gone, route and DNS left) | remote (not started here)
+    status: &'static str,
+    started_at: Option<u64>,
+}
+
+fn state_path() -> Result<PathBuf> {
+    config::tunnels_path().context("Could not find config directory")
+}
+
+fn load_local() -> Result<Vec<LocalTunnel>> {
+    let path = state_path()?;
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display())),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Read-modify-write tunnels.json under the lock
+fn update_local(f: impl FnOnce(&mut Vec<LocalTunnel>)) -> Result<()> {
+    let path = state_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    config::with_lock(&path, || {
+        let mut tunnels = load_local()?;
+        f(&mut tunnels);
+        config::write_atomic(&path, &serde_json::to_string_pretty(&tunnels)?)
+    })
+}
+
+/// Key and log files of a detached tunnel: `<config dir>/ops/tunnels/<id>.{key,log}`
+fn detached_file(id: i64, ext: &str) -> Result<PathBuf> {
+    let path = state_path()?;
+    let dir = path.with_extension("");
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir.join(format!("{}.{}", id, ext)))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn ssh_command(key_path: &str, ssh_target: &str) -> Command {
+    let mut cmd = Command::new("ssh");
+    cmd.arg("-i").arg(key_path)
+        .arg("-o").arg("StrictHostKeyChecking=no")
+        .arg("-o").arg("UserKnownHostsFile=/dev/null")
+        .arg("-o").arg("LogLevel=ERROR")
+        .arg(ssh_target);
+    cmd
+}
+
+/// The `ssh -R` process for a tunnel, everything but stdio
+fn reverse_ssh(key_path: &str, ssh_target: &str, remote_port: u16, local_port: u16) -> Command {
+    let mut cmd = ssh_command(key_path, ssh_target);
+    cmd.arg("-o").arg("ServerAliveInterval=15")
+        .arg("-o").arg("ServerAliveCountMax=3")
+        .arg("-o").arg("ExitOnForwardFailure=yes")
+        .arg("-N")
+        // "localhost" lets ssh try ::1 as well as 127.0.0.1, for dev servers bound to IPv6 only
+        .arg("-R").arg(format!("{}:localhost:{}", remote_port, local_port));
+    cmd
+}
+
+/// `ps` output of a live tunnel process: ssh with our -R forward (guards against reused pids)
+fn is_tunnel_process(command: &str, remote_port: u16) -> bool {
+    command.contains("ssh") && command.contains(&format!("-R {}:", remote_port))
+}
+
+fn is_running(t: &LocalTunnel) -> bool {
+    Command::new("ps")
+        .args(["-o", "command=", "-p", &t.pid.to_string()])
+        .output()
+        .is_ok_and(|o| o.status.success() && is_tunnel_process(&String::from_utf8_lossy(&o.stdout), t.remote_port))
+}
+
+/// Heartbeats until the task is dropped; a missed one only matters to `ops gc` after several
+async fn send_heartbeats(token: String, tunnel_id: i64) {
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = api::tunnel_heartbeat(&token, tunnel_id).await {
+            o_debug!("Tunnel heartbeat failed: {:#}", e);
+        }
+    }
+}
+
+/// A detached tunnel's heartbeats come from a background `ops tunnel __heartbeat <id>`
+fn spawn_heartbeat_process(tunnel_id: i64) -> Result<()> {
+    Command::new(std::env::current_exe()?)
+        .args(["tunnel", "__heartbeat", &tunnel_id.to_string()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .context("Failed to start the tunnel heartbeat")?;
+    Ok(())
+}
+
+/// ops tunnel __heartbeat <id>: heartbeats while the detached tunnel's ssh process runs
+pub async fn handle_heartbeat(id: i64) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+    let beating = tokio::spawn(send_heartbeats(token, id));
+    loop {
+        tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+        if !load_local()?.iter().any(|t| t.id == id && is_running(t)) {
+            beating.abort();
+            return Ok(());
+        }
+    }
+}
+
+/// Drop a tunnel's local record and its detached key and log
+pub fn forget(id: i64) -> Result<()> {
+    update_local(|tunnels| tunnels.retain(|t| t.id != id))?;
+    for ext in ["key", "log"] {
+        if let Ok(path) = detached_file(id, ext) {
+            let _ = fs::remove_file(path);
+        }
+    }
+    Ok(())
+}
+
+/// ops tunnel <subdomain.project> --port <port> --node <id> [--detach]
+pub async fn handle_tunnel(target: Option<String>, local_port: Option<u16>, node_id: Option<u64>, detach: bool) -> Result<()> {
+    let (Some(target), Some(local_port), Some(node_id)) = (target, local_port, node_id) else {
+        bail!("Usage: ops tunnel <subdomain.project> --port <port> --node <id>");
+    };
 
-pub async fn handle_tunnel(target: String, local_port: u16, node_id: u64) -> Result<()> {
     // 1. Parse target: "webhook.redq" -> subdomain + project
     let parts: Vec<&str> = target.split('.').collect();
     if parts.len() != 2 {
@@ -44,13 +215,22 @@ pub async fn handle_tunnel(target: String, local_port: u16, node_id: u64) -> Res
         }
     };
 
-    let mut temp_key_file = tempfile::NamedTempFile::new()?;
-    writeln!(temp_key_file, "{}", key_resp.private_key)?;
-    let meta = temp_key_file.as_file().metadata()?;
-    let mut perms = meta.permissions();
-    perms.set_mode(0o600);
-    temp_key_file.as_file().set_permissions(perms)?;
-    let key_path = temp_key_file.path().to_str().unwrap().to_string();
+    // A detached tunnel outlives this process, so its key can't be a temp file
+    let mut temp_key_file = None;
+    let key_path = if detach {
+        let path = detached_file(tunnel_id, "key")?;
+        let mut file = fs::OpenOptions::new().create(true).write(true).truncate(true).mode(0o600).open(&path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        writeln!(file, "{}", key_resp.private_key)?;
+        path.display().to_string()
+    } else {
+        let mut file = tempfile::NamedTempFile::new()?;
+        writeln!(file, "{}", key_resp.private_key)?;
+        file.as_file().set_permissions(fs::Permissions::from_mode(0o600))?;
+        let path = file.path().display().to_string();
+        temp_key_file = Some(file);
+        path
+    };
 
     let node_domain = format!("{}.node.ops.autos", node_id);
     let ssh_target = format!("root@{}", node_domain);
@@ -69,16 +249,11 @@ pub async fn handle_tunnel(target: String, local_port: u16, node_id: u64) -> Res
         port = remote_port,
     );
 
-    let conf_name = format!("ops-tunnel-{}-{}.caddy", subdomain, project_name);
+    let conf_name = tunnel_conf_name(subdomain, project_name);
 
     // Upload via SSH stdin
     let upload_cmd = format!("mkdir -p /etc/caddy/routes.d && cat > /etc/caddy/routes.d/{}", conf_name);
-    let mut child = Command::new("ssh")
-        .arg("-i").arg(&key_path)
-        .arg("-o").arg("StrictHostKeyChecking=no")
-        .arg("-o").arg("UserKnownHostsFile=/dev/null")
-        .arg("-o").arg("LogLevel=ERROR")
-        .arg(&ssh_target)
+    let mut child = ssh_command(&key_path, &ssh_target)
         .arg(&upload_cmd)
         .stdin(Stdio::piped())
         .stdout(Stdio::null())
@@ -95,12 +270,7 @@ pub async fn handle_tunnel(target: String, local_port: u16, node_id: u64) -> Res
 
     // Validate and reload Caddy
     let reload_cmd = "caddy validate --config /etc/caddy/Caddyfile && systemctl reload caddy";
-    let status = Command::new("ssh")
-        .arg("-i").arg(&key_path)
-        .arg("-o").arg("StrictHostKeyChecking=no")
-        .arg("-o").arg("UserKnownHostsFile=/dev/null")
-        .arg("-o").arg("LogLevel=ERROR")
-        .arg(&ssh_target)
+    let status = ssh_command(&key_path, &ssh_target)
         .arg(reload_cmd)
         .stdout(Stdio::null())
         .stderr(Stdio::null())
@@ -115,27 +285,68 @@ pub async fn handle_tunnel(target: String, local_port: u16, node_id: u64) -> Res
 
     // SSL is handled by Cloudflare — always use https
     let protocol = "https";
+    let mut record = LocalTunnel {
+        id: tunnel_id,
+        target: target_header.clone(),
+        domain: domain.clone(),
+        node_id,
+        remote_port,
+        local_port,
+        pid: 0,
+        started_at: now_secs(),
+    };
+
+    if detach {
+        // 7. Start the reverse tunnel in its own process group, so Ctrl+C in this terminal doesn't reach it
+        let log_path = detached_file(tunnel_id, "log")?;
+        let log = fs::File::create(&log_path).with_context(|| format!("Failed to create {}", log_path.display()))?;
+        let mut ssh_child = reverse_ssh(&key_path, &ssh_target, remote_port, local_port)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(log)
+            .process_group(0)
+            .spawn()
+            .context("Failed to start SSH reverse tunnel")?;
+
+        // A refused forward or auth failure shows up within a few seconds
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        if let Some(status) = ssh_child.try_wait()? {
+            let _ = cleanup_caddy(&key_path, &ssh_target, &conf_name);
+            let _ = api::delete_tunnel(&token, tunnel_id).await;
+            let log = fs::read_to_string(&log_path).unwrap_or_default();
+            let _ = fs::remove_file(&key_path);
+            let _ = fs::remove_file(&log_path);
+            bail!("SSH tunnel exited ({}): {}", status, log.trim());
+        }
+
+        record.pid = ssh_child.id();
+        update_local(|tunnels| tunnels.push(record))?;
+        if let Err(e) = spawn_heartbeat_process(tunnel_id) {
+            o_warn!("   {} {:#}; `ops gc` may remove this tunnel", "⚠".yellow(), e);
+        }
+        o_result!("\n   {} {}", "Tunnel URL:".green().bold(), format!("{}://{}", protocol, domain).cyan().bold());
+        o_result!("   {} localhost:{}", "Forwarding →".green(), local_port);
+        o_detail!("   Running in the background as #{}. Stop it with {}\n", tunnel_id, format!("ops tunnel kill {}", tunnel_id).yellow().bold());
+        return Ok(());
+    }
 
     // 7. Open SSH reverse tunnel
     o_result!("\n   {} {}", "Tunnel URL:".green().bold(), format!("{}://{}", protocol, domain).cyan().bold());
     o_result!("   {} localhost:{}\n", "Forwarding →".green(), local_port);
     o_detail!("   Press {} to stop the tunnel\n", "Ctrl+C".yellow().bold());
 
-    let ssh_child = Command::new("ssh")
-        .arg("-i").arg(&key_path)
-        .arg("-o").arg("StrictHostKeyChecking=no")
-        .arg("-o").arg("UserKnownHostsFile=/dev/null")
-        .arg("-o").arg("LogLevel=ERROR")
-        .arg("-o").arg("ServerAliveInterval=15")
-        .arg("-o").arg("ServerAliveCountMax=3")
-        .arg("-N")
-        .arg("-R").arg(format!("{}:127.0.0.1:{}", remote_port, local_port))
-        .arg(&ssh_target)
+    let ssh_child = reverse_ssh(&key_path, &ssh_target, remote_port, local_port)
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
         .spawn()
         .context("Failed to start SSH reverse tunnel")?;
 
+    // Recorded so `ops tunnel kill` can clean up if this process dies without reaching step 9
+    record.pid = ssh_child.id();
+    if let Err(e) = update_local(|tunnels| tunnels.push(record)) {
+        o_debug!("Failed to record tunnel: {:#}", e);
+    }
+
     let ssh_child = Arc::new(Mutex::new(ssh_child));
 
     // 8. Wait for Ctrl+C or SSH exit
@@ -144,6 +355,7 @@ pub async fn handle_tunnel(target: String, local_port: u16, node_id: u64) -> Res
     let ssh_target_clone = ssh_target.clone();
 
     let child_for_wait = Arc::clone(&ssh_child);
+    let heartbeats = tokio::spawn(send_heartbeats(token.clone(), tunnel_id));
     tokio::select! {
         _ = tokio::signal::ctrl_c() => {
             o_step!("\n{}", "Shutting down tunnel...".yellow());
@@ -168,31 +380,186 @@ pub async fn handle_tunnel(target: String, local_port: u16, node_id: u64) -> Res
         }
     }
 
+    heartbeats.abort();
+
     // 9. Cleanup
     o_detail!("   Removing Caddy route...");
     let _ = cleanup_caddy(&key_path_clone, &ssh_target_clone, &conf_name_clone);
 
     o_detail!("   Removing DNS record...");
     let _ = api::delete_tunnel(&token, tunnel_id).await;
+    let _ = update_local(|tunnels| tunnels.retain(|t| t.id != tunnel_id));
+    drop(temp_key_file);
 
     o_result!("{}", "Tunnel closed.".green());
     Ok(())
 }
 
+/// Local records and backend tunnels as one list, ordered by id
+fn merge_rows(local: &[(LocalTunnel, bool)], remote: &[TunnelInfo]) -> Vec<TunnelRow> {
+    let mut rows: Vec<TunnelRow> = local.iter().map(|(t, running)| TunnelRow {
+        id: t.id,
+        domain: t.domain.clone(),
+        node_id: t.node_id,
+        local_port: Some(t.local_port),
+        status: if *running { "running" } else { "orphaned" },
+        started_at: Some(t.started_at),
+    }).collect();
+    for r in remote {
+        if !rows.iter().any(|row| row.id == r.id) {
+            rows.push(TunnelRow { id: r.id, domain: r.domain.clone(), node_id: r.node_id, local_port: None, status: "remote", started_at: None });
+        }
+    }
+    rows.sort_by_key(|r| r.id);
+    rows
+}
+
+/// ops tunnel list
+pub async fn handle_list(opts: &crate::table::TableOptions) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let local: Vec<(LocalTunnel, bool)> = load_local()?.into_iter().map(|t| {
+        let running = is_running(&t);
+        (t, running)
+    }).collect();
+    let remote = match api::list_tunnels(&token).await {
+        Ok(resp) => resp.tunnels,
+        Err(e) => {
+            o_warn!("{} Could not list tunnels from the backend: {:#}", "⚠".yellow(), e);
+            Vec::new()
+        }
+    };
+    let rows = merge_rows(&local, &remote);
+    if output::json() {
+        return output::print_json(&serde_json::json!({ "tunnels": rows }));
+    }
+    if rows.is_empty() {
+        o_result!("No tunnels. Start one with `ops tunnel <subdomain.project> --port <port> --node <id>`.");
+        return Ok(());
+    }
+
+    use crate::table::{Column, Table};
+    let mut table = Table::new(vec![
+        Column::new("id").right(), Column::new("url"), Column::new("forward"), Column::new("node").right(),
+        Column::new("status"), Column::wide("age"),
+    ]);
+    let now = now_secs();
+    for r in &rows {
+        let status = match r.status {
+            "running" => r.status.green().to_string(),
+            "orphaned" => r.status.red().to_string(),
+            _ => r.status.dimmed().to_string(),
+        };
+        let age = r.started_at
+            .map(|s| humantime::format_duration(Duration::from_secs(now.saturating_sub(s) / 60 * 60)).to_string())
+            .unwrap_or_else(|| "-".into());
+        table.row(vec![
+            r.id.to_string(),
+            format!("https://{}", r.domain),
+            r.local_port.map(|p| format!("localhost:{}", p)).unwrap_or_else(|| "-".into()),
+            r.node_id.to_string(),
+            status,
+            age,
+        ]);
+    }
+    table.print(opts)?;
+    if rows.iter().any(|r| r.status != "running") {
+        o_detail!("\n  {}", "Remove orphaned or remote tunnels with `ops tunnel kill <id>`.".dimmed());
+    }
+    Ok(())
+}
+
+/// ops tunnel kill <id>: stop the ssh process if it runs here, then remove the node route and the DNS record
+pub async fn handle_kill(id: i64) -> Result<()> {
+    let cfg = config::load_config().context("Config error")?;
+    let token = cfg.token.context("Please run `ops login` first.")?;
+
+    let local = load_local()?.into_iter().find(|t| t.id == id);
+    let remote = match api::list_tunnels(&token).await {
+        Ok(resp) => resp.tunnels.into_iter().find(|t| t.id == id),
+        Err(e) => {
+            o_debug!("Failed to list tunnels: {:#}", e);
+            None
+        }
+    };
+    let (node_id, subdomain, project) = match (&local, &remote) {
+        (Some(t), _) => {
+            let (subdomain, project) = t.target.split_once('.').unwrap_or((&t.target, ""));
+            (t.node_id, subdomain.to_string(), project.to_string())
+        }
+        (None, Some(r)) => (r.node_id, r.subdomain.clone(), r.project_name.clone()),
+        (None, None) => bail!("No tunnel #{}. Run `ops tunnel list` to see them.", id),
+    };
+
+    o_step!("{} #{}", "Killing tunnel".cyan(), id);
+    if let Some(t) = local.as_ref().filter(|t| is_running(t)) {
+        signal::kill(Pid::from_raw(t.pid as i32), Signal::SIGTERM)
+            .with_context(|| format!("Failed to stop ssh (pid {})", t.pid))?;
+        o_success!("   {} Stopped ssh (pid {})", "✔".green(), t.pid);
+    }
+
+    o_detail!("   Removing Caddy route...");
+    let key_resp = api::get_node_ci_key(&token, node_id).await.context("Failed to fetch CI key for node")?;
+    let mut key_file = tempfile::NamedTempFile::new()?;
+    writeln!(key_file, "{}", key_resp.private_key)?;
+    key_file.as_file().set_permissions(fs::Permissions::from_mode(0o600))?;
+    let key_path = key_file.path().display().to_string();
+    cleanup_caddy(&key_path, &format!("root@{}.node.ops.autos", node_id), &tunnel_conf_name(&subdomain, &project))
+        .context("Failed to remove the Caddy route")?;
+
+    o_detail!("   Removing DNS record...");
+    match api::delete_tunnel(&token, id).await {
+        Ok(_) => {}
+        // Already removed, e.g. by the foreground process we just stopped
+        Err(e) if matches!(OpsError::api_code(&e), Some(ApiErrorCode::NotFound)) => {}
+        Err(e) => return Err(e.context("Failed to delete tunnel")),
+    }
+
+    forget(id)?;
+    o_result!("{} Tunnel #{} removed", "✔".green(), id);
+    Ok(())
+}
+
 fn cleanup_caddy(key_path: &str, ssh_target: &str, conf_name: &str) -> Result<()> {
     let cmd = format!(
         "rm -f /etc/caddy/routes.d/{} && caddy validate --config /etc/caddy/Caddyfile && systemctl reload caddy",
         conf_name,
     );
-    Command::new("ssh")
-        .arg("-i").arg(key_path)
-        .arg("-o").arg("StrictHostKeyChecking=no")
-        .arg("-o").arg("UserKnownHostsFile=/dev/null")
-        .arg("-o").arg("LogLevel=ERROR")
-        .arg(ssh_target)
+    let status = ssh_command(key_path, ssh_target)
         .arg(&cmd)
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .status()?;
+    if !status.success() {
+        bail!("`{}` failed on {}", cmd, ssh_target);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(id: i64, remote_port: u16) -> LocalTunnel {
+        LocalTunnel {
+            id, target: "hook.redq".into(), domain: format!("hook{}.redq.ops.autos", id), node_id: 7,
+            remote_port, local_port: 3000, pid: 4242, started_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_tunnel_rows() {
+        let ps = "ssh -i /tmp/k -o LogLevel=ERROR root@7.node.ops.autos -N -R 20000:localhost:3000\n";
+        assert!(is_tunnel_process(ps, 20000));
+        assert!(!is_tunnel_process(ps, 2000));
+
+        let remote = vec![
+            TunnelInfo { id: 3, subdomain: "hook".into(), project_name: "redq".into(), node_id: 7, domain: "hook3.redq.ops.autos".into(), last_heartbeat_at: None },
+            TunnelInfo { id: 1, subdomain: "api".into(), project_name: "redq".into(), node_id: 9, domain: "api.redq.ops.autos".into(), last_heartbeat_at: None },
+        ];
+        let rows = merge_rows(&[(local(3, 20000), true), (local(5, 20002), false)], &remote);
+        let summary: Vec<(i64, &str, Option<u16>)> = rows.iter().map(|r| (r.id, r.status, r.local_port)).collect();
+        assert_eq!(summary, vec![(1, "remote", None), (3, "running", Some(3000)), (5, "orphaned", Some(3000))]);
+    }
+}
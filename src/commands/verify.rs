@@ -0,0 +1,234 @@
+//! `ops verify`: the `[[verify]]` assertions of ops.toml, run against the deployed app. Prints a
+//! pass/fail report and exits non-zero on any failure, so CI can gate on it.
+
+use crate::commands::deploy::{load_ops_toml, resolve_targets};
+use crate::commands::ssh::SshSession;
+use crate::output;
+use crate::types::{DeployTarget, OpsToml, VerifyCheck};
+use crate::utils::shell_quote;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::time::Duration;
+
+#[derive(Debug, PartialEq)]
+enum Assertion<'a> {
+    /// GET from this machine
+    Url { url: &'a str, status: u16, contains: Option<&'a str> },
+    /// Running containers of a service on each node
+    Containers { service: &'a str, count: usize },
+    /// Text in a service's recent logs on each node
+    Logs { service: &'a str, needle: &'a str, since: &'a str },
+    /// The domain resolves to one of the app's nodes
+    Dns { domain: &'a str },
+}
+
+impl Assertion<'_> {
+    fn on_node(&self) -> bool {
+        matches!(self, Assertion::Containers { .. } | Assertion::Logs { .. })
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Assertion::Url { url, status, .. } => format!("GET {} → {}", url, status),
+            Assertion::Containers { service, count } => format!("{} × {}", service, count),
+            Assertion::Logs { service, needle, .. } => format!("{} logs ~ \"{}\"", service, needle),
+            Assertion::Dns { domain } => format!("dns {}", domain),
+        }
+    }
+}
+
+fn assertion(check: &VerifyCheck) -> Result<Assertion<'_>> {
+    let kinds = [check.url.is_some(), check.containers.is_some(), check.logs.is_some(), check.dns.is_some()];
+    if kinds.iter().filter(|k| **k).count() != 1 {
+        bail!("set exactly one of url, containers, logs or dns");
+    }
+    Ok(if let Some(url) = &check.url {
+        Assertion::Url { url, status: check.status.unwrap_or(200), contains: check.contains.as_deref() }
+    } else if let Some(service) = &check.containers {
+        Assertion::Containers { service, count: check.count.unwrap_or(1) }
+    } else if let Some(service) = &check.logs {
+        let needle = check.contains.as_deref().context("logs needs `contains`")?;
+        Assertion::Logs { service, needle, since: check.since.as_deref().unwrap_or("10m") }
+    } else {
+        Assertion::Dns { domain: check.dns.as_deref().unwrap_or_default() }
+    })
+}
+
+/// One line of the report; node-side checks give one line per node
+#[derive(Serialize, Debug)]
+pub struct Outcome {
+    pub name: String,
+    pub node: Option<String>,
+    pub passed: bool,
+    pub detail: String,
+}
+
+async fn check_url(url: &str, status: u16, contains: Option<&str>) -> (bool, String) {
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(10)).build() {
+        Ok(c) => c,
+        Err(e) => return (false, e.to_string()),
+    };
+    let resp = match client.get(url).send().await {
+        Ok(r) => r,
+        Err(e) => return (false, e.to_string()),
+    };
+    let code = resp.status().as_u16();
+    if code != status {
+        return (false, format!("{} (expected {})", code, status));
+    }
+    match contains {
+        Some(text) if !resp.text().await.unwrap_or_default().contains(text) => (false, format!("{}, body lacks \"{}\"", code, text)),
+        _ => (true, code.to_string()),
+    }
+}
+
+async fn check_dns(domain: &str, targets: &[DeployTarget]) -> (bool, String) {
+    let resolved: Vec<IpAddr> = match tokio::net::lookup_host((domain, 443)).await {
+        Ok(addrs) => addrs.map(|a| a.ip()).collect(),
+        Err(e) => return (false, e.to_string()),
+    };
+    let node_ips: Vec<IpAddr> = targets.iter()
+        .flat_map(|t| std::iter::once(&t.ip_address).chain(t.ipv6_address.as_ref()))
+        .filter_map(|ip| ip.parse().ok())
+        .collect();
+    let list = resolved.iter().map(|ip| ip.to_string()).collect::<Vec<_>>().join(", ");
+    if resolved.iter().any(|ip| node_ips.contains(ip)) {
+        (true, format!("→ {}", list))
+    } else {
+        (false, format!("→ {}, not a node of this app", list))
+    }
+}
+
+fn check_on_node(config: &OpsToml, session: &SshSession, assertion: &Assertion) -> (bool, String) {
+    let containers = |service: &str| format!("docker ps -q --filter 'name=^{}-{}-[0-9]+$'", config.compose_project(), service);
+    let cmd = match assertion {
+        Assertion::Containers { service, .. } => format!("{} | wc -l", containers(service)),
+        Assertion::Logs { service, needle, since } => format!(
+            "for c in $({}); do docker logs --since {} \"$c\" 2>&1; done | grep -cF -- {}; true",
+            containers(service), shell_quote(since), shell_quote(needle)
+        ),
+        _ => return (false, "not a node check".into()),
+    };
+    let found: usize = match session.exec_output(&cmd) {
+        Ok(out) => String::from_utf8_lossy(&out).trim().parse().unwrap_or(0),
+        Err(e) => return (false, format!("{:#}", e)),
+    };
+    match assertion {
+        Assertion::Containers { count, .. } if found == *count => (true, format!("{} running", found)),
+        Assertion::Containers { count, .. } => (false, format!("{} running (expected {})", found, count)),
+        Assertion::Logs { .. } if found > 0 => (true, format!("{} matching line(s)", found)),
+        Assertion::Logs { since, .. } => (false, format!("not in logs of the last {}", since)),
+        _ => unreachable!(),
+    }
+}
+
+/// Run every [[verify]] check: URL and DNS once from here, container and log checks on each target
+pub async fn run(config: &OpsToml, targets: &[DeployTarget]) -> Result<Vec<Outcome>> {
+    let checks = config.verify.iter().enumerate()
+        .map(|(i, c)| {
+            let a = assertion(c).with_context(|| format!("[[verify]] #{}", i + 1))?;
+            Ok((c.name.clone().unwrap_or_else(|| a.label()), a))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut outcomes = Vec::new();
+    for (name, a) in &checks {
+        let (passed, detail) = match a {
+            Assertion::Url { url, status, contains } => check_url(url, *status, *contains).await,
+            Assertion::Dns { domain } => check_dns(domain, targets).await,
+            _ => continue,
+        };
+        outcomes.push(Outcome { name: name.clone(), node: None, passed, detail });
+    }
+
+    let node_checks: Vec<_> = checks.iter().filter(|(_, a)| a.on_node()).collect();
+    if node_checks.is_empty() {
+        return Ok(outcomes);
+    }
+    for t in targets {
+        let session = SshSession::connect(&t.node_id.to_string()).await;
+        for (name, a) in &node_checks {
+            let (passed, detail) = match &session {
+                Ok(session) => check_on_node(config, session, a),
+                Err(e) => (false, format!("{:#}", e)),
+            };
+            outcomes.push(Outcome { name: name.clone(), node: Some(t.domain.clone()), passed, detail });
+        }
+    }
+    Ok(outcomes)
+}
+
+/// Print the report (table or JSON); Err when any check failed
+pub fn report(outcomes: &[Outcome], json: bool) -> Result<()> {
+    let failed = outcomes.iter().filter(|o| !o.passed).count();
+    if json {
+        let out = serde_json::json!({ "passed": failed == 0, "checks": outcomes });
+        println!("{}", serde_json::to_string_pretty(&out)?);
+    } else {
+        o_step!("\n{}", "🔎 Verify:".cyan());
+        for o in outcomes {
+            let node = o.node.as_deref().unwrap_or("-");
+            if o.passed {
+                o_success!("   {} {:<36} {:<28} {}", "✔".green(), o.name, node, o.detail.dimmed());
+            } else {
+                o_error!("   {} {:<36} {:<28} {}", "✘".red(), o.name.red(), node, o.detail.red());
+            }
+        }
+        let icon = if failed == 0 { "✅".green() } else { "❌".red() };
+        o_result!("\n{} {}/{} checks passed", icon, outcomes.len() - failed, outcomes.len());
+    }
+    if failed > 0 {
+        bail!("{} verify check(s) failed", failed);
+    }
+    Ok(())
+}
+
+/// ops verify
+pub async fn handle_verify(file: String, app_filter: Option<String>) -> Result<()> {
+    let config = load_ops_toml(&file)?;
+    if config.verify.is_empty() {
+        bail!("No [[verify]] checks in {}", file);
+    }
+    let targets = resolve_targets(&config, &app_filter).await?;
+    let outcomes = run(&config, &targets).await?;
+    report(&outcomes, output::json())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assertion() {
+        let config: OpsToml = toml::from_str(r#"
+            project = "shop"
+            deploy_path = "/opt/shop"
+            [deploy]
+            [[verify]]
+            url = "https://shop.example.com/health"
+            [[verify]]
+            name = "two api replicas"
+            containers = "api"
+            count = 2
+            [[verify]]
+            logs = "api"
+            contains = "listening"
+            [[verify]]
+            dns = "shop.example.com"
+            [[verify]]
+            logs = "api"
+            [[verify]]
+            url = "https://shop.example.com"
+            dns = "shop.example.com"
+        "#).unwrap();
+        let v = &config.verify;
+        assert_eq!(assertion(&v[0]).unwrap(), Assertion::Url { url: "https://shop.example.com/health", status: 200, contains: None });
+        assert_eq!(assertion(&v[1]).unwrap(), Assertion::Containers { service: "api", count: 2 });
+        assert_eq!(assertion(&v[2]).unwrap().label(), "api logs ~ \"listening\"");
+        assert_eq!(assertion(&v[3]).unwrap(), Assertion::Dns { domain: "shop.example.com" });
+        assert!(assertion(&v[4]).is_err());
+        assert!(assertion(&v[5]).is_err());
+    }
+}
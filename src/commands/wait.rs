@@ -0,0 +1,160 @@
+//! `ops wait`: block until a deployment finishes, an app is healthy or a node is back, so
+//! scripts can chain build → deploy → wait → smoke test. Exits non-zero on failure or timeout.
+
+use crate::types::DeployTarget;
+use crate::{api, config};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::time::{Duration, Instant};
+
+/// What one poll found
+#[derive(Debug, PartialEq)]
+enum Poll {
+    Done(String),
+    Pending(String),
+    Failed(String),
+}
+
+enum Condition {
+    Deployment(i64),
+    Healthy { app: String, project: String },
+    Node(u64),
+}
+
+impl Condition {
+    fn describe(&self) -> String {
+        match self {
+            Condition::Deployment(id) => format!("deployment #{}", id),
+            Condition::Healthy { app, project } => format!("{}.{} to be healthy", app, project),
+            Condition::Node(id) => format!("node #{}", id),
+        }
+    }
+}
+
+fn deployment_state(status: &str, logs: Option<&str>) -> Poll {
+    match status {
+        "success" => Poll::Done("deployment succeeded".into()),
+        "failed" => {
+            let tail: Vec<&str> = logs.unwrap_or("").lines().rev().take(10).collect();
+            let tail: Vec<&str> = tail.into_iter().rev().collect();
+            if tail.is_empty() {
+                Poll::Failed("deployment failed".into())
+            } else {
+                Poll::Failed(format!("deployment failed:\n{}", tail.join("\n")))
+            }
+        }
+        other => Poll::Pending(format!("deployment {}", other)),
+    }
+}
+
+/// All targets that aren't draining must be healthy, and there must be at least one
+fn targets_state(targets: &[DeployTarget]) -> Poll {
+    let active: Vec<&DeployTarget> = targets.iter().filter(|t| t.status != "draining").collect();
+    if active.is_empty() {
+        return Poll::Pending("no active nodes".into());
+    }
+    let unhealthy: Vec<String> = active.iter()
+        .filter(|t| t.status != "healthy")
+        .map(|t| format!("node {} {}", t.node_id, t.status))
+        .collect();
+    if unhealthy.is_empty() {
+        Poll::Done(format!("{} node(s) healthy", active.len()))
+    } else {
+        Poll::Pending(unhealthy.join(", "))
+    }
+}
+
+async fn check(token: &str, condition: &Condition) -> Result<Poll> {
+    match condition {
+        Condition::Deployment(id) => {
+            let deployment = api::get_deployment(token, *id).await.context("Failed to get deployment")?;
+            Ok(deployment_state(&deployment.status, deployment.logs.as_deref()))
+        }
+        Condition::Healthy { app, project } => {
+            let targets = api::get_app_deploy_targets(token, project, app).await.context("Failed to get deploy targets")?;
+            let state = targets_state(&targets.targets);
+            if !matches!(state, Poll::Done(_)) {
+                return Ok(state);
+            }
+            // The nodes are up; the app must answer through its domain too
+            let url = format!("https://{}.{}.ops.autos/", app, project);
+            let client = reqwest::Client::builder().timeout(Duration::from_secs(10)).build()?;
+            Ok(match client.get(&url).send().await {
+                Ok(res) if res.status().as_u16() < 500 => Poll::Done(format!("{} → {}", url, res.status().as_u16())),
+                Ok(res) => Poll::Pending(format!("{} → {}", url, res.status().as_u16())),
+                Err(e) if e.is_timeout() => Poll::Pending(format!("{} → timeout", url)),
+                Err(_) => Poll::Pending(format!("{} → connect failed", url)),
+            })
+        }
+        Condition::Node(id) => {
+            let node = api::get_node(token, *id).await.context("Failed to get node")?;
+            Ok(match node.status.as_str() {
+                "healthy" => Poll::Done("node healthy".into()),
+                other => Poll::Pending(format!("node {}", other)),
+            })
+        }
+    }
+}
+
+/// ops wait --deployment <id> | --healthy <app.project> | --node <id>
+pub async fn handle_wait(deployment: Option<i64>, healthy: Option<String>, node: Option<u64>, timeout: u64, interval: u64) -> Result<()> {
+    let condition = match (deployment, healthy, node) {
+        (Some(id), _, _) => Condition::Deployment(id),
+        (_, Some(target), _) => {
+            let (app, project) = target.split_once('.')
+                .with_context(|| format!("Invalid target '{}'. Expected app.project (e.g., api.RedQ)", target))?;
+            Condition::Healthy { app: app.to_string(), project: project.to_string() }
+        }
+        (_, _, Some(id)) => Condition::Node(id),
+        _ => bail!("Give one of --deployment, --healthy or --node"),
+    };
+    let token = config::load_config().context("Config error")?.token.context("Please run `ops login` first.")?;
+
+    o_step!("{} {} (timeout {}s)", "⏳ Waiting for".cyan(), condition.describe(), timeout);
+    let start = Instant::now();
+    let deadline = start + Duration::from_secs(timeout);
+    let mut last = String::new();
+    loop {
+        match check(&token, &condition).await? {
+            Poll::Done(msg) => {
+                o_success!("{} {} ({}s)", "✔".green(), msg, start.elapsed().as_secs());
+                return Ok(());
+            }
+            Poll::Failed(msg) => bail!("{}", msg),
+            Poll::Pending(msg) => {
+                if msg != last {
+                    o_detail!("   {} {}", "…".dimmed(), msg);
+                    last = msg;
+                }
+            }
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            bail!("Timed out after {}s waiting for {} (last: {})", timeout, condition.describe(), last);
+        }
+        tokio::time::sleep(Duration::from_secs(interval.max(1)).min(deadline - now)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(node_id: i64, status: &str) -> DeployTarget {
+        DeployTarget {
+            node_id, domain: format!("{}.node.ops.autos", node_id), ip_address: "203.0.113.1".into(), ipv6_address: None,
+            hostname: None, region: None, zone: None, weight: 100, is_primary: node_id == 1, status: status.into(),
+        }
+    }
+
+    #[test]
+    fn test_poll_states() {
+        assert_eq!(deployment_state("success", None), Poll::Done("deployment succeeded".into()));
+        assert_eq!(deployment_state("running", None), Poll::Pending("deployment running".into()));
+        assert_eq!(deployment_state("failed", Some("pull ok\nerror: exit 1\n")), Poll::Failed("deployment failed:\npull ok\nerror: exit 1".into()));
+
+        assert_eq!(targets_state(&[]), Poll::Pending("no active nodes".into()));
+        assert_eq!(targets_state(&[target(1, "healthy"), target(2, "draining")]), Poll::Done("1 node(s) healthy".into()));
+        assert_eq!(targets_state(&[target(1, "healthy"), target(2, "unhealthy")]), Poll::Pending("node 2 unhealthy".into()));
+    }
+}
@@ -1,7 +1,12 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use anyhow::{Context, Result};
+use colored::Colorize;
+use crate::keychain;
+use nix::fcntl::{Flock, FlockArg};
 use std::env; // 引入 env
 
 const CONFIG_DIR: &str = "ops";
@@ -10,9 +15,13 @@ const CONFIG_FILE: &str = "credentials.json";
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Config {
     pub token: Option<String>,
+    /// API token for `ops domain cloudflare`, saved by `ops domain cloudflare token` when there
+    /// is no keychain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloudflare_token: Option<String>,
 }
 
-fn get_config_path() -> Result<PathBuf> {
+pub fn get_config_path() -> Result<PathBuf> {
     let config_dir = dirs::config_dir()
         .context("Could not find config directory")?
         .join(CONFIG_DIR);
@@ -22,26 +31,140 @@ fn get_config_path() -> Result<PathBuf> {
     Ok(config_dir.join(CONFIG_FILE))
 }
 
-pub fn save_config(config: &Config) -> Result<()> {
+/// Team presets for `ops launch --preset <name>`: `<config dir>/ops/presets/<name>.toml`
+pub fn preset_path(name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join(CONFIG_DIR).join("presets").join(format!("{}.toml", name)))
+}
+
+/// Progress of `ops node migrate <from> <to>`: `<config dir>/ops/migrations/<from>-to-<to>.json`
+pub fn migration_path(from: u64, to: u64) -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join(CONFIG_DIR).join("migrations").join(format!("{}-to-{}.json", from, to)))
+}
+
+/// CI keys written by `ops ssh-config export`: `<config dir>/ops/ssh/`
+pub fn ssh_key_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join(CONFIG_DIR).join("ssh"))
+}
+
+/// Tunnels started on this machine: `<config dir>/ops/tunnels.json`, detached ones' files in `tunnels/`
+pub fn tunnels_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join(CONFIG_DIR).join("tunnels.json"))
+}
+
+/// Names `ops __complete` fetched from the API: `<config dir>/ops/completion-cache.json`
+pub fn completion_cache_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join(CONFIG_DIR).join("completion-cache.json"))
+}
+
+/// Hold an exclusive lock on `path` with a `.lock` extension while `f` runs. The lock lives in a separate file
+/// because `write_atomic` replaces `path` itself.
+pub fn with_lock<T>(path: &Path, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let lock_path = path.with_extension("lock");
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open {}", lock_path.display()))?;
+    let _lock = Flock::lock(file, FlockArg::LockExclusive)
+        .map_err(|(_, e)| e)
+        .with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+    f()
+}
+
+/// Write via a 0600 temp file in the same directory and rename it over `path`,
+/// so readers see either the old or the new content, never a partial write
+pub fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let dir = path.parent().context("Path has no parent directory")?;
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to create temp file in {}", dir.display()))?;
+    tmp.as_file().set_permissions(fs::Permissions::from_mode(0o600))?;
+    tmp.write_all(content.as_bytes())?;
+    tmp.as_file().sync_all()?;
+    tmp.persist(path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Read-modify-write credentials.json under the lock, so concurrent ops processes don't lose updates.
+/// Works on the file only: OPS_TOKEN is never written back.
+pub fn update_config(f: impl FnOnce(&mut Config)) -> Result<()> {
     let path = get_config_path()?;
-    let content = serde_json::to_string_pretty(config)?;
-    fs::write(path, content).context("Failed to write config file")
+    with_lock(&path, || {
+        let mut config = read_config_file(&path)?;
+        f(&mut config);
+        write_atomic(&path, &serde_json::to_string_pretty(&config)?).context("Failed to write config file")
+    })
+}
+
+fn read_config_file(path: &Path) -> Result<Config> {
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let content = fs::read_to_string(path).context("Failed to read config file")?;
+    serde_json::from_str(&content).context("Failed to parse config file")
 }
 
 pub fn load_config() -> Result<Config> {
     // 1. 优先检查环境变量
     if let Ok(token) = env::var("OPS_TOKEN") {
         if !token.is_empty() {
-            return Ok(Config { token: Some(token) });
+            return Ok(Config { token: Some(token), ..Default::default() });
         }
     }
 
-    // 2. 其次读取文件
-    let path = get_config_path()?;
-    if !path.exists() {
-        return Ok(Config::default());
+    // 2. 其次读取文件，token 不在文件里时查系统钥匙串
+    let mut config = read_config_file(&get_config_path()?)?;
+    if config.token.is_none() {
+        config.token = keychain::get(keychain::TOKEN);
     }
-    
-    let content = fs::read_to_string(path).context("Failed to read config file")?;
-    serde_json::from_str(&content).context("Failed to parse config file")
-}
\ No newline at end of file
+    Ok(config)
+}
+
+/// Save the login token in the OS keychain, or credentials.json when there is none (or it fails).
+/// Returns where it went.
+pub fn save_token(token: String) -> Result<&'static str> {
+    save_secret(keychain::TOKEN, token, |cfg| &mut cfg.token)
+}
+
+/// Save the `ops domain cloudflare` API token like save_token
+pub fn save_cloudflare_token(token: String) -> Result<&'static str> {
+    save_secret(keychain::CLOUDFLARE_TOKEN, token, |cfg| &mut cfg.cloudflare_token)
+}
+
+fn save_secret(account: &str, secret: String, field: fn(&mut Config) -> &mut Option<String>) -> Result<&'static str> {
+    match keychain::set(account, &secret) {
+        Ok(true) => {
+            // A plaintext copy from before would otherwise shadow the keychain
+            update_config(|cfg| *field(cfg) = None)?;
+            return Ok(keychain::name().unwrap_or("keychain"));
+        }
+        Ok(false) => {}
+        Err(e) => o_warn!("{} ({:#}), saving to {} instead", "⚠ Keychain unavailable".yellow(), e, CONFIG_FILE),
+    }
+    update_config(|cfg| *field(cfg) = Some(secret))?;
+    Ok(CONFIG_FILE)
+}
+
+/// Remove the login and Cloudflare tokens from both credentials.json and the keychain; whether
+/// any of them held the login token
+pub fn clear_token() -> Result<bool> {
+    let mut in_file = false;
+    update_config(|cfg| {
+        in_file = cfg.token.take().is_some();
+        cfg.cloudflare_token = None;
+    })?;
+    let in_keychain = keychain::delete(keychain::TOKEN)?;
+    keychain::delete(keychain::CLOUDFLARE_TOKEN)?;
+    Ok(in_file || in_keychain)
+}
+
+/// Cloudflare API token: CLOUDFLARE_API_TOKEN, else the one saved in credentials.json or the keychain
+pub fn cloudflare_token() -> Result<Option<String>> {
+    if let Ok(token) = env::var("CLOUDFLARE_API_TOKEN") {
+        if !token.is_empty() {
+            return Ok(Some(token));
+        }
+    }
+    let saved = read_config_file(&get_config_path()?)?.cloudflare_token;
+    Ok(saved.or_else(|| keychain::get(keychain::CLOUDFLARE_TOKEN)))
+}
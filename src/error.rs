@@ -0,0 +1,103 @@
+// src/error.rs
+//! Typed errors that callers branch on, instead of matching on message text (which the backend may reword)
+
+use std::fmt;
+
+/// Machine-readable `code` of an API error response
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiErrorCode {
+    Unauthorized,
+    /// Logged in, but the account lacks the project/app role the operation needs
+    PermissionDenied,
+    NotFound,
+    Conflict,
+    /// `ops init` on a server whose IP is already a node
+    NodeAlreadyRegistered,
+    /// `server whoami` from a server that isn't bound to a project
+    ServerUnbound,
+    DomainExists,
+    Other(String),
+    /// No code and no telling HTTP status
+    Unknown,
+}
+
+impl ApiErrorCode {
+    /// Parse the response `code`; backends that don't send one fall back to the HTTP status
+    pub fn from_response(code: Option<&str>, status: u16) -> Self {
+        match code {
+            Some("unauthorized") => ApiErrorCode::Unauthorized,
+            Some("permission_denied") => ApiErrorCode::PermissionDenied,
+            Some("not_found") => ApiErrorCode::NotFound,
+            Some("conflict") => ApiErrorCode::Conflict,
+            Some("node_already_registered") => ApiErrorCode::NodeAlreadyRegistered,
+            Some("server_unbound") => ApiErrorCode::ServerUnbound,
+            Some("domain_exists") => ApiErrorCode::DomainExists,
+            Some(other) => ApiErrorCode::Other(other.to_string()),
+            None => match status {
+                401 => ApiErrorCode::Unauthorized,
+                403 => ApiErrorCode::PermissionDenied,
+                404 => ApiErrorCode::NotFound,
+                409 => ApiErrorCode::Conflict,
+                _ => ApiErrorCode::Unknown,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OpsError {
+    /// Error response from the ops API
+    Api { code: ApiErrorCode, message: String },
+    /// No nodes are bound to the app (or project, when `app` is None)
+    NoNodesBound { app: Option<String>, project: String },
+}
+
+impl OpsError {
+    /// The API error code behind `err`, if it is (or wraps) an API error
+    pub fn api_code(err: &anyhow::Error) -> Option<&ApiErrorCode> {
+        match err.downcast_ref::<OpsError>()? {
+            OpsError::Api { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    pub fn is_no_nodes_bound(err: &anyhow::Error) -> bool {
+        matches!(err.downcast_ref::<OpsError>(), Some(OpsError::NoNodesBound { .. }))
+    }
+}
+
+impl fmt::Display for OpsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpsError::Api { code: ApiErrorCode::Unauthorized, message, .. } =>
+                write!(f, "{} (run `ops login` or set OPS_TOKEN, then try again)", message),
+            OpsError::Api { code: ApiErrorCode::PermissionDenied, message, .. } =>
+                write!(f, "{} (ask a project admin to run `ops access grant`)", message),
+            OpsError::Api { message, .. } => write!(f, "{}", message),
+            OpsError::NoNodesBound { app: Some(app), project } =>
+                write!(f, "No nodes bound to app '{}' in project '{}'. Bind one with `ops set {}.{} --node <id>`.", app, project, app, project),
+            OpsError::NoNodesBound { app: None, project } =>
+                write!(f, "No nodes bound to project '{}'. Bind a node first with `ops set <app.project> --node <id>`.", project),
+        }
+    }
+}
+
+impl std::error::Error for OpsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_code_survives_context() {
+        let err = anyhow::Error::new(OpsError::Api {
+            code: ApiErrorCode::from_response(Some("node_already_registered"), 409),
+            message: "IP already registered".into(),
+        })
+        .context("Failed to init node");
+        assert_eq!(OpsError::api_code(&err), Some(&ApiErrorCode::NodeAlreadyRegistered));
+        assert_eq!(ApiErrorCode::from_response(None, 401), ApiErrorCode::Unauthorized);
+        assert!(!OpsError::is_no_nodes_bound(&err));
+        assert_eq!(ApiErrorCode::from_response(None, 403), ApiErrorCode::PermissionDenied);
+    }
+}
@@ -0,0 +1,141 @@
+//! The login and Cloudflare tokens in the OS keychain: the macOS Keychain through `security`, or
+//! the Secret Service (GNOME Keyring, KWallet) through libsecret's `secret-tool`. Without either,
+//! or with `OPS_KEYRING=file`, the tokens stay in credentials.json. ops only builds for Unix, so
+//! there is no Windows Credential Manager backend.
+
+use crate::commands::ssh::has_binary;
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const SERVICE: &str = "ops-cli";
+/// Keychain account of the login token
+pub const TOKEN: &str = "token";
+/// Keychain account of the `ops domain cloudflare` API token
+pub const CLOUDFLARE_TOKEN: &str = "cloudflare-token";
+/// `OPS_KEYRING=file` keeps the tokens in credentials.json even when a keychain is available
+pub const KEYRING_ENV: &str = "OPS_KEYRING";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backend {
+    MacKeychain,
+    SecretService,
+}
+
+fn backend() -> Option<Backend> {
+    if std::env::var(KEYRING_ENV).is_ok_and(|v| v == "file") {
+        return None;
+    }
+    if cfg!(target_os = "macos") && has_binary("security") {
+        return Some(Backend::MacKeychain);
+    }
+    // secret-tool needs a session bus; there is none over plain SSH or in containers
+    if cfg!(target_os = "linux") && has_binary("secret-tool") && std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some() {
+        return Some(Backend::SecretService);
+    }
+    None
+}
+
+/// The keychain in use, for messages; None when tokens go to credentials.json
+pub fn name() -> Option<&'static str> {
+    backend().map(|b| match b {
+        Backend::MacKeychain => "macOS Keychain",
+        Backend::SecretService => "Secret Service keyring",
+    })
+}
+
+fn command(backend: Backend, action: &str, account: &str) -> Command {
+    let mut cmd = match backend {
+        Backend::MacKeychain => {
+            let mut cmd = Command::new("security");
+            cmd.arg(action).args(["-s", SERVICE, "-a", account]);
+            cmd
+        }
+        Backend::SecretService => {
+            let mut cmd = Command::new("secret-tool");
+            cmd.arg(action);
+            if action == "store" {
+                cmd.args(["--label", &format!("ops CLI {}", account)]);
+            }
+            cmd.args(["service", SERVICE, "account", account]);
+            cmd
+        }
+    };
+    cmd.stderr(Stdio::null());
+    cmd
+}
+
+/// `security -i` reads its commands from stdin: words in double quotes, with `\` escapes
+fn security_quote(word: &str) -> String {
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The secret saved under `account`, if the keychain holds one
+pub fn get(account: &str) -> Option<String> {
+    let backend = backend()?;
+    let action = match backend {
+        Backend::MacKeychain => "find-generic-password",
+        Backend::SecretService => "lookup",
+    };
+    let mut cmd = command(backend, action, account);
+    if backend == Backend::MacKeychain {
+        cmd.arg("-w");
+    }
+    let output = cmd.stdin(Stdio::null()).output().ok()?;
+    let secret = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !secret.is_empty()).then_some(secret)
+}
+
+/// Save `secret` under `account`, replacing any previous one; false when there is no keychain.
+/// The secret goes through stdin so that it never shows in the process list.
+pub fn set(account: &str, secret: &str) -> Result<bool> {
+    let Some(backend) = backend() else { return Ok(false) };
+    let (mut cmd, input) = match backend {
+        // `security` only takes the password as an argument, so the whole command is fed to its
+        // interactive mode instead
+        Backend::MacKeychain => {
+            let mut cmd = Command::new("security");
+            cmd.arg("-i").stdout(Stdio::null()).stderr(Stdio::null());
+            let line = format!("add-generic-password -U -s {} -a {} -w {}\n",
+                security_quote(SERVICE), security_quote(account), security_quote(secret));
+            (cmd, line)
+        }
+        Backend::SecretService => (command(backend, "store", account), secret.to_string()),
+    };
+    let mut child = cmd.stdin(Stdio::piped()).spawn().context("Failed to run the keychain tool")?;
+    child.stdin.take().context("The keychain tool has no stdin")?.write_all(input.as_bytes())?;
+    let status = child.wait().context("Failed to run the keychain tool")?;
+    if !status.success() {
+        bail!("Saving to the {} failed ({})", name().unwrap_or("keychain"), status);
+    }
+    Ok(true)
+}
+
+/// Remove the secret saved under `account`; whether there was one
+pub fn delete(account: &str) -> Result<bool> {
+    let Some(backend) = backend() else { return Ok(false) };
+    if get(account).is_none() {
+        return Ok(false);
+    }
+    let action = match backend {
+        Backend::MacKeychain => "delete-generic-password",
+        Backend::SecretService => "clear",
+    };
+    let status = command(backend, action, account).stdin(Stdio::null()).status()
+        .context("Failed to run the keychain tool")?;
+    if !status.success() {
+        bail!("Removing the {} from the {} failed ({})", account, name().unwrap_or("keychain"), status);
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_security_quote() {
+        assert_eq!(security_quote("abc-123"), r#""abc-123""#);
+        assert_eq!(security_quote(r#"a"b\c"#), r#""a\"b\\c""#);
+    }
+}
@@ -1,18 +1,29 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use anyhow::Result;
 use colored::Colorize;
 
 #[macro_use]
 mod output;
 mod prompt;
+mod registry;
 
 mod api;
+mod caddy;
+mod cloudflare;
 mod commands;
 mod config;
+mod error;
+mod keychain;
 mod scanner;
+mod router;
 mod serve;
+mod node_state;
+mod privilege;
+mod opsignore;
+mod project_state;
 mod ssh;
 mod types;
+mod table;
 mod utils;
 mod update;
 
@@ -32,6 +43,10 @@ struct Cli {
     #[arg(short, long, global = true)]
     yes: bool,
 
+    /// Print machine-readable JSON (node list, project list, pool status, domain list, status)
+    #[arg(long, global = true)]
+    json: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -39,7 +54,21 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     Register,
-    Login,
+    /// Log in and save the token (prompts unless a flag supplies credentials)
+    Login {
+        /// Save an existing token instead of logging in with a password
+        #[arg(long, conflicts_with_all = ["token_stdin", "username"])]
+        token: Option<String>,
+        /// Read the token from stdin
+        #[arg(long, conflicts_with = "username")]
+        token_stdin: bool,
+        /// Log in as this user; the password is prompted for unless --password-stdin is set
+        #[arg(long)]
+        username: Option<String>,
+        /// Read the password from stdin
+        #[arg(long, requires = "username")]
+        password_stdin: bool,
+    },
     Logout,
     Whoami,
 
@@ -66,6 +95,21 @@ enum Commands {
         /// Docker Compose project directory for ops serve
         #[arg(long)]
         compose_dir: Option<String>,
+        /// Replace /etc/caddy/Caddyfile instead of merging into it (a backup is kept)
+        #[arg(long)]
+        force_overwrite: bool,
+        /// Don't set up automatic HTTPS for the node domain
+        #[arg(long)]
+        no_tls: bool,
+        /// Revert everything a previous `ops init` changed on this server
+        #[arg(long)]
+        uninstall: bool,
+        /// Skip confirmation for --uninstall
+        #[arg(long)]
+        force: bool,
+        /// ops.toml whose [deploy.registry.mirrors] this node pulls through
+        #[arg(short, long)]
+        file: Option<String>,
     },
 
     /// Manage nodes
@@ -95,17 +139,31 @@ enum Commands {
         weight: Option<u8>,
     },
 
-    /// SSH into a server or execute a command (format: app.project)
+    /// OpenSSH config for your nodes and apps, for plain ssh, scp and editors
+    #[command(subcommand)]
+    SshConfig(SshConfigCommands),
+
+    /// SSH into a server or execute a command (format: app.project or node ID; prompts if omitted)
     Ssh {
-        target: String,
-        /// (Optional) Command to execute on the remote server
-        command: Option<String>,
+        target: Option<String>,
+        /// Remote user (default: root)
+        #[arg(short, long)]
+        user: Option<String>,
+        /// Local port forwarding (e.g. 5432:localhost:5432), can be repeated
+        #[arg(short = 'L', value_name = "SPEC")]
+        local_forward: Vec<String>,
+        /// Remote port forwarding (e.g. 9000:localhost:3000), can be repeated
+        #[arg(short = 'R', value_name = "SPEC")]
+        remote_forward: Vec<String>,
+        /// (Optional) Command to execute on the remote server; use `--` to pass flags through
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
     },
 
-    /// Push a file or directory to the server (format: source app.project[:/remote/path])
+    /// Push a file or directory to a server (format: source app.project[:/remote/path]; prompts if target is omitted)
     Push {
         source: String,
-        target: String,
+        target: Option<String>,
     },
 
     /// Print the current session token to stdout
@@ -127,19 +185,70 @@ enum Commands {
     #[command(subcommand)]
     NodeGroup(NodeGroupCommands),
 
+    /// List and rotate CI SSH keys
+    #[command(subcommand)]
+    Keys(KeysCommands),
+
+    /// Grant and revoke project/app roles (viewer, deployer, admin)
+    #[command(subcommand)]
+    Access(AccessCommands),
+
     #[command(alias = "ci-key")]
     CiKeys {
         target: String,
+        /// Output format: pem, github-secret, base64 or file:<path>
+        #[arg(short, long, default_value = "pem")]
+        output: String,
+        /// Fetch only the public key
+        #[arg(long)]
+        public: bool,
     },
     
-    /// Get the public IP address of a server
+    /// Get the public IP address of a server (prompts if target is omitted)
     Ip {
-        target: String,
+        target: Option<String>,
+        /// Resolve via local DNS instead of the address registered with the backend
+        #[arg(long)]
+        dns: bool,
     },
 
-    /// Ping a server to check its reachability
+    /// Ping a server to check its reachability (prompts if target is omitted)
     Ping {
-        target: String,
+        target: Option<String>,
+        /// Ping over IPv4 only
+        #[arg(short = '4', long, conflicts_with = "ipv6")]
+        ipv4: bool,
+        /// Ping over IPv6 only
+        #[arg(short = '6', long)]
+        ipv6: bool,
+        /// Check DNS, TCP 22, the CI key, SSH auth, ops serve and docker in turn, and report the first hop that fails
+        #[arg(long, conflicts_with_all = ["ipv4", "ipv6"])]
+        deep: bool,
+    },
+
+    /// Editor integration
+    #[command(subcommand)]
+    Ide(IdeCommands),
+
+    /// External subcommands (`ops-<name>` executables on PATH)
+    #[command(subcommand)]
+    Plugins(PluginsCommands),
+
+    /// `ops <name>` runs `ops-<name>` from PATH when <name> isn't built in
+    #[command(external_subcommand)]
+    External(Vec<String>),
+
+    /// Detect a project's framework, port and commands
+    Scan {
+        /// Project directory
+        #[arg(default_value = ".")]
+        path: String,
+        /// Write the generated Dockerfile, .dockerignore and docker-compose.yml
+        #[arg(long)]
+        write: bool,
+        /// With --write, overwrite files that already exist
+        #[arg(long, requires = "write")]
+        force: bool,
     },
 
     /// Generate ops.toml by scanning current project
@@ -150,10 +259,66 @@ enum Commands {
         /// Accept all defaults without prompting
         #[arg(short, long)]
         yes: bool,
+        /// Defaults to start from: a built-in preset (push, git, nextjs-ghcr, fastapi-push), a .toml file, or a preset in the ops config dir
+        #[arg(long)]
+        preset: Option<String>,
+        /// Project name (default: directory name)
+        #[arg(long)]
+        name: Option<String>,
+        /// App port (default: detected)
+        #[arg(long)]
+        port: Option<u16>,
+        /// Domain to route to the app
+        #[arg(long)]
+        domain: Option<String>,
+        /// Deploy source: push, git or image
+        #[arg(long)]
+        source: Option<String>,
+        /// Remote deploy path (default: /opt/<name>)
+        #[arg(long)]
+        deploy_path: Option<String>,
+        /// Compose file to use instead of generating one (repeatable)
+        #[arg(long = "compose-file")]
+        compose_files: Vec<String>,
+        /// Container registry to pull images from (implies --source image)
+        #[arg(long)]
+        registry: Option<String>,
+        /// Health check URL (default: http://localhost:<port>)
+        #[arg(long)]
+        health_url: Option<String>,
+    },
+
+    /// Create an app from a starter template, ready for `ops deploy`
+    New {
+        /// Template name (see `ops templates list`)
+        template: String,
+        /// Directory to create (default: the template name)
+        directory: Option<String>,
+        /// App name written into the template (default: the directory name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Git repository to take templates from instead of the ops catalog
+        #[arg(long)]
+        from: Option<String>,
+    },
+
+    /// Browse starter templates for `ops new`
+    #[command(subcommand)]
+    Templates(TemplatesCommands),
+
+    /// Check out an app locally from its recorded git repo and ops.toml
+    Clone {
+        /// Target in app.project format (e.g., api.RedQ)
+        target: String,
+        /// Directory to clone into (default: the app name)
+        directory: Option<String>,
     },
 
     /// Deploy services defined in ops.toml
+    #[command(args_conflicts_with_subcommands = true)]
     Deploy {
+        #[command(subcommand)]
+        command: Option<DeployCommands>,
         /// Path to ops.toml config file
         #[arg(short, long, default_value = "ops.toml")]
         file: String,
@@ -163,6 +328,9 @@ enum Commands {
         /// Deploy only services in this app group
         #[arg(long)]
         app: Option<String>,
+        /// Deploy every [[apps]] entry on its own nodes, in depends_on order
+        #[arg(long, conflicts_with_all = ["app", "service", "rolling"])]
+        all_apps: bool,
         /// Skip build, only restart containers
         #[arg(long)]
         restart_only: bool,
@@ -187,8 +355,60 @@ enum Commands {
         /// Run init commands (migrations) after deploy
         #[arg(long)]
         init: bool,
+        /// Deploy despite [policy] violations; the reason is logged on the deployment
+        #[arg(long, value_name = "REASON")]
+        override_policy: Option<String>,
+        /// Write a JSON summary (per-node result, durations, images, release id, URLs) to this file
+        #[arg(long, value_name = "PATH")]
+        summary_file: Option<String>,
+        /// Run the [[verify]] checks after a successful deploy
+        #[arg(long)]
+        verify: bool,
+        /// Print the plan (nodes, services, uploads, route fragments, remote commands) without deploying
+        #[arg(long)]
+        dry_run: bool,
+        /// Apply the [environments.<name>] overlay of ops.toml
+        #[arg(long, value_name = "NAME", env = "OPS_ENV")]
+        env: Option<String>,
+        /// Deploy to this percentage of the pool's nodes and send them about that share of traffic;
+        /// finish with `ops deploy promote` or `ops deploy abort`
+        #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(1..=99),
+            conflicts_with_all = ["all_apps", "node", "dry_run"])]
+        canary: Option<u8>,
+    },
+
+    /// Run the [[verify]] checks of ops.toml against the deployed app
+    Verify {
+        /// Path to ops.toml config file
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Check only the nodes of this app group
+        #[arg(long)]
+        app: Option<String>,
+    },
+
+    /// Encrypted app secrets, and scanning for leaked credentials
+    #[command(subcommand)]
+    Secrets(SecretsCommands),
+
+    /// Deploy a branch as an isolated preview environment with its own subdomains
+    #[command(subcommand)]
+    Preview(PreviewCommands),
+
+    /// Check ops.toml [policy] rules without deploying
+    Policy {
+        /// Path to ops.toml config file
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Values for ${VAR} in compose image names (KEY=VALUE), as passed to deploy
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        env_vars: Vec<String>,
     },
 
+    /// List and clean up images pushed by `ops build`
+    #[command(subcommand)]
+    Image(ImageCommands),
+
     /// Remote build on a persistent build node (like Depot.dev)
     Build {
         /// Path to ops.toml config file
@@ -209,47 +429,300 @@ enum Commands {
         /// Number of parallel image builds (default: 5)
         #[arg(short, long, default_value = "5")]
         jobs: u8,
+        /// Write a JSON summary (status, images, tag, duration) to this file
+        #[arg(long, value_name = "PATH")]
+        summary_file: Option<String>,
     },
 
+    /// Restart services one node at a time, waiting for health checks in between
+    RollingRestart {
+        /// Only this service (default: all [[apps]] services)
+        service: Option<String>,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Only services of this app group
+        #[arg(long)]
+        app: Option<String>,
+        /// Replace replicas one by one with new containers (needs strategy = "up")
+        #[arg(long)]
+        scale: bool,
+    },
+
+    /// Stop an app on all bound nodes and remove its routes (reads ops.toml)
+    Down {
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Only take down this app (default: all [[apps]])
+        #[arg(long)]
+        app: Option<String>,
+        /// Keep Caddy route fragments on the nodes
+        #[arg(long)]
+        keep_routes: bool,
+        /// Also delete the app from OPS (its routes are always removed)
+        #[arg(long, conflicts_with = "keep_routes")]
+        delete: bool,
+        /// Skip confirmation (required in non-interactive mode)
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Inspect what deploy generates from ops.toml
+    #[command(subcommand)]
+    Compose(ComposeCommands),
+
+    /// Offline deploy bundles for nodes without registry or internet access
+    #[command(subcommand)]
+    Bundle(BundleCommands),
+
     /// Show status of deployed services (reads ops.toml)
     Status {
         /// Path to ops.toml
         #[arg(short, long, default_value = "ops.toml")]
         file: String,
+        /// Skip probing app URLs and nodes over HTTP
+        #[arg(long)]
+        no_probe: bool,
+        /// Also show CPU, memory, network and block IO per container
+        #[arg(long)]
+        stats: bool,
+        /// Apply the [environments.<name>] overlay of ops.toml
+        #[arg(long, value_name = "NAME", env = "OPS_ENV")]
+        env: Option<String>,
+    },
+
+    /// List the project's containers on every bound node (via ops serve, or docker compose ps over SSH)
+    Ps {
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Only nodes bound to this app
+        #[arg(long)]
+        app: Option<String>,
+        /// Apply the [environments.<name>] overlay of ops.toml
+        #[arg(long, value_name = "NAME", env = "OPS_ENV")]
+        env: Option<String>,
+        #[command(flatten)]
+        table: table::TableOptions,
+    },
+
+    /// List an app's releases (deployments with their images and git commit)
+    #[command(subcommand)]
+    Releases(ReleasesCommands),
+
+    /// Put back the images and routes of an earlier release
+    Rollback {
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// App in ops.toml (default: the first one)
+        #[arg(long)]
+        app: Option<String>,
+        /// Deployment ID to roll back to (default: the release before the current one)
+        #[arg(long)]
+        to: Option<i64>,
+    },
+
+    /// Show events ops serve reported from an app's nodes, e.g. post-deploy crashes
+    Events {
+        /// Target in app.project format (default: from ops.toml)
+        target: Option<String>,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Number of events to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: u32,
+        /// Show the last log lines attached to each event
+        #[arg(long)]
+        logs: bool,
+    },
+
+    /// Block until a deployment finishes, an app is healthy or a node is healthy
+    #[command(group = clap::ArgGroup::new("condition").required(true))]
+    Wait {
+        /// Deployment ID to wait for (fails if the deployment fails)
+        #[arg(long, group = "condition")]
+        deployment: Option<i64>,
+        /// App in app.project format: all its nodes healthy and its URL answering
+        #[arg(long, group = "condition")]
+        healthy: Option<String>,
+        /// Node ID to wait for
+        #[arg(long, group = "condition")]
+        node: Option<u64>,
+        /// Give up after this many seconds
+        #[arg(long, default_value_t = 300)]
+        timeout: u64,
+        /// Seconds between polls
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
+
+    /// Check a node's Docker daemon, ops serve and registry mirrors
+    Doctor {
+        /// Node ID to check
+        #[arg(long)]
+        node: u64,
+        /// Path to ops.toml (registry mirrors are checked when it has them)
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+
+    /// Compare each node against the state recorded by its last deploy
+    Drift {
+        /// Target in app.project format (default: from ops.toml)
+        target: Option<String>,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+
+    /// Write a project's apps, domains, node groups, env keys and routes to a YAML blueprint
+    Export {
+        /// Project name
+        project: String,
+        /// Output file (default: <project>.ops.yaml)
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+
+    /// Recreate a project from a blueprint written by `ops export`
+    Import {
+        /// Blueprint file
+        file: String,
+        /// Create it under this project name instead
+        #[arg(long)]
+        project: Option<String>,
+    },
+
+    /// Monthly node cost per project and app
+    Cost {
+        /// Only this project
+        project: Option<String>,
+    },
+
+    /// Reports from node history (resource usage)
+    #[command(subcommand)]
+    Report(ReportCommands),
+
+    /// Print the project's apps, services, nodes and domains as a graph
+    Graph {
+        /// Project name (default: from ops.toml)
+        project: Option<String>,
+        /// Output format: dot | mermaid
+        #[arg(long, default_value = "dot")]
+        format: String,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
     },
 
     /// View logs of a deployed service (reads ops.toml)
     Logs {
-        /// Service name (e.g. jug0, juglans-api)
-        service: String,
+        /// Service name (e.g. jug0, juglans-api); with --access, the app (default: first in ops.toml)
+        #[arg(required_unless_present = "access")]
+        service: Option<String>,
         /// Path to ops.toml
         #[arg(long, default_value = "ops.toml")]
         file: String,
-        /// Number of lines to show
+        /// Node ID or app.project to read logs from (default: first node bound in ops.toml)
+        #[arg(long)]
+        target: Option<String>,
+        /// Number of lines to show (not applied with --since or --until)
         #[arg(short = 'n', long, default_value = "100")]
         tail: u32,
         /// Follow log output
         #[arg(short, long)]
         follow: bool,
+        /// Tail the app's Caddy access log (JSON lines) instead, for apps with `access_logs = true`
+        #[arg(long)]
+        access: bool,
+        /// Read from every node of the app at once, each line prefixed with [node-id/service]
+        #[arg(long, conflicts_with_all = ["target", "access"])]
+        all_nodes: bool,
+        /// Only lines from this time on: a duration ago (10m, 2h) or a timestamp (2025-06-01T10:00:00)
+        #[arg(long, conflicts_with = "access")]
+        since: Option<String>,
+        /// Only lines before this time, as a duration ago or a timestamp
+        #[arg(long, conflicts_with = "access")]
+        until: Option<String>,
+        /// Only lines containing this text
+        #[arg(long, conflicts_with = "access")]
+        grep: Option<String>,
+        /// Apply the [environments.<name>] overlay of ops.toml
+        #[arg(long, value_name = "NAME", env = "OPS_ENV")]
+        env: Option<String>,
+    },
+
+    /// Run a one-off task in a fresh service container on the deploy target (docker compose run --rm)
+    Run {
+        /// Compose service to run the task in
+        service: String,
+        /// Command to run, after `--` (default: the service's command)
+        #[arg(last = true)]
+        command: Vec<String>,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Node ID or app.project to run on (default: first node bound to the service's app)
+        #[arg(long)]
+        target: Option<String>,
+        /// Container environment variable (KEY=VALUE), can be repeated
+        #[arg(short, long = "env", value_name = "KEY=VALUE")]
+        env_vars: Vec<String>,
+        /// Compose interpolation variable (KEY=VALUE) as in `ops deploy --set`, can be repeated
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        set_vars: Vec<String>,
+    },
+
+    /// Run a command in a service's running container on the deploy target (docker compose exec)
+    Exec {
+        /// Compose service whose container to enter
+        service: String,
+        /// Command to run, after `--` (default: sh)
+        #[arg(last = true)]
+        command: Vec<String>,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Node ID or app.project to run on (default: first node bound to the service's app)
+        #[arg(long)]
+        target: Option<String>,
+        /// Environment variable (KEY=VALUE) for the command, can be repeated
+        #[arg(short, long = "env", value_name = "KEY=VALUE")]
+        env_vars: Vec<String>,
+        /// User to run the command as
+        #[arg(short, long)]
+        user: Option<String>,
+        /// Compose interpolation variable (KEY=VALUE) as in `ops deploy --set`, can be repeated
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        set_vars: Vec<String>,
     },
 
     /// Start HTTP server exposing container status, logs, metrics
     Serve {
-        /// Bearer token for authentication
+        /// Settings file (default: /etc/ops/serve.toml when it exists); the flags below override it
+        #[arg(long, value_name = "PATH")]
+        config: Option<String>,
+        /// Bearer token for authentication (prefer token_file in serve.toml or the OPS_SERVE_TOKEN env var, args are visible in `ps`)
+        #[arg(long, env = "OPS_SERVE_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+        /// Port to listen on [default: 8377]
         #[arg(long)]
-        token: String,
-        /// Port to listen on
-        #[arg(long, default_value = "8377")]
-        port: u16,
-        /// Docker Compose project directory
+        port: Option<u16>,
+        /// Docker Compose project directories, comma-separated
         #[arg(long)]
-        compose_dir: String,
+        compose_dir: Option<String>,
         /// Install as systemd service and configure Caddy reverse proxy
         #[arg(long)]
         install: bool,
-        /// Domain for Caddy reverse proxy (e.g. api.RedQ.ops.autos)
+        /// Domain to serve over HTTPS via Caddy (e.g. 42.node.ops.autos)
         #[arg(long)]
         domain: Option<String>,
+        /// Serve /metrics/prometheus without the bearer token
+        #[arg(long)]
+        public_metrics: bool,
     },
 
     /// Manage custom domains for your app
@@ -261,15 +734,32 @@ enum Commands {
     Pool(PoolCommands),
 
     /// Create a reverse tunnel to expose local port via public URL
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Tunnel {
+        #[command(subcommand)]
+        command: Option<TunnelCommands>,
         /// Target in subdomain.project format (e.g., webhook.redq)
-        target: String,
+        #[arg(required = true)]
+        target: Option<String>,
         /// Local port to forward traffic to
-        #[arg(short, long)]
-        port: u16,
+        #[arg(short, long, required = true)]
+        port: Option<u16>,
         /// Node ID to tunnel through
+        #[arg(long, required = true)]
+        node: Option<u64>,
+        /// Run the tunnel in the background; stop it with `ops tunnel kill <id>`
         #[arg(long)]
-        node: u64,
+        detach: bool,
+    },
+
+    /// Remove tunnels and branch previews whose heartbeats stopped, with their node routes
+    Gc {
+        /// List what would be removed without removing it
+        #[arg(long)]
+        dry_run: bool,
+        /// Also remove resources that never sent a heartbeat
+        #[arg(long)]
+        force: bool,
     },
 
     /// Update ops to the latest version
@@ -277,14 +767,30 @@ enum Commands {
 
     /// Check current version info
     Version,
+
+    /// Print a shell completion script (bash, zsh or fish)
+    Completions {
+        #[arg(value_parser = ["bash", "zsh", "fish"])]
+        shell: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplatesCommands {
+    /// List available templates
+    List {
+        /// Git repository to list instead of the ops catalog
+        #[arg(long)]
+        from: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
 enum EnvCommands {
     /// Upload local .env file to the target server
-    Upload { target: String },
+    Upload { target: Option<String> },
     /// Download .env file from the target server
-    Download { target: String },
+    Download { target: Option<String> },
 }
 
 #[derive(Subcommand)]
@@ -292,9 +798,20 @@ enum ProjectCommands {
     /// Create a new project
     Create { name: String },
 
-    /// List all projects and their servers. Optional name to filter.
+    /// List projects with their apps, node health and custom domains. Optional name to filter.
     List {
         name: Option<String>,
+        /// Only projects you own
+        #[arg(long, conflicts_with = "shared")]
+        mine: bool,
+        /// Only projects shared with you
+        #[arg(long)]
+        shared: bool,
+        /// Only projects with nodes in this region
+        #[arg(long)]
+        region: Option<String>,
+        #[command(flatten)]
+        table: table::TableOptions,
     },
 }
 
@@ -307,7 +824,10 @@ enum ServerCommands {
 #[derive(Subcommand)]
 enum NodeCommands {
     /// List all your nodes
-    List,
+    List {
+        #[command(flatten)]
+        table: table::TableOptions,
+    },
     /// Show detailed information about a node
     Info {
         /// Node ID
@@ -321,6 +841,215 @@ enum NodeCommands {
         #[arg(long)]
         force: bool,
     },
+    /// Record a node's monthly cost, used by `ops cost`
+    SetCost {
+        /// Node ID
+        id: u64,
+        /// Monthly cost (e.g., 14.00)
+        #[arg(required_unless_present = "clear")]
+        amount: Option<f64>,
+        /// Currency code
+        #[arg(default_value = "USD")]
+        currency: String,
+        /// Remove the node's cost
+        #[arg(long, conflicts_with = "amount")]
+        clear: bool,
+    },
+    /// Give a person SSH access to a node by adding their public keys to authorized_keys
+    Grant {
+        /// Node ID
+        id: u64,
+        /// Use the public keys of this GitHub user
+        #[arg(long, conflicts_with = "pubkey", required_unless_present = "pubkey")]
+        github_user: Option<String>,
+        /// Use the public key(s) in this file
+        #[arg(long)]
+        pubkey: Option<String>,
+        /// Name to revoke the grant by (default: the GitHub user or key file name)
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// Remove the keys granted under a label from a node
+    Revoke {
+        /// Node ID
+        id: u64,
+        /// Label given at grant time
+        label: String,
+    },
+    /// Register machines from Terraform/OpenTofu outputs by running `ops init` on each over SSH
+    ImportTf {
+        /// `terraform output -json` file or a terraform.tfstate
+        file: String,
+        /// Only read this output (default: every output holding IP addresses)
+        #[arg(long)]
+        output: Option<String>,
+        /// SSH user on the new machines (non-root users need passwordless sudo)
+        #[arg(long, default_value = "root")]
+        user: String,
+        /// SSH private key for the new machines
+        #[arg(short, long)]
+        identity: Option<String>,
+        /// Region for machines whose output has none (default: auto-detect)
+        #[arg(long)]
+        region: Option<String>,
+        /// Only list the machines found
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List Caddy route fragments on a node
+    Routes {
+        /// Node ID
+        id: u64,
+        /// Remove fragments whose apps are no longer bound to the node
+        #[arg(long)]
+        prune_orphans: bool,
+    },
+    /// Free disk on a node: stopped containers, dangling images and build cache
+    Prune {
+        /// Node ID
+        id: u64,
+        /// Also remove every image no container uses (the next deploy pulls or rebuilds them)
+        #[arg(long)]
+        all: bool,
+    },
+    /// Container log rotation on a node
+    #[command(subcommand)]
+    Logging(NodeLoggingCommands),
+    /// Back up a node's configuration
+    #[command(subcommand)]
+    Config(NodeConfigCommands),
+    /// Run a shell command on every node an app is deployed to, output prefixed by node
+    Exec {
+        /// App target (e.g., api.RedQ)
+        target: String,
+        /// Nodes at a time (default: all)
+        #[arg(long, value_name = "N", conflicts_with = "rolling")]
+        parallel: Option<usize>,
+        /// One node at a time, stopping at the first failure
+        #[arg(long)]
+        rolling: bool,
+        /// Command to run (after --)
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Move every app from one node to another (resumes an interrupted migration)
+    Migrate {
+        /// Source node ID
+        from: u64,
+        /// Target node ID
+        to: u64,
+        /// Delete the source node once every app has moved
+        #[arg(long)]
+        decommission: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PluginsCommands {
+    /// List the plugins found on PATH
+    List,
+}
+
+#[derive(Subcommand)]
+enum IdeCommands {
+    /// Write VS Code tasks, launch entries and a devcontainer for this project
+    Setup {
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Replace existing tasks.json, launch.json and devcontainer.json instead of merging
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SshConfigCommands {
+    /// Print a Host block per node and app (append it to ~/.ssh/config)
+    Export,
+}
+
+#[derive(Subcommand)]
+enum ReleasesCommands {
+    /// List releases, newest first
+    List {
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// App in ops.toml (default: the first one)
+        #[arg(long)]
+        app: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum NodeConfigCommands {
+    /// Archive routes, the Caddyfile, the ops-serve unit and every compose project's files (env files by variable name only)
+    Backup {
+        /// Node ID
+        id: u64,
+        /// Directory to write the archive to
+        #[arg(long, default_value = ".")]
+        out: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum NodeLoggingCommands {
+    /// Set json-file log rotation in daemon.json and recreate running compose services
+    Configure {
+        /// Node ID
+        id: u64,
+        /// Size at which a container's log file is rotated (e.g. 50m)
+        #[arg(long, default_value = "50m")]
+        max_size: String,
+        /// Rotated files kept per container
+        #[arg(long, default_value_t = 3)]
+        max_file: u32,
+        /// Only write daemon.json; don't restart Docker or recreate containers
+        #[arg(long)]
+        no_restart: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ComposeCommands {
+    /// Print the compose args, overrides, env files and route fragments deploy would put on the node
+    Render {
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Only render this app
+        #[arg(long)]
+        app: Option<String>,
+        /// Environment variables (KEY=VALUE) as passed to `ops deploy --set`
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        env_vars: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BundleCommands {
+    /// Package images, compose files, env templates and route fragments into one archive
+    Create {
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Archive path (default: <project>-bundle.tar.gz)
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Only include routes of this app
+        #[arg(long)]
+        app: Option<String>,
+    },
+    /// Deploy a bundle to a node over SSH
+    Apply {
+        /// Bundle archive from `ops bundle create`
+        bundle: String,
+        /// Node ID to deploy to
+        #[arg(long)]
+        node: u64,
+    },
 }
 
 #[derive(Subcommand)]
@@ -379,6 +1108,8 @@ enum DomainCommands {
         /// Path to ops.toml
         #[arg(short, long, default_value = "ops.toml")]
         file: String,
+        #[command(flatten)]
+        table: table::TableOptions,
     },
     /// Remove a custom domain
     Remove {
@@ -396,12 +1127,253 @@ enum DomainCommands {
         /// Sync only domains for this app
         #[arg(short, long)]
         app: Option<String>,
-        /// Remove domains from backend that are not in ops.toml
+        /// Remove domains from backend that are not in ops.toml (pick which when interactive)
         #[arg(long)]
         prune: bool,
+        /// Remove only these extra backend domains (comma-separated), without prompting
+        #[arg(long, value_delimiter = ',', conflicts_with = "prune")]
+        remove: Option<Vec<String>>,
         /// Skip confirmation when pruning
         #[arg(long)]
         yes: bool,
+        /// Apply the [environments.<name>] overlay of ops.toml
+        #[arg(long, value_name = "NAME", env = "OPS_ENV")]
+        env: Option<String>,
+    },
+    /// Set up a domain whose DNS is on Cloudflare
+    #[command(subcommand)]
+    Cloudflare(CloudflareCommands),
+}
+
+#[derive(Subcommand)]
+enum CloudflareCommands {
+    /// Add the domain, create its CNAME in Cloudflare and fix settings that block certificates
+    Link {
+        /// Custom domain (e.g., api.example.com)
+        domain: String,
+        /// App name (required for project mode with multiple apps)
+        #[arg(short, long)]
+        app: Option<String>,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Proxy through Cloudflare (orange cloud) instead of DNS only
+        #[arg(long)]
+        proxied: bool,
+        /// Replace A/AAAA records and change the zone's SSL mode without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Save a Cloudflare API token (Zone:Read, DNS:Edit, Zone Settings:Edit), from a prompt or stdin
+    Token,
+}
+
+#[derive(Subcommand)]
+enum KeysCommands {
+    /// List CI keypairs, the apps using them and their age
+    List,
+    /// Generate a new CI keypair and replace the old one in each node's authorized_keys
+    Rotate {
+        /// Node ID or app.project
+        target: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AccessCommands {
+    /// Give a user a role on an app or a whole project
+    Grant {
+        /// ops.autos username
+        user: String,
+        /// app.project, or a project name for every app in it
+        target: String,
+        /// viewer, deployer or admin
+        role: String,
+    },
+    /// Remove a user's role on an app or project
+    Revoke {
+        user: String,
+        /// app.project or project
+        target: String,
+    },
+    /// Show who has which role on an app or project
+    List {
+        /// app.project or project
+        target: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum PreviewCommands {
+    /// Deploy the branch as <branch>.<app>.<project>.ops.autos
+    Up {
+        /// Branch to preview (default: the current one)
+        #[arg(long)]
+        branch: Option<String>,
+        /// Remove the preview after this long, e.g. 12h or 3d (default: [preview] ttl, else 7d)
+        #[arg(long)]
+        ttl: Option<String>,
+        /// Path to ops.toml config file
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Set environment variables (KEY=VALUE), can be repeated
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        env_vars: Vec<String>,
+    },
+    /// Remove the branch's preview: containers, volumes, checkout and routes
+    Down {
+        /// Branch to remove (default: the current one)
+        #[arg(long)]
+        branch: Option<String>,
+        /// Path to ops.toml config file
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+    /// List the project's previews with owner, age and expiry
+    List {
+        /// Path to ops.toml config file
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImageCommands {
+    /// Tags under [build.image] prefix, newest first, and where they are deployed
+    List {
+        /// Path to ops.toml config file
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+    /// Delete old tags from the registry and old images from nodes
+    Clean {
+        /// Path to ops.toml config file
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// Tags to keep per service, besides `latest` and deployed ones
+        #[arg(long, default_value = "10")]
+        keep: usize,
+        /// Show what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+        /// Clean even when some nodes can't be inspected (their running tags lose protection)
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretsCommands {
+    /// Scan the working tree (or [deploy] include entries) for keys, tokens and .env files
+    Scan {
+        /// Path to ops.toml config file
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+    /// Store a secret (KEY=VALUE, or KEY to enter the value at a prompt or on stdin)
+    Set {
+        assignment: String,
+        /// Target in app.project format (default: from ops.toml)
+        #[arg(long)]
+        target: Option<String>,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+    /// Print a secret's value
+    Get {
+        key: String,
+        /// Target in app.project format (default: from ops.toml)
+        #[arg(long)]
+        target: Option<String>,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+    /// List secret names (values are not shown)
+    List {
+        /// Target in app.project format (default: from ops.toml)
+        #[arg(long)]
+        target: Option<String>,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+    /// Delete a secret
+    Rm {
+        key: String,
+        /// Target in app.project format (default: from ops.toml)
+        #[arg(long)]
+        target: Option<String>,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReportCommands {
+    /// Average/max CPU and memory per service and node, from `ops serve` stats history
+    Usage {
+        /// App name (default: first app in ops.toml)
+        #[arg(long)]
+        app: Option<String>,
+        /// How far back to look (e.g. 24h, 7d, 30d)
+        #[arg(long, default_value = "7d")]
+        range: String,
+        /// Output format: table | csv | json
+        #[arg(long, default_value = "table")]
+        format: String,
+        /// Path to ops.toml
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DeployCommands {
+    /// Deploy the running canary to the rest of the pool and restore the weights
+    Promote {
+        /// Path to ops.toml config file
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// The app group the canary was started for
+        #[arg(long)]
+        app: Option<String>,
+        /// The canary deploy's environment variables again (KEY=VALUE), can be repeated
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        env_vars: Vec<String>,
+        /// Deploy the remaining nodes one at a time
+        #[arg(long)]
+        rolling: bool,
+    },
+    /// Restore the weights and roll the canary nodes back to the previous release
+    Abort {
+        /// Path to ops.toml config file
+        #[arg(short, long, default_value = "ops.toml")]
+        file: String,
+        /// The app group the canary was started for
+        #[arg(long)]
+        app: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TunnelCommands {
+    /// List tunnels started here and those the backend knows about
+    List {
+        #[command(flatten)]
+        table: table::TableOptions,
+    },
+    /// Stop a tunnel and remove its route and DNS record
+    Kill {
+        /// Tunnel ID from `ops tunnel list`
+        id: i64,
+    },
+    /// Send a detached tunnel's heartbeats while its ssh process runs
+    #[command(name = "__heartbeat", hide = true)]
+    Heartbeat {
+        id: i64,
     },
 }
 
@@ -411,6 +1383,8 @@ enum PoolCommands {
     Status {
         /// Target in app.project format (e.g., api.RedQ)
         target: String,
+        #[command(flatten)]
+        table: table::TableOptions,
     },
     /// Change load balancing strategy
     Strategy {
@@ -419,6 +1393,16 @@ enum PoolCommands {
         /// Strategy: round-robin, geo, weighted, failover
         strategy: String,
     },
+    /// Pin clients to one node by cookie or header
+    Sticky {
+        /// Target in app.project format (e.g., api.RedQ)
+        target: String,
+        /// Mode: cookie, header, off
+        mode: String,
+        /// Cookie name (default: ops_sticky) or header name (required for header)
+        #[arg(long)]
+        name: Option<String>,
+    },
     /// Drain a node (stop routing new traffic)
     Drain {
         /// Target in app.project format (e.g., api.RedQ)
@@ -435,15 +1419,74 @@ enum PoolCommands {
         #[arg(long)]
         node: u64,
     },
+    /// Configure backend health probing for the pool
+    Healthcheck {
+        /// Target in app.project format (e.g., api.RedQ)
+        target: String,
+        /// Check type: http, tcp
+        #[arg(long = "type", default_value = "http")]
+        check_type: String,
+        /// HTTP path (e.g. /healthz) or TCP port
+        #[arg(long)]
+        endpoint: String,
+        /// Seconds between probes
+        #[arg(long, default_value = "10")]
+        interval: i64,
+        /// Probe timeout in seconds
+        #[arg(long, default_value = "5")]
+        timeout: i64,
+        /// Consecutive failures before a node is marked unhealthy
+        #[arg(long, default_value = "3")]
+        unhealthy: i64,
+        /// Consecutive successes before a node is marked healthy again
+        #[arg(long, default_value = "2")]
+        healthy: i64,
+        /// Skip probing the endpoint against a live node before saving
+        #[arg(long)]
+        no_verify: bool,
+    },
+    /// Rehearse node loss: drain a node, verify the app stays reachable, then restore it
+    FailoverTest {
+        /// Target in app.project format (e.g., api.RedQ)
+        target: String,
+        /// Node ID to take out of rotation
+        #[arg(long)]
+        node: u64,
+        /// How long to keep the node drained, in seconds
+        #[arg(long, default_value = "30")]
+        duration: u64,
+        /// Path to probe on the public domain
+        #[arg(long, default_value = "/")]
+        path: String,
+    },
+    /// Set a node's traffic weight (used by the weighted strategy)
+    Weight {
+        /// Target in app.project format (e.g., api.RedQ)
+        target: String,
+        /// Node ID to adjust
+        #[arg(long)]
+        node: u64,
+        /// Load balancing weight (1-100)
+        #[arg(long)]
+        weight: u8,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // A tab press: `COMPLETE=<shell> ops -- <words>` prints the candidates and exits
+    clap_complete::CompleteEnv::with_factory(|| {
+        commands::completions::with_candidates(Cli::command(), commands::completions::account_values)
+    })
+    .var(commands::completions::COMPLETE_VAR)
+    .complete();
+
     dotenvy::dotenv().ok();
     let cli = Cli::parse();
 
     // Initialize output verbosity
-    let verbosity = if cli.quiet {
+    // --json keeps stdout for the JSON document
+    let verbosity = if cli.quiet || cli.json {
         output::Verbosity::Quiet
     } else if cli.verbose {
         output::Verbosity::Verbose
@@ -451,17 +1494,19 @@ async fn main() -> Result<()> {
         output::Verbosity::Normal
     };
     output::init(verbosity);
+    output::init_json(cli.json);
 
-    // Determine interactive mode: disabled by --yes, OPS_YES env, or non-TTY stdin
+    // Determine interactive mode: disabled by --yes, --json, OPS_YES env, or non-TTY stdin
     use std::io::IsTerminal;
     let interactive = !cli.yes
+        && !cli.json
         && std::env::var("OPS_YES").is_err()
         && std::io::stdin().is_terminal();
 
     // Auto-update check (skip for certain commands)
     if !matches!(
         &cli.command,
-        Commands::Update | Commands::Version | Commands::Serve { .. } | Commands::Tunnel { .. }
+        Commands::Update | Commands::Version | Commands::Completions { .. } | Commands::Serve { .. } | Commands::Tunnel { .. } | Commands::Compose(_)
     ) {
         if let Ok(true) = update::check_and_auto_update() {
             return Ok(()); // Exit after update, user should re-run
@@ -470,48 +1515,98 @@ async fn main() -> Result<()> {
 
     let result = match &cli.command {
         Commands::Register => commands::register::handle_register().await,
-        Commands::Login => commands::login::handle_login().await,
+        Commands::Login { token, token_stdin, username, password_stdin } => {
+            use commands::login::LoginInput;
+            let input = match (token, username) {
+                (Some(token), _) => LoginInput::Token(token.clone()),
+                _ if *token_stdin => LoginInput::TokenStdin,
+                (None, Some(username)) => LoginInput::Password { username: username.clone(), password_stdin: *password_stdin },
+                (None, None) => LoginInput::Interactive,
+            };
+            commands::login::handle_login(input).await
+        }
         Commands::Logout => commands::logout::handle_logout().await,
         Commands::Whoami => commands::whoami::handle_whoami().await,
 
-        Commands::Init { daemon, project, app, region, port, hostname, compose_dir } =>
-            commands::init::handle_init(
-                *daemon,
-                project.clone(),
-                app.clone(),
-                region.clone(),
-                *port,
-                hostname.clone(),
-                compose_dir.clone(),
-                interactive,
-            ).await,
+        Commands::Init { uninstall: true, force, .. } =>
+            commands::init::handle_uninstall(*force, interactive).await,
+
+        Commands::Init { region, port, hostname, compose_dir, force_overwrite, no_tls, file, .. } => {
+            let opts = commands::init::InitOptions {
+                region: region.clone(),
+                port: *port,
+                hostname: hostname.clone(),
+                compose_dir: compose_dir.clone(),
+                tls: !*no_tls,
+                force_overwrite_caddy: *force_overwrite,
+                file: file.clone(),
+            };
+            commands::init::handle_init(opts, interactive).await
+        }
 
         Commands::Node(cmd) => match cmd {
-            NodeCommands::List => commands::node::handle_list().await,
+            NodeCommands::List { table } => commands::node::handle_list(table).await,
             NodeCommands::Info { id } => commands::node::handle_info(*id).await,
             NodeCommands::Remove { id, force } => commands::node::handle_remove(*id, *force, interactive).await,
+            NodeCommands::Grant { id, github_user, pubkey, label } =>
+                commands::node::handle_grant(*id, github_user.clone(), pubkey.clone(), label.clone()).await,
+            NodeCommands::Revoke { id, label } => commands::node::handle_revoke(*id, label.clone()).await,
+            NodeCommands::SetCost { id, amount, currency, clear: _ } =>
+                commands::node::handle_set_cost(*id, *amount, currency.clone()).await,
+            NodeCommands::Routes { id, prune_orphans } => commands::node::handle_routes(*id, *prune_orphans, interactive).await,
+            NodeCommands::Prune { id, all } => commands::node::handle_prune(*id, *all, interactive).await,
+            NodeCommands::Logging(NodeLoggingCommands::Configure { id, max_size, max_file, no_restart }) =>
+                commands::log_rotation::handle_configure(*id, max_size.clone(), *max_file, *no_restart, interactive).await,
+            NodeCommands::Config(NodeConfigCommands::Backup { id, out }) =>
+                commands::node_config::handle_backup(*id, out.clone()).await,
+            NodeCommands::Exec { target, parallel, rolling, command } =>
+                commands::node_exec::handle_exec(target.clone(), command.clone(), *parallel, *rolling).await,
+            NodeCommands::Migrate { from, to, decommission } =>
+                commands::node_migrate::handle_migrate(*from, *to, *decommission, interactive).await,
+            NodeCommands::ImportTf { file, output, user, identity, region, dry_run } =>
+                commands::node::handle_import_tf(file.clone(), output.clone(), user.clone(), identity.clone(), region.clone(), *dry_run, interactive).await,
         },
 
         Commands::Set { target, node, primary, region, zone, hostname, weight } =>
             commands::set::handle_set(target.clone(), *node, *primary, region.clone(), zone.clone(), hostname.clone(), *weight, interactive).await,
-        Commands::Ssh { target, command } => commands::ssh::handle_ssh(target.clone(), command.clone()).await,
-        Commands::Push { source, target } => commands::scp::handle_push(source.clone(), target.clone()).await,
+        Commands::Ssh { target, user, local_forward, remote_forward, command } => {
+            let opts = commands::ssh::SshOptions {
+                user: user.clone(),
+                local_forwards: local_forward.clone(),
+                remote_forwards: remote_forward.clone(),
+                tty: false,
+            };
+            commands::ssh::handle_ssh(target.clone(), opts, command.clone(), interactive).await
+        },
+        Commands::Push { source, target } => commands::scp::handle_push(source.clone(), target.clone(), interactive).await,
 
         Commands::Token => commands::token::handle_get_token().await,
+        Commands::SshConfig(SshConfigCommands::Export) => commands::ssh_config::handle_export().await,
 
         Commands::Env(cmd) => match cmd {
-            EnvCommands::Upload { target } => commands::env::handle_upload(target.clone()).await,
-            EnvCommands::Download { target } => commands::env::handle_download(target.clone()).await,
+            EnvCommands::Upload { target } => commands::env::handle_upload(target.clone(), interactive).await,
+            EnvCommands::Download { target } => commands::env::handle_download(target.clone(), interactive).await,
         },
 
-        Commands::CiKeys { target } => commands::ci_key::handle_get_ci_private_key(target.clone()).await,
+        Commands::Keys(cmd) => match cmd {
+            KeysCommands::List => commands::keys::handle_list().await,
+            KeysCommands::Rotate { target } => commands::keys::handle_rotate(target.clone(), interactive).await,
+        },
+        Commands::Access(cmd) => match cmd {
+            AccessCommands::Grant { user, target, role } =>
+                commands::access::handle_grant(user.clone(), target.clone(), role.clone()).await,
+            AccessCommands::Revoke { user, target } => commands::access::handle_revoke(user.clone(), target.clone()).await,
+            AccessCommands::List { target } => commands::access::handle_list(target.clone()).await,
+        },
+        Commands::CiKeys { target, output, public } => commands::ci_key::handle_get_ci_private_key(target.clone(), output.clone(), *public).await,
 
-        Commands::Ip { target } => commands::ip::handle_ip(target.clone()).await,
-        Commands::Ping { target } => commands::ping::handle_ping(target.clone()).await,
+        Commands::Ip { target, dns } => commands::ip::handle_ip(target.clone(), *dns, interactive).await,
+        Commands::Ping { target, ipv4, ipv6, deep } => commands::ping::handle_ping(target.clone(), *ipv4, *ipv6, *deep, interactive).await,
 
         Commands::Project(cmd) => match cmd {
             ProjectCommands::Create { name } => commands::project::handle_create_project(name.clone()).await,
-            ProjectCommands::List { name } => commands::project::handle_list_projects(name.clone()).await,
+            ProjectCommands::List { name, mine, shared, region, table } =>
+                commands::project::handle_list_projects(name.clone(), *mine, *shared, region.clone(), table).await,
         },
         Commands::Server(cmd) => match cmd {
             ServerCommands::Whoami => commands::server::handle_server_whoami().await,
@@ -527,51 +1622,204 @@ async fn main() -> Result<()> {
                 commands::node_group::handle_nodes(target.clone()).await,
         },
         
-        Commands::Launch { output, yes } =>
-            commands::launch::handle_launch(output.clone(), interactive && !*yes).await,
-        Commands::Deploy { file, service, app, restart_only, env_vars, node, region, rolling, force, no_pull, init } =>
-            commands::deploy::handle_deploy(file.clone(), service.clone(), app.clone(), *restart_only, env_vars.clone(), *node, region.clone(), *rolling, *force, *no_pull, *init, interactive).await,
-        Commands::Build { file, git_ref, service, tag, no_push, jobs } =>
-            commands::build::handle_build(file.clone(), git_ref.clone(), service.clone(), tag.clone(), *no_push, *jobs).await,
-        Commands::Status { file } =>
-            commands::status::handle_status(file.clone()).await,
-        Commands::Logs { service, file, tail, follow } =>
-            commands::logs::handle_logs(file.clone(), service.clone(), *tail, *follow).await,
-
-        Commands::Serve { token, port, compose_dir, install, domain } => {
+        Commands::Plugins(PluginsCommands::List) => commands::plugin::handle_list(),
+        Commands::External(args) => commands::plugin::handle_external(args, cli.json, !interactive),
+        Commands::Ide(IdeCommands::Setup { file, force }) => commands::ide::handle_setup(file.clone(), *force),
+        Commands::Scan { path, write, force } => commands::scan::handle_scan(path.clone(), *write, *force),
+
+        Commands::Launch { output, yes, preset, name, port, domain, source, deploy_path, compose_files, registry, health_url } => {
+            let opts = commands::launch::LaunchOptions {
+                name: name.clone(),
+                port: *port,
+                domain: domain.clone(),
+                source: source.clone(),
+                deploy_path: deploy_path.clone(),
+                compose_files: compose_files.clone(),
+                registry: registry.clone(),
+                health_url: health_url.clone(),
+                preset: preset.clone(),
+            };
+            commands::launch::handle_launch(output.clone(), opts, interactive && !*yes).await
+        }
+        Commands::New { template, directory, name, from } =>
+            commands::template::handle_new(template.clone(), directory.clone(), name.clone(), from.clone()).await,
+        Commands::Templates(cmd) => match cmd {
+            TemplatesCommands::List { from } => commands::template::handle_list(from.clone()).await,
+        },
+        Commands::Clone { target, directory } =>
+            commands::clone::handle_clone(target.clone(), directory.clone()).await,
+        Commands::Deploy { command: Some(DeployCommands::Promote { file, app, env_vars, rolling }), .. } =>
+            commands::canary::handle_promote(file.clone(), app.clone(), env_vars.clone(), *rolling, interactive).await,
+        Commands::Deploy { command: Some(DeployCommands::Abort { file, app }), .. } =>
+            commands::canary::handle_abort(file.clone(), app.clone(), interactive).await,
+        Commands::Deploy { command: None, file, service, app, restart_only, env_vars, node, region, rolling, force, no_pull, init, override_policy, summary_file, all_apps, verify, dry_run, env, canary } => {
+            let opts = commands::deploy::DeployOptions {
+                file: file.clone(),
+                service_filter: service.clone(),
+                app_filter: app.clone(),
+                restart_only: *restart_only,
+                env_vars: env_vars.clone(),
+                node_filter: *node,
+                region_filter: region.clone(),
+                rolling: *rolling,
+                force: *force,
+                no_pull: *no_pull,
+                init: *init,
+                override_policy: override_policy.clone(),
+                interactive,
+                summary_file: summary_file.clone(),
+                all_apps: *all_apps,
+                verify: *verify,
+                dry_run: *dry_run,
+                env: env.clone(),
+            };
+            commands::deploy::handle_deploy(opts, canary.map(commands::canary::Mode::Start)).await
+        }
+        Commands::Verify { file, app } =>
+            commands::verify::handle_verify(file.clone(), app.clone()).await,
+        Commands::Preview(cmd) => match cmd {
+            PreviewCommands::Up { branch, ttl, file, env_vars } =>
+                commands::preview::handle_up(file.clone(), branch.clone(), ttl.clone(), env_vars.clone()).await,
+            PreviewCommands::Down { branch, file } =>
+                commands::preview::handle_down(file.clone(), branch.clone(), interactive).await,
+            PreviewCommands::List { file } => commands::preview::handle_list(file.clone()).await,
+        },
+        Commands::Image(cmd) => match cmd {
+            ImageCommands::List { file } => commands::image::handle_list(file.clone()).await,
+            ImageCommands::Clean { file, keep, dry_run, force } =>
+                commands::image::handle_clean(file.clone(), *keep, *dry_run, *force, interactive).await,
+        },
+        Commands::Secrets(cmd) => match cmd {
+            SecretsCommands::Scan { file } => commands::secrets::handle_scan(file.clone()),
+            SecretsCommands::Set { assignment, target, file } =>
+                commands::secret_store::handle_set(file.clone(), target.clone(), assignment.clone()).await,
+            SecretsCommands::Get { key, target, file } =>
+                commands::secret_store::handle_get(file.clone(), target.clone(), key.clone()).await,
+            SecretsCommands::List { target, file } =>
+                commands::secret_store::handle_list(file.clone(), target.clone()).await,
+            SecretsCommands::Rm { key, target, file } =>
+                commands::secret_store::handle_rm(file.clone(), target.clone(), key.clone(), interactive).await,
+        },
+        Commands::Policy { file, env_vars } =>
+            commands::policy::handle_policy(file.clone(), env_vars.clone()),
+        Commands::Build { file, git_ref, service, tag, no_push, jobs, summary_file } =>
+            commands::build::handle_build(file.clone(), git_ref.clone(), service.clone(), tag.clone(), *no_push, *jobs, summary_file.clone()).await,
+        Commands::RollingRestart { service, file, app, scale } =>
+            commands::restart::handle_rolling_restart(file.clone(), service.clone(), app.clone(), *scale).await,
+        Commands::Down { file, app, keep_routes, delete, force } =>
+            commands::down::handle_down(file.clone(), app.clone(), *keep_routes, *delete, *force, interactive).await,
+        Commands::Compose(cmd) => match cmd {
+            ComposeCommands::Render { file, app, env_vars } =>
+                commands::compose::handle_render(file.clone(), app.clone(), env_vars.clone()),
+        },
+        Commands::Bundle(cmd) => match cmd {
+            BundleCommands::Create { file, output, app } =>
+                commands::bundle::handle_create(file.clone(), output.clone(), app.clone()),
+            BundleCommands::Apply { bundle, node } =>
+                commands::bundle::handle_apply(bundle.clone(), *node).await,
+        },
+        Commands::Status { file, no_probe, stats, env } =>
+            commands::status::handle_status(file.clone(), *no_probe, *stats, env.clone()).await,
+        Commands::Ps { file, app, env, table } =>
+            commands::ps::handle_ps(file.clone(), env.clone(), app.clone(), table).await,
+        Commands::Releases(ReleasesCommands::List { file, app }) =>
+            commands::release::handle_list(file.clone(), app.clone()).await,
+        Commands::Rollback { file, app, to } =>
+            commands::release::handle_rollback(file.clone(), app.clone(), *to, interactive).await,
+        Commands::Events { target, file, limit, logs } =>
+            commands::events::handle_events(file.clone(), target.clone(), *limit, *logs).await,
+        Commands::Wait { deployment, healthy, node, timeout, interval } =>
+            commands::wait::handle_wait(*deployment, healthy.clone(), *node, *timeout, *interval).await,
+        Commands::Doctor { node, file } =>
+            commands::doctor::handle_doctor(*node, file.clone()).await,
+        Commands::Drift { target, file } =>
+            commands::drift::handle_drift(file.clone(), target.clone()).await,
+        Commands::Export { project, out } =>
+            commands::blueprint::handle_export(project.clone(), out.clone()).await,
+        Commands::Import { file, project } =>
+            commands::blueprint::handle_import(file.clone(), project.clone(), interactive).await,
+        Commands::Cost { project } => commands::cost::handle_cost(project.clone()).await,
+        Commands::Report(cmd) => match cmd {
+            ReportCommands::Usage { app, range, format, file } =>
+                commands::report::handle_usage(file.clone(), app.clone(), range.clone(), format.clone()).await,
+        },
+        Commands::Graph { project, format, file } =>
+            commands::graph::handle_graph(file.clone(), project.clone(), format.clone()).await,
+        Commands::Run { service, command, file, target, env_vars, set_vars } =>
+            commands::run::handle_run(file.clone(), service.clone(), command.clone(), target.clone(), env_vars.clone(), set_vars.clone()).await,
+        Commands::Exec { service, command, file, target, env_vars, user, set_vars } =>
+            commands::exec::handle_exec(file.clone(), service.clone(), command.clone(), target.clone(), env_vars.clone(), user.clone(), set_vars.clone()).await,
+        Commands::Logs { service, file, target, tail, follow, access, all_nodes, since, until, grep, env } => {
+            let opts = commands::logs::LogOptions {
+                tail: *tail, follow: *follow, all_nodes: *all_nodes, since: since.clone(), until: until.clone(), grep: grep.clone(),
+            };
+            commands::logs::handle_logs(file.clone(), service.clone(), target.clone(), *access, env.clone(), opts).await
+        },
+
+        Commands::Serve { config, token, port, compose_dir, install, domain, public_metrics } => {
+            let overrides = serve::config::Overrides {
+                token: token.clone(), port: *port, compose_dir: compose_dir.clone(), public_metrics: *public_metrics,
+            };
             if *install {
-                commands::serve::handle_install(token.clone(), *port, compose_dir.clone(), domain.clone()).await
+                commands::serve::handle_install(config.clone(), overrides, domain.clone()).await
             } else {
-                commands::serve::handle_serve(token.clone(), *port, compose_dir.clone()).await
+                commands::serve::handle_serve(config.clone(), overrides).await
             }
         },
 
         Commands::Domain(cmd) => match cmd {
             DomainCommands::Add { domain, app, file } =>
                 commands::domain::handle_add(file.clone(), domain.clone(), app.clone()).await,
-            DomainCommands::List { app, file } =>
-                commands::domain::handle_list(file.clone(), app.clone()).await,
+            DomainCommands::List { app, file, table } =>
+                commands::domain::handle_list(file.clone(), app.clone(), table).await,
             DomainCommands::Remove { domain, file } =>
                 commands::domain::handle_remove(file.clone(), domain.clone()).await,
-            DomainCommands::Sync { file, app, prune, yes } =>
-                commands::domain::handle_sync(file.clone(), app.clone(), *prune, interactive && !*yes).await,
+            DomainCommands::Sync { file, app, prune, remove, yes, env } =>
+                commands::domain::handle_sync(file.clone(), app.clone(), *prune, remove.clone(), env.clone(), interactive && !*yes).await,
+            DomainCommands::Cloudflare(CloudflareCommands::Link { domain, app, file, proxied, yes }) =>
+                commands::domain::handle_cloudflare_link(file.clone(), domain.clone(), app.clone(), *proxied, *yes, interactive).await,
+            DomainCommands::Cloudflare(CloudflareCommands::Token) =>
+                commands::domain::handle_cloudflare_token().await,
         },
 
         Commands::Pool(cmd) => match cmd {
-            PoolCommands::Status { target } =>
-                commands::pool::handle_status(target.clone()).await,
+            PoolCommands::Status { target, table } =>
+                commands::pool::handle_status(target.clone(), table).await,
             PoolCommands::Strategy { target, strategy } =>
                 commands::pool::handle_strategy(target.clone(), strategy.clone()).await,
+            PoolCommands::Sticky { target, mode, name } =>
+                commands::pool::handle_sticky(target.clone(), mode.clone(), name.clone()).await,
             PoolCommands::Drain { target, node } =>
                 commands::pool::handle_drain(target.clone(), *node).await,
             PoolCommands::Undrain { target, node } =>
                 commands::pool::handle_undrain(target.clone(), *node).await,
+            PoolCommands::Healthcheck { target, check_type, endpoint, interval, timeout, unhealthy, healthy, no_verify } =>
+                commands::pool::handle_healthcheck(target.clone(), types::HealthCheckConfig {
+                    check_type: check_type.clone(),
+                    endpoint: endpoint.clone(),
+                    interval_seconds: *interval,
+                    timeout_seconds: *timeout,
+                    unhealthy_threshold: *unhealthy,
+                    healthy_threshold: *healthy,
+                }, *no_verify).await,
+            PoolCommands::FailoverTest { target, node, duration, path } =>
+                commands::pool::handle_failover_test(target.clone(), *node, *duration, path.clone(), interactive).await,
+            PoolCommands::Weight { target, node, weight } =>
+                commands::pool::handle_weight(target.clone(), *node, *weight).await,
         },
 
-        Commands::Tunnel { target, port, node } =>
-            commands::tunnel::handle_tunnel(target.clone(), *port, *node).await,
+        Commands::Tunnel { command: Some(TunnelCommands::List { table }), .. } =>
+            commands::tunnel::handle_list(table).await,
+        Commands::Tunnel { command: Some(TunnelCommands::Kill { id }), .. } =>
+            commands::tunnel::handle_kill(*id).await,
+        Commands::Tunnel { command: Some(TunnelCommands::Heartbeat { id }), .. } =>
+            commands::tunnel::handle_heartbeat(*id).await,
+        Commands::Tunnel { command: None, target, port, node, detach } =>
+            commands::tunnel::handle_tunnel(target.clone(), *port, *node, *detach).await,
 
+        Commands::Gc { dry_run, force } => commands::gc::handle_gc(*dry_run, *force, interactive).await,
         Commands::Update => commands::update::handle_update().await,
+        Commands::Completions { shell } => commands::completions::handle_completions(shell),
         Commands::Version => {
             o_detail!("ops-cli version: {}", env!("CARGO_PKG_VERSION").cyan());
             tokio::task::spawn_blocking(|| {
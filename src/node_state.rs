@@ -0,0 +1,153 @@
+// src/node_state.rs
+//! Node-local manifest of everything `ops init` changed, so re-runs converge and `--uninstall` can revert
+
+use crate::privilege::Privilege;
+use crate::router::DEFAULT_SERVE_PORT;
+use crate::utils::shell_quote;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+
+pub const STATE_FILE: &str = "/etc/ops/node-state.json";
+/// Manifest of a user-level init (no root, no sudo), relative to $HOME
+pub const USER_STATE_FILE: &str = ".config/ops/node-state.json";
+
+/// Root-only env file holding OPS_SERVE_TOKEN, referenced by the ops-serve unit's EnvironmentFile=
+pub const SERVE_ENV_FILE: &str = "/etc/ops/serve.env";
+/// SERVE_ENV_FILE of a user-level init, relative to $HOME
+pub const USER_SERVE_ENV_FILE: &str = ".config/ops/serve.env";
+/// `ops serve` settings written by `ops init`; see serve::config
+pub const SERVE_CONFIG_FILE: &str = "/etc/ops/serve.toml";
+/// sha256 manifests of the `[[sync]]` entries last synced to this node
+pub const SYNC_MANIFEST_DIR: &str = "/etc/ops/sync";
+
+/// Shell, run on the node, that calls its ops serve: the token from SERVE_ENV_FILE and the port from
+/// SERVE_CONFIG_FILE (DEFAULT_SERVE_PORT when it sets none). `curl_args` go before the URL.
+pub fn serve_request(curl_args: &str, path: &str) -> String {
+    format!(
+        ". {env} 2>/dev/null; port=$(sed -n 's/^port *= *\\([0-9][0-9]*\\).*/\\1/p' {conf} 2>/dev/null | head -n1); \
+curl -fs {args} -H \"Authorization: Bearer $OPS_SERVE_TOKEN\" \"http://127.0.0.1:${{port:-{default}}}\"{path}",
+        env = SERVE_ENV_FILE, conf = SERVE_CONFIG_FILE, args = curl_args, default = DEFAULT_SERVE_PORT, path = shell_quote(path),
+    )
+}
+
+fn user_state_file() -> Option<PathBuf> {
+    std::env::var("HOME").ok().map(|home| Path::new(&home).join(USER_STATE_FILE))
+}
+
+/// Write the serve token to SERVE_ENV_FILE with 0600 permissions
+pub fn write_serve_env(token: &str) -> Result<()> {
+    write_serve_env_as(&Privilege::Root, SERVE_ENV_FILE, token)
+}
+
+/// Write the serve token to `path` with 0600 permissions, through sudo when that is how root is reached
+pub fn write_serve_env_as(privilege: &Privilege, path: &str, token: &str) -> Result<()> {
+    privilege.write(path, &format!("OPS_SERVE_TOKEN={}\n", token), 0o600)
+}
+
+/// How a managed file came to be, which decides how it is reverted
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileMode {
+    /// Did not exist before → delete
+    Created,
+    /// Replaced wholesale → restore `backup`
+    Replaced,
+    /// Ops block merged into a user file → strip the block
+    Merged,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManagedFile {
+    pub path: String,
+    pub mode: FileMode,
+    pub backup: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthorizedKey {
+    pub path: String,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct NodeState {
+    pub version: String,
+    pub node_id: Option<i64>,
+    pub domain: Option<String>,
+    #[serde(default)]
+    pub files: Vec<ManagedFile>,
+    #[serde(default)]
+    pub authorized_keys: Vec<AuthorizedKey>,
+    #[serde(default)]
+    pub services: Vec<String>,
+    #[serde(default)]
+    pub packages: Vec<String>,
+    /// Set up without root: a `systemctl --user` service and no Caddy
+    #[serde(default)]
+    pub user_level: bool,
+}
+
+impl NodeState {
+    /// Load the manifest, None if this node was never initialized (or by an older ops)
+    pub fn load() -> Result<Option<Self>> {
+        let path = match user_state_file() {
+            Some(user) if !Path::new(STATE_FILE).exists() && user.exists() => user,
+            _ if Path::new(STATE_FILE).exists() => PathBuf::from(STATE_FILE),
+            _ => return Ok(None),
+        };
+        let content = fs::read_to_string(&path).context("Failed to read node state file")?;
+        let state = serde_json::from_str(&content).context("Failed to parse node state file")?;
+        Ok(Some(state))
+    }
+
+    /// Where this manifest is kept: STATE_FILE, or USER_STATE_FILE for a user-level init
+    pub fn path(&self) -> Result<String> {
+        if !self.user_level {
+            return Ok(STATE_FILE.to_string());
+        }
+        let path = user_state_file().context("Could not find HOME directory")?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        self.save_as(&Privilege::Root)
+    }
+
+    /// Save, writing STATE_FILE through sudo when that is how init reaches root
+    pub fn save_as(&mut self, privilege: &Privilege) -> Result<()> {
+        self.version = env!("CARGO_PKG_VERSION").to_string();
+        let content = serde_json::to_string_pretty(self)?;
+        privilege.write(&self.path()?, &content, 0o644).context("Failed to write node state file")
+    }
+
+    pub fn remove(&self, privilege: &Privilege) -> Result<()> {
+        privilege.remove_file(&self.path()?).context("Failed to remove node state file")
+    }
+
+    /// Record a managed file. The first record wins, so the original pre-ops backup is kept across re-runs.
+    pub fn record_file(&mut self, path: &str, mode: FileMode, backup: Option<String>) {
+        if !self.files.iter().any(|f| f.path == path) {
+            self.files.push(ManagedFile { path: path.to_string(), mode, backup });
+        }
+    }
+
+    pub fn record_key(&mut self, path: &str, key: &str) {
+        if !self.authorized_keys.iter().any(|k| k.path == path && k.key == key) {
+            self.authorized_keys.push(AuthorizedKey { path: path.to_string(), key: key.to_string() });
+        }
+    }
+
+    pub fn record_service(&mut self, name: &str) {
+        if !self.services.iter().any(|s| s == name) {
+            self.services.push(name.to_string());
+        }
+    }
+
+    pub fn record_package(&mut self, name: &str) {
+        if !self.packages.iter().any(|p| p == name) {
+            self.packages.push(name.to_string());
+        }
+    }
+}
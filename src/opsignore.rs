@@ -0,0 +1,195 @@
+//! `.opsignore` (gitignore syntax) at the root of a push: paths `source = "push"` deploys and
+//! builds never send, on top of the built-in excludes. With `[deploy] gitignore = true` the root
+//! `.gitignore` applies first, and `.opsignore` can re-include from it with `!`.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+pub const OPSIGNORE_FILE: &str = ".opsignore";
+/// Directories every push skips anyway; not worth walking
+const ALWAYS_EXCLUDED_DIRS: [&str; 4] = ["target", "node_modules", ".git", ".ops"];
+
+#[derive(Debug)]
+struct Rule {
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole relative path rather than any file name
+    anchored: bool,
+}
+
+/// Rules in file order; the last one matching a path decides, as in git
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    pub fn parse(text: &str) -> IgnoreRules {
+        let mut rules = IgnoreRules::default();
+        for line in text.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').filter(|r| r.starts_with(['#', '!'])).unwrap_or(line)),
+            };
+            let dir_only = line.ends_with('/');
+            let line = line.trim_end_matches('/');
+            if line.is_empty() {
+                continue;
+            }
+            let anchored = line.contains('/');
+            rules.rules.push(Rule { pattern: line.trim_start_matches('/').to_string(), negated, dir_only, anchored });
+        }
+        rules
+    }
+
+    /// `.gitignore` (when `gitignore`) then `.opsignore` from `root`; missing files add nothing
+    pub fn load(root: &Path, gitignore: bool) -> Result<IgnoreRules> {
+        let mut text = String::new();
+        let files = if gitignore { vec![".gitignore", OPSIGNORE_FILE] } else { vec![OPSIGNORE_FILE] };
+        for name in files {
+            let path = root.join(name);
+            if path.is_file() {
+                text += &fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+                text.push('\n');
+            }
+        }
+        Ok(IgnoreRules::parse(&text))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether `rel` ('/'-separated, relative to the root) is ignored. Callers walk top-down and
+    /// don't descend into ignored directories, so a parent's rule covers its contents.
+    pub fn is_ignored(&self, rel: &str, is_dir: bool) -> bool {
+        let name = rel.rsplit('/').next().unwrap_or(rel);
+        self.rules.iter().rev()
+            .find(|r| (is_dir || !r.dir_only)
+                && glob_match(r.pattern.as_bytes(), if r.anchored { rel } else { name }.as_bytes()))
+            .is_some_and(|r| !r.negated)
+    }
+
+    /// The top-most ignored paths under `root`, directories with a trailing '/'
+    pub fn ignored_paths(&self, root: &Path) -> Result<Vec<String>> {
+        let mut out = Vec::new();
+        if !self.is_empty() {
+            self.walk(root, "", &mut out)?;
+        }
+        Ok(out)
+    }
+
+    fn walk(&self, dir: &Path, rel: &str, out: &mut Vec<String>) -> Result<()> {
+        let mut entries: Vec<_> = fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?
+            .flatten().collect();
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if is_dir && ALWAYS_EXCLUDED_DIRS.contains(&name.as_str()) {
+                continue;
+            }
+            let rel_path = if rel.is_empty() { name } else { format!("{}/{}", rel, name) };
+            if self.is_ignored(&rel_path, is_dir) {
+                out.push(if is_dir { format!("{}/", rel_path) } else { rel_path });
+            } else if is_dir {
+                self.walk(&entry.path(), &rel_path, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// gitignore globs: `*` and `?` stop at '/', `**` doesn't, `[a-z]` / `[!a-z]` classes, `\` escapes
+fn glob_match(p: &[u8], s: &[u8]) -> bool {
+    match p.first() {
+        None => s.is_empty(),
+        Some(b'*') if p.get(1) == Some(&b'*') => {
+            let rest = &p[2..];
+            // `a/**/b` also matches `a/b`
+            if rest.first() == Some(&b'/') && glob_match(&rest[1..], s) {
+                return true;
+            }
+            (0..=s.len()).any(|i| glob_match(rest, &s[i..]))
+        }
+        Some(b'*') => (0..=s.len())
+            .take_while(|&i| i == 0 || s[i - 1] != b'/')
+            .any(|i| glob_match(&p[1..], &s[i..])),
+        Some(b'?') => s.first().is_some_and(|&c| c != b'/') && glob_match(&p[1..], &s[1..]),
+        Some(b'[') => match p.iter().skip(2).position(|&c| c == b']').map(|i| i + 2) {
+            Some(end) => s.first().is_some_and(|&c| c != b'/' && class_match(&p[1..end], c)) && glob_match(&p[end + 1..], &s[1..]),
+            None => s.first() == Some(&b'[') && glob_match(&p[1..], &s[1..]),
+        },
+        Some(b'\\') if p.len() > 1 => s.first() == Some(&p[1]) && glob_match(&p[2..], &s[1..]),
+        Some(&c) => s.first() == Some(&c) && glob_match(&p[1..], &s[1..]),
+    }
+}
+
+fn class_match(class: &[u8], c: u8) -> bool {
+    let (negated, class) = match class.first() {
+        Some(b'!' | b'^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut i = 0;
+    let mut found = false;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == b'-' {
+            found |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_and_ignored_paths() {
+        let rules = IgnoreRules::parse("# local only\ndatasets/\n*.log\n!keep.log\n/coverage\ndocs/**/*.pdf\n.venv\n\\#notes\n");
+        assert!(rules.is_ignored("datasets", true));
+        assert!(!rules.is_ignored("datasets", false));
+        assert!(rules.is_ignored("app/datasets", true));
+        assert!(rules.is_ignored("app/debug.log", false));
+        assert!(!rules.is_ignored("app/keep.log", false));
+        assert!(rules.is_ignored("coverage", true));
+        assert!(!rules.is_ignored("app/coverage", true));
+        assert!(rules.is_ignored("docs/a/b/manual.pdf", false));
+        assert!(rules.is_ignored("docs/manual.pdf", false));
+        assert!(!rules.is_ignored("manual.pdf", false));
+        assert!(rules.is_ignored("#notes", false));
+        assert!(glob_match(b"img[0-9].png", b"img7.png"));
+        assert!(!glob_match(b"img[!0-9].png", b"img7.png"));
+        assert!(!glob_match(b"*.png", b"a/b.png"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("datasets/raw")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("dist")).unwrap();
+        fs::write(root.join("datasets/raw/a.csv"), "").unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("src/trace.log"), "").unwrap();
+        fs::write(root.join("dist/app.js"), "").unwrap();
+        fs::write(root.join(".gitignore"), "dist/\n").unwrap();
+        fs::write(root.join(OPSIGNORE_FILE), "datasets/\n*.log\n").unwrap();
+
+        let rules = IgnoreRules::load(root, false).unwrap();
+        assert_eq!(rules.ignored_paths(root).unwrap(), vec!["datasets/", "src/trace.log"]);
+        let rules = IgnoreRules::load(root, true).unwrap();
+        assert_eq!(rules.ignored_paths(root).unwrap(), vec!["datasets/", "dist/", "src/trace.log"]);
+        // .opsignore re-includes what .gitignore leaves out
+        fs::write(root.join(OPSIGNORE_FILE), "!dist/\n").unwrap();
+        assert!(IgnoreRules::load(root, true).unwrap().ignored_paths(root).unwrap().is_empty());
+    }
+}
@@ -17,6 +17,7 @@ pub enum Verbosity {
 }
 
 static VERBOSITY: OnceLock<Verbosity> = OnceLock::new();
+static JSON: OnceLock<bool> = OnceLock::new();
 
 /// Initialize the global verbosity level. Must be called once from main().
 pub fn init(v: Verbosity) {
@@ -28,6 +29,23 @@ pub fn verbosity() -> Verbosity {
     *VERBOSITY.get().unwrap_or(&Verbosity::Normal)
 }
 
+/// Enable `--json`: commands that support it print one JSON document on stdout,
+/// and the text macros stay silent. Call once from main(), with Quiet verbosity.
+pub fn init_json(enabled: bool) {
+    JSON.set(enabled).expect("output::init_json called more than once");
+}
+
+/// Whether `--json` was given
+pub fn json() -> bool {
+    *JSON.get().unwrap_or(&false)
+}
+
+/// Print `value` as pretty JSON on stdout
+pub fn print_json<T: serde::Serialize>(value: &T) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
 /// Major phase header. Shown at Normal+.
 #[macro_export]
 macro_rules! o_step {
@@ -94,10 +112,12 @@ macro_rules! o_print {
     };
 }
 
-/// Final result summary. Always shown (even in Quiet mode).
+/// Final result summary. Always shown (even in Quiet mode), except with `--json`.
 #[macro_export]
 macro_rules! o_result {
     ($($arg:tt)*) => {
-        println!($($arg)*);
+        if !$crate::output::json() {
+            println!($($arg)*);
+        }
     };
 }
@@ -0,0 +1,151 @@
+// src/privilege.rs
+//! How `ops init` reaches the root-only parts of a server (systemd units, Caddy, packages):
+//! directly as root, through passwordless `sudo -n`, or not at all, in which case init falls
+//! back to a user-level setup
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Privilege {
+    /// Running as root
+    Root,
+    /// Not root, but `sudo -n` works without a password
+    Sudo,
+    /// Neither; only the user's own files and `systemctl --user`
+    User,
+}
+
+pub fn is_root() -> bool {
+    std::env::var("USER").unwrap_or_default() == "root"
+}
+
+impl Privilege {
+    pub fn detect() -> Self {
+        if is_root() {
+            return Privilege::Root;
+        }
+        let sudo = Command::new("sudo")
+            .args(["-n", "true"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if sudo.is_ok_and(|s| s.success()) { Privilege::Sudo } else { Privilege::User }
+    }
+
+    /// `program`, run through `sudo -n` when that is how root is reached
+    pub fn command(&self, program: &str) -> Command {
+        match self {
+            Privilege::Sudo => {
+                let mut cmd = Command::new("sudo");
+                cmd.args(["-n", program]);
+                cmd
+            }
+            Privilege::Root | Privilege::User => Command::new(program),
+        }
+    }
+
+    /// systemctl for the system manager, or the user's manager without root
+    pub fn systemctl(&self) -> Command {
+        let mut cmd = self.command("systemctl");
+        if *self == Privilege::User {
+            cmd.arg("--user");
+        }
+        cmd
+    }
+
+    /// Write `content` to `path` with `mode`, creating parent directories
+    pub fn write(&self, path: &str, content: &str, mode: u32) -> Result<()> {
+        if *self != Privilege::Sudo {
+            if let Some(dir) = Path::new(path).parent() {
+                fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .mode(mode)
+                .open(path)
+                .with_context(|| format!("Failed to open {}", path))?;
+            // mode() only applies on creation
+            file.set_permissions(fs::Permissions::from_mode(mode))?;
+            return file.write_all(content.as_bytes()).with_context(|| format!("Failed to write {}", path));
+        }
+
+        // Staged in a file only this user can read, then moved into place by root
+        let mut staged = tempfile::NamedTempFile::new()?;
+        staged.write_all(content.as_bytes())?;
+        staged.flush()?;
+        let status = self.command("install")
+            .args(["-D", "-m", &format!("{:o}", mode)])
+            .arg(staged.path())
+            .arg(path)
+            .status()
+            .with_context(|| format!("Failed to run sudo install for {}", path))?;
+        if !status.success() {
+            bail!("sudo install {} failed", path);
+        }
+        Ok(())
+    }
+
+    pub fn create_dir_all(&self, path: &str) -> Result<()> {
+        if *self != Privilege::Sudo {
+            return fs::create_dir_all(path).with_context(|| format!("Failed to create {}", path));
+        }
+        self.run(self.command("mkdir").args(["-p", path]))
+    }
+
+    /// Remove `path`; a missing file is not an error
+    pub fn remove_file(&self, path: &str) -> Result<()> {
+        if *self != Privilege::Sudo {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        self.run(self.command("rm").args(["-f", path]))
+    }
+
+    pub fn copy(&self, from: &str, to: &str) -> Result<()> {
+        if *self != Privilege::Sudo {
+            return fs::copy(from, to).map(|_| ()).with_context(|| format!("Failed to copy {} to {}", from, to));
+        }
+        self.run(self.command("cp").args([from, to]))
+    }
+
+    fn run(&self, cmd: &mut Command) -> Result<()> {
+        let status = cmd.status().with_context(|| format!("Failed to run {:?}", cmd))?;
+        if !status.success() {
+            bail!("{:?} failed with {}", cmd, status);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_and_write() {
+        let args = |cmd: &Command| cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>();
+        let cmd = Privilege::Sudo.systemctl();
+        assert_eq!((cmd.get_program().to_str(), args(&cmd)), (Some("sudo"), vec!["-n".to_string(), "systemctl".into()]));
+        let cmd = Privilege::User.systemctl();
+        assert_eq!((cmd.get_program().to_str(), args(&cmd)), (Some("systemctl"), vec!["--user".to_string()]));
+        assert_eq!(args(&Privilege::Root.systemctl()), Vec::<String>::new());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/serve.env");
+        let path = path.to_str().unwrap();
+        Privilege::User.write(path, "OPS_SERVE_TOKEN=x\n", 0o600).unwrap();
+        assert_eq!(fs::metadata(path).unwrap().permissions().mode() & 0o777, 0o600);
+        Privilege::User.remove_file(path).unwrap();
+        Privilege::User.remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+// src/project_state.rs
+//! Project-local CLI state in `.ops/` next to ops.toml: things worth remembering between runs
+//! that don't belong in ops.toml or the user's credentials
+
+use crate::config;
+use crate::types::NodeWeight;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+
+pub const STATE_DIR: &str = ".ops";
+const STATE_FILE: &str = "state.json";
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct ProjectState {
+    /// Domain of the node the last successful `ops deploy` went to
+    pub last_target: Option<String>,
+    /// Backend app IDs by app name, as returned when `ops deploy` syncs the app record
+    #[serde(default)]
+    pub app_ids: BTreeMap<String, i64>,
+    /// Canary rollouts in progress by app name, from `ops deploy --canary` until promote or abort
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub canaries: BTreeMap<String, CanaryState>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct CanaryState {
+    pub percent: u8,
+    /// Nodes running the canary release
+    pub nodes: Vec<i64>,
+    pub node_group_id: i64,
+    /// The pool's weights and strategy before the canary, put back when it ends
+    pub weights: Vec<NodeWeight>,
+    pub lb_strategy: Option<String>,
+    pub deployment_id: Option<i64>,
+    /// The release the canary nodes ran before, which `ops deploy abort` rolls them back to
+    pub previous_release: Option<i64>,
+    /// Deploy options `ops deploy promote` repeats on the other nodes
+    pub service: Option<String>,
+    /// Keys of the deploy's `--set KEY=VALUE`s; the values are secrets, so promote asks for them again
+    #[serde(default)]
+    pub env_keys: Vec<String>,
+    #[serde(default)]
+    pub restart_only: bool,
+    #[serde(default)]
+    pub no_pull: bool,
+    pub env: Option<String>,
+}
+
+/// `.ops/` in the directory holding `ops_toml`
+pub fn state_dir(ops_toml: &str) -> PathBuf {
+    let parent = Path::new(ops_toml).parent().filter(|p| !p.as_os_str().is_empty());
+    parent.unwrap_or(Path::new(".")).join(STATE_DIR)
+}
+
+impl ProjectState {
+    /// State for the project of `ops_toml`; empty if nothing was recorded yet
+    pub fn load(ops_toml: &str) -> Result<Self> {
+        let path = state_dir(ops_toml).join(STATE_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Read-modify-write under a lock, so parallel deploys from one checkout don't lose updates
+    pub fn update(ops_toml: &str, f: impl FnOnce(&mut Self)) -> Result<()> {
+        let dir = state_dir(ops_toml);
+        if !dir.exists() {
+            fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+            // Local state, never committed
+            fs::write(dir.join(".gitignore"), "*\n")?;
+        }
+        let path = dir.join(STATE_FILE);
+        config::with_lock(&path, || {
+            let mut state = Self::load(ops_toml)?;
+            f(&mut state);
+            config::write_atomic(&path, &serde_json::to_string_pretty(&state)?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let ops_toml = dir.path().join("ops.toml");
+        let ops_toml = ops_toml.to_str().unwrap();
+
+        assert_eq!(ProjectState::load(ops_toml).unwrap(), ProjectState::default());
+        ProjectState::update(ops_toml, |s| { s.app_ids.insert("api".into(), 7); }).unwrap();
+        ProjectState::update(ops_toml, |s| s.last_target = Some("api.shop.ops.autos".into())).unwrap();
+
+        let state = ProjectState::load(ops_toml).unwrap();
+        assert_eq!(state.app_ids.get("api"), Some(&7));
+        assert_eq!(state.last_target.as_deref(), Some("api.shop.ops.autos"));
+        assert!(dir.path().join(".ops/.gitignore").exists());
+        assert_eq!(state_dir("ops.toml"), Path::new(".").join(".ops"));
+    }
+}
@@ -78,3 +78,68 @@ pub fn select(prompt: &str, options: &[&str], default_index: usize, interactive:
         _ => Ok(default_index),
     }
 }
+
+/// Indices picked from "1,3", "2-4", "all" or "" (none); None when the input is invalid
+fn parse_selection(input: &str, len: usize) -> Option<Vec<usize>> {
+    let input = input.trim().to_lowercase();
+    if input.is_empty() || input == "none" {
+        return Some(vec![]);
+    }
+    if input == "all" || input == "a" {
+        return Some((0..len).collect());
+    }
+    let mut picked = Vec::new();
+    for part in input.split(|c: char| c == ',' || c.is_whitespace()).filter(|p| !p.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((a, b)) => (a.trim().parse::<usize>().ok()?, b.trim().parse::<usize>().ok()?),
+            None => { let n = part.parse::<usize>().ok()?; (n, n) }
+        };
+        if start < 1 || end > len || start > end {
+            return None;
+        }
+        for n in start..=end {
+            if !picked.contains(&(n - 1)) {
+                picked.push(n - 1);
+            }
+        }
+    }
+    picked.sort_unstable();
+    Some(picked)
+}
+
+/// Multi-selection from a numbered list ("1,3", "2-4", "all", Enter for none).
+/// Non-interactive returns every index when `all_by_default`, else none.
+pub fn multi_select(prompt: &str, options: &[&str], all_by_default: bool, interactive: bool) -> Result<Vec<usize>> {
+    if !interactive {
+        return Ok(if all_by_default { (0..options.len()).collect() } else { vec![] });
+    }
+    for (i, opt) in options.iter().enumerate() {
+        o_detail!("   {} {}", format!("{})", i + 1).cyan(), opt);
+    }
+    loop {
+        o_print!("\n   {} [1,3 / 2-4 / all, Enter for none]: ", prompt);
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        match parse_selection(&input, options.len()) {
+            Some(picked) => return Ok(picked),
+            None => o_warn!("   Invalid selection '{}', use numbers from 1 to {}", input.trim(), options.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(parse_selection("", 3), Some(vec![]));
+        assert_eq!(parse_selection("all\n", 3), Some(vec![0, 1, 2]));
+        assert_eq!(parse_selection("3, 1", 3), Some(vec![0, 2]));
+        assert_eq!(parse_selection("2-3 2", 3), Some(vec![1, 2]));
+        assert_eq!(parse_selection("4", 3), None);
+        assert_eq!(parse_selection("0", 3), None);
+        assert_eq!(parse_selection("x", 3), None);
+    }
+}
@@ -0,0 +1,297 @@
+// src/registry.rs
+//! Minimal Docker Registry HTTP API v2 client for `ops image`: tags, creation dates and deletes.
+//! Auth follows the registry's challenge: basic auth, or a bearer token from its token service.
+//! Also turns `[deploy.registry]` / `[build.image]` credentials, static or from a helper, into `docker login`.
+
+use crate::api::{http_client, SendWithRetry};
+use crate::commands::common::resolve_env_value;
+use crate::commands::ssh::SshSession;
+use crate::utils::shell_quote;
+use anyhow::{bail, Context, Result};
+use reqwest::{header, Method, Response, StatusCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const DOCKER_HUB: &str = "registry-1.docker.io";
+/// Single-image manifests first, so `created` rarely needs a second request
+const MANIFEST_TYPES: &str = "application/vnd.docker.distribution.manifest.v2+json, \
+    application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.oci.image.index.v1+json";
+
+/// GCE/GKE metadata server, for nodes without gcloud
+const GCP_METADATA_TOKEN: &str = "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// How to get a registry password
+#[derive(Debug, PartialEq)]
+pub enum Credentials {
+    Static { username: String, password: String },
+    /// Short-lived: `command` prints a fresh password on whichever machine needs one
+    Helper { username: String, command: String },
+}
+
+/// One tag of a repository
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagInfo {
+    pub tag: String,
+    pub digest: String,
+    /// RFC 3339 from the image config, None when the registry doesn't expose it
+    pub created: Option<String>,
+}
+
+pub struct Registry {
+    host: String,
+    username: String,
+    password: String,
+    /// Token scope actions, e.g. "pull" or "pull,delete"
+    actions: &'static str,
+    /// Bearer tokens by repository
+    tokens: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct TagList {
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+/// Split an image prefix into (registry host, repository path):
+/// "ghcr.io/org/app" → ("ghcr.io", "org/app"); "org/app" → Docker Hub
+pub fn split_image(prefix: &str) -> (String, String) {
+    let prefix = prefix.trim_end_matches('/');
+    match prefix.split_once('/') {
+        Some((host, path)) if host.contains('.') || host.contains(':') || host == "localhost" => {
+            (host.to_string(), path.to_string())
+        }
+        _ => (DOCKER_HUB.to_string(), prefix.to_string()),
+    }
+}
+
+/// AWS region of an ECR host: `<account>.dkr.ecr.<region>.amazonaws.com`
+fn ecr_region(host: &str) -> Option<String> {
+    let (_, rest) = host.split_once(".dkr.ecr.")?;
+    rest.split('.').next().filter(|r| !r.is_empty()).map(String::from)
+}
+
+impl Credentials {
+    /// Credentials for `host` from the config's `username`, `token`, `helper` and `region`
+    pub fn resolve(host: &str, username: &str, token: &str, helper: Option<&str>, region: Option<&str>) -> Result<Self> {
+        match helper {
+            None => Ok(Credentials::Static {
+                username: resolve_env_value(username)?,
+                password: resolve_env_value(token)?,
+            }),
+            Some("ecr") => {
+                let region = region.map(String::from).or_else(|| ecr_region(host))
+                    .with_context(|| format!("Cannot tell the AWS region from {}; set region", host))?;
+                Ok(Credentials::Helper {
+                    username: "AWS".into(),
+                    command: format!("aws ecr get-login-password --region {}", shell_quote(&region)),
+                })
+            }
+            Some("gcr") => Ok(Credentials::Helper {
+                username: "oauth2accesstoken".into(),
+                command: format!(
+                    "gcloud auth print-access-token 2>/dev/null || curl -sf -H 'Metadata-Flavor: Google' {} | sed -n 's/.*\"access_token\": *\"\\([^\"]*\\)\".*/\\1/p'",
+                    GCP_METADATA_TOKEN
+                ),
+            }),
+            Some("harbor") => {
+                // Robot accounts are named robot$<name> or robot$<project>+<name>: not an env var
+                if !username.starts_with("robot$") {
+                    bail!("Harbor robot account username should start with robot$, got '{}'", username);
+                }
+                Ok(Credentials::Static { username: username.to_string(), password: resolve_env_value(token)? })
+            }
+            Some(other) => bail!("Unknown registry helper '{}' (expected ecr, gcr or harbor)", other),
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        match self {
+            Credentials::Static { username, .. } | Credentials::Helper { username, .. } => username,
+        }
+    }
+
+    /// Log docker on the node in to `host`. Helpers run on the node, right before the pull or
+    /// push, so the node's own cloud identity is used and the token is fresh.
+    pub fn docker_login(&self, session: &SshSession, host: &str) -> Result<()> {
+        let login = format!("docker login {} -u {} --password-stdin", shell_quote(host), shell_quote(self.username()));
+        match self {
+            Credentials::Static { password, .. } => session.exec_with_secret(&login, password),
+            Credentials::Helper { command, .. } => session.exec(
+                &format!("pw=$({}) && [ -n \"$pw\" ] && printf '%s\\n' \"$pw\" | {}", command, login),
+                None,
+            ).context("Registry credential helper failed on the node"),
+        }
+    }
+
+    /// Password for registry API calls from this machine; helpers run locally
+    pub fn password(&self) -> Result<String> {
+        match self {
+            Credentials::Static { password, .. } => Ok(password.clone()),
+            Credentials::Helper { command, .. } => {
+                let output = std::process::Command::new("sh").arg("-c").arg(command).output()
+                    .context("Failed to run the registry credential helper")?;
+                let password = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                if !output.status.success() || password.is_empty() {
+                    bail!("Registry credential helper failed: {}\n{}", command, String::from_utf8_lossy(&output.stderr).trim());
+                }
+                Ok(password)
+            }
+        }
+    }
+}
+
+/// (realm, service) of a `WWW-Authenticate: Bearer realm="...",service="..."` challenge
+fn parse_bearer_challenge(header: &str) -> Option<(String, Option<String>)> {
+    let params = header.trim().strip_prefix("Bearer ")?;
+    let mut realm = None;
+    let mut service = None;
+    for part in params.split(',') {
+        let Some((key, value)) = part.trim().split_once('=') else { continue };
+        let value = value.trim_matches('"').to_string();
+        match key {
+            "realm" => realm = Some(value),
+            "service" => service = Some(value),
+            _ => {}
+        }
+    }
+    Some((realm?, service))
+}
+
+impl Registry {
+    pub fn new(host: &str, username: &str, password: &str, actions: &'static str) -> Self {
+        Registry {
+            host: host.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            actions,
+            tokens: HashMap::new(),
+        }
+    }
+
+    async fn request(&mut self, method: Method, repo: &str, path: &str) -> Result<Response> {
+        let url = format!("https://{}/v2/{}/{}", self.host, repo, path);
+        for _ in 0..2 {
+            let req = http_client().request(method.clone(), &url).header(header::ACCEPT, MANIFEST_TYPES);
+            let req = match self.tokens.get(repo) {
+                Some(token) => req.bearer_auth(token),
+                None => req.basic_auth(&self.username, Some(&self.password)),
+            };
+            let res = req.send_with_retry().await.with_context(|| format!("{} {}", method, url))?;
+            if res.status() != StatusCode::UNAUTHORIZED || self.tokens.contains_key(repo) {
+                return Ok(res);
+            }
+            let challenge = res.headers().get(header::WWW_AUTHENTICATE).and_then(|v| v.to_str().ok()).unwrap_or("");
+            let Some((realm, service)) = parse_bearer_challenge(challenge) else { return Ok(res) };
+            let token = self.fetch_token(&realm, service.as_deref(), repo).await?;
+            self.tokens.insert(repo.to_string(), token);
+        }
+        bail!("{} {}: unauthorized", method, url)
+    }
+
+    async fn fetch_token(&self, realm: &str, service: Option<&str>, repo: &str) -> Result<String> {
+        let scope = format!("repository:{}:{}", repo, self.actions);
+        let mut query = vec![("scope", scope.as_str())];
+        if let Some(service) = service {
+            query.push(("service", service));
+        }
+        let res = http_client()
+            .get(realm)
+            .query(&query)
+            .basic_auth(&self.username, Some(&self.password))
+            .send_with_retry()
+            .await?;
+        if !res.status().is_success() {
+            bail!("{} refused a token for {} ({})", self.host, scope, res.status());
+        }
+        let body: TokenResponse = res.json().await.context("Invalid token response")?;
+        body.token.or(body.access_token).context("Token response has no token")
+    }
+
+    async fn json(&mut self, repo: &str, path: &str) -> Result<(Option<String>, serde_json::Value)> {
+        let res = self.request(Method::GET, repo, path).await?;
+        if !res.status().is_success() {
+            bail!("GET {}/{}: {}", repo, path, res.status());
+        }
+        let digest = res.headers().get("docker-content-digest").and_then(|v| v.to_str().ok()).map(String::from);
+        Ok((digest, res.json().await?))
+    }
+
+    pub async fn tags(&mut self, repo: &str) -> Result<Vec<String>> {
+        let res = self.request(Method::GET, repo, "tags/list?n=1000").await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !res.status().is_success() {
+            bail!("Listing tags of {}/{}: {}", self.host, repo, res.status());
+        }
+        Ok(res.json::<TagList>().await?.tags.unwrap_or_default())
+    }
+
+    /// Digest and creation time of `repo:tag`. For multi-arch images, the time comes from the first platform.
+    pub async fn tag_info(&mut self, repo: &str, tag: &str) -> Result<TagInfo> {
+        let (digest, mut manifest) = self.json(repo, &format!("manifests/{}", tag)).await?;
+        let digest = digest.with_context(|| format!("{}:{} has no digest", repo, tag))?;
+        if let Some(first) = manifest["manifests"][0]["digest"].as_str().map(String::from) {
+            manifest = self.json(repo, &format!("manifests/{}", first)).await?.1;
+        }
+        let created = match manifest["config"]["digest"].as_str() {
+            Some(config) => self.json(repo, &format!("blobs/{}", config)).await.ok()
+                .and_then(|(_, c)| c["created"].as_str().map(String::from)),
+            None => None,
+        };
+        Ok(TagInfo { tag: tag.to_string(), digest, created })
+    }
+
+    /// Delete a manifest, and with it every tag pointing at it
+    pub async fn delete(&mut self, repo: &str, digest: &str) -> Result<()> {
+        let res = self.request(Method::DELETE, repo, &format!("manifests/{}", digest)).await?;
+        match res.status() {
+            s if s.is_success() => Ok(()),
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::UNSUPPORTED_MEDIA_TYPE =>
+                bail!("{} does not support deleting through the registry API", self.host),
+            s => bail!("Deleting {}@{}: {}", repo, digest, s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_challenge() {
+        assert_eq!(split_image("ghcr.io/org/redq/"), ("ghcr.io".into(), "org/redq".into()));
+        assert_eq!(split_image("localhost:5000/app"), ("localhost:5000".into(), "app".into()));
+        assert_eq!(split_image("org/app"), (DOCKER_HUB.into(), "org/app".into()));
+        assert_eq!(
+            parse_bearer_challenge(r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:o/a:pull""#),
+            Some(("https://ghcr.io/token".into(), Some("ghcr.io".into())))
+        );
+        assert_eq!(parse_bearer_challenge(r#"Basic realm="Registry""#), None);
+    }
+
+    #[test]
+    fn test_credentials_resolve() {
+        let ecr = Credentials::resolve("123456789012.dkr.ecr.eu-west-1.amazonaws.com", "oauth2", "", Some("ecr"), None).unwrap();
+        assert_eq!(ecr, Credentials::Helper {
+            username: "AWS".into(),
+            command: "aws ecr get-login-password --region eu-west-1".into(),
+        });
+        assert!(Credentials::resolve("registry.example.com", "oauth2", "", Some("ecr"), None).is_err());
+
+        let harbor = Credentials::resolve("harbor.example.com", "robot$shop+ci", "secret", Some("harbor"), None).unwrap();
+        assert_eq!(harbor.username(), "robot$shop+ci");
+        assert_eq!(shell_quote(harbor.username()), "'robot$shop+ci'");
+        assert!(Credentials::resolve("harbor.example.com", "ci", "secret", Some("harbor"), None).is_err());
+        assert!(Credentials::resolve("x.io", "u", "t", Some("vault"), None).is_err());
+    }
+}
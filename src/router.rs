@@ -0,0 +1,532 @@
+// src/router.rs
+//! Reverse-proxy backends for app routes: Caddy (default) or nginx (`[deploy] router = "nginx"`).
+//! Every fragment starts with an owner header so `ops down` and `ops node routes` can trace it back to its app.
+
+use crate::caddy;
+use crate::commands::ssh::SshSession;
+use crate::types::{AppDef, Compression, Protocol};
+use anyhow::{anyhow, Result};
+
+const OWNER_PREFIX: &str = "# ops-owner: ";
+
+const NGINX_CONF_D: &str = "/etc/nginx/conf.d";
+const NGINX_SNIPPETS_DIR: &str = "/etc/nginx/conf.d/ops-routes";
+const NGINX_BASE_CONF: &str = "/etc/nginx/conf.d/ops.conf";
+pub const DEFAULT_SERVE_PORT: u16 = 8377;
+/// Owned by the caddy user in Caddy's packages
+const CADDY_LOG_DIR: &str = "/var/log/caddy";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Router {
+    Caddy,
+    Nginx,
+}
+
+/// How a route selects its requests
+#[derive(Debug, Clone)]
+pub enum RouteMatch {
+    /// `X-OPS-Target: app.project`, set by the ops edge
+    Target(String),
+    /// Request Host (custom domains pointed directly at the node)
+    Host(String),
+    /// `X-Forwarded-Host`, set by the ops edge for [[routes]] domains
+    ForwardedHost(String),
+}
+
+/// How the proxy talks to an app's upstream
+#[derive(Debug, Clone, Default)]
+pub struct ProxyOptions {
+    pub protocol: Protocol,
+    /// Sticky sessions across upstreams: ("cookie" | "header", name)
+    pub affinity: Option<(&'static str, String)>,
+    /// Response encodings, in order of preference
+    pub compression: Vec<Compression>,
+    /// Advertise (true) or stop advertising (false) HTTP/3; Caddy only
+    pub http3: Option<bool>,
+    /// Request body limit in bytes
+    pub max_body: Option<u64>,
+    /// Cache-Control max-age in seconds for requests matching the path patterns; Caddy only
+    pub cache_static: Option<(u64, Vec<String>)>,
+    /// JSON access log of the route's owner app, at `access_log_path`; Caddy only
+    pub access_log: bool,
+}
+
+impl ProxyOptions {
+    /// `protocol`, `lb` and `edge` of an [[apps]] entry (validated by `load_ops_toml`)
+    pub fn for_app(app: &AppDef) -> Self {
+        ProxyOptions {
+            protocol: app.protocol,
+            affinity: app.lb.affinity().ok().flatten(),
+            compression: app.edge.compression.clone(),
+            http3: app.edge.http3,
+            max_body: app.edge.max_body_bytes().ok().flatten(),
+            cache_static: app.static_cache().ok().flatten(),
+            access_log: app.access_logs,
+        }
+    }
+
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+}
+
+impl Router {
+    /// Parse `[deploy] router` (None = caddy)
+    pub fn from_config(name: Option<&str>) -> Result<Self> {
+        match name.unwrap_or("caddy") {
+            "caddy" => Ok(Router::Caddy),
+            "nginx" => Ok(Router::Nginx),
+            other => Err(anyhow!("Unknown router '{}' (expected caddy or nginx)", other)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Router::Caddy => "Caddy",
+            Router::Nginx => "nginx",
+        }
+    }
+
+    /// Shell globs matching every route fragment this router owns
+    pub fn fragment_globs(&self) -> Vec<String> {
+        match self {
+            Router::Caddy => vec![format!("{}/*.caddy", caddy::ROUTES_DIR)],
+            Router::Nginx => vec![
+                format!("{}/ops-*.conf", NGINX_CONF_D),
+                format!("{}/*.inc", NGINX_SNIPPETS_DIR),
+            ],
+        }
+    }
+
+    /// Render a route fragment. `stem` is the fragment name without extension
+    /// (e.g. "ops-api-RedQ"); `upstream` may list several space-separated addresses (Caddy only).
+    /// Returns (remote path, content).
+    pub fn render(&self, stem: &str, owner_app: &str, project: &str, m: &RouteMatch, upstream: &str, opts: &ProxyOptions) -> (String, String) {
+        let owner = owner_header(owner_app, project);
+        let protocol = opts.protocol;
+        // nginx fragments proxy to a single address; only Caddy balances several
+        let upstream = match self {
+            Router::Caddy => upstream,
+            Router::Nginx => upstream.split_whitespace().next().unwrap_or(upstream),
+        };
+        match self {
+            Router::Caddy => {
+                let (label, matcher_name, matcher) = match m {
+                    RouteMatch::Target(t) => (t, format!("ops_{}_{}", owner_app, project).replace('-', "_"), format!("header X-OPS-Target {}", t)),
+                    RouteMatch::Host(d) => (d, d.replace(['.', '-'], "_"), format!("host {}", d)),
+                    RouteMatch::ForwardedHost(d) => (d, format!("ops_route_{}", d.replace(['.', '-'], "_")), format!("header X-Forwarded-Host {}", d)),
+                };
+                let (logger, log_name) = if opts.access_log { caddy_access_log(owner_app, project) } else { Default::default() };
+                let content = format!(
+                    "{owner}# {label}\n{logger}@{name} {matcher}\nhandle @{name} {{\n{log_name}{edge}{proxy}}}\n",
+                    owner = owner, label = label, logger = logger, name = matcher_name, matcher = matcher,
+                    log_name = log_name, edge = caddy_edge(&matcher_name, opts), proxy = caddy_proxy(upstream, opts),
+                );
+                (format!("{}/{}.caddy", caddy::ROUTES_DIR, stem), content)
+            }
+            Router::Nginx => match m {
+                // Custom domains get their own server block
+                RouteMatch::Host(d) => {
+                    let (http2, location) = match protocol {
+                        Protocol::Http => ("", format!("include {}/proxy_params;\n        proxy_pass http://{};", NGINX_SNIPPETS_DIR, upstream)),
+                        Protocol::Grpc => (
+                            "    http2 on;\n",
+                            format!("grpc_pass grpc://{};\n        grpc_set_header Host $host;\n        grpc_read_timeout 1h;\n        grpc_send_timeout 1h;", upstream),
+                        ),
+                        Protocol::Websocket => (
+                            "",
+                            format!("include {}/proxy_params;\n        proxy_pass http://{};\n        proxy_read_timeout 1h;\n        proxy_send_timeout 1h;", NGINX_SNIPPETS_DIR, upstream),
+                        ),
+                    };
+                    let mut edge = String::new();
+                    if let Some(bytes) = opts.max_body {
+                        edge.push_str(&format!("    client_max_body_size {};\n", bytes));
+                    }
+                    if opts.compression.contains(&Compression::Gzip) {
+                        edge.push_str(&format!("    gzip on;\n    gzip_proxied any;\n    gzip_types {};\n", NGINX_GZIP_TYPES));
+                    }
+                    let content = format!(
+                        "{owner}# {d}\nserver {{\n    listen 80;\n    listen [::]:80;\n{http2}    server_name {d};\n{edge}\n    location / {{\n        {location}\n    }}\n}}\n",
+                        owner = owner, d = d, http2 = http2, edge = edge, location = location,
+                    );
+                    (format!("{}/{}.conf", NGINX_CONF_D, stem), content)
+                }
+                // Header matches are included into the ops server's `location /`.
+                // Timeouts cannot be set inside `if`; that server keeps its own.
+                RouteMatch::Target(v) | RouteMatch::ForwardedHost(v) => {
+                    let var = if matches!(m, RouteMatch::Target(_)) { "$http_x_ops_target" } else { "$http_x_forwarded_host" };
+                    let pass = match protocol {
+                        Protocol::Grpc => format!("grpc_pass grpc://{}", upstream),
+                        Protocol::Http | Protocol::Websocket => format!("proxy_pass http://{}", upstream),
+                    };
+                    // Only `gzip` is allowed inside `if`; the ops server's own settings cover the rest
+                    let gzip = if opts.compression.contains(&Compression::Gzip) { "    gzip on;\n" } else { "" };
+                    let content = format!(
+                        "{owner}# {v}\nif ({var} = \"{v}\") {{\n{gzip}    {pass};\n}}\n",
+                        owner = owner, v = v, var = var, gzip = gzip, pass = pass,
+                    );
+                    (format!("{}/{}.inc", NGINX_SNIPPETS_DIR, stem), content)
+                }
+            },
+        }
+    }
+
+    /// ops.toml options in `opts` that this router leaves out of an app's X-OPS-Target route
+    pub fn ignored_options(&self, opts: &ProxyOptions) -> Vec<&'static str> {
+        let mut ignored = Vec::new();
+        if *self == Router::Caddy {
+            return ignored;
+        }
+        if opts.compression.contains(&Compression::Zstd) {
+            ignored.push("edge.compression \"zstd\"");
+        }
+        if opts.http3.is_some() {
+            ignored.push("edge.http3");
+        }
+        // client_max_body_size isn't allowed inside `if`; only custom-domain server blocks get it
+        if opts.max_body.is_some() {
+            ignored.push("edge.max_body");
+        }
+        if opts.cache_static.is_some() {
+            ignored.push("cache_static");
+        }
+        if opts.access_log {
+            ignored.push("access_logs");
+        }
+        ignored
+    }
+
+    /// Make sure the router's base config exists on the node (nginx only; Caddy is set up by `ops init`)
+    pub fn ensure_base(&self, session: &SshSession) -> Result<()> {
+        match self {
+            Router::Caddy => session.exec(&format!("mkdir -p {}", caddy::ROUTES_DIR), None),
+            Router::Nginx => {
+                session.exec(&format!("mkdir -p {}", NGINX_SNIPPETS_DIR), None)?;
+                session.exec(&format!("cat > {}/proxy_params", NGINX_SNIPPETS_DIR), Some(NGINX_PROXY_PARAMS))?;
+                let base = nginx_base_conf(DEFAULT_SERVE_PORT);
+                session.exec(&format!("[ -f {0} ] || cat > {0}", NGINX_BASE_CONF), Some(&base))
+            }
+        }
+    }
+
+    /// Write a rendered fragment
+    pub fn write(&self, session: &SshSession, path: &str, content: &str) -> Result<()> {
+        session.exec(&format!("cat > {}", path), Some(content))
+    }
+
+    /// Shell that validates and reloads, for use on the node itself
+    pub fn reload_command(&self) -> &'static str {
+        match self {
+            Router::Caddy => "caddy validate --config /etc/caddy/Caddyfile && systemctl reload caddy",
+            Router::Nginx => "nginx -t -q && systemctl reload nginx",
+        }
+    }
+
+    /// Validate and reload
+    pub fn reload(&self, session: &SshSession) -> Result<()> {
+        session.exec(self.reload_command(), None)
+    }
+
+    /// List all fragments of this router on the node with their owners
+    pub fn list_fragments(&self, session: &SshSession) -> Result<Vec<Fragment>> {
+        let cmd = format!(
+            "for f in {}; do [ -e \"$f\" ] || continue; printf '%s\\t%s\\n' \"$f\" \"$(grep -m1 '^{}' \"$f\")\"; done",
+            self.fragment_globs().join(" "),
+            OWNER_PREFIX.trim_end()
+        );
+        let output = session.exec_output(&cmd)?;
+        Ok(String::from_utf8_lossy(&output)
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                let (path, header) = l.split_once('\t').unwrap_or((l, ""));
+                let file = path.rsplit('/').next().unwrap_or(path).to_string();
+                Fragment { router: *self, path: path.to_string(), file, owner: parse_owner(header) }
+            })
+            .collect())
+    }
+
+    /// Remove the given fragments (full paths from `list_fragments`) and reload
+    pub fn remove_fragments(&self, session: &SshSession, paths: &[String]) -> Result<()> {
+        if paths.is_empty() {
+            return Ok(());
+        }
+        let quoted: Vec<String> = paths.iter()
+            .filter(|p| !p.contains('\''))
+            .map(|p| format!("'{}'", p))
+            .collect();
+        session.exec(&format!("rm -f {}", quoted.join(" ")), None)?;
+        self.reload(session)
+    }
+
+    /// Remove all fragments owned by app.project. Returns the removed file names.
+    pub fn remove_app_fragments(&self, session: &SshSession, app: &str, project: &str) -> Result<Vec<String>> {
+        let owned: Vec<Fragment> = self.list_fragments(session)?
+            .into_iter()
+            .filter(|f| f.belongs_to(app, project))
+            .collect();
+        let paths: Vec<String> = owned.iter().map(|f| f.path.clone()).collect();
+        self.remove_fragments(session, &paths)?;
+        Ok(owned.into_iter().map(|f| f.file).collect())
+    }
+}
+
+/// A route fragment found on a node
+#[derive(Debug, Clone)]
+pub struct Fragment {
+    pub router: Router,
+    pub path: String,
+    pub file: String,
+    /// (app, project) from the owner header; None for fragments written before ownership tracking
+    pub owner: Option<(String, String)>,
+}
+
+impl Fragment {
+    /// Tunnel fragments are cleaned up by `ops tunnel` itself
+    pub fn is_tunnel(&self) -> bool {
+        self.file.starts_with("ops-tunnel-")
+    }
+
+    /// Whether this fragment belongs to app.project (owner header, or legacy `ops-{app}-{project}.caddy` name)
+    pub fn belongs_to(&self, app: &str, project: &str) -> bool {
+        match &self.owner {
+            Some((a, p)) => a == app && p == project,
+            None => self.file == format!("ops-{}-{}.caddy", app, project),
+        }
+    }
+}
+
+/// First line of every fragment ops writes, so it can be traced back to its app
+pub fn owner_header(app: &str, project: &str) -> String {
+    format!("{}{}.{}\n", OWNER_PREFIX, app, project)
+}
+
+/// Parse the owner header line ("# ops-owner: api.RedQ")
+pub fn parse_owner(line: &str) -> Option<(String, String)> {
+    let owner = line.trim().strip_prefix(OWNER_PREFIX.trim_end())?.trim();
+    let (app, project) = owner.split_once('.')?;
+    if app.is_empty() || project.is_empty() {
+        return None;
+    }
+    Some((app.to_string(), project.to_string()))
+}
+
+/// Access log file of app.project, written by Caddy when the app sets `access_logs = true`
+pub fn access_log_path(app: &str, project: &str) -> String {
+    format!("{}/access-{}.{}.log", CADDY_LOG_DIR, app, project)
+}
+
+/// A named Caddy logger writing JSON to the app's access log, and the `log_name` line that sends
+/// a route's requests to it. Every fragment of the app defines the same logger (Caddy 2.8+).
+fn caddy_access_log(app: &str, project: &str) -> (String, String) {
+    let name = format!("ops_access_{}_{}", app, project).replace(['-', '.'], "_");
+    let logger = format!(
+        "log {name} {{\n    output file {path} {{\n        roll_size 50MiB\n        roll_keep 5\n    }}\n    format json\n    no_hostname\n}}\n",
+        name = name, path = access_log_path(app, project),
+    );
+    (logger, format!("    log_name {}\n", name))
+}
+
+/// Caddy `encode`, `request_body`, Alt-Svc and Cache-Control directives from the app's options,
+/// indented for the `handle @name` body
+fn caddy_edge(name: &str, opts: &ProxyOptions) -> String {
+    let mut edge = String::new();
+    if !opts.compression.is_empty() {
+        let names: Vec<&str> = opts.compression.iter().map(|c| c.name()).collect();
+        edge.push_str(&format!("    encode {}\n", names.join(" ")));
+    }
+    if let Some(bytes) = opts.max_body {
+        edge.push_str(&format!("    request_body {{\n        max_size {}\n    }}\n", bytes));
+    }
+    // Caddy serves HTTP/3 on its HTTPS sites by default; the header only tells clients about it
+    match opts.http3 {
+        Some(true) => edge.push_str("    header Alt-Svc `h3=\":443\"; ma=86400`\n"),
+        Some(false) => edge.push_str("    header -Alt-Svc\n"),
+        None => {}
+    }
+    // Deferred, so it replaces whatever Cache-Control the app sends for these paths
+    if let Some((max_age, paths)) = &opts.cache_static {
+        edge.push_str(&format!(
+            "    @{name}_static path {paths}\n    header @{name}_static {{\n        Cache-Control \"public, max-age={max_age}\"\n        defer\n    }}\n",
+            name = name, paths = paths.join(" "), max_age = max_age,
+        ));
+    }
+    edge
+}
+
+/// Caddy `reverse_proxy` block for an upstream, indented for a `handle` body
+fn caddy_proxy(upstream: &str, opts: &ProxyOptions) -> String {
+    let mut directives = Vec::new();
+    match opts.protocol {
+        Protocol::Http => {}
+        // gRPC backends rarely speak TLS; streams must not be buffered
+        Protocol::Grpc => directives.extend([
+            "transport http {\n            versions h2c 2\n            dial_timeout 10s\n        }".to_string(),
+            "flush_interval -1".to_string(),
+        ]),
+        // Keep sockets open for a day, and for 5 minutes across Caddy reloads
+        Protocol::Websocket => directives.extend(["flush_interval -1", "stream_timeout 24h", "stream_close_delay 5m"].map(String::from)),
+    }
+    if let Some((mode, name)) = &opts.affinity {
+        directives.push(format!("lb_policy {} {}", mode, name));
+    }
+
+    if directives.is_empty() {
+        return format!("    reverse_proxy {}\n", upstream);
+    }
+    let body: String = directives.iter().map(|d| format!("        {}\n", d)).collect();
+    format!("    reverse_proxy {} {{\n{}    }}\n", upstream, body)
+}
+
+/// Text responses worth compressing (nginx always includes text/html)
+const NGINX_GZIP_TYPES: &str = "text/css text/plain text/xml application/javascript application/json application/xml image/svg+xml";
+
+const NGINX_PROXY_PARAMS: &str = "proxy_set_header Host $host;
+proxy_set_header X-Real-IP $remote_addr;
+proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;
+proxy_set_header X-Forwarded-Proto $scheme;
+proxy_http_version 1.1;
+proxy_set_header Upgrade $http_upgrade;
+proxy_set_header Connection $ops_connection_upgrade;
+";
+
+/// Server for ops domains: header-matched route snippets, then the ops-serve fallback
+fn nginx_base_conf(serve_port: u16) -> String {
+    format!(
+        "# ops-managed: routes from `ops deploy` (router = \"nginx\")
+map $http_upgrade $ops_connection_upgrade {{
+    default upgrade;
+    ''      close;
+}}
+
+server {{
+    listen 80;
+    listen [::]:80;
+    server_name *.ops.autos;
+
+    location / {{
+        include {dir}/proxy_params;
+        include {dir}/*.inc;
+
+        # Fallback: ops-serve daemon
+        proxy_pass http://127.0.0.1:{port};
+    }}
+}}
+",
+        dir = NGINX_SNIPPETS_DIR,
+        port = serve_port,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_owner() {
+        assert_eq!(parse_owner("# ops-owner: api.RedQ"), Some(("api".into(), "RedQ".into())));
+        assert_eq!(parse_owner(owner_header("web", "shop").trim_end()), Some(("web".into(), "shop".into())));
+        assert_eq!(parse_owner("# api.RedQ"), None);
+    }
+
+    #[test]
+    fn test_render_matches_backend() {
+        let m = RouteMatch::Target("api.RedQ".into());
+        let (path, caddy) = Router::Caddy.render("ops-api-RedQ", "api", "RedQ", &m, "10.0.0.5:3000", &ProxyOptions::default());
+        assert_eq!(path, "/etc/caddy/routes.d/ops-api-RedQ.caddy");
+        assert!(caddy.starts_with("# ops-owner: api.RedQ\n"));
+        assert!(caddy.contains("@ops_api_RedQ header X-OPS-Target api.RedQ"));
+
+        let (path, nginx) = Router::Nginx.render("ops-api-RedQ", "api", "RedQ", &m, "10.0.0.5:3000", &ProxyOptions::default());
+        assert_eq!(path, "/etc/nginx/conf.d/ops-routes/ops-api-RedQ.inc");
+        assert!(nginx.contains("if ($http_x_ops_target = \"api.RedQ\")"));
+
+        let host = RouteMatch::Host("api.example.com".into());
+        let (path, nginx) = Router::Nginx.render("ops-api-RedQ-api_example_com", "api", "RedQ", &host, "10.0.0.5:3000", &ProxyOptions::default());
+        assert_eq!(path, "/etc/nginx/conf.d/ops-api-RedQ-api_example_com.conf");
+        assert!(nginx.contains("server_name api.example.com;"));
+    }
+
+    #[test]
+    fn test_render_protocols() {
+        let m = RouteMatch::Target("rpc.RedQ".into());
+        let (_, grpc) = Router::Caddy.render("ops-rpc-RedQ", "rpc", "RedQ", &m, "10.0.0.5:50051", &ProxyOptions::default().with_protocol(Protocol::Grpc));
+        assert!(grpc.contains("reverse_proxy 10.0.0.5:50051 {\n        transport http {\n            versions h2c 2\n"));
+        let (_, ws) = Router::Caddy.render("ops-rpc-RedQ", "rpc", "RedQ", &m, "10.0.0.5:3000", &ProxyOptions::default().with_protocol(Protocol::Websocket));
+        assert!(ws.contains("stream_timeout 24h"));
+
+        let (_, nginx) = Router::Nginx.render("ops-rpc-RedQ", "rpc", "RedQ", &m, "10.0.0.5:50051", &ProxyOptions::default().with_protocol(Protocol::Grpc));
+        assert!(nginx.contains("    grpc_pass grpc://10.0.0.5:50051;\n"));
+        let host = RouteMatch::Host("rpc.example.com".into());
+        let (_, nginx) = Router::Nginx.render("ops-rpc-RedQ-host", "rpc", "RedQ", &host, "10.0.0.5:50051", &ProxyOptions::default().with_protocol(Protocol::Grpc));
+        assert!(nginx.contains("    listen 80;\n    listen [::]:80;\n    http2 on;\n"));
+        assert!(nginx.contains("grpc_read_timeout 1h;"));
+
+        // Sticky replicas: Caddy balances them, nginx keeps the first
+        let app: AppDef = toml::from_str("name = \"web\"\nservices = [\"web\"]\nlb.sticky = \"cookie\"").unwrap();
+        let (_, caddy) = Router::Caddy.render("ops-web-RedQ", "web", "RedQ", &m, "10.0.0.5:3000 10.0.0.6:3000", &ProxyOptions::for_app(&app));
+        assert!(caddy.contains("    reverse_proxy 10.0.0.5:3000 10.0.0.6:3000 {\n        lb_policy cookie ops_sticky\n    }\n"));
+        let (_, nginx) = Router::Nginx.render("ops-web-RedQ", "web", "RedQ", &m, "10.0.0.5:3000 10.0.0.6:3000", &ProxyOptions::for_app(&app));
+        assert!(nginx.contains("proxy_pass http://10.0.0.5:3000;"));
+        let header: AppDef = toml::from_str("name = \"web\"\nservices = [\"web\"]\nlb.sticky = \"header\"").unwrap();
+        assert!(header.lb.affinity().is_err());
+
+        assert_eq!(Protocol::Http.or(Protocol::Grpc), Protocol::Grpc);
+        assert_eq!(Protocol::Websocket.or(Protocol::Grpc), Protocol::Websocket);
+    }
+    #[test]
+    fn test_render_edge_options() {
+        let app: AppDef = toml::from_str("name = \"web\"\nservices = [\"web\"]\n[edge]\ncompression = [\"zstd\", \"gzip\"]\nhttp3 = true\nmax_body = \"10MB\"").unwrap();
+        let opts = ProxyOptions::for_app(&app);
+        assert_eq!(opts.max_body, Some(10 << 20));
+
+        let m = RouteMatch::Target("web.RedQ".into());
+        let (_, caddy) = Router::Caddy.render("ops-web-RedQ", "web", "RedQ", &m, "10.0.0.5:3000", &opts);
+        assert!(caddy.contains("handle @ops_web_RedQ {\n    encode zstd gzip\n    request_body {\n        max_size 10485760\n    }\n    header Alt-Svc `h3=\":443\"; ma=86400`\n    reverse_proxy 10.0.0.5:3000\n}"));
+
+        let (_, inc) = Router::Nginx.render("ops-web-RedQ", "web", "RedQ", &m, "10.0.0.5:3000", &opts);
+        assert!(inc.contains("{\n    gzip on;\n    proxy_pass http://10.0.0.5:3000;\n}"));
+        let host = RouteMatch::Host("web.example.com".into());
+        let (_, server) = Router::Nginx.render("ops-web-RedQ-host", "web", "RedQ", &host, "10.0.0.5:3000", &opts);
+        assert!(server.contains("    client_max_body_size 10485760;\n    gzip on;\n"));
+        assert_eq!(Router::Nginx.ignored_options(&opts), vec!["edge.compression \"zstd\"", "edge.http3", "edge.max_body"]);
+        assert!(Router::Caddy.ignored_options(&opts).is_empty());
+
+        let mut edge = app.edge.clone();
+        edge.max_body = Some("512k".into());
+        assert_eq!(edge.max_body_bytes().unwrap(), Some(512 << 10));
+        edge.max_body = Some("lots".into());
+        assert!(edge.max_body_bytes().is_err());
+    }
+    #[test]
+    fn test_render_static_cache() {
+        let app: AppDef = toml::from_str("name = \"web\"\nservices = [\"web\"]\ncache_static = \"7d\"\ncache_paths = [\"/assets/*\", \"*.css\"]").unwrap();
+        let m = RouteMatch::Target("web.RedQ".into());
+        let (_, caddy) = Router::Caddy.render("ops-web-RedQ", "web", "RedQ", &m, "10.0.0.5:3000", &ProxyOptions::for_app(&app));
+        assert!(caddy.contains(
+            "    @ops_web_RedQ_static path /assets/* *.css\n    header @ops_web_RedQ_static {\n        Cache-Control \"public, max-age=604800\"\n        defer\n    }\n    reverse_proxy"
+        ));
+
+        let mut app = app;
+        app.cache_paths.clear();
+        assert!(app.static_cache().unwrap().unwrap().1.contains(&"/_next/static/*".to_string()));
+        app.cache_paths = vec!["assets/*".into()];
+        assert!(app.static_cache().is_err());
+        app.cache_static = Some("a week".into());
+        assert!(app.static_cache().unwrap_err().to_string().contains("not a duration"));
+    }
+
+    #[test]
+    fn test_render_access_log() {
+        let app: AppDef = toml::from_str("name = \"web-app\"\nservices = [\"web\"]\naccess_logs = true").unwrap();
+        let m = RouteMatch::Host("shop.example.com".into());
+        let (_, caddy) = Router::Caddy.render("ops-web-app-RedQ-shop", "web-app", "RedQ", &m, "10.0.0.5:3000", &ProxyOptions::for_app(&app));
+        assert!(caddy.contains("# shop.example.com\nlog ops_access_web_app_RedQ {\n    output file /var/log/caddy/access-web-app.RedQ.log {\n"));
+        assert!(caddy.contains("    format json\n    no_hostname\n}\n@shop_example_com host shop.example.com\n"));
+        assert!(caddy.contains("handle @shop_example_com {\n    log_name ops_access_web_app_RedQ\n    reverse_proxy"));
+
+        let (_, plain) = Router::Caddy.render("ops-web-app-RedQ", "web-app", "RedQ", &m, "10.0.0.5:3000", &ProxyOptions::default());
+        assert!(!plain.contains("log"));
+    }
+}
@@ -39,14 +39,27 @@ pub fn render_dockerfile(info: &SourceInfo) -> String {
     out
 }
 
-/// Render a docker-compose.yml from project name + SourceInfo
-pub fn render_compose(project_name: &str, info: &SourceInfo) -> String {
-    let service_name = project_name
+/// Compose service name `render_compose` uses for a project
+pub fn service_name(project_name: &str) -> String {
+    project_name
         .to_lowercase()
         .replace(' ', "-")
         .chars()
         .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
-        .collect::<String>();
+        .collect()
+}
+
+/// First port an existing Dockerfile EXPOSEs, e.g. `EXPOSE 8080/tcp`
+pub fn exposed_port(dockerfile: &str) -> Option<u16> {
+    dockerfile.lines()
+        .filter_map(|l| l.trim().strip_prefix("EXPOSE "))
+        .flat_map(|ports| ports.split_whitespace())
+        .find_map(|p| p.split('/').next()?.parse().ok())
+}
+
+/// Render a docker-compose.yml from project name + SourceInfo
+pub fn render_compose(project_name: &str, info: &SourceInfo) -> String {
+    let service_name = service_name(project_name);
 
     let mut out = String::new();
     out.push_str("services:\n");
@@ -62,11 +75,34 @@ pub fn render_compose(project_name: &str, info: &SourceInfo) -> String {
         }
     }
 
+    // Docker's own health status, when the framework has a known health endpoint
+    if let Some(ref path) = info.health_path {
+        let url = format!("http://localhost:{}{}", info.port, path);
+        out.push_str("    healthcheck:\n");
+        out.push_str(&format!("      test: {}\n", health_test(info, &url)));
+        out.push_str("      interval: 30s\n      timeout: 5s\n      retries: 3\n      start_period: 30s\n");
+    }
+
     out.push_str("    restart: unless-stopped\n");
 
     out
 }
 
+/// `healthcheck.test` fetching `url` with a tool the runtime image is known to have
+fn health_test(info: &SourceInfo, url: &str) -> String {
+    let runtime = info.dockerfile_stages.last().map(|s| s.base_image.as_str()).unwrap_or("");
+    if runtime.starts_with("node") {
+        format!("[\"CMD\", \"node\", \"-e\", \"fetch('{}').then(r => process.exit(r.ok ? 0 : 1), () => process.exit(1))\"]", url)
+    } else if runtime.starts_with("python") {
+        format!("[\"CMD\", \"python\", \"-c\", \"import urllib.request; urllib.request.urlopen('{}')\"]", url)
+    } else if runtime.starts_with("ruby") {
+        format!("[\"CMD\", \"ruby\", \"-rnet/http\", \"-e\", \"exit Net::HTTP.get_response(URI('{}')).is_a?(Net::HTTPSuccess)\"]", url)
+    } else {
+        // busybox wget in alpine and nginx:alpine, curl elsewhere
+        format!("[\"CMD-SHELL\", \"wget -qO- {0} >/dev/null || curl -fsS {0} >/dev/null || exit 1\"]", url)
+    }
+}
+
 /// Render .dockerignore from SourceInfo
 pub fn render_dockerignore(info: &SourceInfo) -> String {
     let mut entries = info.dockerignore_entries.clone();
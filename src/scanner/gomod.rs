@@ -58,6 +58,7 @@ pub fn scan(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd: "go mod download".into(),
         build_cmd: Some(format!("CGO_ENABLED=0 go build -o {} .", binary_name)),
         start_cmd: format!("./{}", binary_name),
+        health_path: None,
         binary_name: Some(binary_name),
         entry_point: None,
         package_manager: None,
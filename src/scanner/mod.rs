@@ -2,6 +2,8 @@ pub mod node;
 pub mod python;
 pub mod gomod;
 pub mod rust;
+pub mod php;
+pub mod ruby;
 pub mod static_site;
 pub mod dockerfile;
 
@@ -15,6 +17,9 @@ pub enum Framework {
     NuxtJs,
     ViteSpa,
     RemixJs,
+    SvelteKit,
+    Astro,
+    Angular,
     NodeApi,
     GenericNode,
     FastApi,
@@ -23,6 +28,10 @@ pub enum Framework {
     GenericPython,
     Go,
     Rust,
+    Laravel,
+    GenericPhp,
+    Rails,
+    GenericRuby,
     StaticSite,
 }
 
@@ -33,6 +42,9 @@ impl Framework {
             Framework::NuxtJs => "Nuxt",
             Framework::ViteSpa => "Vite SPA",
             Framework::RemixJs => "Remix",
+            Framework::SvelteKit => "SvelteKit",
+            Framework::Astro => "Astro",
+            Framework::Angular => "Angular",
             Framework::NodeApi => "Node.js API",
             Framework::GenericNode => "Node.js",
             Framework::FastApi => "FastAPI",
@@ -41,6 +53,10 @@ impl Framework {
             Framework::GenericPython => "Python",
             Framework::Go => "Go",
             Framework::Rust => "Rust",
+            Framework::Laravel => "Laravel",
+            Framework::GenericPhp => "PHP",
+            Framework::Rails => "Rails",
+            Framework::GenericRuby => "Ruby",
             Framework::StaticSite => "Static Site",
         }
     }
@@ -51,6 +67,9 @@ impl Framework {
             Framework::NuxtJs => 3000,
             Framework::ViteSpa => 80,
             Framework::RemixJs => 3000,
+            Framework::SvelteKit => 3000,
+            Framework::Astro => 4321,
+            Framework::Angular => 4000,
             Framework::NodeApi | Framework::GenericNode => 3000,
             Framework::FastApi => 8000,
             Framework::Django => 8000,
@@ -58,6 +77,9 @@ impl Framework {
             Framework::GenericPython => 8000,
             Framework::Go => 8080,
             Framework::Rust => 8080,
+            Framework::Laravel | Framework::GenericPhp => 8080,
+            Framework::Rails => 3000,
+            Framework::GenericRuby => 9292,
             Framework::StaticSite => 80,
         }
     }
@@ -86,6 +108,8 @@ pub struct SourceInfo {
     pub install_cmd: String,
     pub build_cmd: Option<String>,
     pub start_cmd: String,
+    /// Path that answers 200 once the app is up, when the framework has a known one
+    pub health_path: Option<String>,
     pub binary_name: Option<String>,
     pub entry_point: Option<String>,
     pub package_manager: Option<String>,
@@ -97,18 +121,26 @@ pub struct SourceInfo {
 
 type ScannerFn = fn(&Path) -> Result<Option<SourceInfo>>;
 
-/// Ordered list of scanners — framework-specific first, then generic language, then fallback
+/// Ordered list of scanners — framework-specific first, then generic language, then fallback.
+/// PHP and Ruby go before Node: their apps often carry a package.json for assets (Vite, esbuild).
 fn scanners() -> Vec<(&'static str, ScannerFn)> {
     vec![
         // Framework-level (high priority)
+        ("Laravel",    php::scan_laravel),
+        ("Rails",      ruby::scan_rails),
         ("Next.js",    node::scan_nextjs),
         ("Nuxt",       node::scan_nuxtjs),
         ("Remix",      node::scan_remix),
+        ("SvelteKit",  node::scan_sveltekit),
+        ("Astro",      node::scan_astro),
+        ("Angular",    node::scan_angular),
         ("Vite SPA",   node::scan_vite_spa),
         ("Django",     python::scan_django),
         ("Flask",      python::scan_flask),
         ("FastAPI",    python::scan_fastapi),
         // Language-level
+        ("PHP",        php::scan_generic),
+        ("Ruby",       ruby::scan_generic),
         ("Node.js",    node::scan_generic),
         ("Python",     python::scan_generic),
         ("Go",         gomod::scan),
@@ -127,3 +159,37 @@ pub fn scan(source_dir: &Path) -> Result<Option<SourceInfo>> {
     }
     Ok(None)
 }
+
+/// A temporary project holding `files` (relative path, content), for the scanner tests
+#[cfg(test)]
+pub(crate) fn project(files: &[(&str, &str)]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, content) in files {
+        let path = dir.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_php_and_ruby_before_node() {
+        let vite = r#"{"devDependencies": {"vite": "^5.0", "laravel-vite-plugin": "^1.0"}, "scripts": {"build": "vite build"}}"#;
+        let laravel = project(&[
+            ("composer.json", r#"{"require": {"php": "^8.2", "laravel/framework": "^11.0"}}"#),
+            ("artisan", "#!/usr/bin/env php"),
+            ("package.json", vite),
+        ]);
+        assert_eq!(scan(laravel.path()).unwrap().unwrap().framework, Framework::Laravel);
+
+        let php = project(&[("index.php", "<?php echo 'hi';"), ("package.json", r#"{"scripts": {"build": "tailwindcss -o app.css"}}"#)]);
+        assert_eq!(scan(php.path()).unwrap().unwrap().framework, Framework::GenericPhp);
+
+        let rack = project(&[("Gemfile", "gem \"sinatra\"\n"), ("config.ru", "run App"), ("package.json", r#"{"scripts": {"build": "esbuild app.js"}}"#)]);
+        assert_eq!(scan(rack.path()).unwrap().unwrap().framework, Framework::GenericRuby);
+    }
+}
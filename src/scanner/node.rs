@@ -69,6 +69,9 @@ fn node_dockerignore() -> Vec<String> {
         ".next".into(),
         ".nuxt".into(),
         ".output".into(),
+        ".svelte-kit".into(),
+        ".astro".into(),
+        ".angular".into(),
         "dist".into(),
         ".git".into(),
         ".env*".into(),
@@ -196,11 +199,13 @@ pub fn scan_nextjs(dir: &Path) -> Result<Option<SourceInfo>> {
         framework: Framework::NextJs,
         version: Some(node_ver),
         port,
-        env_vars: vec![("NODE_ENV".into(), "production".into())],
+        // Docker sets HOSTNAME to the container ID; standalone server.js would bind only to that address
+        env_vars: vec![("NODE_ENV".into(), "production".into()), ("HOSTNAME".into(), "0.0.0.0".into())],
         build_args: vec![],
         install_cmd: install_cmd.clone(),
         build_cmd: Some(format!("{} run build", if pm == "bun" { "bun" } else { &pm })),
         start_cmd: "node server.js".into(),
+        health_path: Some("/".into()),
         binary_name: None,
         entry_point: None,
         package_manager: Some(pm),
@@ -275,6 +280,7 @@ pub fn scan_nuxtjs(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd,
         build_cmd: Some(format!("{} run build", if pm == "bun" { "bun" } else { &pm })),
         start_cmd: "node server/index.mjs".into(),
+        health_path: Some("/".into()),
         binary_name: None,
         entry_point: None,
         package_manager: Some(pm),
@@ -357,6 +363,7 @@ pub fn scan_remix(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd,
         build_cmd: Some(format!("{} run build", run_prefix)),
         start_cmd: format!("{} run start", run_prefix),
+        health_path: Some("/".into()),
         binary_name: None,
         entry_point: None,
         package_manager: Some(pm),
@@ -367,6 +374,356 @@ pub fn scan_remix(dir: &Path) -> Result<Option<SourceInfo>> {
     }))
 }
 
+/// Builder stage plus an nginx stage serving `out_dir`, for frameworks that build to static files
+fn static_stages(base: String, pm: &str, install_cmd: &str, run_prefix: &str, out_dir: &str) -> Vec<DockerStage> {
+    vec![
+        DockerStage {
+            name: Some("builder".into()),
+            base_image: base,
+            workdir: "/app".into(),
+            instructions: vec![
+                lockfile_copy(pm).into(),
+                format!("RUN {}", install_cmd),
+                "COPY . .".into(),
+                format!("RUN {} run build", run_prefix),
+            ],
+            expose: None,
+            cmd: None,
+        },
+        DockerStage {
+            name: None,
+            base_image: "nginx:alpine".into(),
+            workdir: "/usr/share/nginx/html".into(),
+            instructions: vec![
+                format!("COPY --from=builder /app/{} .", out_dir),
+            ],
+            expose: Some(80),
+            cmd: Some(vec!["nginx".into(), "-g".into(), "daemon off;".into()]),
+        },
+    ]
+}
+
+/// deps, builder and runner stages for a Node server; the runner gets `copies` from the builder
+/// and the full node_modules from deps
+fn server_stages(base: String, pm: &str, install_cmd: &str, run_prefix: &str, copies: &[&str], port: u16, cmd: Vec<String>) -> Vec<DockerStage> {
+    let mut runner = vec![
+        "ENV NODE_ENV=production".to_string(),
+        "COPY --from=deps /app/node_modules ./node_modules".into(),
+        "COPY --from=builder /app/package.json ./".into(),
+    ];
+    runner.extend(copies.iter().map(|c| format!("COPY --from=builder /app/{0} ./{0}", c)));
+    vec![
+        DockerStage {
+            name: Some("deps".into()),
+            base_image: base.clone(),
+            workdir: "/app".into(),
+            instructions: vec![
+                lockfile_copy(pm).into(),
+                format!("RUN {}", install_cmd),
+            ],
+            expose: None,
+            cmd: None,
+        },
+        DockerStage {
+            name: Some("builder".into()),
+            base_image: base.clone(),
+            workdir: "/app".into(),
+            instructions: vec![
+                "COPY --from=deps /app/node_modules ./node_modules".into(),
+                "COPY . .".into(),
+                format!("RUN {} run build", run_prefix),
+            ],
+            expose: None,
+            cmd: None,
+        },
+        DockerStage {
+            name: None,
+            base_image: base,
+            workdir: "/app".into(),
+            instructions: runner,
+            expose: Some(port),
+            cmd: Some(cmd),
+        },
+    ]
+}
+
+fn read_config(dir: &Path, names: &[&str]) -> String {
+    names.iter().find_map(|n| fs::read_to_string(dir.join(n)).ok()).unwrap_or_default()
+}
+
+// ─── SvelteKit ────────────────────────────────────────────────────
+
+pub fn scan_sveltekit(dir: &Path) -> Result<Option<SourceInfo>> {
+    let pkg = match read_package_json(dir) {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+
+    if !has_dev_dep(&pkg, "@sveltejs/kit") && !has_dep(&pkg, "@sveltejs/kit") {
+        return Ok(None);
+    }
+
+    let (pm, install_cmd) = detect_package_manager(dir);
+    let node_ver = detect_node_version(dir, &pkg);
+    let base = format!("node:{}-alpine", node_ver);
+    let run_prefix = if pm == "bun" { "bun" } else { &pm };
+    let build_cmd = Some(format!("{} run build", run_prefix));
+    let uses = |name: &str| has_dev_dep(&pkg, name) || has_dep(&pkg, name);
+
+    // adapter-static writes plain files to build/; adapter-node a server started with `node build`
+    if uses("@sveltejs/adapter-static") {
+        return Ok(Some(SourceInfo {
+            family: "SvelteKit".into(),
+            framework: Framework::SvelteKit,
+            version: Some(node_ver),
+            port: 80,
+            env_vars: vec![],
+            build_args: vec![],
+            install_cmd: install_cmd.clone(),
+            build_cmd,
+            start_cmd: "nginx -g 'daemon off;'".into(),
+            health_path: Some("/".into()),
+            binary_name: None,
+            entry_point: None,
+            dockerfile_stages: static_stages(base, &pm, &install_cmd, run_prefix, "build"),
+            package_manager: Some(pm),
+            has_lockfile: true,
+            dockerignore_entries: node_dockerignore(),
+            notes: vec![],
+        }));
+    }
+
+    let mut notes = vec![];
+    if !uses("@sveltejs/adapter-node") {
+        notes.push("Install @sveltejs/adapter-node and use it in svelte.config.js; other adapters don't build a server for Docker".into());
+    }
+    let port = detect_port_from_scripts(&pkg).unwrap_or(3000);
+
+    Ok(Some(SourceInfo {
+        family: "SvelteKit".into(),
+        framework: Framework::SvelteKit,
+        version: Some(node_ver),
+        port,
+        // adapter-node reads PORT and listens on 0.0.0.0
+        env_vars: vec![("NODE_ENV".into(), "production".into()), ("PORT".into(), port.to_string())],
+        build_args: vec![],
+        install_cmd: install_cmd.clone(),
+        build_cmd,
+        start_cmd: "node build".into(),
+        health_path: Some("/".into()),
+        binary_name: None,
+        entry_point: None,
+        dockerfile_stages: server_stages(base, &pm, &install_cmd, run_prefix, &["build"], port, vec!["node".into(), "build".into()]),
+        package_manager: Some(pm),
+        has_lockfile: true,
+        dockerignore_entries: node_dockerignore(),
+        notes,
+    }))
+}
+
+// ─── Astro ────────────────────────────────────────────────────────
+
+pub fn scan_astro(dir: &Path) -> Result<Option<SourceInfo>> {
+    let pkg = match read_package_json(dir) {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+
+    if !has_dep(&pkg, "astro") && !has_dev_dep(&pkg, "astro") {
+        return Ok(None);
+    }
+
+    let (pm, install_cmd) = detect_package_manager(dir);
+    let node_ver = detect_node_version(dir, &pkg);
+    let base = format!("node:{}-alpine", node_ver);
+    let run_prefix = if pm == "bun" { "bun" } else { &pm };
+    let build_cmd = Some(format!("{} run build", run_prefix));
+    let config = read_config(dir, &["astro.config.mjs", "astro.config.ts", "astro.config.js", "astro.config.mts"]);
+    let server_output = config.contains("output: 'server'") || config.contains("output: \"server\"")
+        || config.contains("output: 'hybrid'") || config.contains("output: \"hybrid\"");
+
+    // SSR needs the Node adapter in standalone mode; without it the site builds to dist/
+    if !has_dep(&pkg, "@astrojs/node") {
+        let mut notes = vec![];
+        if server_output {
+            notes.push("Server output needs @astrojs/node (mode: 'standalone') to run in Docker; serving the static build".into());
+        }
+        return Ok(Some(SourceInfo {
+            family: "Astro".into(),
+            framework: Framework::Astro,
+            version: Some(node_ver),
+            port: 80,
+            env_vars: vec![],
+            build_args: vec![],
+            install_cmd: install_cmd.clone(),
+            build_cmd,
+            start_cmd: "nginx -g 'daemon off;'".into(),
+            health_path: Some("/".into()),
+            binary_name: None,
+            entry_point: None,
+            dockerfile_stages: static_stages(base, &pm, &install_cmd, run_prefix, "dist"),
+            package_manager: Some(pm),
+            has_lockfile: true,
+            dockerignore_entries: node_dockerignore(),
+            notes,
+        }));
+    }
+
+    let mut notes = vec![];
+    if config.contains("middleware") {
+        notes.push("@astrojs/node is in middleware mode; set mode: 'standalone' so dist/server/entry.mjs starts a server".into());
+    }
+    let port = detect_port_from_scripts(&pkg).unwrap_or(4321);
+    let cmd = vec!["node".to_string(), "./dist/server/entry.mjs".into()];
+
+    Ok(Some(SourceInfo {
+        family: "Astro".into(),
+        framework: Framework::Astro,
+        version: Some(node_ver),
+        port,
+        // The standalone server binds to localhost unless HOST is set
+        env_vars: vec![
+            ("NODE_ENV".into(), "production".into()),
+            ("HOST".into(), "0.0.0.0".into()),
+            ("PORT".into(), port.to_string()),
+        ],
+        build_args: vec![],
+        install_cmd: install_cmd.clone(),
+        build_cmd,
+        start_cmd: "node ./dist/server/entry.mjs".into(),
+        health_path: Some("/".into()),
+        binary_name: None,
+        entry_point: None,
+        dockerfile_stages: server_stages(base, &pm, &install_cmd, run_prefix, &["dist"], port, cmd),
+        package_manager: Some(pm),
+        has_lockfile: true,
+        dockerignore_entries: node_dockerignore(),
+        notes,
+    }))
+}
+
+// ─── Angular ──────────────────────────────────────────────────────
+
+/// The app's project name, build output directory and whether it uses the application
+/// builder (Angular 17+), which puts the browser files under `browser/`
+fn angular_project(dir: &Path, pkg: &serde_json::Value) -> (String, String, bool) {
+    let fallback = pkg.get("name").and_then(|v| v.as_str()).unwrap_or("app").to_string();
+    let workspace: serde_json::Value = fs::read_to_string(dir.join("angular.json")).ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default();
+    let projects = workspace.get("projects").and_then(|p| p.as_object());
+    let name = workspace.get("defaultProject").and_then(|v| v.as_str()).map(String::from)
+        .or_else(|| projects.and_then(|p| p.iter()
+            .find(|(_, v)| v.get("projectType").and_then(|t| t.as_str()) == Some("application"))
+            .map(|(k, _)| k.clone())))
+        .unwrap_or(fallback);
+
+    let build = &workspace["projects"][&name]["architect"]["build"];
+    let application = build["builder"].as_str().is_some_and(|b| b.ends_with(":application"));
+    let output = match &build["options"]["outputPath"] {
+        serde_json::Value::String(p) => p.clone(),
+        serde_json::Value::Object(o) => o.get("base").and_then(|b| b.as_str()).map(String::from)
+            .unwrap_or_else(|| format!("dist/{}", name)),
+        _ => format!("dist/{}", name),
+    };
+    (name, output.trim_end_matches('/').to_string(), application)
+}
+
+pub fn scan_angular(dir: &Path) -> Result<Option<SourceInfo>> {
+    let pkg = match read_package_json(dir) {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+
+    if !has_dep(&pkg, "@angular/core") {
+        return Ok(None);
+    }
+
+    let (pm, install_cmd) = detect_package_manager(dir);
+    let node_ver = detect_node_version(dir, &pkg);
+    let base = format!("node:{}-alpine", node_ver);
+    let run_prefix = if pm == "bun" { "bun" } else { &pm };
+    let build_cmd = Some(format!("{} run build", run_prefix));
+    let (_, output, application) = angular_project(dir, &pkg);
+
+    // @angular/ssr with the application builder emits <output>/server/server.mjs
+    if has_dep(&pkg, "@angular/ssr") && application {
+        let port = detect_port_from_scripts(&pkg).unwrap_or(4000);
+        let server = format!("{}/server/server.mjs", output);
+        let stages = vec![
+            DockerStage {
+                name: Some("builder".into()),
+                base_image: base.clone(),
+                workdir: "/app".into(),
+                instructions: vec![
+                    lockfile_copy(&pm).into(),
+                    format!("RUN {}", install_cmd),
+                    "COPY . .".into(),
+                    format!("RUN {} run build", run_prefix),
+                ],
+                expose: None,
+                cmd: None,
+            },
+            DockerStage {
+                name: None,
+                base_image: base,
+                workdir: "/app".into(),
+                instructions: vec![
+                    "ENV NODE_ENV=production".into(),
+                    format!("COPY --from=builder /app/{0} ./{0}", output),
+                ],
+                expose: Some(port),
+                cmd: Some(vec!["node".into(), server.clone()]),
+            },
+        ];
+        return Ok(Some(SourceInfo {
+            family: "Angular".into(),
+            framework: Framework::Angular,
+            version: Some(node_ver),
+            port,
+            env_vars: vec![("NODE_ENV".into(), "production".into()), ("PORT".into(), port.to_string())],
+            build_args: vec![],
+            install_cmd,
+            build_cmd,
+            start_cmd: format!("node {}", server),
+            health_path: Some("/".into()),
+            binary_name: None,
+            entry_point: None,
+            package_manager: Some(pm),
+            has_lockfile: true,
+            dockerfile_stages: stages,
+            dockerignore_entries: node_dockerignore(),
+            notes: vec![],
+        }));
+    }
+
+    let mut notes = vec![];
+    if has_dep(&pkg, "@nguniversal/express-engine") || has_dep(&pkg, "@angular/ssr") {
+        notes.push("SSR without the application builder isn't detected; serving the browser build".into());
+    }
+    let browser_dir = if application { format!("{}/browser", output) } else { output };
+
+    Ok(Some(SourceInfo {
+        family: "Angular".into(),
+        framework: Framework::Angular,
+        version: Some(node_ver),
+        port: 80,
+        env_vars: vec![],
+        build_args: vec![],
+        install_cmd: install_cmd.clone(),
+        build_cmd,
+        start_cmd: "nginx -g 'daemon off;'".into(),
+        health_path: Some("/".into()),
+        binary_name: None,
+        entry_point: None,
+        dockerfile_stages: static_stages(base, &pm, &install_cmd, run_prefix, &browser_dir),
+        package_manager: Some(pm),
+        has_lockfile: true,
+        dockerignore_entries: node_dockerignore(),
+        notes,
+    }))
+}
+
 // ─── Vite SPA ─────────────────────────────────────────────────────
 
 pub fn scan_vite_spa(dir: &Path) -> Result<Option<SourceInfo>> {
@@ -428,6 +785,7 @@ pub fn scan_vite_spa(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd,
         build_cmd: Some(format!("{} run build", run_prefix)),
         start_cmd: "nginx -g 'daemon off;'".into(),
+        health_path: Some("/".into()),
         binary_name: None,
         entry_point: None,
         package_manager: Some(pm),
@@ -502,6 +860,7 @@ pub fn scan_generic(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd,
         build_cmd: if has_build { Some(format!("{} run build", run_prefix)) } else { None },
         start_cmd,
+        health_path: None,
         binary_name: None,
         entry_point: None,
         package_manager: Some(pm),
@@ -511,3 +870,47 @@ pub fn scan_generic(dir: &Path) -> Result<Option<SourceInfo>> {
         notes: vec![],
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::project;
+
+    fn runner(info: &SourceInfo) -> &DockerStage {
+        info.dockerfile_stages.last().unwrap()
+    }
+
+    #[test]
+    fn test_sveltekit_astro_angular() {
+        let kit = project(&[("package.json", r#"{"devDependencies": {"@sveltejs/kit": "^2", "@sveltejs/adapter-node": "^5", "vite": "^5"}}"#)]);
+        let info = crate::scanner::scan(kit.path()).unwrap().unwrap();
+        assert_eq!((info.framework.clone(), info.port, info.start_cmd.as_str()), (Framework::SvelteKit, 3000, "node build"));
+        assert!(runner(&info).instructions.contains(&"COPY --from=builder /app/build ./build".to_string()));
+
+        let kit = project(&[("package.json", r#"{"devDependencies": {"@sveltejs/kit": "^2", "@sveltejs/adapter-static": "^3"}}"#)]);
+        let info = scan_sveltekit(kit.path()).unwrap().unwrap();
+        assert_eq!((info.port, runner(&info).base_image.as_str()), (80, "nginx:alpine"));
+
+        let astro = project(&[
+            ("package.json", r#"{"dependencies": {"astro": "^4", "@astrojs/node": "^8"}}"#),
+            ("astro.config.mjs", "export default { output: 'server', adapter: node({ mode: 'standalone' }) }"),
+        ]);
+        let info = scan_astro(astro.path()).unwrap().unwrap();
+        assert_eq!((info.port, info.start_cmd.as_str()), (4321, "node ./dist/server/entry.mjs"));
+        assert!(info.env_vars.contains(&("HOST".into(), "0.0.0.0".into())));
+
+        let astro = project(&[("package.json", r#"{"dependencies": {"astro": "^4"}}"#)]);
+        let info = scan_astro(astro.path()).unwrap().unwrap();
+        assert_eq!(runner(&info).instructions, vec!["COPY --from=builder /app/dist ."]);
+
+        let angular_json = r#"{"projects": {"shop": {"projectType": "application", "architect": {"build": {
+            "builder": "@angular-devkit/build-angular:application", "options": {"outputPath": "dist/shop"}}}}}}"#;
+        let ng = project(&[("package.json", r#"{"dependencies": {"@angular/core": "^18"}}"#), ("angular.json", angular_json)]);
+        let info = scan_angular(ng.path()).unwrap().unwrap();
+        assert_eq!(runner(&info).instructions, vec!["COPY --from=builder /app/dist/shop/browser ."]);
+
+        let ng = project(&[("package.json", r#"{"dependencies": {"@angular/core": "^18", "@angular/ssr": "^18"}}"#), ("angular.json", angular_json)]);
+        let info = scan_angular(ng.path()).unwrap().unwrap();
+        assert_eq!((info.port, info.start_cmd.as_str()), (4000, "node dist/shop/server/server.mjs"));
+    }
+}
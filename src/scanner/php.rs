@@ -0,0 +1,275 @@
+use super::{DockerStage, Framework, SourceInfo};
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+/// nginx in front of php-fpm, in one container
+const PORT: u16 = 8080;
+const APP_DIR: &str = "/var/www/html";
+
+fn read_composer(dir: &Path) -> Option<serde_json::Value> {
+    fs::read_to_string(dir.join("composer.json")).ok().and_then(|c| serde_json::from_str(&c).ok())
+}
+
+fn has_composer_dep(composer: &serde_json::Value, name: &str) -> bool {
+    ["require", "require-dev"].iter().any(|section| composer.get(section).and_then(|s| s.get(name)).is_some())
+}
+
+/// PHP version from composer.json `require.php`: "^8.2", ">=8.1", "8.3.*" → major.minor
+fn detect_php_version(composer: Option<&serde_json::Value>) -> String {
+    let constraint = composer
+        .and_then(|c| c.get("require"))
+        .and_then(|r| r.get("php"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let digits: String = constraint.chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let parts: Vec<&str> = digits.split('.').filter(|p| !p.is_empty()).collect();
+    match parts.as_slice() {
+        [major, minor, ..] => format!("{}.{}", major, minor),
+        [major] => format!("{}.0", major),
+        [] => "8.3".to_string(),
+    }
+}
+
+/// PDO driver for the database in .env.example, with the Alpine package it builds against
+fn database_extension(dir: &Path) -> Option<(&'static str, Option<&'static str>)> {
+    let env = fs::read_to_string(dir.join(".env.example")).ok()?;
+    let connection = env.lines().find_map(|l| l.trim().strip_prefix("DB_CONNECTION="))?.trim();
+    match connection {
+        "pgsql" => Some(("pdo_pgsql", Some("postgresql-dev"))),
+        "mysql" | "mariadb" => Some(("pdo_mysql", None)),
+        _ => None,
+    }
+}
+
+/// The site config of nginx, passing PHP to php-fpm on :9000
+fn nginx_conf_instruction(root: &str) -> String {
+    let lines = [
+        "server {".to_string(),
+        format!("    listen {};", PORT),
+        format!("    root {};", root),
+        "    index index.php index.html;".to_string(),
+        "    location / { try_files $uri $uri/ /index.php?$query_string; }".to_string(),
+        "    location ~ \\.php$ {".to_string(),
+        "        fastcgi_pass 127.0.0.1:9000;".to_string(),
+        "        fastcgi_param SCRIPT_FILENAME $document_root$fastcgi_script_name;".to_string(),
+        "        include fastcgi_params;".to_string(),
+        "    }".to_string(),
+        "}".to_string(),
+    ];
+    let quoted: Vec<String> = lines.iter().map(|l| format!("'{}'", l)).collect();
+    format!("RUN printf '%s\\n' {} > /etc/nginx/http.d/default.conf", quoted.join(" "))
+}
+
+/// composer install without dev dependencies, in its own stage so Composer stays out of the image
+fn vendor_stage() -> DockerStage {
+    DockerStage {
+        name: Some("vendor".into()),
+        base_image: "composer:2".into(),
+        workdir: "/app".into(),
+        instructions: vec![
+            "COPY composer.json composer.lock* ./".into(),
+            "RUN composer install --no-dev --no-scripts --no-autoloader --prefer-dist --no-interaction --ignore-platform-reqs".into(),
+            "COPY . .".into(),
+            "RUN composer dump-autoload --optimize --no-dev".into(),
+        ],
+        expose: None,
+        cmd: None,
+    }
+}
+
+/// Front-end build (Vite) when package.json has a build script
+fn assets_stage(dir: &Path) -> Option<DockerStage> {
+    let pkg: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("package.json")).ok()?).ok()?;
+    pkg.get("scripts").and_then(|s| s.get("build"))?;
+    let install = if dir.join("package-lock.json").exists() { "npm ci" } else { "npm install" };
+    Some(DockerStage {
+        name: Some("assets".into()),
+        base_image: "node:22-alpine".into(),
+        workdir: "/app".into(),
+        instructions: vec![
+            "COPY package.json package-lock.json* ./".into(),
+            format!("RUN {}", install),
+            "COPY . .".into(),
+            "RUN npm run build".into(),
+        ],
+        expose: None,
+        cmd: None,
+    })
+}
+
+/// php-fpm with nginx, the code and vendor/ from the earlier stages
+fn runtime_stage(dir: &Path, php_ver: &str, root: &str, has_vendor: bool, assets: bool, extra: Vec<String>, start: &str) -> DockerStage {
+    let mut packages = vec!["nginx"];
+    let mut extensions = vec!["opcache"];
+    if let Some((ext, package)) = database_extension(dir) {
+        extensions.push(ext);
+        packages.extend(package);
+    }
+    let mut instructions = vec![
+        format!("RUN apk add --no-cache {} && docker-php-ext-install {}", packages.join(" "), extensions.join(" ")),
+        nginx_conf_instruction(root),
+        if has_vendor { "COPY --from=vendor /app .".into() } else { "COPY . .".into() },
+    ];
+    if assets {
+        instructions.push("COPY --from=assets /app/public/build ./public/build".into());
+    }
+    instructions.extend(extra);
+    DockerStage {
+        name: None,
+        base_image: format!("php:{}-fpm-alpine", php_ver),
+        workdir: APP_DIR.into(),
+        instructions,
+        expose: Some(PORT),
+        cmd: Some(vec!["sh".into(), "-c".into(), start.into()]),
+    }
+}
+
+fn php_dockerignore() -> Vec<String> {
+    vec![
+        "vendor".into(),
+        "node_modules".into(),
+        ".git".into(),
+        ".env*".into(),
+        "*.md".into(),
+        ".vscode".into(),
+        ".idea".into(),
+        ".phpunit.result.cache".into(),
+    ]
+}
+
+// ─── Laravel ──────────────────────────────────────────────────────
+
+pub fn scan_laravel(dir: &Path) -> Result<Option<SourceInfo>> {
+    if !dir.join("artisan").exists() {
+        return Ok(None);
+    }
+    let Some(composer) = read_composer(dir) else { return Ok(None) };
+    if !has_composer_dep(&composer, "laravel/framework") {
+        return Ok(None);
+    }
+
+    let php_ver = detect_php_version(Some(&composer));
+    let assets = assets_stage(dir);
+    let start_cmd = "php artisan optimize && php-fpm -D && nginx -g 'daemon off;'";
+
+    let mut stages = vec![vendor_stage()];
+    let has_assets = assets.is_some();
+    stages.extend(assets);
+    stages.push(runtime_stage(
+        dir, &php_ver, &format!("{}/public", APP_DIR), true, has_assets,
+        vec!["RUN chown -R www-data:www-data storage bootstrap/cache".into()],
+        start_cmd,
+    ));
+
+    // Laravel 11 registers /up in bootstrap/app.php
+    let health_path = fs::read_to_string(dir.join("bootstrap/app.php")).ok()
+        .filter(|c| c.contains("health:"))
+        .map(|_| "/up".to_string());
+
+    Ok(Some(SourceInfo {
+        family: "PHP".into(),
+        framework: Framework::Laravel,
+        version: Some(php_ver),
+        port: PORT,
+        env_vars: vec![
+            ("APP_ENV".into(), "production".into()),
+            ("APP_DEBUG".into(), "false".into()),
+            ("LOG_CHANNEL".into(), "stderr".into()),
+        ],
+        build_args: vec![],
+        install_cmd: "composer install --no-dev --optimize-autoloader".into(),
+        build_cmd: has_assets.then(|| "npm run build".into()),
+        start_cmd: start_cmd.into(),
+        health_path,
+        binary_name: None,
+        entry_point: Some("public/index.php".into()),
+        package_manager: Some("composer".into()),
+        has_lockfile: dir.join("composer.lock").exists(),
+        dockerfile_stages: stages,
+        dockerignore_entries: php_dockerignore(),
+        notes: vec![
+            "Set APP_KEY (from `php artisan key:generate --show`) with `ops secrets set`".into(),
+            "Run migrations with `ops run <service> -- php artisan migrate --force`".into(),
+        ],
+    }))
+}
+
+// ─── Generic PHP ──────────────────────────────────────────────────
+
+pub fn scan_generic(dir: &Path) -> Result<Option<SourceInfo>> {
+    let composer = read_composer(dir);
+    let has_index = dir.join("index.php").exists() || dir.join("public/index.php").exists();
+    if composer.is_none() && !has_index {
+        return Ok(None);
+    }
+
+    let php_ver = detect_php_version(composer.as_ref());
+    let (root, entry) = if dir.join("public/index.php").exists() {
+        (format!("{}/public", APP_DIR), "public/index.php")
+    } else {
+        (APP_DIR.to_string(), "index.php")
+    };
+    let start_cmd = "php-fpm -D && nginx -g 'daemon off;'";
+
+    let mut stages = Vec::new();
+    if composer.is_some() {
+        stages.push(vendor_stage());
+    }
+    stages.push(runtime_stage(dir, &php_ver, &root, composer.is_some(), false, vec![], start_cmd));
+
+    Ok(Some(SourceInfo {
+        family: "PHP".into(),
+        framework: Framework::GenericPhp,
+        version: Some(php_ver),
+        port: PORT,
+        env_vars: vec![],
+        build_args: vec![],
+        install_cmd: if composer.is_some() { "composer install --no-dev --optimize-autoloader".into() } else { String::new() },
+        build_cmd: None,
+        start_cmd: start_cmd.into(),
+        health_path: None,
+        binary_name: None,
+        entry_point: Some(entry.into()),
+        package_manager: composer.is_some().then(|| "composer".into()),
+        has_lockfile: dir.join("composer.lock").exists(),
+        dockerfile_stages: stages,
+        dockerignore_entries: php_dockerignore(),
+        notes: vec![],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::project;
+
+    #[test]
+    fn test_laravel_and_plain_php() {
+        let app = project(&[
+            ("composer.json", r#"{"require": {"php": "^8.2", "laravel/framework": "^11.0"}}"#),
+            ("artisan", "#!/usr/bin/env php"),
+            ("package.json", r#"{"scripts": {"build": "vite build"}}"#),
+            ("bootstrap/app.php", "->withRouting(web: __DIR__.'/../routes/web.php', health: '/up')"),
+            (".env.example", "APP_NAME=Shop\nDB_CONNECTION=pgsql\n"),
+        ]);
+        let info = crate::scanner::scan(app.path()).unwrap().unwrap();
+        assert_eq!((info.framework.clone(), info.version.as_deref(), info.port), (Framework::Laravel, Some("8.2"), 8080));
+        assert_eq!(info.health_path.as_deref(), Some("/up"));
+        let names: Vec<Option<&str>> = info.dockerfile_stages.iter().map(|s| s.name.as_deref()).collect();
+        assert_eq!(names, vec![Some("vendor"), Some("assets"), None]);
+        let runtime = info.dockerfile_stages.last().unwrap();
+        assert_eq!(runtime.base_image, "php:8.2-fpm-alpine");
+        assert_eq!(runtime.instructions[0], "RUN apk add --no-cache nginx postgresql-dev && docker-php-ext-install opcache pdo_pgsql");
+        assert!(runtime.instructions[1].contains("'    root /var/www/html/public;'"));
+
+        let site = project(&[("index.php", "<?php echo 'hi';")]);
+        let info = crate::scanner::scan(site.path()).unwrap().unwrap();
+        assert_eq!((info.framework.clone(), info.version.as_deref()), (Framework::GenericPhp, Some("8.3")));
+        assert_eq!(info.dockerfile_stages.len(), 1);
+        assert!(info.dockerfile_stages[0].instructions.contains(&"COPY . .".to_string()));
+    }
+}
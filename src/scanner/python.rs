@@ -156,6 +156,7 @@ pub fn scan_django(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd,
         build_cmd: Some("python manage.py collectstatic --noinput".into()),
         start_cmd: format!("gunicorn {} --bind 0.0.0.0:8000", wsgi_module),
+        health_path: None,
         binary_name: None,
         entry_point: Some(wsgi_module),
         package_manager: Some(pm),
@@ -250,6 +251,7 @@ pub fn scan_flask(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd,
         build_cmd: None,
         start_cmd: format!("gunicorn {} --bind 0.0.0.0:5000", entry),
+        health_path: None,
         binary_name: None,
         entry_point: Some(entry),
         package_manager: Some(pm),
@@ -328,6 +330,7 @@ pub fn scan_fastapi(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd,
         build_cmd: None,
         start_cmd: format!("uvicorn {} --host 0.0.0.0 --port 8000", entry),
+        health_path: Some("/docs".into()),
         binary_name: None,
         entry_point: Some(entry),
         package_manager: Some(pm),
@@ -401,6 +404,7 @@ pub fn scan_generic(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd,
         build_cmd: None,
         start_cmd,
+        health_path: None,
         binary_name: None,
         entry_point: None,
         package_manager: Some(pm),
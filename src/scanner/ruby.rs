@@ -0,0 +1,234 @@
+use super::{DockerStage, Framework, SourceInfo};
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+const BUNDLE_ENV: &str = "ENV BUNDLE_DEPLOYMENT=1 BUNDLE_WITHOUT=development:test BUNDLE_PATH=/usr/local/bundle";
+
+fn read_gemfile(dir: &Path) -> Option<String> {
+    fs::read_to_string(dir.join("Gemfile")).ok()
+}
+
+/// `gem "name"` or `gem 'name', ...` in the Gemfile
+fn has_gem(gemfile: &str, name: &str) -> bool {
+    gemfile.lines().any(|l| {
+        let l = l.trim();
+        l.strip_prefix("gem ")
+            .map(|rest| rest.trim_start().trim_start_matches(['"', '\'']))
+            .is_some_and(|rest| rest.strip_prefix(name).is_some_and(|after| after.starts_with(['"', '\''])))
+    })
+}
+
+/// Ruby version from .ruby-version ("3.3.0", "ruby-3.2.2") or the Gemfile's `ruby "3.3.0"` → major.minor
+fn detect_ruby_version(dir: &Path, gemfile: &str) -> String {
+    let declared = fs::read_to_string(dir.join(".ruby-version")).ok()
+        .map(|v| v.trim().trim_start_matches("ruby-").to_string())
+        .filter(|v| !v.is_empty())
+        .or_else(|| gemfile.lines()
+            .find_map(|l| l.trim().strip_prefix("ruby "))
+            .map(|v| v.trim().trim_matches(['"', '\'']).to_string()));
+    let parts: Vec<String> = declared.unwrap_or_default().split('.').map(String::from).collect();
+    match parts.as_slice() {
+        [major, minor, ..] if major.parse::<u32>().is_ok() && minor.parse::<u32>().is_ok() => format!("{}.{}", major, minor),
+        _ => "3.3".to_string(),
+    }
+}
+
+/// Debian packages to build native gems, and the ones their runtime needs
+fn native_packages(gemfile: &str) -> (Vec<&'static str>, Vec<&'static str>) {
+    let mut build = vec!["build-essential", "git", "libyaml-dev"];
+    let mut runtime = vec![];
+    if has_gem(gemfile, "pg") {
+        build.push("libpq-dev");
+        runtime.push("libpq5");
+    }
+    if has_gem(gemfile, "mysql2") {
+        build.push("default-libmysqlclient-dev");
+        runtime.push("default-mysql-client");
+    }
+    (build, runtime)
+}
+
+fn apt_install(packages: &[&str]) -> String {
+    format!("RUN apt-get update && apt-get install -y --no-install-recommends {} && rm -rf /var/lib/apt/lists/*", packages.join(" "))
+}
+
+/// bundle install in a builder with compilers, then only the installed gems and the app in a slim runtime
+fn stages(ruby_ver: &str, gemfile: &str, has_lock: bool, build_steps: Vec<String>, expose: u16, cmd: Vec<String>) -> Vec<DockerStage> {
+    let base = format!("ruby:{}-slim", ruby_ver);
+    let (build_packages, runtime_packages) = native_packages(gemfile);
+    let mut builder = vec![
+        apt_install(&build_packages),
+        BUNDLE_ENV.into(),
+        format!("COPY Gemfile Gemfile.lock{} ./", if has_lock { "" } else { "*" }),
+        "RUN bundle install && rm -rf ~/.bundle \"${BUNDLE_PATH}\"/ruby/*/cache".into(),
+        "COPY . .".into(),
+    ];
+    builder.extend(build_steps);
+
+    let mut runtime = vec![];
+    if !runtime_packages.is_empty() {
+        runtime.push(apt_install(&runtime_packages));
+    }
+    runtime.extend([
+        BUNDLE_ENV.into(),
+        "COPY --from=builder /usr/local/bundle /usr/local/bundle".into(),
+        "COPY --from=builder /app /app".into(),
+    ]);
+
+    vec![
+        DockerStage { name: Some("builder".into()), base_image: base.clone(), workdir: "/app".into(), instructions: builder, expose: None, cmd: None },
+        DockerStage { name: None, base_image: base, workdir: "/app".into(), instructions: runtime, expose: Some(expose), cmd: Some(cmd) },
+    ]
+}
+
+fn ruby_dockerignore() -> Vec<String> {
+    vec![
+        ".bundle".into(),
+        "vendor/bundle".into(),
+        "log/*".into(),
+        "tmp/*".into(),
+        "storage/*".into(),
+        "node_modules".into(),
+        "public/assets".into(),
+        ".git".into(),
+        ".env*".into(),
+        "config/master.key".into(),
+        "*.md".into(),
+        ".vscode".into(),
+        ".idea".into(),
+    ]
+}
+
+// ─── Rails ────────────────────────────────────────────────────────
+
+pub fn scan_rails(dir: &Path) -> Result<Option<SourceInfo>> {
+    let Some(gemfile) = read_gemfile(dir) else { return Ok(None) };
+    if !has_gem(&gemfile, "rails") || !dir.join("config/application.rb").exists() {
+        return Ok(None);
+    }
+
+    let ruby_ver = detect_ruby_version(dir, &gemfile);
+    let has_lock = dir.join("Gemfile.lock").exists();
+    let application = fs::read_to_string(dir.join("config/application.rb")).unwrap_or_default();
+    let api_only = application.contains("api_only = true");
+
+    // API-only apps have no asset pipeline; the dummy key lets precompile run without credentials
+    let precompile = "RUN SECRET_KEY_BASE_DUMMY=1 ./bin/rails assets:precompile";
+    let build_steps = if api_only { vec![] } else { vec![precompile.to_string()] };
+    let cmd: Vec<String> = ["./bin/rails", "server", "-b", "0.0.0.0", "-p", "3000"].iter().map(|s| s.to_string()).collect();
+
+    // Rails 7.1+ routes /up to Rails::HealthController
+    let health_path = fs::read_to_string(dir.join("config/routes.rb")).ok()
+        .filter(|r| r.contains("rails/health"))
+        .map(|_| "/up".to_string());
+
+    let mut notes = vec!["Set SECRET_KEY_BASE (from `bin/rails secret`) or RAILS_MASTER_KEY with `ops secrets set`".to_string()];
+    if !has_lock {
+        notes.push("Commit Gemfile.lock so the image installs the gem versions you tested".into());
+    }
+    notes.push("Run migrations with `ops run <service> -- ./bin/rails db:migrate`".into());
+
+    Ok(Some(SourceInfo {
+        family: "Ruby".into(),
+        framework: Framework::Rails,
+        version: Some(ruby_ver.clone()),
+        port: 3000,
+        env_vars: vec![
+            ("RAILS_ENV".into(), "production".into()),
+            ("RAILS_LOG_TO_STDOUT".into(), "1".into()),
+            ("RAILS_SERVE_STATIC_FILES".into(), "1".into()),
+        ],
+        build_args: vec![],
+        install_cmd: "bundle install".into(),
+        build_cmd: (!api_only).then(|| "bin/rails assets:precompile".into()),
+        start_cmd: cmd.join(" "),
+        health_path,
+        binary_name: None,
+        entry_point: Some("config.ru".into()),
+        package_manager: Some("bundler".into()),
+        has_lockfile: has_lock,
+        dockerfile_stages: stages(&ruby_ver, &gemfile, has_lock, build_steps, 3000, cmd),
+        dockerignore_entries: ruby_dockerignore(),
+        notes,
+    }))
+}
+
+// ─── Generic Ruby ─────────────────────────────────────────────────
+
+pub fn scan_generic(dir: &Path) -> Result<Option<SourceInfo>> {
+    let Some(gemfile) = read_gemfile(dir) else { return Ok(None) };
+
+    let ruby_ver = detect_ruby_version(dir, &gemfile);
+    let has_lock = dir.join("Gemfile.lock").exists();
+
+    // Rack apps (Sinatra, Roda, Hanami) have a config.ru
+    let port = 9292;
+    let (cmd, entry): (Vec<&str>, &str) = if dir.join("config.ru").exists() {
+        (vec!["bundle", "exec", "rackup", "-o", "0.0.0.0", "-p", "9292"], "config.ru")
+    } else if dir.join("app.rb").exists() {
+        (vec!["bundle", "exec", "ruby", "app.rb"], "app.rb")
+    } else {
+        (vec!["bundle", "exec", "ruby", "main.rb"], "main.rb")
+    };
+    let cmd: Vec<String> = cmd.into_iter().map(String::from).collect();
+
+    let mut notes = vec![];
+    if entry != "config.ru" {
+        notes.push(format!("Make {} listen on 0.0.0.0:{}", entry, port));
+    }
+
+    Ok(Some(SourceInfo {
+        family: "Ruby".into(),
+        framework: Framework::GenericRuby,
+        version: Some(ruby_ver.clone()),
+        port,
+        env_vars: vec![("RACK_ENV".into(), "production".into())],
+        build_args: vec![],
+        install_cmd: "bundle install".into(),
+        build_cmd: None,
+        start_cmd: cmd.join(" "),
+        health_path: None,
+        binary_name: None,
+        entry_point: Some(entry.into()),
+        package_manager: Some("bundler".into()),
+        has_lockfile: has_lock,
+        dockerfile_stages: stages(&ruby_ver, &gemfile, has_lock, vec![], port, cmd),
+        dockerignore_entries: ruby_dockerignore(),
+        notes,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::project;
+
+    #[test]
+    fn test_rails_and_rack() {
+        let gemfile = "source \"https://rubygems.org\"\nruby \"3.2.2\"\ngem \"rails\", \"~> 7.1\"\ngem 'pg', '~> 1.1'\ngem \"rails-i18n\"\n";
+        assert!(has_gem(gemfile, "rails") && has_gem(gemfile, "pg"));
+        assert!(!has_gem(gemfile, "mysql2") && !has_gem(gemfile, "rail"));
+
+        let app = project(&[
+            ("Gemfile", gemfile),
+            ("Gemfile.lock", ""),
+            ("config/application.rb", "module Shop\n  class Application < Rails::Application\n  end\nend\n"),
+            ("config/routes.rb", "get \"up\" => \"rails/health#show\", as: :rails_health_check\n"),
+        ]);
+        let info = crate::scanner::scan(app.path()).unwrap().unwrap();
+        assert_eq!((info.framework.clone(), info.version.as_deref(), info.port), (Framework::Rails, Some("3.2"), 3000));
+        assert_eq!(info.health_path.as_deref(), Some("/up"));
+        let (builder, runtime) = (&info.dockerfile_stages[0], &info.dockerfile_stages[1]);
+        assert_eq!(builder.instructions.last().unwrap(), "RUN SECRET_KEY_BASE_DUMMY=1 ./bin/rails assets:precompile");
+        assert!(builder.instructions[0].contains("libpq-dev"));
+        assert!(runtime.instructions[0].contains("libpq5"));
+        assert_eq!(runtime.base_image, "ruby:3.2-slim");
+
+        let rack = project(&[("Gemfile", "gem 'sinatra'\n"), ("config.ru", "run App"), (".ruby-version", "ruby-3.3.4\n")]);
+        let info = crate::scanner::scan(rack.path()).unwrap().unwrap();
+        assert_eq!((info.framework.clone(), info.version.as_deref()), (Framework::GenericRuby, Some("3.3")));
+        assert_eq!(info.start_cmd, "bundle exec rackup -o 0.0.0.0 -p 9292");
+        assert_eq!(info.dockerfile_stages[1].instructions[0], BUNDLE_ENV);
+    }
+}
@@ -53,6 +53,7 @@ pub fn scan(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd: "cargo build --release".into(),
         build_cmd: Some("cargo build --release".into()),
         start_cmd: format!("./{}", binary_name),
+        health_path: None,
         binary_name: Some(binary_name),
         entry_point: None,
         package_manager: Some("cargo".into()),
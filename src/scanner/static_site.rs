@@ -13,6 +13,8 @@ pub fn scan(dir: &Path) -> Result<Option<SourceInfo>> {
         || dir.join("go.mod").exists()
         || dir.join("requirements.txt").exists()
         || dir.join("pyproject.toml").exists()
+        || dir.join("composer.json").exists()
+        || dir.join("Gemfile").exists()
     {
         return Ok(None);
     }
@@ -40,6 +42,7 @@ pub fn scan(dir: &Path) -> Result<Option<SourceInfo>> {
         install_cmd: String::new(),
         build_cmd: None,
         start_cmd: "nginx -g 'daemon off;'".into(),
+        health_path: Some("/".into()),
         binary_name: None,
         entry_point: None,
         package_manager: None,
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use serde::Serialize;
+use super::compose_command;
 use std::process::Command;
 
 #[derive(Serialize)]
@@ -26,7 +27,6 @@ pub fn deploy(compose_dir: &str) -> Result<ActionResult> {
 
 pub fn deploy_with_repo(deploy_path: &str, git_repo: Option<&str>, branch: Option<&str>) -> Result<ActionResult> {
     let git_dir = std::path::Path::new(deploy_path).join(".git");
-    let branch = branch.unwrap_or("main");
 
     // Check if .git exists
     if !git_dir.exists() {
@@ -38,7 +38,7 @@ pub fn deploy_with_repo(deploy_path: &str, git_repo: Option<&str>, branch: Optio
             }
 
             let clone_output = Command::new("git")
-                .args(["clone", "--branch", branch, repo, deploy_path])
+                .args(["clone", "--branch", branch.unwrap_or("main"), repo, deploy_path])
                 .output()?;
 
             if !clone_output.status.success() {
@@ -55,6 +55,27 @@ pub fn deploy_with_repo(deploy_path: &str, git_repo: Option<&str>, branch: Optio
             });
         }
     } else {
+        // A requested branch is checked out first; `git pull` alone deploys whatever is checked out
+        if let Some(branch) = branch {
+            let head = Command::new("git")
+                .args(["rev-parse", "--abbrev-ref", "HEAD"])
+                .current_dir(deploy_path)
+                .output()?;
+            if String::from_utf8_lossy(&head.stdout).trim() != branch {
+                let checkout = Command::new("git")
+                    .args(["checkout", branch])
+                    .current_dir(deploy_path)
+                    .output()?;
+                if !checkout.status.success() {
+                    let stderr = String::from_utf8_lossy(&checkout.stderr);
+                    return Ok(ActionResult {
+                        success: false,
+                        message: format!("git checkout {} failed: {}", branch, stderr),
+                    });
+                }
+            }
+        }
+
         // git pull
         let git_output = Command::new("git")
             .args(["pull"])
@@ -71,9 +92,8 @@ pub fn deploy_with_repo(deploy_path: &str, git_repo: Option<&str>, branch: Optio
     }
 
     // docker compose up -d --build
-    let output = Command::new("docker")
-        .args(["compose", "up", "-d", "--build"])
-        .current_dir(deploy_path)
+    let output = compose_command(deploy_path)
+        .args(["up", "-d", "--build"])
         .output()?;
 
     if !output.status.success() {
@@ -91,12 +111,8 @@ pub fn deploy_with_repo(deploy_path: &str, git_repo: Option<&str>, branch: Optio
 }
 
 fn run_compose_command(compose_dir: &str, args: &[&str], action: &str) -> Result<ActionResult> {
-    let mut cmd_args = vec!["compose"];
-    cmd_args.extend_from_slice(args);
-
-    let output = Command::new("docker")
-        .args(&cmd_args)
-        .current_dir(compose_dir)
+    let output = compose_command(compose_dir)
+        .args(args)
         .output()?;
 
     if !output.status.success() {
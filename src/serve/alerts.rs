@@ -0,0 +1,107 @@
+//! `[[alerts]]` in serve.toml: node CPU, memory, disk and load thresholds. A rule that holds for
+//! its `for` duration is reported to the backend as a node event once, and again only after the
+//! metric has dropped back below the threshold.
+
+use super::config::AlertRule;
+use super::metrics::SystemMetrics;
+use crate::api;
+use crate::node_state::NodeState;
+use crate::types::NodeEvent;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+pub const ALERT_EVENT: &str = "node-alert";
+
+/// cpu, memory and disk in percent, load as the 1-minute average
+pub fn metric_value(m: &SystemMetrics, metric: &str) -> Option<f64> {
+    let percent = |used: f64, total: f64| if total > 0.0 { used * 100.0 / total } else { 0.0 };
+    match metric {
+        "cpu" => Some(m.cpu_percent),
+        "memory" => Some(percent(m.memory_used_mb as f64, m.memory_total_mb as f64)),
+        "disk" => Some(percent(m.disk_used_gb, m.disk_total_gb)),
+        "load" => Some(m.load_average[0]),
+        _ => None,
+    }
+}
+
+fn key(rule: &AlertRule) -> String {
+    format!("{}>{}", rule.metric, rule.above)
+}
+
+/// Since when each rule has been above its threshold, and which ones were reported. Keyed by the
+/// rule itself, so a reload keeps the state of rules it didn't change.
+#[derive(Default)]
+pub struct Tracker {
+    above_since: HashMap<String, Instant>,
+    reported: HashSet<String>,
+}
+
+impl Tracker {
+    /// The reasons of the rules that start firing with these metrics
+    pub fn observe(&mut self, rules: &[AlertRule], metrics: &SystemMetrics, now: Instant) -> Vec<String> {
+        let mut firing = Vec::new();
+        for rule in rules {
+            let Some(value) = metric_value(metrics, &rule.metric) else { continue };
+            let key = key(rule);
+            if value <= rule.above {
+                self.above_since.remove(&key);
+                self.reported.remove(&key);
+                continue;
+            }
+            let since = *self.above_since.entry(key.clone()).or_insert(now);
+            if now.duration_since(since) >= rule.duration() && self.reported.insert(key) {
+                let held = rule.for_.as_deref().map(|d| format!(" for {}", d)).unwrap_or_default();
+                firing.push(format!("{} {:.1} above {}{}", rule.metric, value, rule.above, held));
+            }
+        }
+        firing
+    }
+}
+
+/// Send a firing rule to the backend as an event of this node
+pub async fn report(serve_token: &str, reason: String) {
+    let Some(node_id) = NodeState::load().ok().flatten().and_then(|s| s.node_id) else {
+        eprintln!("alert: no node id in node state, not reporting");
+        return;
+    };
+    let event = NodeEvent {
+        kind: ALERT_EVENT.into(),
+        project: None,
+        app: None,
+        service: String::new(),
+        container: String::new(),
+        reason,
+        exit_code: 0,
+        restart_count: 0,
+        logs: String::new(),
+    };
+    if let Err(e) = api::report_node_event(serve_token, node_id, &event).await {
+        eprintln!("alert report error: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker() {
+        let rules = vec![AlertRule { metric: "disk".into(), above: 90.0, for_: Some("5m".into()) }];
+        let metrics = |disk_used_gb: f64| SystemMetrics {
+            cpu_percent: 0.0, memory_used_mb: 0, memory_total_mb: 0, disk_used_gb, disk_total_gb: 100.0,
+            uptime_seconds: 0, load_average: [0.0; 3],
+        };
+        let start = Instant::now();
+        let mut tracker = Tracker::default();
+        assert!(tracker.observe(&rules, &metrics(95.0), start).is_empty());
+        assert!(tracker.observe(&rules, &metrics(95.0), start + Duration::from_secs(200)).is_empty());
+        assert_eq!(tracker.observe(&rules, &metrics(95.0), start + Duration::from_secs(300)),
+            vec!["disk 95.0 above 90 for 5m"]);
+        // Once per episode
+        assert!(tracker.observe(&rules, &metrics(96.0), start + Duration::from_secs(400)).is_empty());
+        assert!(tracker.observe(&rules, &metrics(50.0), start + Duration::from_secs(500)).is_empty());
+        assert!(tracker.observe(&rules, &metrics(95.0), start + Duration::from_secs(600)).is_empty());
+        assert_eq!(tracker.observe(&rules, &metrics(95.0), start + Duration::from_secs(900)).len(), 1);
+    }
+}
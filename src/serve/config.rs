@@ -0,0 +1,314 @@
+//! `/etc/ops/serve.toml`: the daemon's settings, so the systemd unit only says `ops serve --config`.
+//! Command-line flags override the file. `systemctl reload ops-serve` (SIGHUP) re-reads it, except
+//! `port`, which needs a restart.
+
+use crate::node_state::{SERVE_CONFIG_FILE, SERVE_ENV_FILE};
+use crate::privilege::Privilege;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 8377;
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(60);
+pub const ALERT_METRICS: [&str; 4] = ["cpu", "memory", "disk", "load"];
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ServeConfig {
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub compose_dirs: Vec<String>,
+    /// File holding the bearer token: the bare token, or an `OPS_SERVE_TOKEN=` line like serve.env
+    pub token_file: Option<String>,
+    #[serde(default)]
+    pub public_metrics: bool,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub watch: Vec<WatchConfig>,
+    #[serde(default)]
+    pub alerts: Vec<AlertRule>,
+}
+
+/// Domains Caddy serves the daemon on over HTTPS; applied by `ops init` and `ops serve --install`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct TlsConfig {
+    #[serde(default)]
+    pub domains: Vec<String>,
+}
+
+/// `POST /webhooks/<name>` deploys `deploy_path` when signed with `secret`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookConfig {
+    pub name: String,
+    pub secret: Option<String>,
+    pub secret_file: Option<String>,
+    pub deploy_path: String,
+    /// Only pushes to this branch deploy
+    pub branch: Option<String>,
+}
+
+/// Poll the remote of the branch checked out in `deploy_path` and deploy when it moves
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WatchConfig {
+    pub deploy_path: String,
+    /// e.g. "2m"; default 1m
+    pub interval: Option<String>,
+}
+
+/// Report a node event when `metric` stays above `above` for `for`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    /// cpu | memory | disk (percent) | load (1-minute average)
+    pub metric: String,
+    pub above: f64,
+    /// e.g. "5m"; default: the first check above the threshold
+    #[serde(rename = "for")]
+    pub for_: Option<String>,
+}
+
+impl AlertRule {
+    pub fn duration(&self) -> Duration {
+        self.for_.as_deref().and_then(|d| humantime::parse_duration(d).ok()).unwrap_or_default()
+    }
+}
+
+impl WatchConfig {
+    pub fn interval(&self) -> Duration {
+        self.interval.as_deref().and_then(|d| humantime::parse_duration(d).ok()).unwrap_or(DEFAULT_WATCH_INTERVAL)
+    }
+}
+
+/// Flags given to `ops serve`, which win over the file
+#[derive(Debug, Default, Clone)]
+pub struct Overrides {
+    pub token: Option<String>,
+    pub port: Option<u16>,
+    pub compose_dir: Option<String>,
+    pub public_metrics: bool,
+}
+
+/// What the daemon runs with: the file and the flags combined and checked
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub token: String,
+    pub port: u16,
+    pub compose_dirs: Vec<String>,
+    pub public_metrics: bool,
+    /// With their secrets read
+    pub webhooks: Vec<WebhookConfig>,
+    pub watch: Vec<WatchConfig>,
+    pub alerts: Vec<AlertRule>,
+}
+
+/// The token in a token file: its first line, without an `OPS_SERVE_TOKEN=` prefix
+fn read_secret(path: &str) -> Result<String> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    let line = content.lines().map(str::trim).find(|l| !l.is_empty() && !l.starts_with('#')).unwrap_or("");
+    let secret = line.strip_prefix("OPS_SERVE_TOKEN=").unwrap_or(line).trim_matches('"');
+    if secret.is_empty() {
+        bail!("{} is empty", path);
+    }
+    Ok(secret.to_string())
+}
+
+fn check_duration(what: &str, value: &Option<String>) -> Result<()> {
+    if let Some(d) = value {
+        humantime::parse_duration(d).map_err(|e| anyhow!("Invalid {} '{}': {}", what, d, e))?;
+    }
+    Ok(())
+}
+
+impl ServeConfig {
+    /// `path`, or /etc/ops/serve.toml when it exists; no file means flags only
+    pub fn load(path: Option<&str>) -> Result<ServeConfig> {
+        let path = match path {
+            Some(p) => p,
+            None if Path::new(SERVE_CONFIG_FILE).exists() => SERVE_CONFIG_FILE,
+            None => return Ok(ServeConfig::default()),
+        };
+        let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path))
+    }
+
+    pub fn resolve(self, overrides: &Overrides) -> Result<Settings> {
+        let token = match (&overrides.token, &self.token_file) {
+            (Some(token), _) => token.clone(),
+            (None, Some(file)) => read_secret(file)?,
+            (None, None) => bail!("No serve token. Pass --token, set OPS_SERVE_TOKEN, or set token_file in {}", SERVE_CONFIG_FILE),
+        };
+        let compose_dirs: Vec<String> = match &overrides.compose_dir {
+            Some(dirs) => dirs.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+            None => self.compose_dirs,
+        };
+        if compose_dirs.is_empty() {
+            bail!("No compose directories. Pass --compose-dir or set compose_dirs in {}", SERVE_CONFIG_FILE);
+        }
+
+        let mut webhooks = self.webhooks;
+        for (i, hook) in webhooks.iter().enumerate() {
+            if webhooks[..i].iter().any(|h| h.name == hook.name) {
+                bail!("Two webhooks are named '{}'", hook.name);
+            }
+        }
+        for hook in &mut webhooks {
+            hook.secret = match (&hook.secret, &hook.secret_file) {
+                // An empty secret would accept any request with an empty X-Gitlab-Token
+                (Some(secret), _) if secret.trim().is_empty() => bail!("Webhook '{}' has an empty secret", hook.name),
+                (Some(secret), _) => Some(secret.clone()),
+                (None, Some(file)) => Some(read_secret(file)?),
+                (None, None) => bail!("Webhook '{}' has no secret or secret_file", hook.name),
+            };
+        }
+        for w in &self.watch {
+            check_duration("watch interval", &w.interval)?;
+        }
+        for rule in &self.alerts {
+            if !ALERT_METRICS.contains(&rule.metric.as_str()) {
+                bail!("Unknown alert metric '{}'. Use one of: {}", rule.metric, ALERT_METRICS.join(", "));
+            }
+            check_duration("alert duration", &rule.for_)?;
+        }
+
+        Ok(Settings {
+            token,
+            port: overrides.port.or(self.port).unwrap_or(DEFAULT_PORT),
+            compose_dirs,
+            public_metrics: overrides.public_metrics || self.public_metrics,
+            webhooks,
+            watch: self.watch,
+            alerts: self.alerts,
+        })
+    }
+}
+
+/// The serve.toml `ops init` and `ops serve --install` write: the token stays in serve.env
+pub fn render(port: u16, compose_dirs: &[String], tls_domains: &[String], public_metrics: bool) -> String {
+    let list = |items: &[String]| items.iter().map(|i| format!("{:?}", i)).collect::<Vec<_>>().join(", ");
+    format!(r#"# ops serve settings. Flags given to `ops serve` override these.
+# Apply changes with `systemctl reload ops-serve`; a new port needs `systemctl restart ops-serve`.
+port = {}
+compose_dirs = [{}]
+token_file = "{}"
+public_metrics = {}
+
+# Domains Caddy serves this daemon on over HTTPS
+[tls]
+domains = [{}]
+
+# Deploy on a signed push (GitHub X-Hub-Signature-256 or GitLab X-Gitlab-Token): POST /webhooks/<name>
+# [[webhooks]]
+# name = "shop"
+# secret_file = "/etc/ops/webhooks/shop.secret"
+# deploy_path = "/opt/shop"
+# branch = "main"
+
+# Deploy when the remote of the checked-out branch moves
+# [[watch]]
+# deploy_path = "/opt/shop"
+# interval = "2m"
+
+# Report a node event when a metric stays above a threshold
+# [[alerts]]
+# metric = "disk"   # cpu | memory | disk | load
+# above = 90
+# for = "10m"
+"#, port, list(compose_dirs), SERVE_ENV_FILE, public_metrics, list(tls_domains))
+}
+
+/// Write serve.toml unless one exists, so edits survive re-runs; whether it was written
+pub fn write_default(privilege: &Privilege, port: u16, compose_dirs: &[String], tls_domains: &[String], public_metrics: bool) -> Result<bool> {
+    if Path::new(SERVE_CONFIG_FILE).exists() {
+        return Ok(false);
+    }
+    // Webhook secrets may be written inline, so only root reads it
+    privilege.write(SERVE_CONFIG_FILE, &render(port, compose_dirs, tls_domains, public_metrics), 0o600)
+        .with_context(|| format!("Failed to write {}", SERVE_CONFIG_FILE))?;
+    Ok(true)
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new().chain_update(block.map(|b| b ^ 0x36)).chain_update(message).finalize();
+    Sha256::new().chain_update(block.map(|b| b ^ 0x5c)).chain_update(inner).finalize().into()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// A GitHub `X-Hub-Signature-256: sha256=<hex>` over the body, or a GitLab `X-Gitlab-Token` equal to the secret
+pub fn verify_webhook(secret: &str, github_signature: Option<&str>, gitlab_token: Option<&str>, body: &[u8]) -> bool {
+    if let Some(signature) = github_signature.and_then(|s| s.strip_prefix("sha256=")) {
+        let expected: String = hmac_sha256(secret.as_bytes(), body).iter().map(|b| format!("{:02x}", b)).collect();
+        return constant_time_eq(expected.as_bytes(), signature.to_ascii_lowercase().as_bytes());
+    }
+    gitlab_token.is_some_and(|t| constant_time_eq(t.as_bytes(), secret.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_and_webhook_signature() {
+        let dir = tempfile::tempdir().unwrap();
+        let token_file = dir.path().join("serve.env");
+        std::fs::write(&token_file, "OPS_SERVE_TOKEN=abc123\n").unwrap();
+
+        let mut config: ServeConfig = toml::from_str(&format!(r#"
+            compose_dirs = ["/opt/shop"]
+            token_file = "{}"
+            [[webhooks]]
+            name = "shop"
+            secret = "s3cret"
+            deploy_path = "/opt/shop"
+            [[watch]]
+            deploy_path = "/opt/shop"
+            interval = "2m"
+            [[alerts]]
+            metric = "disk"
+            above = 90
+            for = "10m"
+        "#, token_file.display())).unwrap();
+        let settings = config.clone().resolve(&Overrides::default()).unwrap();
+        assert_eq!((settings.token.as_str(), settings.port), ("abc123", DEFAULT_PORT));
+        assert_eq!(settings.watch[0].interval(), Duration::from_secs(120));
+        assert_eq!(settings.alerts[0].duration(), Duration::from_secs(600));
+
+        let overrides = Overrides { token: Some("flag".into()), port: Some(9000), compose_dir: Some("/a, /b".into()), public_metrics: true };
+        let settings = config.clone().resolve(&overrides).unwrap();
+        assert_eq!((settings.token.as_str(), settings.port, settings.public_metrics), ("flag", 9000, true));
+        assert_eq!(settings.compose_dirs, vec!["/a", "/b"]);
+
+        config.webhooks[0].secret = Some(" ".into());
+        assert!(config.clone().resolve(&Overrides::default()).is_err());
+        config.webhooks[0].secret = Some("s3cret".into());
+        config.alerts[0].metric = "iops".into();
+        assert!(config.clone().resolve(&Overrides::default()).is_err());
+        assert!(ServeConfig::default().resolve(&Overrides::default()).is_err());
+
+        // RFC 4231 test case 2
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(mac.iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        let body = b"{\"ref\":\"refs/heads/main\"}";
+        let signature = format!("sha256={}", hmac_sha256(b"s3cret", body).iter().map(|b| format!("{:02x}", b)).collect::<String>());
+        assert!(verify_webhook("s3cret", Some(&signature), None, body));
+        assert!(!verify_webhook("other", Some(&signature), None, body));
+        assert!(verify_webhook("s3cret", None, Some("s3cret"), body));
+        assert!(!verify_webhook("s3cret", None, None, body));
+
+        let rendered: ServeConfig = toml::from_str(&render(8377, &["/opt/shop".into()], &["42.node.ops.autos".into()], false)).unwrap();
+        assert_eq!(rendered.tls.domains, vec!["42.node.ops.autos"]);
+        assert_eq!(rendered.token_file.as_deref(), Some(SERVE_ENV_FILE));
+    }
+}
@@ -1,9 +1,12 @@
 use anyhow::Result;
-use serde::Serialize;
-use std::process::Command;
+use serde::{Deserialize, Serialize};
+use super::compose_command;
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Container {
+    /// Compose project; empty from serve versions that did not report it
+    #[serde(default)]
+    pub project: String,
     pub name: String,
     pub service: String,
     pub state: String,
@@ -12,9 +15,8 @@ pub struct Container {
 }
 
 pub fn list_containers(compose_dir: &str) -> Result<Vec<Container>> {
-    let output = Command::new("docker")
-        .args(["compose", "ps", "--format", "json", "-a"])
-        .current_dir(compose_dir)
+    let output = compose_command(compose_dir)
+        .args(["ps", "--format", "json", "-a"])
         .output()?;
 
     if !output.status.success() {
@@ -32,6 +34,7 @@ pub fn list_containers(compose_dir: &str) -> Result<Vec<Container>> {
         }
         if let Ok(v) = serde_json::from_str::<serde_json::Value>(line) {
             containers.push(Container {
+                project: v["Project"].as_str().unwrap_or("").to_string(),
                 name: v["Name"].as_str().unwrap_or("").to_string(),
                 service: v["Service"].as_str().unwrap_or("").to_string(),
                 state: v["State"].as_str().unwrap_or("").to_string(),
@@ -45,9 +48,8 @@ pub fn list_containers(compose_dir: &str) -> Result<Vec<Container>> {
 }
 
 pub fn list_services(compose_dir: &str) -> Result<Vec<String>> {
-    let output = Command::new("docker")
-        .args(["compose", "config", "--services"])
-        .current_dir(compose_dir)
+    let output = compose_command(compose_dir)
+        .args(["config", "--services"])
         .output()?;
 
     if !output.status.success() {
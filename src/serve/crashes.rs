@@ -0,0 +1,143 @@
+//! Post-deploy crash detection: after a `/deploy`, `ops serve` watches the compose project for
+//! a few minutes and reports containers that restart in a loop or exit non-zero to the backend,
+//! with their last log lines, where `ops events` and `ops status` show them.
+
+use super::{compose_command, logs};
+use crate::api;
+use crate::commands::deploy::load_ops_toml;
+use crate::node_state::NodeState;
+use crate::types::NodeEvent;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+pub const WATCH_WINDOW: Duration = Duration::from_secs(180);
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Restarts since the deploy that make a restart loop
+const RESTART_LOOP_THRESHOLD: u64 = 3;
+const LOG_LINES: u32 = 50;
+pub const CRASH_EVENT: &str = "post-deploy-crash";
+
+/// `docker inspect` format of `ContainerState`
+const INSPECT_FORMAT: &str = "{{.Name}}|{{index .Config.Labels \"com.docker.compose.service\"}}|{{.State.Status}}|{{.State.ExitCode}}|{{.RestartCount}}";
+
+#[derive(Debug, Clone, PartialEq)]
+struct ContainerState {
+    container: String,
+    service: String,
+    /// running | restarting | exited | ...
+    state: String,
+    exit_code: i64,
+    restart_count: u64,
+}
+
+fn parse_inspect(output: &str) -> Vec<ContainerState> {
+    output.lines().filter_map(|line| {
+        let parts: Vec<&str> = line.trim().split('|').collect();
+        let [name, service, state, exit_code, restarts] = parts[..] else { return None };
+        Some(ContainerState {
+            container: name.trim_start_matches('/').to_string(),
+            service: service.to_string(),
+            state: state.to_string(),
+            exit_code: exit_code.parse().ok()?,
+            restart_count: restarts.parse().ok()?,
+        })
+    }).collect()
+}
+
+/// Every container of the compose project, stopped ones included
+fn inspect(compose_dir: &str) -> Result<Vec<ContainerState>> {
+    let ids = compose_command(compose_dir).args(["ps", "-a", "-q"]).output()?;
+    let ids: Vec<String> = String::from_utf8_lossy(&ids.stdout).split_whitespace().map(String::from).collect();
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let output = Command::new("docker").args(["inspect", "-f", INSPECT_FORMAT]).args(&ids).output()?;
+    Ok(parse_inspect(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Why a container counts as crashed, given its restart count when the deploy finished
+fn crash_reason(c: &ContainerState, restarts_at_deploy: u64) -> Option<String> {
+    let restarts = c.restart_count.saturating_sub(restarts_at_deploy);
+    if restarts >= RESTART_LOOP_THRESHOLD || (c.state == "restarting" && restarts > 0) {
+        return Some(format!("restart loop ({} restarts)", restarts));
+    }
+    if c.state == "exited" && c.exit_code != 0 {
+        return Some(format!("exited with code {}", c.exit_code));
+    }
+    None
+}
+
+/// Watch `deploy_path` for WATCH_WINDOW and report each crashed container once
+pub async fn watch_after_deploy(deploy_path: String, serve_token: String) {
+    let Some(node_id) = NodeState::load().ok().flatten().and_then(|s| s.node_id) else {
+        eprintln!("crash watch: no node id in node state, not watching {}", deploy_path);
+        return;
+    };
+    let config = load_ops_toml(&format!("{}/ops.toml", deploy_path)).ok();
+
+    let dir = deploy_path.clone();
+    let Ok(Ok(initial)) = tokio::task::spawn_blocking(move || inspect(&dir)).await else { return };
+    let restarts_at_deploy: HashMap<String, u64> = initial.into_iter().map(|c| (c.container, c.restart_count)).collect();
+
+    let mut reported = HashSet::new();
+    let deadline = Instant::now() + WATCH_WINDOW;
+    while Instant::now() < deadline {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let dir = deploy_path.clone();
+        let containers = match tokio::task::spawn_blocking(move || inspect(&dir)).await {
+            Ok(Ok(list)) => list,
+            Ok(Err(e)) => { eprintln!("crash watch error for {}: {}", deploy_path, e); continue; }
+            Err(_) => continue,
+        };
+        for c in containers {
+            if reported.contains(&c.container) {
+                continue;
+            }
+            let Some(reason) = crash_reason(&c, restarts_at_deploy.get(&c.container).copied().unwrap_or(0)) else { continue };
+            reported.insert(c.container.clone());
+
+            let (dir, service) = (deploy_path.clone(), c.service.clone());
+            let logs = tokio::task::spawn_blocking(move || logs::get_logs(&dir, &service, LOG_LINES))
+                .await.ok().and_then(|r| r.ok()).unwrap_or_default();
+            let event = NodeEvent {
+                kind: CRASH_EVENT.into(),
+                project: config.as_ref().map(|c| c.project.clone()),
+                app: config.as_ref().and_then(|cfg| cfg.apps.iter().find(|a| a.services.contains(&c.service)).map(|a| a.name.clone())),
+                service: c.service,
+                container: c.container,
+                reason,
+                exit_code: c.exit_code,
+                restart_count: c.restart_count,
+                logs,
+            };
+            eprintln!("post-deploy crash: {} ({})", event.container, event.reason);
+            if let Err(e) = api::report_node_event(&serve_token, node_id, &event).await {
+                eprintln!("crash report error: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_crash_reason() {
+        let list = parse_inspect("/shop-api-1|api|restarting|1|5\n/shop-web-1|web|running|0|0\n/shop-migrate-1|migrate|exited|2|0\n/shop-init-1|init|exited|0|0\ngarbage\n");
+        assert_eq!(list.len(), 4);
+        assert_eq!(list[0], ContainerState {
+            container: "shop-api-1".into(), service: "api".into(), state: "restarting".into(), exit_code: 1, restart_count: 5,
+        });
+
+        assert_eq!(crash_reason(&list[0], 0).as_deref(), Some("restart loop (5 restarts)"));
+        // Restarts from before the deploy don't count
+        assert_eq!(crash_reason(&list[0], 5), None);
+        assert_eq!(crash_reason(&list[1], 0), None);
+        assert_eq!(crash_reason(&list[2], 0).as_deref(), Some("exited with code 2"));
+        // One-off jobs that finished fine
+        assert_eq!(crash_reason(&list[3], 0), None);
+    }
+}
@@ -0,0 +1,111 @@
+//! `POST /gc` from `ops gc`: remove the node side of tunnels and previews whose heartbeats
+//! stopped. For a tunnel that is its Caddy route; for a preview its containers, checkout and
+//! routes. Anything already gone counts as removed, so the backend records can go too.
+
+use super::previews;
+use crate::router::Router;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+/// Where `ops tunnel` writes its route fragments
+pub const TUNNEL_ROUTES_DIR: &str = "/etc/caddy/routes.d";
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct GcRequest {
+    /// `subdomain.project` of each tunnel
+    #[serde(default)]
+    pub tunnels: Vec<String>,
+    /// `slug.project` of each preview
+    #[serde(default)]
+    pub previews: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct GcResult {
+    /// Names from the request that are no longer on the node
+    pub removed: Vec<String>,
+    /// "<name>: <error>"
+    pub failed: Vec<String>,
+}
+
+/// File name of a tunnel's route fragment in TUNNEL_ROUTES_DIR
+pub fn tunnel_conf_name(subdomain: &str, project: &str) -> String {
+    format!("ops-tunnel-{}-{}.caddy", subdomain, project)
+}
+
+/// A DNS label as the backend allows it; anything else could escape the routes directory
+fn valid_label(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    name.split_once('.').filter(|(a, b)| valid_label(a) && valid_label(b))
+}
+
+fn remove_tunnel_route(target: &str) -> Result<bool, String> {
+    let (subdomain, project) = split_name(target).ok_or("not a subdomain.project target")?;
+    match std::fs::remove_file(Path::new(TUNNEL_ROUTES_DIR).join(tunnel_conf_name(subdomain, project))) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub fn collect(req: &GcRequest) -> GcResult {
+    let mut result = GcResult::default();
+
+    let mut reload = false;
+    for target in &req.tunnels {
+        match remove_tunnel_route(target) {
+            Ok(removed) => {
+                reload |= removed;
+                result.removed.push(target.clone());
+            }
+            Err(e) => result.failed.push(format!("{}: {}", target, e)),
+        }
+    }
+    if reload {
+        let status = Command::new("sh").arg("-c").arg(Router::Caddy.reload_command()).status();
+        if !status.is_ok_and(|s| s.success()) {
+            eprintln!("gc: caddy reload failed after removing tunnel routes");
+        }
+    }
+
+    let installed = previews::installed();
+    for name in &req.previews {
+        let Some((slug, project)) = split_name(name) else {
+            result.failed.push(format!("{}: not a slug.project name", name));
+            continue;
+        };
+        match installed.iter().find(|m| m.slug == slug && m.project == project) {
+            None => result.removed.push(name.clone()),
+            Some(manifest) => match previews::remove(manifest) {
+                Ok(()) => {
+                    eprintln!("gc: removed preview {}", name);
+                    result.removed.push(name.clone());
+                }
+                Err(e) => result.failed.push(format!("{}: {:#}", name, e)),
+            },
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(tunnel_conf_name("hook", "redq"), "ops-tunnel-hook-redq.caddy");
+        assert_eq!(split_name("feature-x.RedQ"), Some(("feature-x", "RedQ")));
+        assert_eq!(split_name("../etc.passwd"), None);
+        assert_eq!(split_name("hook"), None);
+        assert_eq!(split_name(".redq"), None);
+
+        let result = collect(&GcRequest { tunnels: vec!["a/b.c".into()], previews: vec!["gone.nowhere".into()] });
+        assert_eq!(result.removed, vec!["gone.nowhere"]);
+        assert_eq!(result.failed, vec!["a/b.c: not a subdomain.project target"]);
+    }
+}
@@ -1,17 +1,16 @@
 use anyhow::Result;
-use std::process::Command;
+use super::compose_command;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command as TokioCommand;
 
 pub fn get_logs(compose_dir: &str, service: &str, lines: u32) -> Result<String> {
     let lines_str = lines.to_string();
-    let mut args = vec!["compose", "logs", "--tail", &lines_str, "--no-color"];
+    let mut args = vec!["logs", "--tail", &lines_str, "--no-color"];
     if service != "all" {
         args.push(service);
     }
-    let output = Command::new("docker")
+    let output = compose_command(compose_dir)
         .args(&args)
-        .current_dir(compose_dir)
         .output()?;
 
     if !output.status.success() {
@@ -27,13 +26,13 @@ pub async fn stream_logs(
     service: &str,
     sender: tokio::sync::mpsc::Sender<String>,
 ) -> Result<()> {
-    let mut args = vec!["compose", "logs", "-f", "--tail", "50", "--no-color"];
+    let mut args = vec!["logs", "-f", "--tail", "50", "--no-color"];
     if service != "all" {
         args.push(service);
     }
-    let mut child = TokioCommand::new("docker")
-        .args(&args)
-        .current_dir(compose_dir)
+    let mut cmd = compose_command(compose_dir);
+    cmd.args(&args);
+    let mut child = TokioCommand::from(cmd)
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()?;
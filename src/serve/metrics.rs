@@ -1,5 +1,7 @@
+use crate::serve::stats::ContainerStats;
 use anyhow::Result;
 use serde::Serialize;
+use std::fmt::Write;
 
 #[derive(Serialize, Debug)]
 pub struct SystemMetrics {
@@ -121,3 +123,73 @@ fn read_loadavg() -> Result<[f64; 3]> {
         *parts.get(2).unwrap_or(&0.0),
     ])
 }
+
+const MIB: f64 = 1_048_576.0;
+const GIB: f64 = 1_073_741_824.0;
+
+/// Label value with `\`, `"` and newlines escaped as the exposition format requires
+fn label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Node and per-container metrics in the Prometheus text exposition format (version 0.0.4)
+pub fn render_prometheus(system: &SystemMetrics, containers: &[ContainerStats]) -> String {
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: Vec<(String, f64)>| {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge", name, help, name);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+
+    gauge("ops_node_cpu_percent", "CPU usage of the node in percent", vec![(String::new(), system.cpu_percent)]);
+    gauge("ops_node_memory_used_bytes", "Memory in use (total minus available)", vec![(String::new(), system.memory_used_mb as f64 * MIB)]);
+    gauge("ops_node_memory_total_bytes", "Total memory", vec![(String::new(), system.memory_total_mb as f64 * MIB)]);
+    gauge("ops_node_disk_used_bytes", "Used space on /", vec![(String::new(), (system.disk_used_gb * GIB).round())]);
+    gauge("ops_node_disk_total_bytes", "Size of /", vec![(String::new(), (system.disk_total_gb * GIB).round())]);
+    gauge("ops_node_uptime_seconds", "Seconds since boot", vec![(String::new(), system.uptime_seconds as f64)]);
+    gauge("ops_node_load", "Load average", ["1m", "5m", "15m"].iter().zip(system.load_average)
+        .map(|(period, v)| (format!("{{period=\"{}\"}}", period), v)).collect());
+
+    let per_container = |f: fn(&ContainerStats) -> f64| -> Vec<(String, f64)> {
+        containers.iter().map(|c| (
+            format!("{{container=\"{}\",project=\"{}\",service=\"{}\"}}", label(&c.container), label(&c.project), label(&c.service)),
+            f(c),
+        )).collect()
+    };
+    gauge("ops_container_cpu_percent", "CPU usage of the container in percent of one core", per_container(|c| c.cpu_percent));
+    gauge("ops_container_memory_bytes", "Memory used by the container", per_container(|c| (c.mem_mb * MIB).round()));
+    gauge("ops_container_memory_limit_bytes", "Memory limit of the container", per_container(|c| (c.mem_limit_mb * MIB).round()));
+    gauge("ops_container_network_receive_bytes", "Bytes received since the container started", per_container(|c| (c.net_rx_mb * MIB).round()));
+    gauge("ops_container_network_transmit_bytes", "Bytes sent since the container started", per_container(|c| (c.net_tx_mb * MIB).round()));
+    gauge("ops_container_block_read_bytes", "Bytes read from block devices", per_container(|c| (c.block_read_mb * MIB).round()));
+    gauge("ops_container_block_write_bytes", "Bytes written to block devices", per_container(|c| (c.block_write_mb * MIB).round()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus() {
+        let system = SystemMetrics {
+            cpu_percent: 12.5, memory_used_mb: 512, memory_total_mb: 2048, disk_used_gb: 1.5, disk_total_gb: 20.0,
+            uptime_seconds: 3600, load_average: [0.5, 0.25, 0.0],
+        };
+        let containers = vec![ContainerStats {
+            container: "shop-api-1".into(), project: "shop".into(), service: "a\"pi".into(),
+            cpu_percent: 3.25, mem_mb: 256.0, mem_limit_mb: 2048.0,
+            net_rx_mb: 1.5, net_tx_mb: 0.5, block_read_mb: 8.0, block_write_mb: 0.0,
+        }];
+        let text = render_prometheus(&system, &containers);
+        assert!(text.contains("# HELP ops_node_cpu_percent CPU usage of the node in percent\n# TYPE ops_node_cpu_percent gauge\nops_node_cpu_percent 12.5\n"));
+        assert!(text.contains("ops_node_memory_used_bytes 536870912\n"));
+        assert!(text.contains("ops_node_disk_used_bytes 1610612736\n"));
+        assert!(text.contains("ops_node_load{period=\"5m\"} 0.25\n"));
+        assert!(text.contains("ops_container_memory_bytes{container=\"shop-api-1\",project=\"shop\",service=\"a\\\"pi\"} 268435456\n"));
+
+        // No containers: the families are still declared
+        assert!(render_prometheus(&system, &[]).contains("# TYPE ops_container_cpu_percent gauge\n# HELP"));
+    }
+}
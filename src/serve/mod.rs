@@ -2,3 +2,33 @@ pub mod containers;
 pub mod logs;
 pub mod metrics;
 pub mod actions;
+pub mod stats;
+pub mod previews;
+pub mod crashes;
+pub mod queue;
+pub mod config;
+pub mod watch;
+pub mod alerts;
+pub mod gc;
+
+use std::process::Command;
+
+/// `docker compose` in `compose_dir`, honouring `[deploy]` compose_project_name, profiles
+/// and compose_files from the ops.toml in that directory (if any)
+pub fn compose_command(compose_dir: &str) -> Command {
+    let mut cmd = Command::new("docker");
+    cmd.arg("compose").current_dir(compose_dir);
+    let path = std::path::Path::new(compose_dir).join("ops.toml");
+    if let Ok(config) = crate::commands::deploy::load_ops_toml(&path.to_string_lossy()) {
+        if let Some(name) = &config.deploy.compose_project_name {
+            cmd.args(["-p", name]);
+        }
+        for profile in &config.deploy.profiles {
+            cmd.args(["--profile", profile]);
+        }
+        for file in config.deploy.compose_files.iter().flatten() {
+            cmd.args(["-f", file]);
+        }
+    }
+    cmd
+}
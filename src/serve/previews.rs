@@ -0,0 +1,160 @@
+//! Branch preview cleanup: `ops preview up` leaves a manifest per preview on the node, and
+//! `ops serve` removes previews whose TTL ran out or whose git branch was deleted. It also sends
+//! the backend a heartbeat for every preview still here, so ones lost with a node get expired.
+
+use crate::api;
+use crate::node_state::NodeState;
+use crate::router::Router;
+use crate::types::PreviewRef;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const PREVIEWS_DIR: &str = "/etc/ops/previews";
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Everything on the node that belongs to one preview
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PreviewManifest {
+    pub project: String,
+    pub slug: String,
+    pub branch: String,
+    pub compose_project: String,
+    pub deploy_path: String,
+    pub override_file: String,
+    /// "caddy" | "nginx"
+    pub router: String,
+    /// Route fragment paths
+    pub fragments: Vec<String>,
+    /// Unix seconds
+    pub expires_at: u64,
+    /// The checkout is a git clone whose branch can be checked against origin
+    pub git: bool,
+}
+
+impl PreviewManifest {
+    /// `<PREVIEWS_DIR>/<compose project>.json`
+    pub fn path(&self) -> String {
+        manifest_path(&self.compose_project)
+    }
+
+    /// Shell that removes the preview's containers, volumes, files and routes, then its manifest
+    pub fn remove_script(&self) -> String {
+        let router = Router::from_config(Some(&self.router)).unwrap_or(Router::Caddy);
+        let fragments: Vec<String> = self.fragments.iter().filter(|f| !f.contains('\'')).map(|f| format!("'{}'", f)).collect();
+        let reload = if fragments.is_empty() {
+            String::new()
+        } else {
+            format!(" && rm -f {} && ({})", fragments.join(" "), router.reload_command())
+        };
+        format!(
+            "docker compose -p {} down -v --remove-orphans && rm -rf {} {}{} && rm -f {}",
+            self.compose_project, self.deploy_path, self.override_file, reload, self.path()
+        )
+    }
+}
+
+pub fn manifest_path(compose_project: &str) -> String {
+    format!("{}/{}.json", PREVIEWS_DIR, compose_project)
+}
+
+/// Why a preview should go, if it should
+fn stale_reason(manifest: &PreviewManifest, now: u64) -> Option<String> {
+    if now >= manifest.expires_at {
+        return Some("TTL expired".into());
+    }
+    if manifest.git && branch_deleted(&manifest.deploy_path, &manifest.branch) {
+        return Some(format!("branch '{}' was deleted", manifest.branch));
+    }
+    None
+}
+
+/// `git ls-remote --exit-code` exits 2 when the ref doesn't exist; network errors (128) keep the preview
+fn branch_deleted(checkout: &str, branch: &str) -> bool {
+    Command::new("git")
+        .args(["-C", checkout, "ls-remote", "--exit-code", "--heads", "origin", branch])
+        .env("GIT_SSH_COMMAND", "ssh -o StrictHostKeyChecking=no -o BatchMode=yes")
+        .output()
+        .is_ok_and(|o| o.status.code() == Some(2))
+}
+
+/// Every preview with a manifest on this node
+pub fn installed() -> Vec<PreviewManifest> {
+    let Ok(entries) = std::fs::read_dir(PREVIEWS_DIR) else { return Vec::new() };
+    entries.flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .filter_map(|p| std::fs::read_to_string(p).ok())
+        .filter_map(|c| serde_json::from_str::<PreviewManifest>(&c).ok())
+        .collect()
+}
+
+/// Remove a preview's containers, volumes, checkout and routes
+pub fn remove(manifest: &PreviewManifest) -> Result<()> {
+    // Never rm -rf a relative path
+    if !Path::new(&manifest.deploy_path).starts_with("/") {
+        bail!("deploy path {} is not absolute", manifest.deploy_path);
+    }
+    let status = Command::new("sh").arg("-c").arg(manifest.remove_script()).status()?;
+    if !status.success() {
+        bail!("removal exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Remove every stale preview on this node
+pub fn enforce() -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    for manifest in installed() {
+        let Some(reason) = stale_reason(&manifest, now) else { continue };
+        match remove(&manifest) {
+            Ok(()) => eprintln!("removed preview {}.{} ({})", manifest.slug, manifest.project, reason),
+            Err(e) => eprintln!("preview cleanup error: {}.{} ({})", manifest.slug, manifest.project, e),
+        }
+    }
+    Ok(())
+}
+
+/// Tell the backend which previews are still on this node
+pub async fn heartbeat(serve_token: &str) -> Result<()> {
+    let previews: Vec<PreviewRef> = installed().into_iter()
+        .map(|m| PreviewRef { project: m.project, slug: m.slug })
+        .collect();
+    if previews.is_empty() {
+        return Ok(());
+    }
+    let node_id = NodeState::load()?.and_then(|s| s.node_id).context("No node id in node state")?;
+    api::preview_heartbeats(serve_token, node_id, &previews).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_and_remove_script() {
+        let manifest = PreviewManifest {
+            project: "RedQ".into(),
+            slug: "feature-x".into(),
+            branch: "feature/x".into(),
+            compose_project: "redq-feature-x".into(),
+            deploy_path: "/opt/redq-preview-feature-x".into(),
+            override_file: "/opt/redq-preview-feature-x.ops-preview.yml".into(),
+            router: "caddy".into(),
+            fragments: vec!["/etc/caddy/routes/ops-preview-feature-x-api-RedQ.caddy".into()],
+            expires_at: 1_000,
+            git: false,
+        };
+        assert_eq!(stale_reason(&manifest, 999), None);
+        assert_eq!(stale_reason(&manifest, 1_000).as_deref(), Some("TTL expired"));
+
+        let script = manifest.remove_script();
+        assert!(script.starts_with("docker compose -p redq-feature-x down -v --remove-orphans && rm -rf /opt/redq-preview-feature-x "));
+        assert!(script.contains("rm -f '/etc/caddy/routes/ops-preview-feature-x-api-RedQ.caddy' && (caddy validate"));
+        assert!(script.ends_with("rm -f /etc/ops/previews/redq-feature-x.json"));
+    }
+}
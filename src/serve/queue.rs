@@ -0,0 +1,318 @@
+//! Deploy queue: `/deploy` requests for the same deploy path (CI, webhooks, someone by hand) run
+//! one at a time in arrival order. `GET /deploys` shows the queue. `ops deploy` takes a place in it
+//! too (`POST /deploys`), so a webhook can't start while the CLI is deploying the same path.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+
+/// Finished deploys kept for `/deploys`
+const KEEP_FINISHED: usize = 20;
+/// A place held by `ops deploy` is given up after this long without a poll or a finish,
+/// in case the CLI was killed or lost its connection
+pub const HOLD_LEASE_SECS: u64 = 3600;
+/// How often a waiting deploy looks for expired holds ahead of it
+const EXPIRY_CHECK: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+impl DeployStatus {
+    pub fn is_active(self) -> bool {
+        matches!(self, DeployStatus::Queued | DeployStatus::Running)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueuedDeploy {
+    pub id: u64,
+    pub deploy_path: String,
+    pub status: DeployStatus,
+    /// Unix seconds
+    pub queued_at: u64,
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Places held by `ops deploy`: Unix seconds after which the place is given up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease_until: Option<u64>,
+}
+
+/// `POST /deploys`: hold a place for a deploy run elsewhere
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HoldRequest {
+    pub deploy_path: String,
+}
+
+/// A held place, and how many deploys of its path are still ahead of it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HeldPlace {
+    pub id: u64,
+    pub ahead: usize,
+}
+
+/// `POST /deploys/:id/finish`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FinishRequest {
+    pub success: bool,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// `GET /deploys`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeployList {
+    pub deploys: Vec<QueuedDeploy>,
+}
+
+/// Queued or running deploys of `deploy_path` ahead of `id` (all of them for `None`)
+pub fn ahead(entries: &[QueuedDeploy], deploy_path: &str, id: Option<u64>) -> usize {
+    let path = normalize(deploy_path);
+    entries.iter()
+        .filter(|e| e.status.is_active() && e.deploy_path == path && id.is_none_or(|id| e.id < id))
+        .count()
+}
+
+fn normalize(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        p => p,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[derive(Default)]
+pub struct DeployQueue {
+    entries: Mutex<(u64, Vec<QueuedDeploy>)>,
+    changed: Notify,
+}
+
+/// Fail the held places nobody has heard from within their lease
+fn expire_holds(entries: &mut [QueuedDeploy]) {
+    let now = now();
+    for e in entries.iter_mut().filter(|e| e.status.is_active() && e.lease_until.is_some_and(|t| t < now)) {
+        e.status = DeployStatus::Failed;
+        e.finished_at = Some(now);
+        e.message = Some("given up: ops deploy stopped answering".into());
+    }
+}
+
+/// A `/deploy` request's place in the queue. Dropped before `detach`, e.g. because the client
+/// disconnected while it waited, it leaves the queue instead of blocking the deploys behind it.
+pub struct Ticket<'a> {
+    queue: &'a DeployQueue,
+    pub id: u64,
+    finished: bool,
+}
+
+impl Ticket<'_> {
+    pub async fn wait_turn(&self) {
+        self.queue.wait_turn(self.id).await
+    }
+
+    /// Stop cancelling on drop, once the deploy has started and will finish the entry itself
+    pub fn detach(mut self) -> u64 {
+        self.finished = true;
+        self.id
+    }
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.queue.finish(self.id, false, "cancelled: the client went away");
+        }
+    }
+}
+
+impl DeployQueue {
+    /// Add a deploy of `deploy_path`; returns its id and how many are ahead of it
+    pub fn enqueue(&self, deploy_path: &str) -> (u64, usize) {
+        self.push(deploy_path, None)
+    }
+
+    /// enqueue, as a ticket that leaves the queue when dropped unfinished
+    pub fn ticket(&self, deploy_path: &str) -> (Ticket<'_>, usize) {
+        let (id, waiting) = self.enqueue(deploy_path);
+        (Ticket { queue: self, id, finished: false }, waiting)
+    }
+
+    /// Hold a place for a deploy run elsewhere (`ops deploy`), kept for HOLD_LEASE_SECS at a time
+    pub fn hold(&self, deploy_path: &str) -> HeldPlace {
+        let (id, ahead) = self.push(deploy_path, Some(now() + HOLD_LEASE_SECS));
+        HeldPlace { id, ahead }
+    }
+
+    fn push(&self, deploy_path: &str, lease_until: Option<u64>) -> (u64, usize) {
+        let mut guard = self.entries.lock().unwrap();
+        let (next_id, entries) = &mut *guard;
+        expire_holds(entries);
+        *next_id += 1;
+        let id = *next_id;
+        let waiting = ahead(entries, deploy_path, None);
+        entries.push(QueuedDeploy {
+            id,
+            deploy_path: normalize(deploy_path).to_string(),
+            status: DeployStatus::Queued,
+            queued_at: now(),
+            started_at: None,
+            finished_at: None,
+            message: None,
+            lease_until,
+        });
+        (id, waiting)
+    }
+
+    /// For a held place: renew its lease and mark it running once nothing is ahead. None when
+    /// the place is gone (finished, or given up after its lease ran out).
+    pub fn try_start(&self, id: u64) -> Option<usize> {
+        let mut guard = self.entries.lock().unwrap();
+        let entries = &mut guard.1;
+        expire_holds(entries);
+        let i = entries.iter().position(|e| e.id == id && e.status.is_active())?;
+        entries[i].lease_until = Some(now() + HOLD_LEASE_SECS);
+        let waiting = ahead(entries, &entries[i].deploy_path.clone(), Some(id));
+        if waiting == 0 && entries[i].status == DeployStatus::Queued {
+            entries[i].status = DeployStatus::Running;
+            entries[i].started_at = Some(now());
+        }
+        Some(waiting)
+    }
+
+    /// Wait until nothing is ahead of `id`, then mark it running
+    pub async fn wait_turn(&self, id: u64) {
+        loop {
+            let notified = self.changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            {
+                let mut guard = self.entries.lock().unwrap();
+                let entries = &mut guard.1;
+                expire_holds(entries);
+                let Some(i) = entries.iter().position(|e| e.id == id) else { return };
+                if ahead(entries, &entries[i].deploy_path.clone(), Some(id)) == 0 {
+                    entries[i].status = DeployStatus::Running;
+                    entries[i].started_at = Some(now());
+                    return;
+                }
+            }
+            // A hold ahead can expire without anyone calling finish
+            let _ = tokio::time::timeout(EXPIRY_CHECK, notified).await;
+        }
+    }
+
+    pub fn finish(&self, id: u64, success: bool, message: &str) {
+        let mut guard = self.entries.lock().unwrap();
+        let entries = &mut guard.1;
+        if let Some(e) = entries.iter_mut().find(|e| e.id == id) {
+            e.status = if success { DeployStatus::Done } else { DeployStatus::Failed };
+            e.finished_at = Some(now());
+            e.message = Some(message.to_string());
+        }
+        // Drop the oldest finished entries
+        let finished = entries.iter().filter(|e| !e.status.is_active()).count();
+        let mut excess = finished.saturating_sub(KEEP_FINISHED);
+        entries.retain(|e| {
+            if excess > 0 && !e.status.is_active() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+        drop(guard);
+        self.changed.notify_waiters();
+    }
+
+    /// Entries in arrival order, optionally only those of one deploy path
+    pub fn snapshot(&self, deploy_path: Option<&str>) -> Vec<QueuedDeploy> {
+        let mut guard = self.entries.lock().unwrap();
+        expire_holds(&mut guard.1);
+        guard.1.iter()
+            .filter(|e| deploy_path.is_none_or(|p| e.deploy_path == normalize(p)))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_queue_serializes_per_path() {
+        let queue = Arc::new(DeployQueue::default());
+        let (first, ahead_first) = queue.enqueue("/opt/shop/");
+        let (second, ahead_second) = queue.enqueue("/opt/shop");
+        let (other, ahead_other) = queue.enqueue("/opt/blog");
+        assert_eq!((ahead_first, ahead_second, ahead_other), (0, 1, 0));
+
+        queue.wait_turn(first).await;
+        queue.wait_turn(other).await;
+        let waiter = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.wait_turn(second).await }
+        });
+        tokio::task::yield_now().await;
+        let shop = queue.snapshot(Some("/opt/shop"));
+        assert_eq!(shop.iter().map(|e| e.status).collect::<Vec<_>>(), vec![DeployStatus::Running, DeployStatus::Queued]);
+        assert_eq!(ahead(&shop, "/opt/shop", Some(second)), 1);
+
+        queue.finish(first, true, "ok");
+        waiter.await.unwrap();
+        let shop = queue.snapshot(Some("/opt/shop"));
+        assert_eq!(shop.iter().map(|e| e.status).collect::<Vec<_>>(), vec![DeployStatus::Done, DeployStatus::Running]);
+
+        // A client that goes away while waiting gives up its place
+        let (ticket, waiting) = queue.ticket("/opt/shop");
+        assert_eq!(waiting, 1);
+        let id = ticket.id;
+        drop(ticket);
+        assert_eq!(queue.snapshot(None).iter().find(|e| e.id == id).unwrap().status, DeployStatus::Failed);
+
+        // A detached ticket stays put until its deploy finishes it
+        let (ticket, _) = queue.ticket("/opt/shop");
+        let id = ticket.detach();
+        assert_eq!(queue.snapshot(None).iter().find(|e| e.id == id).unwrap().status, DeployStatus::Queued);
+        queue.finish(id, true, "deployed");
+
+        for _ in 0..KEEP_FINISHED + 5 {
+            let (id, _) = queue.enqueue("/opt/blog");
+            queue.finish(id, false, "failed");
+        }
+        assert_eq!(queue.snapshot(None).iter().filter(|e| !e.status.is_active()).count(), KEEP_FINISHED);
+    }
+
+    #[test]
+    fn test_held_places() {
+        let queue = DeployQueue::default();
+        let (first, _) = queue.enqueue("/opt/shop");
+        let held = queue.hold("/opt/shop");
+        assert_eq!(held.ahead, 1);
+        assert_eq!(queue.try_start(held.id), Some(1));
+        queue.finish(first, true, "ok");
+        assert_eq!(queue.try_start(held.id), Some(0));
+        assert_eq!(queue.snapshot(None).last().unwrap().status, DeployStatus::Running);
+        // Anything queued after it waits for the held place
+        let (later, _) = queue.enqueue("/opt/shop");
+        assert_eq!(ahead(&queue.snapshot(None), "/opt/shop", Some(later)), 1);
+
+        // A hold nobody renews is given up
+        queue.entries.lock().unwrap().1.iter_mut().find(|e| e.id == held.id).unwrap().lease_until = Some(1);
+        assert_eq!(queue.try_start(held.id), None);
+        assert_eq!(ahead(&queue.snapshot(None), "/opt/shop", Some(later)), 0);
+    }
+}
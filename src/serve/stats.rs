@@ -0,0 +1,211 @@
+//! Per-container CPU/memory history: `ops serve` samples `docker stats` every minute into
+//! one JSONL file per day, read back over SSH by `ops report usage`. The current figures, with
+//! network and block IO, are served live at `/containers/stats` and shown by `ops status --stats`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const STATS_DIR: &str = "/var/lib/ops/stats";
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+const RETENTION_DAYS: u64 = 30;
+
+/// One container at one point in time
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Unix seconds
+    pub ts: u64,
+    pub container: String,
+    /// Compose labels; empty for containers started with `docker run` (deploy-id)
+    #[serde(default)]
+    pub project: String,
+    #[serde(default)]
+    pub service: String,
+    pub cpu_percent: f64,
+    pub mem_mb: f64,
+}
+
+/// `YYYY-MM-DD.jsonl` for a unix timestamp
+pub fn day_file(ts: u64) -> String {
+    let date = humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(ts)).to_string();
+    format!("{}.jsonl", &date[..10])
+}
+
+/// Take one sample of every running container and append it to today's file
+pub fn sample_and_record() -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let samples = collect(now)?;
+    if samples.is_empty() {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(STATS_DIR)?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(format!("{}/{}", STATS_DIR, day_file(now)))?;
+    let mut lines = String::new();
+    for s in &samples {
+        lines.push_str(&serde_json::to_string(s)?);
+        lines.push('\n');
+    }
+    file.write_all(lines.as_bytes())?;
+
+    prune(now);
+    Ok(())
+}
+
+/// `docker stats --no-stream` format of `ContainerStats`
+pub const STATS_FORMAT: &str = "{{.Name}}|{{.CPUPerc}}|{{.MemUsage}}|{{.NetIO}}|{{.BlockIO}}";
+/// `docker ps` format giving each container's compose labels, which docker stats lacks
+pub const LABELS_FORMAT: &str = "{{.Names}}|{{.Label \"com.docker.compose.project\"}}|{{.Label \"com.docker.compose.service\"}}";
+
+/// One container right now (`/containers/stats`)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContainerStats {
+    pub container: String,
+    pub project: String,
+    pub service: String,
+    pub cpu_percent: f64,
+    pub mem_mb: f64,
+    pub mem_limit_mb: f64,
+    pub net_rx_mb: f64,
+    pub net_tx_mb: f64,
+    pub block_read_mb: f64,
+    pub block_write_mb: f64,
+}
+
+fn docker_output(args: &[&str]) -> Result<String> {
+    let out = Command::new("docker").args(args).output()?;
+    if !out.status.success() {
+        anyhow::bail!("docker {} failed: {}", args[0], String::from_utf8_lossy(&out.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+pub fn parse_labels(output: &str) -> HashMap<String, (String, String)> {
+    output
+        .lines()
+        .filter_map(|l| {
+            let mut parts = l.splitn(3, '|');
+            Some((parts.next()?.to_string(), (parts.next()?.to_string(), parts.next()?.to_string())))
+        })
+        .collect()
+}
+
+/// Current stats of every running container
+pub fn current() -> Result<Vec<ContainerStats>> {
+    let stats = docker_output(&["stats", "--no-stream", "--format", STATS_FORMAT])?;
+    let labels = parse_labels(&docker_output(&["ps", "--format", LABELS_FORMAT])?);
+    Ok(parse_current(&stats, &labels))
+}
+
+pub fn parse_current(stats: &str, labels: &HashMap<String, (String, String)>) -> Vec<ContainerStats> {
+    stats.lines().filter_map(|l| parse_current_line(l, labels)).collect()
+}
+
+/// "1.2kB / 3.4MB" in MiB
+fn parse_pair_mb(s: &str) -> Option<(f64, f64)> {
+    let (a, b) = s.split_once('/')?;
+    Some((parse_size_mb(a.trim())?, parse_size_mb(b.trim())?))
+}
+
+fn parse_current_line(line: &str, labels: &HashMap<String, (String, String)>) -> Option<ContainerStats> {
+    let parts: Vec<&str> = line.split('|').map(str::trim).collect();
+    let [container, cpu, mem, net, block] = parts[..] else { return None };
+    let (mem_mb, mem_limit_mb) = parse_pair_mb(mem)?;
+    let (net_rx_mb, net_tx_mb) = parse_pair_mb(net)?;
+    let (block_read_mb, block_write_mb) = parse_pair_mb(block)?;
+    let (project, service) = labels.get(container).cloned().unwrap_or_default();
+    Some(ContainerStats {
+        container: container.to_string(),
+        project,
+        service,
+        cpu_percent: cpu.trim_end_matches('%').parse().ok()?,
+        mem_mb,
+        mem_limit_mb,
+        net_rx_mb,
+        net_tx_mb,
+        block_read_mb,
+        block_write_mb,
+    })
+}
+
+fn collect(ts: u64) -> Result<Vec<Sample>> {
+    let stats = docker_output(&["stats", "--no-stream", "--format", "{{.Name}}|{{.CPUPerc}}|{{.MemUsage}}"])?;
+    let labels = parse_labels(&docker_output(&["ps", "--format", LABELS_FORMAT])?);
+    Ok(stats.lines().filter_map(|l| parse_stats_line(l, ts, &labels)).collect())
+}
+
+fn parse_stats_line(line: &str, ts: u64, labels: &HashMap<String, (String, String)>) -> Option<Sample> {
+    let mut parts = line.splitn(3, '|');
+    let container = parts.next()?.trim().to_string();
+    let cpu_percent = parts.next()?.trim().trim_end_matches('%').parse().ok()?;
+    // "123.4MiB / 1.944GiB"
+    let mem_mb = parse_size_mb(parts.next()?.split('/').next()?.trim())?;
+    let (project, service) = labels.get(&container).cloned().unwrap_or_default();
+    Some(Sample { ts, container, project, service, cpu_percent, mem_mb })
+}
+
+/// Docker's binary sizes ("512KiB", "1.5GiB", "0B") in MiB
+fn parse_size_mb(s: &str) -> Option<f64> {
+    let split = s.find(|c: char| c.is_ascii_alphabetic())?;
+    let (num, unit) = s.split_at(split);
+    let n: f64 = num.trim().parse().ok()?;
+    let factor = match unit {
+        "B" => 1.0 / 1_048_576.0,
+        "KiB" | "kB" | "KB" => 1.0 / 1024.0,
+        "MiB" | "MB" => 1.0,
+        "GiB" | "GB" => 1024.0,
+        "TiB" | "TB" => 1_048_576.0,
+        _ => return None,
+    };
+    Some(n * factor)
+}
+
+/// Delete day files past the retention window
+fn prune(now: u64) {
+    let oldest = day_file(now.saturating_sub(RETENTION_DAYS * 86_400));
+    let Ok(entries) = std::fs::read_dir(STATS_DIR) else { return };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.ends_with(".jsonl") && name < oldest {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stats_line() {
+        let labels = HashMap::from([("redq-db-1".to_string(), ("redq".to_string(), "db".to_string()))]);
+        let s = parse_stats_line("redq-db-1|12.50%|512MiB / 1.944GiB", 1_700_000_000, &labels).unwrap();
+        assert_eq!((s.project.as_str(), s.service.as_str(), s.cpu_percent, s.mem_mb), ("redq", "db", 12.5, 512.0));
+
+        let s = parse_stats_line("redq-api-42|0.00%|1.5GiB / 4GiB", 0, &labels).unwrap();
+        assert_eq!((s.service.as_str(), s.mem_mb), ("", 1536.0));
+        assert!(parse_stats_line("garbage", 0, &labels).is_none());
+
+        assert_eq!(day_file(1_700_000_000), "2023-11-14.jsonl");
+    }
+
+    #[test]
+    fn test_parse_current() {
+        let labels = parse_labels("shop-api-1|shop|api\nshop-api-812||\n");
+        let stats = parse_current(
+            "shop-api-1|3.25%|256MiB / 2GiB|1.5MB / 512kB|8MB / 0B\nshop-api-812|0.10%|64MiB / 2GiB|0B / 0B|0B / 0B\nbroken|1%|x\n",
+            &labels,
+        );
+        assert_eq!(stats.len(), 2);
+        let api = &stats[0];
+        assert_eq!((api.service.as_str(), api.cpu_percent, api.mem_mb, api.mem_limit_mb), ("api", 3.25, 256.0, 2048.0));
+        assert_eq!((api.net_rx_mb, api.net_tx_mb, api.block_read_mb), (1.5, 0.5, 8.0));
+        assert_eq!(stats[1].project, "");
+    }
+}
@@ -0,0 +1,66 @@
+//! `[[watch]]` in serve.toml: deploy a path when the remote of its checked-out branch moves, for
+//! repositories that can't send webhooks to the node.
+
+use anyhow::{bail, Result};
+use std::process::Command;
+use std::time::Duration;
+
+/// How often the daemon looks for watched paths that are due
+pub const TICK: Duration = Duration::from_secs(15);
+/// Longest wait between checks of a path whose checks or deploys keep failing
+pub const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// The wait before the next check of a path after `failures` failed checks or deploys in a row:
+/// its interval, doubled per failure, up to MAX_BACKOFF
+pub fn backoff(interval: Duration, failures: u32) -> Duration {
+    interval.saturating_mul(1 << failures.min(16)).min(MAX_BACKOFF.max(interval))
+}
+
+fn git(deploy_path: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new("git").arg("-C").arg(deploy_path).args(args).output()?;
+    if !output.status.success() {
+        bail!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The commit `origin` has for `branch`, from `git ls-remote` output
+fn remote_head(ls_remote: &str, branch: &str) -> Option<String> {
+    let full = format!("refs/heads/{}", branch);
+    ls_remote.lines()
+        .filter_map(|l| l.split_once('\t'))
+        .find(|(_, name)| *name == full)
+        .map(|(sha, _)| sha.to_string())
+}
+
+/// Whether `origin` has moved past the commit checked out in `deploy_path`
+pub fn remote_moved(deploy_path: &str) -> Result<bool> {
+    let branch = git(deploy_path, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    if branch == "HEAD" {
+        bail!("{} has a detached HEAD; nothing to follow", deploy_path);
+    }
+    let local = git(deploy_path, &["rev-parse", "HEAD"])?;
+    let remote = git(deploy_path, &["ls-remote", "origin", &format!("refs/heads/{}", branch)])?;
+    match remote_head(&remote, &branch) {
+        Some(sha) => Ok(sha != local),
+        None => bail!("origin has no branch {}", branch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_head() {
+        let out = "1111111111111111111111111111111111111111\trefs/heads/main-old\n2222222222222222222222222222222222222222\trefs/heads/main\n";
+        assert_eq!(remote_head(out, "main").as_deref(), Some("2222222222222222222222222222222222222222"));
+        assert_eq!(remote_head(out, "dev"), None);
+
+        let minute = Duration::from_secs(60);
+        assert_eq!(backoff(minute, 0), minute);
+        assert_eq!(backoff(minute, 3), minute * 8);
+        assert_eq!(backoff(minute, 40), MAX_BACKOFF);
+        assert_eq!(backoff(MAX_BACKOFF * 2, 5), MAX_BACKOFF * 2);
+    }
+}
@@ -8,6 +8,8 @@ use std::io::Write;
 use std::process::Command;
 use colored::Colorize;
 
+pub const AUTHORIZED_KEY_COMMENT: &str = "# Added by ops.autos CLI for CI/CD";
+
 fn get_ssh_dir() -> Result<PathBuf> {
     dirs::home_dir()
         .context("Could not find home directory")
@@ -58,10 +60,21 @@ pub fn get_default_pubkey() -> Result<String> {
     Ok(content.trim().to_string())
 }
 
-pub fn add_to_authorized_keys(pubkey: &str) -> Result<()> {
+pub fn authorized_keys_path() -> Result<PathBuf> {
+    Ok(get_ssh_dir()?.join("authorized_keys"))
+}
+
+/// Append `pubkey` to authorized_keys unless it is already there. Returns true if it was added.
+pub fn add_to_authorized_keys(pubkey: &str) -> Result<bool> {
     let ssh_dir = get_ssh_dir()?;
     let authorized_keys_path = ssh_dir.join("authorized_keys");
 
+    // 已存在则跳过，避免重复 init 时堆积
+    let existing = fs::read_to_string(&authorized_keys_path).unwrap_or_default();
+    if existing.lines().any(|l| l.trim() == pubkey.trim()) {
+        return Ok(false);
+    }
+
     // 确保目录存在
     fs::create_dir_all(&ssh_dir)?;
 
@@ -71,8 +84,43 @@ pub fn add_to_authorized_keys(pubkey: &str) -> Result<()> {
         .open(&authorized_keys_path)
         .with_context(|| format!("Failed to open authorized_keys file at {:?}", authorized_keys_path))?;
 
-    writeln!(file, "\n# Added by ops.autos CLI for CI/CD")?;
+    writeln!(file, "\n{}", AUTHORIZED_KEY_COMMENT)?;
     writeln!(file, "{}", pubkey)?;
 
-    Ok(())
+    Ok(true)
+}
+
+/// Remove `pubkey` (and the comment line ops added above it) from the given authorized_keys file.
+/// Returns true if the key was found.
+pub fn remove_from_authorized_keys(path: &str, pubkey: &str) -> Result<bool> {
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return Ok(false),
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    let mut kept: Vec<&str> = Vec::with_capacity(lines.len());
+    let mut found = false;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim() == pubkey.trim() {
+            found = true;
+            continue;
+        }
+        let next_is_key = lines.get(i + 1).is_some_and(|n| n.trim() == pubkey.trim());
+        if line.trim() == AUTHORIZED_KEY_COMMENT && next_is_key {
+            // Also drop the blank separator written before the comment
+            if kept.last().is_some_and(|l| l.trim().is_empty()) {
+                kept.pop();
+            }
+            continue;
+        }
+        kept.push(line);
+    }
+    if found {
+        let mut out = kept.join("\n");
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        fs::write(path, out).with_context(|| format!("Failed to write {}", path))?;
+    }
+    Ok(found)
 }
\ No newline at end of file
@@ -0,0 +1,214 @@
+//! Table rendering for list commands.
+//!
+//! Columns are sized by display width, so colored cells, emoji and CJK text stay aligned.
+//! Tables are fitted to the terminal by truncating the widest columns, unless `--wide` is
+//! given, which also shows the columns marked wide-only. `--columns` and `--sort` pick and
+//! order columns by their key (the lowercase header).
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use console::{measure_text_width, pad_str, strip_ansi_codes, Alignment};
+use std::cmp::Ordering;
+
+/// `--wide`, `--columns` and `--sort`, flattened into each list command
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct TableOptions {
+    /// Show every column and don't truncate to the terminal width
+    #[arg(long)]
+    pub wide: bool,
+    /// Comma-separated columns to show, in order (e.g. id,status,region)
+    #[arg(long, value_delimiter = ',')]
+    pub columns: Option<Vec<String>>,
+    /// Sort rows by a column; prefix with - for descending (e.g. --sort=-weight)
+    #[arg(long, allow_hyphen_values = true)]
+    pub sort: Option<String>,
+}
+
+pub struct Column {
+    key: &'static str,
+    wide_only: bool,
+    right: bool,
+}
+
+impl Column {
+    pub fn new(key: &'static str) -> Self {
+        Column { key, wide_only: false, right: false }
+    }
+
+    /// Shown only with `--wide` or when picked with `--columns`
+    pub fn wide(key: &'static str) -> Self {
+        Column { key, wide_only: true, right: false }
+    }
+
+    /// Right-aligned, for numbers
+    pub fn right(mut self) -> Self {
+        self.right = true;
+        self
+    }
+
+    fn header(&self) -> String {
+        self.key.replace('_', " ").to_uppercase()
+    }
+}
+
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Narrowest a column is truncated to when fitting the terminal
+const MIN_WIDTH: usize = 6;
+const GAP: &str = "  ";
+const INDENT: &str = "  ";
+
+/// Numbers (with an optional leading # as in `#12` ids, or a trailing %) compare as numbers,
+/// anything else as text
+fn compare(a: &str, b: &str) -> Ordering {
+    let num = |s: &str| s.strip_prefix('#').unwrap_or(s).trim_end_matches('%').parse::<f64>().ok();
+    match (num(a), num(b)) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        _ => a.to_lowercase().cmp(&b.to_lowercase()),
+    }
+}
+
+impl Table {
+    pub fn new(columns: Vec<Column>) -> Self {
+        Table { columns, rows: Vec::new() }
+    }
+
+    /// Add a row; cells may be colored and are matched to columns by position
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    fn index_of(&self, key: &str) -> Result<usize> {
+        match self.columns.iter().position(|c| c.key.eq_ignore_ascii_case(key.trim())) {
+            Some(i) => Ok(i),
+            None => bail!(
+                "Unknown column '{}'. Available: {}",
+                key.trim(),
+                self.columns.iter().map(|c| c.key).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+
+    /// The table as lines, fitted to `term_width` when given
+    fn render(&self, opts: &TableOptions, term_width: Option<usize>) -> Result<Vec<String>> {
+        let visible: Vec<usize> = match &opts.columns {
+            Some(keys) => keys.iter().filter(|k| !k.trim().is_empty()).map(|k| self.index_of(k)).collect::<Result<_>>()?,
+            None => (0..self.columns.len()).filter(|&i| opts.wide || !self.columns[i].wide_only).collect(),
+        };
+
+        let mut rows: Vec<&Vec<String>> = self.rows.iter().collect();
+        if let Some(sort) = &opts.sort {
+            let (key, descending) = match sort.strip_prefix('-') {
+                Some(key) => (key, true),
+                None => (sort.as_str(), false),
+            };
+            let i = self.index_of(key)?;
+            let cell = |row: &Vec<String>| strip_ansi_codes(row.get(i).map(String::as_str).unwrap_or("")).to_string();
+            rows.sort_by(|a, b| {
+                let ord = compare(&cell(a), &cell(b));
+                if descending { ord.reverse() } else { ord }
+            });
+        }
+
+        let headers: Vec<String> = visible.iter().map(|&i| self.columns[i].header()).collect();
+        let mut widths: Vec<usize> = headers.iter().map(|h| measure_text_width(h)).collect();
+        for row in &rows {
+            for (w, &i) in widths.iter_mut().zip(&visible) {
+                *w = (*w).max(row.get(i).map(|c| measure_text_width(c)).unwrap_or(0));
+            }
+        }
+
+        // Shrink the widest column one character at a time until the table fits
+        if let Some(limit) = term_width.filter(|_| !opts.wide) {
+            let total = |widths: &[usize]| INDENT.len() + widths.iter().sum::<usize>() + GAP.len() * widths.len().saturating_sub(1);
+            while total(&widths) > limit {
+                match widths.iter_mut().filter(|w| **w > MIN_WIDTH).max_by_key(|w| **w) {
+                    Some(w) => *w -= 1,
+                    None => break,
+                }
+            }
+        }
+
+        let line = |cells: Vec<String>| {
+            let padded: Vec<String> = cells.iter().zip(&visible).zip(&widths).map(|((cell, &i), &w)| {
+                let align = if self.columns[i].right { Alignment::Right } else { Alignment::Left };
+                let truncate = (measure_text_width(cell) > w).then_some("…");
+                pad_str(cell, w, align, truncate).to_string()
+            }).collect();
+            format!("{}{}", INDENT, padded.join(GAP)).trim_end().to_string()
+        };
+
+        let mut lines = vec![line(headers.iter().map(|h| h.bold().to_string()).collect())];
+        for row in rows {
+            lines.push(line(visible.iter().map(|&i| row.get(i).cloned().unwrap_or_default()).collect()));
+        }
+        Ok(lines)
+    }
+
+    /// Print the table, fitted to the terminal when stdout is one
+    pub fn print(&self, opts: &TableOptions) -> Result<()> {
+        let term_width = console::Term::stdout().size_checked().map(|(_, w)| w as usize);
+        for line in self.render(opts, term_width)? {
+            o_detail!("{}", line);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        let mut t = Table::new(vec![Column::new("id").right(), Column::new("hostname"), Column::new("weight").right(), Column::wide("region")]);
+        t.row(vec!["7".into(), "web-東京".into(), "100".into(), "ap-northeast".into()]);
+        t.row(vec!["12".into(), "db".green().to_string(), "20".into(), "eu-west".into()]);
+        t
+    }
+
+    fn plain(lines: Vec<String>) -> Vec<String> {
+        lines.iter().map(|l| strip_ansi_codes(l).to_string()).collect()
+    }
+
+    #[test]
+    fn test_render_widths_columns_and_sort() {
+        let t = table();
+        assert_eq!(plain(t.render(&TableOptions::default(), None).unwrap()), vec![
+            "  ID  HOSTNAME  WEIGHT",
+            "   7  web-東京     100",
+            "  12  db            20",
+        ]);
+
+        let opts = TableOptions { wide: true, columns: None, sort: Some("-hostname".into()) };
+        assert_eq!(plain(t.render(&opts, Some(20)).unwrap()), vec![
+            "  ID  HOSTNAME  WEIGHT  REGION",
+            "   7  web-東京     100  ap-northeast",
+            "  12  db            20  eu-west",
+        ]);
+
+        let opts = TableOptions { wide: false, columns: Some(vec!["region".into(), "id".into()]), sort: Some("weight".into()) };
+        assert_eq!(plain(t.render(&opts, None).unwrap()), vec![
+            "  REGION        ID",
+            "  eu-west       12",
+            "  ap-northeast   7",
+        ]);
+
+        // Fitted to 14 columns: the widest column is truncated
+        let opts = TableOptions { wide: false, columns: Some(vec!["region".into(), "id".into()]), sort: None };
+        assert_eq!(plain(t.render(&opts, Some(14)).unwrap())[1], "  ap-nort…   7");
+
+        // `#9` before `#10`, as numbers
+        let mut ids = Table::new(vec![Column::new("id").right()]);
+        for id in [10, 9, 100] {
+            ids.row(vec![format!("#{}", id).dimmed().to_string()]);
+        }
+        let opts = TableOptions { sort: Some("id".into()), ..Default::default() };
+        assert_eq!(plain(ids.render(&opts, None).unwrap()), vec!["    ID", "    #9", "   #10", "  #100"]);
+
+        let bad = TableOptions { columns: Some(vec!["nope".into()]), ..Default::default() };
+        assert!(t.render(&bad, None).unwrap_err().to_string().contains("Available: id, hostname, weight, region"));
+    }
+}
@@ -12,9 +12,115 @@ pub struct CiKeyResponse {
 }
 
 
+#[derive(Deserialize, Debug)]
+pub struct CiPublicKeyResponse {
+    pub public_key: String,
+}
+
+
+/// New keypair from POST .../ci-key/rotate
+#[derive(Deserialize, Debug)]
+pub struct CiKeyRotateResponse {
+    pub public_key: String,
+    pub private_key: String,
+}
+
+
+/// One CI keypair (GET /ci-keys). Node-group keys are shared by every app bound to the group.
+#[derive(Deserialize, Debug)]
+pub struct CiKeyInfo {
+    /// "node" | "node_group"
+    pub scope: String,
+    pub node_id: Option<i64>,
+    pub node_group_id: Option<i64>,
+    /// app.project targets using this key
+    #[serde(default)]
+    pub apps: Vec<String>,
+    pub key_type: Option<String>,
+    pub fingerprint: Option<String>,
+    pub created_at: String,
+}
+
+
+#[derive(Deserialize, Debug)]
+pub struct CiKeyListResponse {
+    pub keys: Vec<CiKeyInfo>,
+}
+
+
+/// A user's role on a project or app (GET .../access)
+#[derive(Deserialize, Debug)]
+pub struct AccessGrant {
+    pub user: String,
+    /// "viewer" | "deployer" | "admin"
+    pub role: String,
+    /// "project" | "app"; project roles also apply to every app in the project
+    pub scope: String,
+    pub granted_by: Option<String>,
+}
+
+
+#[derive(Deserialize, Debug)]
+pub struct AccessListResponse {
+    pub grants: Vec<AccessGrant>,
+}
+
+
+/// A branch preview environment (PUT/GET /projects/:p/previews/:slug)
+#[derive(Deserialize, Debug)]
+pub struct PreviewInfo {
+    pub branch: String,
+    pub node_id: i64,
+    /// Public URLs, one per [[apps]] entry with a port, e.g. https://feature-x.api.RedQ.ops.autos
+    #[serde(default)]
+    pub urls: Vec<String>,
+    /// User who ran the last `ops preview up`
+    pub owner: Option<String>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+}
+
+
+#[derive(Deserialize, Debug)]
+pub struct PreviewListResponse {
+    pub previews: Vec<PreviewInfo>,
+}
+
+/// A starter for `ops new`: a directory in a git repository
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TemplateInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Git URL; None in a repository's own templates.toml
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// Directory in the repository (default: the template name)
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Branch or tag (default: the repository's default branch)
+    #[serde(default, rename = "ref")]
+    pub git_ref: Option<String>,
+}
+
+/// GET /templates, and the `templates.toml` index of a template repository
+#[derive(Deserialize, Debug)]
+pub struct TemplateListResponse {
+    pub templates: Vec<TemplateInfo>,
+}
+
+
 #[derive(Deserialize, Debug)]
 pub struct ErrorResponse {
     pub error: String,
+    /// Machine-readable error code (see error::ApiErrorCode)
+    #[serde(default)]
+    pub code: Option<String>,
+    /// For `permission_denied`: the role the operation needs ("deployer") and where ("api.RedQ")
+    #[serde(default)]
+    pub required_role: Option<String>,
+    #[serde(default)]
+    pub resource: Option<String>,
 }
 
 
@@ -53,7 +159,7 @@ pub struct ServerWhoamiResponse {
 
 // --- 新增：项目列表相关的结构体 ---
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct NodeItem {
     pub environment: String,
     pub ip_address: String,
@@ -61,14 +167,50 @@ pub struct NodeItem {
 }
 
 
-#[derive(Deserialize, Debug)]
+/// An app of a project with its bound nodes and custom domains
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProjectAppSummary {
+    pub name: String,
+    #[serde(default)]
+    pub node_count: i64,
+    /// None from backends that don't report health
+    #[serde(default)]
+    pub healthy_nodes: Option<u32>,
+    #[serde(default)]
+    pub domains: Vec<String>,
+}
+
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ProjectItem {
     pub name: String,
+    #[serde(default)]
     pub nodes: Vec<NodeItem>,
+    /// Owner's username; projects shared with you have someone else's
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Your role on a shared project
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub apps: Vec<ProjectAppSummary>,
 }
 
 
-#[derive(Deserialize, Debug)]
+/// Server-side filters for GET /projects
+#[derive(Serialize, Debug, Default)]
+pub struct ProjectListQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// "mine" | "shared"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ProjectListResponse {
     pub projects: Vec<ProjectItem>,
 }
@@ -96,6 +238,147 @@ pub struct OpsToml {
     #[serde(default)]
     pub init: Vec<InitStep>,
     pub build: Option<BuildConfig>,
+    #[serde(default)]
+    pub policy: Option<PolicyConfig>,
+    #[serde(default)]
+    pub preview: Option<PreviewConfig>,
+    #[serde(default)]
+    pub verify: Vec<VerifyCheck>,
+    #[serde(default)]
+    pub hooks: Option<HooksConfig>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub environments: std::collections::BTreeMap<String, EnvOverlay>,  // [environments.<name>], --env 选择
+    /// Environment applied by `apply_env`
+    #[serde(skip)]
+    pub env: Option<String>,
+    /// Directory holding the ops.toml, set by `load_ops_toml`
+    #[serde(skip)]
+    pub dir: std::path::PathBuf,
+}
+
+impl OpsToml {
+    /// Local project directory: where the ops.toml lives
+    pub fn project_dir(&self) -> &std::path::Path {
+        if self.dir.as_os_str().is_empty() { std::path::Path::new(".") } else { &self.dir }
+    }
+
+    /// Compose project name: [deploy] compose_project_name, falling back to `project`
+    pub fn compose_project(&self) -> &str {
+        self.deploy.compose_project_name.as_deref().unwrap_or(&self.project)
+    }
+
+    /// Apply the `[environments.<name>]` overlay on top of the base config
+    pub fn apply_env(&mut self, name: &str) -> anyhow::Result<()> {
+        let Some(overlay) = self.environments.get(name).cloned() else {
+            let known: Vec<&str> = self.environments.keys().map(String::as_str).collect();
+            if known.is_empty() {
+                anyhow::bail!("Unknown environment '{}': no [environments.*] tables", name);
+            }
+            anyhow::bail!("Unknown environment '{}'. Defined: {}", name, known.join(", "));
+        };
+        if let Some(path) = overlay.deploy_path {
+            self.deploy_path = path;
+        }
+        if let Some(files) = overlay.compose_files {
+            self.deploy.compose_files = Some(files);
+        }
+        if let Some(env_files) = overlay.env_files {
+            self.env_files = env_files;
+        }
+        for (app, domains) in overlay.domains {
+            let def = self.apps.iter_mut().find(|a| a.name == app)
+                .ok_or_else(|| anyhow::anyhow!("[environments.{}] domains: no [[apps]] named '{}'", name, app))?;
+            def.domains = domains;
+        }
+        if let Some(routes) = overlay.routes {
+            self.routes = routes;
+        }
+        self.env = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Overlay of the applied environment, if any
+    pub fn active_env(&self) -> Option<&EnvOverlay> {
+        self.env.as_ref().and_then(|e| self.environments.get(e))
+    }
+
+    /// Whether a node is selected by the applied environment's `node` / `region`
+    pub fn env_selects(&self, node_id: i64, region: Option<&str>) -> bool {
+        self.active_env().is_none_or(|o| {
+            o.node.is_none_or(|n| node_id == n as i64)
+                && o.region.as_ref().is_none_or(|r| region == Some(r.as_str()))
+        })
+    }
+}
+
+
+// ===== 多环境覆盖 =====
+
+/// `[environments.<name>]`: overrides applied by `--env <name>`; unset fields keep the base value
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct EnvOverlay {
+    #[serde(default)]
+    pub deploy_path: Option<String>,
+    #[serde(default)]
+    pub compose_files: Option<Vec<String>>,
+    #[serde(default)]
+    pub env_files: Option<Vec<EnvFileMapping>>,
+    /// App name → domains, replacing that app's `domains`
+    #[serde(default)]
+    pub domains: std::collections::BTreeMap<String, Vec<String>>,
+    /// Replaces `[[routes]]`
+    #[serde(default)]
+    pub routes: Option<Vec<RouteDef>>,
+    /// Only this node (default for `ops deploy --node`)
+    #[serde(default)]
+    pub node: Option<u64>,
+    /// Only nodes in this region (default for `ops deploy --region`)
+    #[serde(default)]
+    pub region: Option<String>,
+}
+
+
+// ===== 部署钩子 =====
+
+/// `[hooks]`: shell commands `ops deploy` runs on each node, in deploy_path
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct HooksConfig {
+    /// After code and env files are synced, before containers start; a failure stops the deploy
+    #[serde(default)]
+    pub pre_deploy: Vec<String>,
+    /// After containers are up and health checks passed; a failure fails the deploy
+    #[serde(default)]
+    pub post_deploy: Vec<String>,
+    /// When any step of the deploy on the node failed, including the other hooks
+    #[serde(default)]
+    pub on_failure: Vec<String>,
+}
+
+
+// ===== 分支预览 =====
+
+/// `[preview]`: defaults for `ops preview up`
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct PreviewConfig {
+    /// How long a preview lives before `ops serve` removes it, e.g. "3d" (default 7d)
+    pub ttl: Option<String>,
+}
+
+
+// ===== 部署策略 =====
+
+/// `[policy]`: rules `ops deploy` checks before touching any node
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct PolicyConfig {
+    /// Branches deploys may come from (deploy.branch for source = "git", else the local checkout)
+    #[serde(default)]
+    pub branches: Vec<String>,
+    /// Every compose `image:` must start with one of these (e.g. "ghcr.io/ourorg/")
+    #[serde(default)]
+    pub image_prefixes: Vec<String>,
+    /// Reject `:latest` and untagged images
+    #[serde(default)]
+    pub no_latest: bool,
 }
 
 
@@ -129,13 +412,35 @@ pub struct BuildGitConfig {
 pub struct BuildImageConfig {
     pub dockerfile: String,                     // e.g. "Dockerfile.prod"
     pub registry: String,                       // e.g. "ghcr.io"
-    pub token: String,                          // e.g. "$GHCR_PAT"
+    #[serde(default)]
+    pub token: String,                          // e.g. "$GHCR_PAT"; unused with ecr/gcr helpers
     #[serde(default = "default_registry_username")]
     pub username: String,                       // e.g. "oauth2"
+    #[serde(default)]
+    pub helper: Option<String>,                 // "ecr" | "gcr" | "harbor"
+    #[serde(default)]
+    pub region: Option<String>,                 // ecr: AWS region, default from the registry host
     pub prefix: String,                         // e.g. "ghcr.io/scheissedu/redq"
     #[serde(default = "default_binary_arg")]
     pub binary_arg: String,                     // Dockerfile ARG name
     pub services: Vec<String>,                  // 服务列表
+    #[serde(default)]
+    pub scan: Option<String>,                   // "trivy": 推送前扫描漏洞，critical 则失败
+}
+
+
+/// Vulnerability counts of one built image (`[build.image] scan`)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ImageScan {
+    pub service: String,
+    pub image: String,
+    pub critical: u64,
+    pub high: u64,
+    pub medium: u64,
+    pub low: u64,
+    /// IDs of the critical vulnerabilities (e.g. CVE-2024-3094)
+    #[serde(default)]
+    pub critical_ids: Vec<String>,
 }
 
 
@@ -143,9 +448,9 @@ pub struct BuildImageConfig {
 pub struct DeployConfig {
     #[serde(default = "default_source")]
     pub source: String,
-    // strategy removed — all deploys use deploy-id based zero-downtime
+    // 默认 deploy-id 零停机 (docker run)；"up" = compose scale 替换，保留完整 compose 定义
     #[serde(default)]
-    pub strategy: Option<String>,  // ignored, kept for backward compat
+    pub strategy: Option<String>,
     #[serde(default)]
     pub branch: Option<String>,
     #[serde(default)]
@@ -156,6 +461,18 @@ pub struct DeployConfig {
     pub registry: Option<RegistryConfig>,
     #[serde(default)]
     pub include: Vec<String>,
+    #[serde(default)]
+    pub gitignore: bool,                        // source = "push" 时也跳过 .gitignore 里的路径（.opsignore 总是生效）
+    #[serde(default)]
+    pub router: Option<String>,                 // "caddy" (默认) | "nginx"
+    #[serde(default)]
+    pub compose_project_name: Option<String>,   // docker compose -p (默认: project)
+    #[serde(default)]
+    pub profiles: Vec<String>,                  // docker compose --profile
+    #[serde(default)]
+    pub secret_scan: Option<String>,            // source = "push" 同步前扫描密钥: "warn" | "block" | "off"
+    #[serde(default)]
+    pub disk_check: Option<String>,             // pull/build 前检查节点磁盘: "block" (默认) | "warn" | "off"
 }
 
 
@@ -167,15 +484,148 @@ pub struct AppDef {
     pub domains: Vec<String>,
     #[serde(default)]
     pub port: Option<u16>,
+    #[serde(default)]
+    pub protocol: Protocol,                     // "http" (默认) | "grpc" | "websocket"
+    #[serde(default)]
+    pub lb: LbConfig,                           // lb.sticky = "cookie" | "header"
+    #[serde(default)]
+    pub edge: EdgeConfig,                       // edge.compression / edge.http3 / edge.max_body
+    #[serde(default)]
+    pub cache_static: Option<String>,           // 静态资源缓存时长, e.g. "7d" → Cache-Control max-age
+    #[serde(default)]
+    pub cache_paths: Vec<String>,               // cache_static 匹配的路径; 为空时用 DEFAULT_CACHE_PATHS
+    #[serde(default)]
+    pub access_logs: bool,                      // Caddy 为该 app 写 JSON 访问日志, `ops logs --access` 查看
+    #[serde(default)]
+    pub depends_on: Vec<String>,                // `ops deploy --all-apps` 先部署这些 app
+    #[serde(default)]
+    pub wait_for: Vec<String>,                  // up 之后、健康检查之前等待就绪: "db:5432" | "db" | "10.0.0.5:6379"
+    #[serde(default)]
+    pub wait_timeout: Option<u64>,              // wait_for 超时秒数 (默认 60)
+}
+
+
+/// Session affinity for an app, applied by the backend LB across nodes and by Caddy across replicas on a node
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct LbConfig {
+    pub sticky: Option<Sticky>,
+    pub cookie: Option<String>,                 // 默认 "ops_sticky"
+    pub header: Option<String>,                 // sticky = "header" 时必填, e.g. "X-Session-Id"
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sticky {
+    Cookie,
+    Header,
+}
+
+pub const DEFAULT_STICKY_COOKIE: &str = "ops_sticky";
+
+impl LbConfig {
+    /// (mode, cookie or header name) as sent to the backend; None when affinity is off
+    pub fn affinity(&self) -> anyhow::Result<Option<(&'static str, String)>> {
+        match self.sticky {
+            None => Ok(None),
+            Some(Sticky::Cookie) => Ok(Some(("cookie", self.cookie.clone().unwrap_or_else(|| DEFAULT_STICKY_COOKIE.into())))),
+            Some(Sticky::Header) => match &self.header {
+                Some(h) => Ok(Some(("header", h.clone()))),
+                None => Err(anyhow::anyhow!("lb.sticky = \"header\" needs lb.header (e.g. \"X-Session-Id\")")),
+            },
+        }
+    }
+}
+
+
+/// Paths `cache_static` applies to when `cache_paths` is empty: build output dirs and asset extensions
+pub const DEFAULT_CACHE_PATHS: &[&str] = &[
+    "/assets/*", "/static/*", "/_next/static/*", "/_astro/*", "/_app/immutable/*",
+    "*.js", "*.css", "*.woff2", "*.png", "*.jpg", "*.svg", "*.webp", "*.ico",
+];
+
+impl AppDef {
+    /// (max-age in seconds, path patterns) from `cache_static` and `cache_paths`; None when unset
+    pub fn static_cache(&self) -> anyhow::Result<Option<(u64, Vec<String>)>> {
+        let Some(ttl) = &self.cache_static else { return Ok(None) };
+        let max_age = humantime::parse_duration(ttl)
+            .map_err(|_| anyhow::anyhow!("cache_static '{}' is not a duration (e.g. \"1h\", \"7d\")", ttl))?;
+        let paths: Vec<String> = if self.cache_paths.is_empty() {
+            DEFAULT_CACHE_PATHS.iter().map(|p| p.to_string()).collect()
+        } else {
+            self.cache_paths.clone()
+        };
+        if let Some(bad) = paths.iter().find(|p| !(p.starts_with('/') || p.starts_with('*')) || p.contains(char::is_whitespace)) {
+            return Err(anyhow::anyhow!("cache_paths entry '{}' must start with / or * and have no spaces", bad));
+        }
+        Ok(Some((max_age.as_secs(), paths)))
+    }
+}
+
+/// Proxy options `ops deploy` renders into the app's route fragments on each node
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct EdgeConfig {
+    #[serde(default)]
+    pub compression: Vec<Compression>,          // 按偏好排序, e.g. ["zstd", "gzip"]
+    #[serde(default)]
+    pub http3: Option<bool>,                    // 是否通过 Alt-Svc 公布 HTTP/3; 不设置则保持默认
+    #[serde(default)]
+    pub max_body: Option<String>,               // 请求体上限, e.g. "10MB"
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Zstd,
+    Gzip,
+}
+
+impl Compression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::Zstd => "zstd",
+            Compression::Gzip => "gzip",
+        }
+    }
+}
+
+impl EdgeConfig {
+    /// `max_body` in bytes: a number with an optional K, M or G unit (1024-based, B suffix optional)
+    pub fn max_body_bytes(&self) -> anyhow::Result<Option<u64>> {
+        let Some(size) = &self.max_body else { return Ok(None) };
+        let upper = size.trim().to_uppercase();
+        let number = upper.trim_end_matches('B');
+        let (digits, multiplier) = match number.chars().last() {
+            Some('K') => (&number[..number.len() - 1], 1u64 << 10),
+            Some('M') => (&number[..number.len() - 1], 1 << 20),
+            Some('G') => (&number[..number.len() - 1], 1 << 30),
+            _ => (number, 1),
+        };
+        match digits.trim().parse::<u64>() {
+            Ok(n) if n > 0 => Ok(Some(n * multiplier)),
+            _ => Err(anyhow::anyhow!("edge.max_body '{}' is not a size (e.g. \"512KB\", \"10MB\", \"1GB\")", size)),
+        }
+    }
 }
 
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RegistryConfig {
     pub url: String,
+    /// Unused with the ecr/gcr helpers, which mint a token on the node
+    #[serde(default)]
     pub token: String,
     #[serde(default = "default_registry_username")]
     pub username: String,
+    /// Credential helper: "ecr" | "gcr" | "harbor"; None = static username/token
+    #[serde(default)]
+    pub helper: Option<String>,
+    /// ecr: AWS region, default from the registry host
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Registry host → mirror or pull-through cache URL ("docker.io" = "https://mirror.gcr.io"),
+    /// written to the node's Docker config by `ops init`
+    #[serde(default)]
+    pub mirrors: std::collections::BTreeMap<String, String>,
 }
 
 
@@ -208,6 +658,28 @@ pub struct RouteDef {
     pub port: u16,
     #[serde(default)]
     pub ssl: bool,
+    #[serde(default)]
+    pub protocol: Protocol,
+}
+
+
+/// What an upstream speaks; selects the reverse-proxy transport of its route fragments
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Http,
+    /// gRPC over cleartext HTTP/2 (h2c)
+    Grpc,
+    /// Long-lived upgraded connections
+    Websocket,
+}
+
+impl Protocol {
+    /// This protocol if set explicitly, else `fallback` (a route inherits its app's protocol)
+    pub fn or(self, fallback: Protocol) -> Protocol {
+        if self == Protocol::Http { fallback } else { self }
+    }
 }
 
 
@@ -221,10 +693,33 @@ pub struct HealthCheck {
     pub interval: u32,
     #[serde(default)]
     pub initial_delay: u32,
+    #[serde(default = "default_hc_timeout")]
+    pub timeout: u32,                           // 单次请求超时 (秒)
+    pub expected_status: Option<u16>,           // 期望状态码，不指定则 2xx/3xx 均视为成功
+    #[serde(default = "default_hc_from")]
+    pub from: String,                           // "node" (在节点上 curl) | "local" (在 CLI 机器上 curl)
+    #[serde(default)]
+    pub required: bool,                         // 失败时使部署失败
+}
+
+/// `[[verify]]`: one assertion of `ops verify`. Exactly one of url / containers / logs / dns is set.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct VerifyCheck {
+    pub name: Option<String>,
+    pub url: Option<String>,                    // 在 CLI 机器上 GET
+    pub status: Option<u16>,                    // url 的期望状态码 (默认 200)
+    pub containers: Option<String>,             // compose service: 每个节点上运行中的容器数
+    pub count: Option<usize>,                   // containers 期望数量 (默认 1)
+    pub logs: Option<String>,                   // compose service: 日志须包含 contains
+    pub contains: Option<String>,               // url 响应体或 logs 中的文本
+    pub since: Option<String>,                  // logs 时间窗口 (默认 "10m")
+    pub dns: Option<String>,                    // 域名须解析到某个绑定节点
 }
 
 fn default_retries() -> u32 { 10 }
 fn default_interval() -> u32 { 2 }
+fn default_hc_timeout() -> u32 { 5 }
+fn default_hc_from() -> String { "node".into() }
 
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -270,6 +765,25 @@ pub struct UpdateDeploymentResponse {
 }
 
 
+/// A deployment record (GET /apps/deployments/:id)
+#[derive(Deserialize, Debug)]
+pub struct DeploymentInfo {
+    /// "success" | "failed" | "running"
+    pub status: String,
+    pub logs: Option<String>,
+}
+
+
+/// App record as synced by `ops deploy` (GET /apps/:project/:app)
+#[derive(Deserialize, Debug)]
+pub struct AppDetailResponse {
+    pub github_repo: Option<String>,
+    pub github_branch: Option<String>,
+    /// The ops.toml of the last deploy, as JSON
+    pub config_json: Option<String>,
+}
+
+
 // ===== Node Group API 结构 =====
 
 #[derive(Deserialize, Debug)]
@@ -317,7 +831,7 @@ pub struct NodeGroupDetailResponse {
 }
 
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct HealthCheckConfig {
     pub check_type: String,
     pub endpoint: String,
@@ -348,6 +862,26 @@ pub struct NodeSetResponse {
 
 // ===== Nodes API =====
 
+/// Body of POST /nodes/init and /nodes/reinit
+#[derive(Serialize, Debug, Default)]
+pub struct NodeInitRequest {
+    pub ssh_pub_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_projects: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_apps: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Global IPv6 address of the server; the backend only sees the address the request came from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6_address: Option<String>,
+}
+
+
 #[derive(Deserialize, Debug)]
 pub struct NodeInitResponse {
     pub message: String,
@@ -358,13 +892,16 @@ pub struct NodeInitResponse {
     pub serve_port: u16,
     pub ci_ssh_public_key: String,
     pub region: Option<String>,
+    #[serde(default)]
+    pub ipv6_address: Option<String>,
 }
 
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Node {
     pub id: i64,
     pub ip_address: String,
+    pub ipv6_address: Option<String>,
     pub hostname: Option<String>,
     pub domain: String,
     pub region: Option<String>,
@@ -377,10 +914,14 @@ pub struct Node {
     pub has_serve_token: i64,
     pub created_at: String,
     pub bound_apps: Option<Vec<BoundApp>>,
+    #[serde(default)]
+    pub monthly_cost: Option<f64>,
+    #[serde(default)]
+    pub cost_currency: Option<String>,
 }
 
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct BoundApp {
     pub id: i64,
     pub name: String,
@@ -389,7 +930,7 @@ pub struct BoundApp {
 }
 
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct NodeListResponse {
     pub nodes: Vec<Node>,
 }
@@ -462,7 +1003,7 @@ pub struct AddDomainResponse {
 }
 
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct DomainItem {
     pub domain: String,
     pub status: String,
@@ -471,7 +1012,7 @@ pub struct DomainItem {
 }
 
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ListDomainsResponse {
     pub domains: Vec<DomainItem>,
     pub default_domain: String,
@@ -480,11 +1021,12 @@ pub struct ListDomainsResponse {
 
 // ===== Deploy Targets API (multi-node deployment) =====
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DeployTarget {
     pub node_id: i64,
     pub domain: String,
     pub ip_address: String,
+    pub ipv6_address: Option<String>,
     pub hostname: Option<String>,
     pub region: Option<String>,
     pub zone: Option<String>,
@@ -494,14 +1036,166 @@ pub struct DeployTarget {
 }
 
 
-#[derive(Deserialize, Debug)]
+/// One node's load-balancer weight (1-100), for `api::set_node_weights`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NodeWeight {
+    pub node_id: i64,
+    pub weight: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct DeployTargetsResponse {
     pub mode: String,
     pub node_group_id: Option<i64>,
     pub lb_strategy: Option<String>,
+    #[serde(default)]
+    pub lb_sticky: Option<String>,
+    #[serde(default)]
+    pub lb_sticky_key: Option<String>,
     pub targets: Vec<DeployTarget>,
 }
 
+// ===== Deployed State API (drift detection) =====
+
+/// Where an app lives on a node, recorded with its state so `ops drift` can re-read it without ops.toml
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StateScope {
+    pub project: String,
+    pub deploy_path: String,
+    pub compose_project: String,
+    /// Relative to deploy_path
+    pub compose_files: Vec<String>,
+    /// Relative to deploy_path
+    pub env_files: Vec<String>,
+    /// Route owners (app names)
+    pub apps: Vec<String>,
+    /// Services deployed as deploy-id containers (`{compose_project}-{svc}-{id}`)
+    pub services: Vec<String>,
+    pub router: String,
+    /// Compose services of each [[apps]] entry; empty when ops.toml has no [[apps]] or for older records
+    #[serde(default)]
+    pub app_services: std::collections::BTreeMap<String, Vec<String>>,
+}
+
+/// What is on a node: checksums, image IDs of running containers, and route domains
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct NodeSnapshot {
+    /// sha256 of the compose files, empty if the deploy path is gone
+    pub compose_checksum: String,
+    /// env file → sha256
+    pub env_files: std::collections::BTreeMap<String, String>,
+    /// service → image ID(s) of its running containers, comma-separated
+    pub images: std::collections::BTreeMap<String, String>,
+    pub domains: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeployedState {
+    pub scope: StateScope,
+    pub snapshot: NodeSnapshot,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NodeDeployedState {
+    pub node_id: i64,
+    pub deployment_id: i64,
+    pub recorded_at: Option<String>,
+    pub state: DeployedState,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeployedStateResponse {
+    pub nodes: Vec<NodeDeployedState>,
+}
+
+// ===== Releases API (rollback) =====
+
+/// A service's image at a deployment, kept on the node under `tag` so later builds and prunes don't lose it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReleaseImage {
+    pub service: String,
+    /// Reference the container ran, e.g. "redq-api:latest"
+    pub image: String,
+    /// e.g. "redq-api:ops-release-120"
+    pub tag: String,
+}
+
+/// A route fragment as written by the deployment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RouteFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// What one node ran after a deployment
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NodeRelease {
+    pub git_sha: Option<String>,
+    #[serde(default)]
+    pub images: Vec<ReleaseImage>,
+    #[serde(default)]
+    pub routes: Vec<RouteFile>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReleaseNode {
+    pub node_id: i64,
+    #[serde(flatten)]
+    pub release: NodeRelease,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReleaseInfo {
+    pub deployment_id: i64,
+    /// "success" | "failed" | "running"
+    pub status: String,
+    /// "cli" | "rollback" | ...
+    pub trigger: Option<String>,
+    pub created_at: String,
+    #[serde(default)]
+    pub nodes: Vec<ReleaseNode>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReleaseListResponse {
+    pub app_id: i64,
+    pub releases: Vec<ReleaseInfo>,
+}
+
+// ===== Node Events API =====
+
+/// Something `ops serve` noticed on a node, e.g. a "post-deploy-crash" after a `/deploy`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NodeEvent {
+    pub kind: String,
+    pub project: Option<String>,
+    /// The [[apps]] entry owning the service, if ops.toml names one
+    pub app: Option<String>,
+    pub service: String,
+    pub container: String,
+    /// "restart loop (4 restarts)" | "exited with code 1"
+    pub reason: String,
+    pub exit_code: i64,
+    pub restart_count: u64,
+    /// Last log lines of the container
+    #[serde(default)]
+    pub logs: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EventInfo {
+    pub id: i64,
+    pub node_id: i64,
+    pub created_at: String,
+    #[serde(flatten)]
+    pub event: NodeEvent,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EventListResponse {
+    pub events: Vec<EventInfo>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct CreateTunnelResponse {
     pub tunnel_id: i64,
@@ -509,3 +1203,79 @@ pub struct CreateTunnelResponse {
     pub node_ip: String,
 }
 
+/// A tunnel as recorded by the backend (GET /tunnels)
+#[derive(Deserialize, Debug, Clone)]
+pub struct TunnelInfo {
+    pub id: i64,
+    pub subdomain: String,
+    pub project_name: String,
+    pub node_id: u64,
+    pub domain: String,
+    /// Last heartbeat from the `ops tunnel` process
+    #[serde(default)]
+    pub last_heartbeat_at: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TunnelListResponse {
+    pub tunnels: Vec<TunnelInfo>,
+}
+
+/// A preview `ops serve` still has on its node (POST /nodes/:id/previews/heartbeat)
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PreviewRef {
+    pub project: String,
+    pub slug: String,
+}
+
+/// A preview whose node stopped sending heartbeats for it
+#[derive(Deserialize, Debug, Clone)]
+pub struct StalePreview {
+    pub project: String,
+    pub slug: String,
+    pub branch: String,
+    pub node_id: i64,
+    pub last_heartbeat_at: Option<String>,
+}
+
+/// Tunnels and previews without a recent heartbeat (GET /gc/stale)
+#[derive(Deserialize, Debug, Default)]
+pub struct StaleResources {
+    #[serde(default)]
+    pub tunnels: Vec<TunnelInfo>,
+    #[serde(default)]
+    pub previews: Vec<StalePreview>,
+}
+
+
+// ===== Secrets =====
+
+/// A secret's metadata; values only come back from `GET .../secrets/:key` and deploys
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SecretInfo {
+    pub key: String,
+    pub updated_at: String,
+    #[serde(default)]
+    pub updated_by: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SecretListResponse {
+    pub secrets: Vec<SecretInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SecretValueResponse {
+    pub value: String,
+}
+
+/// Every secret of an app, decrypted for a deploy
+#[derive(Deserialize, Debug)]
+pub struct SecretValuesResponse {
+    pub values: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SetSecretRequest {
+    pub value: String,
+}
@@ -1,4 +1,4 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use colored::Colorize;
 use self_update::cargo_crate_version;
 use self_update::backends::github::ReleaseList;
@@ -8,16 +8,106 @@ const REPO_OWNER: &str = "ops3000";
 const REPO_NAME: &str = "ops-cli";
 const BIN_NAME: &str = "ops";
 
-fn get_asset_name() -> &'static str {
-    if cfg!(target_os = "linux") && cfg!(target_arch = "x86_64") {
-        "ops-linux-amd64.tar.gz"
-    } else if cfg!(target_os = "macos") && cfg!(target_arch = "x86_64") {
-        "ops-darwin-amd64.tar.gz"
-    } else if cfg!(target_os = "macos") && cfg!(target_arch = "aarch64") {
-        "ops-darwin-arm64.tar.gz"
-    } else {
-        panic!("Unsupported platform for self-update")
+const RELEASES_URL: &str = "https://github.com/ops3000/ops-cli/releases";
+
+/// A release artifact: `<name>.tar.gz` holding the binary `bin`. Matches the matrix in release.yml.
+#[derive(Debug, PartialEq)]
+struct ReleaseAsset {
+    target: &'static str,
+    name: &'static str,
+}
+
+impl ReleaseAsset {
+    fn file_name(&self) -> String {
+        format!("{}.tar.gz", self.name)
+    }
+}
+
+/// The builds of the release workflow (.github/workflows/release.yml)
+const RELEASE_ASSETS: [ReleaseAsset; 4] = [
+    ReleaseAsset { target: "x86_64-unknown-linux-gnu", name: "ops-linux-amd64" },
+    ReleaseAsset { target: "aarch64-unknown-linux-gnu", name: "ops-linux-arm64" },
+    ReleaseAsset { target: "x86_64-apple-darwin", name: "ops-darwin-amd64" },
+    ReleaseAsset { target: "aarch64-apple-darwin", name: "ops-darwin-arm64" },
+];
+
+/// Whether this process is an Intel binary translated by Rosetta on Apple Silicon
+fn under_rosetta() -> bool {
+    std::process::Command::new("sysctl")
+        .args(["-n", "sysctl.proc_translated"])
+        .output()
+        .is_ok_and(|o| o.stdout.trim_ascii() == b"1")
+}
+
+/// Target triple for an OS and architecture as `std::env::consts` names them
+fn target_for(os: &str, arch: &str) -> Option<&'static str> {
+    match (os, arch) {
+        ("linux", "x86_64") => Some("x86_64-unknown-linux-gnu"),
+        ("linux", "aarch64") => Some("aarch64-unknown-linux-gnu"),
+        ("macos", "x86_64") => Some("x86_64-apple-darwin"),
+        ("macos", "aarch64") => Some("aarch64-apple-darwin"),
+        _ => None,
+    }
+}
+
+/// The machine's platform, as "os/arch", and the target to update to. An Intel build running
+/// under Rosetta moves to the native arm64 build.
+fn host_target() -> (String, Option<&'static str>) {
+    let (os, mut arch) = (std::env::consts::OS, std::env::consts::ARCH);
+    if os == "macos" && arch == "x86_64" && under_rosetta() {
+        arch = "aarch64";
     }
+    (format!("{}/{}", os, arch), target_for(os, arch))
+}
+
+/// The artifact of `release` for this machine, or why there is none
+fn select_asset(release: &Release) -> Result<&'static ReleaseAsset> {
+    let (platform, target) = host_target();
+    let asset = target
+        .and_then(|t| RELEASE_ASSETS.iter().find(|a| a.target == t))
+        .with_context(|| format!(
+            "No ops builds are published for {}. Build from source instead: cargo install --git https://github.com/{}/{}",
+            platform, REPO_OWNER, REPO_NAME
+        ))?;
+    if !release.assets.iter().any(|a| a.name == asset.file_name()) {
+        bail!(
+            "Release {} has no build for {} ({}). Download another version from {} or build from source",
+            release.version, platform, asset.file_name(), RELEASES_URL
+        );
+    }
+    Ok(asset)
+}
+
+/// Download `asset` of `release` and replace the running binary with it
+fn install(release: &Release, asset: &ReleaseAsset, show_progress: bool) -> Result<()> {
+    let tmp_dir = tempfile::Builder::new()
+        .prefix("ops-update")
+        .tempdir()
+        .context("Failed to create temp directory")?;
+
+    let tmp_tarball_path = tmp_dir.path().join(asset.file_name());
+    let tmp_tarball = std::fs::File::create(&tmp_tarball_path)?;
+
+    // Use browser_download_url pattern instead of the API URL
+    // (API URL requires Accept: application/octet-stream header which causes http crate conflicts)
+    let download_url = format!("{}/download/v{}/{}", RELEASES_URL, release.version, asset.file_name());
+
+    self_update::Download::from_url(&download_url)
+        .show_progress(show_progress)
+        .download_to(&tmp_tarball)?;
+
+    let bin_name = std::path::PathBuf::from(BIN_NAME);
+    self_update::Extract::from_source(&tmp_tarball_path)
+        .archive(self_update::ArchiveKind::Tar(Some(self_update::Compression::Gz)))
+        .extract_file(tmp_dir.path(), &bin_name)?;
+
+    let new_exe = tmp_dir.path().join(BIN_NAME);
+    let current_exe = std::env::current_exe()?;
+
+    self_update::Move::from_source(&new_exe)
+        .replace_using_temp(&current_exe)
+        .to_dest(&current_exe)?;
+    Ok(())
 }
 
 fn fetch_latest_release() -> Result<Release> {
@@ -72,43 +162,8 @@ pub fn update_self() -> Result<()> {
         release.version.green()
     );
 
-    let asset_name = get_asset_name();
-    let asset = release
-        .assets
-        .iter()
-        .find(|a| a.name == asset_name)
-        .with_context(|| format!("Asset '{}' not found in release {}", asset_name, release.version))?;
-
-    let tmp_dir = tempfile::Builder::new()
-        .prefix("ops-update")
-        .tempdir()
-        .context("Failed to create temp directory")?;
-
-    let tmp_tarball_path = tmp_dir.path().join(asset_name);
-    let tmp_tarball = std::fs::File::create(&tmp_tarball_path)?;
-
-    // Use browser_download_url pattern instead of the API URL
-    // (API URL requires Accept: application/octet-stream header which causes http crate conflicts)
-    let download_url = format!(
-        "https://github.com/{}/{}/releases/download/v{}/{}",
-        REPO_OWNER, REPO_NAME, release.version, asset_name
-    );
-
-    self_update::Download::from_url(&download_url)
-        .show_progress(true)
-        .download_to(&tmp_tarball)?;
-
-    let bin_name = std::path::PathBuf::from(BIN_NAME);
-    self_update::Extract::from_source(&tmp_tarball_path)
-        .archive(self_update::ArchiveKind::Tar(Some(self_update::Compression::Gz)))
-        .extract_file(tmp_dir.path(), &bin_name)?;
-
-    let new_exe = tmp_dir.path().join(BIN_NAME);
-    let current_exe = std::env::current_exe()?;
-
-    self_update::Move::from_source(&new_exe)
-        .replace_using_temp(&current_exe)
-        .to_dest(&current_exe)?;
+    let asset = select_asset(&release)?;
+    install(&release, asset, true)?;
 
     o_success!(
         "{}",
@@ -140,45 +195,21 @@ pub fn check_and_auto_update() -> Result<bool> {
     };
 
     if latest > current {
+        // No build for this platform: keep running the current version
+        let asset = match select_asset(&release) {
+            Ok(a) => a,
+            Err(e) => {
+                o_debug!("Skipping auto-update: {:#}", e);
+                return Ok(false);
+            }
+        };
         o_step!(
             "{}",
             format!("🔄 Updating ops {} → {}...", current, latest).yellow()
         );
 
         // Perform the update
-        let asset_name = get_asset_name();
-        let asset = match release.assets.iter().find(|a| a.name == asset_name) {
-            Some(a) => a,
-            None => return Ok(false),
-        };
-
-        let tmp_dir = tempfile::Builder::new()
-            .prefix("ops-update")
-            .tempdir()?;
-
-        let tmp_tarball_path = tmp_dir.path().join(asset_name);
-        let _tmp_tarball = std::fs::File::create(&tmp_tarball_path)?;
-
-        let download_url = format!(
-            "https://github.com/{}/{}/releases/download/v{}/{}",
-            REPO_OWNER, REPO_NAME, release.version, asset_name
-        );
-
-        self_update::Download::from_url(&download_url)
-            .show_progress(false)
-            .download_to(&std::fs::File::create(&tmp_tarball_path)?)?;
-
-        let bin_name = std::path::PathBuf::from(BIN_NAME);
-        self_update::Extract::from_source(&tmp_tarball_path)
-            .archive(self_update::ArchiveKind::Tar(Some(self_update::Compression::Gz)))
-            .extract_file(tmp_dir.path(), &bin_name)?;
-
-        let new_exe = tmp_dir.path().join(BIN_NAME);
-        let current_exe = std::env::current_exe()?;
-
-        self_update::Move::from_source(&new_exe)
-            .replace_using_temp(&current_exe)
-            .to_dest(&current_exe)?;
+        install(&release, asset, false)?;
 
         o_success!(
             "{}",
@@ -190,3 +221,23 @@ pub fn check_and_auto_update() -> Result<bool> {
 
     Ok(false)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_assets() {
+        for (os, arch, name) in [
+            ("linux", "x86_64", "ops-linux-amd64"),
+            ("linux", "aarch64", "ops-linux-arm64"),
+            ("macos", "aarch64", "ops-darwin-arm64"),
+        ] {
+            let target = target_for(os, arch).unwrap();
+            assert_eq!(RELEASE_ASSETS.iter().find(|a| a.target == target).unwrap().name, name);
+        }
+        assert!(target_for("freebsd", "x86_64").is_none());
+        assert!(target_for("windows", "x86_64").is_none());
+        assert_eq!(RELEASE_ASSETS[1].file_name(), "ops-linux-arm64.tar.gz");
+    }
+}
@@ -39,14 +39,39 @@ impl Target {
     pub fn is_node_id(&self) -> bool {
         matches!(self, Target::NodeId { .. })
     }
+
+    /// Get (app, project) for commands that only operate on apps/environments
+    pub fn app_project(&self) -> Result<(String, String)> {
+        match self {
+            Target::AppTarget { app, project, .. } => Ok((app.clone(), project.clone())),
+            Target::NodeId { id, .. } => Err(anyhow!(
+                "Expected app.project format (e.g., api.RedQ), not a node ID ({})", id
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    /// Canonical form accepted by `parse_target` ("12345", "api.RedQ:/path")
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::NodeId { id, .. } => write!(f, "{}", id)?,
+            Target::AppTarget { app, project, .. } => write!(f, "{}.{}", app, project)?,
+        }
+        if let Some(path) = self.path() {
+            write!(f, ":{}", path)?;
+        }
+        Ok(())
+    }
 }
 
 /// Parse a target string into Target
 /// Supports:
 /// - "12345" → NodeId
 /// - "12345:/path" → NodeId with path
-/// - "api.RedQ" → AppTarget
+/// - "api.RedQ" / "prod.RedQ" → AppTarget (app or environment name + project)
 /// - "api.RedQ:/path" → AppTarget with path
+/// - Full domains: "12345.node.ops.autos", "api.RedQ.ops.autos"
 pub fn parse_target(target_str: &str) -> Result<Target> {
     // 1. Split off the path (after colon)
     let (server_part, path_part) = match target_str.split_once(':') {
@@ -54,14 +79,18 @@ pub fn parse_target(target_str: &str) -> Result<Target> {
         None => (target_str, None),
     };
 
-    // 2. Check if it's a pure node ID (numeric)
-    if let Ok(id) = server_part.parse::<u64>() {
+    // 2. Accept full domains as printed by deploy/status output
+    let server_part = server_part.strip_suffix(".ops.autos").unwrap_or(server_part);
+
+    // 3. Check if it's a node ID ("12345" or "12345.node")
+    let id_part = server_part.strip_suffix(".node").unwrap_or(server_part);
+    if let Ok(id) = id_part.parse::<u64>() {
         return Ok(Target::NodeId { id, path: path_part });
     }
 
-    // 3. Parse as app.project format
+    // 4. Parse as app.project format
     let parts: Vec<&str> = server_part.split('.').collect();
-    if parts.len() != 2 {
+    if parts.len() != 2 || parts.iter().any(|p| p.is_empty()) {
         return Err(anyhow!(
             "Invalid target format. Expected 'app.project' (e.g., api.RedQ) or node ID (e.g., 12345)"
         ));
@@ -74,6 +103,43 @@ pub fn parse_target(target_str: &str) -> Result<Target> {
     })
 }
 
+/// Quote a word for a remote POSIX shell; plain words are left as-is
+pub fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Mask every occurrence of `secret` in command output before it is printed or logged
+pub fn redact(text: &str, secret: &str) -> String {
+    let secret = secret.trim();
+    if secret.is_empty() {
+        return text.to_string();
+    }
+    text.replace(secret, "***")
+}
+
+/// Standard base64 (RFC 4648, padded)
+pub fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,6 +194,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_target_full_domain() {
+        let node = parse_target("12345.node.ops.autos").unwrap();
+        assert!(matches!(node, Target::NodeId { id: 12345, .. }));
+
+        let app = parse_target("api.RedQ.ops.autos:/var/www").unwrap();
+        assert_eq!(app.to_string(), "api.RedQ:/var/www");
+        assert_eq!(app.app_project().unwrap(), ("api".to_string(), "RedQ".to_string()));
+    }
+
+    #[test]
+    fn test_parse_target_invalid() {
+        assert!(parse_target("api").is_err());
+        assert!(parse_target("a.b.c").is_err());
+        assert!(parse_target(".RedQ").is_err());
+        assert!(parse_target("12345").unwrap().app_project().is_err());
+    }
+
     #[test]
     fn test_target_domain() {
         let node = Target::NodeId { id: 12345, path: None };
@@ -140,4 +224,27 @@ mod tests {
         };
         assert_eq!(app.domain(), "api.RedQ.ops.autos");
     }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("manage.py"), "manage.py");
+        assert_eq!(shell_quote("select 1"), "'select 1'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar\n"), "Zm9vYmFyCg==");
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(redact("login with ghp_abc failed", "ghp_abc\n"), "login with *** failed");
+        assert_eq!(redact("nothing here", ""), "nothing here");
+    }
 }